@@ -0,0 +1,151 @@
+//! Cost of copying and hashing conversations.
+//!
+//! `deep_copy` rebuilds each message's content, as cloning did before
+//! content was shared; `shared` is a plain clone. The tree has no agent
+//! pool, so forking is measured through `Session::checkpoint`, which clones
+//! the history the same way a fork would.
+//!
+//! The cache benches cover put/get from concurrent tasks and writes into a
+//! full cache, where every put evicts.
+
+use agent_sdk::provider::{CacheConfig, CacheKey, GenerateResponse, Message, ResponseCache};
+use agent_sdk::Session;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TURNS: usize = 50;
+const BODY: usize = 8 * 1024;
+
+/// A conversation of `TURNS` user/assistant pairs with large bodies
+fn conversation() -> Vec<Message> {
+    let body = "lorem ipsum ".repeat(BODY / 12);
+    let mut messages = vec![Message::system("You are a helpful assistant.")];
+    for i in 0..TURNS {
+        messages.push(Message::user(format!("{i}: {body}")));
+        messages.push(Message::assistant(format!("{i}: {body}")));
+    }
+    messages
+}
+
+fn deep_copy(messages: &[Message]) -> Vec<Message> {
+    messages
+        .iter()
+        .map(|m| Message {
+            content: m.content.to_vec().into(),
+            ..m.clone()
+        })
+        .collect()
+}
+
+fn clone(c: &mut Criterion) {
+    let messages = conversation();
+    let mut group = c.benchmark_group("conversation_clone");
+    group.bench_function("deep_copy", |b| b.iter(|| deep_copy(black_box(&messages))));
+    group.bench_function("shared", |b| b.iter(|| black_box(&messages).clone()));
+    group.finish();
+
+    let mut session = Session::new("bench");
+    session.messages = messages;
+    c.bench_function("session_checkpoint", |b| {
+        b.iter_batched(
+            || session.clone(),
+            |mut session| session.checkpoint(),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+fn cache(c: &mut Criterion) {
+    let messages = conversation();
+    c.bench_function("cache_key", |b| {
+        b.iter(|| CacheKey::from_request(black_box(&messages), "gpt-4o-mini", &None))
+    });
+
+    // Put/get from concurrent tasks over a working set of distinct requests
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .build()
+        .unwrap();
+    let cache = Arc::new(ResponseCache::new(CacheConfig::new(
+        true,
+        Duration::from_secs(300),
+        1_000,
+    )));
+    let keys: Arc<Vec<CacheKey>> = Arc::new(
+        (0..256)
+            .map(|i| {
+                let messages = [Message::user(format!("question {i}"))];
+                CacheKey::from_request(&messages, "gpt-4o-mini", &None)
+            })
+            .collect(),
+    );
+    c.bench_function("cache_put_get_8_tasks", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let tasks: Vec<_> = (0..8)
+                    .map(|task| {
+                        let cache = cache.clone();
+                        let keys = keys.clone();
+                        tokio::spawn(async move {
+                            for (i, key) in keys.iter().enumerate() {
+                                if (i + task) % 4 == 0 {
+                                    cache.put(key.clone(), response()).await;
+                                } else {
+                                    black_box(cache.get(key).await);
+                                }
+                            }
+                        })
+                    })
+                    .collect();
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            })
+        })
+    });
+}
+
+fn response() -> GenerateResponse {
+    GenerateResponse {
+        content: "answer".to_string(),
+        usage: None,
+        model: "gpt-4o-mini".to_string(),
+        finish_reason: Some("stop".to_string()),
+        tool_calls: Vec::new(),
+    }
+}
+
+/// Writes into a full cache, which must evict on every put
+fn full_cache(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let cache = ResponseCache::new(CacheConfig::new(true, Duration::from_secs(300), 50_000));
+    let key = |i: usize| {
+        CacheKey::from_request(
+            &[Message::user(format!("question {i}"))],
+            "gpt-4o-mini",
+            &None,
+        )
+    };
+    runtime.block_on(async {
+        for i in 0..50_000 {
+            cache.put(key(i), response()).await;
+        }
+    });
+    let mut next = 50_000;
+    c.bench_function("cache_put_full_50k", |b| {
+        b.iter_batched(
+            || {
+                next += 1;
+                key(next)
+            },
+            |key| runtime.block_on(cache.put(key, response())),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, clone, cache, full_cache);
+criterion_main!(benches);
@@ -0,0 +1,99 @@
+//! Throughput of decoding a chat completions stream.
+//!
+//! `string_lines` is the previous decoder: chunks appended to a `String`,
+//! each line copied out and parsed into a `serde_json::Value`.
+//! `byte_lines` is the current one: `LineDecoder` plus borrowed parsing.
+
+use agent_sdk::provider::{parse_chat_completion_chunk, sse_data, LineDecoder, StreamEvent};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const TOKENS: usize = 2_000;
+
+/// A streamed completion split into network chunks of `chunk_size` bytes
+fn stream_body(chunk_size: usize) -> Vec<Vec<u8>> {
+    let mut body = String::new();
+    for i in 0..TOKENS {
+        body.push_str(&format!(
+            "data: {{\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\
+             \"model\":\"gpt-4o-mini\",\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"tok{} \"}},\
+             \"finish_reason\":null}}]}}\n\n",
+            i
+        ));
+    }
+    body.push_str(
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n",
+    );
+    body.as_bytes()
+        .chunks(chunk_size)
+        .map(<[u8]>::to_vec)
+        .collect()
+}
+
+fn string_lines(chunks: &[Vec<u8>]) -> usize {
+    let mut buffer = String::new();
+    let mut text = 0;
+    for bytes in chunks {
+        buffer.push_str(&String::from_utf8_lossy(bytes));
+        while let Some(line_end) = buffer.find('\n') {
+            let line = buffer[..line_end].trim().to_string();
+            buffer.drain(..=line_end);
+            if let Some(data) = line.strip_prefix("data: ") {
+                if data == "[DONE]" {
+                    return text;
+                }
+                if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
+                    if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
+                        text += content.to_string().len();
+                    }
+                }
+            }
+        }
+    }
+    text
+}
+
+fn byte_lines(chunks: &[Vec<u8>]) -> usize {
+    let mut lines = LineDecoder::new();
+    let mut text = 0;
+    for bytes in chunks {
+        lines.push(bytes);
+        while let Some(line) = lines.next_line() {
+            let Some(data) = sse_data(&line) else {
+                continue;
+            };
+            if data == b"[DONE]" {
+                return text;
+            }
+            for event in parse_chat_completion_chunk(data).unwrap_or_default() {
+                if let StreamEvent::TextDelta(content) = event {
+                    text += content.len();
+                }
+            }
+        }
+    }
+    text
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chat_stream_decode");
+    for chunk_size in [64, 1024, 16 * 1024] {
+        let chunks = stream_body(chunk_size);
+        let bytes: usize = chunks.iter().map(Vec::len).sum();
+        assert_eq!(string_lines(&chunks), byte_lines(&chunks));
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_with_input(
+            BenchmarkId::new("string_lines", chunk_size),
+            &chunks,
+            |b, chunks| b.iter(|| string_lines(black_box(chunks))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("byte_lines", chunk_size),
+            &chunks,
+            |b, chunks| b.iter(|| byte_lines(black_box(chunks))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -35,7 +35,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         system_prompt: Some("You are a concise and practical coding assistant.".into()),
         max_iterations: 3,
         tool_choice: ToolChoice::None,
-        generate_options: Default::default(),
+        ..Default::default()
     });
 
     match agent
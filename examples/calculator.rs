@@ -1,12 +1,34 @@
-use agent_sdk::{Agent, AgentOptions, OpenRouterProvider, Tool, ToolChoice, ToolResult};
+use agent_sdk::{Agent, AgentOptions, OpenRouterProvider, ToolChoice, ToolResult, Typed, TypedTool};
 use async_trait::async_trait;
-use serde_json::{json, Value};
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::env;
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CalculatorParams {
+    /// First number
+    a: f64,
+    /// Second number
+    b: f64,
+    /// Operation to perform
+    operation: Operation,
+}
+
 struct CalculatorTool;
 
 #[async_trait]
-impl Tool for CalculatorTool {
+impl TypedTool for CalculatorTool {
+    type Params = CalculatorParams;
+
     fn name(&self) -> &str {
         "calculator"
     }
@@ -15,40 +37,19 @@ impl Tool for CalculatorTool {
         "Perform arithmetic operations (add, sub, mul, div)"
     }
 
-    fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "a": {"type": "number", "description": "First number"},
-                "b": {"type": "number", "description": "Second number"},
-                "operation": {
-                    "type": "string",
-                    "enum": ["add", "sub", "mul", "div"],
-                    "description": "Operation to perform"
-                }
-            },
-            "required": ["a", "b", "operation"]
-        })
-    }
-
-    async fn execute(&self, params: &Value) -> ToolResult {
-        // 注意：参数已经通过 validate_parameters 校验，这里可以安全地使用 unwrap
-        let a = params["a"].as_f64().unwrap();
-        let b = params["b"].as_f64().unwrap();
-        let operation = params["operation"].as_str().unwrap();
-
+    async fn execute(&self, params: CalculatorParams) -> ToolResult {
+        let CalculatorParams { a, b, operation } = params;
         let result = match operation {
-            "add" => a + b,
-            "sub" => a - b,
-            "mul" => a * b,
-            "div" => {
+            Operation::Add => a + b,
+            Operation::Sub => a - b,
+            Operation::Mul => a * b,
+            Operation::Div => {
                 if b != 0.0 {
                     a / b
                 } else {
                     return ToolResult::error("Division by zero");
                 }
             }
-            _ => unreachable!(), // 由于校验，这里不会到达
         };
 
         ToolResult::success(result.to_string())
@@ -69,7 +70,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ..Default::default()
     });
 
-    agent.register_tool(Box::new(CalculatorTool)).await;
+    agent.register_tool(Box::new(Typed(CalculatorTool))).await;
 
     println!("Agent with calculator tool ready!");
     println!("Testing: What is 15 * 23?");
@@ -1,12 +1,34 @@
-use agent_sdk::{Agent, OpenRouterProvider, Tool, ToolResult, AgentOptions, ToolChoice, EventBus, AgentEvent};
+use agent_sdk::{Agent, OpenRouterProvider, ToolResult, Typed, TypedTool, AgentOptions, ToolChoice, EventBus, AgentEvent};
 use async_trait::async_trait;
-use serde_json::{json, Value};
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::{env, sync::Arc};
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CalculatorParams {
+    /// First number
+    a: f64,
+    /// Second number
+    b: f64,
+    /// Operation to perform
+    operation: Operation,
+}
+
 struct CalculatorTool;
 
 #[async_trait]
-impl Tool for CalculatorTool {
+impl TypedTool for CalculatorTool {
+    type Params = CalculatorParams;
+
     fn name(&self) -> &str {
         "calculator"
     }
@@ -15,35 +37,19 @@ impl Tool for CalculatorTool {
         "Perform arithmetic operations"
     }
 
-    fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "a": {"type": "number"},
-                "b": {"type": "number"},
-                "operation": {"type": "string", "enum": ["add", "sub", "mul", "div"]}
-            },
-            "required": ["a", "b", "operation"]
-        })
-    }
-
-    async fn execute(&self, params: &Value) -> ToolResult {
-        let a = params["a"].as_f64().unwrap();
-        let b = params["b"].as_f64().unwrap();
-        let operation = params["operation"].as_str().unwrap();
-
+    async fn execute(&self, params: CalculatorParams) -> ToolResult {
+        let CalculatorParams { a, b, operation } = params;
         let result = match operation {
-            "add" => a + b,
-            "sub" => a - b,
-            "mul" => a * b,
-            "div" => {
+            Operation::Add => a + b,
+            Operation::Sub => a - b,
+            Operation::Mul => a * b,
+            Operation::Div => {
                 if b != 0.0 {
                     a / b
                 } else {
                     return ToolResult::error("Division by zero");
                 }
             }
-            _ => unreachable!(),
         };
 
         ToolResult::success(result.to_string())
@@ -101,6 +107,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 AgentEvent::ConversationFailed { error } => {
                     println!("💥 Conversation failed: {}", error);
                 }
+                _ => {}
             }
         }
     });
@@ -119,7 +126,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
         .with_event_bus(event_bus);
 
-    agent.register_tool(Box::new(CalculatorTool)).await;
+    agent.register_tool(Box::new(Typed(CalculatorTool))).await;
 
     println!("🤖 Agent with event monitoring ready!\n");
 
@@ -1,13 +1,35 @@
-use agent_sdk::{Agent, OpenRouterProvider, Tool, ToolResult, AgentOptions, ToolChoice};
+use agent_sdk::{Agent, OpenRouterProvider, ToolResult, Typed, TypedTool, AgentOptions, ToolChoice};
 use async_trait::async_trait;
-use serde_json::{json, Value};
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::env;
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CalculatorParams {
+    /// First number
+    a: f64,
+    /// Second number
+    b: f64,
+    /// Operation to perform
+    operation: Operation,
+}
+
 // 计算器工具
 struct CalculatorTool;
 
 #[async_trait]
-impl Tool for CalculatorTool {
+impl TypedTool for CalculatorTool {
+    type Params = CalculatorParams;
+
     fn name(&self) -> &str {
         "calculator"
     }
@@ -16,50 +38,49 @@ impl Tool for CalculatorTool {
         "Perform arithmetic operations (add, sub, mul, div)"
     }
 
-    fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "a": {"type": "number", "description": "First number"},
-                "b": {"type": "number", "description": "Second number"},
-                "operation": {
-                    "type": "string", 
-                    "enum": ["add", "sub", "mul", "div"],
-                    "description": "Operation to perform"
-                }
-            },
-            "required": ["a", "b", "operation"]
-        })
-    }
-
-    async fn execute(&self, params: &Value) -> ToolResult {
-        let a = params["a"].as_f64().unwrap_or(0.0);
-        let b = params["b"].as_f64().unwrap_or(0.0);
-        let operation = params["operation"].as_str().unwrap_or("add");
-
+    async fn execute(&self, params: CalculatorParams) -> ToolResult {
+        let CalculatorParams { a, b, operation } = params;
         let result = match operation {
-            "add" => a + b,
-            "sub" => a - b,
-            "mul" => a * b,
-            "div" => {
+            Operation::Add => a + b,
+            Operation::Sub => a - b,
+            Operation::Mul => a * b,
+            Operation::Div => {
                 if b != 0.0 {
                     a / b
                 } else {
                     return ToolResult::error("Division by zero");
                 }
             }
-            _ => return ToolResult::error("Unknown operation"),
         };
 
         ToolResult::success(result.to_string())
     }
 }
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum TextOperation {
+    Uppercase,
+    Lowercase,
+    Reverse,
+    CountWords,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct TextParams {
+    /// Text to process
+    text: String,
+    /// Operation to perform on text
+    operation: TextOperation,
+}
+
 // 文本处理工具
 struct TextTool;
 
 #[async_trait]
-impl Tool for TextTool {
+impl TypedTool for TextTool {
+    type Params = TextParams;
+
     fn name(&self) -> &str {
         "text_processor"
     }
@@ -68,32 +89,13 @@ impl Tool for TextTool {
         "Process text (uppercase, lowercase, reverse, count_words)"
     }
 
-    fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "text": {"type": "string", "description": "Text to process"},
-                "operation": {
-                    "type": "string",
-                    "enum": ["uppercase", "lowercase", "reverse", "count_words"],
-                    "description": "Operation to perform on text"
-                }
-            },
-            "required": ["text", "operation"]
-        })
-    }
-
-    async fn execute(&self, params: &Value) -> ToolResult {
-        // 参数已校验，可以安全使用
-        let text = params["text"].as_str().unwrap();
-        let operation = params["operation"].as_str().unwrap();
-
-        let result = match operation {
-            "uppercase" => text.to_uppercase(),
-            "lowercase" => text.to_lowercase(),
-            "reverse" => text.chars().rev().collect(),
-            "count_words" => text.split_whitespace().count().to_string(),
-            _ => unreachable!(), // 校验确保不会到达
+    async fn execute(&self, params: TextParams) -> ToolResult {
+        let text = params.text;
+        let result = match params.operation {
+            TextOperation::Uppercase => text.to_uppercase(),
+            TextOperation::Lowercase => text.to_lowercase(),
+            TextOperation::Reverse => text.chars().rev().collect(),
+            TextOperation::CountWords => text.split_whitespace().count().to_string(),
         };
 
         ToolResult::success(result)
@@ -118,8 +120,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
 
     // 注册多个工具
-    agent.register_tool(Box::new(CalculatorTool)).await;
-    agent.register_tool(Box::new(TextTool)).await;
+    agent.register_tool(Box::new(Typed(CalculatorTool))).await;
+    agent.register_tool(Box::new(Typed(TextTool))).await;
 
     println!("🤖 Multi-tool Agent Ready!");
     println!("Available tools: calculator, text_processor");
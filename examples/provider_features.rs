@@ -61,6 +61,7 @@ async fn example_retry_and_rate_limiting() -> Result<(), Box<dyn std::error::Err
             connect_timeout: Duration::from_secs(10),
             request_timeout: Duration::from_secs(120),
             stream_timeout: Some(Duration::from_secs(300)),
+            stream_idle_timeout: Some(Duration::from_secs(60)),
         })
         .build()?;
 
@@ -160,6 +161,7 @@ async fn example_context_window() -> Result<(), Box<dyn std::error::Error>> {
         .context_config(ContextWindowConfig {
             max_tokens: 1000, // Small window for demo
             truncation_strategy: TruncationStrategy::DropOldest,
+            ..Default::default()
         })
         .build()?;
 
@@ -195,7 +197,7 @@ async fn example_multimodal() -> Result<(), Box<dyn std::error::Error>> {
         .build()?;
 
     // Example with image URL
-    let _messages = vec![
+    let _messages = [
         Message::user_with_image_url(
             "What do you see in this image?",
             "https://example.com/image.jpg"
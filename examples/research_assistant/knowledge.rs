@@ -0,0 +1,135 @@
+//! Knowledge base: documents chunked, embedded and indexed for retrieval.
+
+use agent_sdk::provider::EmbeddingUsage;
+use agent_sdk::retrieval::{HnswIndex, Retriever, TextChunker};
+use agent_sdk::{EmbeddingProvider, EmbeddingRequest, EmbeddingResponse, ProviderError};
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Documents indexed when no directory is given
+const SAMPLE_DOCUMENTS: [(&str, &str); 3] = [
+    (
+        "hnsw.md",
+        "HNSW (hierarchical navigable small world) graphs answer approximate nearest \
+         neighbour queries in logarithmic time. Each vector is linked to its closest \
+         neighbours on several layers; searches start on the sparse top layer and \
+         descend. The m parameter bounds links per node, ef_construction trades build \
+         time for recall, and ef_search trades query time for recall.",
+    ),
+    (
+        "chunking.md",
+        "Retrieval quality depends on chunking. Chunks that are too long dilute the \
+         embedding with unrelated text, chunks that are too short lose context. A few \
+         hundred characters with a small overlap is a good default for prose; split on \
+         paragraph and sentence boundaries where possible.",
+    ),
+    (
+        "evaluation.md",
+        "Evaluate a RAG pipeline on recall (is the right passage retrieved?) and \
+         faithfulness (does the answer only claim what the passages support?). Keep a \
+         fixed set of questions with known source passages and rerun it after changing \
+         the chunker, the embedding model or the index parameters.",
+    ),
+];
+
+/// Embeds text by hashing its words into a fixed number of buckets
+///
+/// Needs no API key, so the example runs offline; related texts share words
+/// and end up close. Use a real embedding model for anything else.
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self { dimensions }
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.len() > 2)
+        {
+            let mut hasher = DefaultHasher::new();
+            word.to_lowercase().hash(&mut hasher);
+            vector[hasher.finish() as usize % self.dimensions] += 1.0;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt().max(1e-6);
+        vector.iter_mut().for_each(|v| *v /= norm);
+        vector
+    }
+}
+
+impl EmbeddingProvider for HashingEmbedder {
+    fn create_embeddings(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<EmbeddingResponse, ProviderError>> + Send + '_>> {
+        let embeddings = request.input.iter().map(|text| self.embed(text)).collect();
+        let tokens = request
+            .input
+            .iter()
+            .map(|text| text.split_whitespace().count() as u32)
+            .sum();
+        Box::pin(async move {
+            Ok(EmbeddingResponse {
+                embeddings,
+                model: "hashing".to_string(),
+                usage: Some(EmbeddingUsage {
+                    prompt_tokens: tokens,
+                    total_tokens: tokens,
+                }),
+            })
+        })
+    }
+}
+
+/// Documents to ingest as (id, text): the `.md` and `.txt` files of `dir`,
+/// or the built-in samples
+pub async fn load_documents(dir: Option<&Path>) -> std::io::Result<Vec<(String, String)>> {
+    let Some(dir) = dir else {
+        return Ok(SAMPLE_DOCUMENTS
+            .iter()
+            .map(|(id, text)| (id.to_string(), text.to_string()))
+            .collect());
+    };
+
+    let mut documents = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_text = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e == "md" || e == "txt");
+        if is_text {
+            let text = tokio::fs::read_to_string(&path).await?;
+            let id = path.file_name().unwrap().to_string_lossy().to_string();
+            documents.push((id, text));
+        }
+    }
+    documents.sort();
+    Ok(documents)
+}
+
+/// Index `documents` in memory, returning the retriever and the chunk count
+pub async fn ingest(
+    embedder: Arc<dyn EmbeddingProvider>,
+    documents: &[(String, String)],
+) -> Result<(Arc<Retriever>, usize), Box<dyn std::error::Error>> {
+    let retriever = Retriever::new(embedder, Arc::new(HnswIndex::new()))
+        .with_chunker(TextChunker::new(600, 80));
+    let mut chunks = 0;
+    for (id, text) in documents {
+        chunks += retriever
+            .add_document(id, text, json!({ "source": id }))
+            .await?;
+    }
+    Ok((Arc::new(retriever), chunks))
+}
@@ -0,0 +1,297 @@
+//! Research assistant combining the SDK's subsystems in one agent:
+//! a knowledge base searched through retrieval, web search and page
+//! fetching, long-term memory, approval before notes are written, a
+//! streamed terminal UI and a cost report built from the run trace.
+//!
+//! With `OPEN_ROUTER_API_KEY` set it talks to a real model (override it
+//! with `RESEARCH_MODEL`) and embeds documents through OpenRouter; with
+//! `BRAVE_API_KEY` it searches the web with Brave. Without keys it runs
+//! offline against a scripted `MockProvider` answering the default
+//! question, which makes it a smoke test of the whole stack.
+//!
+//! Arguments: `--docs DIR` indexes the `.md` and `.txt` files of `DIR`
+//! instead of the built-in samples, `--yes` approves writes without
+//! asking, and the remaining words are the question.
+
+mod knowledge;
+mod tools;
+mod ui;
+
+use agent_sdk::retrieval::RetrievalTool;
+use agent_sdk::testing::MockProvider;
+use agent_sdk::tool::{RecallTool, RememberTool};
+use agent_sdk::{
+    Agent, AgentMemory, AgentOptions, ApprovalManager, BraveSearch, EmbeddingProvider,
+    GenerateResponse, InMemoryStore, LlmProvider, OpenRouterProvider, SearchBackend, ToolCall,
+    Usage, WebSearchTool,
+};
+use futures_util::StreamExt;
+use knowledge::HashingEmbedder;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tools::{FetchUrlTool, OfflineSearch, SaveNoteTool, TerminalApprover};
+use ui::{Pricing, StreamView};
+
+const DEFAULT_MODEL: &str = "google/gemini-2.5-flash-lite-preview-09-2025";
+const DEFAULT_QUESTION: &str =
+    "How should I tune HNSW and chunking for a RAG pipeline? Save a short note with your advice.";
+
+const SYSTEM_PROMPT: &str = "You are a research assistant. Search the knowledge base first, \
+    use web search and fetch_url for anything it does not cover, and cite sources by document \
+    name or URL. Remember durable preferences of the user. When asked to keep results, save a \
+    concise markdown note.";
+
+struct Args {
+    question: String,
+    docs: Option<PathBuf>,
+    auto_approve: bool,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        question: DEFAULT_QUESTION.to_string(),
+        docs: None,
+        auto_approve: false,
+    };
+    let mut words = Vec::new();
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--docs" => args.docs = argv.next().map(PathBuf::from),
+            "--yes" => args.auto_approve = true,
+            _ => words.push(arg),
+        }
+    }
+    if !words.is_empty() {
+        args.question = words.join(" ");
+    }
+    args
+}
+
+/// Tools shared by the online and offline agents
+struct Toolkit {
+    retrieval: RetrievalTool,
+    search: Arc<dyn SearchBackend>,
+    memory: AgentMemory,
+    notes_dir: PathBuf,
+}
+
+async fn build_agent<P: LlmProvider>(
+    provider: P,
+    toolkit: Toolkit,
+    auto_approve: bool,
+) -> Agent<P> {
+    let approvals = ApprovalManager::new()
+        .with_handler(Arc::new(TerminalApprover { auto_approve }))
+        .timeout(Duration::from_secs(120));
+    let mut agent = Agent::new(provider)
+        .with_options(AgentOptions {
+            system_prompt: Some(SYSTEM_PROMPT.to_string()),
+            max_iterations: 8,
+            ..Default::default()
+        })
+        .with_approvals(approvals);
+
+    agent.register_tool(Box::new(toolkit.retrieval)).await;
+    agent
+        .register_tool(Box::new(WebSearchTool::new(toolkit.search).max_results(3)))
+        .await;
+    agent.register_tool(Box::new(FetchUrlTool::new())).await;
+    agent
+        .register_tool(Box::new(RememberTool::new(toolkit.memory.clone())))
+        .await;
+    agent
+        .register_tool(Box::new(RecallTool::new(toolkit.memory)))
+        .await;
+    agent
+        .register_tool(Box::new(SaveNoteTool::new(toolkit.notes_dir)))
+        .await;
+    agent
+}
+
+/// Stream one question through the agent and print what it cost
+async fn research<P: LlmProvider>(
+    mut agent: Agent<P>,
+    question: &str,
+    pricing: Pricing,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("\n> {}\n", question);
+    let mut view = StreamView::default();
+    let mut events = agent.run_stream_events(question);
+    while let Some(event) = events.next().await {
+        view.render(&event?);
+    }
+    drop(events);
+
+    ui::cost_report(agent.run_trace(), pricing);
+    Ok(())
+}
+
+/// Scripted replies walking through every tool, for running without keys
+fn offline_provider() -> MockProvider {
+    let reply = |content: &str, calls: Vec<ToolCall>, prompt_tokens: u32| {
+        let completion_tokens = 40 + content.len() as u32 / 4;
+        GenerateResponse {
+            content: content.to_string(),
+            usage: Some(Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            }),
+            model: "offline".to_string(),
+            finish_reason: Some(
+                if calls.is_empty() {
+                    "stop"
+                } else {
+                    "tool_calls"
+                }
+                .into(),
+            ),
+            tool_calls: calls,
+        }
+    };
+    let call = |id: &str, name: &str, parameters: serde_json::Value| ToolCall {
+        id: id.to_string(),
+        name: name.to_string(),
+        parameters,
+    };
+
+    MockProvider::new()
+        .with_model("offline")
+        .with_latency(Duration::from_millis(150))
+        .response(reply(
+            "",
+            vec![
+                call(
+                    "call_1",
+                    "search_knowledge_base",
+                    json!({"query": "HNSW parameters"}),
+                ),
+                call(
+                    "call_2",
+                    "search_knowledge_base",
+                    json!({"query": "chunk size"}),
+                ),
+            ],
+            620,
+        ))
+        .response(reply(
+            "",
+            vec![call("call_3", "web_search", json!({"query": "HNSW paper"}))],
+            1_140,
+        ))
+        .response(reply(
+            "",
+            vec![call(
+                "call_4",
+                "remember",
+                json!({"key": "research_topic", "content": "RAG tuning"}),
+            )],
+            1_460,
+        ))
+        .response(reply(
+            "",
+            vec![call(
+                "call_5",
+                "save_note",
+                json!({
+                    "title": "RAG tuning",
+                    "content": "- Chunks of a few hundred characters with small overlap\n\
+                                - Raise ef_search until recall stops improving\n\
+                                - Re-run a fixed question set after every change"
+                }),
+            )],
+            1_650,
+        ))
+        .response(reply(
+            "Start with chunks of a few hundred characters and a small overlap \
+             (chunking.md). For HNSW, keep m moderate and raise ef_search until recall \
+             on a fixed question set stops improving (hnsw.md, evaluation.md); the \
+             original paper is at https://arxiv.org/abs/1603.09320. I saved these points \
+             as the note \"RAG tuning\".",
+            Vec::new(),
+            1_820,
+        ))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = parse_args();
+    let api_key = std::env::var("OPEN_ROUTER_API_KEY").ok();
+    let model = std::env::var("RESEARCH_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string());
+    let online = match &api_key {
+        Some(key) => Some(OpenRouterProvider::new(key.clone(), model.clone())?),
+        None => None,
+    };
+
+    // Knowledge base
+    let embedder: Arc<dyn EmbeddingProvider> = match &api_key {
+        Some(key) => Arc::new(OpenRouterProvider::new(key.clone(), model.clone())?),
+        None => Arc::new(HashingEmbedder::new(256)),
+    };
+    let documents = knowledge::load_documents(args.docs.as_deref()).await?;
+    let (retriever, chunks) = knowledge::ingest(embedder, &documents).await?;
+    let retrieval = RetrievalTool::new(retriever)
+        .with_name("search_knowledge_base")
+        .with_description("Search the user's research documents for relevant passages")
+        .max_results(4);
+
+    let search: Arc<dyn SearchBackend> = match std::env::var("BRAVE_API_KEY") {
+        Ok(key) => Arc::new(BraveSearch::new(key)),
+        Err(_) => Arc::new(OfflineSearch),
+    };
+    let notes_dir = std::env::temp_dir().join("agent-sdk-research-notes");
+    let toolkit = Toolkit {
+        retrieval,
+        search: search.clone(),
+        memory: AgentMemory::new("research-assistant", Arc::new(InMemoryStore::new())),
+        notes_dir: notes_dir.clone(),
+    };
+
+    ui::header(
+        "Research assistant",
+        &[
+            format!(
+                "model     {}",
+                if online.is_some() {
+                    model.as_str()
+                } else {
+                    "offline (scripted)"
+                }
+            ),
+            format!("knowledge {} documents, {} chunks", documents.len(), chunks),
+            format!("search    {}", search.name()),
+            format!("notes     {}", notes_dir.display()),
+        ],
+    );
+
+    match online {
+        Some(provider) => {
+            let agent = build_agent(provider, toolkit, args.auto_approve).await;
+            // Rough prices of the default model
+            research(
+                agent,
+                &args.question,
+                Pricing {
+                    prompt: 0.10,
+                    completion: 0.40,
+                },
+            )
+            .await
+        }
+        None => {
+            let agent = build_agent(offline_provider(), toolkit, true).await;
+            research(
+                agent,
+                DEFAULT_QUESTION,
+                Pricing {
+                    prompt: 0.10,
+                    completion: 0.40,
+                },
+            )
+            .await
+        }
+    }
+}
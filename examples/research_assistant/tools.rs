@@ -0,0 +1,246 @@
+//! Tools of the research assistant that the SDK does not ship.
+
+use agent_sdk::{
+    ApprovalDecision, ApprovalHandler, DangerLevel, SearchBackend, SearchResult, Tool, ToolCall,
+    ToolError, ToolInfo, ToolMetadata, ToolResult,
+};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest page text returned to the model
+const MAX_PAGE_CHARS: usize = 4_000;
+
+/// Fetches a web page and returns its text
+pub struct FetchUrlTool {
+    client: reqwest::Client,
+}
+
+impl FetchUrlTool {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+/// Text of an HTML page, with tags, scripts and styles removed
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len() / 2);
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let lower = rest.get(..7).unwrap_or_default().to_ascii_lowercase();
+        let skip_to = if lower.starts_with("<script") {
+            "</script>"
+        } else if lower.starts_with("<style") {
+            "</style>"
+        } else {
+            ">"
+        };
+        match rest.to_ascii_lowercase().find(skip_to) {
+            Some(end) => rest = &rest[end + skip_to.len()..],
+            None => rest = "",
+        }
+        text.push(' ');
+    }
+    text.push_str(rest);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[async_trait]
+impl Tool for FetchUrlTool {
+    fn name(&self) -> &str {
+        "fetch_url"
+    }
+
+    fn description(&self) -> &str {
+        "Download a web page over HTTP and return its text, e.g. to read a search result"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "description": "http or https URL"}
+            },
+            "required": ["url"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+            .expected_latency(Duration::from_secs(2))
+            .timeout(Duration::from_secs(20))
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let url = params["url"].as_str().unwrap_or_default().trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return ToolError::invalid_args("url must start with http:// or https://").into();
+        }
+        let response = match self.client.get(url).send().await {
+            Ok(response) => response,
+            Err(e) => return ToolError::upstream(format!("Request failed: {}", e)).into(),
+        };
+        let status = response.status();
+        if !status.is_success() {
+            return ToolError::upstream(format!("{} returned {}", url, status))
+                .with_retryable(status.is_server_error())
+                .into();
+        }
+        match response.text().await {
+            Ok(body) => {
+                let text: String = html_to_text(&body).chars().take(MAX_PAGE_CHARS).collect();
+                ToolResult::success(text)
+            }
+            Err(e) => ToolError::upstream(format!("Could not read the body: {}", e)).into(),
+        }
+    }
+}
+
+/// Writes research notes to files; needs approval
+pub struct SaveNoteTool {
+    dir: PathBuf,
+}
+
+impl SaveNoteTool {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Tool for SaveNoteTool {
+    fn name(&self) -> &str {
+        "save_note"
+    }
+
+    fn description(&self) -> &str {
+        "Save a markdown research note to a file, replacing any note with the same title"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "title": {"type": "string", "description": "Short title, used as file name"},
+                "content": {"type": "string", "description": "Markdown body"}
+            },
+            "required": ["title", "content"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default().danger(DangerLevel::Moderate)
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let title = params["title"].as_str().unwrap_or_default().trim();
+        let slug: String = title
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let slug = slug.trim_matches('-');
+        if slug.is_empty() {
+            return ToolError::invalid_args("title must contain letters or digits").into();
+        }
+        let content = params["content"].as_str().unwrap_or_default();
+
+        let path = self.dir.join(format!("{}.md", slug));
+        let write = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            tokio::fs::write(&path, format!("# {}\n\n{}\n", title, content)).await
+        };
+        match write.await {
+            Ok(()) => ToolResult::success(format!("Saved to {}", path.display())),
+            Err(e) => {
+                ToolError::upstream(format!("Could not write {}: {}", path.display(), e)).into()
+            }
+        }
+    }
+}
+
+/// Answers every query from a fixed list, for running without a search API key
+pub struct OfflineSearch;
+
+#[async_trait]
+impl SearchBackend for OfflineSearch {
+    fn name(&self) -> &str {
+        "offline"
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        max_results: usize,
+    ) -> Result<Vec<SearchResult>, ToolError> {
+        let results = [
+            (
+                "Efficient and robust approximate nearest neighbor search using HNSW graphs",
+                "https://arxiv.org/abs/1603.09320",
+                "Malkov and Yashunin introduce hierarchical navigable small world graphs.",
+            ),
+            (
+                "Retrieval-Augmented Generation for Knowledge-Intensive NLP Tasks",
+                "https://arxiv.org/abs/2005.11401",
+                "Lewis et al. combine a retriever with a generator for open-domain QA.",
+            ),
+        ];
+        Ok(results
+            .iter()
+            .take(max_results)
+            .enumerate()
+            .map(|(i, (title, url, snippet))| SearchResult {
+                rank: i + 1,
+                title: title.to_string(),
+                url: url.to_string(),
+                snippet: format!("{} (offline result for \"{}\")", snippet, query),
+            })
+            .collect())
+    }
+}
+
+/// Asks on the terminal before a risky tool runs
+pub struct TerminalApprover {
+    /// Approve without asking, e.g. in the offline demo
+    pub auto_approve: bool,
+}
+
+#[async_trait]
+impl ApprovalHandler for TerminalApprover {
+    async fn decide(&self, call: &ToolCall, tool: &ToolInfo) -> ApprovalDecision {
+        println!();
+        println!(
+            "\x1b[33m? {} ({}) wants to run with {}\x1b[0m",
+            call.name,
+            tool.metadata.danger.as_str(),
+            call.parameters
+        );
+        if self.auto_approve {
+            println!("\x1b[33m  auto-approved\x1b[0m");
+            return ApprovalDecision::Approve;
+        }
+
+        print!("\x1b[33m  approve? [y/N] \x1b[0m");
+        let _ = std::io::stdout().flush();
+        let answer = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+        .await;
+        match answer {
+            Ok(Ok(line)) if line.trim().eq_ignore_ascii_case("y") => ApprovalDecision::Approve,
+            _ => ApprovalDecision::Reject("The user declined to save the note".to_string()),
+        }
+    }
+}
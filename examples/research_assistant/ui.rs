@@ -0,0 +1,158 @@
+//! Terminal output: run header, streamed answer, tool activity and costs.
+
+use agent_sdk::{AgentStreamEvent, RunTrace, Usage};
+use std::io::Write;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Price of a model in dollars per million tokens
+#[derive(Debug, Clone, Copy)]
+pub struct Pricing {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt
+            + usage.completion_tokens as f64 * self.completion)
+            / 1_000_000.0
+    }
+}
+
+pub fn header(title: &str, lines: &[String]) {
+    let width = lines
+        .iter()
+        .map(|l| l.chars().count())
+        .chain([title.chars().count()])
+        .max()
+        .unwrap_or(0)
+        + 2;
+    println!("{}┌{}┐{}", CYAN, "─".repeat(width), RESET);
+    println!(
+        "{}│ {}{:<w$}{}{} │{}",
+        CYAN,
+        BOLD,
+        title,
+        RESET,
+        CYAN,
+        RESET,
+        w = width - 2
+    );
+    for line in lines {
+        println!(
+            "{}│ {}{:<w$}{} │{}",
+            CYAN,
+            RESET,
+            line,
+            CYAN,
+            RESET,
+            w = width - 2
+        );
+    }
+    println!("{}└{}┘{}", CYAN, "─".repeat(width), RESET);
+}
+
+/// Renders a run's stream events as they arrive
+#[derive(Default)]
+pub struct StreamView {
+    /// Whether the cursor is in the middle of streamed text
+    mid_line: bool,
+}
+
+impl StreamView {
+    pub fn render(&mut self, event: &AgentStreamEvent) {
+        match event {
+            AgentStreamEvent::TextDelta(text) => {
+                print!("{}", text);
+                self.mid_line = !text.ends_with('\n');
+            }
+            AgentStreamEvent::ToolCallStarted(call) => {
+                self.break_line();
+                println!("{}⚙ {} {}{}", DIM, call.name, call.parameters, RESET);
+            }
+            AgentStreamEvent::ToolCallFinished { call, result } => {
+                self.break_line();
+                let first_line = result.content.lines().next().unwrap_or_default();
+                if result.success {
+                    println!(
+                        "{}  ✓ {}: {}{}",
+                        GREEN,
+                        call.name,
+                        truncate(first_line, 80),
+                        RESET
+                    );
+                } else {
+                    let error = result.error.as_deref().unwrap_or("failed");
+                    println!("{}  ✗ {}: {}{}", RED, call.name, truncate(error, 80), RESET);
+                }
+            }
+            AgentStreamEvent::FinalAnswer(_) => self.break_line(),
+        }
+        let _ = std::io::stdout().flush();
+    }
+
+    fn break_line(&mut self) {
+        if self.mid_line {
+            println!();
+            self.mid_line = false;
+        }
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max).collect::<String>())
+    }
+}
+
+/// Tokens, latency and cost of every LLM request of the run
+pub fn cost_report(trace: &RunTrace, pricing: Pricing) {
+    println!();
+    println!("{}Cost report{}", BOLD, RESET);
+    println!(
+        "{}  #  {:>8} {:>8} {:>8} {:>10}  tools{}",
+        DIM, "prompt", "output", "ms", "cost", RESET
+    );
+    for iteration in &trace.iterations {
+        let usage = iteration.response.usage.clone().unwrap_or_default();
+        let tools: Vec<&str> = iteration
+            .tool_calls
+            .iter()
+            .map(|t| t.call.name.as_str())
+            .collect();
+        println!(
+            "  {:<2} {:>8} {:>8} {:>8} {:>10}  {}",
+            iteration.iteration + 1,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            iteration.llm_duration_ms,
+            format!("${:.5}", pricing.cost(&usage)),
+            tools.join(", ")
+        );
+    }
+    let total = trace.usage();
+    println!(
+        "  {}Σ  {:>8} {:>8} {:>8} {:>10}{}",
+        BOLD,
+        total.prompt_tokens,
+        total.completion_tokens,
+        "",
+        format!("${:.5}", pricing.cost(&total)),
+        RESET
+    );
+    println!(
+        "{}  {} tool calls, {} failed{}",
+        DIM,
+        trace.tool_calls().count(),
+        trace.tool_calls().filter(|t| !t.success).count(),
+        RESET
+    );
+}
@@ -1,10 +1,43 @@
-use super::options::{AgentOptions, ToolChoice};
+use super::gauge::{ContextGauge, ContextUsage};
+use super::middleware::{IterationContext, RunMiddleware, RunRequest};
+use super::observation::Observation;
+use super::options::{AgentOptions, RunOverrides, ToolChoice, ToolExecution};
+use super::outcome::{FinishReason, RunOutcome, RunProgress};
+use super::pause::PauseHandle;
+use super::stream::{AgentRunStream, AgentStreamEvent};
+use super::telemetry;
+use super::trace::{IterationTrace, RequestSummary, RunTrace, ToolCallTrace};
+use crate::bundle::ConversationBundle;
 use crate::error::{AgentError, Result};
-use crate::events::{AgentEvent, EventBus};
-use crate::provider::{LlmProvider, Message, StreamResponse};
-use crate::tool::{Tool, ToolCallParser, ToolExecutor, ToolRegistry, ToolResult};
+use crate::events::{AgentEvent, EventBus, RunMetadata};
+use crate::hooks::{AgentHook, HookErrorPolicy, HookFailure, HookRegistry};
+use crate::memory::{Memory, SummarizingMemory};
+use crate::provider::{
+    context_window_for_model, parse_with_repair, tokenizer_for_model, ContentBlock,
+    GenerateOptions, GenerateResponse, JsonSchema, LlmProvider, Message, ProviderError,
+    ProviderSwitch, ResponseFormat, Role, StreamEvent, StreamResponse, ToolSchema, ToolSelection,
+};
+use crate::session::Session;
+use crate::tool::{
+    coerce_arguments, ApprovalManager, ApprovalTimeout, DryRun, Tool, ToolCall, ToolCallParser,
+    ToolError, ToolErrorKind, ToolExecutor, ToolInfo, ToolOutputPolicy, ToolRegistry, ToolResult,
+    ToolResultCache,
+};
+use futures_util::future::join_all;
+use serde::de::DeserializeOwned;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+/// Aborts the wrapped task when dropped
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
 
 pub struct Agent<P: LlmProvider> {
     provider: P,
@@ -13,6 +46,16 @@ pub struct Agent<P: LlmProvider> {
     conversation: Vec<Message>,
     options: AgentOptions,
     event_bus: Option<Arc<EventBus>>,
+    hooks: HookRegistry,
+    middleware: Vec<Arc<dyn RunMiddleware>>,
+    cancellation: CancellationToken,
+    pause: PauseHandle,
+    context_gauge: ContextGauge,
+    /// Receives incremental output while `run_stream_events` drives a run
+    stream_sink: Option<mpsc::UnboundedSender<AgentStreamEvent>>,
+    progress: RunProgress,
+    /// Metadata of the current or most recent run
+    run_metadata: RunMetadata,
 }
 
 impl<P: LlmProvider> Agent<P> {
@@ -27,6 +70,14 @@ impl<P: LlmProvider> Agent<P> {
             conversation: Vec::new(),
             options: AgentOptions::default(),
             event_bus: None,
+            hooks: HookRegistry::new(),
+            middleware: Vec::new(),
+            cancellation: CancellationToken::new(),
+            pause: PauseHandle::new(),
+            context_gauge: ContextGauge::new(),
+            stream_sink: None,
+            progress: RunProgress::default(),
+            run_metadata: RunMetadata::default(),
         }
     }
 
@@ -35,25 +86,809 @@ impl<P: LlmProvider> Agent<P> {
         self
     }
 
+    pub fn options(&self) -> &AgentOptions {
+        &self.options
+    }
+
+    /// Replace the options used by subsequent runs
+    pub fn set_options(&mut self, options: AgentOptions) {
+        self.options = options;
+    }
+
+    /// Truncate tool results to `policy` before they reach the conversation
+    pub fn with_tool_output_policy(mut self, policy: ToolOutputPolicy) -> Self {
+        self.executor.set_output_policy(Some(policy));
+        self
+    }
+
+    /// Reuse results of the tools `cache` covers instead of running
+    /// identical calls again
+    pub fn with_tool_cache(mut self, cache: ToolResultCache) -> Self {
+        self.executor.set_cache(Some(cache));
+        self
+    }
+
+    pub fn set_tool_cache(&mut self, cache: Option<ToolResultCache>) {
+        self.executor.set_cache(cache);
+    }
+
+    /// Preview runs: tool calls are recorded in `dry_run` and answered with
+    /// synthetic results instead of being executed
+    pub fn with_dry_run(mut self, dry_run: DryRun) -> Self {
+        self.executor.set_dry_run(Some(dry_run));
+        self
+    }
+
+    /// Switch dry-run mode on or off for later tool calls
+    pub fn set_dry_run(&mut self, dry_run: Option<DryRun>) {
+        self.executor.set_dry_run(dry_run);
+    }
+
+    /// Ask `approvals` before running calls to risky tools
+    pub fn with_approvals(mut self, approvals: ApprovalManager) -> Self {
+        self.executor.set_approvals(Some(approvals));
+        self
+    }
+
+    pub fn set_approvals(&mut self, approvals: Option<ApprovalManager>) {
+        self.executor.set_approvals(approvals);
+    }
+
+    pub fn approvals(&self) -> Option<&ApprovalManager> {
+        self.executor.approvals()
+    }
+
     pub fn with_event_bus(mut self, event_bus: Arc<EventBus>) -> Self {
         self.event_bus = Some(event_bus);
         self
     }
 
+    /// Replace the hooks run at each point of the agent loop
+    pub fn with_hooks(mut self, hooks: HookRegistry) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Append a hook with the default error policy
+    pub fn add_hook(&mut self, hook: Arc<dyn AgentHook>) {
+        self.hooks.add(hook);
+    }
+
+    /// Append a hook with an explicit error policy
+    pub fn add_hook_with_policy(&mut self, hook: Arc<dyn AgentHook>, policy: HookErrorPolicy) {
+        self.hooks.add_with_policy(hook, policy);
+    }
+
+    /// Wrap runs in `middleware`, inside any added before it
+    pub fn with_middleware(mut self, middleware: Arc<dyn RunMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    pub fn add_middleware(&mut self, middleware: Arc<dyn RunMiddleware>) {
+        self.middleware.push(middleware);
+    }
+
+    /// Cancel runs through `token`, e.g. a child of an application-wide token
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Token that stops the current and later runs when cancelled
+    ///
+    /// Cancelling drops the in-flight LLM request or tool execution, and the
+    /// run fails with `AgentError::Cancelled`.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Replace a cancelled token so the agent can run again
+    pub fn reset_cancellation(&mut self) -> CancellationToken {
+        self.cancellation = CancellationToken::new();
+        self.cancellation.clone()
+    }
+
+    /// Handle that pauses runs between steps, e.g. from a UI task
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.pause.clone()
+    }
+
+    /// Live context usage, shareable with a UI task
+    pub fn context_gauge(&self) -> ContextGauge {
+        self.context_gauge.clone()
+    }
+
+    pub fn context_usage(&self) -> ContextUsage {
+        self.context_gauge.usage()
+    }
+
+    /// Handle to the agent's tools for registering or removing tools while a
+    /// run is in progress, e.g. once an MCP server connects
+    pub fn tool_registry(&self) -> ToolRegistry {
+        self.tools.clone()
+    }
+
+    pub async fn unregister_tool(&mut self, name: &str) -> bool {
+        self.tools.unregister(name).await
+    }
+
     pub async fn register_tool(&mut self, tool: Box<dyn Tool>) {
         self.tools.register(tool).await;
     }
 
+    /// Keep a renamed tool callable under its old name
+    ///
+    /// Calls to `alias` run `target` and emit `DeprecatedToolAlias`.
+    pub async fn register_tool_alias(&mut self, alias: &str, target: &str) {
+        self.tools.register_alias(alias, target).await;
+    }
+
     fn emit_event(&self, event: AgentEvent) {
+        *self
+            .progress
+            .events
+            .lock()
+            .unwrap()
+            .entry(event.kind())
+            .or_default() += 1;
+        telemetry::record_event(&event);
         if let Some(bus) = &self.event_bus {
-            bus.emit(event);
+            bus.emit_with_metadata(event, self.run_metadata.clone());
+        }
+    }
+
+    /// Metadata of the current or most recent run, see `RunOverrides::metadata`
+    pub fn run_metadata(&self) -> &RunMetadata {
+        &self.run_metadata
+    }
+
+    /// What each LLM request of the current or most recent run sent and
+    /// got back, with its tool calls and timings
+    pub fn run_trace(&self) -> &RunTrace {
+        &self.progress.trace
+    }
+
+    /// The most recent run's conversation with the tools offered, its token
+    /// usage and event counts
+    ///
+    /// Continue it elsewhere with `ConversationBundle::into_session`.
+    pub async fn export_conversation(&self, id: impl Into<String>) -> ConversationBundle {
+        let mut bundle = ConversationBundle::new(id, self.conversation.clone())
+            .with_tools(self.tool_schemas().await)
+            .with_usage(self.progress.usage.clone());
+        for (&kind, &count) in self.progress.events.lock().unwrap().iter() {
+            bundle.record_events(kind, count);
+        }
+        bundle
+    }
+
+    /// How long the next request would wait for the provider's rate limiter
+    ///
+    /// `None` if the provider has no client-side rate limiter.
+    pub async fn rate_limit_wait_estimate(&self) -> Option<Duration> {
+        match self.provider.rate_limiter() {
+            Some(limiter) => Some(limiter.wait_estimate().await),
+            None => None,
+        }
+    }
+
+    /// Forward rate limiter waits to the event bus until the guard is dropped
+    fn forward_rate_limit_waits(&self) -> Option<AbortOnDrop> {
+        let bus = self.event_bus.clone()?;
+        let metadata = self.run_metadata.clone();
+        let mut waits = self.provider.rate_limiter()?.subscribe_waits();
+        let provider = self.provider.name().to_string();
+        Some(AbortOnDrop(tokio::spawn(async move {
+            loop {
+                match waits.recv().await {
+                    Ok(wait) => bus.emit_with_metadata(
+                        AgentEvent::RateLimitWait {
+                            provider: provider.clone(),
+                            wait_ms: wait.wait.as_millis() as u64,
+                        },
+                        metadata.clone(),
+                    ),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })))
+    }
+
+    /// Emit the provider switches received on `switches` so far
+    fn emit_provider_switches(
+        &self,
+        switches: Option<tokio::sync::broadcast::Receiver<ProviderSwitch>>,
+    ) {
+        let Some(mut switches) = switches else {
+            return;
+        };
+        loop {
+            match switches.try_recv() {
+                Ok(switch) => self.emit_event(AgentEvent::ProviderSwitched { switch }),
+                Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Emit the approval timeouts for `call` received on `timeouts`
+    fn emit_approval_timeouts(
+        &self,
+        call: &ToolCall,
+        timeouts: Option<tokio::sync::broadcast::Receiver<ApprovalTimeout>>,
+    ) {
+        let Some(mut timeouts) = timeouts else {
+            return;
+        };
+        loop {
+            match timeouts.try_recv() {
+                // Calls running in parallel share the channel
+                Ok(timeout) if timeout.call_id == call.id => {
+                    self.emit_event(AgentEvent::ApprovalTimedOut { timeout })
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
         }
     }
 
     pub async fn run(&mut self, input: &str) -> Result<String> {
+        self.run_with_overrides(input, RunOverrides::default())
+            .await
+    }
+
+    /// Run a conversation and report why it stopped
+    ///
+    /// Runs that hit `max_iterations`, `run_timeout` or `max_total_tokens`,
+    /// are cancelled or stop on a tool failure return an outcome instead of
+    /// an error; other failures are still errors.
+    pub async fn run_outcome(&mut self, input: &str) -> Result<RunOutcome> {
+        let started = Instant::now();
+        let result = self
+            .run_loop(input, Vec::new(), RunOverrides::default())
+            .await;
+        self.hooks.run_end(&result).await;
+
+        let (text, finish_reason) = match result {
+            Ok(text) => (
+                text,
+                self.progress.finish.unwrap_or(FinishReason::Completed),
+            ),
+            Err(error) => {
+                let finish_reason = match (&error, self.progress.finish) {
+                    (AgentError::Cancelled, _) => FinishReason::Cancelled,
+                    (AgentError::Timeout(_), _) => FinishReason::Timeout,
+                    (_, Some(reason)) => reason,
+                    (_, None) => return Err(error),
+                };
+                let text = self
+                    .conversation
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == Role::Assistant)
+                    .map(Message::content_as_text)
+                    .unwrap_or_default();
+                (text, finish_reason)
+            }
+        };
+        Ok(RunOutcome {
+            text,
+            finish_reason,
+            usage: self.progress.usage.clone(),
+            iterations: self.progress.iterations,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Run a single conversation with model/sampling overrides for this run only
+    pub async fn run_with_overrides(
+        &mut self,
+        input: &str,
+        overrides: RunOverrides,
+    ) -> Result<String> {
+        let result = self.run_loop(input, Vec::new(), overrides).await;
+        self.hooks.run_end(&result).await;
+        result
+    }
+
+    /// Continue the conversation stored in `session`
+    ///
+    /// The session's turns are replayed after the system prompts, and the
+    /// session is updated with this run's turns when it completes. Save it
+    /// with a `SessionStore` to resume after a restart.
+    pub async fn run_in_session(&mut self, session: &mut Session, input: &str) -> Result<String> {
+        let result = self
+            .run_loop(input, session.messages.clone(), RunOverrides::default())
+            .await;
+        self.hooks.run_end(&result).await;
+        if result.is_ok() {
+            session.record_run(&self.conversation);
+        }
+        result
+    }
+
+    /// Continue the conversation in `memory` and flush the new turns to
+    /// its store when the run completes
+    pub async fn run_with_memory(&mut self, memory: &mut Memory, input: &str) -> Result<String> {
+        let result = self
+            .run_loop(input, memory.messages().to_vec(), RunOverrides::default())
+            .await;
+        self.hooks.run_end(&result).await;
+        if result.is_ok() {
+            memory.record_run(&self.conversation, memory.messages().len());
+            memory.flush().await?;
+        }
+        result
+    }
+
+    /// Like `run_with_memory`, but first folds older turns into the
+    /// memory's summary if the conversation has grown too long
+    pub async fn run_with_summarizing_memory(
+        &mut self,
+        memory: &mut SummarizingMemory,
+        input: &str,
+    ) -> Result<String> {
+        memory.compact().await?;
+        let result = self
+            .run_loop(input, memory.context(), RunOverrides::default())
+            .await;
+        self.hooks.run_end(&result).await;
+        if result.is_ok() {
+            memory.record_run(&self.conversation);
+            memory.flush().await?;
+        }
+        result
+    }
+
+    /// Run a conversation and deserialize the final answer as `T`
+    ///
+    /// The schema is sent as the provider's native response format when
+    /// supported and appended to the input otherwise. An answer that does not
+    /// match is sent back with a repair prompt, up to
+    /// `STRUCTURED_REPAIR_ATTEMPTS` times.
+    pub async fn run_structured<T: DeserializeOwned>(
+        &mut self,
+        input: &str,
+        schema: &JsonSchema,
+    ) -> Result<T> {
+        let native = self.provider.capabilities().structured_output;
+        let (input, overrides) = if native {
+            let overrides = RunOverrides {
+                response_format: Some(ResponseFormat::JsonSchema(schema.clone())),
+                ..Default::default()
+            };
+            (input.to_string(), overrides)
+        } else {
+            let input = format!("{}\n\n{}", input, schema.instruction());
+            (input, RunOverrides::default())
+        };
+        let options = overrides.apply(&self.options.generate_options);
+
+        let content = self.run_with_overrides(&input, overrides).await?;
+        // The conversation ends with the answer, which the repair loop re-adds
+        let mut messages = self.conversation.clone();
+        messages.pop();
+        let value =
+            parse_with_repair(&self.provider, messages, content, schema, Some(options)).await?;
+        Ok(value)
+    }
+
+    /// Run a conversation as a stream of text deltas, tool calls and the
+    /// final answer
+    ///
+    /// Text is streamed token by token when the provider supports streaming
+    /// and arrives as one delta per LLM turn otherwise.
+    pub fn run_stream_events<'a>(&'a mut self, input: &'a str) -> AgentRunStream<'a>
+    where
+        P: 'a,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        AgentRunStream::new(
+            Box::pin(async move {
+                self.stream_sink = Some(tx);
+                let result = self.run(input).await;
+                self.stream_sink = None;
+                result
+            }),
+            rx,
+        )
+    }
+
+    /// Send incremental output to an active `run_stream_events` consumer
+    fn stream_event(&self, event: AgentStreamEvent) {
+        if let Some(sink) = &self.stream_sink {
+            let _ = sink.send(event);
+        }
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.stream_sink
+            .as_ref()
+            .is_some_and(|sink| !sink.is_closed())
+    }
+
+    /// Generate through the provider's event stream, forwarding text deltas
+    async fn stream_response(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: GenerateOptions,
+    ) -> crate::provider::Result<GenerateResponse> {
+        let model = options
+            .model
+            .clone()
+            .unwrap_or_else(|| self.provider.model().to_string());
+        let mut events = self
+            .provider
+            .generate_stream_events(messages, tools, Some(options))
+            .await?;
+
+        let mut content = String::new();
+        // (id, name, arguments) by tool call index
+        let mut calls: Vec<(String, String, String)> = Vec::new();
+        let mut usage = None;
+        let mut finish_reason = None;
+        while let Some(event) = events.receiver.recv().await {
+            match event? {
+                StreamEvent::TextDelta(text) => {
+                    content.push_str(&text);
+                    self.stream_event(AgentStreamEvent::TextDelta(text));
+                }
+                StreamEvent::ToolCallDelta {
+                    index,
+                    id,
+                    name,
+                    arguments_delta,
+                } => {
+                    if calls.len() <= index {
+                        calls.resize(index + 1, Default::default());
+                    }
+                    let call = &mut calls[index];
+                    if let Some(id) = id {
+                        call.0 = id;
+                    }
+                    if let Some(name) = name {
+                        call.1 = name;
+                    }
+                    call.2.push_str(&arguments_delta);
+                }
+                StreamEvent::UsageUpdate(update) => usage = Some(update),
+                StreamEvent::Done {
+                    finish_reason: reason,
+                } => {
+                    finish_reason = reason;
+                    break;
+                }
+            }
+        }
+
+        let tool_calls = calls
+            .into_iter()
+            .filter(|(_, name, _)| !name.is_empty())
+            .map(|(id, name, arguments)| {
+                let parameters = if arguments.trim().is_empty() {
+                    serde_json::json!({})
+                } else {
+                    serde_json::from_str(&arguments).map_err(|e| {
+                        ProviderError::ParseError(format!(
+                            "Invalid arguments for tool {}: {}",
+                            name, e
+                        ))
+                    })?
+                };
+                Ok(ToolCall {
+                    id,
+                    name,
+                    parameters,
+                })
+            })
+            .collect::<crate::provider::Result<Vec<_>>>()?;
+
+        Ok(GenerateResponse {
+            content,
+            usage,
+            model,
+            finish_reason,
+            tool_calls,
+        })
+    }
+
+    /// Resolve aliases, run hooks and coercion, and announce a tool call
+    async fn prepare_tool_call(&self, call: &mut ToolCall) -> Result<()> {
+        // Calls after the current one are deferred while paused
+        self.wait_if_paused().await?;
+        if let Some(tool) = self.tools.resolve_alias(&call.name).await {
+            self.emit_event(AgentEvent::DeprecatedToolAlias {
+                alias: std::mem::replace(&mut call.name, tool.clone()),
+                tool,
+            });
+        }
+        // The id pairs the result with the assistant's tool call, so
+        // hooks may rewrite the call but not its id
+        let id = call.id.clone();
+        let hook_result = self.hooks.before_tool_call(call).await;
+        self.hook_failed(hook_result)?;
+        call.id = id;
+        if self.options.coerce_tool_arguments {
+            if let Some(info) = self.tools.tool_info(&call.name).await {
+                coerce_arguments(&mut call.parameters, &info.parameters_schema);
+            }
+        }
+
+        self.emit_event(AgentEvent::ToolCallStarted { call: call.clone() });
+        self.stream_event(AgentStreamEvent::ToolCallStarted(call.clone()));
+        Ok(())
+    }
+
+    /// Execute a tool call if it is allowed, timing it
+    async fn execute_tool_call(&self, call: &ToolCall) -> (ToolResult, Duration) {
+        let started = Instant::now();
+        let span = telemetry::Span::tool(call);
+        let result = if self.options.is_tool_allowed(&call.name) {
+            let approval_timeouts = self.executor.approvals().map(|a| a.subscribe_timeouts());
+            let result = span
+                .instrument(
+                    self.executor
+                        .execute_with_timeout(call, self.options.tool_timeout),
+                )
+                .await;
+            self.emit_approval_timeouts(call, approval_timeouts);
+            result
+        } else {
+            ToolResult::failed(ToolError::permission_denied(format!(
+                "Tool not available: {}",
+                call.name
+            )))
+        };
+        span.record_tool_result(&result);
+        (result, started.elapsed())
+    }
+
+    /// Run the after-call hooks and report a tool call's outcome
+    async fn finish_tool_call(
+        &self,
+        call: &ToolCall,
+        mut result: ToolResult,
+        elapsed: Duration,
+    ) -> Result<ToolResult> {
+        if let Some(limit_ms) = result
+            .failure
+            .as_ref()
+            .filter(|f| f.kind == ToolErrorKind::Timeout)
+            .and_then(|f| f.details.as_ref()?.get("timeout_ms")?.as_u64())
+        {
+            self.emit_event(AgentEvent::Timeout {
+                tool: Some(call.name.clone()),
+                limit_ms,
+            });
+        }
+        self.emit_event(AgentEvent::ToolCallTimed {
+            call_id: call.id.clone(),
+            tool: call.name.clone(),
+            duration_ms: elapsed.as_millis() as u64,
+        });
+
+        let hook_result = self.hooks.after_tool_call(call, &mut result).await;
+        self.hook_failed(hook_result)?;
+
+        if result.success {
+            self.emit_event(AgentEvent::ToolCallCompleted {
+                call: call.clone(),
+                result: result.clone(),
+            });
+        } else {
+            self.emit_event(AgentEvent::ToolCallFailed {
+                call: call.clone(),
+                error: result.error.clone().unwrap_or_default(),
+            });
+        }
+
+        self.stream_event(AgentStreamEvent::ToolCallFinished {
+            call: call.clone(),
+            result: result.clone(),
+        });
+        Ok(result)
+    }
+
+    /// Report logged hook errors, and a hook abort or hook error as a failed
+    /// conversation
+    fn hook_failed(&self, result: Result<Vec<HookFailure>>) -> Result<()> {
+        match result {
+            Ok(failures) => {
+                self.emit_hook_failures(failures);
+                Ok(())
+            }
+            Err(e) => {
+                self.emit_event(AgentEvent::ConversationFailed {
+                    error: e.to_string(),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    fn emit_hook_failures(&self, failures: Vec<HookFailure>) {
+        for failure in failures {
+            self.emit_event(AgentEvent::HookFailed { failure });
+        }
+    }
+
+    /// Report the run as cancelled
+    fn cancelled<T>(&self) -> Result<T> {
+        self.emit_event(AgentEvent::ConversationFailed {
+            error: AgentError::Cancelled.to_string(),
+        });
+        Err(AgentError::Cancelled)
+    }
+
+    /// Publish context usage and report thresholds crossed for the first time
+    /// this run; `reported` counts the thresholds already reported
+    fn update_context_usage(&self, usage: ContextUsage, thresholds: &[f64], reported: &mut usize) {
+        self.context_gauge.set(usage);
+        let (Some(ratio), Some(max_tokens)) = (usage.ratio(), usage.max_tokens) else {
+            return;
+        };
+        while let Some(&threshold) = thresholds.get(*reported) {
+            if ratio < threshold {
+                break;
+            }
+            self.emit_event(AgentEvent::ContextPressure {
+                used_tokens: usage.used_tokens,
+                max_tokens,
+                threshold,
+            });
+            *reported += 1;
+        }
+    }
+
+    /// End the run within `max_total_tokens` with the last assistant text so
+    /// far
+    fn finish_over_budget(&mut self, used_tokens: usize, max_tokens: usize) -> Result<String> {
+        self.emit_event(AgentEvent::BudgetExceeded {
+            used_tokens,
+            max_tokens,
+        });
+        let answer = self
+            .progress
+            .trace
+            .iterations
+            .iter()
+            .rev()
+            .map(|i| &i.response.content)
+            .find(|content| !content.is_empty())
+            .cloned()
+            .unwrap_or_default();
+        self.progress.finish = Some(FinishReason::Budget);
+        self.emit_event(AgentEvent::ConversationCompleted {
+            response: answer.clone(),
+        });
+        self.stream_event(AgentStreamEvent::FinalAnswer(answer.clone()));
+        Ok(answer)
+    }
+
+    /// Wait while the run is paused; cancellation still ends the run
+    async fn wait_if_paused(&self) -> Result<()> {
+        if !self.pause.is_paused() {
+            return Ok(());
+        }
+        self.emit_event(AgentEvent::RunPaused);
+        tokio::select! {
+            biased;
+            _ = self.cancellation.cancelled() => return self.cancelled(),
+            _ = self.pause.wait_resumed() => {}
+        }
+        self.emit_event(AgentEvent::RunResumed);
+        Ok(())
+    }
+
+    /// Run the conversation within `AgentOptions::run_timeout`
+    async fn run_loop(
+        &mut self,
+        input: &str,
+        history: Vec<Message>,
+        overrides: RunOverrides,
+    ) -> Result<String> {
+        let span = telemetry::Span::run(self.provider.name(), self.provider.model());
+        let middleware = self.middleware.clone();
+        let mut run = RunRequest {
+            input: input.to_string(),
+            overrides,
+        };
+        let result = span
+            .instrument(async {
+                let mut result = Ok(String::new());
+                let mut entered = 0;
+                for m in &middleware {
+                    if let Err(e) = m.before_run(&mut run).await {
+                        result = Err(e);
+                        break;
+                    }
+                    entered += 1;
+                }
+                if entered == middleware.len() {
+                    span.record_metadata(&run.overrides.metadata);
+                    result = self
+                        .run_with_timeout(&run.input, history, run.overrides.clone())
+                        .await;
+                }
+                for m in middleware[..entered].iter().rev() {
+                    m.after_run(&run, &mut result).await;
+                }
+                result
+            })
+            .await;
+
+        span.record_usage(&self.progress.usage);
+        span.record_iterations(self.progress.iterations);
+        if let Err(e) = &result {
+            span.record_error(&e.to_string());
+        }
+        result
+    }
+
+    async fn run_with_timeout(
+        &mut self,
+        input: &str,
+        history: Vec<Message>,
+        overrides: RunOverrides,
+    ) -> Result<String> {
+        let Some(limit) = self.options.run_timeout else {
+            return self.run_iterations(input, history, overrides).await;
+        };
+        match tokio::time::timeout(limit, self.run_iterations(input, history, overrides)).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.emit_event(AgentEvent::Timeout {
+                    tool: None,
+                    limit_ms: limit.as_millis() as u64,
+                });
+                self.emit_event(AgentEvent::ConversationFailed {
+                    error: AgentError::Timeout(limit).to_string(),
+                });
+                Err(AgentError::Timeout(limit))
+            }
+        }
+    }
+
+    async fn run_iterations(
+        &mut self,
+        input: &str,
+        history: Vec<Message>,
+        overrides: RunOverrides,
+    ) -> Result<String> {
+        self.progress = RunProgress::default();
+        self.run_metadata = Arc::new(overrides.metadata.clone());
         self.emit_event(AgentEvent::ConversationStarted {
             input: input.to_string(),
         });
+        let hook_result = self.hooks.run_start(input).await;
+        self.hook_failed(hook_result)?;
+
+        if !overrides.is_empty() {
+            self.emit_event(AgentEvent::RunOverridesApplied {
+                overrides: overrides.clone(),
+            });
+        }
+        let generate_options = overrides.apply(&self.options.generate_options);
+        let max_context_tokens = match &generate_options.model {
+            Some(model) => context_window_for_model(model),
+            None => self.provider.context_window(),
+        };
+        let tokenizer = tokenizer_for_model(
+            generate_options
+                .model
+                .as_deref()
+                .unwrap_or(self.provider.model()),
+        );
+        let mut pressure_thresholds = self.options.context_pressure_thresholds.clone();
+        pressure_thresholds.sort_by(f64::total_cmp);
+        let mut pressure_reported = 0;
 
         self.conversation.clear();
 
@@ -62,37 +897,193 @@ impl<P: LlmProvider> Agent<P> {
             self.conversation.push(Message::system(system_prompt));
         }
 
+        // 优先使用 provider 的原生工具调用
+        let native_tools = self.tools_enabled() && self.provider.capabilities().native_tools;
+        let native_tool_choice = native_tools && self.provider.capabilities().tool_choice;
+        let mut tool_schemas = Vec::new();
+        let mut tools_version = self.tools.version();
+        let mut offered = self.offered_tool_names().await;
+        if native_tools {
+            tool_schemas = self.tool_schemas().await;
+        }
+
         // 添加工具描述
-        if self.tools_enabled() {
-            let tools_desc = self.format_tools_description().await;
-            if !tools_desc.is_empty() {
-                let tool_prompt = format!(
-                    "You have access to the following tools:\n{}\n\nTo use a tool, respond with JSON in this format:\n{{\n  \"tool_calls\": [\n    {{\n      \"id\": \"call_1\",\n      \"name\": \"tool_name\",\n      \"parameters\": {{\n        \"param1\": \"value1\"\n      }}\n    }}\n  ]\n}}",
-                    tools_desc
-                );
+        let tool_prompt_index = self.conversation.len();
+        let mut has_tool_prompt = false;
+        if self.tools_enabled() && !native_tools {
+            if let Some(tool_prompt) = self.tool_prompt().await {
                 self.conversation.push(Message::system(tool_prompt));
+                has_tool_prompt = true;
+            }
+        } else if native_tools && !native_tool_choice {
+            if let Some(instruction) = self.tool_choice_instruction() {
+                self.conversation.push(Message::system(instruction));
             }
         }
 
+        // 恢复会话历史
+        self.conversation.extend(history);
+
         // 添加用户输入
         self.conversation.push(Message::user(input));
 
         // 执行对话循环
-        for _ in 0..self.options.max_iterations {
+        for iteration in 0..self.options.max_iterations {
+            self.wait_if_paused().await?;
+            if self.cancellation.is_cancelled() {
+                return self.cancelled();
+            }
+            let ctx = IterationContext {
+                iteration,
+                usage: self.progress.usage.clone(),
+                metadata: self.run_metadata.clone(),
+            };
+            for middleware in self.middleware.clone() {
+                if let Err(e) = middleware.before_iteration(&ctx).await {
+                    self.emit_event(AgentEvent::ConversationFailed {
+                        error: e.to_string(),
+                    });
+                    return Err(e);
+                }
+            }
+
+            // Pick up tools registered or removed since the last request
+            if self.tools_enabled() && self.tools.version() != tools_version {
+                tools_version = self.tools.version();
+                let current = self.offered_tool_names().await;
+                let added: Vec<String> = current
+                    .iter()
+                    .filter(|name| !offered.contains(name))
+                    .cloned()
+                    .collect();
+                let removed: Vec<String> = offered
+                    .iter()
+                    .filter(|name| !current.contains(name))
+                    .cloned()
+                    .collect();
+                offered = current;
+
+                if !added.is_empty() || !removed.is_empty() {
+                    if native_tools {
+                        tool_schemas = self.tool_schemas().await;
+                    } else {
+                        match (self.tool_prompt().await, has_tool_prompt) {
+                            (Some(prompt), true) => {
+                                self.conversation[tool_prompt_index] = Message::system(prompt);
+                            }
+                            (Some(prompt), false) => {
+                                self.conversation
+                                    .insert(tool_prompt_index, Message::system(prompt));
+                                has_tool_prompt = true;
+                            }
+                            (None, true) => {
+                                self.conversation.remove(tool_prompt_index);
+                                has_tool_prompt = false;
+                            }
+                            (None, false) => {}
+                        }
+                    }
+                    self.emit_event(AgentEvent::ToolsChanged { added, removed });
+                }
+            }
+
+            let mut messages = self.conversation.clone();
+            let hook_result = self.hooks.before_llm_request(&mut messages).await;
+            self.hook_failed(hook_result)?;
+
+            let estimate = ContextUsage {
+                used_tokens: tokenizer.count_request_tokens(&messages, &tool_schemas),
+                max_tokens: max_context_tokens,
+            };
+            if let Some(max_tokens) = self.options.max_total_tokens {
+                let projected = self.progress.usage.total_tokens as usize + estimate.used_tokens;
+                if projected > max_tokens {
+                    return self.finish_over_budget(projected, max_tokens);
+                }
+            }
+
             self.emit_event(AgentEvent::LlmRequestSent {
-                messages: self.conversation.clone(),
+                messages: messages.clone(),
             });
+            self.update_context_usage(estimate, &pressure_thresholds, &mut pressure_reported);
 
-            let response = match self
-                .provider
-                .generate(
-                    self.conversation.clone(),
-                    Some(self.options.generate_options.clone()),
+            self.progress.iterations += 1;
+            let mut request_options = generate_options.clone();
+            if native_tool_choice {
+                // A specific tool is forced once so the model can then answer
+                let forced = match &self.options.tool_choice {
+                    ToolChoice::Required => Some(ToolSelection::Any),
+                    ToolChoice::Specific(name) if iteration == 0 => {
+                        Some(ToolSelection::Tool(name.clone()))
+                    }
+                    _ => None,
+                };
+                if forced.is_some() {
+                    request_options.tool_choice = forced;
+                }
+            }
+            if native_tools && !self.options.parallel_tool_calls {
+                request_options.parallel_tool_calls = Some(false);
+            }
+            let request_summary = RequestSummary {
+                model: request_options
+                    .model
+                    .clone()
+                    .unwrap_or_else(|| self.provider.model().to_string()),
+                messages: messages.len(),
+                estimated_tokens: estimate.used_tokens,
+                tools: if self.tools_enabled() {
+                    offered.clone()
+                } else {
+                    Vec::new()
+                },
+            };
+            let llm_span =
+                telemetry::Span::llm(self.provider.name(), &request_summary.model, iteration);
+            let requested_at = Instant::now();
+            let throttle_events = self.forward_rate_limit_waits();
+            let switches = self.provider.provider_switches();
+            let capabilities = self.provider.capabilities();
+            let streamed = self.is_streaming()
+                && capabilities.streaming
+                && (!native_tools || capabilities.native_tools);
+            let request = if streamed {
+                let tools = if native_tools {
+                    tool_schemas.clone()
+                } else {
+                    Vec::new()
+                };
+                Box::pin(self.stream_response(messages, tools, request_options))
+            } else if native_tools {
+                self.provider.generate_with_tools(
+                    messages,
+                    tool_schemas.clone(),
+                    Some(request_options),
                 )
-                .await
-            {
-                Ok(resp) => resp,
+            } else {
+                self.provider.generate(messages, Some(request_options))
+            };
+
+            // Dropping the request on cancellation aborts the HTTP call
+            let cancellation = self.cancellation.clone();
+            let result = tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => None,
+                result = llm_span.instrument(request) => Some(result),
+            };
+            drop(throttle_events);
+            self.emit_provider_switches(switches);
+            let Some(result) = result else {
+                return self.cancelled();
+            };
+            let llm_duration = requested_at.elapsed();
+            let mut response = match result {
+                Ok(resp) => {
+                    llm_span.record_response(&resp);
+                    resp
+                }
                 Err(e) => {
+                    llm_span.record_error(&e.to_string());
                     let error_msg = format!("LLM request failed: {}", e);
                     self.emit_event(AgentEvent::ConversationFailed {
                         error: error_msg.clone(),
@@ -101,16 +1092,54 @@ impl<P: LlmProvider> Agent<P> {
                 }
             };
 
+            if let Some(usage) = &response.usage {
+                self.progress.add_usage(usage);
+            }
+            // Providers that do not report usage send zeros
+            if let Some(usage) = response.usage.as_ref().filter(|u| u.prompt_tokens > 0) {
+                let reported = ContextUsage {
+                    used_tokens: (usage.prompt_tokens + usage.completion_tokens) as usize,
+                    max_tokens: max_context_tokens,
+                };
+                self.update_context_usage(reported, &pressure_thresholds, &mut pressure_reported);
+            }
+
+            response.content = self
+                .options
+                .post_processing
+                .apply(&response.content, generate_options.stop.as_deref());
+
+            let hook_result = self.hooks.after_llm_response(&mut response).await;
+            self.hook_failed(hook_result)?;
+            if !streamed && !response.content.is_empty() {
+                self.stream_event(AgentStreamEvent::TextDelta(response.content.clone()));
+            }
+
             self.emit_event(AgentEvent::LlmResponseReceived {
                 content: response.content.clone(),
                 model: response.model.clone(),
             });
 
-            self.conversation
-                .push(Message::assistant(&response.content));
+            if response.tool_calls.is_empty() {
+                self.conversation
+                    .push(Message::assistant(&response.content));
+            } else {
+                self.conversation.push(Message::assistant_with_tool_calls(
+                    &response.content,
+                    &response.tool_calls,
+                ));
+            }
+            self.progress.trace.iterations.push(IterationTrace {
+                iteration,
+                request: request_summary,
+                response: response.clone(),
+                llm_duration_ms: llm_duration.as_millis() as u64,
+                tool_calls: Vec::new(),
+                added_messages: self.conversation[self.conversation.len() - 1..].to_vec(),
+            });
 
             // 检查是否有工具调用
-            let tool_calls = self.process_tool_calls(&response.content).await?;
+            let tool_calls = self.process_tool_calls(&response, native_tools)?;
 
             if tool_calls.is_empty() {
                 if matches!(self.options.tool_choice, ToolChoice::Required) {
@@ -126,81 +1155,191 @@ impl<P: LlmProvider> Agent<P> {
                 self.emit_event(AgentEvent::ConversationCompleted {
                     response: response.content.clone(),
                 });
+                self.stream_event(AgentStreamEvent::FinalAnswer(response.content.clone()));
                 return Ok(response.content);
             }
 
+            if let Some(max_tokens) = self.options.max_total_tokens {
+                let used_tokens = self.progress.usage.total_tokens as usize;
+                if used_tokens >= max_tokens {
+                    return self.finish_over_budget(used_tokens, max_tokens);
+                }
+            }
+
             self.emit_event(AgentEvent::ToolCallsDetected {
                 calls: tool_calls.clone(),
             });
 
+            let mut tool_calls = tool_calls;
+            let rejected = match self.options.max_tool_calls_per_iteration {
+                Some(max) if tool_calls.len() > max => tool_calls.split_off(max),
+                _ => Vec::new(),
+            };
+
             // 执行工具调用
             let mut results = Vec::new();
-            for call in tool_calls {
-                self.emit_event(AgentEvent::ToolCallStarted { call: call.clone() });
-
-                let result = self.executor.execute_single(&call).await;
-
-                if result.success {
-                    self.emit_event(AgentEvent::ToolCallCompleted {
-                        call: call.clone(),
-                        result: result.clone(),
-                    });
-                } else {
-                    self.emit_event(AgentEvent::ToolCallFailed {
-                        call: call.clone(),
-                        error: result.error.clone().unwrap_or_default(),
-                    });
+            let mut durations = Vec::new();
+            let mut executed_calls = Vec::new();
+            let max_concurrency = match self.options.tool_execution {
+                ToolExecution::Parallel { max_concurrency } if tool_calls.len() > 1 => {
+                    Some(max_concurrency.max(1))
+                }
+                _ => None,
+            };
+            if let Some(max_concurrency) = max_concurrency {
+                for mut call in tool_calls {
+                    self.prepare_tool_call(&mut call).await?;
+                    executed_calls.push(call);
                 }
+                // Permits bound the calls in flight; `join_all` keeps call order
+                let permits = Semaphore::new(max_concurrency);
+                let executions = join_all(executed_calls.iter().map(|call| async {
+                    let _permit = permits.acquire().await;
+                    self.execute_tool_call(call).await
+                }));
+                let outcomes = tokio::select! {
+                    biased;
+                    _ = cancellation.cancelled() => return self.cancelled(),
+                    outcomes = executions => outcomes,
+                };
+                for (call, (result, elapsed)) in executed_calls.iter().zip(outcomes) {
+                    results.push(self.finish_tool_call(call, result, elapsed).await?);
+                    durations.push(elapsed);
+                }
+            } else {
+                for mut call in tool_calls {
+                    self.prepare_tool_call(&mut call).await?;
+                    let (result, elapsed) = tokio::select! {
+                        biased;
+                        _ = cancellation.cancelled() => return self.cancelled(),
+                        outcome = self.execute_tool_call(&call) => outcome,
+                    };
+                    results.push(self.finish_tool_call(&call, result, elapsed).await?);
+                    durations.push(elapsed);
+                    executed_calls.push(call);
+                }
+            }
 
+            let executed = results.len();
+            for call in rejected {
+                let max = self
+                    .options
+                    .max_tool_calls_per_iteration
+                    .unwrap_or_default();
+                let result = ToolResult::failed(
+                    ToolError::permission_denied(format!(
+                        "Not executed: at most {} tool calls are run per response; call {} again in your next response if it is still needed",
+                        max, call.name
+                    ))
+                    .with_retryable(true)
+                    .with_details(serde_json::json!({ "max_tool_calls": max })),
+                );
+                self.emit_event(AgentEvent::ToolCallFailed {
+                    call: call.clone(),
+                    error: result.error.clone().unwrap_or_default(),
+                });
                 results.push(result);
+                durations.push(Duration::ZERO);
+                executed_calls.push(call);
+            }
+
+            if native_tools {
+                self.conversation
+                    .push(Self::tool_results_message(&executed_calls, &results));
+            } else {
+                let observations: Vec<Observation> = executed_calls
+                    .iter()
+                    .zip(&results)
+                    .map(|(call, result)| Observation { call, result })
+                    .collect();
+                let text = self.options.observation_format.format(&observations);
+                self.conversation.push(Message::user(text));
+            }
+            let added = self.conversation[self.conversation.len() - 1].clone();
+            if let Some(trace) = self.progress.trace.iterations.last_mut() {
+                trace.tool_calls = executed_calls
+                    .iter()
+                    .zip(&results)
+                    .zip(&durations)
+                    .map(|((call, result), duration)| ToolCallTrace {
+                        call: call.clone(),
+                        success: result.success,
+                        content: result.content.clone(),
+                        error: result.error.clone(),
+                        duration_ms: duration.as_millis() as u64,
+                    })
+                    .collect();
+                trace.added_messages.push(added);
             }
 
-            let results_text = self.format_tool_results(&results);
-            self.conversation
-                .push(Message::user(&format!("Tool results:\n{}", results_text)));
+            if self.options.stop_on_tool_failure {
+                // Calls rejected by the per-response cap are not failures
+                if let Some(error) = results[..executed].iter().find(|r| !r.success) {
+                    let error_msg = error.error.clone().unwrap_or_default();
+                    self.emit_event(AgentEvent::ConversationFailed {
+                        error: error_msg.clone(),
+                    });
+                    self.progress.finish = Some(FinishReason::ToolFailure);
+                    return Err(AgentError::ToolExecutionFailed(error_msg));
+                }
+            }
         }
 
         let error_msg = "Max iterations reached".to_string();
         self.emit_event(AgentEvent::ConversationFailed {
             error: error_msg.clone(),
         });
+        self.progress.finish = Some(FinishReason::MaxIterations);
         Err(AgentError::ParseError(error_msg))
     }
 
+    /// Stream the answer text
+    ///
+    /// With tools enabled the whole run completes before the answer is sent
+    /// as one chunk; use `run_stream_events` for incremental output.
     pub async fn run_stream(&mut self, input: &str) -> Result<StreamResponse> {
         if !self.tools_enabled() {
+            self.run_metadata = RunMetadata::default();
             self.conversation.clear();
             if let Some(system_prompt) = &self.options.system_prompt {
                 self.conversation.push(Message::system(system_prompt));
             }
             self.conversation.push(Message::user(input));
 
+            let failures = self.hooks.run_start(input).await?;
+            self.emit_hook_failures(failures);
+            let mut messages = self.conversation.clone();
+            let failures = self.hooks.before_llm_request(&mut messages).await?;
+            self.emit_hook_failures(failures);
+
             self.emit_event(AgentEvent::LlmRequestSent {
-                messages: self.conversation.clone(),
+                messages: messages.clone(),
             });
 
-            return self
-                .provider
-                .generate_stream(
-                    self.conversation.clone(),
-                    Some(self.options.generate_options.clone()),
-                )
-                .await
-                .map_err(Into::into);
+            let _throttle_events = self.forward_rate_limit_waits();
+            let switches = self.provider.provider_switches();
+            let cancellation = self.cancellation.clone();
+            let stream = tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return self.cancelled(),
+                stream = self
+                    .provider
+                    .generate_stream(messages, Some(self.options.generate_options.clone())) => stream,
+            };
+            self.emit_provider_switches(switches);
+            return Ok(stream?.cancel_on(cancellation));
         }
 
         // 工具模式仍走 run() 聚合后返回单 chunk
         let result = self.run(input).await?;
 
-        let (tx, rx) = mpsc::channel(1);
-        tokio::spawn(async move {
+        Ok(StreamResponse::spawn(1, |tx| async move {
             let _ = tx.send(Ok(result)).await;
-        });
-
-        Ok(StreamResponse { receiver: rx })
+        }))
     }
 
-    async fn format_tools_description(&self) -> String {
+    /// Registered tools filtered by the tool choice and the allowed tool set
+    async fn offered_tools(&self) -> Vec<ToolInfo> {
         let tools = self.tools.list_tools().await;
         let target_tool = match &self.options.tool_choice {
             ToolChoice::Specific(name) => Some(name.as_str()),
@@ -208,23 +1347,90 @@ impl<P: LlmProvider> Agent<P> {
         };
 
         tools
-            .iter()
+            .into_iter()
             .filter(|tool| target_tool.map(|name| tool.name == name).unwrap_or(true))
-            .map(|tool| format!("- {}: {}", tool.name, tool.description))
+            .filter(|tool| self.options.is_tool_allowed(&tool.name))
+            .collect()
+    }
+
+    /// Names of the offered tools, sorted
+    async fn offered_tool_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .offered_tools()
+            .await
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// System prompt describing the offered tools for text-based tool calling
+    async fn tool_prompt(&self) -> Option<String> {
+        let tools_desc = self.format_tools_description().await;
+        if tools_desc.is_empty() {
+            return None;
+        }
+        let mut prompt = format!(
+            "You have access to the following tools:\n{}\n\nTo use a tool, respond with JSON in this format:\n{{\n  \"tool_calls\": [\n    {{\n      \"id\": \"call_1\",\n      \"name\": \"tool_name\",\n      \"parameters\": {{\n        \"param1\": \"value1\"\n      }}\n    }}\n  ]\n}}",
+            tools_desc
+        );
+        if let Some(instruction) = self.tool_choice_instruction() {
+            prompt.push_str("\n\n");
+            prompt.push_str(&instruction);
+        }
+        Some(prompt)
+    }
+
+    /// Prompt-level stand-in for a native forced tool choice
+    fn tool_choice_instruction(&self) -> Option<String> {
+        match &self.options.tool_choice {
+            ToolChoice::Specific(name) => Some(format!(
+                "You must call the {} tool before giving your final answer.",
+                name
+            )),
+            _ => None,
+        }
+    }
+
+    async fn format_tools_description(&self) -> String {
+        self.offered_tools()
+            .await
+            .iter()
+            .map(|tool| {
+                let description = tool.full_description().replace('\n', "\n  ");
+                format!("- {}: {}", tool.name, description)
+            })
             .collect::<Vec<_>>()
             .join("\n")
     }
 
+    async fn tool_schemas(&self) -> Vec<ToolSchema> {
+        self.offered_tools()
+            .await
+            .into_iter()
+            .map(ToolSchema::from)
+            .collect()
+    }
+
     fn tools_enabled(&self) -> bool {
         !matches!(self.options.tool_choice, ToolChoice::None)
     }
 
-    async fn process_tool_calls(&self, content: &str) -> Result<Vec<crate::tool::ToolCall>> {
+    fn process_tool_calls(
+        &self,
+        response: &GenerateResponse,
+        native_tools: bool,
+    ) -> Result<Vec<ToolCall>> {
         if !self.tools_enabled() {
             return Ok(Vec::new());
         }
 
-        let mut calls = ToolCallParser::extract_from_content(content);
+        let mut calls = if native_tools {
+            response.tool_calls.clone()
+        } else {
+            ToolCallParser::extract_from_content(&response.content)
+        };
         if let ToolChoice::Specific(expected_name) = &self.options.tool_choice {
             if calls.iter().any(|call| call.name != *expected_name) {
                 return Err(AgentError::ParseError(format!(
@@ -239,35 +1445,40 @@ impl<P: LlmProvider> Agent<P> {
         Ok(calls)
     }
 
-    fn format_tool_results(&self, results: &[ToolResult]) -> String {
-        results
-            .iter()
-            .enumerate()
-            .map(|(i, result)| {
-                if result.success {
-                    format!("Result {}: {}", i + 1, result.content)
-                } else {
-                    format!(
-                        "Error {}: {}",
-                        i + 1,
-                        result
-                            .error
-                            .as_ref()
-                            .unwrap_or(&"Unknown error".to_string())
-                    )
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+    fn tool_results_message(calls: &[ToolCall], results: &[ToolResult]) -> Message {
+        Message::tool_results(
+            calls
+                .iter()
+                .zip(results)
+                .map(|(call, result)| {
+                    if result.success {
+                        ContentBlock::tool_result_with_images(
+                            &call.id,
+                            &result.content,
+                            result.images.clone(),
+                        )
+                    } else {
+                        ContentBlock::tool_result(
+                            &call.id,
+                            result.error.as_deref().unwrap_or("Unknown error"),
+                            true,
+                        )
+                    }
+                })
+                .collect(),
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::provider::{GenerateOptions, GenerateResponse, Usage};
+    use crate::provider::{GenerateOptions, GenerateResponse, ProviderCapabilities, Usage};
+    use async_trait::async_trait;
+    use serde_json::Value;
     use std::future::Future;
     use std::pin::Pin;
+    use std::sync::Mutex;
 
     struct MockProvider {
         content: String,
@@ -285,15 +1496,18 @@ mod tests {
         fn generate(
             &self,
             _messages: Vec<Message>,
-            _options: Option<GenerateOptions>,
+            options: Option<GenerateOptions>,
         ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
         {
             Box::pin(async move {
                 Ok(GenerateResponse {
                     content: self.content.clone(),
                     usage: Some(Usage::default()),
-                    model: self.model().to_string(),
+                    model: options
+                        .and_then(|o| o.model)
+                        .unwrap_or_else(|| self.model().to_string()),
                     finish_reason: Some("stop".to_string()),
+                    tool_calls: Vec::new(),
                 })
             })
         }
@@ -305,12 +1519,10 @@ mod tests {
         ) -> Pin<Box<dyn Future<Output = crate::provider::Result<StreamResponse>> + Send + '_>>
         {
             Box::pin(async move {
-                let (tx, rx) = mpsc::channel(2);
                 let content = self.content.clone();
-                tokio::spawn(async move {
+                Ok(StreamResponse::spawn(2, |tx| async move {
                     let _ = tx.send(Ok(content)).await;
-                });
-                Ok(StreamResponse { receiver: rx })
+                }))
             })
         }
 
@@ -321,6 +1533,206 @@ mod tests {
         }
     }
 
+    /// Provider with native tool calling that replays scripted responses
+    struct NativeToolProvider {
+        responses: Mutex<Vec<GenerateResponse>>,
+        requests: Arc<Mutex<Vec<Vec<Message>>>>,
+        options: Arc<Mutex<Vec<GenerateOptions>>>,
+        tool_choice: bool,
+    }
+
+    impl NativeToolProvider {
+        fn new(mut responses: Vec<GenerateResponse>) -> Self {
+            responses.reverse();
+            Self {
+                responses: Mutex::new(responses),
+                requests: Arc::new(Mutex::new(Vec::new())),
+                options: Arc::new(Mutex::new(Vec::new())),
+                tool_choice: false,
+            }
+        }
+
+        fn with_tool_choice(mut self) -> Self {
+            self.tool_choice = true;
+            self
+        }
+    }
+
+    impl LlmProvider for NativeToolProvider {
+        fn name(&self) -> &str {
+            "native-mock"
+        }
+
+        fn model(&self) -> &str {
+            "native-mock-model"
+        }
+
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                native_tools: true,
+                tool_choice: self.tool_choice,
+                ..Default::default()
+            }
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            Box::pin(async { panic!("native provider should not use text generation") })
+        }
+
+        fn generate_with_tools(
+            &self,
+            messages: Vec<Message>,
+            _tools: Vec<ToolSchema>,
+            options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            self.requests.lock().unwrap().push(messages);
+            self.options
+                .lock()
+                .unwrap()
+                .push(options.unwrap_or_default());
+            let response = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop()
+                .expect("script exhausted");
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    /// Text-only provider that replays scripted replies and records requests
+    struct ReplyProvider {
+        replies: Mutex<Vec<&'static str>>,
+        requests: Arc<Mutex<Vec<Vec<Message>>>>,
+    }
+
+    impl LlmProvider for ReplyProvider {
+        fn name(&self) -> &str {
+            "reply-mock"
+        }
+
+        fn model(&self) -> &str {
+            "reply-mock-model"
+        }
+
+        fn generate(
+            &self,
+            messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            self.requests.lock().unwrap().push(messages);
+            let content = self.replies.lock().unwrap().remove(0);
+            Box::pin(async move { Ok(scripted_response(content, Vec::new())) })
+        }
+    }
+
+    fn scripted_response(content: &str, tool_calls: Vec<ToolCall>) -> GenerateResponse {
+        GenerateResponse {
+            content: content.to_string(),
+            usage: None,
+            model: "native-mock-model".to_string(),
+            finish_reason: None,
+            tool_calls,
+        }
+    }
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echoes the text parameter"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {"text": {"type": "string"}},
+                "required": ["text"]
+            })
+        }
+
+        async fn execute(&self, params: &Value) -> ToolResult {
+            ToolResult::success(params["text"].as_str().unwrap_or_default())
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_cache_reuses_results_of_identical_calls() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingTool(Arc<AtomicUsize>);
+
+        #[async_trait]
+        impl Tool for CountingTool {
+            fn name(&self) -> &str {
+                "search"
+            }
+
+            fn description(&self) -> &str {
+                "Counts its executions"
+            }
+
+            fn parameters_schema(&self) -> Value {
+                serde_json::json!({"type": "object"})
+            }
+
+            async fn execute(&self, _params: &Value) -> ToolResult {
+                let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+                ToolResult::success(format!("execution {}", n))
+            }
+        }
+
+        let search = |id: &str, parameters: Value| ToolCall {
+            id: id.to_string(),
+            name: "search".to_string(),
+            parameters,
+        };
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![search("call_1", serde_json::json!({"q": "a", "n": 1}))],
+            ),
+            scripted_response(
+                "",
+                vec![search("call_2", serde_json::json!({"n": 1, "q": "a"}))],
+            ),
+            scripted_response("", vec![search("call_3", serde_json::json!({"q": "b"}))]),
+            scripted_response("done", vec![]),
+        ]);
+        let requests = provider.requests.clone();
+        let executions = Arc::new(AtomicUsize::new(0));
+        let cache = ToolResultCache::new().cache_tool("search", Duration::from_secs(60));
+        let mut agent = Agent::new(provider).with_tool_cache(cache.clone());
+        agent
+            .register_tool(Box::new(CountingTool(executions.clone())))
+            .await;
+
+        agent.run("search twice").await.unwrap();
+        assert_eq!(executions.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats().hits, 1);
+
+        let requests = requests.lock().unwrap();
+        let result_of = |request: &Vec<Message>| match &request.last().unwrap().content[0] {
+            ContentBlock::ToolResult { content, .. } => content.clone(),
+            other => panic!("expected tool result block, got {:?}", other),
+        };
+        assert_eq!(result_of(&requests[1]), "execution 1");
+        assert_eq!(result_of(&requests[2]), "execution 1");
+        assert_eq!(result_of(&requests[3]), "execution 2");
+    }
+
     #[tokio::test]
     async fn tool_choice_none_ignores_tool_call_payload() {
         let provider = MockProvider {
@@ -394,4 +1806,1516 @@ mod tests {
             .expect("chunk should be ok");
         assert_eq!(chunk, "streamed content");
     }
+
+    #[tokio::test]
+    async fn run_with_overrides_uses_override_model_and_emits_event() {
+        let provider = MockProvider {
+            content: "answer".to_string(),
+        };
+        let event_bus = Arc::new(EventBus::new(16));
+        let mut receiver = event_bus.subscribe();
+
+        let mut agent = Agent::new(provider)
+            .with_options(AgentOptions {
+                tool_choice: ToolChoice::None,
+                ..Default::default()
+            })
+            .with_event_bus(event_bus);
+
+        let overrides = RunOverrides {
+            model: Some("bigger-model".to_string()),
+            temperature: Some(0.1),
+            ..Default::default()
+        };
+        agent
+            .run_with_overrides("hi", overrides.clone())
+            .await
+            .expect("run should succeed");
+
+        let mut applied = None;
+        let mut response_model = None;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                AgentEvent::RunOverridesApplied { overrides } => applied = Some(overrides),
+                AgentEvent::LlmResponseReceived { model, .. } => response_model = Some(model),
+                _ => {}
+            }
+        }
+
+        assert_eq!(applied, Some(overrides));
+        assert_eq!(response_model.as_deref(), Some("bigger-model"));
+    }
+
+    #[tokio::test]
+    async fn run_metadata_is_attached_to_every_event() {
+        let provider = MockProvider {
+            content: "answer".to_string(),
+        };
+        let event_bus = Arc::new(EventBus::new(16));
+        let mut receiver = event_bus.subscribe_with_metadata();
+        let mut agent = Agent::new(provider)
+            .with_options(AgentOptions {
+                tool_choice: ToolChoice::None,
+                ..Default::default()
+            })
+            .with_event_bus(event_bus);
+
+        let overrides = RunOverrides::default()
+            .tag("ticket", "T-42")
+            .tag("experiment", "b");
+        agent.run_with_overrides("hi", overrides).await.unwrap();
+        assert_eq!(agent.run_metadata()["ticket"], "T-42");
+
+        let mut events = 0;
+        while let Ok(tagged) = receiver.try_recv() {
+            events += 1;
+            assert_eq!(
+                tagged.metadata.get("ticket").map(String::as_str),
+                Some("T-42")
+            );
+            assert_eq!(
+                tagged.metadata.get("experiment").map(String::as_str),
+                Some("b")
+            );
+        }
+        assert!(events >= 3);
+
+        agent.run("again").await.unwrap();
+        assert!(agent.run_metadata().is_empty());
+    }
+
+    #[tokio::test]
+    async fn native_tool_calls_round_trip_as_content_blocks() {
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![ToolCall {
+                    id: "call_42".to_string(),
+                    name: "echo".to_string(),
+                    parameters: serde_json::json!({"text": "pong"}),
+                }],
+            ),
+            scripted_response("done", Vec::new()),
+        ]);
+        let requests = provider.requests.clone();
+
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let result = agent.run("ping").await.expect("run should succeed");
+        assert_eq!(result, "done");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        // No prompt-engineered tool description in native mode
+        assert!(requests[0]
+            .iter()
+            .all(|m| m.role != crate::provider::Role::System));
+
+        let second = &requests[1];
+        assert_eq!(second[1].tool_calls()[0].id, "call_42");
+        match &second[2].content[0] {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+                ..
+            } => {
+                assert_eq!(tool_use_id, "call_42");
+                assert_eq!(content, "pong");
+                assert!(!is_error);
+            }
+            other => panic!("expected tool result block, got {:?}", other),
+        }
+    }
+
+    /// Routes runs to a tenant's model and stops them once they use
+    /// `budget` tokens
+    struct TenantMiddleware {
+        budget: u32,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl RunMiddleware for TenantMiddleware {
+        async fn before_run(&self, run: &mut RunRequest) -> Result<()> {
+            run.overrides.model = Some("tenant-model".to_string());
+            run.overrides
+                .metadata
+                .insert("tenant".into(), "acme".into());
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("start {}", run.input));
+            Ok(())
+        }
+
+        async fn before_iteration(&self, ctx: &IterationContext) -> Result<()> {
+            self.log.lock().unwrap().push(format!(
+                "iteration {} for {}",
+                ctx.iteration, ctx.metadata["tenant"]
+            ));
+            if ctx.usage.total_tokens >= self.budget {
+                return Err(AgentError::InvalidParameters("over budget".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn after_run(&self, _run: &RunRequest, result: &mut Result<String>) {
+            if result.is_err() {
+                *result = Ok("Sorry, this request used up the budget".to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_wraps_the_run() {
+        let mut call = scripted_response(
+            "",
+            vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "echo".to_string(),
+                parameters: serde_json::json!({"text": "pong"}),
+            }],
+        );
+        call.usage = Some(Usage {
+            prompt_tokens: 80,
+            completion_tokens: 20,
+            total_tokens: 100,
+        });
+        let provider = NativeToolProvider::new(vec![call, scripted_response("done", Vec::new())]);
+        let options = provider.options.clone();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut agent = Agent::new(provider).with_middleware(Arc::new(TenantMiddleware {
+            budget: 50,
+            log: log.clone(),
+        }));
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let result = agent.run("ping").await.unwrap();
+        assert_eq!(result, "Sorry, this request used up the budget");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["start ping", "iteration 0 for acme", "iteration 1 for acme"]
+        );
+        let options = options.lock().unwrap();
+        assert_eq!(options.len(), 1);
+        assert_eq!(options[0].model.as_deref(), Some("tenant-model"));
+        assert_eq!(agent.run_metadata()["tenant"], "acme");
+    }
+
+    #[tokio::test]
+    async fn run_trace_records_each_iteration() {
+        let mut call = scripted_response(
+            "Let me echo",
+            vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "echo".to_string(),
+                parameters: serde_json::json!({"text": "pong"}),
+            }],
+        );
+        call.usage = Some(Usage {
+            prompt_tokens: 30,
+            completion_tokens: 5,
+            total_tokens: 35,
+        });
+        let provider = NativeToolProvider::new(vec![call, scripted_response("done", Vec::new())]);
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(EchoTool)).await;
+        agent.run("ping").await.unwrap();
+
+        let trace = agent.run_trace();
+        assert_eq!(trace.iterations.len(), 2);
+        let first = &trace.iterations[0];
+        assert_eq!(first.request.model, "native-mock-model");
+        assert_eq!(first.request.messages, 1);
+        assert_eq!(first.request.tools, ["echo"]);
+        assert!(first.request.estimated_tokens > 0);
+        assert_eq!(first.response.content, "Let me echo");
+        assert_eq!(first.tool_calls.len(), 1);
+        assert!(first.tool_calls[0].success);
+        assert_eq!(first.tool_calls[0].content, "pong");
+        assert_eq!(first.added_messages.len(), 2);
+        assert!(matches!(
+            &first.added_messages[1].content[0],
+            ContentBlock::ToolResult { content, .. } if content == "pong"
+        ));
+
+        let last = &trace.iterations[1];
+        assert_eq!(last.request.messages, 3);
+        assert!(last.tool_calls.is_empty());
+        assert_eq!(last.added_messages[0].content_as_text(), "done");
+        assert_eq!(trace.usage().total_tokens, 35);
+        assert_eq!(trace.tool_calls().count(), 1);
+        let json = serde_json::to_value(trace).unwrap();
+        assert_eq!(
+            json["iterations"][0]["tool_calls"][0]["call"]["name"],
+            "echo"
+        );
+    }
+
+    #[tokio::test]
+    async fn deprecated_tool_alias_runs_the_renamed_tool() {
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "say".to_string(),
+                    parameters: serde_json::json!({"text": "pong"}),
+                }],
+            ),
+            scripted_response("done", Vec::new()),
+        ]);
+        let event_bus = Arc::new(EventBus::new(16));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(provider).with_event_bus(event_bus);
+        agent.register_tool(Box::new(EchoTool)).await;
+        agent.register_tool_alias("say", "echo").await;
+
+        agent.run("ping").await.expect("run should succeed");
+
+        let mut deprecated = None;
+        let mut completed = None;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                AgentEvent::DeprecatedToolAlias { alias, tool } => deprecated = Some((alias, tool)),
+                AgentEvent::ToolCallCompleted { call, result } => {
+                    completed = Some((call.name, result.content))
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(deprecated, Some(("say".to_string(), "echo".to_string())));
+        assert_eq!(completed, Some(("echo".to_string(), "pong".to_string())));
+    }
+
+    /// Provider whose requests never complete
+    struct HangingProvider;
+
+    impl LlmProvider for HangingProvider {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        fn model(&self) -> &str {
+            "hanging-model"
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_aborts_an_in_flight_request() {
+        let mut agent = Agent::new(HangingProvider).with_options(AgentOptions {
+            tool_choice: ToolChoice::None,
+            ..Default::default()
+        });
+        let token = agent.cancellation_token();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        });
+
+        let result = tokio::time::timeout(Duration::from_secs(5), agent.run("hi"))
+            .await
+            .expect("cancel should stop the run promptly");
+        assert!(matches!(result, Err(AgentError::Cancelled)));
+        // Later runs stay cancelled until the token is replaced
+        assert!(matches!(agent.run("hi").await, Err(AgentError::Cancelled)));
+        agent.reset_cancellation();
+        assert!(!agent.cancellation_token().is_cancelled());
+    }
+
+    struct SlowTool;
+
+    #[async_trait]
+    impl Tool for SlowTool {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn description(&self) -> &str {
+            "Takes a minute"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            ToolResult::success("finally")
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_a_running_tool() {
+        let provider = NativeToolProvider::new(vec![scripted_response(
+            "",
+            vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "slow".to_string(),
+                parameters: serde_json::json!({}),
+            }],
+        )]);
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(SlowTool)).await;
+        let token = agent.cancellation_token();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        });
+
+        let result = tokio::time::timeout(Duration::from_secs(5), agent.run("go"))
+            .await
+            .expect("cancel should stop the tool promptly");
+        assert!(matches!(result, Err(AgentError::Cancelled)));
+    }
+
+    /// Streaming provider with native tools that replays scripted events
+    struct StreamingProvider {
+        turns: Mutex<Vec<Vec<StreamEvent>>>,
+    }
+
+    impl LlmProvider for StreamingProvider {
+        fn name(&self) -> &str {
+            "streaming-mock"
+        }
+
+        fn model(&self) -> &str {
+            "streaming-mock-model"
+        }
+
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                streaming: true,
+                native_tools: true,
+                ..Default::default()
+            }
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            Box::pin(async { panic!("streaming runs should not use generate") })
+        }
+
+        fn generate_stream_events(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolSchema>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<
+            Box<
+                dyn Future<Output = crate::provider::Result<crate::provider::StreamEvents>>
+                    + Send
+                    + '_,
+            >,
+        > {
+            let events = self.turns.lock().unwrap().remove(0);
+            Box::pin(async move {
+                let (tx, rx) = mpsc::channel(16);
+                for event in events {
+                    tx.send(Ok(event)).await.unwrap();
+                }
+                Ok(crate::provider::StreamEvents::new(rx))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn run_stream_events_streams_text_and_tool_calls() {
+        use futures_util::StreamExt;
+
+        let provider = StreamingProvider {
+            turns: Mutex::new(vec![
+                vec![
+                    StreamEvent::TextDelta("Checking".to_string()),
+                    StreamEvent::ToolCallDelta {
+                        index: 0,
+                        id: Some("call_1".to_string()),
+                        name: Some("echo".to_string()),
+                        arguments_delta: "{\"text\":".to_string(),
+                    },
+                    StreamEvent::ToolCallDelta {
+                        index: 0,
+                        id: None,
+                        name: None,
+                        arguments_delta: " \"pong\"}".to_string(),
+                    },
+                    StreamEvent::Done {
+                        finish_reason: Some("tool_use".to_string()),
+                    },
+                ],
+                vec![
+                    StreamEvent::TextDelta("It said ".to_string()),
+                    StreamEvent::TextDelta("pong".to_string()),
+                    StreamEvent::Done {
+                        finish_reason: None,
+                    },
+                ],
+            ]),
+        };
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let events: Vec<AgentStreamEvent> = agent
+            .run_stream_events("ping")
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        let summary: Vec<String> = events
+            .iter()
+            .map(|event| match event {
+                AgentStreamEvent::TextDelta(text) => format!("text:{}", text),
+                AgentStreamEvent::ToolCallStarted(call) => format!("start:{}", call.name),
+                AgentStreamEvent::ToolCallFinished { result, .. } => {
+                    format!("finish:{}", result.content)
+                }
+                AgentStreamEvent::FinalAnswer(text) => format!("final:{}", text),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "text:Checking",
+                "start:echo",
+                "finish:pong",
+                "text:It said ",
+                "text:pong",
+                "final:It said pong"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_stream_events_ends_with_the_run_error() {
+        use futures_util::StreamExt;
+
+        let mut agent = Agent::new(HangingProvider).with_options(AgentOptions {
+            tool_choice: ToolChoice::None,
+            run_timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        let mut stream = agent.run_stream_events("hi");
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(AgentError::Timeout(_)))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    /// Provider with a ten-token context window that reports usage
+    struct SmallWindowProvider;
+
+    impl LlmProvider for SmallWindowProvider {
+        fn name(&self) -> &str {
+            "small-window"
+        }
+
+        fn model(&self) -> &str {
+            "small-window-model"
+        }
+
+        fn context_window(&self) -> Option<usize> {
+            Some(10)
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            let mut response = scripted_response("ok", Vec::new());
+            response.usage = Some(Usage {
+                prompt_tokens: 9,
+                completion_tokens: 1,
+                total_tokens: 10,
+            });
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    #[tokio::test]
+    async fn context_gauge_tracks_usage_and_reports_pressure_once() {
+        let event_bus = Arc::new(EventBus::new(16));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(SmallWindowProvider)
+            .with_options(AgentOptions {
+                tool_choice: ToolChoice::None,
+                context_pressure_thresholds: vec![0.95, 0.5, 0.8],
+                ..Default::default()
+            })
+            .with_event_bus(event_bus);
+        let gauge = agent.context_gauge();
+
+        agent.run("hello there").await.unwrap();
+
+        let usage = gauge.usage();
+        assert_eq!((usage.used_tokens, usage.max_tokens), (10, Some(10)));
+        assert_eq!(usage.remaining_tokens(), Some(0));
+        let mut thresholds = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let AgentEvent::ContextPressure { threshold, .. } = event {
+                thresholds.push(threshold);
+            }
+        }
+        assert_eq!(thresholds, [0.5, 0.8, 0.95]);
+    }
+
+    /// Pauses its agent when called
+    struct PauseTool {
+        handle: PauseHandle,
+    }
+
+    #[async_trait]
+    impl Tool for PauseTool {
+        fn name(&self) -> &str {
+            "pause"
+        }
+
+        fn description(&self) -> &str {
+            "Pauses the run"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            self.handle.pause();
+            ToolResult::success("paused")
+        }
+    }
+
+    #[tokio::test]
+    async fn pause_defers_remaining_tool_calls_until_resume() {
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![
+                    ToolCall {
+                        id: "call_1".to_string(),
+                        name: "pause".to_string(),
+                        parameters: serde_json::json!({}),
+                    },
+                    ToolCall {
+                        id: "call_2".to_string(),
+                        name: "echo".to_string(),
+                        parameters: serde_json::json!({"text": "later"}),
+                    },
+                ],
+            ),
+            scripted_response("done", Vec::new()),
+        ]);
+        let event_bus = Arc::new(EventBus::new(64));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(provider).with_event_bus(event_bus);
+        let handle = agent.pause_handle();
+        agent
+            .register_tool(Box::new(PauseTool {
+                handle: handle.clone(),
+            }))
+            .await;
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let run = tokio::spawn(async move { agent.run("go").await });
+        let mut started = Vec::new();
+        loop {
+            match receiver.recv().await.unwrap() {
+                AgentEvent::ToolCallStarted { call } => started.push(call.name),
+                AgentEvent::RunPaused => break,
+                _ => {}
+            }
+        }
+        assert_eq!(started, ["pause"]);
+
+        handle.resume();
+        assert_eq!(run.await.unwrap().unwrap(), "done");
+        let mut after_resume = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                AgentEvent::RunResumed => after_resume.push("resumed".to_string()),
+                AgentEvent::ToolCallStarted { call } => after_resume.push(call.name),
+                _ => {}
+            }
+        }
+        assert_eq!(after_resume, ["resumed", "echo"]);
+    }
+
+    #[tokio::test]
+    async fn run_timeout_fails_a_stuck_run() {
+        let event_bus = Arc::new(EventBus::new(16));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(HangingProvider)
+            .with_options(AgentOptions {
+                tool_choice: ToolChoice::None,
+                run_timeout: Some(Duration::from_millis(20)),
+                ..Default::default()
+            })
+            .with_event_bus(event_bus);
+
+        let result = agent.run("hi").await;
+        assert!(matches!(result, Err(AgentError::Timeout(_))));
+
+        let mut timeout = None;
+        while let Ok(event) = receiver.try_recv() {
+            if let AgentEvent::Timeout { tool, limit_ms } = event {
+                timeout = Some((tool, limit_ms));
+            }
+        }
+        assert_eq!(timeout, Some((None, 20)));
+
+        let outcome = agent.run_outcome("hi").await.unwrap();
+        assert_eq!(outcome.finish_reason, FinishReason::Timeout);
+    }
+
+    #[tokio::test]
+    async fn tool_timeout_returns_a_timeout_error_to_the_model() {
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "slow".to_string(),
+                    parameters: serde_json::json!({}),
+                }],
+            ),
+            scripted_response("gave up", Vec::new()),
+        ]);
+        let requests = provider.requests.clone();
+        let event_bus = Arc::new(EventBus::new(16));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(provider)
+            .with_options(AgentOptions {
+                tool_timeout: Some(Duration::from_millis(20)),
+                ..Default::default()
+            })
+            .with_event_bus(event_bus);
+        agent.register_tool(Box::new(SlowTool)).await;
+
+        assert_eq!(agent.run("go").await.unwrap(), "gave up");
+
+        let requests = requests.lock().unwrap();
+        match &requests[1][2].content[0] {
+            ContentBlock::ToolResult {
+                content, is_error, ..
+            } => {
+                assert!(is_error);
+                assert!(content.contains("timed out"));
+            }
+            other => panic!("expected tool result block, got {:?}", other),
+        }
+        let mut timeout = None;
+        while let Ok(event) = receiver.try_recv() {
+            if let AgentEvent::Timeout { tool, limit_ms } = event {
+                timeout = Some((tool, limit_ms));
+            }
+        }
+        assert_eq!(timeout, Some((Some("slow".to_string()), 20)));
+    }
+
+    /// Sleeps for `ms` milliseconds and returns its `label`
+    struct NapTool;
+
+    #[async_trait]
+    impl Tool for NapTool {
+        fn name(&self) -> &str {
+            "nap"
+        }
+
+        fn description(&self) -> &str {
+            "Sleeps, then returns the label"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {"ms": {"type": "number"}, "label": {"type": "string"}},
+                "required": ["ms", "label"]
+            })
+        }
+
+        async fn execute(&self, params: &Value) -> ToolResult {
+            let ms = params["ms"].as_u64().unwrap_or_default();
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            ToolResult::success(params["label"].as_str().unwrap_or_default())
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_execution_keeps_call_order() {
+        let nap = |id: &str, ms: u64| ToolCall {
+            id: id.to_string(),
+            name: "nap".to_string(),
+            parameters: serde_json::json!({"ms": ms, "label": id}),
+        };
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![nap("call_1", 300), nap("call_2", 100), nap("call_3", 100)],
+            ),
+            scripted_response("rested", Vec::new()),
+        ]);
+        let requests = provider.requests.clone();
+        let event_bus = Arc::new(EventBus::new(32));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(provider)
+            .with_options(AgentOptions {
+                tool_execution: ToolExecution::Parallel { max_concurrency: 2 },
+                ..Default::default()
+            })
+            .with_event_bus(event_bus);
+        agent.register_tool(Box::new(NapTool)).await;
+
+        let started = Instant::now();
+        assert_eq!(agent.run("nap").await.unwrap(), "rested");
+        // call_2 and call_3 run one after another alongside call_1
+        assert!(started.elapsed() < Duration::from_millis(450));
+
+        let requests = requests.lock().unwrap();
+        let ids: Vec<&str> = requests[1][2]
+            .content
+            .iter()
+            .map(|block| match block {
+                ContentBlock::ToolResult { tool_use_id, .. } => tool_use_id.as_str(),
+                other => panic!("expected tool result block, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(ids, ["call_1", "call_2", "call_3"]);
+
+        let mut timed = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let AgentEvent::ToolCallTimed {
+                call_id,
+                duration_ms,
+                ..
+            } = event
+            {
+                timed.push((call_id, duration_ms));
+            }
+        }
+        assert_eq!(timed.len(), 3);
+        assert_eq!(timed[0].0, "call_1");
+        assert!(timed[0].1 >= 300);
+    }
+
+    #[tokio::test]
+    async fn tool_choice_specific_forces_the_first_request() {
+        let script = || {
+            vec![
+                scripted_response(
+                    "",
+                    vec![ToolCall {
+                        id: "call_1".to_string(),
+                        name: "echo".to_string(),
+                        parameters: serde_json::json!({"text": "hi"}),
+                    }],
+                ),
+                scripted_response("done", Vec::new()),
+            ]
+        };
+        let options = || AgentOptions {
+            tool_choice: ToolChoice::Specific("echo".to_string()),
+            ..Default::default()
+        };
+
+        let provider = NativeToolProvider::new(script()).with_tool_choice();
+        let sent = provider.options.clone();
+        let mut agent = Agent::new(provider).with_options(options());
+        agent.register_tool(Box::new(EchoTool)).await;
+        assert_eq!(agent.run("go").await.unwrap(), "done");
+        let tool_choices: Vec<_> = sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|o| o.tool_choice.clone())
+            .collect();
+        assert_eq!(
+            tool_choices,
+            [Some(ToolSelection::Tool("echo".to_string())), None]
+        );
+
+        // Without native support the constraint is stated in the prompt
+        let provider = NativeToolProvider::new(script());
+        let requests = provider.requests.clone();
+        let mut agent = Agent::new(provider).with_options(options());
+        agent.register_tool(Box::new(EchoTool)).await;
+        assert_eq!(agent.run("go").await.unwrap(), "done");
+        assert!(requests.lock().unwrap()[0][0]
+            .content_as_text()
+            .contains("You must call the echo tool"));
+    }
+
+    #[tokio::test]
+    async fn tool_calls_over_the_cap_are_rejected() {
+        let echo = |id: &str| ToolCall {
+            id: id.to_string(),
+            name: "echo".to_string(),
+            parameters: serde_json::json!({"text": id}),
+        };
+        let provider = NativeToolProvider::new(vec![
+            scripted_response("", vec![echo("call_1"), echo("call_2"), echo("call_3")]),
+            scripted_response("done", Vec::new()),
+        ]);
+        let requests = provider.requests.clone();
+        let sent = provider.options.clone();
+        let mut agent = Agent::new(provider).with_options(AgentOptions {
+            parallel_tool_calls: false,
+            max_tool_calls_per_iteration: Some(1),
+            stop_on_tool_failure: true,
+            ..Default::default()
+        });
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        assert_eq!(agent.run("go").await.unwrap(), "done");
+        assert_eq!(sent.lock().unwrap()[0].parallel_tool_calls, Some(false));
+
+        let requests = requests.lock().unwrap();
+        let results: Vec<(&str, bool)> = requests[1][2]
+            .content
+            .iter()
+            .map(|block| match block {
+                ContentBlock::ToolResult {
+                    content, is_error, ..
+                } => (content.as_str(), *is_error),
+                other => panic!("expected tool result block, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(results[0], ("call_1", false));
+        assert!(results[1].1 && results[1].0.contains("at most 1 tool calls"));
+        assert!(results[2].1);
+    }
+
+    #[tokio::test]
+    async fn run_outcome_reports_why_the_run_stopped() {
+        let echo = |id: &str| ToolCall {
+            id: id.to_string(),
+            name: "echo".to_string(),
+            parameters: serde_json::json!({"text": "hi"}),
+        };
+        let mut first = scripted_response("thinking", vec![echo("call_1")]);
+        first.usage = Some(Usage {
+            prompt_tokens: 10,
+            completion_tokens: 2,
+            total_tokens: 12,
+        });
+        let mut second = scripted_response("still thinking", vec![echo("call_2")]);
+        second.usage = first.usage.clone();
+        let mut agent =
+            Agent::new(NativeToolProvider::new(vec![first, second])).with_options(AgentOptions {
+                max_iterations: 2,
+                ..Default::default()
+            });
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let outcome = agent.run_outcome("go").await.unwrap();
+        assert_eq!(outcome.finish_reason, FinishReason::MaxIterations);
+        assert_eq!(outcome.text(), "still thinking");
+        assert_eq!(outcome.iterations, 2);
+        assert_eq!(outcome.usage.total_tokens, 24);
+
+        let missing = ToolCall {
+            id: "call_1".to_string(),
+            name: "missing".to_string(),
+            parameters: serde_json::json!({}),
+        };
+        let provider = NativeToolProvider::new(vec![
+            scripted_response("", vec![missing.clone()]),
+            scripted_response("", vec![missing]),
+        ]);
+        let mut agent = Agent::new(provider).with_options(AgentOptions {
+            stop_on_tool_failure: true,
+            ..Default::default()
+        });
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let outcome = agent.run_outcome("go").await.unwrap();
+        assert_eq!(outcome.finish_reason, FinishReason::ToolFailure);
+        assert_eq!(outcome.iterations, 1);
+        assert!(matches!(
+            agent.run("go").await,
+            Err(AgentError::ToolExecutionFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn token_budget_ends_the_run_with_the_best_answer_so_far() {
+        let echo = ToolCall {
+            id: "call_1".to_string(),
+            name: "echo".to_string(),
+            parameters: serde_json::json!({"text": "hi"}),
+        };
+        let mut first = scripted_response("draft answer", vec![echo]);
+        first.usage = Some(Usage {
+            prompt_tokens: 450,
+            completion_tokens: 50,
+            total_tokens: 500,
+        });
+        let provider = NativeToolProvider::new(vec![first, scripted_response("final", vec![])]);
+        let requests = provider.requests.clone();
+        let recorder = crate::testing::EventRecorder::new();
+        let mut agent = Agent::new(provider)
+            .with_options(AgentOptions {
+                max_total_tokens: Some(500),
+                ..Default::default()
+            })
+            .with_event_bus(recorder.bus());
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        let outcome = agent.run_outcome("go").await.unwrap();
+        assert_eq!(outcome.finish_reason, FinishReason::Budget);
+        assert_eq!(outcome.text(), "draft answer");
+        assert_eq!(outcome.iterations, 1);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        recorder.assert_emitted(crate::events::EventKind::BudgetExceeded);
+        recorder.assert_not_emitted(crate::events::EventKind::ToolCallStarted);
+        assert!(recorder.events().iter().any(|e| matches!(
+            e,
+            AgentEvent::BudgetExceeded {
+                used_tokens: 500,
+                max_tokens: 500
+            }
+        )));
+
+        // The first prompt alone is estimated above the budget
+        let provider = NativeToolProvider::new(vec![scripted_response("unused", vec![])]);
+        let requests = provider.requests.clone();
+        let mut agent = Agent::new(provider).with_options(AgentOptions {
+            max_total_tokens: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(agent.run("hello there").await.unwrap(), "");
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    /// Rewrites tool arguments and results and records the run outcome
+    struct RewritingHook {
+        ended: Arc<Mutex<Option<String>>>,
+    }
+
+    #[async_trait]
+    impl crate::hooks::AgentHook for RewritingHook {
+        async fn before_tool_call(&self, call: &mut ToolCall) -> Result<crate::hooks::HookOutcome> {
+            call.parameters = serde_json::json!({"text": "rewritten"});
+            call.id = "hijacked".to_string();
+            Ok(crate::hooks::HookOutcome::Continue)
+        }
+
+        async fn after_tool_call(
+            &self,
+            _call: &ToolCall,
+            result: &mut ToolResult,
+        ) -> Result<crate::hooks::HookOutcome> {
+            result.content = result.content.to_uppercase();
+            Ok(crate::hooks::HookOutcome::Continue)
+        }
+
+        async fn after_llm_response(
+            &self,
+            response: &mut GenerateResponse,
+        ) -> Result<crate::hooks::HookOutcome> {
+            if response.content == "forbidden" {
+                return Ok(crate::hooks::HookOutcome::Abort("blocked".to_string()));
+            }
+            Ok(crate::hooks::HookOutcome::Continue)
+        }
+
+        async fn on_run_end(&self, result: std::result::Result<&str, &AgentError>) {
+            *self.ended.lock().unwrap() = Some(match result {
+                Ok(text) => text.to_string(),
+                Err(e) => e.to_string(),
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn hooks_rewrite_tool_calls_and_observe_the_run() {
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "echo".to_string(),
+                    parameters: serde_json::json!({"text": "original"}),
+                }],
+            ),
+            scripted_response("done", Vec::new()),
+        ]);
+        let requests = provider.requests.clone();
+        let ended = Arc::new(Mutex::new(None));
+
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(EchoTool)).await;
+        agent.add_hook(Arc::new(RewritingHook {
+            ended: ended.clone(),
+        }));
+
+        assert_eq!(agent.run("ping").await.unwrap(), "done");
+        assert_eq!(ended.lock().unwrap().as_deref(), Some("done"));
+
+        let requests = requests.lock().unwrap();
+        match &requests[1][2].content[0] {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                ..
+            } => {
+                assert_eq!(tool_use_id, "call_1");
+                assert_eq!(content, "REWRITTEN");
+            }
+            other => panic!("expected tool result block, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn hook_abort_fails_the_run() {
+        let provider = MockProvider {
+            content: "forbidden".to_string(),
+        };
+        let ended = Arc::new(Mutex::new(None));
+
+        let mut agent = Agent::new(provider);
+        agent.add_hook(Arc::new(RewritingHook {
+            ended: ended.clone(),
+        }));
+
+        let err = agent.run("hi").await.expect_err("hook should abort");
+        assert!(matches!(err, AgentError::HookAborted { ref reason, .. } if reason == "blocked"));
+        assert!(ended
+            .lock()
+            .unwrap()
+            .as_deref()
+            .unwrap()
+            .contains("blocked"));
+    }
+
+    /// Provider whose requests always go through a saturated rate limiter
+    struct ThrottledProvider {
+        limiter: crate::provider::RateLimiter,
+    }
+
+    impl LlmProvider for ThrottledProvider {
+        fn name(&self) -> &str {
+            "throttled"
+        }
+
+        fn model(&self) -> &str {
+            "throttled-model"
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            Box::pin(async move {
+                let _guard = self.limiter.acquire().await;
+                Err(crate::provider::ProviderError::Other(
+                    "unreachable".to_string(),
+                ))
+            })
+        }
+
+        fn rate_limiter(&self) -> Option<&crate::provider::RateLimiter> {
+            Some(&self.limiter)
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limit_waits_are_published_as_events() {
+        let limiter =
+            crate::provider::RateLimiter::new(crate::provider::RateLimitConfig::new(1, 1));
+        drop(limiter.acquire().await);
+
+        let bus = Arc::new(EventBus::new(16));
+        let mut events = bus.subscribe();
+        let mut agent = Agent::new(ThrottledProvider { limiter }).with_event_bus(bus);
+        assert!(agent.rate_limit_wait_estimate().await.unwrap() > Duration::from_secs(59));
+
+        let run = tokio::spawn(async move { agent.run("hi").await });
+        loop {
+            if let AgentEvent::RateLimitWait { provider, wait_ms } = events.recv().await.unwrap() {
+                assert_eq!(provider, "throttled");
+                assert!(wait_ms > 59_000);
+                break;
+            }
+        }
+        run.abort();
+    }
+
+    #[tokio::test]
+    async fn run_structured_repairs_answers_that_do_not_match() {
+        #[derive(serde::Deserialize)]
+        struct Answer {
+            value: i64,
+        }
+
+        let provider = ReplyProvider {
+            replies: Mutex::new(vec!["about forty-two", "{\"value\": 42}"]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let requests = provider.requests.clone();
+        let mut agent = Agent::new(provider).with_options(AgentOptions {
+            tool_choice: ToolChoice::None,
+            ..Default::default()
+        });
+        let schema = JsonSchema::new(
+            "answer",
+            serde_json::json!({
+                "type": "object",
+                "properties": {"value": {"type": "integer"}},
+                "required": ["value"]
+            }),
+        );
+
+        let answer: Answer = agent.run_structured("What is it?", &schema).await.unwrap();
+        assert_eq!(answer.value, 42);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        // Without native support the schema travels with the input
+        assert!(requests[0][0].content_as_text().contains("JSON Schema"));
+        assert_eq!(requests[1][1].content_as_text(), "about forty-two");
+    }
+
+    #[tokio::test]
+    async fn run_in_session_continues_the_stored_conversation() {
+        let provider = ReplyProvider {
+            replies: Mutex::new(vec!["Hi Ada", "Your name is Ada"]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let requests = provider.requests.clone();
+        let mut agent = Agent::new(provider).with_options(AgentOptions {
+            system_prompt: Some("Be brief".to_string()),
+            tool_choice: ToolChoice::None,
+            ..Default::default()
+        });
+        let mut session = Session::new("chat");
+
+        agent
+            .run_in_session(&mut session, "I am Ada")
+            .await
+            .unwrap();
+        assert_eq!(session.messages.len(), 2);
+        assert_eq!(session.runs, 1);
+
+        let answer = agent
+            .run_in_session(&mut session, "What is my name?")
+            .await
+            .unwrap();
+        assert_eq!(answer, "Your name is Ada");
+        assert_eq!(session.messages.len(), 4);
+
+        let requests = requests.lock().unwrap();
+        let texts: Vec<String> = requests[1].iter().map(|m| m.content_as_text()).collect();
+        assert_eq!(
+            texts,
+            ["Be brief", "I am Ada", "Hi Ada", "What is my name?"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_with_memory_flushes_turns_to_the_store() {
+        let store: Arc<dyn crate::memory::MemoryStore> =
+            Arc::new(crate::memory::InMemoryStore::new());
+        let reply = |text: &'static str| ReplyProvider {
+            replies: Mutex::new(vec![text]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let options = AgentOptions {
+            tool_choice: ToolChoice::None,
+            ..Default::default()
+        };
+
+        let mut memory = Memory::open("chat", store.clone()).await.unwrap();
+        Agent::new(reply("Hi Ada"))
+            .with_options(options.clone())
+            .run_with_memory(&mut memory, "I am Ada")
+            .await
+            .unwrap();
+        assert_eq!(store.load("chat").await.unwrap().len(), 2);
+
+        // A fresh agent and memory, as after a restart
+        let provider = reply("Your name is Ada");
+        let requests = provider.requests.clone();
+        let mut memory = Memory::open("chat", store.clone()).await.unwrap();
+        Agent::new(provider)
+            .with_options(options)
+            .run_with_memory(&mut memory, "What is my name?")
+            .await
+            .unwrap();
+        let texts: Vec<String> = requests.lock().unwrap()[0]
+            .iter()
+            .map(|m| m.content_as_text())
+            .collect();
+        assert_eq!(texts, ["I am Ada", "Hi Ada", "What is my name?"]);
+        assert_eq!(store.load("chat").await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn run_with_summarizing_memory_replaces_older_turns_with_a_summary() {
+        let store: Arc<dyn crate::memory::MemoryStore> =
+            Arc::new(crate::memory::InMemoryStore::new());
+        let mut memory = Memory::open("chat", store.clone()).await.unwrap();
+        for text in ["I am Ada", "Hi Ada", "I like tea", "Noted"] {
+            memory.push(if memory.messages().len() % 2 == 0 {
+                Message::user(text)
+            } else {
+                Message::assistant(text)
+            });
+        }
+        let summarizer = ReplyProvider {
+            replies: Mutex::new(vec!["The user is Ada."]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let summarized = summarizer.requests.clone();
+        let mut memory = SummarizingMemory::new(memory, Arc::new(summarizer))
+            .max_messages(2)
+            .keep_turns(1);
+
+        let provider = ReplyProvider {
+            replies: Mutex::new(vec!["Tea, Ada"]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let requests = provider.requests.clone();
+        Agent::new(provider)
+            .with_options(AgentOptions {
+                tool_choice: ToolChoice::None,
+                ..Default::default()
+            })
+            .run_with_summarizing_memory(&mut memory, "What do I like?")
+            .await
+            .unwrap();
+
+        assert!(summarized.lock().unwrap()[0][1]
+            .content_as_text()
+            .ends_with("user: I am Ada\n\nassistant: Hi Ada"));
+        let texts: Vec<String> = requests.lock().unwrap()[0]
+            .iter()
+            .map(|m| m.content_as_text())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "Summary of the earlier conversation:\nThe user is Ada.",
+                "I like tea",
+                "Noted",
+                "What do I like?"
+            ]
+        );
+        // The store keeps every turn
+        assert_eq!(store.load("chat").await.unwrap().len(), 6);
+        assert_eq!(memory.memory().messages().len(), 6);
+        assert_eq!(memory.context().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn exported_conversation_continues_in_another_agent() {
+        let mut agent = Agent::new(ReplyProvider {
+            replies: Mutex::new(vec!["Hi Ada"]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        });
+        agent.register_tool(Box::new(EchoTool)).await;
+        agent.run("I am Ada").await.unwrap();
+
+        let json = agent.export_conversation("chat").await.to_json().unwrap();
+        let bundle = ConversationBundle::from_json(&json).unwrap();
+        assert_eq!(bundle.tools[0].name, "echo");
+        assert_eq!(bundle.events["conversation_started"], 1);
+        assert_eq!(bundle.events["conversation_completed"], 1);
+
+        let mut session = bundle.into_session();
+        let provider = ReplyProvider {
+            replies: Mutex::new(vec!["Your name is Ada"]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let requests = provider.requests.clone();
+        Agent::new(provider)
+            .with_options(AgentOptions {
+                tool_choice: ToolChoice::None,
+                ..Default::default()
+            })
+            .run_in_session(&mut session, "What is my name?")
+            .await
+            .unwrap();
+        let texts: Vec<String> = requests.lock().unwrap()[0]
+            .iter()
+            .map(|m| m.content_as_text())
+            .collect();
+        assert_eq!(texts, ["I am Ada", "Hi Ada", "What is my name?"]);
+    }
+
+    struct DeleteTool;
+
+    #[async_trait]
+    impl Tool for DeleteTool {
+        fn name(&self) -> &str {
+            "delete_file"
+        }
+
+        fn description(&self) -> &str {
+            "Deletes a file"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {"path": {"type": "string"}}})
+        }
+
+        fn metadata(&self) -> crate::tool::ToolMetadata {
+            crate::tool::ToolMetadata::default()
+                .danger(crate::tool::DangerLevel::Destructive)
+                .cost_hint("free")
+                .example("Remove a temp file", serde_json::json!({"path": "/tmp/x"}))
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolResult::success("deleted")
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_prompt_includes_metadata_hints() {
+        let provider = ReplyProvider {
+            replies: Mutex::new(vec!["no tools needed"]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let requests = provider.requests.clone();
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(DeleteTool)).await;
+        agent.run("clean up").await.unwrap();
+
+        let prompt = requests.lock().unwrap()[0][0].content_as_text();
+        assert!(prompt.contains("- delete_file: Deletes a file\n  Risk: destructive. Cost: free."));
+        assert!(prompt.contains("  - Remove a temp file: {\"path\":\"/tmp/x\"}"));
+
+        let schema = ToolSchema::from(agent.tools.tool_info("delete_file").await.unwrap());
+        assert!(schema.description.contains("Risk: destructive."));
+    }
+
+    /// Registers `echo` when called, like a connector coming online mid-run
+    struct ConnectTool(ToolRegistry);
+
+    #[async_trait]
+    impl Tool for ConnectTool {
+        fn name(&self) -> &str {
+            "connect"
+        }
+
+        fn description(&self) -> &str {
+            "Connects to the echo server"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            self.0.register(Box::new(EchoTool)).await;
+            self.0.unregister("connect").await;
+            ToolResult::success("connected")
+        }
+    }
+
+    #[tokio::test]
+    async fn tools_registered_mid_run_are_offered_next_iteration() {
+        let provider = ReplyProvider {
+            replies: Mutex::new(vec![
+                r#"{"tool_calls": [{"id": "call_1", "name": "connect", "parameters": {}}]}"#,
+                "done",
+            ]),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+        let requests = provider.requests.clone();
+        let event_bus = Arc::new(EventBus::new(32));
+        let mut receiver = event_bus.subscribe();
+        let mut agent = Agent::new(provider).with_event_bus(event_bus);
+        let registry = agent.tool_registry();
+        agent.register_tool(Box::new(ConnectTool(registry))).await;
+
+        assert_eq!(agent.run("connect please").await.unwrap(), "done");
+
+        let requests = requests.lock().unwrap();
+        let first_prompt = requests[0][0].content_as_text();
+        let second_prompt = requests[1][0].content_as_text();
+        assert!(first_prompt.contains("- connect:") && !first_prompt.contains("- echo:"));
+        assert!(second_prompt.contains("- echo:") && !second_prompt.contains("- connect:"));
+
+        let mut changed = None;
+        while let Ok(event) = receiver.try_recv() {
+            if let AgentEvent::ToolsChanged { added, removed } = event {
+                changed = Some((added, removed));
+            }
+        }
+        assert_eq!(
+            changed,
+            Some((vec!["echo".to_string()], vec!["connect".to_string()]))
+        );
+    }
+    struct SlowApprover;
+
+    #[async_trait]
+    impl crate::tool::ApprovalHandler for SlowApprover {
+        async fn decide(
+            &self,
+            _call: &ToolCall,
+            _tool: &crate::tool::ToolInfo,
+        ) -> crate::tool::ApprovalDecision {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            crate::tool::ApprovalDecision::Approve
+        }
+    }
+
+    #[tokio::test]
+    async fn approval_timeouts_are_reported() {
+        let provider = NativeToolProvider::new(vec![
+            scripted_response(
+                "",
+                vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "echo".to_string(),
+                    parameters: serde_json::json!({"text": "hi"}),
+                }],
+            ),
+            scripted_response("done", Vec::new()),
+        ]);
+        let event_bus = Arc::new(EventBus::new(64));
+        let mut receiver = event_bus.subscribe();
+        let approvals = ApprovalManager::new()
+            .with_handler(Arc::new(SlowApprover))
+            .require_from(crate::tool::DangerLevel::Safe)
+            .tool_timeout("echo", Duration::from_millis(10));
+        let mut agent = Agent::new(provider)
+            .with_event_bus(event_bus)
+            .with_approvals(approvals);
+        agent.register_tool(Box::new(EchoTool)).await;
+
+        assert_eq!(agent.run("go").await.unwrap(), "done");
+        let mut timed_out = Vec::new();
+        let mut failed = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                AgentEvent::ApprovalTimedOut { timeout } => timed_out.push(timeout),
+                AgentEvent::ToolCallFailed { call, .. } => failed.push(call.name),
+                _ => {}
+            }
+        }
+        assert_eq!(timed_out.len(), 1);
+        assert_eq!(timed_out[0].call_id, "call_1");
+        assert_eq!(timed_out[0].timeout, Duration::from_millis(10));
+        assert_eq!(failed, ["echo"]);
+    }
 }
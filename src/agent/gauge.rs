@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Tokens the conversation occupies in the model's context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextUsage {
+    pub used_tokens: usize,
+    /// Context window of the model, if known
+    pub max_tokens: Option<usize>,
+}
+
+impl ContextUsage {
+    /// Fraction of the window in use
+    pub fn ratio(&self) -> Option<f64> {
+        self.max_tokens
+            .filter(|max| *max > 0)
+            .map(|max| self.used_tokens as f64 / max as f64)
+    }
+
+    pub fn remaining_tokens(&self) -> Option<usize> {
+        self.max_tokens
+            .map(|max| max.saturating_sub(self.used_tokens))
+    }
+}
+
+/// Live context usage of an agent, for context meters in UIs
+///
+/// Updated before each LLM request with an estimate and after each response
+/// with the provider's reported usage. Clones observe the same agent.
+#[derive(Clone, Debug)]
+pub struct ContextGauge {
+    usage: Arc<watch::Sender<ContextUsage>>,
+}
+
+impl ContextGauge {
+    pub fn new() -> Self {
+        Self {
+            usage: Arc::new(watch::Sender::new(ContextUsage::default())),
+        }
+    }
+
+    pub fn usage(&self) -> ContextUsage {
+        *self.usage.borrow()
+    }
+
+    /// Receiver that is notified on every update
+    pub fn subscribe(&self) -> watch::Receiver<ContextUsage> {
+        self.usage.subscribe()
+    }
+
+    pub(crate) fn set(&self, usage: ContextUsage) {
+        self.usage.send_replace(usage);
+    }
+}
+
+impl Default for ContextGauge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,51 @@
+use super::options::RunOverrides;
+use crate::error::Result;
+use crate::events::RunMetadata;
+use crate::provider::Usage;
+use async_trait::async_trait;
+
+/// Input and overrides of a run, as seen by `RunMiddleware::before_run`
+#[derive(Debug, Clone)]
+pub struct RunRequest {
+    pub input: String,
+    pub overrides: RunOverrides,
+}
+
+/// Progress of a run before one of its LLM requests
+#[derive(Debug, Clone)]
+pub struct IterationContext {
+    /// Zero-based index of the request about to be sent
+    pub iteration: usize,
+    /// Tokens used by the run so far
+    pub usage: Usage,
+    /// The run's `RunOverrides::metadata`
+    pub metadata: RunMetadata,
+}
+
+/// Wraps whole agent runs, e.g. for tenancy, budgets or experiment flags
+///
+/// Unlike an `AgentHook`, middleware can rewrite the input and overrides of
+/// a run and replace its result. `before_run` is called in registration
+/// order and `after_run` in reverse, so the first middleware added is the
+/// outermost. Covers every `Agent` entry point that runs the tool loop.
+#[async_trait]
+pub trait RunMiddleware: Send + Sync {
+    /// Called before the run starts; an `Err` fails the run without
+    /// sending anything to the provider
+    async fn before_run(&self, run: &mut RunRequest) -> Result<()> {
+        let _ = run;
+        Ok(())
+    }
+
+    /// Called before each LLM request; an `Err` stops the run with it
+    async fn before_iteration(&self, ctx: &IterationContext) -> Result<()> {
+        let _ = ctx;
+        Ok(())
+    }
+
+    /// Called with the run's result, including failures, which may be
+    /// replaced
+    async fn after_run(&self, run: &RunRequest, result: &mut Result<String>) {
+        let _ = (run, result);
+    }
+}
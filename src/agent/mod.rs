@@ -1,5 +1,27 @@
+#[allow(clippy::module_inception)]
 pub mod agent;
+pub mod gauge;
+pub mod middleware;
+pub mod observation;
 pub mod options;
+pub mod outcome;
+pub mod pause;
+pub mod postprocess;
+pub mod profile;
+pub mod stream;
+mod telemetry;
+pub mod topology;
+pub mod trace;
 
 pub use agent::*;
+pub use gauge::*;
+pub use middleware::*;
+pub use observation::*;
 pub use options::*;
+pub use outcome::{FinishReason, RunOutcome};
+pub use pause::*;
+pub use postprocess::*;
+pub use profile::*;
+pub use stream::*;
+pub use topology::*;
+pub use trace::*;
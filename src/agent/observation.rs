@@ -0,0 +1,174 @@
+use crate::tool::{ToolCall, ToolResult};
+use std::sync::Arc;
+
+/// A tool call paired with its result
+#[derive(Debug, Clone, Copy)]
+pub struct Observation<'a> {
+    pub call: &'a ToolCall,
+    pub result: &'a ToolResult,
+}
+
+impl Observation<'_> {
+    /// Result content, or the error message for a failed call
+    pub fn text(&self) -> &str {
+        if self.result.success {
+            &self.result.content
+        } else {
+            self.result.error.as_deref().unwrap_or("Unknown error")
+        }
+    }
+}
+
+/// Renders tool results into the message sent back to the model
+pub trait ObservationFormatter: Send + Sync {
+    fn format(&self, observations: &[Observation<'_>]) -> String;
+}
+
+/// How tool results are written back to the model in prompt-based tool mode
+///
+/// Providers with native tool calling always receive one tool result block
+/// per call.
+#[derive(Clone, Default)]
+pub enum ObservationFormat {
+    /// `Tool results:` followed by `Result 1: ...` / `Error 1: ...` lines
+    #[default]
+    Plain,
+    /// JSON array of `{id, tool, status, content}` objects
+    Json,
+    /// `<tool_result id=".." tool=".." status="..">` elements
+    XmlTagged,
+    Custom(Arc<dyn ObservationFormatter>),
+}
+
+impl std::fmt::Debug for ObservationFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plain => write!(f, "Plain"),
+            Self::Json => write!(f, "Json"),
+            Self::XmlTagged => write!(f, "XmlTagged"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl ObservationFormat {
+    pub fn format(&self, observations: &[Observation<'_>]) -> String {
+        match self {
+            Self::Plain => Self::plain(observations),
+            Self::Json => Self::json(observations),
+            Self::XmlTagged => Self::xml(observations),
+            Self::Custom(formatter) => formatter.format(observations),
+        }
+    }
+
+    fn plain(observations: &[Observation<'_>]) -> String {
+        let lines: Vec<String> = observations
+            .iter()
+            .enumerate()
+            .map(|(i, obs)| {
+                let label = if obs.result.success {
+                    "Result"
+                } else {
+                    "Error"
+                };
+                format!("{} {}: {}", label, i + 1, obs.text())
+            })
+            .collect();
+        format!("Tool results:\n{}", lines.join("\n"))
+    }
+
+    fn json(observations: &[Observation<'_>]) -> String {
+        let entries: Vec<serde_json::Value> = observations
+            .iter()
+            .map(|obs| {
+                let mut entry = serde_json::json!({
+                    "id": obs.call.id,
+                    "tool": obs.call.name,
+                    "status": status(obs),
+                    "content": obs.text(),
+                });
+                if let Some(kind) = obs.result.error_kind() {
+                    entry["error_kind"] = serde_json::json!(kind);
+                }
+                entry
+            })
+            .collect();
+        serde_json::to_string_pretty(&entries).unwrap_or_default()
+    }
+
+    fn xml(observations: &[Observation<'_>]) -> String {
+        observations
+            .iter()
+            .map(|obs| {
+                format!(
+                    "<tool_result id=\"{}\" tool=\"{}\" status=\"{}\">\n{}\n</tool_result>",
+                    escape_xml(&obs.call.id),
+                    escape_xml(&obs.call.name),
+                    status(obs),
+                    escape_xml(obs.text())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn status(obs: &Observation<'_>) -> &'static str {
+    if obs.result.success {
+        "ok"
+    } else {
+        "error"
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::ToolError;
+
+    fn call(id: &str, name: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            parameters: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn formats_results_with_call_ids() {
+        let calls = [call("call_0", "search"), call("call_1", "fetch")];
+        let results = [
+            ToolResult::success("a < b"),
+            ToolResult::failed(ToolError::upstream("503")),
+        ];
+        let observations: Vec<Observation> = calls
+            .iter()
+            .zip(&results)
+            .map(|(call, result)| Observation { call, result })
+            .collect();
+
+        assert_eq!(
+            ObservationFormat::Plain.format(&observations),
+            "Tool results:\nResult 1: a < b\nError 2: 503"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&ObservationFormat::Json.format(&observations)).unwrap();
+        assert_eq!(json[1]["id"], "call_1");
+        assert_eq!(json[1]["status"], "error");
+        assert_eq!(json[1]["error_kind"], "upstream");
+
+        assert_eq!(
+            ObservationFormat::XmlTagged.format(&observations),
+            "<tool_result id=\"call_0\" tool=\"search\" status=\"ok\">\na &lt; b\n</tool_result>\n\
+             <tool_result id=\"call_1\" tool=\"fetch\" status=\"error\">\n503\n</tool_result>"
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use crate::provider::GenerateOptions;
+use super::observation::ObservationFormat;
+use super::postprocess::ResponsePostProcessing;
+use crate::provider::{GenerateOptions, ResponseFormat};
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct AgentOptions {
@@ -6,6 +10,49 @@ pub struct AgentOptions {
     pub max_iterations: usize,
     pub tool_choice: ToolChoice,
     pub generate_options: GenerateOptions,
+    pub post_processing: ResponsePostProcessing,
+    /// How tool results are written back to the model without native tools
+    pub observation_format: ObservationFormat,
+    /// Restrict the registered tools offered to the model (None = all tools)
+    ///
+    /// Entries are tool names or prefixes ending in `*`, e.g. `fs__*` for
+    /// every tool in the `fs` namespace.
+    pub allowed_tools: Option<Vec<String>>,
+    /// Repair near-miss tool arguments against the tool schema before
+    /// validation, e.g. `"5"` for a number (see `coerce_arguments`)
+    pub coerce_tool_arguments: bool,
+    /// Longest a whole run may take, including every LLM request and tool call
+    pub run_timeout: Option<Duration>,
+    /// Longest a tool call may take unless the tool's metadata sets its own
+    pub tool_timeout: Option<Duration>,
+    /// Fractions of the context window at which `ContextPressure` is emitted,
+    /// once per run each
+    pub context_pressure_thresholds: Vec<f64>,
+    /// Whether the tool calls of one LLM turn run one after another or
+    /// concurrently
+    pub tool_execution: ToolExecution,
+    /// Let the model request several tool calls in one response; when false,
+    /// providers that support it are asked for at most one
+    pub parallel_tool_calls: bool,
+    /// Most tool calls executed from one response; the rest are rejected with
+    /// an error asking the model to call them again later
+    pub max_tool_calls_per_iteration: Option<usize>,
+    /// End the run when a tool call fails instead of returning the error to
+    /// the model
+    pub stop_on_tool_failure: bool,
+    /// Most tokens a run may use, summed over its LLM responses
+    ///
+    /// A request whose estimated prompt would cross the budget is not sent,
+    /// and tool calls are not run once it is used up. The run then ends with
+    /// the last assistant text so far and emits `BudgetExceeded`.
+    pub max_total_tokens: Option<usize>,
+}
+
+impl AgentOptions {
+    /// Check whether a registered tool may be offered to and called by the model
+    pub fn is_tool_allowed(&self, name: &str) -> bool {
+        tool_allowed(self.allowed_tools.as_deref(), name)
+    }
 }
 
 impl Default for AgentOptions {
@@ -15,10 +62,96 @@ impl Default for AgentOptions {
             max_iterations: 10,
             tool_choice: ToolChoice::Auto,
             generate_options: GenerateOptions::default(),
+            post_processing: ResponsePostProcessing::default(),
+            observation_format: ObservationFormat::default(),
+            allowed_tools: None,
+            coerce_tool_arguments: false,
+            run_timeout: None,
+            tool_timeout: None,
+            context_pressure_thresholds: vec![0.8, 0.95],
+            tool_execution: ToolExecution::Sequential,
+            parallel_tool_calls: true,
+            max_tool_calls_per_iteration: None,
+            stop_on_tool_failure: false,
+            max_total_tokens: None,
         }
     }
 }
 
+/// Whether `name` matches one of `allowed`, names or prefixes ending in
+/// `*`; every tool is allowed without a list
+pub(crate) fn tool_allowed(allowed: Option<&[String]>, name: &str) -> bool {
+    allowed
+        .map(|allowed| {
+            allowed
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => pattern == name,
+                })
+        })
+        .unwrap_or(true)
+}
+
+/// Per-run overrides applied on top of `AgentOptions::generate_options`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunOverrides {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    pub response_format: Option<ResponseFormat>,
+    /// Tags such as a ticket id, user id or experiment, attached to every
+    /// event the run emits
+    pub metadata: HashMap<String, String>,
+}
+
+impl RunOverrides {
+    pub fn metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Add one metadata entry
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Check whether any override is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge the overrides into a copy of the base options
+    pub fn apply(&self, base: &GenerateOptions) -> GenerateOptions {
+        GenerateOptions {
+            model: self.model.clone().or_else(|| base.model.clone()),
+            temperature: self.temperature.or(base.temperature),
+            max_tokens: self.max_tokens.or(base.max_tokens),
+            top_p: self.top_p.or(base.top_p),
+            response_format: self
+                .response_format
+                .clone()
+                .or_else(|| base.response_format.clone()),
+            ..base.clone()
+        }
+    }
+}
+
+/// How the tool calls requested in one LLM turn are executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolExecution {
+    /// One at a time; hooks and pauses apply between calls
+    #[default]
+    Sequential,
+    /// Up to `max_concurrency` calls at once
+    ///
+    /// All calls are prepared (hooks, pause checks) before any runs, and
+    /// results are reported and sent back to the model in call order.
+    Parallel { max_concurrency: usize },
+}
+
 #[derive(Debug, Clone)]
 pub enum ToolChoice {
     Auto,
@@ -26,3 +159,21 @@ pub enum ToolChoice {
     Required,
     Specific(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_tools_match_names_and_prefixes() {
+        let options = AgentOptions {
+            allowed_tools: Some(vec!["fs__*".to_string(), "search".to_string()]),
+            ..Default::default()
+        };
+        assert!(options.is_tool_allowed("fs__read_file"));
+        assert!(options.is_tool_allowed("search"));
+        assert!(!options.is_tool_allowed("search_web"));
+        assert!(!options.is_tool_allowed("shell__run"));
+        assert!(AgentOptions::default().is_tool_allowed("anything"));
+    }
+}
@@ -0,0 +1,66 @@
+use super::trace::RunTrace;
+use crate::events::EventKind;
+use crate::provider::Usage;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Why a run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishReason {
+    /// The model gave a final answer
+    Completed,
+    /// `max_iterations` LLM requests were made without a final answer
+    MaxIterations,
+    /// The run would have used more than `max_total_tokens`
+    Budget,
+    /// The run exceeded `run_timeout`
+    Timeout,
+    /// The run's cancellation token was cancelled
+    Cancelled,
+    /// A tool call failed with `stop_on_tool_failure` set
+    ToolFailure,
+}
+
+/// Result of `Agent::run_outcome`
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    /// The final answer, or the last assistant text for an unfinished run
+    pub text: String,
+    pub finish_reason: FinishReason,
+    /// Tokens summed over every LLM response of the run
+    pub usage: Usage,
+    /// LLM requests made
+    pub iterations: usize,
+    pub duration: Duration,
+}
+
+impl RunOutcome {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.finish_reason == FinishReason::Completed
+    }
+}
+
+/// Counters kept while a run is in progress
+#[derive(Debug, Default)]
+pub(crate) struct RunProgress {
+    pub iterations: usize,
+    pub usage: Usage,
+    /// Set when the run stops for a reason its result does not identify
+    pub finish: Option<FinishReason>,
+    /// Events emitted, by kind
+    pub events: Mutex<BTreeMap<EventKind, u64>>,
+    pub trace: RunTrace,
+}
+
+impl RunProgress {
+    pub fn add_usage(&mut self, usage: &Usage) {
+        self.usage.prompt_tokens += usage.prompt_tokens;
+        self.usage.completion_tokens += usage.completion_tokens;
+        self.usage.total_tokens += usage.total_tokens;
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Pauses and resumes an agent's runs from another task
+///
+/// A paused run finishes the step in progress (an LLM request or tool call)
+/// and waits before the next one, so tool calls after the current one are
+/// deferred until `resume`. Clones control the same agent.
+#[derive(Clone, Debug)]
+pub struct PauseHandle {
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl PauseHandle {
+    pub fn new() -> Self {
+        Self {
+            paused: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Resolve once the handle is not paused
+    pub async fn wait_resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives in `self`, so the channel cannot close here
+        let _ = paused.wait_for(|paused| !*paused).await;
+    }
+}
+
+impl Default for PauseHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn wait_resumed_wakes_on_resume() {
+        let handle = PauseHandle::new();
+        handle.wait_resumed().await;
+
+        handle.pause();
+        let waiter = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.wait_resumed().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        handle.resume();
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("resume should wake the waiter")
+            .unwrap();
+    }
+}
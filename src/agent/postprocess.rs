@@ -0,0 +1,146 @@
+/// Post-processing applied to model responses before the agent uses them
+///
+/// All steps are disabled by default so responses are passed through untouched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponsePostProcessing {
+    /// Remove a stop sequence echoed at the end of the response
+    pub trim_stop_sequences: bool,
+    /// Strip leading and trailing whitespace
+    pub trim_whitespace: bool,
+    /// Append a closing ``` when the response leaves a code fence open
+    pub close_code_fences: bool,
+    /// Append missing `}`/`]` when the response is a truncated JSON document
+    pub close_json: bool,
+}
+
+impl ResponsePostProcessing {
+    /// Enable every post-processing step
+    pub fn all() -> Self {
+        Self {
+            trim_stop_sequences: true,
+            trim_whitespace: true,
+            close_code_fences: true,
+            close_json: true,
+        }
+    }
+
+    /// Apply the enabled steps to a response
+    pub fn apply(&self, content: &str, stop: Option<&[String]>) -> String {
+        let mut content = content.to_string();
+
+        if self.trim_stop_sequences {
+            if let Some(stop) = stop {
+                for seq in stop.iter().filter(|s| !s.is_empty()) {
+                    let trimmed = content.trim_end();
+                    if let Some(stripped) = trimmed.strip_suffix(seq.as_str()) {
+                        content.truncate(stripped.len());
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.trim_whitespace {
+            content = content.trim().to_string();
+        }
+
+        if self.close_code_fences && content.matches("```").count() % 2 == 1 {
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str("```");
+        }
+
+        if self.close_json {
+            Self::close_json_document(&mut content);
+        }
+
+        content
+    }
+
+    fn close_json_document(content: &mut String) {
+        let trimmed = content.trim_start();
+        if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+            return;
+        }
+
+        let mut stack = Vec::new();
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for ch in trimmed.chars() {
+            if in_string {
+                match ch {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match ch {
+                '"' => in_string = true,
+                '{' => stack.push('}'),
+                '[' => stack.push(']'),
+                '}' | ']' => {
+                    if stack.last() == Some(&ch) {
+                        stack.pop();
+                    } else {
+                        // Mismatched brackets: not something we can repair
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if in_string {
+            content.push('"');
+        }
+        while let Some(closer) = stack.pop() {
+            content.push(closer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_leaves_content_untouched() {
+        let content = "  hello STOP  ";
+        let stop = vec!["STOP".to_string()];
+        assert_eq!(
+            ResponsePostProcessing::default().apply(content, Some(&stop)),
+            content
+        );
+    }
+
+    #[test]
+    fn trims_echoed_stop_sequence_and_whitespace() {
+        let stop = vec!["</answer>".to_string()];
+        let result = ResponsePostProcessing::all().apply("  42 </answer>\n", Some(&stop));
+        assert_eq!(result, "42");
+    }
+
+    #[test]
+    fn closes_unbalanced_code_fence() {
+        let result = ResponsePostProcessing::all().apply("```rust\nfn main() {}", None);
+        assert_eq!(result, "```rust\nfn main() {}\n```");
+    }
+
+    #[test]
+    fn closes_truncated_json_document() {
+        let result = ResponsePostProcessing::all().apply(r#"{"items": [1, 2, {"name": "a}"#, None);
+        assert_eq!(result, r#"{"items": [1, 2, {"name": "a}"}]}"#);
+        assert!(serde_json::from_str::<serde_json::Value>(&result).is_ok());
+    }
+
+    #[test]
+    fn ignores_braces_in_prose() {
+        let result = ResponsePostProcessing::all().apply("Use a { to open a block", None);
+        assert_eq!(result, "Use a { to open a block");
+    }
+}
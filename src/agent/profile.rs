@@ -0,0 +1,260 @@
+use super::agent::Agent;
+use super::options::AgentOptions;
+use crate::error::{AgentError, Result};
+use crate::provider::LlmProvider;
+use crate::tool::Tool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Named bundle of agent configuration, e.g. "coder" or "researcher"
+///
+/// A profile carries the options (system prompt, iteration limit, tool choice,
+/// generation options) and the tool set an agent should run with. The tool set
+/// doubles as a guardrail: an agent using the profile can only call its tools.
+#[derive(Clone)]
+pub struct AgentProfile {
+    pub name: String,
+    pub options: AgentOptions,
+    pub tools: Vec<Arc<dyn Tool>>,
+}
+
+impl AgentProfile {
+    /// Create a profile with default options and no tools
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            options: AgentOptions::default(),
+            tools: Vec::new(),
+        }
+    }
+
+    /// Set the options used by agents running this profile
+    pub fn with_options(mut self, options: AgentOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the system prompt
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.options.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Use a specific model instead of the provider's default
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.options.generate_options.model = Some(model.into());
+        self
+    }
+
+    /// Set the maximum number of iterations per run
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.options.max_iterations = max_iterations;
+        self
+    }
+
+    /// Add a tool to the profile's tool set
+    pub fn with_tool(mut self, tool: Arc<dyn Tool>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Options with the allowed tool set narrowed to this profile's tools
+    fn resolved_options(&self) -> AgentOptions {
+        let mut options = self.options.clone();
+        options.allowed_tools = Some(
+            self.tools
+                .iter()
+                .map(|tool| tool.name().to_string())
+                .collect(),
+        );
+        options
+    }
+
+    /// Apply this profile to an existing agent
+    pub async fn apply<P: LlmProvider>(&self, agent: &mut Agent<P>) {
+        for tool in &self.tools {
+            agent.register_tool(Box::new(tool.clone())).await;
+        }
+        agent.set_options(self.resolved_options());
+    }
+}
+
+impl std::fmt::Debug for AgentProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentProfile")
+            .field("name", &self.name)
+            .field("options", &self.options)
+            .field(
+                "tools",
+                &self.tools.iter().map(|t| t.name()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Registry of named agent profiles
+#[derive(Debug, Clone, Default)]
+pub struct Profiles {
+    profiles: HashMap<String, AgentProfile>,
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a profile, replacing any profile with the same name
+    pub fn register(&mut self, profile: AgentProfile) {
+        self.profiles.insert(profile.name.clone(), profile);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AgentProfile> {
+        self.profiles.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Create an agent configured with the named profile
+    pub async fn spawn<P: LlmProvider>(&self, name: &str, provider: P) -> Result<Agent<P>> {
+        let mut agent = Agent::new(provider);
+        self.switch(name, &mut agent).await?;
+        Ok(agent)
+    }
+
+    /// Switch an existing agent to the named profile
+    pub async fn switch<P: LlmProvider>(&self, name: &str, agent: &mut Agent<P>) -> Result<()> {
+        let profile = self
+            .get(name)
+            .ok_or_else(|| AgentError::ProfileNotFound(name.to_string()))?;
+        profile.apply(agent).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{GenerateOptions, GenerateResponse, Message};
+    use crate::tool::ToolResult;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::future::Future;
+    use std::pin::Pin;
+
+    struct NamedTool(&'static str);
+
+    #[async_trait]
+    impl Tool for NamedTool {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "test tool"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolResult::success(self.0)
+        }
+    }
+
+    struct EchoModelProvider;
+
+    impl LlmProvider for EchoModelProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn model(&self) -> &str {
+            "default-model"
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            Box::pin(async move {
+                let model = options
+                    .and_then(|o| o.model)
+                    .unwrap_or_else(|| "default-model".to_string());
+                Ok(GenerateResponse {
+                    content: model.clone(),
+                    usage: None,
+                    model,
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    fn profiles() -> Profiles {
+        let mut profiles = Profiles::new();
+        profiles.register(
+            AgentProfile::new("coder")
+                .with_model("big-model")
+                .with_system_prompt("You write code.")
+                .with_tool(Arc::new(NamedTool("shell"))),
+        );
+        profiles.register(
+            AgentProfile::new("researcher")
+                .with_max_iterations(3)
+                .with_tool(Arc::new(NamedTool("search"))),
+        );
+        profiles
+    }
+
+    #[tokio::test]
+    async fn spawns_agent_with_profile_configuration() {
+        let profiles = profiles();
+        let mut agent = profiles
+            .spawn("coder", EchoModelProvider)
+            .await
+            .expect("profile should exist");
+
+        assert_eq!(
+            agent.options().system_prompt.as_deref(),
+            Some("You write code.")
+        );
+        assert!(agent.options().is_tool_allowed("shell"));
+        assert!(!agent.options().is_tool_allowed("search"));
+        assert_eq!(agent.run("hi").await.unwrap(), "big-model");
+    }
+
+    #[tokio::test]
+    async fn switching_profiles_replaces_tool_set() {
+        let profiles = profiles();
+        let mut agent = profiles.spawn("coder", EchoModelProvider).await.unwrap();
+
+        profiles.switch("researcher", &mut agent).await.unwrap();
+
+        assert_eq!(agent.options().max_iterations, 3);
+        assert!(agent.options().is_tool_allowed("search"));
+        assert!(!agent.options().is_tool_allowed("shell"));
+    }
+
+    #[tokio::test]
+    async fn unknown_profile_is_an_error() {
+        let err = profiles()
+            .spawn("missing", EchoModelProvider)
+            .await
+            .err()
+            .expect("should fail");
+        assert!(matches!(err, AgentError::ProfileNotFound(name) if name == "missing"));
+    }
+
+    #[test]
+    fn lists_profile_names_sorted() {
+        assert_eq!(profiles().names(), vec!["coder", "researcher"]);
+    }
+}
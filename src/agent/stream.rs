@@ -0,0 +1,73 @@
+use crate::error::{AgentError, Result};
+use crate::tool::{ToolCall, ToolResult};
+use futures_util::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// Incremental output of `Agent::run_stream_events`
+#[derive(Debug, Clone)]
+pub enum AgentStreamEvent {
+    /// Text from the model as it is generated
+    TextDelta(String),
+    ToolCallStarted(ToolCall),
+    ToolCallFinished {
+        call: ToolCall,
+        result: ToolResult,
+    },
+    /// The run completed with this answer; always the last event
+    FinalAnswer(String),
+}
+
+type RunFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Stream of a run's events; the run makes progress as the stream is polled
+///
+/// A failed run ends the stream with its error. Dropping the stream stops
+/// the run.
+pub struct AgentRunStream<'a> {
+    run: Option<RunFuture<'a>>,
+    events: mpsc::UnboundedReceiver<AgentStreamEvent>,
+    error: Option<AgentError>,
+}
+
+impl<'a> AgentRunStream<'a> {
+    pub(crate) fn new(
+        run: RunFuture<'a>,
+        events: mpsc::UnboundedReceiver<AgentStreamEvent>,
+    ) -> Self {
+        Self {
+            run: Some(run),
+            events,
+            error: None,
+        }
+    }
+}
+
+impl Stream for AgentRunStream<'_> {
+    type Item = Result<AgentStreamEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Poll::Ready(Some(event)) = self.events.poll_recv(cx) {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            let Some(run) = self.run.as_mut() else {
+                // The run has finished; the sender is gone once events drain
+                return match self.events.poll_recv(cx) {
+                    Poll::Ready(Some(event)) => Poll::Ready(Some(Ok(event))),
+                    Poll::Ready(None) => Poll::Ready(self.error.take().map(Err)),
+                    Poll::Pending => Poll::Pending,
+                };
+            };
+            match run.as_mut().poll(cx) {
+                Poll::Ready(result) => {
+                    self.run = None;
+                    self.error = result.err();
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
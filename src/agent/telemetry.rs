@@ -0,0 +1,393 @@
+//! Spans for runs, LLM calls and tool calls (feature = "otel").
+//!
+//! Spans are created with `tracing` and carry the OpenTelemetry GenAI
+//! attribute names, so `tracing-opentelemetry` exports them as they are.
+//! Agent events are recorded as events of the innermost span. Without the
+//! feature every method is a no-op.
+
+use crate::events::AgentEvent;
+use crate::provider::{GenerateResponse, Usage};
+use crate::tool::{ToolCall, ToolResult};
+use std::collections::HashMap;
+use std::future::Future;
+
+#[cfg(feature = "otel")]
+use tracing::field::Empty;
+
+#[derive(Clone)]
+pub(crate) struct Span {
+    #[cfg(feature = "otel")]
+    inner: tracing::Span,
+}
+
+#[cfg(feature = "otel")]
+impl Span {
+    /// Span of a whole run, parent of its LLM and tool spans
+    pub(crate) fn run(provider: &str, model: &str) -> Self {
+        let inner = tracing::info_span!(
+            "invoke_agent",
+            otel.name = "invoke_agent",
+            otel.status_code = Empty,
+            otel.status_message = Empty,
+            gen_ai.operation.name = "invoke_agent",
+            gen_ai.system = provider,
+            gen_ai.request.model = model,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            agent.iterations = Empty,
+            agent.metadata = Empty,
+        );
+        Self { inner }
+    }
+
+    /// Span of one LLM request
+    pub(crate) fn llm(provider: &str, model: &str, iteration: usize) -> Self {
+        let inner = tracing::info_span!(
+            "chat",
+            otel.name = %format_args!("chat {}", model),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            otel.status_message = Empty,
+            gen_ai.operation.name = "chat",
+            gen_ai.system = provider,
+            gen_ai.request.model = model,
+            gen_ai.response.model = Empty,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            agent.iteration = iteration,
+        );
+        Self { inner }
+    }
+
+    /// Span of one tool execution
+    pub(crate) fn tool(call: &ToolCall) -> Self {
+        let inner = tracing::info_span!(
+            "execute_tool",
+            otel.name = %format_args!("execute_tool {}", call.name),
+            otel.status_code = Empty,
+            otel.status_message = Empty,
+            gen_ai.operation.name = "execute_tool",
+            gen_ai.tool.name = call.name.as_str(),
+            gen_ai.tool.call.id = call.id.as_str(),
+        );
+        Self { inner }
+    }
+
+    pub(crate) async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        tracing::Instrument::instrument(future, self.inner.clone()).await
+    }
+
+    pub(crate) fn record_metadata(&self, metadata: &HashMap<String, String>) {
+        if metadata.is_empty() {
+            return;
+        }
+        let metadata: std::collections::BTreeMap<_, _> = metadata.iter().collect();
+        if let Ok(json) = serde_json::to_string(&metadata) {
+            self.inner.record("agent.metadata", json.as_str());
+        }
+    }
+
+    pub(crate) fn record_usage(&self, usage: &Usage) {
+        self.inner
+            .record("gen_ai.usage.input_tokens", usage.prompt_tokens)
+            .record("gen_ai.usage.output_tokens", usage.completion_tokens);
+    }
+
+    pub(crate) fn record_iterations(&self, iterations: usize) {
+        self.inner.record("agent.iterations", iterations);
+    }
+
+    pub(crate) fn record_response(&self, response: &GenerateResponse) {
+        self.inner
+            .record("gen_ai.response.model", response.model.as_str());
+        if let Some(reason) = &response.finish_reason {
+            self.inner
+                .record("gen_ai.response.finish_reasons", reason.as_str());
+        }
+        if let Some(usage) = &response.usage {
+            self.record_usage(usage);
+        }
+    }
+
+    pub(crate) fn record_tool_result(&self, result: &ToolResult) {
+        if !result.success {
+            self.record_error(result.error.as_deref().unwrap_or("Tool call failed"));
+        }
+    }
+
+    pub(crate) fn record_error(&self, error: &str) {
+        self.inner
+            .record("otel.status_code", "ERROR")
+            .record("otel.status_message", error);
+    }
+}
+
+/// Record `event` as an event of the current span
+#[cfg(feature = "otel")]
+pub(crate) fn record_event(event: &AgentEvent) {
+    let kind = event.kind().name();
+    tracing::info!(event.kind = kind.as_str(), "{}", kind);
+}
+
+#[cfg(not(feature = "otel"))]
+impl Span {
+    pub(crate) fn run(_provider: &str, _model: &str) -> Self {
+        Self {}
+    }
+
+    pub(crate) fn llm(_provider: &str, _model: &str, _iteration: usize) -> Self {
+        Self {}
+    }
+
+    pub(crate) fn tool(_call: &ToolCall) -> Self {
+        Self {}
+    }
+
+    pub(crate) async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        future.await
+    }
+
+    pub(crate) fn record_metadata(&self, _metadata: &HashMap<String, String>) {}
+
+    pub(crate) fn record_usage(&self, _usage: &Usage) {}
+
+    pub(crate) fn record_iterations(&self, _iterations: usize) {}
+
+    pub(crate) fn record_response(&self, _response: &GenerateResponse) {}
+
+    pub(crate) fn record_tool_result(&self, _result: &ToolResult) {}
+
+    pub(crate) fn record_error(&self, _error: &str) {}
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn record_event(_event: &AgentEvent) {}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use crate::agent::Agent;
+    use crate::testing::MockProvider;
+    use crate::tool::{Tool, ToolResult};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Debug, Default)]
+    struct RecordedSpan {
+        parent: Option<u64>,
+        fields: HashMap<String, String>,
+    }
+
+    /// Id of the span an event was recorded in, and its message
+    type RecordedEvent = (Option<u64>, String);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    /// Keeps spans and events in memory; good for current-thread runtimes only
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+        events: Arc<Mutex<Vec<RecordedEvent>>>,
+        stack: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Recorder {
+        fn current(&self) -> Option<u64> {
+            self.stack.lock().unwrap().last().copied()
+        }
+
+        fn field(&self, id: u64, name: &str) -> Option<String> {
+            self.spans.lock().unwrap()[id as usize - 1]
+                .fields
+                .get(name)
+                .cloned()
+        }
+
+        fn ids_named(&self, name: &str) -> Vec<u64> {
+            let spans = self.spans.lock().unwrap();
+            (1..=spans.len() as u64)
+                .filter(|&id| {
+                    spans[id as usize - 1]
+                        .fields
+                        .get("otel.name")
+                        .map(String::as_str)
+                        == Some(name)
+                })
+                .collect()
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut span = RecordedSpan {
+                parent: attrs.parent().map(Id::into_u64).or_else(|| self.current()),
+                ..Default::default()
+            };
+            attrs.record(&mut Fields(&mut span.fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].fields));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = HashMap::new();
+            event.record(&mut Fields(&mut fields));
+            let parent = event.parent().map(Id::into_u64).or_else(|| self.current());
+            let message = fields.remove("message").unwrap_or_default();
+            self.events.lock().unwrap().push((parent, message));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.stack.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.stack.lock().unwrap().pop();
+        }
+    }
+
+    struct FailingTool;
+
+    #[async_trait]
+    impl Tool for FailingTool {
+        fn name(&self) -> &str {
+            "lookup"
+        }
+
+        fn description(&self) -> &str {
+            "Always fails"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolResult::error("not found")
+        }
+    }
+
+    #[tokio::test]
+    async fn run_llm_and_tool_spans_are_nested_with_genai_attributes() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let provider = MockProvider::new()
+            .tool_call("lookup", json!({}))
+            .text("done");
+        let mut agent = Agent::new(provider);
+        agent.register_tool(Box::new(FailingTool)).await;
+        agent.run("look it up").await.unwrap();
+
+        let runs = recorder.ids_named("invoke_agent");
+        assert_eq!(runs.len(), 1);
+        let run = runs[0];
+        assert_eq!(
+            recorder.field(run, "agent.iterations").as_deref(),
+            Some("2")
+        );
+        assert_eq!(
+            recorder.field(run, "gen_ai.request.model").as_deref(),
+            Some("mock-model")
+        );
+
+        let chats = recorder.ids_named("chat mock-model");
+        assert_eq!(chats.len(), 2);
+        for &chat in &chats {
+            assert_eq!(
+                recorder.spans.lock().unwrap()[chat as usize - 1].parent,
+                Some(run)
+            );
+            assert_eq!(
+                recorder.field(chat, "gen_ai.system").as_deref(),
+                Some("mock")
+            );
+            assert_eq!(
+                recorder.field(chat, "gen_ai.usage.input_tokens").as_deref(),
+                Some("0")
+            );
+        }
+        assert_eq!(
+            recorder
+                .field(chats[0], "gen_ai.response.finish_reasons")
+                .as_deref(),
+            Some("tool_calls")
+        );
+
+        let tools = recorder.ids_named("execute_tool lookup");
+        assert_eq!(tools.len(), 1);
+        let tool = tools[0];
+        assert_eq!(
+            recorder.spans.lock().unwrap()[tool as usize - 1].parent,
+            Some(run)
+        );
+        assert_eq!(
+            recorder.field(tool, "gen_ai.tool.call.id").as_deref(),
+            Some("call_1")
+        );
+        assert_eq!(
+            recorder.field(tool, "otel.status_code").as_deref(),
+            Some("ERROR")
+        );
+        assert_eq!(recorder.field(run, "otel.status_code"), None);
+
+        let events = recorder.events.lock().unwrap();
+        assert!(events.contains(&(Some(run), "conversation_started".to_string())));
+        assert!(events.contains(&(Some(run), "tool_call_failed".to_string())));
+    }
+
+    #[tokio::test]
+    async fn failed_runs_and_llm_calls_are_marked_as_errors() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let provider = MockProvider::new().error(crate::provider::ProviderError::RequestFailed(
+            "down".to_string(),
+        ));
+        let mut agent = Agent::new(provider);
+        assert!(agent.run("hi").await.is_err());
+
+        let run = recorder.ids_named("invoke_agent")[0];
+        let chat = recorder.ids_named("chat mock-model")[0];
+        assert_eq!(
+            recorder.field(run, "otel.status_code").as_deref(),
+            Some("ERROR")
+        );
+        assert_eq!(
+            recorder.field(chat, "otel.status_code").as_deref(),
+            Some("ERROR")
+        );
+        assert!(recorder
+            .field(chat, "otel.status_message")
+            .unwrap()
+            .contains("down"));
+    }
+}
@@ -0,0 +1,311 @@
+use super::agent::Agent;
+use super::profile::Profiles;
+use crate::error::{AgentError, Result};
+use crate::events::EventBus;
+use crate::provider::LlmProvider;
+use crate::tool::Tool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Declarative description of a group of cooperating agents
+///
+/// Each agent is created from a named profile. Handoff edges record which
+/// agents may pass work to which, and shared tools are registered on every
+/// agent in addition to its profile's own tools. All agents publish to one
+/// shared `EventBus`.
+#[derive(Clone)]
+pub struct Topology {
+    profiles: Profiles,
+    agents: Vec<(String, String)>,
+    handoffs: Vec<(String, String)>,
+    shared_tools: Vec<Arc<dyn Tool>>,
+    event_capacity: usize,
+}
+
+impl Topology {
+    pub fn new(profiles: Profiles) -> Self {
+        Self {
+            profiles,
+            agents: Vec::new(),
+            handoffs: Vec::new(),
+            shared_tools: Vec::new(),
+            event_capacity: 100,
+        }
+    }
+
+    /// Declare an agent named `name` running the profile `profile`
+    pub fn agent(mut self, name: impl Into<String>, profile: impl Into<String>) -> Self {
+        self.agents.push((name.into(), profile.into()));
+        self
+    }
+
+    /// Allow the agent `from` to hand work off to the agent `to`
+    pub fn handoff(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.handoffs.push((from.into(), to.into()));
+        self
+    }
+
+    /// Register a tool on every agent in the topology
+    pub fn shared_tool(mut self, tool: Arc<dyn Tool>) -> Self {
+        self.shared_tools.push(tool);
+        self
+    }
+
+    /// Set the capacity of the shared event bus
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut seen = std::collections::HashSet::new();
+        for (name, profile) in &self.agents {
+            if !seen.insert(name.as_str()) {
+                return Err(AgentError::InvalidTopology(format!(
+                    "duplicate agent '{}'",
+                    name
+                )));
+            }
+            if self.profiles.get(profile).is_none() {
+                return Err(AgentError::ProfileNotFound(profile.clone()));
+            }
+        }
+
+        for (from, to) in &self.handoffs {
+            for endpoint in [from, to] {
+                if !seen.contains(endpoint.as_str()) {
+                    return Err(AgentError::InvalidTopology(format!(
+                        "handoff references unknown agent '{}'",
+                        endpoint
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create every declared agent, wired to a shared event bus
+    ///
+    /// `provider` is called once per agent with the agent's name.
+    pub async fn build<P, F>(self, mut provider: F) -> Result<Team<P>>
+    where
+        P: LlmProvider,
+        F: FnMut(&str) -> P,
+    {
+        self.validate()?;
+
+        let event_bus = Arc::new(EventBus::new(self.event_capacity));
+        let shared_names: Vec<String> = self
+            .shared_tools
+            .iter()
+            .map(|tool| tool.name().to_string())
+            .collect();
+
+        let mut agents = HashMap::new();
+        for (name, profile) in &self.agents {
+            let mut agent = Agent::new(provider(name)).with_event_bus(event_bus.clone());
+            self.profiles.switch(profile, &mut agent).await?;
+
+            for tool in &self.shared_tools {
+                agent.register_tool(Box::new(tool.clone())).await;
+            }
+            if !shared_names.is_empty() {
+                let mut options = agent.options().clone();
+                if let Some(allowed) = options.allowed_tools.as_mut() {
+                    allowed.extend(shared_names.iter().cloned());
+                }
+                agent.set_options(options);
+            }
+
+            agents.insert(name.clone(), agent);
+        }
+
+        Ok(Team {
+            agents,
+            handoffs: self.handoffs,
+            event_bus,
+        })
+    }
+}
+
+impl std::fmt::Debug for Topology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Topology")
+            .field("profiles", &self.profiles)
+            .field("agents", &self.agents)
+            .field("handoffs", &self.handoffs)
+            .field(
+                "shared_tools",
+                &self
+                    .shared_tools
+                    .iter()
+                    .map(|t| t.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field("event_capacity", &self.event_capacity)
+            .finish()
+    }
+}
+
+/// Agents instantiated from a `Topology`
+pub struct Team<P: LlmProvider> {
+    agents: HashMap<String, Agent<P>>,
+    handoffs: Vec<(String, String)>,
+    event_bus: Arc<EventBus>,
+}
+
+impl<P: LlmProvider> Team<P> {
+    pub fn agent(&self, name: &str) -> Option<&Agent<P>> {
+        self.agents.get(name)
+    }
+
+    pub fn agent_mut(&mut self, name: &str) -> Option<&mut Agent<P>> {
+        self.agents.get_mut(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.agents.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Agents that `from` may hand work off to, in declaration order
+    pub fn handoff_targets(&self, from: &str) -> Vec<&str> {
+        self.handoffs
+            .iter()
+            .filter(|(source, _)| source == from)
+            .map(|(_, target)| target.as_str())
+            .collect()
+    }
+
+    pub fn can_handoff(&self, from: &str, to: &str) -> bool {
+        self.handoffs
+            .iter()
+            .any(|(source, target)| source == from && target == to)
+    }
+
+    /// Event bus shared by every agent in the team
+    pub fn event_bus(&self) -> Arc<EventBus> {
+        self.event_bus.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentProfile;
+    use crate::events::AgentEvent;
+    use crate::provider::{GenerateOptions, GenerateResponse, Message};
+    use crate::tool::ToolResult;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::future::Future;
+    use std::pin::Pin;
+
+    struct NamedTool(&'static str);
+
+    #[async_trait]
+    impl Tool for NamedTool {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "test tool"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolResult::success(self.0)
+        }
+    }
+
+    struct NamedProvider(String);
+
+    impl LlmProvider for NamedProvider {
+        fn name(&self) -> &str {
+            &self.0
+        }
+
+        fn model(&self) -> &str {
+            "test-model"
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            Box::pin(async move {
+                Ok(GenerateResponse {
+                    content: format!("reply from {}", self.0),
+                    usage: None,
+                    model: "test-model".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    fn profiles() -> Profiles {
+        let mut profiles = Profiles::new();
+        profiles.register(AgentProfile::new("planner").with_tool(Arc::new(NamedTool("plan"))));
+        profiles.register(AgentProfile::new("coder").with_tool(Arc::new(NamedTool("shell"))));
+        profiles
+    }
+
+    #[tokio::test]
+    async fn builds_wired_team() {
+        let mut team = Topology::new(profiles())
+            .agent("lead", "planner")
+            .agent("dev", "coder")
+            .handoff("lead", "dev")
+            .shared_tool(Arc::new(NamedTool("notes")))
+            .build(|name| NamedProvider(name.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(team.names(), vec!["dev", "lead"]);
+        assert_eq!(team.handoff_targets("lead"), vec!["dev"]);
+        assert!(!team.can_handoff("dev", "lead"));
+
+        let dev = team.agent("dev").unwrap().options();
+        assert!(dev.is_tool_allowed("shell"));
+        assert!(dev.is_tool_allowed("notes"));
+        assert!(!dev.is_tool_allowed("plan"));
+
+        let mut events = team.event_bus().subscribe();
+        let reply = team.agent_mut("lead").unwrap().run("hi").await.unwrap();
+        assert_eq!(reply, "reply from lead");
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            AgentEvent::ConversationStarted { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_profiles_and_handoff_endpoints() {
+        let err = Topology::new(profiles())
+            .agent("lead", "manager")
+            .build(|name| NamedProvider(name.to_string()))
+            .await
+            .err()
+            .expect("should fail");
+        assert!(matches!(err, AgentError::ProfileNotFound(name) if name == "manager"));
+
+        let err = Topology::new(profiles())
+            .agent("lead", "planner")
+            .handoff("lead", "reviewer")
+            .build(|name| NamedProvider(name.to_string()))
+            .await
+            .err()
+            .expect("should fail");
+        assert!(matches!(err, AgentError::InvalidTopology(_)));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::provider::{GenerateResponse, Message, Usage};
+use crate::tool::ToolCall;
+
+/// What was sent to the model in one iteration
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RequestSummary {
+    pub model: String,
+    pub messages: usize,
+    /// Estimated prompt tokens, including tool schemas
+    pub estimated_tokens: usize,
+    /// Names of the tools offered
+    pub tools: Vec<String>,
+}
+
+/// A tool call made in one iteration and its result
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ToolCallTrace {
+    pub call: ToolCall,
+    pub success: bool,
+    pub content: String,
+    pub error: Option<String>,
+    /// Zero for calls that were not executed
+    pub duration_ms: u64,
+}
+
+/// One LLM request of a run and what came of it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IterationTrace {
+    /// Zero-based
+    pub iteration: usize,
+    pub request: RequestSummary,
+    /// The response after post-processing and hooks
+    pub response: GenerateResponse,
+    pub llm_duration_ms: u64,
+    pub tool_calls: Vec<ToolCallTrace>,
+    /// Messages this iteration added to the conversation: the assistant
+    /// turn, then the tool results
+    pub added_messages: Vec<Message>,
+}
+
+/// Iterations of the most recent run, see `Agent::run_trace`
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RunTrace {
+    pub iterations: Vec<IterationTrace>,
+}
+
+impl RunTrace {
+    /// Tokens summed over every response that reported usage
+    pub fn usage(&self) -> Usage {
+        let mut total = Usage::default();
+        for usage in self
+            .iterations
+            .iter()
+            .filter_map(|i| i.response.usage.as_ref())
+        {
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.total_tokens += usage.total_tokens;
+        }
+        total
+    }
+
+    /// Every tool call of the run, in order
+    pub fn tool_calls(&self) -> impl Iterator<Item = &ToolCallTrace> {
+        self.iterations.iter().flat_map(|i| &i.tool_calls)
+    }
+}
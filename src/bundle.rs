@@ -0,0 +1,173 @@
+//! Portable snapshot of a conversation.
+//!
+//! A `ConversationBundle` is plain JSON: the messages in the content block
+//! format used throughout the SDK, the tools that were offered, token usage
+//! and how many events of each kind were emitted. It names its format and
+//! version, so another process, or a program in another language, can check
+//! it can read the bundle before continuing the conversation.
+
+use crate::error::{AgentError, Result};
+use crate::events::{AgentEvent, EventKind};
+use crate::provider::{Message, Role, ToolSchema, Usage};
+use crate::session::Session;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Value of `ConversationBundle::format`
+pub const BUNDLE_FORMAT: &str = "agent-sdk.conversation";
+/// Newest bundle version this crate writes and reads
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Conversation in a self-describing JSON format, see the module docs
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConversationBundle {
+    pub format: String,
+    pub version: u32,
+    pub id: String,
+    pub messages: Vec<Message>,
+    /// Tools offered to the model
+    #[serde(default)]
+    pub tools: Vec<ToolSchema>,
+    #[serde(default)]
+    pub usage: Usage,
+    /// Events emitted, by snake_case kind, e.g. `tool_call_completed`
+    #[serde(default)]
+    pub events: BTreeMap<String, u64>,
+    /// Application state, e.g. a session's variables
+    #[serde(default)]
+    pub variables: BTreeMap<String, Value>,
+    /// Unix seconds
+    pub exported_at: u64,
+}
+
+impl ConversationBundle {
+    pub fn new(id: impl Into<String>, messages: Vec<Message>) -> Self {
+        Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            id: id.into(),
+            messages,
+            tools: Vec::new(),
+            usage: Usage::default(),
+            events: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The session's turns and variables
+    pub fn from_session(session: &Session) -> Self {
+        let mut bundle = Self::new(&session.id, session.messages.clone());
+        bundle.variables = session
+            .variables
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bundle
+    }
+
+    /// A new session to continue the conversation in
+    ///
+    /// Leading system prompts are dropped, since agents send their own.
+    pub fn into_session(self) -> Session {
+        let mut session = Session::new(self.id);
+        session.messages = self
+            .messages
+            .into_iter()
+            .skip_while(|m| m.role == Role::System)
+            .collect();
+        session.variables = self.variables.into_iter().collect();
+        session
+    }
+
+    pub fn with_tools(mut self, tools: Vec<ToolSchema>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Count an event, e.g. one received from an `EventBus`
+    pub fn record_event(&mut self, event: &AgentEvent) {
+        self.record_events(event.kind(), 1);
+    }
+
+    pub(crate) fn record_events(&mut self, kind: EventKind, count: u64) {
+        *self.events.entry(kind.name()).or_default() += count;
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| AgentError::ParseError(e.to_string()))
+    }
+
+    /// Parse a bundle, rejecting other formats and newer versions
+    pub fn from_json(json: &str) -> Result<Self> {
+        let bundle: Self =
+            serde_json::from_str(json).map_err(|e| AgentError::ParseError(e.to_string()))?;
+        if bundle.format != BUNDLE_FORMAT {
+            return Err(AgentError::ParseError(format!(
+                "Not a conversation bundle: format is '{}'",
+                bundle.format
+            )));
+        }
+        if bundle.version > BUNDLE_VERSION {
+            return Err(AgentError::ParseError(format!(
+                "Conversation bundle version {} is newer than supported version {}",
+                bundle.version, BUNDLE_VERSION
+            )));
+        }
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_a_session_through_json() {
+        let mut session = Session::new("chat-1");
+        session.messages = vec![Message::user("hi"), Message::assistant("hello")];
+        session.set_variable("plan", json!("pro"));
+
+        let mut bundle = ConversationBundle::from_session(&session).with_usage(Usage {
+            prompt_tokens: 10,
+            completion_tokens: 2,
+            total_tokens: 12,
+        });
+        bundle.record_event(&AgentEvent::RunPaused);
+        bundle.record_event(&AgentEvent::RunPaused);
+        let json = bundle.to_json().unwrap();
+
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["format"], BUNDLE_FORMAT);
+        assert_eq!(value["version"], BUNDLE_VERSION);
+        assert_eq!(value["events"], json!({"run_paused": 2}));
+        assert_eq!(
+            value["messages"][0],
+            json!({"role": "user", "content": [{"type": "text", "text": "hi"}]})
+        );
+
+        let imported = ConversationBundle::from_json(&json).unwrap();
+        assert_eq!(imported.usage.total_tokens, 12);
+        let session = imported.into_session();
+        assert_eq!(session.id, "chat-1");
+        assert_eq!(session.messages.len(), 2);
+        assert_eq!(session.variables["plan"], "pro");
+
+        let mut newer = value.clone();
+        newer["version"] = json!(BUNDLE_VERSION + 1);
+        assert!(ConversationBundle::from_json(&newer.to_string()).is_err());
+        let mut other = value;
+        other["format"] = json!("something-else");
+        assert!(ConversationBundle::from_json(&other.to_string()).is_err());
+    }
+}
@@ -7,6 +7,21 @@ pub enum AgentError {
     ToolExecutionFailed(String),
     ParseError(String),
     InvalidParameters(String),
+    ProfileNotFound(String),
+    InvalidTopology(String),
+    /// An `AgentHook` returned `HookOutcome::Abort`
+    HookAborted {
+        hook: String,
+        reason: String,
+    },
+    /// A `SessionStore` failed to load or save
+    Session(String),
+    /// A `MemoryStore` failed to load or append
+    Memory(String),
+    /// The run's cancellation token was cancelled
+    Cancelled,
+    /// The run exceeded `AgentOptions::run_timeout`
+    Timeout(std::time::Duration),
 }
 
 impl From<ProviderError> for AgentError {
@@ -23,6 +38,15 @@ impl std::fmt::Display for AgentError {
             Self::ToolExecutionFailed(msg) => write!(f, "Tool execution failed: {}", msg),
             Self::ParseError(msg) => write!(f, "Parse error: {}", msg),
             Self::InvalidParameters(msg) => write!(f, "Invalid parameters: {}", msg),
+            Self::ProfileNotFound(name) => write!(f, "Profile not found: {}", name),
+            Self::InvalidTopology(msg) => write!(f, "Invalid topology: {}", msg),
+            Self::HookAborted { hook, reason } => {
+                write!(f, "Run aborted by hook {}: {}", hook, reason)
+            }
+            Self::Session(msg) => write!(f, "Session store error: {}", msg),
+            Self::Memory(msg) => write!(f, "Memory store error: {}", msg),
+            Self::Cancelled => write!(f, "Run cancelled"),
+            Self::Timeout(limit) => write!(f, "Run timed out after {:?}", limit),
         }
     }
 }
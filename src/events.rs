@@ -1,11 +1,21 @@
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use futures_util::FutureExt;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Notify};
 
 #[derive(Debug, Clone)]
 pub enum AgentEvent {
     ConversationStarted {
         input: String,
     },
+    RunOverridesApplied {
+        overrides: crate::agent::RunOverrides,
+    },
     LlmRequestSent {
         messages: Vec<crate::provider::Message>,
     },
@@ -27,39 +37,861 @@ pub enum AgentEvent {
         call: crate::tool::ToolCall,
         error: String,
     },
+    /// Wall-clock time a tool call took, reported before its outcome
+    ToolCallTimed {
+        call_id: String,
+        tool: String,
+        duration_ms: u64,
+    },
     ConversationCompleted {
         response: String,
     },
     ConversationFailed {
         error: String,
     },
+    /// A request is blocked by the provider's client-side rate limiter and
+    /// resumes in about `wait_ms`
+    RateLimitWait {
+        provider: String,
+        wait_ms: u64,
+    },
+    /// Tools were registered or unregistered during a run; the new set is
+    /// offered from the next LLM request
+    ToolsChanged {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// The model called a tool by a deprecated alias; the call runs as
+    /// `tool`
+    DeprecatedToolAlias {
+        alias: String,
+        tool: String,
+    },
+    /// The conversation reached `threshold` of the model's context window
+    ContextPressure {
+        used_tokens: usize,
+        max_tokens: usize,
+        threshold: f64,
+    },
+    /// The run stopped at a step boundary because its `PauseHandle` was paused
+    RunPaused,
+    RunResumed,
+    /// A tool call, or the whole run when `tool` is None, hit its time limit
+    Timeout {
+        tool: Option<String>,
+        limit_ms: u64,
+    },
+    /// The provider moved this conversation to another backend, e.g. after
+    /// its pinned pool member was ejected
+    ProviderSwitched {
+        switch: crate::provider::ProviderSwitch,
+    },
+    /// Nobody decided a tool call's approval in time; the manager's default
+    /// decision was applied
+    ApprovalTimedOut {
+        timeout: crate::tool::ApprovalTimeout,
+    },
+    /// A hook returned an error that its `HookErrorPolicy::Log` let through
+    HookFailed {
+        failure: crate::hooks::HookFailure,
+    },
+    /// The run stopped early because it would have used more than
+    /// `max_total_tokens`
+    BudgetExceeded {
+        /// Tokens used, plus the estimated prompt when the next request was
+        /// not sent
+        used_tokens: usize,
+        max_tokens: usize,
+    },
+}
+
+/// Event class used for sampling and aggregated counts
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    ConversationStarted,
+    RunOverridesApplied,
+    LlmRequestSent,
+    LlmResponseReceived,
+    ToolCallsDetected,
+    ToolCallStarted,
+    ToolCallCompleted,
+    ToolCallFailed,
+    ToolCallTimed,
+    ConversationCompleted,
+    ConversationFailed,
+    RateLimitWait,
+    ToolsChanged,
+    DeprecatedToolAlias,
+    ContextPressure,
+    RunPaused,
+    RunResumed,
+    Timeout,
+    ProviderSwitched,
+    ApprovalTimedOut,
+    HookFailed,
+    BudgetExceeded,
+}
+
+impl AgentEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            AgentEvent::ConversationStarted { .. } => EventKind::ConversationStarted,
+            AgentEvent::RunOverridesApplied { .. } => EventKind::RunOverridesApplied,
+            AgentEvent::LlmRequestSent { .. } => EventKind::LlmRequestSent,
+            AgentEvent::LlmResponseReceived { .. } => EventKind::LlmResponseReceived,
+            AgentEvent::ToolCallsDetected { .. } => EventKind::ToolCallsDetected,
+            AgentEvent::ToolCallStarted { .. } => EventKind::ToolCallStarted,
+            AgentEvent::ToolCallCompleted { .. } => EventKind::ToolCallCompleted,
+            AgentEvent::ToolCallFailed { .. } => EventKind::ToolCallFailed,
+            AgentEvent::ToolCallTimed { .. } => EventKind::ToolCallTimed,
+            AgentEvent::ConversationCompleted { .. } => EventKind::ConversationCompleted,
+            AgentEvent::ConversationFailed { .. } => EventKind::ConversationFailed,
+            AgentEvent::RateLimitWait { .. } => EventKind::RateLimitWait,
+            AgentEvent::ToolsChanged { .. } => EventKind::ToolsChanged,
+            AgentEvent::DeprecatedToolAlias { .. } => EventKind::DeprecatedToolAlias,
+            AgentEvent::ContextPressure { .. } => EventKind::ContextPressure,
+            AgentEvent::RunPaused => EventKind::RunPaused,
+            AgentEvent::RunResumed => EventKind::RunResumed,
+            AgentEvent::Timeout { .. } => EventKind::Timeout,
+            AgentEvent::ProviderSwitched { .. } => EventKind::ProviderSwitched,
+            AgentEvent::ApprovalTimedOut { .. } => EventKind::ApprovalTimedOut,
+            AgentEvent::HookFailed { .. } => EventKind::HookFailed,
+            AgentEvent::BudgetExceeded { .. } => EventKind::BudgetExceeded,
+        }
+    }
+}
+
+impl EventKind {
+    /// Snake-case name, as serialized
+    pub(crate) fn name(self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
 }
 
 pub type EventHandler = Arc<dyn Fn(&AgentEvent) + Send + Sync>;
 
+/// Callback registered with `EventBus::on`; an `Err` is counted as a failure
+pub type AsyncEventHandler = Arc<
+    dyn Fn(AgentEvent) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync,
+>;
+
+/// Handle for a callback registered with `EventBus::on`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallbackId(u64);
+
+/// Per-callback delivery counters, see `EventBus::callback_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallbackStats {
+    /// Invocations spawned
+    pub invocations: u64,
+    /// Invocations that returned `Err`
+    pub failures: u64,
+    /// Invocations that panicked
+    pub panics: u64,
+    /// Events not delivered because `emit` ran outside a Tokio runtime
+    pub skipped: u64,
+    /// Error or panic message of the most recent failed invocation
+    pub last_error: Option<String>,
+}
+
+struct Callback {
+    id: CallbackId,
+    handler: AsyncEventHandler,
+    stats: Arc<Mutex<CallbackStats>>,
+}
+
+impl Callback {
+    /// Run the handler on its own task so a slow or panicking callback never
+    /// blocks `emit` or affects other callbacks
+    fn dispatch(&self, event: &AgentEvent) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.stats.lock().unwrap().skipped += 1;
+            return;
+        };
+        self.stats.lock().unwrap().invocations += 1;
+
+        let handler = self.handler.clone();
+        let stats = self.stats.clone();
+        let event = event.clone();
+        runtime.spawn(async move {
+            // Building the future runs user code too, so isolate it as well
+            let outcome = match std::panic::catch_unwind(AssertUnwindSafe(|| handler(event))) {
+                Ok(future) => AssertUnwindSafe(future).catch_unwind().await,
+                Err(panic) => Err(panic),
+            };
+            let mut stats = stats.lock().unwrap();
+            match outcome {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => {
+                    stats.failures += 1;
+                    stats.last_error = Some(error);
+                }
+                Err(panic) => {
+                    stats.panics += 1;
+                    stats.last_error = Some(panic_message(panic.as_ref()));
+                }
+            }
+        });
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "callback panicked".to_string()
+    }
+}
+
+/// Caller-supplied tags of a run, e.g. ticket id, user id or experiment
+pub type RunMetadata = Arc<HashMap<String, String>>;
+
+/// Event with the metadata of the run that emitted it, see
+/// `EventBus::subscribe_with_metadata`
+#[derive(Debug, Clone)]
+pub struct RunEvent {
+    pub event: AgentEvent,
+    /// Empty for events emitted outside a run or without metadata
+    pub metadata: RunMetadata,
+}
+
+/// Item delivered to a queued or persistent subscriber
+// Nearly every delivery is an event, so boxing it would only add an allocation
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum EventDelivery {
+    Event(AgentEvent),
+    /// `n` events were dropped at this point because the subscriber fell behind
+    Lagged(u64),
+}
+
 pub struct EventBus {
     sender: broadcast::Sender<AgentEvent>,
+    tagged: broadcast::Sender<RunEvent>,
+    queued: Arc<Mutex<Vec<QueuedSender>>>,
+    log: Option<Arc<EventLog>>,
+    callbacks: Arc<Mutex<Vec<Callback>>>,
+    next_callback_id: Arc<AtomicU64>,
 }
 
 impl EventBus {
     pub fn new(capacity: usize) -> Self {
         let (sender, _) = broadcast::channel(capacity);
-        Self { sender }
+        let (tagged, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            tagged,
+            queued: Arc::new(Mutex::new(Vec::new())),
+            log: None,
+            callbacks: Arc::new(Mutex::new(Vec::new())),
+            next_callback_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Record emitted events in an in-memory log for persistent subscribers
+    ///
+    /// The log keeps the most recent `retention` events; pass `usize::MAX` to
+    /// keep everything.
+    pub fn with_event_log(mut self, retention: usize) -> Self {
+        self.log = Some(Arc::new(EventLog::new(retention)));
+        self
     }
 
     pub fn emit(&self, event: AgentEvent) {
+        self.emit_with_metadata(event, RunMetadata::default());
+    }
+
+    /// Emit an event on behalf of a run tagged with `metadata`
+    ///
+    /// Only `subscribe_with_metadata` receivers see the metadata.
+    pub fn emit_with_metadata(&self, event: AgentEvent, metadata: RunMetadata) {
+        if self.tagged.receiver_count() > 0 {
+            let _ = self.tagged.send(RunEvent {
+                event: event.clone(),
+                metadata,
+            });
+        }
+        if let Some(log) = &self.log {
+            log.append(event.clone());
+        }
+
+        let mut queued = self.queued.lock().unwrap();
+        queued.retain(|subscriber| subscriber.deliver(&event));
+        drop(queued);
+
+        for callback in self.callbacks.lock().unwrap().iter() {
+            callback.dispatch(&event);
+        }
+
         let _ = self.sender.send(event);
     }
 
+    /// Register an async callback invoked for every emitted event
+    ///
+    /// Each invocation runs on its own Tokio task, so invocations for
+    /// consecutive events may overlap. Errors and panics are recorded in
+    /// `callback_stats` and never reach `emit` or other callbacks.
+    pub fn on<F, Fut>(&self, callback: F) -> CallbackId
+    where
+        F: Fn(AgentEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let id = CallbackId(self.next_callback_id.fetch_add(1, Ordering::Relaxed));
+        let handler: AsyncEventHandler = Arc::new(move |event| Box::pin(callback(event)));
+        self.callbacks.lock().unwrap().push(Callback {
+            id,
+            handler,
+            stats: Arc::new(Mutex::new(CallbackStats::default())),
+        });
+        id
+    }
+
+    /// Unregister a callback; returns false if it was not registered
+    pub fn off(&self, id: CallbackId) -> bool {
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let before = callbacks.len();
+        callbacks.retain(|callback| callback.id != id);
+        callbacks.len() != before
+    }
+
+    /// Delivery counters for a registered callback
+    pub fn callback_stats(&self, id: CallbackId) -> Option<CallbackStats> {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|callback| callback.id == id)
+            .map(|callback| callback.stats.lock().unwrap().clone())
+    }
+
+    /// Subscribe through the shared broadcast channel
+    ///
+    /// A slow receiver gets `RecvError::Lagged` and skips ahead; use
+    /// `subscribe_queued` or `subscribe_persistent` for explicit gap handling.
     pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
         self.sender.subscribe()
     }
+
+    /// Like `subscribe`, with each event's run metadata for filtering and
+    /// cost attribution
+    pub fn subscribe_with_metadata(&self) -> broadcast::Receiver<RunEvent> {
+        self.tagged.subscribe()
+    }
+
+    /// Subscribe with a dedicated bounded queue
+    ///
+    /// When the queue is full, new events are dropped for this subscriber only
+    /// and a `Lagged(n)` marker is delivered where the gap occurred.
+    pub fn subscribe_queued(&self, capacity: usize) -> QueuedSubscriber {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.queued.lock().unwrap().push(QueuedSender {
+            tx,
+            dropped: dropped.clone(),
+        });
+        QueuedSubscriber { rx, dropped }
+    }
+
+    /// Subscribe with batching and per-kind sampling, see `EventBatchConfig`
+    pub fn subscribe_batched(&self, config: EventBatchConfig) -> BatchedSubscriber {
+        BatchedSubscriber {
+            inner: self.subscribe_queued(config.queue_capacity),
+            config,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Subscribe by reading the event log, starting at the oldest retained event
+    ///
+    /// Returns `None` if the bus was created without `with_event_log`.
+    pub fn subscribe_persistent(&self) -> Option<PersistentSubscriber> {
+        let log = self.log.clone()?;
+        let cursor = log.state.lock().unwrap().first_seq;
+        Some(PersistentSubscriber { log, cursor })
+    }
 }
 
 impl Clone for EventBus {
     fn clone(&self) -> Self {
         Self {
             sender: self.sender.clone(),
+            tagged: self.tagged.clone(),
+            queued: self.queued.clone(),
+            log: self.log.clone(),
+            callbacks: self.callbacks.clone(),
+            next_callback_id: self.next_callback_id.clone(),
+        }
+    }
+}
+
+struct QueuedSender {
+    tx: mpsc::Sender<EventDelivery>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl QueuedSender {
+    /// Try to enqueue an event; returns false once the subscriber is gone
+    fn deliver(&self, event: &AgentEvent) -> bool {
+        use mpsc::error::TrySendError;
+
+        // Report an earlier gap before any newer event
+        let pending = self.dropped.swap(0, Ordering::AcqRel);
+        if pending > 0 {
+            match self.tx.try_send(EventDelivery::Lagged(pending)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(pending + 1, Ordering::AcqRel);
+                    return true;
+                }
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+
+        match self.tx.try_send(EventDelivery::Event(event.clone())) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::AcqRel);
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Subscriber with its own bounded queue, see `EventBus::subscribe_queued`
+pub struct QueuedSubscriber {
+    rx: mpsc::Receiver<EventDelivery>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl QueuedSubscriber {
+    /// Receive the next event or lag notification
+    ///
+    /// Returns `None` once every `EventBus` handle has been dropped.
+    pub async fn recv(&mut self) -> Option<EventDelivery> {
+        match self.rx.try_recv() {
+            Ok(delivery) => return Some(delivery),
+            Err(mpsc::error::TryRecvError::Empty) => {}
+            Err(mpsc::error::TryRecvError::Disconnected) => return self.take_lagged(),
+        }
+        // Queue drained: report drops that have not been flushed by a later emit
+        if let Some(lagged) = self.take_lagged() {
+            return Some(lagged);
+        }
+        match self.rx.recv().await {
+            Some(delivery) => Some(delivery),
+            None => self.take_lagged(),
+        }
+    }
+
+    fn take_lagged(&self) -> Option<EventDelivery> {
+        match self.dropped.swap(0, Ordering::AcqRel) {
+            0 => None,
+            n => Some(EventDelivery::Lagged(n)),
+        }
+    }
+}
+
+/// Batching and sampling settings for `EventBus::subscribe_batched`
+#[derive(Debug, Clone)]
+pub struct EventBatchConfig {
+    /// Flush once this many sampled events are in the batch
+    pub max_events: usize,
+    /// Flush this long after the first event of a batch arrived
+    pub max_delay: Duration,
+    /// Fraction of events of each kind to keep, in `0.0..=1.0`; kinds not
+    /// listed are kept in full
+    pub sample_rates: HashMap<EventKind, f64>,
+    /// Capacity of the underlying queue, see `EventBus::subscribe_queued`
+    pub queue_capacity: usize,
+}
+
+impl Default for EventBatchConfig {
+    fn default() -> Self {
+        Self {
+            max_events: 100,
+            max_delay: Duration::from_millis(100),
+            sample_rates: HashMap::new(),
+            queue_capacity: 1024,
+        }
+    }
+}
+
+impl EventBatchConfig {
+    pub fn new(max_events: usize, max_delay: Duration) -> Self {
+        Self {
+            max_events: max_events.max(1),
+            max_delay,
+            ..Default::default()
+        }
+    }
+
+    /// Keep `rate` of the events of `kind`
+    pub fn sample(mut self, kind: EventKind, rate: f64) -> Self {
+        self.sample_rates.insert(kind, rate.clamp(0.0, 1.0));
+        self
+    }
+
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+}
+
+/// Events delivered together by a `BatchedSubscriber`
+#[derive(Debug, Clone, Default)]
+pub struct EventBatch {
+    /// Events that survived sampling, in emission order
+    pub events: Vec<AgentEvent>,
+    /// Exact number of events of each kind observed, including sampled-out ones
+    pub counts: HashMap<EventKind, u64>,
+    /// Events lost because the subscriber fell behind; their kinds are unknown
+    pub dropped: u64,
+}
+
+impl EventBatch {
+    /// Number of events the batch accounts for
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum::<u64>() + self.dropped
+    }
+}
+
+/// Subscriber that groups and samples events, see `EventBus::subscribe_batched`
+pub struct BatchedSubscriber {
+    inner: QueuedSubscriber,
+    config: EventBatchConfig,
+    /// Events observed so far per sampled kind
+    seen: HashMap<EventKind, u64>,
+}
+
+impl BatchedSubscriber {
+    /// Wait for the next batch
+    ///
+    /// A batch starts with the next event and is flushed when it holds
+    /// `max_events` sampled events or `max_delay` has elapsed. Returns `None`
+    /// once every `EventBus` handle has been dropped and nothing is pending.
+    pub async fn recv(&mut self) -> Option<EventBatch> {
+        let mut batch = EventBatch::default();
+        let first = self.inner.recv().await?;
+        self.add(&mut batch, first);
+
+        let deadline = tokio::time::Instant::now() + self.config.max_delay;
+        while batch.events.len() < self.config.max_events {
+            match tokio::time::timeout_at(deadline, self.inner.recv()).await {
+                Ok(Some(delivery)) => self.add(&mut batch, delivery),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        Some(batch)
+    }
+
+    fn add(&mut self, batch: &mut EventBatch, delivery: EventDelivery) {
+        let event = match delivery {
+            EventDelivery::Event(event) => event,
+            EventDelivery::Lagged(n) => {
+                batch.dropped += n;
+                return;
+            }
+        };
+        let kind = event.kind();
+        *batch.counts.entry(kind).or_insert(0) += 1;
+        if self.keep(kind) {
+            batch.events.push(event);
+        }
+    }
+
+    /// Deterministic sampling: keep the event whenever the running quota
+    /// `seen * rate` crosses an integer, so the first event is always kept
+    fn keep(&mut self, kind: EventKind) -> bool {
+        let Some(&rate) = self.config.sample_rates.get(&kind) else {
+            return true;
+        };
+        let seen = self.seen.entry(kind).or_insert(0);
+        let before = (*seen as f64 * rate).ceil();
+        *seen += 1;
+        (*seen as f64 * rate).ceil() > before
+    }
+}
+
+struct EventLogState {
+    events: VecDeque<AgentEvent>,
+    /// Sequence number of the first retained event
+    first_seq: u64,
+}
+
+struct EventLog {
+    state: Mutex<EventLogState>,
+    retention: usize,
+    notify: Notify,
+}
+
+impl EventLog {
+    fn new(retention: usize) -> Self {
+        Self {
+            state: Mutex::new(EventLogState {
+                events: VecDeque::new(),
+                first_seq: 0,
+            }),
+            retention: retention.max(1),
+            notify: Notify::new(),
         }
     }
+
+    fn append(&self, event: AgentEvent) {
+        let mut state = self.state.lock().unwrap();
+        state.events.push_back(event);
+        while state.events.len() > self.retention {
+            state.events.pop_front();
+            state.first_seq += 1;
+        }
+        drop(state);
+        self.notify.notify_waiters();
+    }
+}
+
+/// Subscriber that reads from the event log, see `EventBus::subscribe_persistent`
+///
+/// Events are never dropped while they are still retained by the log; a
+/// subscriber that falls behind the retention window gets `Lagged(n)`.
+pub struct PersistentSubscriber {
+    log: Arc<EventLog>,
+    cursor: u64,
+}
+
+impl PersistentSubscriber {
+    /// Sequence number of the next event this subscriber will read
+    pub fn position(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Wait for the next event or lag notification
+    pub async fn recv(&mut self) -> EventDelivery {
+        let log = self.log.clone();
+        loop {
+            let notified = log.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(delivery) = self.try_recv() {
+                return delivery;
+            }
+            notified.await;
+        }
+    }
+
+    /// Read the next event without waiting
+    pub fn try_recv(&mut self) -> Option<EventDelivery> {
+        let state = self.log.state.lock().unwrap();
+        if self.cursor < state.first_seq {
+            let missed = state.first_seq - self.cursor;
+            self.cursor = state.first_seq;
+            return Some(EventDelivery::Lagged(missed));
+        }
+        let index = (self.cursor - state.first_seq) as usize;
+        let event = state.events.get(index)?.clone();
+        self.cursor += 1;
+        Some(EventDelivery::Event(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(input: &str) -> AgentEvent {
+        AgentEvent::ConversationStarted {
+            input: input.to_string(),
+        }
+    }
+
+    fn input_of(delivery: EventDelivery) -> String {
+        match delivery {
+            EventDelivery::Event(AgentEvent::ConversationStarted { input }) => input,
+            other => panic!("unexpected delivery: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_subscriber_reports_lag_where_events_were_dropped() {
+        let bus = EventBus::new(16);
+        let mut subscriber = bus.subscribe_queued(2);
+
+        for i in 0..5 {
+            bus.emit(started(&i.to_string()));
+        }
+
+        assert_eq!(input_of(subscriber.recv().await.unwrap()), "0");
+        assert_eq!(input_of(subscriber.recv().await.unwrap()), "1");
+        assert!(matches!(
+            subscriber.recv().await,
+            Some(EventDelivery::Lagged(3))
+        ));
+
+        bus.emit(started("5"));
+        assert_eq!(input_of(subscriber.recv().await.unwrap()), "5");
+    }
+
+    #[tokio::test]
+    async fn queued_subscriber_ends_when_bus_is_dropped() {
+        let bus = EventBus::new(16);
+        let mut subscriber = bus.subscribe_queued(4);
+        bus.emit(started("last"));
+        drop(bus);
+
+        assert_eq!(input_of(subscriber.recv().await.unwrap()), "last");
+        assert!(subscriber.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn persistent_subscriber_replays_log_and_waits_for_new_events() {
+        let bus = EventBus::new(1).with_event_log(usize::MAX);
+        bus.emit(started("before"));
+
+        let mut subscriber = bus.subscribe_persistent().unwrap();
+        assert_eq!(input_of(subscriber.recv().await), "before");
+
+        let emitter = bus.clone();
+        tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            emitter.emit(started("after"));
+        });
+        assert_eq!(input_of(subscriber.recv().await), "after");
+        assert_eq!(subscriber.position(), 2);
+    }
+
+    #[tokio::test]
+    async fn persistent_subscriber_lags_behind_retention_window() {
+        let bus = EventBus::new(1).with_event_log(2);
+        let mut subscriber = bus.subscribe_persistent().unwrap();
+
+        for i in 0..4 {
+            bus.emit(started(&i.to_string()));
+        }
+
+        assert!(matches!(subscriber.recv().await, EventDelivery::Lagged(2)));
+        assert_eq!(input_of(subscriber.recv().await), "2");
+        assert_eq!(input_of(subscriber.recv().await), "3");
+        assert!(subscriber.try_recv().is_none());
+    }
+
+    #[test]
+    fn persistent_subscription_requires_event_log() {
+        assert!(EventBus::new(1).subscribe_persistent().is_none());
+    }
+
+    #[tokio::test]
+    async fn callbacks_isolate_errors_and_panics() {
+        let bus = EventBus::new(16);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let ok = bus.on(move |event| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(event);
+                Ok(())
+            }
+        });
+        let failing = bus.on(|_| async { Err("handler failed".to_string()) });
+        let panicking = bus.on(|_| async {
+            panic!("handler panicked");
+            #[allow(unreachable_code)]
+            Ok(())
+        });
+
+        bus.emit(started("a"));
+        bus.emit(started("b"));
+
+        let mut received = [rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        received.sort_by_key(|e| format!("{:?}", e));
+        assert!(matches!(&received[0], AgentEvent::ConversationStarted { input } if input == "a"));
+
+        // Let the failing tasks finish
+        for _ in 0..100 {
+            let failing = bus.callback_stats(failing).unwrap();
+            let panicking = bus.callback_stats(panicking).unwrap();
+            if failing.failures == 2 && panicking.panics == 2 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        let stats = bus.callback_stats(failing).unwrap();
+        assert_eq!(stats.invocations, 2);
+        assert_eq!(stats.failures, 2);
+        assert_eq!(stats.last_error.as_deref(), Some("handler failed"));
+
+        let stats = bus.callback_stats(panicking).unwrap();
+        assert_eq!(stats.panics, 2);
+        assert_eq!(stats.last_error.as_deref(), Some("handler panicked"));
+
+        assert_eq!(bus.callback_stats(ok).unwrap().failures, 0);
+        assert!(bus.off(ok));
+        assert!(bus.callback_stats(ok).is_none());
+    }
+
+    #[test]
+    fn callbacks_are_skipped_outside_a_runtime() {
+        let bus = EventBus::new(16);
+        let id = bus.on(|_| async { Ok(()) });
+        bus.emit(started("a"));
+
+        let stats = bus.callback_stats(id).unwrap();
+        assert_eq!(stats.invocations, 0);
+        assert_eq!(stats.skipped, 1);
+    }
+
+    fn tool_started(name: &str) -> AgentEvent {
+        AgentEvent::ToolCallStarted {
+            call: crate::tool::ToolCall {
+                id: name.to_string(),
+                name: name.to_string(),
+                parameters: serde_json::json!({}),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn batched_subscriber_samples_but_counts_every_event() {
+        let bus = EventBus::new(16);
+        let config = EventBatchConfig::new(100, Duration::from_millis(20))
+            .sample(EventKind::ToolCallStarted, 0.25);
+        let mut subscriber = bus.subscribe_batched(config);
+
+        bus.emit(started("a"));
+        for i in 0..8 {
+            bus.emit(tool_started(&i.to_string()));
+        }
+
+        let batch = subscriber.recv().await.unwrap();
+        assert_eq!(batch.counts[&EventKind::ConversationStarted], 1);
+        assert_eq!(batch.counts[&EventKind::ToolCallStarted], 8);
+        assert_eq!(batch.total(), 9);
+        // One conversation event plus 2 of the 8 tool events
+        assert_eq!(batch.events.len(), 3);
+        assert_eq!(batch.events[1].kind(), EventKind::ToolCallStarted);
+    }
+
+    #[tokio::test]
+    async fn batched_subscriber_flushes_on_size() {
+        let bus = EventBus::new(16);
+        let mut subscriber =
+            bus.subscribe_batched(EventBatchConfig::new(2, Duration::from_secs(60)));
+
+        for input in ["a", "b", "c"] {
+            bus.emit(started(input));
+        }
+
+        assert_eq!(subscriber.recv().await.unwrap().events.len(), 2);
+        drop(bus);
+        let last = subscriber.recv().await.unwrap();
+        assert_eq!(last.events.len(), 1);
+        assert!(subscriber.recv().await.is_none());
+    }
 }
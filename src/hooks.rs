@@ -1,8 +1,245 @@
+use crate::error::{AgentError, Result};
 use crate::events::{AgentEvent, EventBus};
+use crate::provider::{GenerateResponse, Message};
+use crate::tool::{ToolCall, ToolResult};
+use async_trait::async_trait;
 use std::sync::Arc;
 
+/// What the agent loop does after a hook returns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookOutcome {
+    /// Run the next hook registered for this point
+    Continue,
+    /// Skip the remaining hooks for this point and carry on with the run
+    Stop,
+    /// Fail the run with `AgentError::HookAborted`
+    Abort(String),
+}
+
+/// How the agent treats a hook that returns `Err`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookErrorPolicy {
+    /// Report the error as `AgentEvent::HookFailed`, and as a `tracing`
+    /// warning with the `tracing` feature, then run the next hook
+    #[default]
+    Log,
+    /// Run the next hook without reporting
+    Ignore,
+    /// Fail the run with the hook's error
+    Abort,
+}
+
+/// Async hook into the agent loop
+///
+/// Execution points, in order, for `Agent::run` and `Agent::run_with_overrides`:
+///
+/// 1. `on_run_start` once, before the conversation is built
+/// 2. per iteration, `before_llm_request` with the messages about to be sent
+/// 3. `after_llm_response` with the post-processed response
+/// 4. per tool call, `before_tool_call` then `after_tool_call`
+/// 5. `on_run_end` once with the final result, including failures
+///
+/// `Agent::run_stream` without tools only reaches points 1 and 2. Changes
+/// made to messages in `before_llm_request` apply to that request only and
+/// are not kept in the conversation. Hooks run sequentially in registration
+/// order.
+#[async_trait]
+pub trait AgentHook: Send + Sync {
+    /// Name used in error messages
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    async fn on_run_start(&self, input: &str) -> Result<HookOutcome> {
+        let _ = input;
+        Ok(HookOutcome::Continue)
+    }
+
+    async fn before_llm_request(&self, messages: &mut Vec<Message>) -> Result<HookOutcome> {
+        let _ = messages;
+        Ok(HookOutcome::Continue)
+    }
+
+    async fn after_llm_response(&self, response: &mut GenerateResponse) -> Result<HookOutcome> {
+        let _ = response;
+        Ok(HookOutcome::Continue)
+    }
+
+    /// Runs for allowed and disallowed tools alike; the call may be rewritten
+    async fn before_tool_call(&self, call: &mut ToolCall) -> Result<HookOutcome> {
+        let _ = call;
+        Ok(HookOutcome::Continue)
+    }
+
+    async fn after_tool_call(
+        &self,
+        call: &ToolCall,
+        result: &mut ToolResult,
+    ) -> Result<HookOutcome> {
+        let _ = (call, result);
+        Ok(HookOutcome::Continue)
+    }
+
+    /// Observe the outcome of the run; cannot change it
+    async fn on_run_end(&self, result: std::result::Result<&str, &AgentError>) {
+        let _ = result;
+    }
+}
+
+/// A hook error reported under `HookErrorPolicy::Log`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookFailure {
+    pub hook: String,
+    /// Execution point, e.g. `before_tool_call`
+    pub point: &'static str,
+    pub error: String,
+}
+
+struct HookEntry {
+    hook: Arc<dyn AgentHook>,
+    policy: HookErrorPolicy,
+}
+
+/// Ordered set of `AgentHook`s attached to an agent
+#[derive(Clone, Default)]
+pub struct HookRegistry {
+    entries: Vec<Arc<HookEntry>>,
+}
+
+impl HookRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a hook with the default `HookErrorPolicy::Log`
+    pub fn add(&mut self, hook: Arc<dyn AgentHook>) {
+        self.add_with_policy(hook, HookErrorPolicy::default());
+    }
+
+    /// Append a hook; hooks run in the order they were added
+    pub fn add_with_policy(&mut self, hook: Arc<dyn AgentHook>, policy: HookErrorPolicy) {
+        self.entries.push(Arc::new(HookEntry { hook, policy }));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Apply the outcome and error policy; returns whether to run the next
+    /// hook, recording logged errors in `failures`
+    fn resolve(
+        entry: &HookEntry,
+        point: &'static str,
+        result: Result<HookOutcome>,
+        failures: &mut Vec<HookFailure>,
+    ) -> Result<bool> {
+        match result {
+            Ok(HookOutcome::Continue) => Ok(true),
+            Ok(HookOutcome::Stop) => Ok(false),
+            Ok(HookOutcome::Abort(reason)) => Err(AgentError::HookAborted {
+                hook: entry.hook.name().to_string(),
+                reason,
+            }),
+            Err(error) => match entry.policy {
+                HookErrorPolicy::Log => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(hook = entry.hook.name(), point, %error, "Hook failed");
+                    failures.push(HookFailure {
+                        hook: entry.hook.name().to_string(),
+                        point,
+                        error: error.to_string(),
+                    });
+                    Ok(true)
+                }
+                HookErrorPolicy::Ignore => Ok(true),
+                HookErrorPolicy::Abort => Err(error),
+            },
+        }
+    }
+
+    pub(crate) async fn run_start(&self, input: &str) -> Result<Vec<HookFailure>> {
+        let mut failures = Vec::new();
+        for entry in &self.entries {
+            let result = entry.hook.on_run_start(input).await;
+            if !Self::resolve(entry, "on_run_start", result, &mut failures)? {
+                break;
+            }
+        }
+        Ok(failures)
+    }
+
+    pub(crate) async fn before_llm_request(
+        &self,
+        messages: &mut Vec<Message>,
+    ) -> Result<Vec<HookFailure>> {
+        let mut failures = Vec::new();
+        for entry in &self.entries {
+            let result = entry.hook.before_llm_request(messages).await;
+            if !Self::resolve(entry, "before_llm_request", result, &mut failures)? {
+                break;
+            }
+        }
+        Ok(failures)
+    }
+
+    pub(crate) async fn after_llm_response(
+        &self,
+        response: &mut GenerateResponse,
+    ) -> Result<Vec<HookFailure>> {
+        let mut failures = Vec::new();
+        for entry in &self.entries {
+            let result = entry.hook.after_llm_response(response).await;
+            if !Self::resolve(entry, "after_llm_response", result, &mut failures)? {
+                break;
+            }
+        }
+        Ok(failures)
+    }
+
+    pub(crate) async fn before_tool_call(&self, call: &mut ToolCall) -> Result<Vec<HookFailure>> {
+        let mut failures = Vec::new();
+        for entry in &self.entries {
+            let result = entry.hook.before_tool_call(call).await;
+            if !Self::resolve(entry, "before_tool_call", result, &mut failures)? {
+                break;
+            }
+        }
+        Ok(failures)
+    }
+
+    pub(crate) async fn after_tool_call(
+        &self,
+        call: &ToolCall,
+        result: &mut ToolResult,
+    ) -> Result<Vec<HookFailure>> {
+        let mut failures = Vec::new();
+        for entry in &self.entries {
+            let outcome = entry.hook.after_tool_call(call, result).await;
+            if !Self::resolve(entry, "after_tool_call", outcome, &mut failures)? {
+                break;
+            }
+        }
+        Ok(failures)
+    }
+
+    pub(crate) async fn run_end(&self, result: &Result<String>) {
+        let result = result.as_ref().map(String::as_str);
+        for entry in &self.entries {
+            entry.hook.on_run_end(result).await;
+        }
+    }
+}
+
 pub type HookFn = Arc<dyn Fn(&AgentEvent) -> bool + Send + Sync>;
 
+/// Event-bus observer running closures on a background task
+///
+/// Hooks registered here only see events after the fact and cannot change
+/// the run; implement `AgentHook` to influence the agent loop.
 pub struct HookManager {
     event_bus: Arc<EventBus>,
     hooks: Vec<HookFn>,
@@ -28,7 +265,13 @@ impl HookManager {
         let hooks = self.hooks.clone();
 
         tokio::spawn(async move {
-            while let Ok(event) = receiver.recv().await {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    // Keep monitoring after falling behind instead of stopping
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
                 for hook in &hooks {
                     if !hook(&event) {
                         // Hook 返回 false 表示停止处理
@@ -119,3 +362,104 @@ impl HookManager {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Recording {
+        label: &'static str,
+        outcome: HookOutcome,
+        fail: bool,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl AgentHook for Recording {
+        fn name(&self) -> &str {
+            self.label
+        }
+
+        async fn on_run_start(&self, _input: &str) -> Result<HookOutcome> {
+            self.log.lock().unwrap().push(self.label);
+            if self.fail {
+                return Err(AgentError::ParseError("boom".to_string()));
+            }
+            Ok(self.outcome.clone())
+        }
+    }
+
+    fn hook(
+        label: &'static str,
+        outcome: HookOutcome,
+        fail: bool,
+        log: &Arc<Mutex<Vec<&'static str>>>,
+    ) -> Arc<dyn AgentHook> {
+        Arc::new(Recording {
+            label,
+            outcome,
+            fail,
+            log: log.clone(),
+        })
+    }
+
+    #[tokio::test]
+    async fn runs_in_order_until_stopped() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = HookRegistry::new();
+        hooks.add_with_policy(
+            hook("failing", HookOutcome::Continue, true, &log),
+            HookErrorPolicy::Ignore,
+        );
+        hooks.add(hook("first", HookOutcome::Continue, false, &log));
+        hooks.add(hook("stopper", HookOutcome::Stop, false, &log));
+        hooks.add(hook("skipped", HookOutcome::Continue, false, &log));
+
+        let failures = hooks.run_start("hi").await.unwrap();
+        assert_eq!(*log.lock().unwrap(), vec!["failing", "first", "stopper"]);
+        assert!(failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn log_policy_reports_the_error_and_continues() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = HookRegistry::new();
+        hooks.add(hook("failing", HookOutcome::Continue, true, &log));
+        hooks.add(hook("next", HookOutcome::Continue, false, &log));
+
+        let failures = hooks.run_start("hi").await.unwrap();
+        assert_eq!(*log.lock().unwrap(), vec!["failing", "next"]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].hook, "failing");
+        assert_eq!(failures[0].point, "on_run_start");
+        assert!(failures[0].error.contains("boom"));
+    }
+
+    #[tokio::test]
+    async fn abort_outcome_and_abort_policy_fail_the_run() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let mut hooks = HookRegistry::new();
+        hooks.add(hook(
+            "guard",
+            HookOutcome::Abort("no".to_string()),
+            false,
+            &log,
+        ));
+        assert!(matches!(
+            hooks.run_start("hi").await,
+            Err(AgentError::HookAborted { hook, reason }) if hook == "guard" && reason == "no"
+        ));
+
+        let mut hooks = HookRegistry::new();
+        hooks.add_with_policy(
+            hook("strict", HookOutcome::Continue, true, &log),
+            HookErrorPolicy::Abort,
+        );
+        assert!(matches!(
+            hooks.run_start("hi").await,
+            Err(AgentError::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+//! Random identifiers.
+//!
+//! Ids are UUID v4, so ids generated concurrently, in other tasks or other
+//! processes, do not collide.
+
+use uuid::Uuid;
+
+/// A new random id, e.g. `0b6c4c1e-8f0a-4d8e-9a43-5f2c7e1d9b20`
+pub fn new_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// A new id for a tool call whose provider did not assign one
+pub fn tool_call_id() -> String {
+    format!("call_{}", Uuid::new_v4().simple())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[tokio::test]
+    async fn ids_from_concurrent_tasks_are_unique() {
+        let tasks: Vec<_> = (0..8)
+            .map(|_| tokio::spawn(async { (0..1_000).map(|_| new_id()).collect::<Vec<_>>() }))
+            .collect();
+        let mut ids = HashSet::new();
+        for task in tasks {
+            ids.extend(task.await.unwrap());
+        }
+        assert_eq!(ids.len(), 8_000);
+        assert!(tool_call_id().starts_with("call_"));
+    }
+}
@@ -1,17 +1,37 @@
 pub mod agent;
+pub mod bundle;
 pub mod error;
 pub mod events;
 pub mod hooks;
+pub mod ids;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+pub mod memory;
 pub mod provider;
+pub mod realtime;
+#[cfg(feature = "retrieval")]
+pub mod retrieval;
+pub mod schema;
+pub mod session;
+mod storage;
+pub mod testing;
 pub mod tool;
 
 pub use agent::*;
 pub use error::AgentError;
 pub use events::*;
 pub use hooks::*;
+#[cfg(feature = "providers")]
 pub use provider::{
-    AnthropicProvider, GenerateOptions, GenerateResponse, LlmProvider, Message, OpenRouterProvider,
-    Role, StreamResponse, Usage, ProviderError,
+    AnthropicProvider, OpenRouterProvider, OpenAIProvider, OllamaProvider, MistralProvider,
+    GroqProvider, OpenAiCompatProvider, AuthHeader, ProviderConfig, ProviderRegistry,
+};
+pub use provider::{
+    GenerateOptions, GenerateResponse, LlmProvider, Message,
+    FallbackProvider, ProviderPool, LoadBalanceStrategy,
+    Role, StreamResponse, Usage, ProviderError, DecodingConstraint, ProviderCapabilities,
+    JsonSchema, ResponseFormat,
+    StreamEvent, StreamEvents, ToolSchema,
     // Reliability features
     RetryConfig, RateLimitConfig, TimeoutConfig,
     // Middleware
@@ -22,8 +42,25 @@ pub use provider::{
     ContextWindowConfig, ContextWindowManager, TruncationStrategy,
     // Advanced features
     EmbeddingProvider, EmbeddingRequest, EmbeddingResponse,
+    FineTuneProvider, CreateFineTuneRequest, FineTuneJob, FineTuneStatus,
+    ImageGenerationProvider, ImageRequest, ImageResponse,
+    FileProvider, FileUpload, ProviderFile,
+    DeferredProvider, JobHandle, JobStatus,
     BatchRequest, SingleRequest, BatchResponse, execute_batch_concurrent, execute_batch_sequential,
     // Multimodal
     ContentBlock, ImageSource, ImageDetail,
 };
+pub use bundle::{ConversationBundle, BUNDLE_FORMAT, BUNDLE_VERSION};
+pub use session::{Checkpoint, FileSessionStore, InMemorySessionStore, Session, SessionStore};
+#[cfg(feature = "sqlite")]
+pub use session::SqliteSessionStore;
+pub use memory::{
+    AgentMemory, Episode, InMemoryStore, JsonlMemoryStore, Memory, MemoryStore, SummarizingMemory,
+};
+#[cfg(feature = "sqlite")]
+pub use memory::SqliteMemoryStore;
+#[cfg(feature = "redis")]
+pub use memory::RedisMemoryStore;
 pub use tool::*;
+pub use tokio_util::sync::CancellationToken;
+pub use schemars;
@@ -1,7 +1,4 @@
-mod provider;
-
-use crate::provider::LlmProvider;
-use provider::OpenRouterProvider;
+use agent_sdk::provider::{self, LlmProvider, OpenRouterProvider};
 use std::env;
 
 #[tokio::main]
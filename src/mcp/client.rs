@@ -0,0 +1,501 @@
+use super::transport::Transport;
+use super::{McpError, Result, PROTOCOL_VERSION};
+use crate::tool::{DangerLevel, Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+
+/// Tool advertised by an MCP server
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpToolInfo {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments
+    pub input_schema: Value,
+    /// From the `readOnlyHint` and `destructiveHint` annotations; tools
+    /// without them are assumed destructive
+    pub danger: DangerLevel,
+}
+
+impl McpToolInfo {
+    fn from_json(tool: &Value) -> Result<Self> {
+        let name = tool["name"]
+            .as_str()
+            .ok_or_else(|| McpError::Protocol("Tool without a name".to_string()))?;
+        Ok(Self {
+            name: name.to_string(),
+            description: tool["description"].as_str().unwrap_or_default().to_string(),
+            input_schema: match &tool["inputSchema"] {
+                Value::Null => serde_json::json!({"type": "object"}),
+                schema => schema.clone(),
+            },
+            danger: danger_level(&tool["annotations"]),
+        })
+    }
+}
+
+/// Danger level the server's hints claim, trusting a hint only where it
+/// lowers the level from the spec's default of destructive
+fn danger_level(annotations: &Value) -> DangerLevel {
+    if annotations["readOnlyHint"] == true {
+        DangerLevel::Safe
+    } else if annotations["destructiveHint"] == false {
+        DangerLevel::Moderate
+    } else {
+        DangerLevel::Destructive
+    }
+}
+
+/// Result of a remote tool call
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpToolOutput {
+    /// Content items, e.g. `{"type": "text", "text": "..."}`
+    pub content: Vec<Value>,
+    /// The tool itself reported a failure
+    pub is_error: bool,
+}
+
+impl McpToolOutput {
+    /// Text items joined by newlines, with placeholders for other content
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .map(|item| match item["type"].as_str() {
+                Some("text") => item["text"].as_str().unwrap_or_default().to_string(),
+                Some("resource") => match item["resource"]["text"].as_str() {
+                    Some(text) => text.to_string(),
+                    None => format!(
+                        "[resource: {}]",
+                        item["resource"]["uri"].as_str().unwrap_or_default()
+                    ),
+                },
+                Some(kind) => format!(
+                    "[{}: {}]",
+                    kind,
+                    item["mimeType"].as_str().unwrap_or("unknown type")
+                ),
+                None => item.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Connection to one MCP server
+pub struct McpClient {
+    outgoing: mpsc::UnboundedSender<Value>,
+    pending: Pending,
+    next_id: AtomicU64,
+    request_timeout: Duration,
+    server_info: Value,
+    dispatcher: JoinHandle<()>,
+    /// Server process for stdio connections, killed on drop
+    _child: Option<Child>,
+}
+
+impl McpClient {
+    /// Start `program` and talk to it over stdin and stdout
+    pub async fn connect_stdio<I, S>(program: impl AsRef<OsStr>, args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(program);
+        command.args(args);
+        Self::connect_command(command).await
+    }
+
+    /// Like `connect_stdio`, for a command with its own environment or
+    /// working directory
+    pub async fn connect_command(command: Command) -> Result<Self> {
+        Self::start(Transport::stdio(command)?).await
+    }
+
+    /// Connect to a server's SSE endpoint, e.g. `http://localhost:8000/sse`
+    pub async fn connect_sse(url: &str) -> Result<Self> {
+        Self::connect_sse_with(reqwest::Client::new(), url).await
+    }
+
+    /// Connect over SSE through `client`, e.g. one sending an
+    /// `Authorization` header by default
+    pub async fn connect_sse_with(client: reqwest::Client, url: &str) -> Result<Self> {
+        Self::start(Transport::sse(client, url).await?).await
+    }
+
+    async fn start(transport: Transport) -> Result<Self> {
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let dispatcher = tokio::spawn(dispatch(
+            transport.incoming,
+            pending.clone(),
+            transport.outgoing.downgrade(),
+        ));
+        let mut client = Self {
+            outgoing: transport.outgoing,
+            pending,
+            next_id: AtomicU64::new(1),
+            request_timeout: Duration::from_secs(30),
+            server_info: Value::Null,
+            dispatcher,
+            _child: transport.child,
+        };
+
+        let result = client
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "agent-sdk",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+        client.server_info = result["serverInfo"].clone();
+        client.notify("notifications/initialized")?;
+        Ok(client)
+    }
+
+    /// Longest to wait for each response; 30 seconds by default
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// `serverInfo` from the initialize handshake, e.g. name and version
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    /// Send a JSON-RPC request and wait for its result
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        if self.outgoing.send(message).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(McpError::Closed);
+        }
+
+        match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(McpError::Closed),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(McpError::Timeout(self.request_timeout))
+            }
+        }
+    }
+
+    fn notify(&self, method: &str) -> Result<()> {
+        self.outgoing
+            .send(serde_json::json!({"jsonrpc": "2.0", "method": method}))
+            .map_err(|_| McpError::Closed)
+    }
+
+    /// Every tool the server offers, following pagination
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => serde_json::json!({ "cursor": cursor }),
+                None => serde_json::json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            let page = result["tools"]
+                .as_array()
+                .ok_or_else(|| McpError::Protocol("tools/list result has no tools".to_string()))?;
+            for tool in page {
+                tools.push(McpToolInfo::from_json(tool)?);
+            }
+            match result["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<McpToolOutput> {
+        let result = self
+            .request(
+                "tools/call",
+                serde_json::json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        Ok(McpToolOutput {
+            content: result["content"].as_array().cloned().unwrap_or_default(),
+            is_error: result["isError"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// The server's tools as `Tool`s, ready for `Agent::register_tool`
+    pub async fn tools(self: &Arc<Self>) -> Result<Vec<McpTool>> {
+        Ok(self
+            .list_tools()
+            .await?
+            .into_iter()
+            .map(|info| McpTool {
+                client: self.clone(),
+                info,
+            })
+            .collect())
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+/// Route responses to waiting requests and answer server requests
+async fn dispatch(
+    mut incoming: mpsc::UnboundedReceiver<Value>,
+    pending: Pending,
+    outgoing: mpsc::WeakUnboundedSender<Value>,
+) {
+    while let Some(message) = incoming.recv().await {
+        let id = &message["id"];
+        if let Some(method) = message["method"].as_str() {
+            // Requests from the server need an answer; notifications do not
+            if !id.is_null() {
+                let reply = if method == "ping" {
+                    serde_json::json!({"jsonrpc": "2.0", "id": id, "result": {}})
+                } else {
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {"code": -32601, "message": format!("Method not found: {}", method)},
+                    })
+                };
+                if let Some(outgoing) = outgoing.upgrade() {
+                    let _ = outgoing.send(reply);
+                }
+            }
+            continue;
+        }
+
+        let Some(id) = id.as_u64() else {
+            continue;
+        };
+        let Some(waiter) = pending.lock().unwrap().remove(&id) else {
+            continue;
+        };
+        let result = match message.get("error") {
+            Some(error) => Err(McpError::Server {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            }),
+            None => Ok(message["result"].clone()),
+        };
+        let _ = waiter.send(result);
+    }
+    // Dropping the senders fails the remaining requests with `Closed`
+    pending.lock().unwrap().clear();
+}
+
+/// Remote MCP tool exposed as a local `Tool`
+pub struct McpTool {
+    client: Arc<McpClient>,
+    info: McpToolInfo,
+}
+
+impl McpTool {
+    pub fn info(&self) -> &McpToolInfo {
+        &self.info
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        self.info.input_schema.clone()
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default().danger(self.info.danger)
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        match self.client.call_tool(&self.info.name, params.clone()).await {
+            Ok(output) if output.is_error => ToolResult::error(output.text()),
+            Ok(output) => ToolResult::success(output.text()),
+            Err(McpError::Timeout(limit)) => {
+                ToolError::timeout(format!("MCP tool timed out after {:?}", limit)).into()
+            }
+            Err(e) => ToolError::upstream(e.to_string()).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-process server answering initialize, a paginated tools/list and
+    /// tools/call
+    fn fake_server() -> Transport {
+        let (outgoing, mut requests) = mpsc::unbounded_channel::<Value>();
+        let (responses, incoming) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                let id = request["id"].clone();
+                let result = match request["method"].as_str().unwrap_or_default() {
+                    "initialize" => serde_json::json!({
+                        "protocolVersion": PROTOCOL_VERSION,
+                        "serverInfo": {"name": "fake", "version": "1.0"}
+                    }),
+                    "tools/list" if request["params"]["cursor"].is_null() => serde_json::json!({
+                        "tools": [{
+                            "name": "echo",
+                            "description": "Echo text",
+                            "annotations": {"readOnlyHint": true},
+                            "inputSchema": {
+                                "type": "object",
+                                "properties": {"text": {"type": "string"}},
+                                "required": ["text"]
+                            }
+                        }],
+                        "nextCursor": "page-2"
+                    }),
+                    "tools/list" => serde_json::json!({"tools": [{"name": "fail"}]}),
+                    "tools/call" => {
+                        let args = &request["params"]["arguments"];
+                        // Ask the client something first, as servers may
+                        let _ = responses.send(serde_json::json!({
+                            "jsonrpc": "2.0", "id": "srv-1", "method": "ping"
+                        }));
+                        serde_json::json!({
+                            "content": [{"type": "text", "text": args["text"].as_str().unwrap_or("boom")}],
+                            "isError": request["params"]["name"] == "fail"
+                        })
+                    }
+                    _ => continue,
+                };
+                let _ = responses
+                    .send(serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result}));
+            }
+        });
+        Transport {
+            outgoing,
+            incoming,
+            child: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_and_calls_remote_tools() {
+        let client = Arc::new(McpClient::start(fake_server()).await.unwrap());
+        assert_eq!(client.server_info()["name"], "fake");
+
+        let tools = client.tools().await.unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].name(), "echo");
+        assert_eq!(
+            tools[1].parameters_schema(),
+            serde_json::json!({"type": "object"})
+        );
+        assert_eq!(tools[0].metadata().danger, DangerLevel::Safe);
+        assert_eq!(tools[1].metadata().danger, DangerLevel::Destructive);
+
+        let result = tools[0].execute(&serde_json::json!({"text": "hi"})).await;
+        assert!(result.success);
+        assert_eq!(result.content, "hi");
+
+        let result = tools[1].execute(&serde_json::json!({})).await;
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn maps_annotations_to_danger_levels() {
+        let danger = |annotations: Value| danger_level(&annotations);
+        assert_eq!(
+            danger(serde_json::json!({"readOnlyHint": true})),
+            DangerLevel::Safe
+        );
+        assert_eq!(
+            danger(serde_json::json!({"destructiveHint": false})),
+            DangerLevel::Moderate
+        );
+        assert_eq!(
+            danger(serde_json::json!({"readOnlyHint": false, "destructiveHint": true})),
+            DangerLevel::Destructive
+        );
+        assert_eq!(
+            danger(serde_json::json!({"readOnlyHint": "yes"})),
+            DangerLevel::Destructive
+        );
+        assert_eq!(danger(Value::Null), DangerLevel::Destructive);
+    }
+
+    #[test]
+    fn renders_non_text_content() {
+        let output = McpToolOutput {
+            content: vec![
+                serde_json::json!({"type": "text", "text": "chart:"}),
+                serde_json::json!({"type": "image", "data": "AAAA", "mimeType": "image/png"}),
+                serde_json::json!({"type": "resource", "resource": {"uri": "file:///a.txt"}}),
+            ],
+            is_error: false,
+        };
+        assert_eq!(
+            output.text(),
+            "chart:\n[image: image/png]\n[resource: file:///a.txt]"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn connects_over_stdio() {
+        let script = r#"
+            read init
+            echo '{"jsonrpc":"2.0","id":1,"result":{"serverInfo":{"name":"sh"}}}'
+            read initialized
+            read list
+            echo 'starting up'
+            echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"date"}]}}'
+            read call
+        "#;
+        let client = McpClient::connect_stdio("sh", ["-c", script])
+            .await
+            .unwrap()
+            .request_timeout(Duration::from_secs(5));
+
+        assert_eq!(client.server_info()["name"], "sh");
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools[0].name, "date");
+
+        // The script exits without answering the call
+        assert_eq!(
+            client.call_tool("date", serde_json::json!({})).await,
+            Err(McpError::Closed)
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Model Context Protocol client and server.
+//!
+//! `McpClient` connects to an MCP server over stdio or SSE, lists its tools
+//! and calls them. `McpClient::tools` wraps each remote tool as a `Tool` so
+//! it can be registered with an agent like a local one.
+//!
+//! `McpServer` goes the other way, publishing this crate's tools and agents
+//! over stdio or SSE for other MCP clients to call.
+
+mod client;
+mod server;
+mod transport;
+
+pub use client::{McpClient, McpTool, McpToolInfo, McpToolOutput};
+pub use server::McpServer;
+
+/// MCP revision this client speaks
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum McpError {
+    /// Starting, reaching or talking to the server failed
+    Transport(String),
+    /// The server sent something this client cannot use
+    Protocol(String),
+    /// The server answered with a JSON-RPC error
+    Server {
+        code: i64,
+        message: String,
+    },
+    Timeout(std::time::Duration),
+    /// The connection closed before the server answered
+    Closed,
+}
+
+impl std::fmt::Display for McpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(msg) => write!(f, "MCP transport error: {}", msg),
+            Self::Protocol(msg) => write!(f, "MCP protocol error: {}", msg),
+            Self::Server { code, message } => write!(f, "MCP server error {}: {}", code, message),
+            Self::Timeout(limit) => write!(f, "MCP request timed out after {:?}", limit),
+            Self::Closed => write!(f, "MCP connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for McpError {}
+
+pub type Result<T> = std::result::Result<T, McpError>;
@@ -0,0 +1,907 @@
+use super::{McpError, Result, PROTOCOL_VERSION};
+use crate::agent::{tool_allowed, Agent};
+use crate::provider::{ImageSource, LlmProvider};
+use crate::tool::{
+    ApprovalManager, DangerLevel, Tool, ToolCall, ToolExecutor, ToolInfo, ToolRegistry, ToolResult,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+type Sessions = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>>;
+
+/// Largest message body accepted on `POST /messages`
+const MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+/// Longest request or header line accepted
+const MAX_LINE_BYTES: usize = 8 * 1024;
+/// Most header lines accepted in one request
+const MAX_HEADERS: usize = 100;
+
+/// Publishes tools, and agents as tools, to MCP clients
+///
+/// Tools added with `tool` or `agent` take precedence over registry tools
+/// with the same name. Only safe tools are published unless `max_danger`
+/// says otherwise, and calls run through a `ToolExecutor` under the
+/// server's approvals, allowed tools and tool timeout, as an agent's would.
+pub struct McpServer {
+    name: String,
+    version: String,
+    tools: Vec<Box<dyn Tool>>,
+    registry: Option<ToolRegistry>,
+    approvals: Option<ApprovalManager>,
+    allowed_tools: Option<Vec<String>>,
+    tool_timeout: Option<Duration>,
+    max_danger: DangerLevel,
+    auth_token: Option<String>,
+    allowed_hosts: Vec<String>,
+}
+
+impl McpServer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            tools: Vec::new(),
+            registry: None,
+            approvals: None,
+            allowed_tools: None,
+            tool_timeout: None,
+            max_danger: DangerLevel::Safe,
+            auth_token: None,
+            allowed_hosts: Vec::new(),
+        }
+    }
+
+    /// Version reported in `serverInfo`; the crate version by default
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    pub fn tool(mut self, tool: Box<dyn Tool>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Publish every tool in `registry`, including ones registered later
+    pub fn registry(mut self, registry: ToolRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Publish `agent.run` as a tool taking `{"input": "..."}`
+    ///
+    /// Each call is a fresh conversation; concurrent calls wait their turn.
+    /// The agent's tools run under its own approvals.
+    pub fn agent<P: LlmProvider + 'static>(
+        self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        agent: Agent<P>,
+    ) -> Self {
+        self.tool(Box::new(AgentEntrypoint {
+            name: name.into(),
+            description: description.into(),
+            agent: tokio::sync::Mutex::new(agent),
+        }))
+    }
+
+    /// Hold calls to risky tools until `approvals` allows them
+    pub fn approvals(mut self, approvals: ApprovalManager) -> Self {
+        self.approvals = Some(approvals);
+        self
+    }
+
+    /// Only publish tools with these names, or starting with a prefix
+    /// written as `prefix*`
+    pub fn allowed_tools(mut self, tools: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Timeout for calls to tools whose metadata declares none
+    pub fn tool_timeout(mut self, timeout: Duration) -> Self {
+        self.tool_timeout = Some(timeout);
+        self
+    }
+
+    /// Use `agent`'s approvals, allowed tools and tool timeout, e.g. when
+    /// publishing its registry
+    pub fn policy_of<P: LlmProvider>(mut self, agent: &Agent<P>) -> Self {
+        self.approvals = agent.approvals().cloned();
+        self.allowed_tools = agent.options().allowed_tools.clone();
+        self.tool_timeout = agent.options().tool_timeout;
+        self
+    }
+
+    /// Also publish tools up to `level`; only safe tools are by default
+    ///
+    /// Any client that reaches the server can call what it publishes, so
+    /// set `approvals` before opting in to destructive tools.
+    pub fn max_danger(mut self, level: DangerLevel) -> Self {
+        self.max_danger = level;
+        self
+    }
+
+    /// Token SSE clients must send as `Authorization: Bearer <token>`;
+    /// `serve_sse` refuses to start without one
+    pub fn auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Accept SSE requests whose `Host`, and `Origin` if sent, name `host`,
+    /// e.g. `mcp.example.com:8000`
+    ///
+    /// The listener's address on loopback is always accepted; other hosts
+    /// are refused so a web page cannot reach the server through DNS
+    /// rebinding.
+    pub fn allow_host(mut self, host: impl Into<String>) -> Self {
+        self.allowed_hosts.push(host.into());
+        self
+    }
+
+    /// Serve one client over this process's stdin and stdout
+    pub async fn serve_stdio(self) -> Result<()> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serve one client exchanging newline-delimited JSON, until `reader`
+    /// ends and calls in flight finish
+    pub async fn serve<R, W>(self, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let handler = Arc::new(self.into_handler().await);
+        let (replies, mut outgoing) = mpsc::unbounded_channel::<Value>();
+        let mut lines = BufReader::new(reader).lines();
+
+        let read = async move {
+            while let Some(line) = lines.next_line().await? {
+                let Ok(message) = serde_json::from_str::<Value>(line.trim()) else {
+                    continue;
+                };
+                spawn_handler(handler.clone(), message, replies.clone());
+            }
+            Ok::<_, std::io::Error>(())
+        };
+        let write = async {
+            while let Some(reply) = outgoing.recv().await {
+                let mut line = reply.to_string();
+                line.push('\n');
+                writer.write_all(line.as_bytes()).await?;
+                writer.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        let (read, write) = tokio::join!(read, write);
+        read.and(write)
+            .map_err(|e| McpError::Transport(e.to_string()))
+    }
+
+    /// Accept SSE clients on `listener`: each opens `GET /sse` and posts
+    /// messages to the endpoint announced there
+    ///
+    /// Requests need the `auth_token` and an allowed host.
+    pub async fn serve_sse(self, listener: TcpListener) -> Result<()> {
+        let Some(token) = self.auth_token.clone() else {
+            return Err(McpError::Transport(
+                "serve_sse needs an auth_token".to_string(),
+            ));
+        };
+        let port = listener
+            .local_addr()
+            .map_err(|e| McpError::Transport(e.to_string()))?
+            .port();
+        let mut hosts = self.allowed_hosts.clone();
+        hosts.extend(["127.0.0.1", "localhost", "[::1]"].map(|host| format!("{}:{}", host, port)));
+        let access = Arc::new(Access { token, hosts });
+
+        let handler = Arc::new(self.into_handler().await);
+        let sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+        loop {
+            let (stream, _) = listener
+                .accept()
+                .await
+                .map_err(|e| McpError::Transport(e.to_string()))?;
+            let handler = handler.clone();
+            let sessions = sessions.clone();
+            let access = access.clone();
+            tokio::spawn(async move {
+                let _ = handle_http(stream, handler, sessions, &access).await;
+            });
+        }
+    }
+
+    /// Register the server's own tools and set up executors for them and
+    /// the registry
+    async fn into_handler(self) -> Handler {
+        let own = ToolRegistry::new();
+        for tool in self.tools {
+            own.register(tool).await;
+        }
+        let executor = |registry: ToolRegistry| {
+            let executor = ToolExecutor::new(registry.clone());
+            let executor = match &self.approvals {
+                Some(approvals) => executor.with_approvals(approvals.clone()),
+                None => executor,
+            };
+            (registry, executor)
+        };
+        let mut executors = vec![executor(own)];
+        executors.extend(self.registry.map(executor));
+        Handler {
+            name: self.name,
+            version: self.version,
+            executors,
+            allowed_tools: self.allowed_tools,
+            tool_timeout: self.tool_timeout,
+            max_danger: self.max_danger,
+        }
+    }
+}
+
+/// Answers JSON-RPC messages for a server
+struct Handler {
+    name: String,
+    version: String,
+    /// The server's own tools, then the shared registry's
+    executors: Vec<(ToolRegistry, ToolExecutor)>,
+    allowed_tools: Option<Vec<String>>,
+    tool_timeout: Option<Duration>,
+    max_danger: DangerLevel,
+}
+
+impl Handler {
+    fn publishes(&self, info: &ToolInfo) -> bool {
+        info.metadata.danger <= self.max_danger
+            && tool_allowed(self.allowed_tools.as_deref(), &info.name)
+    }
+
+    /// Published tools, sorted by name
+    async fn list_tools(&self) -> Vec<ToolInfo> {
+        let mut tools: Vec<ToolInfo> = Vec::new();
+        for (registry, _) in &self.executors {
+            for info in registry.list_tools().await {
+                if !tools.iter().any(|tool| tool.name == info.name) {
+                    tools.push(info);
+                }
+            }
+        }
+        tools.retain(|info| self.publishes(info));
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+    }
+
+    /// Run a published tool; `None` if there is none called `name`
+    async fn call_tool(&self, name: &str, arguments: Value) -> Option<ToolResult> {
+        for (registry, executor) in &self.executors {
+            let Some(info) = registry.tool_info(name).await else {
+                continue;
+            };
+            if !self.publishes(&info) {
+                return None;
+            }
+            let call = ToolCall {
+                id: crate::ids::new_id(),
+                name: name.to_string(),
+                parameters: arguments,
+            };
+            return Some(
+                executor
+                    .execute_with_timeout(&call, self.tool_timeout)
+                    .await,
+            );
+        }
+        None
+    }
+
+    /// Reply to one JSON-RPC message; `None` for notifications
+    async fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let result = match method {
+            "initialize" => Ok(serde_json::json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": self.name, "version": self.version},
+            })),
+            "ping" => Ok(serde_json::json!({})),
+            "tools/list" => {
+                let tools: Vec<Value> = self
+                    .list_tools()
+                    .await
+                    .into_iter()
+                    .map(|info| {
+                        let danger = info.metadata.danger;
+                        serde_json::json!({
+                            "name": info.name,
+                            "description": info.description,
+                            "inputSchema": info.parameters_schema,
+                            "annotations": {
+                                "readOnlyHint": danger == DangerLevel::Safe,
+                                "destructiveHint": danger == DangerLevel::Destructive,
+                            },
+                        })
+                    })
+                    .collect();
+                Ok(serde_json::json!({ "tools": tools }))
+            }
+            "tools/call" => {
+                let name = params["name"].as_str().unwrap_or_default();
+                let arguments = match &params["arguments"] {
+                    Value::Null => serde_json::json!({}),
+                    arguments => arguments.clone(),
+                };
+                match self.call_tool(name, arguments).await {
+                    Some(result) => Ok(call_result(result)),
+                    None => Err((-32602, format!("Unknown tool: {}", name))),
+                }
+            }
+            _ => Err((-32601, format!("Method not found: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            }),
+        })
+    }
+}
+
+/// Who may talk to an SSE server
+struct Access {
+    token: String,
+    /// `host:port` values accepted in `Host` and `Origin`
+    hosts: Vec<String>,
+}
+
+impl Access {
+    fn allows_host(&self, host: Option<&str>, origin: Option<&str>) -> bool {
+        let known = |host: &str| self.hosts.iter().any(|allowed| allowed == host);
+        let origin_known = origin.is_none_or(|origin| {
+            origin
+                .strip_prefix("http://")
+                .or_else(|| origin.strip_prefix("https://"))
+                .is_some_and(known)
+        });
+        host.is_some_and(known) && origin_known
+    }
+
+    /// Compare the bearer token without leaking where it differs
+    fn authorizes(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        token.len() == self.token.len()
+            && token
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Handle `message` in its own task so slow tool calls do not hold up
+/// other requests
+fn spawn_handler(handler: Arc<Handler>, message: Value, replies: mpsc::UnboundedSender<Value>) {
+    tokio::spawn(async move {
+        if let Some(reply) = handler.handle(&message).await {
+            let _ = replies.send(reply);
+        }
+    });
+}
+
+/// `tools/call` result for a tool's outcome
+fn call_result(result: ToolResult) -> Value {
+    let text = if result.success {
+        result.content
+    } else {
+        result.error.unwrap_or(result.content)
+    };
+    let mut content = vec![serde_json::json!({"type": "text", "text": text})];
+    for image in result.images {
+        content.push(match image {
+            ImageSource::Base64 { media_type, data } => {
+                serde_json::json!({"type": "image", "data": data, "mimeType": media_type})
+            }
+            ImageSource::Url { url } => serde_json::json!({"type": "text", "text": url}),
+        });
+    }
+    serde_json::json!({"content": content, "isError": !result.success})
+}
+
+/// Answer one HTTP request: `GET /sse` opens a session's event stream and
+/// `POST /messages?session_id=...` delivers a message to it
+async fn handle_http(
+    stream: TcpStream,
+    handler: Arc<Handler>,
+    sessions: Sessions,
+    access: &Access,
+) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut request_line = String::new();
+    if !read_line_limited(&mut stream, &mut request_line).await? {
+        return respond(stream.get_mut(), "414 URI Too Long").await;
+    }
+    // `None` once a Content-Length fails to parse
+    let mut content_length = Some(0);
+    let (mut host, mut origin, mut authorization) = (None, None, None);
+    let mut headers = 0;
+    loop {
+        let mut header = String::new();
+        let complete = read_line_limited(&mut stream, &mut header).await?;
+        if complete && header.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if !complete || headers > MAX_HEADERS {
+            return respond(stream.get_mut(), "431 Request Header Fields Too Large").await;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim().to_string();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.parse().ok(),
+                "host" => host = Some(value),
+                "origin" => origin = Some(value),
+                "authorization" => authorization = Some(value),
+                _ => {}
+            }
+        }
+    }
+    let Some(content_length) = content_length else {
+        return respond(stream.get_mut(), "400 Bad Request").await;
+    };
+    if !access.allows_host(host.as_deref(), origin.as_deref()) {
+        return respond(stream.get_mut(), "403 Forbidden").await;
+    }
+    if !access.authorizes(authorization.as_deref()) {
+        return respond(stream.get_mut(), "401 Unauthorized").await;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+    match (method, path) {
+        ("GET", "/sse") => {
+            let session = crate::ids::new_id();
+            let (replies, mut outgoing) = mpsc::unbounded_channel();
+            sessions.lock().unwrap().insert(session.clone(), replies);
+
+            let stream = stream.get_mut();
+            let opened = async {
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                          Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+                    )
+                    .await?;
+                stream
+                    .write_all(
+                        format!(
+                            "event: endpoint\ndata: /messages?session_id={}\n\n",
+                            session
+                        )
+                        .as_bytes(),
+                    )
+                    .await?;
+                while let Some(reply) = outgoing.recv().await {
+                    stream
+                        .write_all(format!("event: message\ndata: {}\n\n", reply).as_bytes())
+                        .await?;
+                }
+                Ok::<_, std::io::Error>(())
+            };
+            let result = opened.await;
+            sessions.lock().unwrap().remove(&session);
+            result
+        }
+        ("POST", "/messages") if content_length > MAX_BODY_BYTES => {
+            respond(stream.get_mut(), "413 Payload Too Large").await
+        }
+        ("POST", "/messages") => {
+            let mut body = vec![0; content_length];
+            stream.read_exact(&mut body).await?;
+            let session = target
+                .split_once("session_id=")
+                .map(|(_, rest)| rest.split('&').next().unwrap_or(rest))
+                .unwrap_or_default();
+            let replies = sessions.lock().unwrap().get(session).cloned();
+            let status = match (replies, serde_json::from_slice::<Value>(&body)) {
+                (Some(replies), Ok(message)) => {
+                    spawn_handler(handler, message, replies);
+                    "202 Accepted"
+                }
+                (None, _) => "404 Not Found",
+                (_, Err(_)) => "400 Bad Request",
+            };
+            respond(stream.get_mut(), status).await
+        }
+        _ => respond(stream.get_mut(), "404 Not Found").await,
+    }
+}
+
+/// Read one line of at most `MAX_LINE_BYTES`; `false` if it is longer
+///
+/// A connection that ends mid-request reads as an empty line.
+async fn read_line_limited(
+    stream: &mut BufReader<TcpStream>,
+    line: &mut String,
+) -> std::io::Result<bool> {
+    let read = (&mut *stream)
+        .take(MAX_LINE_BYTES as u64)
+        .read_line(line)
+        .await?;
+    Ok(read < MAX_LINE_BYTES || line.ends_with('\n'))
+}
+
+async fn respond(stream: &mut TcpStream, status: &str) -> std::io::Result<()> {
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .as_bytes(),
+        )
+        .await
+}
+
+/// An agent's `run` published as a tool
+struct AgentEntrypoint<P: LlmProvider> {
+    name: String,
+    description: String,
+    agent: tokio::sync::Mutex<Agent<P>>,
+}
+
+#[async_trait]
+impl<P: LlmProvider + 'static> Tool for AgentEntrypoint<P> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "input": {"type": "string", "description": "Task or question for the agent"}
+            },
+            "required": ["input"]
+        })
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let input = params["input"].as_str().unwrap_or_default();
+        match self.agent.lock().await.run(input).await {
+            Ok(response) => ToolResult::success(response),
+            Err(e) => ToolResult::error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::McpClient;
+    use crate::provider::{GenerateOptions, GenerateResponse, Message};
+    use crate::tool::ToolMetadata;
+    use std::future::Future;
+    use std::pin::Pin;
+
+    /// Answers with the last user message, shouted
+    struct Shout;
+
+    impl LlmProvider for Shout {
+        fn name(&self) -> &str {
+            "shout"
+        }
+
+        fn model(&self) -> &str {
+            "m"
+        }
+
+        fn generate(
+            &self,
+            messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<GenerateResponse>> + Send + '_>>
+        {
+            let content = messages
+                .last()
+                .map(Message::content_as_text)
+                .unwrap_or_default()
+                .to_uppercase();
+            Box::pin(async move {
+                Ok(GenerateResponse {
+                    content,
+                    usage: None,
+                    model: "m".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    struct Echo;
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echo text"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {"text": {"type": "string"}},
+                "required": ["text"]
+            })
+        }
+
+        async fn execute(&self, params: &Value) -> ToolResult {
+            ToolResult::success(params["text"].as_str().unwrap_or_default())
+        }
+    }
+
+    /// Destructive tool that records whether it ran
+    struct Wipe(Arc<std::sync::atomic::AtomicBool>);
+
+    #[async_trait]
+    impl Tool for Wipe {
+        fn name(&self) -> &str {
+            "wipe"
+        }
+
+        fn description(&self) -> &str {
+            "Delete everything"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn metadata(&self) -> ToolMetadata {
+            ToolMetadata::default().danger(DangerLevel::Destructive)
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            ToolResult::success("wiped")
+        }
+    }
+
+    fn server() -> McpServer {
+        McpServer::new("test")
+            .tool(Box::new(Echo))
+            .agent("ask", "Ask the agent", Agent::new(Shout))
+    }
+
+    fn call(name: &str) -> Value {
+        serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": name}
+        })
+    }
+
+    async fn tool_names(handler: &Handler) -> Vec<String> {
+        handler
+            .list_tools()
+            .await
+            .into_iter()
+            .map(|info| info.name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn answers_json_rpc_requests() {
+        let server = server().into_handler().await;
+        let reply = server
+            .handle(&serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+            .await
+            .unwrap();
+        let names: Vec<_> = reply["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["ask", "echo"]);
+        assert_eq!(
+            reply["result"]["tools"][0]["annotations"],
+            serde_json::json!({"readOnlyHint": true, "destructiveHint": false})
+        );
+
+        let reply = server
+            .handle(&serde_json::json!({
+                "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+                "params": {"name": "echo", "arguments": {}}
+            }))
+            .await
+            .unwrap();
+        assert_eq!(reply["result"]["isError"], true);
+
+        let reply = server
+            .handle(&serde_json::json!({
+                "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                "params": {"name": "missing"}
+            }))
+            .await
+            .unwrap();
+        assert_eq!(reply["error"]["code"], -32602);
+
+        let reply = server
+            .handle(&serde_json::json!({"jsonrpc": "2.0", "id": 4, "method": "resources/list"}))
+            .await
+            .unwrap();
+        assert_eq!(reply["error"]["code"], -32601);
+
+        assert!(server
+            .handle(&serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn refuses_risky_tools_unless_opted_in() {
+        let wiped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(Wipe(wiped.clone()))).await;
+        registry.register(Box::new(Echo)).await;
+
+        let handler = McpServer::new("test")
+            .registry(registry.clone())
+            .into_handler()
+            .await;
+        assert_eq!(tool_names(&handler).await, ["echo"]);
+        let reply = handler.handle(&call("wipe")).await.unwrap();
+        assert_eq!(reply["error"]["code"], -32602);
+
+        // Opting in still runs calls past the approvals, which reject
+        // without a handler
+        let handler = McpServer::new("test")
+            .registry(registry)
+            .max_danger(DangerLevel::Destructive)
+            .approvals(ApprovalManager::new())
+            .into_handler()
+            .await;
+        assert_eq!(tool_names(&handler).await, ["echo", "wipe"]);
+        let reply = handler.handle(&call("wipe")).await.unwrap();
+        assert_eq!(reply["result"]["isError"], true);
+        assert!(!wiped.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn applies_the_agents_tool_policy() {
+        let agent = Agent::new(Shout).with_options(crate::agent::AgentOptions {
+            allowed_tools: Some(vec!["ask".to_string()]),
+            ..Default::default()
+        });
+        let handler = server().policy_of(&agent).into_handler().await;
+        assert_eq!(tool_names(&handler).await, ["ask"]);
+        let reply = handler.handle(&call("echo")).await.unwrap();
+        assert_eq!(reply["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn serves_newline_delimited_json() {
+        let (client, server_side) = tokio::io::duplex(4096);
+        let (reader, writer) = tokio::io::split(server_side);
+        let serving = tokio::spawn(server().serve(reader, writer));
+
+        let (client_reader, mut client_writer) = tokio::io::split(client);
+        client_writer
+            .write_all(
+                b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\
+                  \"params\":{\"name\":\"ask\",\"arguments\":{\"input\":\"hello\"}}}\n",
+            )
+            .await
+            .unwrap();
+        client_writer.shutdown().await.unwrap();
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let reply: Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(reply["result"]["content"][0]["text"], "HELLO");
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_mcp_clients_over_sse() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", listener.local_addr().unwrap());
+        let registry = ToolRegistry::new();
+        let server = McpServer::new("test")
+            .registry(registry.clone())
+            .auth_token("secret");
+        tokio::spawn(server.serve_sse(listener));
+        registry.register(Box::new(Echo)).await;
+
+        assert!(McpClient::connect_sse(&url).await.is_err());
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Authorization", "Bearer secret".parse().unwrap());
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let client = McpClient::connect_sse_with(http, &url).await.unwrap();
+        assert_eq!(client.server_info()["name"], "test");
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools[0].name, "echo");
+        let output = client
+            .call_tool("echo", serde_json::json!({"text": "over sse"}))
+            .await
+            .unwrap();
+        assert_eq!(output.text(), "over sse");
+    }
+
+    #[tokio::test]
+    async fn serve_sse_needs_an_auth_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        assert!(server().serve_sse(listener).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server().auth_token("secret").serve_sse(listener));
+
+        let status = |request: String| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).await.unwrap();
+            line
+        };
+        let post = |headers: &str| {
+            format!(
+                "POST /messages?session_id=x HTTP/1.1\r\nHost: {}\r\n{}\r\n",
+                addr, headers
+            )
+        };
+        let auth = "Authorization: Bearer secret\r\n";
+        let huge_body = post(&format!("{}Content-Length: {}\r\n", auth, usize::MAX));
+        assert!(status(huge_body).await.contains("413"));
+        let bad_length = post(&format!("{}Content-Length: -1\r\n", auth));
+        assert!(status(bad_length).await.contains("400"));
+        assert!(status(post("")).await.contains("401"));
+        assert!(status(post("Authorization: Bearer secrex\r\n"))
+            .await
+            .contains("401"));
+        let foreign = post(&format!("{}Origin: http://evil.example\r\n", auth));
+        assert!(status(foreign).await.contains("403"));
+        let rebound = format!(
+            "POST /messages?session_id=x HTTP/1.1\r\nHost: evil.example:{}\r\n{}\r\n",
+            addr.port(),
+            auth
+        );
+        assert!(status(rebound).await.contains("403"));
+        assert!(status(post(auth)).await.contains("404"));
+        let long_header = format!(
+            "GET /sse HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+            "a".repeat(MAX_LINE_BYTES)
+        );
+        assert!(status(long_header).await.contains("431"));
+        let many_headers = format!("GET /sse HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(200));
+        assert!(status(many_headers).await.contains("431"));
+    }
+}
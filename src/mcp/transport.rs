@@ -0,0 +1,255 @@
+//! Message transports to MCP servers.
+//!
+//! Both transports carry JSON-RPC messages as `serde_json::Value`s over a
+//! pair of channels, with background tasks doing the I/O.
+
+use super::McpError;
+use crate::provider::LineDecoder;
+use futures_util::StreamExt;
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+
+/// Channels to and from a server
+pub(crate) struct Transport {
+    pub(crate) outgoing: mpsc::UnboundedSender<Value>,
+    pub(crate) incoming: mpsc::UnboundedReceiver<Value>,
+    /// Server process, killed when the transport is dropped
+    pub(crate) child: Option<Child>,
+}
+
+impl Transport {
+    /// Start `command` and exchange newline-delimited JSON over its stdin
+    /// and stdout
+    pub(crate) fn stdio(mut command: Command) -> Result<Self, McpError> {
+        command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true);
+        let mut child = command
+            .spawn()
+            .map_err(|e| McpError::Transport(format!("Failed to start server: {}", e)))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (outgoing, mut to_server) = mpsc::unbounded_channel::<Value>();
+        let (from_server, incoming) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Some(message) = to_server.recv().await {
+                let mut line = message.to_string();
+                line.push('\n');
+                if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                // Servers may log non-JSON lines; skip them
+                if let Ok(message) = serde_json::from_str::<Value>(line.trim()) {
+                    if from_server.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            outgoing,
+            incoming,
+            child: Some(child),
+        })
+    }
+
+    /// Open the SSE stream at `url`, then POST messages to the endpoint the
+    /// server announces in its first `endpoint` event
+    pub(crate) async fn sse(client: reqwest::Client, url: &str) -> Result<Self, McpError> {
+        let response = client
+            .get(url)
+            .header("Accept", "text/event-stream")
+            .send()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(McpError::Transport(format!(
+                "SSE connection failed: {}",
+                response.status()
+            )));
+        }
+        let base = response.url().clone();
+
+        let (endpoint_tx, endpoint_rx) = oneshot::channel::<String>();
+        let (from_server, incoming) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut endpoint_tx = Some(endpoint_tx);
+            let mut parser = SseParser::default();
+            let mut stream = response.bytes_stream();
+            while let Some(Ok(bytes)) = stream.next().await {
+                // The client is gone; stop holding the connection open
+                if from_server.is_closed() {
+                    return;
+                }
+                for event in parser.feed(&bytes) {
+                    match event.event.as_str() {
+                        "endpoint" => {
+                            if let Some(tx) = endpoint_tx.take() {
+                                let _ = tx.send(event.data);
+                            }
+                        }
+                        "message" => {
+                            if let Ok(message) = serde_json::from_str(&event.data) {
+                                if from_server.send(message).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        });
+
+        let endpoint = endpoint_rx
+            .await
+            .map_err(|_| McpError::Transport("Stream closed before the endpoint event".into()))?;
+        let endpoint = endpoint_url(&base, &endpoint)?;
+
+        let (outgoing, mut to_server) = mpsc::unbounded_channel::<Value>();
+        tokio::spawn(async move {
+            while let Some(message) = to_server.recv().await {
+                let sent = client.post(endpoint.clone()).json(&message).send().await;
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            outgoing,
+            incoming,
+            child: None,
+        })
+    }
+}
+
+/// `endpoint` resolved against the stream's URL, refusing endpoints on
+/// another origin so a server cannot have requests posted elsewhere
+fn endpoint_url(base: &reqwest::Url, endpoint: &str) -> Result<reqwest::Url, McpError> {
+    let url = base
+        .join(endpoint.trim())
+        .map_err(|e| McpError::Transport(format!("Invalid endpoint {}: {}", endpoint, e)))?;
+    if url.origin() != base.origin() {
+        return Err(McpError::Transport(format!(
+            "Endpoint {} is not on the origin of {}",
+            url, base
+        )));
+    }
+    Ok(url)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SseEvent {
+    pub(crate) event: String,
+    pub(crate) data: String,
+}
+
+/// Incremental `text/event-stream` parser
+///
+/// Lines are decoded as UTF-8 only once complete, so characters split
+/// across chunks survive.
+#[derive(Default)]
+pub(crate) struct SseParser {
+    lines: LineDecoder,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Add a chunk of the stream and return the events it completed
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.lines.push(chunk);
+        let mut events = Vec::new();
+        while let Some(line) = self.lines.next_line() {
+            let line = String::from_utf8_lossy(&line);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(SseEvent {
+                        event: self.event.take().unwrap_or_else(|| "message".to_string()),
+                        data: self.data.join("\n"),
+                    });
+                }
+                self.event = None;
+                self.data.clear();
+            } else if let Some(name) = line.strip_prefix("event:") {
+                self.event = Some(name.trim().to_string());
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data
+                    .push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_events_split_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser
+            .feed(b"event: endpoint\ndata: /messages?session=1\n")
+            .is_empty());
+
+        let events = parser.feed(b"\n: keep-alive\n\ndata: {\"id\":1}\r\n\r\n");
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: "endpoint".to_string(),
+                    data: "/messages?session=1".to_string(),
+                },
+                SseEvent {
+                    event: "message".to_string(),
+                    data: "{\"id\":1}".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_endpoints_on_another_origin() {
+        let base = reqwest::Url::parse("http://localhost:3000/sse").unwrap();
+        assert_eq!(
+            endpoint_url(&base, " /messages?session=1\n")
+                .unwrap()
+                .as_str(),
+            "http://localhost:3000/messages?session=1"
+        );
+        for endpoint in [
+            "http://evil.example/messages",
+            "//evil.example/messages",
+            "https://localhost:3000/messages",
+            "http://localhost:3001/messages",
+        ] {
+            assert!(matches!(
+                endpoint_url(&base, endpoint),
+                Err(McpError::Transport(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn keeps_characters_split_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b"data: caf\xC3").is_empty());
+        let events = parser.feed(b"\xA9\n\n");
+        assert_eq!(events[0].data, "café");
+    }
+}
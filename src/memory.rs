@@ -0,0 +1,1168 @@
+//! Conversation memory that outlives the process.
+//!
+//! A `Memory` holds the turns of one conversation. Backed by a
+//! `MemoryStore`, it loads earlier turns on open and appends new ones on
+//! `flush`; `Agent::run_with_memory` flushes after every run. Stores only
+//! ever append, so several processes can share a conversation.
+//!
+//! `SummarizingMemory` keeps long conversations small: once they grow past
+//! a message count or token budget, older turns are replaced in the context
+//! by a summary written by an LLM, while the store keeps every turn.
+//!
+//! `AgentMemory` holds what an agent chose to remember: key-value facts
+//! and a log of episodes, kept per agent id in a `MemoryStore` apart from
+//! its conversations and exposed to the model by `RememberTool` and
+//! `RecallTool`.
+
+use crate::error::{AgentError, Result};
+use crate::provider::{
+    transcript, CharEstimateTokenizer, ContentBlock, GenerateOptions, LlmProvider, Message, Role,
+    Tokenizer, SUMMARY_PROMPT,
+};
+use crate::storage;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Append-only storage for conversation turns
+#[async_trait]
+pub trait MemoryStore: Send + Sync {
+    /// Add `messages` after the stored turns of `conversation`
+    async fn append(&self, conversation: &str, messages: &[Message]) -> Result<()>;
+
+    /// All stored turns of `conversation`, oldest first
+    async fn load(&self, conversation: &str) -> Result<Vec<Message>>;
+
+    /// Returns whether anything was removed
+    async fn clear(&self, conversation: &str) -> Result<bool>;
+
+    /// Stored conversation ids, sorted
+    async fn list(&self) -> Result<Vec<String>>;
+
+    /// Facts `agent` saved through `AgentMemory`, by key
+    ///
+    /// Facts and episodes live apart from conversations, so they never show
+    /// up in `list` or `load`. Stores that keep only conversations can leave
+    /// these methods out; they then fail.
+    async fn facts(&self, _agent: &str) -> Result<BTreeMap<String, String>> {
+        Err(no_agent_memory())
+    }
+
+    /// Save `value` under `key`, replacing what was there
+    async fn set_fact(&self, _agent: &str, _key: &str, _value: &str) -> Result<()> {
+        Err(no_agent_memory())
+    }
+
+    /// Returns whether `key` was set
+    async fn remove_fact(&self, _agent: &str, _key: &str) -> Result<bool> {
+        Err(no_agent_memory())
+    }
+
+    async fn append_episode(&self, _agent: &str, _episode: &Episode) -> Result<()> {
+        Err(no_agent_memory())
+    }
+
+    /// Every episode of `agent`, oldest first
+    async fn episodes(&self, _agent: &str) -> Result<Vec<Episode>> {
+        Err(no_agent_memory())
+    }
+}
+
+fn no_agent_memory() -> AgentError {
+    AgentError::Memory("This store does not keep agent memory".to_string())
+}
+
+/// The turns of one conversation, optionally persisted to a store
+#[derive(Clone)]
+pub struct Memory {
+    id: String,
+    messages: Vec<Message>,
+    store: Option<Arc<dyn MemoryStore>>,
+    /// Messages already in the store
+    flushed: usize,
+}
+
+impl std::fmt::Debug for Memory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Memory")
+            .field("id", &self.id)
+            .field("messages", &self.messages.len())
+            .field("store", &self.store.is_some())
+            .field("flushed", &self.flushed)
+            .finish()
+    }
+}
+
+impl Memory {
+    /// In-process memory that is lost when dropped
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            messages: Vec::new(),
+            store: None,
+            flushed: 0,
+        }
+    }
+
+    /// Memory of conversation `id` in `store`, starting from its stored turns
+    pub async fn open(id: impl Into<String>, store: Arc<dyn MemoryStore>) -> Result<Self> {
+        let id = id.into();
+        let messages = store.load(&id).await?;
+        Ok(Self {
+            id,
+            flushed: messages.len(),
+            messages,
+            store: Some(store),
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Messages not yet written to the store
+    pub fn pending(&self) -> &[Message] {
+        &self.messages[self.flushed..]
+    }
+
+    /// Write pending messages to the store; a no-op without one
+    pub async fn flush(&mut self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        if self.flushed < self.messages.len() {
+            store.append(&self.id, self.pending()).await?;
+            self.flushed = self.messages.len();
+        }
+        Ok(())
+    }
+
+    /// Drop all turns, here and in the store
+    pub async fn clear(&mut self) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.clear(&self.id).await?;
+        }
+        self.messages.clear();
+        self.flushed = 0;
+        Ok(())
+    }
+
+    /// Append the turns of `conversation` that came after the `known`
+    /// history turns it started with, skipping its leading system messages
+    pub(crate) fn record_run(&mut self, conversation: &[Message], known: usize) {
+        let turns = conversation.iter().skip_while(|m| m.role == Role::System);
+        self.messages.extend(turns.skip(known).cloned());
+    }
+}
+
+/// Whether `message` opens a turn: a user message that is not only tool
+/// results
+fn starts_turn(message: &Message) -> bool {
+    message.role == Role::User
+        && message
+            .content
+            .iter()
+            .any(|block| !matches!(block, ContentBlock::ToolResult { .. }))
+}
+
+/// A `Memory` whose older turns are replaced by an LLM-written summary once
+/// the conversation grows too long
+///
+/// Every turn is still flushed to the store; the summary only shapes the
+/// context sent to the model. It lives in this value, so a conversation
+/// reopened after a restart is summarized again on its next compaction.
+pub struct SummarizingMemory {
+    memory: Memory,
+    summarizer: Arc<dyn LlmProvider>,
+    tokenizer: Arc<dyn Tokenizer>,
+    max_messages: usize,
+    max_tokens: Option<usize>,
+    keep_turns: usize,
+    summary_max_tokens: u32,
+    summary: Option<String>,
+    /// Messages of `memory` covered by `summary`
+    summarized: usize,
+}
+
+impl std::fmt::Debug for SummarizingMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SummarizingMemory")
+            .field("memory", &self.memory)
+            .field("summarizer", &self.summarizer.model())
+            .field("max_messages", &self.max_messages)
+            .field("max_tokens", &self.max_tokens)
+            .field("keep_turns", &self.keep_turns)
+            .field("summarized", &self.summarized)
+            .finish()
+    }
+}
+
+impl SummarizingMemory {
+    /// Summarize with `summarizer`, often a cheaper model, once more than 40
+    /// messages follow the summary, keeping the last 4 turns verbatim
+    pub fn new(memory: Memory, summarizer: Arc<dyn LlmProvider>) -> Self {
+        Self {
+            memory,
+            summarizer,
+            tokenizer: Arc::new(CharEstimateTokenizer::default()),
+            max_messages: 40,
+            max_tokens: None,
+            keep_turns: 4,
+            summary_max_tokens: 512,
+            summary: None,
+            summarized: 0,
+        }
+    }
+
+    /// Compact once the context holds more than `max_messages` messages
+    pub fn max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// Also compact once the context exceeds `max_tokens`
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Number of most recent turns never summarized
+    pub fn keep_turns(mut self, keep_turns: usize) -> Self {
+        self.keep_turns = keep_turns;
+        self
+    }
+
+    /// Longest summary to ask for; 512 tokens by default
+    pub fn summary_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.summary_max_tokens = max_tokens;
+        self
+    }
+
+    /// Count tokens for `max_tokens` with `tokenizer` instead of a
+    /// character estimate
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Every turn, including summarized ones
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    /// Turns not covered by the summary
+    fn recent(&self) -> &[Message] {
+        &self.memory.messages()[self.summarized..]
+    }
+
+    /// What the model sees: the summary, then the turns after it
+    pub fn context(&self) -> Vec<Message> {
+        let summary = self.summary.as_ref().map(|summary| {
+            Message::system(format!("Summary of the earlier conversation:\n{}", summary))
+        });
+        summary
+            .into_iter()
+            .chain(self.recent().iter().cloned())
+            .collect()
+    }
+
+    /// Whether the context is over the message count or token budget
+    pub fn needs_compaction(&self) -> bool {
+        if self.recent().len() > self.max_messages {
+            return true;
+        }
+        self.max_tokens.is_some_and(|max_tokens| {
+            let context = self.context();
+            let tokens: usize = context
+                .iter()
+                .map(|m| self.tokenizer.count_message_tokens(m))
+                .sum();
+            tokens > max_tokens
+        })
+    }
+
+    /// Fold the turns before the last `keep_turns` into the summary if the
+    /// context is too long; returns whether it did
+    pub async fn compact(&mut self) -> Result<bool> {
+        if !self.needs_compaction() {
+            return Ok(false);
+        }
+        let recent = self.recent();
+        let starts: Vec<usize> = recent
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| starts_turn(m))
+            .map(|(i, _)| i)
+            .collect();
+        let cut = match self.keep_turns {
+            0 => recent.len(),
+            keep if starts.len() > keep => starts[starts.len() - keep],
+            _ => return Ok(false),
+        };
+        if cut == 0 {
+            return Ok(false);
+        }
+
+        let mut input = String::new();
+        if let Some(summary) = &self.summary {
+            input.push_str(&format!("Summary so far:\n{}\n\n", summary));
+        }
+        input.push_str(&transcript(&recent[..cut]));
+        let options = GenerateOptions {
+            max_tokens: Some(self.summary_max_tokens),
+            ..Default::default()
+        };
+        let response = self
+            .summarizer
+            .generate(
+                vec![Message::system(SUMMARY_PROMPT), Message::user(input)],
+                Some(options),
+            )
+            .await?;
+        let summary = response.content.trim();
+        if summary.is_empty() {
+            return Err(memory_error("The summarizer returned an empty summary"));
+        }
+        self.summary = Some(summary.to_string());
+        self.summarized += cut;
+        Ok(true)
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        self.memory.flush().await
+    }
+
+    /// Record a run that started from `context()`
+    pub(crate) fn record_run(&mut self, conversation: &[Message]) {
+        let known = self.recent().len();
+        self.memory.record_run(conversation, known);
+    }
+}
+
+fn memory_error(error: impl std::fmt::Display) -> AgentError {
+    AgentError::Memory(error.to_string())
+}
+
+/// Something that happened, as the agent noted it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Episode {
+    pub text: String,
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+}
+
+/// Facts and episodes one agent saves for later, on a `MemoryStore`
+///
+/// Agents sharing a store see each other's changes while keeping their own
+/// namespace, separate from every conversation.
+#[derive(Clone)]
+pub struct AgentMemory {
+    agent_id: String,
+    store: Arc<dyn MemoryStore>,
+}
+
+impl std::fmt::Debug for AgentMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentMemory")
+            .field("agent_id", &self.agent_id)
+            .finish()
+    }
+}
+
+impl AgentMemory {
+    pub fn new(agent_id: impl Into<String>, store: Arc<dyn MemoryStore>) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            store,
+        }
+    }
+
+    pub fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+
+    /// Save `value` under `key`, replacing what was there
+    pub async fn remember(&self, key: &str, value: &str) -> Result<()> {
+        self.store.set_fact(&self.agent_id, key, value).await
+    }
+
+    /// Returns whether `key` was set
+    pub async fn forget(&self, key: &str) -> Result<bool> {
+        self.store.remove_fact(&self.agent_id, key).await
+    }
+
+    pub async fn fact(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.facts().await?.remove(key))
+    }
+
+    /// Every fact, by key
+    pub async fn facts(&self) -> Result<BTreeMap<String, String>> {
+        self.store.facts(&self.agent_id).await
+    }
+
+    /// Note that `text` happened, now
+    pub async fn record_episode(&self, text: &str) -> Result<()> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let episode = Episode {
+            text: text.to_string(),
+            timestamp,
+        };
+        self.store.append_episode(&self.agent_id, &episode).await
+    }
+
+    /// Every episode, oldest first
+    pub async fn episodes(&self) -> Result<Vec<Episode>> {
+        self.store.episodes(&self.agent_id).await
+    }
+}
+
+/// Facts and episodes of one agent in an `InMemoryStore`
+#[derive(Clone, Default)]
+struct AgentRecord {
+    facts: BTreeMap<String, String>,
+    episodes: Vec<Episode>,
+}
+
+/// Conversations kept in memory, shared between clones
+#[derive(Clone, Default)]
+pub struct InMemoryStore {
+    conversations: Arc<RwLock<HashMap<String, Vec<Message>>>>,
+    agents: Arc<RwLock<HashMap<String, AgentRecord>>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl MemoryStore for InMemoryStore {
+    async fn append(&self, conversation: &str, messages: &[Message]) -> Result<()> {
+        self.conversations
+            .write()
+            .await
+            .entry(conversation.to_string())
+            .or_default()
+            .extend_from_slice(messages);
+        Ok(())
+    }
+
+    async fn load(&self, conversation: &str) -> Result<Vec<Message>> {
+        Ok(self
+            .conversations
+            .read()
+            .await
+            .get(conversation)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn clear(&self, conversation: &str) -> Result<bool> {
+        Ok(self
+            .conversations
+            .write()
+            .await
+            .remove(conversation)
+            .is_some())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut ids: Vec<String> = self.conversations.read().await.keys().cloned().collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    async fn facts(&self, agent: &str) -> Result<BTreeMap<String, String>> {
+        let agents = self.agents.read().await;
+        Ok(agents
+            .get(agent)
+            .map(|record| record.facts.clone())
+            .unwrap_or_default())
+    }
+
+    async fn set_fact(&self, agent: &str, key: &str, value: &str) -> Result<()> {
+        let mut agents = self.agents.write().await;
+        let record = agents.entry(agent.to_string()).or_default();
+        record.facts.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    async fn remove_fact(&self, agent: &str, key: &str) -> Result<bool> {
+        let mut agents = self.agents.write().await;
+        Ok(agents
+            .get_mut(agent)
+            .is_some_and(|record| record.facts.remove(key).is_some()))
+    }
+
+    async fn append_episode(&self, agent: &str, episode: &Episode) -> Result<()> {
+        let mut agents = self.agents.write().await;
+        let record = agents.entry(agent.to_string()).or_default();
+        record.episodes.push(episode.clone());
+        Ok(())
+    }
+
+    async fn episodes(&self, agent: &str) -> Result<Vec<Episode>> {
+        let agents = self.agents.read().await;
+        Ok(agents
+            .get(agent)
+            .map(|record| record.episodes.clone())
+            .unwrap_or_default())
+    }
+}
+
+/// One JSON lines file per conversation in a directory
+///
+/// Agent memory goes in its `agents` subdirectory: a JSON file of facts,
+/// rewritten on every change, and a JSON lines file of episodes per agent.
+/// Fact changes are serialized within one store, not across processes.
+#[derive(Debug, Clone)]
+pub struct JsonlMemoryStore {
+    dir: PathBuf,
+    facts_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl JsonlMemoryStore {
+    /// Store conversations in `dir`, created on first append
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            facts_lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    fn path(&self, conversation: &str) -> Result<PathBuf> {
+        storage::id_path(&self.dir, conversation, "jsonl").ok_or_else(|| {
+            AgentError::Memory(format!(
+                "Conversation id '{}' must use letters, digits, '-', '_' or '.'",
+                conversation
+            ))
+        })
+    }
+
+    /// `agents/<agent>.<extension>`, creating the directory
+    async fn agent_path(&self, agent: &str, extension: &str) -> Result<PathBuf> {
+        let dir = self.dir.join("agents");
+        let path = storage::id_path(&dir, agent, extension).ok_or_else(|| {
+            AgentError::Memory(format!(
+                "Agent id '{}' must use letters, digits, '-', '_' or '.'",
+                agent
+            ))
+        })?;
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(memory_error)?;
+        Ok(path)
+    }
+
+    async fn read_facts(&self, path: &std::path::Path) -> Result<BTreeMap<String, String>> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(memory_error),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(memory_error(e)),
+        }
+    }
+
+    /// Replace the facts file through a rename, so readers never see it
+    /// half written
+    async fn write_facts(
+        &self,
+        path: &std::path::Path,
+        facts: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let bytes = serde_json::to_vec(facts).map_err(memory_error)?;
+        let temp = path.with_extension("json.tmp");
+        tokio::fs::write(&temp, bytes).await.map_err(memory_error)?;
+        tokio::fs::rename(&temp, path).await.map_err(memory_error)
+    }
+}
+
+#[async_trait]
+impl MemoryStore for JsonlMemoryStore {
+    async fn append(&self, conversation: &str, messages: &[Message]) -> Result<()> {
+        let path = self.path(conversation)?;
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(memory_error)?;
+        storage::append_json_lines(&path, messages)
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn load(&self, conversation: &str) -> Result<Vec<Message>> {
+        let text = match tokio::fs::read_to_string(self.path(conversation)?).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(memory_error(e)),
+        };
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(memory_error))
+            .collect()
+    }
+
+    async fn clear(&self, conversation: &str) -> Result<bool> {
+        match tokio::fs::remove_file(self.path(conversation)?).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(memory_error(e)),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        storage::list_ids(&self.dir, "jsonl")
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn facts(&self, agent: &str) -> Result<BTreeMap<String, String>> {
+        self.read_facts(&self.agent_path(agent, "facts.json").await?)
+            .await
+    }
+
+    async fn set_fact(&self, agent: &str, key: &str, value: &str) -> Result<()> {
+        let path = self.agent_path(agent, "facts.json").await?;
+        let _guard = self.facts_lock.lock().await;
+        let mut facts = self.read_facts(&path).await?;
+        facts.insert(key.to_string(), value.to_string());
+        self.write_facts(&path, &facts).await
+    }
+
+    async fn remove_fact(&self, agent: &str, key: &str) -> Result<bool> {
+        let path = self.agent_path(agent, "facts.json").await?;
+        let _guard = self.facts_lock.lock().await;
+        let mut facts = self.read_facts(&path).await?;
+        if facts.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.write_facts(&path, &facts).await?;
+        Ok(true)
+    }
+
+    async fn append_episode(&self, agent: &str, episode: &Episode) -> Result<()> {
+        let path = self.agent_path(agent, "episodes.jsonl").await?;
+        storage::append_json_line(&path, episode)
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn episodes(&self, agent: &str) -> Result<Vec<Episode>> {
+        let path = self.agent_path(agent, "episodes.jsonl").await?;
+        let text = match tokio::fs::read_to_string(path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(memory_error(e)),
+        };
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(memory_error))
+            .collect()
+    }
+}
+
+/// Conversations in a SQLite table, one row per message (feature = "sqlite")
+#[cfg(feature = "sqlite")]
+#[derive(Clone)]
+pub struct SqliteMemoryStore {
+    db: storage::SqliteDb,
+}
+
+#[cfg(feature = "sqlite")]
+const MEMORY_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS agent_memory (
+    conversation TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    PRIMARY KEY (conversation, seq)
+);
+CREATE TABLE IF NOT EXISTS agent_facts (
+    agent TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (agent, key)
+);
+CREATE TABLE IF NOT EXISTS agent_episodes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    agent TEXT NOT NULL,
+    text TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+)";
+
+#[cfg(feature = "sqlite")]
+impl SqliteMemoryStore {
+    /// Open `path`, creating the database and its table if needed
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let db = storage::SqliteDb::open(path.as_ref(), MEMORY_SCHEMA, AgentError::Memory)?;
+        Ok(Self { db })
+    }
+
+    /// Conversations kept in an in-process database, lost with the store
+    pub fn open_in_memory() -> Result<Self> {
+        let db = storage::SqliteDb::open_in_memory(MEMORY_SCHEMA, AgentError::Memory)?;
+        Ok(Self { db })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait]
+impl MemoryStore for SqliteMemoryStore {
+    async fn append(&self, conversation: &str, messages: &[Message]) -> Result<()> {
+        let rows = messages
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(memory_error)?;
+        let conversation = conversation.to_string();
+        self.db
+            .with_connection(move |db| {
+                // One transaction, so concurrent appends cannot take the same seq
+                let tx = db
+                    .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+                    .map_err(memory_error)?;
+                let next: i64 = tx
+                .query_row(
+                    "SELECT COALESCE(MAX(seq) + 1, 0) FROM agent_memory WHERE conversation = ?1",
+                    [&conversation],
+                    |row| row.get(0),
+                )
+                .map_err(memory_error)?;
+                for (i, message) in rows.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO agent_memory (conversation, seq, message) VALUES (?1, ?2, ?3)",
+                        rusqlite::params![conversation, next + i as i64, message],
+                    )
+                    .map_err(memory_error)?;
+                }
+                tx.commit().map_err(memory_error)
+            })
+            .await
+    }
+
+    async fn load(&self, conversation: &str) -> Result<Vec<Message>> {
+        let conversation = conversation.to_string();
+        let rows: Vec<String> = self
+            .db
+            .with_connection(move |db| {
+                let mut statement = db
+                    .prepare(
+                        "SELECT message FROM agent_memory WHERE conversation = ?1 ORDER BY seq",
+                    )
+                    .map_err(memory_error)?;
+                let rows = statement
+                    .query_map([&conversation], |row| row.get(0))
+                    .map_err(memory_error)?
+                    .collect::<std::result::Result<Vec<String>, _>>()
+                    .map_err(memory_error)?;
+                Ok(rows)
+            })
+            .await?;
+        rows.iter()
+            .map(|row| serde_json::from_str(row).map_err(memory_error))
+            .collect()
+    }
+
+    async fn clear(&self, conversation: &str) -> Result<bool> {
+        let conversation = conversation.to_string();
+        self.db
+            .with_connection(move |db| {
+                db.execute(
+                    "DELETE FROM agent_memory WHERE conversation = ?1",
+                    [&conversation],
+                )
+                .map(|rows| rows > 0)
+                .map_err(memory_error)
+            })
+            .await
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        self.db
+            .with_connection(|db| {
+                let mut statement = db
+                    .prepare("SELECT DISTINCT conversation FROM agent_memory ORDER BY conversation")
+                    .map_err(memory_error)?;
+                let ids = statement
+                    .query_map([], |row| row.get(0))
+                    .map_err(memory_error)?
+                    .collect::<std::result::Result<Vec<String>, _>>()
+                    .map_err(memory_error)?;
+                Ok(ids)
+            })
+            .await
+    }
+
+    async fn facts(&self, agent: &str) -> Result<BTreeMap<String, String>> {
+        let agent = agent.to_string();
+        self.db
+            .with_connection(move |db| {
+                let mut statement = db
+                    .prepare("SELECT key, value FROM agent_facts WHERE agent = ?1")
+                    .map_err(memory_error)?;
+                let facts = statement
+                    .query_map([&agent], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .map_err(memory_error)?
+                    .collect::<std::result::Result<BTreeMap<String, String>, _>>()
+                    .map_err(memory_error)?;
+                Ok(facts)
+            })
+            .await
+    }
+
+    async fn set_fact(&self, agent: &str, key: &str, value: &str) -> Result<()> {
+        let params = (agent.to_string(), key.to_string(), value.to_string());
+        self.db
+            .with_connection(move |db| {
+                db.execute(
+                    "INSERT OR REPLACE INTO agent_facts (agent, key, value) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![params.0, params.1, params.2],
+                )
+                .map(|_| ())
+                .map_err(memory_error)
+            })
+            .await
+    }
+
+    async fn remove_fact(&self, agent: &str, key: &str) -> Result<bool> {
+        let (agent, key) = (agent.to_string(), key.to_string());
+        self.db
+            .with_connection(move |db| {
+                db.execute(
+                    "DELETE FROM agent_facts WHERE agent = ?1 AND key = ?2",
+                    [&agent, &key],
+                )
+                .map(|rows| rows > 0)
+                .map_err(memory_error)
+            })
+            .await
+    }
+
+    async fn append_episode(&self, agent: &str, episode: &Episode) -> Result<()> {
+        let (agent, episode) = (agent.to_string(), episode.clone());
+        self.db
+            .with_connection(move |db| {
+                db.execute(
+                    "INSERT INTO agent_episodes (agent, text, timestamp) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![agent, episode.text, episode.timestamp as i64],
+                )
+                .map(|_| ())
+                .map_err(memory_error)
+            })
+            .await
+    }
+
+    async fn episodes(&self, agent: &str) -> Result<Vec<Episode>> {
+        let agent = agent.to_string();
+        self.db
+            .with_connection(move |db| {
+                let mut statement = db
+                    .prepare(
+                        "SELECT text, timestamp FROM agent_episodes WHERE agent = ?1 ORDER BY id",
+                    )
+                    .map_err(memory_error)?;
+                let episodes = statement
+                    .query_map([&agent], |row| {
+                        Ok(Episode {
+                            text: row.get(0)?,
+                            timestamp: row.get::<_, i64>(1)? as u64,
+                        })
+                    })
+                    .map_err(memory_error)?
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(memory_error)?;
+                Ok(episodes)
+            })
+            .await
+    }
+}
+
+/// Conversations as Redis lists, one JSON message per element
+/// (feature = "redis")
+#[cfg(feature = "redis")]
+#[derive(Clone)]
+pub struct RedisMemoryStore {
+    connection: redis::aio::MultiplexedConnection,
+    prefix: String,
+    agent_prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisMemoryStore {
+    /// Connect to `url`, e.g. `redis://127.0.0.1/`; keys are
+    /// `agent:memory:<conversation>`, and `agent:facts:<agent>` and
+    /// `agent:episodes:<agent>` for agent memory
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(memory_error)?;
+        let connection = client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(memory_error)?;
+        Ok(Self {
+            connection,
+            prefix: "agent:memory:".to_string(),
+            agent_prefix: "agent:".to_string(),
+        })
+    }
+
+    /// Prefix for keys instead of `agent:memory:`
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Prefix for agent memory keys instead of `agent:`
+    ///
+    /// Keep it from overlapping the conversation prefix, or agent memory
+    /// shows up in `list`.
+    pub fn with_agent_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.agent_prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, conversation: &str) -> String {
+        format!("{}{}", self.prefix, conversation)
+    }
+
+    fn agent_key(&self, kind: &str, agent: &str) -> String {
+        format!("{}{}:{}", self.agent_prefix, kind, agent)
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl MemoryStore for RedisMemoryStore {
+    async fn append(&self, conversation: &str, messages: &[Message]) -> Result<()> {
+        use redis::AsyncCommands;
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let values = messages
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(memory_error)?;
+        self.connection
+            .clone()
+            .rpush::<_, _, ()>(self.key(conversation), values)
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn load(&self, conversation: &str) -> Result<Vec<Message>> {
+        use redis::AsyncCommands;
+        let values: Vec<String> = self
+            .connection
+            .clone()
+            .lrange(self.key(conversation), 0, -1)
+            .await
+            .map_err(memory_error)?;
+        values
+            .iter()
+            .map(|value| serde_json::from_str(value).map_err(memory_error))
+            .collect()
+    }
+
+    async fn clear(&self, conversation: &str) -> Result<bool> {
+        use redis::AsyncCommands;
+        let removed: i64 = self
+            .connection
+            .clone()
+            .del(self.key(conversation))
+            .await
+            .map_err(memory_error)?;
+        Ok(removed > 0)
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        use redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let mut keys: Vec<String> = {
+            let mut iter = connection
+                .scan_match::<_, String>(format!("{}*", self.prefix))
+                .await
+                .map_err(memory_error)?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        keys.sort_unstable();
+        keys.dedup();
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect())
+    }
+
+    async fn facts(&self, agent: &str) -> Result<BTreeMap<String, String>> {
+        use redis::AsyncCommands;
+        self.connection
+            .clone()
+            .hgetall(self.agent_key("facts", agent))
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn set_fact(&self, agent: &str, key: &str, value: &str) -> Result<()> {
+        use redis::AsyncCommands;
+        self.connection
+            .clone()
+            .hset::<_, _, _, ()>(self.agent_key("facts", agent), key, value)
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn remove_fact(&self, agent: &str, key: &str) -> Result<bool> {
+        use redis::AsyncCommands;
+        let removed: i64 = self
+            .connection
+            .clone()
+            .hdel(self.agent_key("facts", agent), key)
+            .await
+            .map_err(memory_error)?;
+        Ok(removed > 0)
+    }
+
+    async fn append_episode(&self, agent: &str, episode: &Episode) -> Result<()> {
+        use redis::AsyncCommands;
+        let value = serde_json::to_string(episode).map_err(memory_error)?;
+        self.connection
+            .clone()
+            .rpush::<_, _, ()>(self.agent_key("episodes", agent), value)
+            .await
+            .map_err(memory_error)
+    }
+
+    async fn episodes(&self, agent: &str) -> Result<Vec<Episode>> {
+        use redis::AsyncCommands;
+        let values: Vec<String> = self
+            .connection
+            .clone()
+            .lrange(self.agent_key("episodes", agent), 0, -1)
+            .await
+            .map_err(memory_error)?;
+        values
+            .iter()
+            .map(|value| serde_json::from_str(value).map_err(memory_error))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn appends_and_reloads(store: Arc<dyn MemoryStore>) {
+        let mut memory = Memory::open("chat-1", store.clone()).await.unwrap();
+        memory.push(Message::user("hi"));
+        memory.push(Message::assistant("hello"));
+        memory.flush().await.unwrap();
+        assert!(memory.pending().is_empty());
+
+        // Another process picks the conversation up and adds to it
+        let mut other = Memory::open("chat-1", store.clone()).await.unwrap();
+        assert_eq!(other.messages().len(), 2);
+        other.push(Message::user("bye"));
+        other.flush().await.unwrap();
+        other.flush().await.unwrap();
+
+        let stored = store.load("chat-1").await.unwrap();
+        let texts: Vec<String> = stored.iter().map(Message::content_as_text).collect();
+        assert_eq!(texts, ["hi", "hello", "bye"]);
+        assert_eq!(store.list().await.unwrap(), vec!["chat-1".to_string()]);
+
+        other.clear().await.unwrap();
+        assert!(store.load("chat-1").await.unwrap().is_empty());
+        assert!(!store.clear("chat-1").await.unwrap());
+    }
+
+    async fn keeps_agent_memory_apart(store: Arc<dyn MemoryStore>) {
+        let bob = AgentMemory::new("bob", store.clone());
+        bob.remember("city", "Paris").await.unwrap();
+        bob.remember("city", "Lyon").await.unwrap();
+        bob.remember("pet", "cat").await.unwrap();
+        bob.record_episode("Moved house").await.unwrap();
+        assert!(bob.forget("pet").await.unwrap());
+        assert!(!bob.forget("pet").await.unwrap());
+
+        // A conversation with the old `<agent>.memory` id is just a conversation
+        store
+            .append("bob.memory", &[Message::user("not a fact")])
+            .await
+            .unwrap();
+        assert_eq!(store.list().await.unwrap(), vec!["bob.memory".to_string()]);
+
+        let facts = bob.facts().await.unwrap();
+        assert_eq!(facts.len(), 1);
+        assert_eq!(facts["city"], "Lyon");
+        let episodes = bob.episodes().await.unwrap();
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].text, "Moved house");
+        assert!(AgentMemory::new("ada", store.clone())
+            .facts()
+            .await
+            .unwrap()
+            .is_empty());
+        store.clear("bob.memory").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_appends_and_reloads() {
+        appends_and_reloads(Arc::new(InMemoryStore::new())).await;
+        keeps_agent_memory_apart(Arc::new(InMemoryStore::new())).await;
+    }
+
+    #[tokio::test]
+    async fn jsonl_store_appends_and_reloads() {
+        let dir = std::env::temp_dir().join(format!("agent-sdk-memory-{}", std::process::id()));
+        let store = JsonlMemoryStore::new(&dir);
+        appends_and_reloads(Arc::new(store.clone())).await;
+        keeps_agent_memory_apart(Arc::new(store.clone())).await;
+        assert!(matches!(
+            store.append("../escape", &[Message::user("x")]).await,
+            Err(AgentError::Memory(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_store_appends_and_reloads() {
+        appends_and_reloads(Arc::new(SqliteMemoryStore::open_in_memory().unwrap())).await;
+        keeps_agent_memory_apart(Arc::new(SqliteMemoryStore::open_in_memory().unwrap())).await;
+    }
+
+    #[test]
+    fn records_only_the_turns_after_its_own() {
+        let mut memory = Memory::new("chat");
+        memory.push(Message::user("earlier"));
+        memory.record_run(
+            &[
+                Message::system("be brief"),
+                Message::user("earlier"),
+                Message::user("now"),
+                Message::assistant("ok"),
+            ],
+            1,
+        );
+        assert_eq!(memory.messages().len(), 3);
+        assert_eq!(memory.pending().len(), 3);
+        assert_eq!(memory.messages()[2].content_as_text(), "ok");
+    }
+}
@@ -1,18 +1,22 @@
+use super::pipeline::RequestPipeline;
 use super::{
-    GenerateOptions, GenerateResponse, LlmProvider, Message, ProviderError, Result, Role, Usage,
-    ProviderClient, ProviderClientBuilder, RetryConfig, RateLimitConfig, TimeoutConfig,
-    MiddlewareChain, ResponseCache, CacheConfig, CacheKey, ContextWindowManager, ContextWindowConfig,
+    GenerateOptions, GenerateResponse, LlmProvider, Message, ProviderCapabilities, ProviderError,
+    Result, Role, StreamEvent, StreamEvents, ToolSchema, ToolSelection, Usage,
+    ProviderClient, KeepAliveConfig, ProviderClientBuilder, RetryConfig, RateLimitConfig, TimeoutConfig,
+    MiddlewareChain, ResponseCache, CacheConfig, ContextWindowManager, ContextWindowConfig,
+    FileProvider, FileUpload, ProviderFile, DeferredProvider, JobHandle, JobStatus,
 };
-use futures_util::StreamExt;
+use super::deferred::check_deferrable;
+use super::stream_decode::{sse_data, LineDecoder};
 use std::env;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
-use tokio::sync::mpsc;
 
 const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
 const ANTHROPIC_VERSION: &str = "2023-06-01";
 const DEFAULT_MAX_TOKENS: u32 = 1024;
+/// Beta flag required by the Files API and by messages that reference files
+const FILES_BETA: &str = "files-api-2025-04-14";
 
 /// Configuration for Anthropic prompt caching
 #[derive(Debug, Clone)]
@@ -53,6 +57,29 @@ impl PromptCacheConfig {
             cache_tool_definitions: false,
         }
     }
+
+    /// Mark the system prompt and the last tool definition in `body` as
+    /// cache breakpoints
+    pub(super) fn apply(&self, body: &mut serde_json::Value) {
+        if !self.enabled {
+            return;
+        }
+        let breakpoint = serde_json::json!({"type": "ephemeral"});
+        if self.cache_system_messages {
+            if let Some(system) = body["system"].as_str() {
+                body["system"] = serde_json::json!([{
+                    "type": "text",
+                    "text": system,
+                    "cache_control": breakpoint,
+                }]);
+            }
+        }
+        if self.cache_tool_definitions {
+            if let Some(tool) = body["tools"].as_array_mut().and_then(|t| t.last_mut()) {
+                tool["cache_control"] = breakpoint;
+            }
+        }
+    }
 }
 
 pub struct AnthropicProvider {
@@ -181,20 +208,55 @@ impl AnthropicProvider {
                 }
                 img
             }
+            ContentBlock::File { file_id } => serde_json::json!({
+                "type": "document",
+                "source": {"type": "file", "file_id": file_id},
+            }),
+            ContentBlock::ToolUse { id, name, input } => serde_json::json!({
+                "type": "tool_use",
+                "id": id,
+                "name": name,
+                "input": input,
+            }),
+            ContentBlock::ToolResult { tool_use_id, content, is_error, images } => {
+                let content = if images.is_empty() {
+                    serde_json::json!(content)
+                } else {
+                    let mut blocks = vec![ContentBlock::Text { text: content.clone() }];
+                    blocks.extend(images.iter().map(|source| ContentBlock::Image {
+                        source: source.clone(),
+                        detail: None,
+                    }));
+                    Self::format_message_content(&blocks)
+                };
+                serde_json::json!({
+                    "type": "tool_result",
+                    "tool_use_id": tool_use_id,
+                    "content": content,
+                    "is_error": is_error,
+                })
+            }
         }).collect::<Vec<_>>())
     }
 
-    fn build_request_body_for_model(
+    pub(super) fn build_request_body_for_model(
         model: &str,
         messages: Vec<Message>,
         options: Option<GenerateOptions>,
         stream: bool,
     ) -> serde_json::Value {
         let opts = options.unwrap_or_default();
-        let (system, messages_json) = Self::split_system_and_messages(messages);
+        let (system, mut messages_json) = Self::split_system_and_messages(messages);
+
+        if let Some(prefix) = Self::prefill_text(&opts) {
+            messages_json.push(serde_json::json!({
+                "role": "assistant",
+                "content": prefix,
+            }));
+        }
 
         let mut body = serde_json::json!({
-            "model": model,
+            "model": opts.model.as_deref().unwrap_or(model),
             "messages": messages_json,
             "stream": stream,
             "max_tokens": opts.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
@@ -216,13 +278,79 @@ impl AnthropicProvider {
         body
     }
 
+    pub(super) fn add_tools_to_body(
+        body: &mut serde_json::Value,
+        tools: &[ToolSchema],
+        options: Option<&GenerateOptions>,
+    ) {
+        if tools.is_empty() {
+            return;
+        }
+        let mut choice = match options.and_then(|o| o.tool_choice.as_ref()) {
+            Some(ToolSelection::Any) => Some(serde_json::json!({"type": "any"})),
+            Some(ToolSelection::Tool(name)) => {
+                Some(serde_json::json!({"type": "tool", "name": name}))
+            }
+            None => None,
+        };
+        if options.and_then(|o| o.parallel_tool_calls) == Some(false) {
+            choice.get_or_insert_with(|| serde_json::json!({"type": "auto"}))
+                ["disable_parallel_tool_use"] = serde_json::json!(true);
+        }
+        if let Some(choice) = choice {
+            body["tool_choice"] = choice;
+        }
+        body["tools"] = serde_json::json!(tools
+            .iter()
+            .map(|tool| match &tool.native {
+                Some(native) => native.clone(),
+                None => serde_json::json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.parameters,
+                }),
+            })
+            .collect::<Vec<_>>());
+    }
+
+    /// Anthropic rejects a final assistant turn that ends with whitespace
+    pub(super) fn prefill_text(opts: &GenerateOptions) -> Option<&str> {
+        opts.assistant_prefix
+            .as_deref()
+            .map(str::trim_end)
+            .filter(|p| !p.is_empty())
+    }
+
+    fn check_supported(options: &Option<GenerateOptions>) -> Result<()> {
+        if options.as_ref().is_some_and(|o| o.constraint.is_some()) {
+            return Err(ProviderError::Other(
+                "Constrained decoding is not supported by the Anthropic provider".to_string(),
+            ));
+        }
+        if options.as_ref().is_some_and(|o| o.response_format.is_some()) {
+            return Err(ProviderError::Other(
+                "Structured output is not supported by the Anthropic provider; use generate_structured"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     fn build_request_body(
         &self,
         messages: Vec<Message>,
         options: Option<GenerateOptions>,
         stream: bool,
     ) -> serde_json::Value {
-        Self::build_request_body_for_model(&self.model, messages, options, stream)
+        let extensions = options
+            .as_ref()
+            .map(|o| o.extensions.for_provider("anthropic"))
+            .unwrap_or_default();
+        let mut body = Self::build_request_body_for_model(&self.model, messages, options, stream);
+        for (name, value) in extensions {
+            body[name] = value;
+        }
+        body
     }
 
     fn map_status_error(
@@ -246,16 +374,70 @@ impl AnthropicProvider {
         ProviderError::RequestFailed(format!("{}: {}", status, text))
     }
 
-    async fn send_request(&self, body: serde_json::Value) -> Result<reqwest::Response> {
+    async fn send_request(&self, mut body: serde_json::Value) -> Result<reqwest::Response> {
+        self.prompt_cache_config.apply(&mut body);
+        let betas = Self::required_betas(&body);
+        let streaming = body["stream"] == true;
+        self.send(|client| {
+            let mut request = client
+                .post(format!("{}/messages", self.base_url))
+                .header("content-type", "application/json")
+                .json(&body);
+            if streaming {
+                request = self.client.stream_request(request);
+            }
+            if betas.is_empty() {
+                request
+            } else {
+                request.header("anthropic-beta", betas.join(","))
+            }
+        })
+        .await
+    }
+
+    /// Beta flags needed for the files and versioned tools `body` uses
+    pub(super) fn required_betas(body: &serde_json::Value) -> Vec<&'static str> {
+        let mut betas = Vec::new();
+        if Self::references_files(body) {
+            betas.push(FILES_BETA);
+        }
+        let tool_types = body["tools"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|tool| tool["type"].as_str());
+        for tool_type in tool_types {
+            if let Some(beta) = computer_use_beta(tool_type) {
+                if !betas.contains(&beta) {
+                    betas.push(beta);
+                }
+            }
+        }
+        betas
+    }
+
+    /// Whether any message content is a `document` with a `file` source
+    fn references_files(body: &serde_json::Value) -> bool {
+        body["messages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["content"].as_array())
+            .flatten()
+            .any(|block| block["source"]["type"] == "file")
+    }
+
+    /// Send the request made by `build` with the API version, authentication,
+    /// rate limiting and retries
+    async fn send<F>(&self, build: F) -> Result<reqwest::Response>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
         let _guard = self.client.acquire_rate_limit().await;
 
         self.client.retry_policy().execute_with_retry(|| async {
-            let mut request = self
-                .client
-                .http_client()
-                .post(format!("{}/messages", self.base_url))
-                .header("anthropic-version", ANTHROPIC_VERSION)
-                .header("content-type", "application/json");
+            let mut request = build(self.client.http_client())
+                .header("anthropic-version", ANTHROPIC_VERSION);
 
             if self.api_key.trim().is_empty() && self.auth_token.is_none() {
                 return Err(ProviderError::AuthenticationFailed("No API key or auth token provided".to_string()));
@@ -270,7 +452,6 @@ impl AnthropicProvider {
             }
 
             let response = request
-                .json(&body)
                 .send()
                 .await
                 .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
@@ -286,7 +467,19 @@ impl AnthropicProvider {
         }).await
     }
 
-    fn parse_generate_response_with_model(
+    fn parse_file(json: &serde_json::Value) -> Result<ProviderFile> {
+        let id = json["id"]
+            .as_str()
+            .ok_or_else(|| ProviderError::ParseError("Missing `id` in file".to_string()))?;
+        Ok(ProviderFile {
+            id: id.to_string(),
+            filename: json["filename"].as_str().unwrap_or_default().to_string(),
+            bytes: json["size_bytes"].as_u64().unwrap_or_default(),
+            purpose: None,
+        })
+    }
+
+    pub(super) fn parse_generate_response_with_model(
         json: serde_json::Value,
         fallback_model: &str,
     ) -> GenerateResponse {
@@ -303,6 +496,20 @@ impl AnthropicProvider {
             })
             .unwrap_or_default();
 
+        let tool_calls = json["content"]
+            .as_array()
+            .map(|arr| {
+                arr.iter()
+                    .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some("tool_use"))
+                    .map(|block| crate::tool::ToolCall {
+                        id: block["id"].as_str().unwrap_or_default().to_string(),
+                        name: block["name"].as_str().unwrap_or_default().to_string(),
+                        parameters: block["input"].clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
         let usage = json.get("usage").map(|u| {
             let prompt_tokens = u["input_tokens"].as_u64().unwrap_or(0) as u32;
             let completion_tokens = u["output_tokens"].as_u64().unwrap_or(0) as u32;
@@ -324,6 +531,7 @@ impl AnthropicProvider {
             usage,
             model,
             finish_reason,
+            tool_calls,
         }
     }
 
@@ -331,6 +539,45 @@ impl AnthropicProvider {
         Ok(Self::parse_generate_response_with_model(json, &self.model))
     }
 
+    fn pipeline(&self) -> RequestPipeline<'_> {
+        RequestPipeline {
+            model: &self.model,
+            context_manager: self.context_manager.as_ref(),
+            cache: self.cache.as_ref(),
+            middleware: self.middleware.as_ref(),
+        }
+    }
+
+    async fn generate_inner(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Result<GenerateResponse> {
+        Self::check_supported(&options)?;
+        let tools = &tools;
+        self.pipeline()
+            .generate(messages, tools, options, |messages, options| async move {
+                let prefix = options
+                    .as_ref()
+                    .and_then(Self::prefill_text)
+                    .map(String::from);
+                let mut body = self.build_request_body(messages, options.clone(), false);
+                Self::add_tools_to_body(&mut body, tools, options.as_ref());
+                let response = self.send_request(body).await?;
+                let json: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+                let mut response = self.parse_generate_response(json)?;
+                if let Some(prefix) = prefix {
+                    response.content.insert_str(0, &prefix);
+                }
+                Ok(response)
+            })
+            .await
+    }
+
     fn extract_stream_text(event_json: &serde_json::Value) -> Option<String> {
         let event_type = event_json.get("type").and_then(|v| v.as_str())?;
         if event_type == "content_block_delta"
@@ -340,6 +587,89 @@ impl AnthropicProvider {
         }
         None
     }
+
+    /// Translate one SSE event into typed stream events
+    ///
+    /// Tool call deltas are indexed by their content block index.
+    pub(super) fn parse_stream_event(
+        event_json: &serde_json::Value,
+        state: &mut StreamState,
+    ) -> Result<Vec<StreamEvent>> {
+        let mut events = Vec::new();
+        let index = event_json["index"].as_u64().unwrap_or(0) as usize;
+
+        match event_json["type"].as_str().unwrap_or("") {
+            "message_start" => {
+                state.input_tokens = event_json["message"]["usage"]["input_tokens"]
+                    .as_u64()
+                    .unwrap_or(0) as u32;
+            }
+            "content_block_start" => {
+                let block = &event_json["content_block"];
+                if block["type"].as_str() == Some("tool_use") {
+                    events.push(StreamEvent::ToolCallDelta {
+                        index,
+                        id: block["id"].as_str().map(String::from),
+                        name: block["name"].as_str().map(String::from),
+                        arguments_delta: String::new(),
+                    });
+                }
+            }
+            "content_block_delta" => {
+                if let Some(text) = Self::extract_stream_text(event_json) {
+                    events.push(StreamEvent::TextDelta(text));
+                } else if event_json["delta"]["type"].as_str() == Some("input_json_delta") {
+                    events.push(StreamEvent::ToolCallDelta {
+                        index,
+                        id: None,
+                        name: None,
+                        arguments_delta: event_json["delta"]["partial_json"]
+                            .as_str()
+                            .unwrap_or("")
+                            .to_string(),
+                    });
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = event_json["delta"]["stop_reason"].as_str() {
+                    state.finish_reason = Some(reason.to_string());
+                }
+                if let Some(output) = event_json["usage"]["output_tokens"].as_u64() {
+                    let output = output as u32;
+                    events.push(StreamEvent::UsageUpdate(Usage {
+                        prompt_tokens: state.input_tokens,
+                        completion_tokens: output,
+                        total_tokens: state.input_tokens + output,
+                    }));
+                }
+            }
+            "message_stop" => {
+                events.push(StreamEvent::Done {
+                    finish_reason: state.finish_reason.take(),
+                });
+            }
+            "error" => {
+                let message = event_json["error"]["message"]
+                    .as_str()
+                    .unwrap_or("unknown stream error");
+                return Err(ProviderError::RequestFailed(message.to_string()));
+            }
+            _ => {}
+        }
+
+        Ok(events)
+    }
+}
+
+
+/// Running state while parsing an Anthropic SSE stream
+///
+/// Input tokens arrive with `message_start` and output tokens with
+/// `message_delta`, so both are tracked to report combined usage.
+#[derive(Debug, Default)]
+pub(super) struct StreamState {
+    input_tokens: u32,
+    finish_reason: Option<String>,
 }
 
 /// Builder for creating an AnthropicProvider with custom configuration
@@ -414,12 +744,24 @@ impl AnthropicProviderBuilder {
         self
     }
 
+    /// Set the connection pool and keep-alive configuration
+    pub fn keep_alive_config(mut self, config: KeepAliveConfig) -> Self {
+        self.client_builder = self.client_builder.keep_alive_config(config);
+        self
+    }
+
     /// Set a proxy URL
     pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
         self.client_builder = self.client_builder.proxy(proxy);
         self
     }
 
+    /// Add a header sent with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.client_builder = self.client_builder.header(name, value);
+        self
+    }
+
     /// Set the middleware chain
     pub fn middleware(mut self, middleware: MiddlewareChain) -> Self {
         self.middleware = Some(middleware);
@@ -485,92 +827,224 @@ impl AnthropicProviderBuilder {
     }
 }
 
-impl LlmProvider for AnthropicProvider {
-    fn name(&self) -> &str {
-        "anthropic"
+/// Beta flag for a versioned computer-use tool type such as `computer_20250124`
+pub(crate) fn computer_use_beta(tool_type: &str) -> Option<&'static str> {
+    let (name, version) = tool_type.rsplit_once('_')?;
+    if !matches!(name, "computer" | "text_editor" | "bash") {
+        return None;
     }
+    match version {
+        "20241022" => Some("computer-use-2024-10-22"),
+        "20250124" => Some("computer-use-2025-01-24"),
+        _ => None,
+    }
+}
 
-    fn model(&self) -> &str {
-        &self.model
+/// Files through the `/files` beta endpoints; referenced files are sent as
+/// `document` blocks
+impl FileProvider for AnthropicProvider {
+    fn upload_file(
+        &self,
+        upload: FileUpload,
+    ) -> Pin<Box<dyn Future<Output = Result<ProviderFile>> + Send + '_>> {
+        Box::pin(async move {
+            let (content_type, body) = upload.multipart();
+            let response = self
+                .send(|client| {
+                    client
+                        .post(format!("{}/files", self.base_url))
+                        .header("anthropic-beta", FILES_BETA)
+                        .header("content-type", content_type.as_str())
+                        .body(body.clone())
+                })
+                .await?;
+            let json: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+            Self::parse_file(&json)
+        })
     }
 
-    fn generate(
+    fn list_files(&self) -> Pin<Box<dyn Future<Output = Result<Vec<ProviderFile>>> + Send + '_>> {
+        Box::pin(async move {
+            let response = self
+                .send(|client| {
+                    client
+                        .get(format!("{}/files", self.base_url))
+                        .header("anthropic-beta", FILES_BETA)
+                })
+                .await?;
+            let json: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+            json["data"]
+                .as_array()
+                .map(|data| data.iter().map(Self::parse_file).collect())
+                .unwrap_or_else(|| Ok(Vec::new()))
+        })
+    }
+
+    fn delete_file<'a>(
+        &'a self,
+        file_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            self.send(|client| {
+                client
+                    .delete(format!("{}/files/{}", self.base_url, file_id))
+                    .header("anthropic-beta", FILES_BETA)
+            })
+            .await?;
+            Ok(())
+        })
+    }
+}
+
+/// Deferred generation through the Message Batches API, one request per
+/// batch; batches finish within 24 hours at a discount
+impl DeferredProvider for AnthropicProvider {
+    fn submit(
         &self,
         messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
         options: Option<GenerateOptions>,
-    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+    ) -> Pin<Box<dyn Future<Output = Result<JobHandle>> + Send + '_>> {
         Box::pin(async move {
-            // Apply context window management if configured
-            let messages = if let Some(manager) = &self.context_manager {
-                manager.truncate_if_needed(messages)
-            } else {
-                messages
-            };
-
-            // Check cache first
-            if let Some(cache) = &self.cache {
-                let key = CacheKey::from_request(&messages, &self.model, &options);
-                if let Some(cached) = cache.get(&key).await {
-                    return Ok(cached);
-                }
+            Self::check_supported(&options)?;
+            check_deferrable(&options)?;
+            let mut params = self.build_request_body(messages, options.clone(), false);
+            Self::add_tools_to_body(&mut params, &tools, options.as_ref());
+            if let Some(params) = params.as_object_mut() {
+                params.remove("stream");
             }
+            let betas = Self::required_betas(&params);
+            let body = serde_json::json!({
+                "requests": [{"custom_id": "generation", "params": params}],
+            });
+            let response = self
+                .send(|client| {
+                    let request = client
+                        .post(format!("{}/messages/batches", self.base_url))
+                        .json(&body);
+                    if betas.is_empty() {
+                        request
+                    } else {
+                        request.header("anthropic-beta", betas.join(","))
+                    }
+                })
+                .await?;
+            let batch: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+            let id = batch["id"]
+                .as_str()
+                .ok_or_else(|| ProviderError::ParseError("Missing `id` in batch".to_string()))?;
+            Ok(JobHandle::new(id, "anthropic"))
+        })
+    }
 
-            // Execute middleware before_request
-            let mut ctx = super::RequestContext {
-                messages: messages.clone(),
-                options: options.clone(),
-                metadata: std::collections::HashMap::new(),
+    fn poll<'a>(
+        &'a self,
+        job: &'a JobHandle,
+    ) -> Pin<Box<dyn Future<Output = Result<JobStatus>> + Send + 'a>> {
+        Box::pin(async move {
+            let url = format!("{}/messages/batches/{}", self.base_url, job.id);
+            let batch: serde_json::Value = self
+                .send(|client| client.get(&url))
+                .await?
+                .json()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+            if batch["processing_status"] != "ended" {
+                return Ok(JobStatus::Pending);
+            }
+            let Some(results_url) = batch["results_url"].as_str() else {
+                return Ok(JobStatus::Failed("Batch produced no results".to_string()));
             };
+            let results = self
+                .send(|client| client.get(results_url))
+                .await?
+                .text()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+            Ok(Self::parse_batch_results(&results, &self.model))
+        })
+    }
 
-            if let Some(mw) = &self.middleware {
-                if let Err(e) = mw.execute_before(&mut ctx).await {
-                    if let Some(mw) = &self.middleware {
-                        let _ = mw.execute_error(&e).await;
-                    }
-                    return Err(e);
-                }
-            }
+    fn cancel<'a>(
+        &'a self,
+        job: &'a JobHandle,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let url = format!("{}/messages/batches/{}/cancel", self.base_url, job.id);
+            self.send(|client| client.post(&url)).await?;
+            Ok(())
+        })
+    }
+}
 
-            // Make the actual request
-            let result = async {
-                let body = self.build_request_body(ctx.messages.clone(), ctx.options.clone(), false);
-                let response = self.send_request(body).await?;
-                let json: serde_json::Value = response
-                    .json()
-                    .await
-                    .map_err(|e| ProviderError::ParseError(e.to_string()))?;
-                self.parse_generate_response(json)
-            }.await;
-
-            match result {
-                Ok(response) => {
-                    // Store in cache
-                    if let Some(cache) = &self.cache {
-                        let key = CacheKey::from_request(&messages, &self.model, &options);
-                        cache.put(key, response.clone()).await;
-                    }
+impl AnthropicProvider {
+    fn parse_batch_results(results: &str, fallback_model: &str) -> JobStatus {
+        let Some(line) = results
+            .lines()
+            .find_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        else {
+            return JobStatus::Failed("Batch results are empty".to_string());
+        };
+        let result = &line["result"];
+        match result["type"].as_str() {
+            Some("succeeded") => JobStatus::Completed(Self::parse_generate_response_with_model(
+                result["message"].clone(),
+                fallback_model,
+            )),
+            Some("errored") => JobStatus::Failed(
+                result["error"]["error"]["message"]
+                    .as_str()
+                    .unwrap_or("Request failed")
+                    .to_string(),
+            ),
+            other => JobStatus::Failed(format!("Request {}", other.unwrap_or("failed"))),
+        }
+    }
+}
 
-                    // Execute middleware after_response
-                    let mut resp_ctx = super::ResponseContext {
-                        response: response.clone(),
-                        metadata: ctx.metadata,
-                    };
+impl LlmProvider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
 
-                    if let Some(mw) = &self.middleware {
-                        mw.execute_after(&mut resp_ctx).await?;
-                    }
+    fn model(&self) -> &str {
+        &self.model
+    }
 
-                    Ok(resp_ctx.response)
-                }
-                Err(e) => {
-                    // Execute middleware on_error
-                    if let Some(mw) = &self.middleware {
-                        let _ = mw.execute_error(&e).await;
-                    }
-                    Err(e)
-                }
-            }
-        })
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            assistant_prefill: true,
+            native_tools: true,
+            tool_choice: true,
+            ..Default::default()
+        }
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, Vec::new(), options))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, tools, options))
     }
 
     fn generate_stream(
@@ -579,32 +1053,72 @@ impl LlmProvider for AnthropicProvider {
         options: Option<GenerateOptions>,
     ) -> Pin<Box<dyn Future<Output = Result<super::StreamResponse>> + Send + '_>> {
         Box::pin(async move {
-            let body = self.build_request_body(messages, options, true);
+            let events = self
+                .generate_stream_events(messages, Vec::new(), options)
+                .await?;
+            Ok(super::StreamResponse::from_events(events))
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            Self::check_supported(&options)?;
+            let prefix = options
+                .as_ref()
+                .and_then(Self::prefill_text)
+                .map(String::from);
+            let tool_options = options.clone();
+            let mut body = self.build_request_body(messages, options, true);
+            Self::add_tools_to_body(&mut body, &tools, tool_options.as_ref());
+            let deadline = self.client.stream_deadline();
             let response = self.send_request(body).await?;
-            let (tx, rx) = mpsc::channel(100);
+            Ok(StreamEvents::spawn(100, |tx| async move {
+                if let Some(prefix) = prefix {
+                    if tx.send(Ok(StreamEvent::TextDelta(prefix))).await.is_err() {
+                        return;
+                    }
+                }
 
-            tokio::spawn(async move {
                 let mut stream = response.bytes_stream();
-                let mut buffer = String::new();
+                let mut lines = LineDecoder::new();
+                let mut state = StreamState::default();
 
-                while let Some(chunk) = stream.next().await {
+                loop {
+                    let chunk = match deadline.next(&mut stream).await {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(e) => {
+                            let _ = tx.send(Err(e)).await;
+                            return;
+                        }
+                    };
                     match chunk {
                         Ok(bytes) => {
-                            buffer.push_str(&String::from_utf8_lossy(&bytes));
-                            while let Some(line_end) = buffer.find('\n') {
-                                let line = buffer[..line_end].trim().to_string();
-                                buffer.drain(..=line_end);
-
-                                if let Some(data) = line.strip_prefix("data: ") {
+                            lines.push(&bytes);
+                            while let Some(line) = lines.next_line() {
+                                if let Some(data) = sse_data(&line) {
                                     if data.is_empty() {
                                         continue;
                                     }
                                     if let Ok(event_json) =
-                                        serde_json::from_str::<serde_json::Value>(data)
+                                        serde_json::from_slice::<serde_json::Value>(data)
                                     {
-                                        if let Some(text) = Self::extract_stream_text(&event_json) {
-                                            if tx.send(Ok(text)).await.is_err() {
-                                                break;
+                                        match Self::parse_stream_event(&event_json, &mut state) {
+                                            Ok(events) => {
+                                                for event in events {
+                                                    if tx.send(Ok(event)).await.is_err() {
+                                                        return;
+                                                    }
+                                                }
+                                            }
+                                            Err(e) => {
+                                                let _ = tx.send(Err(e)).await;
+                                                return;
                                             }
                                         }
                                     }
@@ -619,16 +1133,23 @@ impl LlmProvider for AnthropicProvider {
                         }
                     }
                 }
-            });
-
-            Ok(super::StreamResponse { receiver: rx })
+            }))
         })
     }
+
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(self.client.preconnect(&self.base_url))
+    }
+
+    fn rate_limiter(&self) -> Option<&super::RateLimiter> {
+        Some(self.client.rate_limiter())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::provider::{ContentBlock, ImageSource};
 
     #[test]
     fn split_system_messages_and_chat_messages() {
@@ -656,6 +1177,7 @@ mod tests {
                 max_tokens: Some(42),
                 top_p: Some(0.9),
                 stop: Some(vec!["END".to_string()]),
+                ..Default::default()
             }),
             false,
         );
@@ -667,6 +1189,134 @@ mod tests {
         assert_eq!(body["stop_sequences"][0], "END");
     }
 
+    #[test]
+    fn prompt_cache_marks_system_prompt_and_last_tool() {
+        let mut body = serde_json::json!({
+            "system": "sys",
+            "tools": [{"name": "a"}, {"name": "b"}],
+        });
+        PromptCacheConfig::default().apply(&mut body);
+
+        assert_eq!(body["system"][0]["text"], "sys");
+        assert_eq!(body["system"][0]["cache_control"]["type"], "ephemeral");
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(body["tools"][1]["cache_control"]["type"], "ephemeral");
+
+        let mut body = serde_json::json!({"system": "sys"});
+        PromptCacheConfig::disabled().apply(&mut body);
+        assert_eq!(body["system"], "sys");
+    }
+
+    #[test]
+    fn request_body_appends_trimmed_assistant_prefix() {
+        let body = AnthropicProvider::build_request_body_for_model(
+            "claude-3-5-sonnet-20241022",
+            vec![Message::user("give me json")],
+            Some(GenerateOptions {
+                assistant_prefix: Some("{\"answer\": ".to_string()),
+                ..Default::default()
+            }),
+            false,
+        );
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(messages[1]["content"], "{\"answer\":");
+    }
+
+    #[test]
+    fn request_body_uses_model_override() {
+        let body = AnthropicProvider::build_request_body_for_model(
+            "claude-3-5-haiku-20241022",
+            vec![Message::user("hello")],
+            Some(GenerateOptions {
+                model: Some("claude-3-5-sonnet-20241022".to_string()),
+                ..Default::default()
+            }),
+            false,
+        );
+
+        assert_eq!(body["model"], "claude-3-5-sonnet-20241022");
+    }
+
+    #[test]
+    fn request_body_sends_native_tools_and_tool_images() {
+        let mut body = AnthropicProvider::build_request_body_for_model(
+            "claude-3-7-sonnet-20250219",
+            vec![Message::tool_results(vec![ContentBlock::tool_result_with_images(
+                "toolu_1",
+                "clicked",
+                vec![ImageSource::Base64 {
+                    media_type: "image/png".to_string(),
+                    data: "iVBORw0KGgo=".to_string(),
+                }],
+            )])],
+            None,
+            false,
+        );
+        let computer = serde_json::json!({
+            "type": "computer_20250124",
+            "name": "computer",
+            "display_width_px": 1280,
+            "display_height_px": 800
+        });
+        AnthropicProvider::add_tools_to_body(
+            &mut body,
+            &[ToolSchema {
+                name: "computer".to_string(),
+                description: "Use the computer".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+                native: Some(computer.clone()),
+            }],
+            None,
+        );
+
+        assert_eq!(body["tools"][0], computer);
+        assert_eq!(
+            AnthropicProvider::required_betas(&body),
+            vec!["computer-use-2025-01-24"]
+        );
+        let result = &body["messages"][0]["content"][0];
+        assert_eq!(result["content"][0]["text"], "clicked");
+        assert_eq!(result["content"][1]["type"], "image");
+        assert_eq!(result["content"][1]["source"]["media_type"], "image/png");
+    }
+
+    #[test]
+    fn request_body_references_uploaded_files() {
+        let body = AnthropicProvider::build_request_body_for_model(
+            "claude-3-5-haiku-20241022",
+            vec![Message::user_with_file("summarize this", "file_011")],
+            None,
+            false,
+        );
+
+        let document = &body["messages"][0]["content"][1];
+        assert_eq!(document["type"], "document");
+        assert_eq!(document["source"]["file_id"], "file_011");
+        assert!(AnthropicProvider::references_files(&body));
+
+        let body = AnthropicProvider::build_request_body_for_model(
+            "claude-3-5-haiku-20241022",
+            vec![Message::user("hello")],
+            None,
+            false,
+        );
+        assert!(!AnthropicProvider::references_files(&body));
+    }
+
+    #[test]
+    fn rejects_decoding_constraints() {
+        let options = Some(GenerateOptions {
+            constraint: Some(crate::provider::DecodingConstraint::Regex("[0-9]+".to_string())),
+            ..Default::default()
+        });
+
+        assert!(AnthropicProvider::check_supported(&options).is_err());
+        assert!(AnthropicProvider::check_supported(&None).is_ok());
+    }
+
     #[test]
     fn parse_non_stream_response() {
         let json = serde_json::json!({
@@ -685,6 +1335,46 @@ mod tests {
         assert_eq!(resp.usage.as_ref().map(|u| u.total_tokens), Some(18));
     }
 
+    #[test]
+    fn parse_tool_use_response() {
+        let json = serde_json::json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "stop_reason": "tool_use",
+            "content": [
+                {"type":"text","text":"Let me calculate."},
+                {"type":"tool_use","id":"toolu_1","name":"calculator","input":{"a":1,"b":2}}
+            ]
+        });
+
+        let resp = AnthropicProvider::parse_generate_response_with_model(json, "fallback");
+        assert_eq!(resp.content, "Let me calculate.");
+        assert_eq!(resp.finish_reason.as_deref(), Some("tool_use"));
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(resp.tool_calls[0].id, "toolu_1");
+        assert_eq!(resp.tool_calls[0].parameters["b"], 2);
+    }
+
+    #[test]
+    fn formats_tool_use_and_tool_result_blocks() {
+        let call = crate::tool::ToolCall {
+            id: "toolu_1".to_string(),
+            name: "calculator".to_string(),
+            parameters: serde_json::json!({"a": 1}),
+        };
+        let (_, chat) = AnthropicProvider::split_system_and_messages(vec![
+            Message::assistant_with_tool_calls("", &[call]),
+            Message::tool_results(vec![crate::provider::ContentBlock::tool_result(
+                "toolu_1", "3", false,
+            )]),
+        ]);
+
+        assert_eq!(chat[0]["content"][0]["type"], "tool_use");
+        assert_eq!(chat[0]["content"][0]["input"]["a"], 1);
+        assert_eq!(chat[1]["role"], "user");
+        assert_eq!(chat[1]["content"][0]["type"], "tool_result");
+        assert_eq!(chat[1]["content"][0]["tool_use_id"], "toolu_1");
+    }
+
     #[test]
     fn extract_stream_text_delta_only() {
         let text_event = serde_json::json!({
@@ -712,4 +1402,68 @@ mod tests {
                 .filter(|v| !v.is_empty())
         );
     }
+
+    #[test]
+    fn parses_stream_events_with_tool_deltas_and_usage() {
+        let mut state = StreamState::default();
+        let sse = [
+            serde_json::json!({"type": "message_start", "message": {"usage": {"input_tokens": 12}}}),
+            serde_json::json!({"type": "content_block_start", "index": 1,
+                "content_block": {"type": "tool_use", "id": "toolu_1", "name": "search", "input": {}}}),
+            serde_json::json!({"type": "content_block_delta", "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"q\": \"rust\"}"}}),
+            serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"},
+                "usage": {"output_tokens": 4}}),
+            serde_json::json!({"type": "message_stop"}),
+        ];
+        let events: Vec<StreamEvent> = sse
+            .iter()
+            .flat_map(|e| AnthropicProvider::parse_stream_event(e, &mut state).unwrap())
+            .collect();
+
+        match events.as_slice() {
+            [StreamEvent::ToolCallDelta { index: 1, id, name, .. },
+             StreamEvent::ToolCallDelta { index: 1, arguments_delta, .. },
+             StreamEvent::UsageUpdate(usage),
+             StreamEvent::Done { finish_reason }] => {
+                assert_eq!(id.as_deref(), Some("toolu_1"));
+                assert_eq!(name.as_deref(), Some("search"));
+                assert_eq!(arguments_delta, "{\"q\": \"rust\"}");
+                assert_eq!(usage.total_tokens, 16);
+                assert_eq!(finish_reason.as_deref(), Some("tool_use"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stream_error_event_is_surfaced() {
+        let event = serde_json::json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}});
+        let err = AnthropicProvider::parse_stream_event(&event, &mut StreamState::default()).unwrap_err();
+        assert!(matches!(err, ProviderError::RequestFailed(msg) if msg == "Overloaded"));
+    }
+
+    #[test]
+    fn parses_batch_results() {
+        let results = concat!(
+            r#"{"custom_id":"generation","result":{"type":"succeeded","message":"#,
+            r#"{"model":"claude-3-5-haiku-latest","stop_reason":"end_turn","#,
+            r#""content":[{"type":"text","text":"queued hello"}]}}}"#,
+            "\n"
+        );
+        match AnthropicProvider::parse_batch_results(results, "fallback") {
+            JobStatus::Completed(resp) => assert_eq!(resp.content, "queued hello"),
+            other => panic!("unexpected status: {:?}", other),
+        }
+
+        let results = r#"{"custom_id":"generation","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"Bad prompt"}}}}"#;
+        assert!(matches!(
+            AnthropicProvider::parse_batch_results(results, "fallback"),
+            JobStatus::Failed(reason) if reason == "Bad prompt"
+        ));
+        assert!(matches!(
+            AnthropicProvider::parse_batch_results(r#"{"result":{"type":"expired"}}"#, "fallback"),
+            JobStatus::Failed(reason) if reason == "Request expired"
+        ));
+    }
 }
@@ -216,6 +216,7 @@ mod tests {
                     }),
                     model: "test".to_string(),
                     finish_reason: None,
+                    tool_calls: Vec::new(),
                 }),
             },
             SingleResponse {
@@ -254,6 +255,7 @@ mod tests {
                 }),
                 model: "test".to_string(),
                 finish_reason: None,
+                tool_calls: Vec::new(),
             }),
         };
 
@@ -0,0 +1,1036 @@
+use super::anthropic::{AnthropicProvider, StreamState};
+use super::pipeline::RequestPipeline;
+use super::sigv4::{self, AwsCredentials, SigningRequest};
+use super::{
+    CacheConfig, ContextWindowConfig, ContextWindowManager, GenerateOptions, GenerateResponse,
+    KeepAliveConfig, LlmProvider, Message, MiddlewareChain, ProviderCapabilities, ProviderClient,
+    ProviderClientBuilder, ProviderError, RateLimitConfig, ResponseCache, Result, RetryConfig,
+    Role, StreamEvent, StreamEvents, TimeoutConfig, ToolSchema, Usage,
+};
+use base64::Engine;
+use bytes::{Buf, BytesMut};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::SystemTime;
+
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+const ANTHROPIC_BEDROCK_VERSION: &str = "bedrock-2023-05-31";
+
+/// Model families with distinct request and response formats on Bedrock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BedrockModelFamily {
+    /// `anthropic.claude-*`, using the Messages API format
+    Anthropic,
+    /// `amazon.titan-text-*`
+    Titan,
+    /// `meta.llama3-*`
+    Llama,
+}
+
+impl BedrockModelFamily {
+    /// Detect the family from a model id or cross-region inference profile id
+    pub fn from_model_id(model: &str) -> Option<Self> {
+        if model.contains("anthropic.") {
+            Some(Self::Anthropic)
+        } else if model.contains("amazon.titan-text") {
+            Some(Self::Titan)
+        } else if model.contains("meta.llama") {
+            Some(Self::Llama)
+        } else {
+            None
+        }
+    }
+}
+
+/// AWS Bedrock Provider 实现（InvokeModel API）
+pub struct BedrockProvider {
+    credentials: AwsCredentials,
+    region: String,
+    model: String,
+    client: ProviderClient,
+    endpoint: Option<String>,
+    middleware: Option<MiddlewareChain>,
+    cache: Option<ResponseCache>,
+    context_manager: Option<ContextWindowManager>,
+}
+
+impl BedrockProvider {
+    /// Create a provider using credentials and region from the environment
+    pub fn from_env(model: impl Into<String>) -> Result<Self> {
+        Self::builder().model(model).build()
+    }
+
+    /// Create a builder for configuring the Bedrock provider
+    pub fn builder() -> BedrockProviderBuilder {
+        BedrockProviderBuilder::default()
+    }
+
+    fn host(&self) -> String {
+        format!("bedrock-runtime.{}.amazonaws.com", self.region)
+    }
+
+    fn family_for(&self, options: &Option<GenerateOptions>) -> Result<BedrockModelFamily> {
+        let model = Self::model_for(&self.model, options);
+        BedrockModelFamily::from_model_id(model).ok_or_else(|| {
+            ProviderError::ModelNotAvailable(format!("Unsupported Bedrock model family: {}", model))
+        })
+    }
+
+    fn model_for<'a>(default: &'a str, options: &'a Option<GenerateOptions>) -> &'a str {
+        options
+            .as_ref()
+            .and_then(|o| o.model.as_deref())
+            .unwrap_or(default)
+    }
+
+    fn check_supported(
+        family: BedrockModelFamily,
+        tools: &[ToolSchema],
+        options: &Option<GenerateOptions>,
+    ) -> Result<()> {
+        if options.as_ref().is_some_and(|o| o.constraint.is_some()) {
+            return Err(ProviderError::Other(
+                "Constrained decoding is not supported by the Bedrock provider".to_string(),
+            ));
+        }
+        if options
+            .as_ref()
+            .is_some_and(|o| o.response_format.is_some())
+        {
+            return Err(ProviderError::Other(
+                "Structured output is not supported by the Bedrock provider; use generate_structured"
+                    .to_string(),
+            ));
+        }
+        if family == BedrockModelFamily::Anthropic {
+            return Ok(());
+        }
+        if options
+            .as_ref()
+            .is_some_and(|o| o.assistant_prefix.is_some())
+        {
+            return Err(ProviderError::Other(format!(
+                "Assistant prefill is not supported for {:?} models on Bedrock",
+                family
+            )));
+        }
+        if !tools.is_empty() {
+            return Err(ProviderError::Other(format!(
+                "Native tool calling is not supported for {:?} models on Bedrock",
+                family
+            )));
+        }
+        Ok(())
+    }
+
+    fn build_request_body(
+        family: BedrockModelFamily,
+        messages: Vec<Message>,
+        tools: &[ToolSchema],
+        options: Option<GenerateOptions>,
+    ) -> serde_json::Value {
+        let extensions = options
+            .as_ref()
+            .map(|o| o.extensions.for_provider("bedrock"))
+            .unwrap_or_default();
+        let mut body = match family {
+            BedrockModelFamily::Anthropic => {
+                let tool_options = options.clone();
+                let mut body =
+                    AnthropicProvider::build_request_body_for_model("", messages, options, false);
+                AnthropicProvider::add_tools_to_body(&mut body, tools, tool_options.as_ref());
+                // The model is in the URL and streaming is chosen by endpoint
+                if let Some(obj) = body.as_object_mut() {
+                    obj.remove("model");
+                    obj.remove("stream");
+                }
+                body["anthropic_version"] = serde_json::json!(ANTHROPIC_BEDROCK_VERSION);
+                let betas = AnthropicProvider::required_betas(&body);
+                if !betas.is_empty() {
+                    body["anthropic_beta"] = serde_json::json!(betas);
+                }
+                body
+            }
+            BedrockModelFamily::Titan => {
+                let opts = options.unwrap_or_default();
+                let mut config = serde_json::json!({
+                    "maxTokenCount": opts.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+                });
+                if let Some(temp) = opts.temperature {
+                    config["temperature"] = serde_json::json!(temp);
+                }
+                if let Some(top_p) = opts.top_p {
+                    config["topP"] = serde_json::json!(top_p);
+                }
+                if let Some(stop) = opts.stop {
+                    config["stopSequences"] = serde_json::json!(stop);
+                }
+                serde_json::json!({
+                    "inputText": Self::titan_prompt(&messages),
+                    "textGenerationConfig": config,
+                })
+            }
+            BedrockModelFamily::Llama => {
+                let opts = options.unwrap_or_default();
+                let mut body = serde_json::json!({
+                    "prompt": Self::llama_prompt(&messages),
+                    "max_gen_len": opts.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+                });
+                if let Some(temp) = opts.temperature {
+                    body["temperature"] = serde_json::json!(temp);
+                }
+                if let Some(top_p) = opts.top_p {
+                    body["top_p"] = serde_json::json!(top_p);
+                }
+                body
+            }
+        };
+        // Titan takes sampling parameters inside its generation config
+        let target = match family {
+            BedrockModelFamily::Titan => &mut body["textGenerationConfig"],
+            _ => &mut body,
+        };
+        for (name, value) in extensions {
+            target[name] = value;
+        }
+        body
+    }
+
+    /// Flatten a conversation into Titan's `User:`/`Bot:` transcript format
+    fn titan_prompt(messages: &[Message]) -> String {
+        let mut prompt = String::new();
+        for message in messages {
+            let label = match message.role {
+                Role::System => "",
+                Role::User => "User: ",
+                Role::Assistant => "Bot: ",
+            };
+            prompt.push_str(label);
+            prompt.push_str(&message.content_as_text());
+            prompt.push('\n');
+        }
+        prompt.push_str("Bot:");
+        prompt
+    }
+
+    /// Render a conversation with the Llama 3 chat template
+    fn llama_prompt(messages: &[Message]) -> String {
+        let mut prompt = String::from("<|begin_of_text|>");
+        for message in messages {
+            let role = match message.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            prompt.push_str(&format!(
+                "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                role,
+                message.content_as_text()
+            ));
+        }
+        prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+        prompt
+    }
+
+    /// Usage reported in the final chunk of a Titan or Llama stream
+    fn invocation_usage(json: &serde_json::Value) -> Option<Usage> {
+        let metrics = json.get("amazon-bedrock-invocationMetrics")?;
+        let prompt = metrics["inputTokenCount"].as_u64().unwrap_or(0) as u32;
+        let completion = metrics["outputTokenCount"].as_u64().unwrap_or(0) as u32;
+        Some(Usage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        })
+    }
+
+    fn parse_generate_response(
+        family: BedrockModelFamily,
+        json: serde_json::Value,
+        model: &str,
+    ) -> GenerateResponse {
+        match family {
+            BedrockModelFamily::Anthropic => {
+                AnthropicProvider::parse_generate_response_with_model(json, model)
+            }
+            BedrockModelFamily::Titan => {
+                let result = &json["results"][0];
+                let prompt = json["inputTextTokenCount"].as_u64().unwrap_or(0) as u32;
+                let completion = result["tokenCount"].as_u64().unwrap_or(0) as u32;
+                GenerateResponse {
+                    content: result["outputText"].as_str().unwrap_or("").to_string(),
+                    usage: Some(Usage {
+                        prompt_tokens: prompt,
+                        completion_tokens: completion,
+                        total_tokens: prompt + completion,
+                    }),
+                    model: model.to_string(),
+                    finish_reason: result["completionReason"].as_str().map(String::from),
+                    tool_calls: Vec::new(),
+                }
+            }
+            BedrockModelFamily::Llama => {
+                let prompt = json["prompt_token_count"].as_u64().unwrap_or(0) as u32;
+                let completion = json["generation_token_count"].as_u64().unwrap_or(0) as u32;
+                GenerateResponse {
+                    content: json["generation"].as_str().unwrap_or("").to_string(),
+                    usage: Some(Usage {
+                        prompt_tokens: prompt,
+                        completion_tokens: completion,
+                        total_tokens: prompt + completion,
+                    }),
+                    model: model.to_string(),
+                    finish_reason: json["stop_reason"].as_str().map(String::from),
+                    tool_calls: Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// Translate one decoded stream chunk into typed events
+    fn parse_stream_chunk(
+        family: BedrockModelFamily,
+        json: &serde_json::Value,
+        state: &mut StreamState,
+    ) -> Result<Vec<StreamEvent>> {
+        let (text, stop_reason) = match family {
+            BedrockModelFamily::Anthropic => {
+                return AnthropicProvider::parse_stream_event(json, state)
+            }
+            BedrockModelFamily::Titan => (&json["outputText"], &json["completionReason"]),
+            BedrockModelFamily::Llama => (&json["generation"], &json["stop_reason"]),
+        };
+
+        let mut events = Vec::new();
+        if let Some(text) = text.as_str().filter(|t| !t.is_empty()) {
+            events.push(StreamEvent::TextDelta(text.to_string()));
+        }
+        if let Some(reason) = stop_reason.as_str() {
+            if let Some(usage) = Self::invocation_usage(json) {
+                events.push(StreamEvent::UsageUpdate(usage));
+            }
+            events.push(StreamEvent::Done {
+                finish_reason: Some(reason.to_string()),
+            });
+        }
+        Ok(events)
+    }
+
+    fn pipeline(&self) -> RequestPipeline<'_> {
+        RequestPipeline {
+            model: &self.model,
+            context_manager: self.context_manager.as_ref(),
+            cache: self.cache.as_ref(),
+            middleware: self.middleware.as_ref(),
+        }
+    }
+
+    async fn generate_inner(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Result<GenerateResponse> {
+        let family = self.family_for(&options)?;
+        Self::check_supported(family, &tools, &options)?;
+        let tools = &tools;
+        self.pipeline()
+            .generate(messages, tools, options, |messages, options| async move {
+                let model = Self::model_for(&self.model, &options).to_string();
+                let prefix = options
+                    .as_ref()
+                    .and_then(AnthropicProvider::prefill_text)
+                    .map(String::from);
+                let body = Self::build_request_body(family, messages, tools, options);
+                let response = self.send_request(&model, "invoke", body).await?;
+                let json: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+                let mut response = Self::parse_generate_response(family, json, &model);
+                if let Some(prefix) = prefix {
+                    response.content.insert_str(0, &prefix);
+                }
+                Ok(response)
+            })
+            .await
+    }
+
+    /// Sign and send a request to `/model/{model}/{action}`
+    async fn send_request(
+        &self,
+        model: &str,
+        action: &str,
+        body: serde_json::Value,
+    ) -> Result<reqwest::Response> {
+        let _guard = self.client.acquire_rate_limit().await;
+
+        let host = self.host();
+        let path = format!("/model/{}/{}", sigv4::uri_encode(model), action);
+        let url = match &self.endpoint {
+            Some(endpoint) => format!("{}{}", endpoint, path),
+            None => format!("https://{}{}", host, path),
+        };
+        let body = serde_json::to_vec(&body).map_err(|e| ProviderError::Other(e.to_string()))?;
+
+        self.client
+            .retry_policy()
+            .execute_with_retry(|| async {
+                // Sign per attempt so retries carry a fresh timestamp
+                let signed = sigv4::sign(
+                    &SigningRequest {
+                        method: "POST",
+                        host: &host,
+                        path: &path,
+                        body: &body,
+                        region: &self.region,
+                        service: "bedrock",
+                    },
+                    &self.credentials,
+                    SystemTime::now(),
+                );
+
+                let mut request = self
+                    .client
+                    .http_client()
+                    .post(&url)
+                    .header("host", &host)
+                    .header("x-amz-date", &signed.amz_date)
+                    .header("authorization", &signed.authorization)
+                    .header("content-type", "application/json")
+                    .header("accept", "application/json");
+                if let Some(token) = &signed.security_token {
+                    request = request.header("x-amz-security-token", token);
+                }
+                if action.ends_with("-stream") {
+                    request = self.client.stream_request(request);
+                }
+
+                let response = request
+                    .body(body.clone())
+                    .send()
+                    .await
+                    .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+
+                let status = response.status();
+                if status == reqwest::StatusCode::FORBIDDEN
+                    || status == reqwest::StatusCode::UNAUTHORIZED
+                {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::AuthenticationFailed(text));
+                }
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    return Err(ProviderError::RateLimited { retry_after: None });
+                }
+                if status == reqwest::StatusCode::NOT_FOUND {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::ModelNotAvailable(text));
+                }
+                if !status.is_success() {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::RequestFailed(format!(
+                        "{}: {}",
+                        status, text
+                    )));
+                }
+
+                Ok(response)
+            })
+            .await
+    }
+}
+
+/// One message decoded from an `application/vnd.amazon.eventstream` body
+#[derive(Debug, PartialEq)]
+struct EventStreamMessage {
+    message_type: Option<String>,
+    event_type: Option<String>,
+    exception_type: Option<String>,
+    payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    /// Decode the first complete message in `buf`
+    ///
+    /// Returns the message and the number of bytes consumed, or `None` when
+    /// more bytes are needed. CRCs are not verified; the transport is TLS.
+    fn decode(buf: &[u8]) -> Result<Option<(Self, usize)>> {
+        if buf.len() < 12 {
+            return Ok(None);
+        }
+        let total_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        let headers_len = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+        if total_len < 16 + headers_len {
+            return Err(ProviderError::ParseError(
+                "Malformed event stream prelude".to_string(),
+            ));
+        }
+        if buf.len() < total_len {
+            return Ok(None);
+        }
+
+        let mut message = Self {
+            message_type: None,
+            event_type: None,
+            exception_type: None,
+            payload: buf[12 + headers_len..total_len - 4].to_vec(),
+        };
+
+        let headers = &buf[12..12 + headers_len];
+        let mut pos = 0;
+        let malformed = || ProviderError::ParseError("Malformed event stream header".to_string());
+        while pos < headers.len() {
+            let name_len = headers[pos] as usize;
+            pos += 1;
+            let name = headers.get(pos..pos + name_len).ok_or_else(malformed)?;
+            let name = String::from_utf8_lossy(name).to_string();
+            pos += name_len;
+            let value_type = *headers.get(pos).ok_or_else(malformed)?;
+            pos += 1;
+
+            // Only string headers are read; other types are skipped by size
+            let value_len = match value_type {
+                0 | 1 => 0,
+                2 => 1,
+                3 => 2,
+                4 => 4,
+                5 | 8 => 8,
+                9 => 16,
+                6 | 7 => {
+                    let len = headers.get(pos..pos + 2).ok_or_else(malformed)?;
+                    pos += 2;
+                    u16::from_be_bytes([len[0], len[1]]) as usize
+                }
+                _ => return Err(malformed()),
+            };
+            let value = headers.get(pos..pos + value_len).ok_or_else(malformed)?;
+            pos += value_len;
+
+            if value_type == 7 {
+                let value = String::from_utf8_lossy(value).to_string();
+                match name.as_str() {
+                    ":message-type" => message.message_type = Some(value),
+                    ":event-type" => message.event_type = Some(value),
+                    ":exception-type" => message.exception_type = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Some((message, total_len)))
+    }
+
+    /// Model chunk JSON carried by a `chunk` event
+    fn chunk_json(&self) -> Result<Option<serde_json::Value>> {
+        if let Some(exception) = &self.exception_type {
+            let json: serde_json::Value = serde_json::from_slice(&self.payload).unwrap_or_default();
+            let message = json["message"].as_str().unwrap_or("stream exception");
+            return Err(if exception == "throttlingException" {
+                ProviderError::RateLimited { retry_after: None }
+            } else {
+                ProviderError::RequestFailed(format!("{}: {}", exception, message))
+            });
+        }
+        if self.event_type.as_deref() != Some("chunk") {
+            return Ok(None);
+        }
+
+        let envelope: serde_json::Value = serde_json::from_slice(&self.payload)
+            .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(envelope["bytes"].as_str().unwrap_or_default())
+            .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| ProviderError::ParseError(e.to_string()))
+    }
+}
+
+/// Builder for creating a BedrockProvider with custom configuration
+pub struct BedrockProviderBuilder {
+    credentials: Option<AwsCredentials>,
+    region: Option<String>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    client_builder: ProviderClientBuilder,
+    middleware: Option<MiddlewareChain>,
+    cache_config: Option<CacheConfig>,
+    context_config: Option<ContextWindowConfig>,
+}
+
+impl Default for BedrockProviderBuilder {
+    fn default() -> Self {
+        Self {
+            credentials: None,
+            region: None,
+            model: None,
+            endpoint: None,
+            client_builder: ProviderClient::builder(),
+            middleware: None,
+            cache_config: None,
+            context_config: None,
+        }
+    }
+}
+
+impl BedrockProviderBuilder {
+    /// Set the credentials; defaults to the standard AWS environment variables
+    pub fn credentials(mut self, credentials: AwsCredentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Set the region; defaults to `AWS_REGION` or `AWS_DEFAULT_REGION`
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Set the model id, e.g. `anthropic.claude-3-haiku-20240307-v1:0`
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Override the endpoint URL, e.g. for a VPC endpoint
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Set the retry configuration
+    pub fn retry_config(mut self, config: RetryConfig) -> Self {
+        self.client_builder = self.client_builder.retry_config(config);
+        self
+    }
+
+    /// Set the timeout configuration
+    pub fn timeout_config(mut self, config: TimeoutConfig) -> Self {
+        self.client_builder = self.client_builder.timeout_config(config);
+        self
+    }
+
+    /// Set the rate limit configuration
+    pub fn rate_limit_config(mut self, config: RateLimitConfig) -> Self {
+        self.client_builder = self.client_builder.rate_limit_config(config);
+        self
+    }
+
+    /// Set the connection pool and keep-alive configuration
+    pub fn keep_alive_config(mut self, config: KeepAliveConfig) -> Self {
+        self.client_builder = self.client_builder.keep_alive_config(config);
+        self
+    }
+
+    /// Set a proxy URL
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.client_builder = self.client_builder.proxy(proxy);
+        self
+    }
+
+    /// Add a header sent with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.client_builder = self.client_builder.header(name, value);
+        self
+    }
+
+    /// Set the middleware chain
+    pub fn middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = Some(middleware);
+        self
+    }
+
+    /// Enable response caching with the given configuration
+    pub fn cache_config(mut self, config: CacheConfig) -> Self {
+        self.cache_config = Some(config);
+        self
+    }
+
+    /// Enable context window management with the given configuration
+    pub fn context_config(mut self, config: ContextWindowConfig) -> Self {
+        self.context_config = Some(config);
+        self
+    }
+
+    /// Disable retries
+    pub fn no_retry(mut self) -> Self {
+        self.client_builder = self.client_builder.no_retry();
+        self
+    }
+
+    /// Disable rate limiting
+    pub fn no_rate_limit(mut self) -> Self {
+        self.client_builder = self.client_builder.no_rate_limit();
+        self
+    }
+
+    /// Build the Bedrock provider
+    pub fn build(self) -> Result<BedrockProvider> {
+        let credentials = self
+            .credentials
+            .or_else(AwsCredentials::from_env)
+            .ok_or_else(|| {
+                ProviderError::AuthenticationFailed("AWS credentials are required".to_string())
+            })?;
+
+        let region = self
+            .region
+            .or_else(|| std::env::var("AWS_REGION").ok())
+            .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+            .ok_or_else(|| ProviderError::RequestFailed("Region is required".to_string()))?;
+
+        let model = self
+            .model
+            .ok_or_else(|| ProviderError::RequestFailed("Model is required".to_string()))?;
+
+        let client = self.client_builder.build()?;
+
+        let cache = self.cache_config.map(ResponseCache::new);
+        let context_manager = self.context_config.map(ContextWindowManager::new);
+
+        Ok(BedrockProvider {
+            credentials,
+            region,
+            model,
+            client,
+            endpoint: self.endpoint,
+            middleware: self.middleware,
+            cache,
+            context_manager,
+        })
+    }
+}
+
+impl LlmProvider for BedrockProvider {
+    fn name(&self) -> &str {
+        "bedrock"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        let anthropic =
+            BedrockModelFamily::from_model_id(&self.model) == Some(BedrockModelFamily::Anthropic);
+        ProviderCapabilities {
+            streaming: true,
+            assistant_prefill: anthropic,
+            native_tools: anthropic,
+            tool_choice: anthropic,
+            ..Default::default()
+        }
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, Vec::new(), options))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, tools, options))
+    }
+
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<super::StreamResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let events = self
+                .generate_stream_events(messages, Vec::new(), options)
+                .await?;
+            Ok(super::StreamResponse::from_events(events))
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            let family = self.family_for(&options)?;
+            Self::check_supported(family, &tools, &options)?;
+            let model = Self::model_for(&self.model, &options).to_string();
+            let prefix = options
+                .as_ref()
+                .and_then(AnthropicProvider::prefill_text)
+                .map(String::from);
+            let body = Self::build_request_body(family, messages, &tools, options);
+            let deadline = self.client.stream_deadline();
+            let response = self
+                .send_request(&model, "invoke-with-response-stream", body)
+                .await?;
+
+            Ok(StreamEvents::spawn(100, |tx| async move {
+                if let Some(prefix) = prefix {
+                    if tx.send(Ok(StreamEvent::TextDelta(prefix))).await.is_err() {
+                        return;
+                    }
+                }
+
+                let mut stream = response.bytes_stream();
+                let mut buffer = BytesMut::new();
+                let mut state = StreamState::default();
+
+                loop {
+                    let chunk = match deadline.next(&mut stream).await {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(e) => {
+                            let _ = tx.send(Err(e)).await;
+                            return;
+                        }
+                    };
+                    let bytes = match chunk {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            let _ = tx
+                                .send(Err(ProviderError::RequestFailed(e.to_string())))
+                                .await;
+                            return;
+                        }
+                    };
+                    buffer.extend_from_slice(&bytes);
+
+                    loop {
+                        let events = match EventStreamMessage::decode(&buffer) {
+                            Ok(Some((message, consumed))) => {
+                                buffer.advance(consumed);
+                                message.chunk_json().and_then(|json| match json {
+                                    Some(json) => {
+                                        Self::parse_stream_chunk(family, &json, &mut state)
+                                    }
+                                    None => Ok(Vec::new()),
+                                })
+                            }
+                            Ok(None) => break,
+                            Err(e) => Err(e),
+                        };
+
+                        match events {
+                            Ok(events) => {
+                                for event in events {
+                                    if tx.send(Ok(event)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                let _ = tx.send(Err(e)).await;
+                                return;
+                            }
+                        }
+                    }
+                }
+            }))
+        })
+    }
+
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            let url = match &self.endpoint {
+                Some(endpoint) => endpoint.clone(),
+                None => format!("https://{}", self.host()),
+            };
+            self.client.preconnect(&url).await
+        })
+    }
+
+    fn rate_limiter(&self) -> Option<&super::RateLimiter> {
+        Some(self.client.rate_limiter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a single event stream message with string headers
+    fn encode_message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+        let mut header_bytes = Vec::new();
+        for (name, value) in headers {
+            header_bytes.push(name.len() as u8);
+            header_bytes.extend_from_slice(name.as_bytes());
+            header_bytes.push(7);
+            header_bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            header_bytes.extend_from_slice(value.as_bytes());
+        }
+        let total_len = 16 + header_bytes.len() + payload.len();
+        let mut message = Vec::new();
+        message.extend_from_slice(&(total_len as u32).to_be_bytes());
+        message.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
+        message.extend_from_slice(&[0; 4]);
+        message.extend_from_slice(&header_bytes);
+        message.extend_from_slice(payload);
+        message.extend_from_slice(&[0; 4]);
+        message
+    }
+
+    fn chunk_message(chunk: serde_json::Value) -> Vec<u8> {
+        let bytes = base64::engine::general_purpose::STANDARD.encode(chunk.to_string());
+        encode_message(
+            &[(":message-type", "event"), (":event-type", "chunk")],
+            serde_json::json!({ "bytes": bytes }).to_string().as_bytes(),
+        )
+    }
+
+    #[test]
+    fn detects_model_families() {
+        assert_eq!(
+            BedrockModelFamily::from_model_id("us.anthropic.claude-3-5-sonnet-20241022-v2:0"),
+            Some(BedrockModelFamily::Anthropic)
+        );
+        assert_eq!(
+            BedrockModelFamily::from_model_id("amazon.titan-text-express-v1"),
+            Some(BedrockModelFamily::Titan)
+        );
+        assert_eq!(
+            BedrockModelFamily::from_model_id("meta.llama3-8b-instruct-v1:0"),
+            Some(BedrockModelFamily::Llama)
+        );
+        assert_eq!(BedrockModelFamily::from_model_id("cohere.command-r"), None);
+    }
+
+    #[test]
+    fn builds_family_specific_bodies() {
+        let messages = vec![Message::system("be brief"), Message::user("hi")];
+        let options = Some(GenerateOptions {
+            max_tokens: Some(50),
+            ..Default::default()
+        });
+
+        let anthropic = BedrockProvider::build_request_body(
+            BedrockModelFamily::Anthropic,
+            messages.clone(),
+            &[],
+            options.clone(),
+        );
+        assert_eq!(anthropic["anthropic_version"], ANTHROPIC_BEDROCK_VERSION);
+        assert_eq!(anthropic["system"], "be brief");
+        assert!(anthropic.get("model").is_none());
+        assert!(anthropic.get("stream").is_none());
+
+        let titan = BedrockProvider::build_request_body(
+            BedrockModelFamily::Titan,
+            messages.clone(),
+            &[],
+            options.clone(),
+        );
+        assert_eq!(titan["inputText"], "be brief\nUser: hi\nBot:");
+        assert_eq!(titan["textGenerationConfig"]["maxTokenCount"], 50);
+
+        let llama =
+            BedrockProvider::build_request_body(BedrockModelFamily::Llama, messages, &[], options);
+        assert_eq!(llama["max_gen_len"], 50);
+        assert!(llama["prompt"]
+            .as_str()
+            .unwrap()
+            .ends_with("<|start_header_id|>assistant<|end_header_id|>\n\n"));
+    }
+
+    #[test]
+    fn rejects_tools_for_non_anthropic_models() {
+        let tool = ToolSchema {
+            name: "search".to_string(),
+            description: "search".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+            native: None,
+        };
+        assert!(BedrockProvider::check_supported(
+            BedrockModelFamily::Llama,
+            std::slice::from_ref(&tool),
+            &None
+        )
+        .is_err());
+        assert!(
+            BedrockProvider::check_supported(BedrockModelFamily::Anthropic, &[tool], &None).is_ok()
+        );
+    }
+
+    #[test]
+    fn parses_titan_and_llama_responses() {
+        let titan = BedrockProvider::parse_generate_response(
+            BedrockModelFamily::Titan,
+            serde_json::json!({
+                "inputTextTokenCount": 4,
+                "results": [{"tokenCount": 2, "outputText": "Hi!", "completionReason": "FINISH"}]
+            }),
+            "amazon.titan-text-express-v1",
+        );
+        assert_eq!(titan.content, "Hi!");
+        assert_eq!(titan.usage.map(|u| u.total_tokens), Some(6));
+
+        let llama = BedrockProvider::parse_generate_response(
+            BedrockModelFamily::Llama,
+            serde_json::json!({
+                "generation": "Hello",
+                "prompt_token_count": 7,
+                "generation_token_count": 1,
+                "stop_reason": "stop"
+            }),
+            "meta.llama3-8b-instruct-v1:0",
+        );
+        assert_eq!(llama.content, "Hello");
+        assert_eq!(llama.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn decodes_event_stream_chunks_across_reads() {
+        let message = chunk_message(serde_json::json!({
+            "generation": "Hi",
+            "stop_reason": "stop",
+            "amazon-bedrock-invocationMetrics": {"inputTokenCount": 3, "outputTokenCount": 1}
+        }));
+
+        assert!(EventStreamMessage::decode(&message[..10])
+            .unwrap()
+            .is_none());
+
+        let (decoded, consumed) = EventStreamMessage::decode(&message).unwrap().unwrap();
+        assert_eq!(consumed, message.len());
+        let json = decoded.chunk_json().unwrap().unwrap();
+
+        let events = BedrockProvider::parse_stream_chunk(
+            BedrockModelFamily::Llama,
+            &json,
+            &mut StreamState::default(),
+        )
+        .unwrap();
+        match events.as_slice() {
+            [StreamEvent::TextDelta(text), StreamEvent::UsageUpdate(usage), StreamEvent::Done { finish_reason }] =>
+            {
+                assert_eq!(text, "Hi");
+                assert_eq!(usage.total_tokens, 4);
+                assert_eq!(finish_reason.as_deref(), Some("stop"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn surfaces_stream_exceptions() {
+        let message = encode_message(
+            &[
+                (":message-type", "exception"),
+                (":exception-type", "throttlingException"),
+            ],
+            br#"{"message":"slow down"}"#,
+        );
+        let (decoded, _) = EventStreamMessage::decode(&message).unwrap().unwrap();
+        assert!(matches!(
+            decoded.chunk_json(),
+            Err(ProviderError::RateLimited { .. })
+        ));
+    }
+}
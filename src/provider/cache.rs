@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::{Hash, Hasher};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
-use super::{Message, GenerateOptions, GenerateResponse};
+use super::{Message, GenerateOptions, GenerateResponse, ToolSchema};
 
 /// Configuration for response caching
 #[derive(Debug, Clone)]
@@ -64,6 +64,21 @@ impl CacheConfig {
     }
 }
 
+/// Feeds serialized bytes straight into a hasher, so hashing a message
+/// does not build a copy of it
+struct HashWriter<'a, H>(&'a mut H);
+
+impl<H: Hasher> std::io::Write for HashWriter<'_, H> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.write(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Key for caching responses
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CacheKey {
@@ -81,15 +96,20 @@ impl CacheKey {
     ) -> Self {
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
 
-        // Hash messages
+        // Hash messages, including image and tool blocks
         for msg in messages {
-            format!("{:?}:{}", msg.role, msg.content_as_text()).hash(&mut hasher);
+            let _ = serde_json::to_writer(HashWriter(&mut hasher), msg);
+            // Separate messages so content cannot shift between them
+            0xffu8.hash(&mut hasher);
         }
         let messages_hash = hasher.finish();
 
         // Hash options
         let mut options_hasher = std::collections::hash_map::DefaultHasher::new();
         if let Some(opts) = options {
+            if let Some(model) = &opts.model {
+                model.hash(&mut options_hasher);
+            }
             if let Some(temp) = opts.temperature {
                 temp.to_bits().hash(&mut options_hasher);
             }
@@ -102,6 +122,30 @@ impl CacheKey {
             if let Some(stop) = &opts.stop {
                 stop.hash(&mut options_hasher);
             }
+            if let Some(prefix) = &opts.assistant_prefix {
+                prefix.hash(&mut options_hasher);
+            }
+            if let Some(constraint) = &opts.constraint {
+                constraint.hash(&mut options_hasher);
+            }
+            if let Some(format) = &opts.response_format {
+                format!("{:?}", format).hash(&mut options_hasher);
+            }
+            if let Some(tool_choice) = &opts.tool_choice {
+                tool_choice.hash(&mut options_hasher);
+            }
+            if let Some(parallel) = opts.parallel_tool_calls {
+                parallel.hash(&mut options_hasher);
+            }
+            if let Some(extra) = &opts.extra_body {
+                serde_json::to_string(extra)
+                    .unwrap_or_default()
+                    .hash(&mut options_hasher);
+            }
+            for (name, value) in opts.extensions.iter() {
+                name.hash(&mut options_hasher);
+                value.to_string().hash(&mut options_hasher);
+            }
         }
         let options_hash = options_hasher.finish();
 
@@ -111,6 +155,20 @@ impl CacheKey {
             options_hash,
         }
     }
+
+    /// Mix the tool definitions of a native tool calling request into the key
+    pub fn with_tools(mut self, tools: &[ToolSchema]) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.options_hash.hash(&mut hasher);
+        for tool in tools {
+            tool.name.hash(&mut hasher);
+            tool.description.hash(&mut hasher);
+            tool.parameters.to_string().hash(&mut hasher);
+            tool.native.as_ref().map(|n| n.to_string()).hash(&mut hasher);
+        }
+        self.options_hash = hasher.finish();
+        self
+    }
 }
 
 /// Entry in the cache
@@ -119,64 +177,189 @@ struct CacheEntry {
     response: GenerateResponse,
     created_at: Instant,
     access_count: usize,
+    /// Insertion order within the shard
+    seq: u64,
 }
 
 impl CacheEntry {
-    fn new(response: GenerateResponse) -> Self {
-        Self {
-            response,
-            created_at: Instant::now(),
-            access_count: 0,
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.created_at.elapsed() > ttl
+    }
+}
+
+/// One lock's worth of entries, indexed for eviction
+#[derive(Debug, Default)]
+struct Shard {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Eviction order: least accessed first, then oldest
+    by_use: BTreeMap<(usize, u64), CacheKey>,
+    /// Insertion order, which is also expiry order since all entries share
+    /// one TTL
+    by_age: BTreeMap<u64, CacheKey>,
+    next_seq: u64,
+}
+
+impl Shard {
+    fn insert(&mut self, key: CacheKey, response: GenerateResponse) {
+        self.remove(&key);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.by_use.insert((0, seq), key.clone());
+        self.by_age.insert(seq, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                created_at: Instant::now(),
+                access_count: 0,
+                seq,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.by_use.remove(&(entry.access_count, entry.seq));
+        self.by_age.remove(&entry.seq);
+        Some(entry)
+    }
+
+    /// Count a hit on `key` and return its response
+    fn touch(&mut self, key: &CacheKey) -> Option<GenerateResponse> {
+        let entry = self.entries.get_mut(key)?;
+        let key = self.by_use.remove(&(entry.access_count, entry.seq))?;
+        entry.access_count += 1;
+        self.by_use.insert((entry.access_count, entry.seq), key);
+        Some(entry.response.clone())
+    }
+
+    /// Evict the least accessed entry, the oldest among ties
+    fn evict_least_used(&mut self) -> bool {
+        let Some((_, key)) = self.by_use.pop_first() else {
+            return false;
+        };
+        if let Some(entry) = self.entries.remove(&key) {
+            self.by_age.remove(&entry.seq);
         }
+        true
     }
 
-    fn is_expired(&self, ttl: Duration) -> bool {
-        self.created_at.elapsed() > ttl
+    /// Remove expired entries, oldest first; returns how many
+    fn purge_expired(&mut self, ttl: Duration) -> usize {
+        let mut purged = 0;
+        while let Some((_, key)) = self.by_age.first_key_value() {
+            if !self.entries[key].is_expired(ttl) {
+                break;
+            }
+            let key = key.clone();
+            self.remove(&key);
+            purged += 1;
+        }
+        purged
     }
 }
 
-/// Response cache with TTL and LRU eviction
+/// Most shards a cache is split into
+const MAX_SHARDS: usize = 16;
+/// Fewest entries a shard is sized for; smaller caches use one shard so
+/// eviction stays exactly least-used
+const MIN_SHARD_ENTRIES: usize = 64;
+
+/// Hit, miss and eviction counts shared by clones of a cache
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Response cache with TTL and least-used eviction
+///
+/// Entries are spread over shards with their own locks, so concurrent
+/// requests for different keys do not wait on each other. Capacity and
+/// eviction apply per shard; when full, a shard drops its least accessed
+/// entry, the oldest among ties.
+///
+/// Writes never scan the cache. Expired entries are dropped when looked up
+/// and by a background sweep, started when the cache is created inside a
+/// Tokio runtime; otherwise call `purge_expired` periodically.
 pub struct ResponseCache {
     config: CacheConfig,
-    entries: Arc<RwLock<HashMap<CacheKey, CacheEntry>>>,
-    stats: Arc<RwLock<CacheStats>>,
+    shards: Arc<[Mutex<Shard>]>,
+    shard_capacity: usize,
+    stats: Arc<Counters>,
 }
 
 impl ResponseCache {
     /// Create a new response cache with the given configuration
     pub fn new(config: CacheConfig) -> Self {
-        Self {
+        let shards = (config.max_entries / MIN_SHARD_ENTRIES).clamp(1, MAX_SHARDS);
+        let cache = Self {
+            shard_capacity: config.max_entries.div_ceil(shards),
+            shards: (0..shards).map(|_| Mutex::default()).collect(),
+            stats: Arc::new(Counters::default()),
             config,
-            entries: Arc::new(RwLock::new(HashMap::new())),
-            stats: Arc::new(RwLock::new(CacheStats::default())),
+        };
+        if cache.config.enabled {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(Self::sweep(
+                    Arc::downgrade(&cache.shards),
+                    cache.config.ttl,
+                ));
+            }
+        }
+        cache
+    }
+
+    /// Purge expired entries every half TTL, between 1 and 60 seconds,
+    /// until the cache is dropped
+    async fn sweep(shards: Weak<[Mutex<Shard>]>, ttl: Duration) {
+        let period = (ttl / 2).clamp(Duration::from_secs(1), Duration::from_secs(60));
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let Some(shards) = shards.upgrade() else {
+                return;
+            };
+            for shard in shards.iter() {
+                shard.lock().unwrap().purge_expired(ttl);
+            }
         }
     }
 
+    fn shard(&self, key: &CacheKey) -> std::sync::MutexGuard<'_, Shard> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        let index = hasher.finish() as usize % self.shards.len();
+        self.shards[index].lock().unwrap()
+    }
+
     /// Get a cached response if available and not expired
     pub async fn get(&self, key: &CacheKey) -> Option<GenerateResponse> {
         if !self.config.enabled {
             return None;
         }
 
-        let mut entries = self.entries.write().await;
-
-        if let Some(entry) = entries.get_mut(key) {
-            if entry.is_expired(self.config.ttl) {
-                entries.remove(key);
-                let mut stats = self.stats.write().await;
-                stats.misses += 1;
-                None
-            } else {
-                entry.access_count += 1;
-                let mut stats = self.stats.write().await;
-                stats.hits += 1;
-                Some(entry.response.clone())
-            }
-        } else {
-            let mut stats = self.stats.write().await;
-            stats.misses += 1;
+        let mut shard = self.shard(key);
+
+        let expired = shard
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(self.config.ttl));
+        let response = if expired {
+            shard.remove(key);
             None
-        }
+        } else {
+            shard.touch(key)
+        };
+        let counter = if response.is_some() {
+            &self.stats.hits
+        } else {
+            &self.stats.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
     }
 
     /// Store a response in the cache
@@ -185,60 +368,54 @@ impl ResponseCache {
             return;
         }
 
-        let mut entries = self.entries.write().await;
+        let mut shard = self.shard(&key);
 
-        // Evict expired entries
-        entries.retain(|_, entry| !entry.is_expired(self.config.ttl));
-
-        // Evict least recently used entries if at capacity
-        if entries.len() >= self.config.max_entries {
-            self.evict_lru(&mut entries).await;
+        // Evict the least used entry if at capacity
+        if shard.entries.len() >= self.shard_capacity
+            && !shard.entries.contains_key(&key)
+            && shard.evict_least_used()
+        {
+            self.stats.evictions.fetch_add(1, Ordering::Relaxed);
         }
 
-        entries.insert(key, CacheEntry::new(response));
+        shard.insert(key, response);
     }
 
-    /// Evict the least recently used entry
-    async fn evict_lru(&self, entries: &mut HashMap<CacheKey, CacheEntry>) {
-        if let Some((key_to_remove, _)) = entries
+    /// Remove every expired entry now; returns how many were removed
+    pub fn purge_expired(&self) -> usize {
+        self.shards
             .iter()
-            .min_by_key(|(_, entry)| (entry.access_count, entry.created_at))
-        {
-            let key_to_remove = key_to_remove.clone();
-            entries.remove(&key_to_remove);
-
-            let mut stats = self.stats.write().await;
-            stats.evictions += 1;
-        }
+            .map(|shard| shard.lock().unwrap().purge_expired(self.config.ttl))
+            .sum()
     }
 
     /// Clear all entries from the cache
     pub async fn clear(&self) {
-        let mut entries = self.entries.write().await;
-        entries.clear();
+        for shard in self.shards.iter() {
+            *shard.lock().unwrap() = Shard::default();
+        }
     }
 
     /// Get cache statistics
     pub async fn stats(&self) -> CacheStats {
-        let stats = self.stats.read().await;
-        stats.clone()
+        CacheStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            evictions: self.stats.evictions.load(Ordering::Relaxed),
+        }
     }
 
     /// Get the number of entries in the cache
     pub async fn size(&self) -> usize {
-        let entries = self.entries.read().await;
-        entries.len()
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().entries.len())
+            .sum()
     }
 
     /// Get the hit rate (hits / total requests)
     pub async fn hit_rate(&self) -> f64 {
-        let stats = self.stats.read().await;
-        let total = stats.hits + stats.misses;
-        if total == 0 {
-            0.0
-        } else {
-            stats.hits as f64 / total as f64
-        }
+        self.stats().await.hit_rate()
     }
 }
 
@@ -246,7 +423,8 @@ impl Clone for ResponseCache {
     fn clone(&self) -> Self {
         Self {
             config: self.config.clone(),
-            entries: Arc::clone(&self.entries),
+            shards: Arc::clone(&self.shards),
+            shard_capacity: self.shard_capacity,
             stats: Arc::clone(&self.stats),
         }
     }
@@ -283,7 +461,7 @@ impl CacheStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::provider::{Role, Usage};
+    use crate::provider::Usage;
 
     fn create_message(content: &str) -> Message {
         Message::user(content)
@@ -299,6 +477,7 @@ mod tests {
             }),
             model: "test-model".to_string(),
             finish_reason: Some("stop".to_string()),
+            tool_calls: Vec::new(),
         }
     }
 
@@ -308,8 +487,7 @@ mod tests {
         let options = Some(GenerateOptions {
             temperature: Some(0.7),
             max_tokens: Some(100),
-            top_p: None,
-            stop: None,
+            ..Default::default()
         });
 
         let key1 = CacheKey::from_request(&messages, "model", &options);
@@ -329,10 +507,26 @@ mod tests {
         assert_ne!(key1, key2);
     }
 
+    #[test]
+    fn test_cache_key_includes_tools() {
+        let messages = vec![create_message("Hello")];
+        let tools = vec![ToolSchema {
+            name: "calculator".to_string(),
+            description: "Adds numbers".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+            native: None,
+        }];
+
+        let plain = CacheKey::from_request(&messages, "model", &None);
+        let with_tools = CacheKey::from_request(&messages, "model", &None).with_tools(&tools);
+
+        assert_ne!(plain, with_tools);
+    }
+
     #[tokio::test]
     async fn test_cache_hit() {
         let cache = ResponseCache::new(CacheConfig::default());
-        let key = CacheKey::from_request(&vec![create_message("test")], "model", &None);
+        let key = CacheKey::from_request(&[create_message("test")], "model", &None);
         let response = create_response("cached response");
 
         cache.put(key.clone(), response.clone()).await;
@@ -349,7 +543,7 @@ mod tests {
     #[tokio::test]
     async fn test_cache_miss() {
         let cache = ResponseCache::new(CacheConfig::default());
-        let key = CacheKey::from_request(&vec![create_message("test")], "model", &None);
+        let key = CacheKey::from_request(&[create_message("test")], "model", &None);
 
         let cached = cache.get(&key).await;
         assert!(cached.is_none());
@@ -367,7 +561,7 @@ mod tests {
             max_entries: 10,
         };
         let cache = ResponseCache::new(config);
-        let key = CacheKey::from_request(&vec![create_message("test")], "model", &None);
+        let key = CacheKey::from_request(&[create_message("test")], "model", &None);
         let response = create_response("cached response");
 
         cache.put(key.clone(), response).await;
@@ -382,6 +576,40 @@ mod tests {
         assert!(cache.get(&key).await.is_none());
     }
 
+    #[tokio::test]
+    async fn test_background_sweep() {
+        let config = CacheConfig::new(true, Duration::from_millis(100), 10);
+        let cache = ResponseCache::new(config);
+        for text in ["a", "b"] {
+            let key = CacheKey::from_request(&[create_message(text)], "model", &None);
+            cache.put(key, create_response(text)).await;
+        }
+
+        // The sweep runs at most once a second
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert_eq!(cache.size().await, 0);
+        assert_eq!(cache.stats().await.total_requests(), 0);
+    }
+
+    #[test]
+    fn test_purge_without_runtime() {
+        let cache = ResponseCache::new(CacheConfig::new(true, Duration::from_millis(20), 10));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let old = CacheKey::from_request(&[create_message("old")], "model", &None);
+            cache.put(old, create_response("old")).await;
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            let new = CacheKey::from_request(&[create_message("new")], "model", &None);
+            cache.put(new.clone(), create_response("new")).await;
+
+            assert_eq!(cache.purge_expired(), 1);
+            assert!(cache.get(&new).await.is_some());
+        });
+    }
+
     #[tokio::test]
     async fn test_cache_eviction() {
         let config = CacheConfig {
@@ -394,7 +622,7 @@ mod tests {
         // Add 3 entries (should evict the least used one)
         for i in 0..3 {
             let key = CacheKey::from_request(
-                &vec![create_message(&format!("test{}", i))],
+                &[create_message(&format!("test{}", i))],
                 "model",
                 &None,
             );
@@ -411,7 +639,7 @@ mod tests {
     #[tokio::test]
     async fn test_cache_disabled() {
         let cache = ResponseCache::new(CacheConfig::disabled());
-        let key = CacheKey::from_request(&vec![create_message("test")], "model", &None);
+        let key = CacheKey::from_request(&[create_message("test")], "model", &None);
         let response = create_response("cached response");
 
         cache.put(key.clone(), response).await;
@@ -423,7 +651,7 @@ mod tests {
     #[tokio::test]
     async fn test_hit_rate() {
         let cache = ResponseCache::new(CacheConfig::default());
-        let key = CacheKey::from_request(&vec![create_message("test")], "model", &None);
+        let key = CacheKey::from_request(&[create_message("test")], "model", &None);
         let response = create_response("cached response");
 
         cache.put(key.clone(), response).await;
@@ -431,10 +659,44 @@ mod tests {
         // 1 hit
         cache.get(&key).await;
         // 1 miss
-        let other_key = CacheKey::from_request(&vec![create_message("other")], "model", &None);
+        let other_key = CacheKey::from_request(&[create_message("other")], "model", &None);
         cache.get(&other_key).await;
 
         let hit_rate = cache.hit_rate().await;
         assert!((hit_rate - 0.5).abs() < 0.01); // Should be 50%
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_load() {
+        let cache = ResponseCache::new(CacheConfig::new(true, Duration::from_secs(60), 512));
+        let keys: Arc<Vec<CacheKey>> = Arc::new(
+            (0..256)
+                .map(|i| CacheKey::from_request(&[create_message(&i.to_string())], "model", &None))
+                .collect(),
+        );
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let keys = keys.clone();
+                tokio::spawn(async move {
+                    for key in keys.iter() {
+                        if cache.get(key).await.is_none() {
+                            cache.put(key.clone(), create_response("cached")).await;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // Every lookup is counted once, and each key was stored at least once
+        let stats = cache.stats().await;
+        assert_eq!(stats.total_requests(), 8 * 256);
+        assert!(stats.misses >= 256);
+        assert_eq!(cache.size().await, 256);
+        assert_eq!(stats.evictions, 0);
+    }
 }
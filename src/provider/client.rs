@@ -1,9 +1,10 @@
 use std::sync::Arc;
+use std::time::Duration;
 use reqwest::Client;
 use crate::provider::{Result, ProviderError};
 use super::retry::{RetryConfig, RetryPolicy};
 use super::rate_limit::{RateLimitConfig, RateLimiter, RateLimitGuard};
-use super::timeout::TimeoutConfig;
+use super::timeout::{StreamDeadline, TimeoutConfig};
 
 /// Shared HTTP client with retry, rate limiting, and timeout support
 #[derive(Debug, Clone)]
@@ -11,6 +12,7 @@ pub struct ProviderClient {
     http_client: Client,
     retry_policy: Arc<RetryPolicy>,
     rate_limiter: Arc<RateLimiter>,
+    timeout_config: TimeoutConfig,
 }
 
 impl ProviderClient {
@@ -24,6 +26,32 @@ impl ProviderClient {
             http_client,
             retry_policy: Arc::new(retry_policy),
             rate_limiter: Arc::new(rate_limiter),
+            timeout_config: TimeoutConfig::default(),
+        }
+    }
+
+    /// Use `config` for streamed responses; connect and request timeouts
+    /// are set on the HTTP client itself
+    pub fn with_timeout_config(mut self, config: TimeoutConfig) -> Self {
+        self.timeout_config = config;
+        self
+    }
+
+    pub fn timeout_config(&self) -> &TimeoutConfig {
+        &self.timeout_config
+    }
+
+    /// Deadlines for reading a streamed response requested now
+    pub(crate) fn stream_deadline(&self) -> StreamDeadline {
+        self.timeout_config.stream_deadline()
+    }
+
+    /// Let a streamed request run past `request_timeout`, up to a second
+    /// beyond `stream_timeout` so the stream deadline is what trips first
+    pub(crate) fn stream_request(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.timeout_config.stream_timeout {
+            Some(limit) => request.timeout(limit + Duration::from_secs(1)),
+            None => request,
         }
     }
 
@@ -47,20 +75,105 @@ impl ProviderClient {
         self.rate_limiter.acquire().await
     }
 
+    /// How long a request sent through this client now would likely wait,
+    /// so schedulers can hold work back instead of queueing it blindly
+    pub async fn estimated_wait(&self) -> WaitEstimate {
+        let (retries_in_flight, backoff) = self.retry_policy.backoffs_in_flight();
+        WaitEstimate {
+            rate_limit: self.rate_limiter.wait_estimate().await,
+            backoff,
+            retries_in_flight,
+            available_permits: self.rate_limiter.stats().await.available_permits,
+        }
+    }
+
+    /// Resolve DNS and open a TLS connection to `url`'s host, leaving it in
+    /// the pool for the next request
+    ///
+    /// Sends a `HEAD` request; any HTTP status counts as success. Not rate
+    /// limited or retried.
+    pub async fn preconnect(&self, url: &str) -> Result<()> {
+        self.http_client
+            .head(url)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| ProviderError::RequestFailed(format!("Failed to connect to {}: {}", url, e)))
+    }
+
     /// Create a builder for configuring a provider client
     pub fn builder() -> ProviderClientBuilder {
         ProviderClientBuilder::default()
     }
 }
 
+/// Report from `ProviderClient::estimated_wait`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitEstimate {
+    /// Until the request and token windows have room
+    pub rate_limit: Duration,
+    /// Longest backoff left among requests waiting to be retried
+    pub backoff: Duration,
+    /// Requests waiting to be retried
+    pub retries_in_flight: usize,
+    /// Concurrency permits free right now; at zero the request also waits
+    /// for one of the requests in flight to finish
+    pub available_permits: usize,
+}
+
+impl WaitEstimate {
+    /// Expected delay before a new request is sent
+    ///
+    /// A retry in flight means the backend is throttling or failing, so a
+    /// request sent before its backoff ends would likely fail the same way.
+    pub fn total(&self) -> Duration {
+        self.rate_limit.max(self.backoff)
+    }
+}
+
+/// Connection pool and keep-alive settings
+#[derive(Debug, Clone)]
+pub struct KeepAliveConfig {
+    /// How long an idle pooled connection stays open; `None` keeps it
+    /// until the server closes it
+    pub pool_idle_timeout: Option<Duration>,
+    /// Most idle connections kept per host
+    pub pool_max_idle_per_host: usize,
+    /// Interval of TCP keep-alive probes on open connections
+    pub tcp_keepalive: Option<Duration>,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+        }
+    }
+}
+
+impl KeepAliveConfig {
+    /// Keep connections warm between the turns of an interactive session
+    pub fn interactive() -> Self {
+        Self {
+            pool_idle_timeout: Some(Duration::from_secs(600)),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
 /// Builder for creating a ProviderClient with custom configuration
 #[derive(Debug)]
 pub struct ProviderClientBuilder {
     retry_config: RetryConfig,
     timeout_config: TimeoutConfig,
     rate_limit_config: RateLimitConfig,
+    keep_alive_config: KeepAliveConfig,
     proxy: Option<String>,
     user_agent: Option<String>,
+    headers: Vec<(String, String)>,
 }
 
 impl Default for ProviderClientBuilder {
@@ -69,11 +182,13 @@ impl Default for ProviderClientBuilder {
             retry_config: RetryConfig::default(),
             timeout_config: TimeoutConfig::default(),
             rate_limit_config: RateLimitConfig::default(),
+            keep_alive_config: KeepAliveConfig::default(),
             proxy: None,
             user_agent: Some(format!(
                 "agent-sdk-rs/{}",
                 env!("CARGO_PKG_VERSION")
             )),
+            headers: Vec::new(),
         }
     }
 }
@@ -97,6 +212,12 @@ impl ProviderClientBuilder {
         self
     }
 
+    /// Set the connection pool and keep-alive configuration
+    pub fn keep_alive_config(mut self, config: KeepAliveConfig) -> Self {
+        self.keep_alive_config = config;
+        self
+    }
+
     /// Set a proxy URL
     pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
         self.proxy = Some(proxy.into());
@@ -109,6 +230,12 @@ impl ProviderClientBuilder {
         self
     }
 
+    /// Add a header sent with every request, e.g. for gateway routing
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
     /// Disable retries
     pub fn no_retry(mut self) -> Self {
         self.retry_config = RetryConfig::none();
@@ -125,7 +252,10 @@ impl ProviderClientBuilder {
     pub fn build(self) -> Result<ProviderClient> {
         let mut client_builder = Client::builder()
             .connect_timeout(self.timeout_config.connect_timeout)
-            .timeout(self.timeout_config.request_timeout);
+            .timeout(self.timeout_config.request_timeout)
+            .pool_idle_timeout(self.keep_alive_config.pool_idle_timeout)
+            .pool_max_idle_per_host(self.keep_alive_config.pool_max_idle_per_host)
+            .tcp_keepalive(self.keep_alive_config.tcp_keepalive);
 
         // Avoid reading system proxy settings in environments where it may panic
         // (e.g. headless CI/macOS sandbox without a dynamic store).
@@ -137,6 +267,21 @@ impl ProviderClientBuilder {
             client_builder = client_builder.user_agent(user_agent);
         }
 
+        if !self.headers.is_empty() {
+            let mut headers = reqwest::header::HeaderMap::new();
+            for (name, value) in &self.headers {
+                let invalid = |e: &dyn std::fmt::Display| {
+                    ProviderError::RequestFailed(format!("Invalid header {}: {}", name, e))
+                };
+                let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| invalid(&e))?;
+                let value =
+                    reqwest::header::HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+                headers.append(name, value);
+            }
+            client_builder = client_builder.default_headers(headers);
+        }
+
         if let Some(proxy_url) = self.proxy {
             let proxy = reqwest::Proxy::all(&proxy_url)
                 .map_err(|e| ProviderError::RequestFailed(format!("Invalid proxy: {}", e)))?;
@@ -150,7 +295,8 @@ impl ProviderClientBuilder {
         let retry_policy = RetryPolicy::new(self.retry_config);
         let rate_limiter = RateLimiter::new(self.rate_limit_config);
 
-        Ok(ProviderClient::new(http_client, retry_policy, rate_limiter))
+        Ok(ProviderClient::new(http_client, retry_policy, rate_limiter)
+            .with_timeout_config(self.timeout_config))
     }
 }
 
@@ -174,6 +320,59 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[test]
+    fn test_builder_keep_alive() {
+        let client = ProviderClient::builder()
+            .keep_alive_config(KeepAliveConfig::interactive())
+            .build();
+        assert!(client.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_preconnect_reports_unreachable_hosts() {
+        let client = ProviderClient::builder().build().unwrap();
+        // Nothing listens on port 9 of localhost
+        let error = client.preconnect("http://127.0.0.1:9").await.unwrap_err();
+        assert!(error.to_string().contains("Failed to connect"));
+    }
+
+    #[tokio::test]
+    async fn test_estimated_wait_reports_backoffs_in_flight() {
+        let client = ProviderClient::builder()
+            .retry_config(RetryConfig::new(1, Duration::from_millis(300)))
+            .build()
+            .unwrap();
+        let idle = client.estimated_wait().await;
+        assert_eq!((idle.total(), idle.retries_in_flight), (Duration::ZERO, 0));
+
+        let retrying = client.clone();
+        let request = tokio::spawn(async move {
+            let mut failed = false;
+            retrying
+                .retry_policy()
+                .execute_with_retry(|| {
+                    let first = !std::mem::replace(&mut failed, true);
+                    async move {
+                        if first {
+                            Err(ProviderError::RequestFailed("503 Service Unavailable".into()))
+                        } else {
+                            Ok(())
+                        }
+                    }
+                })
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let estimate = client.estimated_wait().await;
+        assert_eq!(estimate.retries_in_flight, 1);
+        assert!(estimate.backoff > Duration::from_millis(100));
+        assert!(estimate.total() <= Duration::from_millis(300));
+
+        request.await.unwrap().unwrap();
+        assert_eq!(client.estimated_wait().await.retries_in_flight, 0);
+    }
+
     #[test]
     fn test_builder_no_retry() {
         let client = ProviderClient::builder()
@@ -182,6 +381,18 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[test]
+    fn test_builder_validates_headers() {
+        assert!(ProviderClient::builder()
+            .header("X-Title", "My App")
+            .build()
+            .is_ok());
+        assert!(ProviderClient::builder()
+            .header("bad header", "value")
+            .build()
+            .is_err());
+    }
+
     #[test]
     fn test_builder_with_proxy() {
         let client = ProviderClient::builder()
@@ -1,12 +1,47 @@
-use super::{Message, Role};
+use super::tokenizer::{context_window_for_model, tokenizer_for_model};
+use super::{
+    CharEstimateTokenizer, ContentBlock, EmbeddingProvider, EmbeddingRequest, GenerateOptions,
+    LlmProvider, Message, Role, Tokenizer,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_SUMMARY_MAX_TOKENS: usize = 512;
+
+pub(crate) const SUMMARY_PROMPT: &str = "Summarize the conversation below for an assistant that will continue it. Keep facts, decisions, open questions and tool results that later turns may rely on. Reply with the summary only.";
 
 /// Configuration for context window management
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ContextWindowConfig {
     /// Maximum number of tokens allowed in the context
     pub max_tokens: usize,
     /// Strategy to use when truncating messages
     pub truncation_strategy: TruncationStrategy,
+    /// Token counter (defaults to ~4 characters per token)
+    pub tokenizer: Option<Arc<dyn Tokenizer>>,
+    /// Provider that writes summaries for `TruncationStrategy::Summarize`
+    pub summarizer: Option<Arc<dyn LlmProvider>>,
+    /// Token budget for a summary, reserved out of `max_tokens`
+    pub summary_max_tokens: usize,
+    /// Embeddings that score messages for `TruncationStrategy::Relevance`
+    pub embedder: Option<Arc<dyn EmbeddingProvider>>,
+}
+
+impl std::fmt::Debug for ContextWindowConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextWindowConfig")
+            .field("max_tokens", &self.max_tokens)
+            .field("truncation_strategy", &self.truncation_strategy)
+            .field("tokenizer", &self.tokenizer.as_ref().map(|_| "custom"))
+            .field(
+                "summarizer",
+                &self.summarizer.as_ref().map(|p| p.model().to_string()),
+            )
+            .field("summary_max_tokens", &self.summary_max_tokens)
+            .field("embedder", &self.embedder.as_ref().map(|_| "custom"))
+            .finish()
+    }
 }
 
 impl Default for ContextWindowConfig {
@@ -14,6 +49,10 @@ impl Default for ContextWindowConfig {
         Self {
             max_tokens: 100_000, // Default to 100k tokens
             truncation_strategy: TruncationStrategy::DropOldest,
+            tokenizer: None,
+            summarizer: None,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            embedder: None,
         }
     }
 }
@@ -24,14 +63,61 @@ impl ContextWindowConfig {
         Self {
             max_tokens,
             truncation_strategy,
+            tokenizer: None,
+            summarizer: None,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            embedder: None,
+        }
+    }
+
+    /// Window and tokenizer preset for `model`
+    ///
+    /// Leaves `reserve_output` tokens for the reply. Unknown models get the
+    /// default 100k window; the tokenizer is exact for OpenAI models with the
+    /// `tiktoken` feature and an estimate otherwise.
+    pub fn for_model(model: &str, reserve_output: usize) -> Self {
+        let window = context_window_for_model(model).unwrap_or(100_000);
+        Self {
+            max_tokens: window.saturating_sub(reserve_output),
+            truncation_strategy: TruncationStrategy::DropOldest,
+            tokenizer: Some(tokenizer_for_model(model)),
+            summarizer: None,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            embedder: None,
         }
     }
 
+    /// Count tokens with `tokenizer`
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = Some(tokenizer);
+        self
+    }
+
+    /// Summarize dropped history with `summarizer` (often a cheaper model)
+    /// and switch to `TruncationStrategy::Summarize`
+    pub fn with_summarizer(mut self, summarizer: Arc<dyn LlmProvider>) -> Self {
+        self.summarizer = Some(summarizer);
+        self.truncation_strategy = TruncationStrategy::Summarize;
+        self
+    }
+
+    /// Drop the old turns least related to the latest user message, scored
+    /// with `embedder`, and switch to `TruncationStrategy::Relevance`
+    pub fn with_embedder(mut self, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        self.embedder = Some(embedder);
+        self.truncation_strategy = TruncationStrategy::Relevance;
+        self
+    }
+
     /// Create a configuration for small context windows (e.g., 4k tokens)
     pub fn small() -> Self {
         Self {
             max_tokens: 4_000,
             truncation_strategy: TruncationStrategy::DropOldest,
+            tokenizer: None,
+            summarizer: None,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            embedder: None,
         }
     }
 
@@ -40,6 +126,10 @@ impl ContextWindowConfig {
         Self {
             max_tokens: 32_000,
             truncation_strategy: TruncationStrategy::DropOldest,
+            tokenizer: None,
+            summarizer: None,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            embedder: None,
         }
     }
 
@@ -48,6 +138,10 @@ impl ContextWindowConfig {
         Self {
             max_tokens: 200_000,
             truncation_strategy: TruncationStrategy::DropMiddle,
+            tokenizer: None,
+            summarizer: None,
+            summary_max_tokens: DEFAULT_SUMMARY_MAX_TOKENS,
+            embedder: None,
         }
     }
 }
@@ -59,28 +153,41 @@ pub enum TruncationStrategy {
     DropOldest,
     /// Keep first and last messages, drop middle (preserves instructions and recent context)
     DropMiddle,
-    /// Summarize old messages (future feature - currently behaves like DropOldest)
+    /// Replace the oldest messages with a summary written by
+    /// `ContextWindowConfig::summarizer`; behaves like DropOldest without one
+    /// or if summarizing fails
     Summarize,
+    /// Drop the turns least related to the latest user message first, scored
+    /// with `ContextWindowConfig::embedder`; behaves like DropOldest without
+    /// one or if embedding fails
+    Relevance,
 }
 
 /// Manager for handling context window limits
 pub struct ContextWindowManager {
     config: ContextWindowConfig,
+    tokenizer: Arc<dyn Tokenizer>,
+    /// Last summary, keyed by a hash of the messages it covers
+    last_summary: Mutex<Option<(u64, String)>>,
 }
 
 impl ContextWindowManager {
     /// Create a new context window manager with the given configuration
     pub fn new(config: ContextWindowConfig) -> Self {
-        Self { config }
+        let tokenizer = config
+            .tokenizer
+            .clone()
+            .unwrap_or_else(|| Arc::new(CharEstimateTokenizer::default()));
+        Self {
+            config,
+            tokenizer,
+            last_summary: Mutex::new(None),
+        }
     }
 
-    /// Estimate the number of tokens in a message
-    /// This is a rough estimate: ~4 characters per token for English text
+    /// Count the tokens of a message with the configured tokenizer
     fn estimate_tokens(&self, message: &Message) -> usize {
-        // Rough estimation: 4 characters per token
-        // This is a simplification - real tokenization is more complex
-        let char_count = message.content_as_text().len();
-        (char_count + 3) / 4 // Round up
+        self.tokenizer.count_message_tokens(message)
     }
 
     /// Estimate total tokens in a list of messages
@@ -88,7 +195,28 @@ impl ContextWindowManager {
         messages.iter().map(|m| self.estimate_tokens(m)).sum()
     }
 
+    /// Truncate messages if they exceed the context window limit, writing a
+    /// summary of the dropped turns when the strategy is `Summarize`
+    pub async fn truncate(&self, messages: Vec<Message>) -> Vec<Message> {
+        if self.fits_in_window(&messages) {
+            return messages;
+        }
+        let config = &self.config;
+        match (&config.truncation_strategy, &config.summarizer, &config.embedder) {
+            (TruncationStrategy::Summarize, Some(summarizer), _) => {
+                self.summarize_oldest(messages, summarizer.as_ref()).await
+            }
+            (TruncationStrategy::Relevance, _, Some(embedder)) => {
+                self.drop_least_relevant(messages, embedder.as_ref()).await
+            }
+            _ => self.truncate_if_needed(messages),
+        }
+    }
+
     /// Truncate messages if they exceed the context window limit
+    ///
+    /// Synchronous, so `Summarize` and `Relevance` behave like `DropOldest`;
+    /// use `truncate` for them.
     pub fn truncate_if_needed(&self, messages: Vec<Message>) -> Vec<Message> {
         let total_tokens = self.estimate_total_tokens(&messages);
 
@@ -99,16 +227,14 @@ impl ContextWindowManager {
         match self.config.truncation_strategy {
             TruncationStrategy::DropOldest => self.drop_oldest(messages),
             TruncationStrategy::DropMiddle => self.drop_middle(messages),
-            TruncationStrategy::Summarize => {
-                // TODO: Implement summarization in the future
-                // For now, fall back to DropOldest
+            TruncationStrategy::Summarize | TruncationStrategy::Relevance => {
                 self.drop_oldest(messages)
             }
         }
     }
 
     /// Drop oldest messages until we're within the token limit
-    fn drop_oldest(&self, mut messages: Vec<Message>) -> Vec<Message> {
+    fn drop_oldest(&self, messages: Vec<Message>) -> Vec<Message> {
         // Preserve system messages at the beginning
         let system_messages: Vec<Message> = messages
             .iter()
@@ -140,6 +266,165 @@ impl ContextWindowManager {
         result
     }
 
+    /// Replace the oldest non-system messages with a summary message placed
+    /// after the system messages
+    async fn summarize_oldest(
+        &self,
+        messages: Vec<Message>,
+        summarizer: &dyn LlmProvider,
+    ) -> Vec<Message> {
+        let (system_messages, mut kept): (Vec<Message>, Vec<Message>) =
+            messages.into_iter().partition(|m| m.role == Role::System);
+
+        // Keep the newest turns that fit beside the system prompt and summary
+        let available_tokens = self
+            .config
+            .max_tokens
+            .saturating_sub(self.estimate_total_tokens(&system_messages))
+            .saturating_sub(self.config.summary_max_tokens);
+        let mut dropped = Vec::new();
+        while !kept.is_empty() && self.estimate_total_tokens(&kept) > available_tokens {
+            dropped.push(kept.remove(0));
+        }
+
+        let mut result = system_messages;
+        if !dropped.is_empty() {
+            match self.summary_of(&dropped, summarizer).await {
+                Some(summary) => result.push(Message::system(format!(
+                    "Summary of the earlier conversation:\n{}",
+                    summary
+                ))),
+                None => {
+                    // Without a summary the freed budget can hold more turns
+                    let mut all = dropped;
+                    all.extend(kept);
+                    let mut fallback = result;
+                    fallback.extend(all);
+                    return self.drop_oldest(fallback);
+                }
+            }
+        }
+        result.extend(kept);
+        result
+    }
+
+    /// Drop whole turns, least relevant to the latest user message first
+    ///
+    /// A turn is a user message and the replies and tool results after it,
+    /// so tool calls stay with their results. The current turn is never
+    /// dropped.
+    async fn drop_least_relevant(
+        &self,
+        messages: Vec<Message>,
+        embedder: &dyn EmbeddingProvider,
+    ) -> Vec<Message> {
+        let (system_messages, rest): (Vec<Message>, Vec<Message>) =
+            messages.into_iter().partition(|m| m.role == Role::System);
+
+        let mut turns: Vec<Vec<Message>> = Vec::new();
+        for message in rest {
+            let starts_turn = message.role == Role::User
+                && message
+                    .content
+                    .iter()
+                    .any(|block| !matches!(block, ContentBlock::ToolResult { .. }));
+            match turns.last_mut() {
+                Some(turn) if !starts_turn => turn.push(message),
+                _ => turns.push(vec![message]),
+            }
+        }
+        let Some(current) = turns.pop() else {
+            return system_messages;
+        };
+
+        let query = current[0].content_as_text();
+        let mut input = vec![query];
+        input.extend(turns.iter().map(|turn| turn_text(turn)));
+        let scores = match embedder
+            .create_embeddings(EmbeddingRequest::new_batch(input))
+            .await
+        {
+            Ok(response) if response.embeddings.len() == turns.len() + 1 => {
+                let query = &response.embeddings[0];
+                response.embeddings[1..]
+                    .iter()
+                    .map(|embedding| cosine_similarity(query, embedding))
+                    .collect::<Vec<_>>()
+            }
+            _ => {
+                let mut all = system_messages;
+                all.extend(turns.into_iter().flatten());
+                all.extend(current);
+                return self.drop_oldest(all);
+            }
+        };
+
+        // Least relevant first; older first among equals
+        let mut order: Vec<usize> = (0..turns.len()).collect();
+        order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]).then(a.cmp(&b)));
+
+        let mut used = self.estimate_total_tokens(&system_messages)
+            + turns
+                .iter()
+                .chain(std::iter::once(&current))
+                .map(|turn| self.estimate_total_tokens(turn))
+                .sum::<usize>();
+        let mut dropped = vec![false; turns.len()];
+        for index in order {
+            if used <= self.config.max_tokens {
+                break;
+            }
+            used -= self.estimate_total_tokens(&turns[index]);
+            dropped[index] = true;
+        }
+
+        let mut result = system_messages;
+        for (turn, dropped) in turns.into_iter().zip(dropped) {
+            if !dropped {
+                result.extend(turn);
+            }
+        }
+        result.extend(current);
+        // The current turn alone may still be too long
+        self.truncate_if_needed(result)
+    }
+
+    /// Summary of `dropped`, reusing the previous one for the same messages
+    async fn summary_of(
+        &self,
+        dropped: &[Message],
+        summarizer: &dyn LlmProvider,
+    ) -> Option<String> {
+        let transcript = transcript(dropped);
+        let mut hasher = DefaultHasher::new();
+        transcript.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some((cached_key, summary)) = self.last_summary.lock().unwrap().as_ref() {
+            if *cached_key == key {
+                return Some(summary.clone());
+            }
+        }
+
+        let options = GenerateOptions {
+            max_tokens: Some(self.config.summary_max_tokens as u32),
+            ..Default::default()
+        };
+        let response = summarizer
+            .generate(
+                vec![Message::system(SUMMARY_PROMPT), Message::user(transcript)],
+                Some(options),
+            )
+            .await
+            .ok()?;
+        let summary = response.content.trim().to_string();
+        if summary.is_empty() {
+            return None;
+        }
+        *self.last_summary.lock().unwrap() = Some((key, summary.clone()));
+        Some(summary)
+    }
+
     /// Keep first and last messages, drop middle ones
     fn drop_middle(&self, messages: Vec<Message>) -> Vec<Message> {
         if messages.len() <= 2 {
@@ -227,9 +512,52 @@ impl ContextWindowManager {
     }
 }
 
+/// Text of a turn for embedding, including tool results
+/// Messages as `role: text` paragraphs, for a summarizer to read
+pub(crate) fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let role = match m.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            format!("{}: {}", role, m.content_as_text())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn turn_text(turn: &[Message]) -> String {
+    turn.iter()
+        .flat_map(|m| m.content.iter())
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            ContentBlock::ToolResult { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::provider::{GenerateResponse, ProviderError, Result};
+    use std::future::Future;
+    use std::pin::Pin;
 
     fn create_message(role: Role, content: &str) -> Message {
         match role {
@@ -242,8 +570,8 @@ mod tests {
     #[test]
     fn test_token_estimation() {
         let manager = ContextWindowManager::new(ContextWindowConfig::default());
-        let message = create_message(Role::User, "Hello world"); // 11 chars ~= 3 tokens
-        assert_eq!(manager.estimate_tokens(&message), 3);
+        let message = create_message(Role::User, "Hello world"); // 11 chars ~= 3 tokens, plus 3 for message framing
+        assert_eq!(manager.estimate_tokens(&message), 6);
     }
 
     #[test]
@@ -316,4 +644,184 @@ mod tests {
         ];
         assert!(!manager.fits_in_window(&large_messages));
     }
+
+    struct Summarizer {
+        calls: std::sync::atomic::AtomicUsize,
+        fail: bool,
+    }
+
+    impl LlmProvider for Summarizer {
+        fn name(&self) -> &str {
+            "summarizer"
+        }
+
+        fn model(&self) -> &str {
+            "cheap"
+        }
+
+        fn generate(
+            &self,
+            messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let transcript = messages[1].content_as_text();
+            let fail = self.fail;
+            Box::pin(async move {
+                if fail {
+                    return Err(ProviderError::Other("down".to_string()));
+                }
+                Ok(GenerateResponse {
+                    content: format!("{} earlier turns", transcript.matches("user:").count()),
+                    usage: None,
+                    model: "cheap".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    fn long_conversation() -> Vec<Message> {
+        let mut messages = vec![create_message(Role::System, "Be helpful")];
+        for i in 0..6 {
+            messages.push(create_message(
+                Role::User,
+                &format!("question {} {}", i, "x".repeat(40)),
+            ));
+            messages.push(create_message(
+                Role::Assistant,
+                &format!("answer {} {}", i, "y".repeat(40)),
+            ));
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_summarize_replaces_dropped_turns() {
+        let summarizer = Arc::new(Summarizer {
+            calls: Default::default(),
+            fail: false,
+        });
+        let mut config = ContextWindowConfig::new(120, TruncationStrategy::DropOldest)
+            .with_summarizer(summarizer.clone());
+        config.summary_max_tokens = 20;
+        let manager = ContextWindowManager::new(config);
+
+        let result = manager.truncate(long_conversation()).await;
+        assert_eq!(result[0].content_as_text(), "Be helpful");
+        assert_eq!(result[1].role, Role::System);
+        assert!(result[1].content_as_text().ends_with("earlier turns"));
+        assert_eq!(
+            result.last().unwrap().content_as_text(),
+            long_conversation()[12].content_as_text()
+        );
+        assert!(manager.fits_in_window(&result));
+
+        // The same overflow reuses the previous summary
+        manager.truncate(long_conversation()).await;
+        assert_eq!(
+            summarizer.calls.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_summarize_falls_back_to_drop_oldest() {
+        let config = ContextWindowConfig::new(120, TruncationStrategy::DropOldest).with_summarizer(
+            Arc::new(Summarizer {
+                calls: Default::default(),
+                fail: true,
+            }),
+        );
+        let manager = ContextWindowManager::new(config);
+
+        let result = manager.truncate(long_conversation()).await;
+        let expected = ContextWindowManager::new(ContextWindowConfig::new(
+            120,
+            TruncationStrategy::DropOldest,
+        ))
+        .truncate_if_needed(long_conversation());
+        assert_eq!(result.len(), expected.len());
+        assert!(result
+            .iter()
+            .all(|m| !m.content_as_text().starts_with("Summary")));
+    }
+
+    /// Embeds text as [mentions rust, mentions cooking]
+    struct KeywordEmbedder;
+
+    impl EmbeddingProvider for KeywordEmbedder {
+        fn create_embeddings(
+            &self,
+            request: EmbeddingRequest,
+        ) -> Pin<Box<dyn Future<Output = Result<crate::provider::EmbeddingResponse>> + Send + '_>>
+        {
+            let embeddings = request
+                .input
+                .iter()
+                .map(|text| {
+                    vec![
+                        text.contains("rust") as u8 as f32,
+                        text.contains("cook") as u8 as f32,
+                    ]
+                })
+                .collect();
+            Box::pin(async move {
+                Ok(crate::provider::EmbeddingResponse {
+                    embeddings,
+                    model: "keywords".to_string(),
+                    usage: None,
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relevance_drops_unrelated_turns_first() {
+        let call = crate::tool::ToolCall {
+            id: "call_1".to_string(),
+            name: "recipes".to_string(),
+            parameters: serde_json::json!({}),
+        };
+        let messages = vec![
+            create_message(Role::System, "Be helpful"),
+            create_message(Role::User, "How do rust lifetimes work?"),
+            create_message(Role::Assistant, "In rust, lifetimes name how long borrows last."),
+            create_message(Role::User, "How long do I cook pasta?"),
+            Message::assistant_with_tool_calls("", &[call]),
+            Message::tool_results(vec![ContentBlock::tool_result(
+                "call_1",
+                "cook it 10 minutes",
+                false,
+            )]),
+            create_message(Role::Assistant, "About ten minutes."),
+            create_message(Role::User, "And the rust borrow checker?"),
+        ];
+        let config = ContextWindowConfig::new(60, TruncationStrategy::DropOldest)
+            .with_embedder(Arc::new(KeywordEmbedder));
+        let manager = ContextWindowManager::new(config);
+
+        let result = manager.truncate(messages.clone()).await;
+        let texts: Vec<String> = result.iter().map(|m| m.content_as_text()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Be helpful",
+                "How do rust lifetimes work?",
+                "In rust, lifetimes name how long borrows last.",
+                "And the rust borrow checker?",
+            ]
+        );
+
+        // Oldest-first would have kept the cooking turn instead
+        let oldest = ContextWindowManager::new(ContextWindowConfig::new(
+            60,
+            TruncationStrategy::DropOldest,
+        ))
+        .truncate_if_needed(messages);
+        assert!(oldest
+            .iter()
+            .any(|m| m.content_as_text() == "About ten minutes."));
+    }
 }
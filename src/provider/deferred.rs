@@ -0,0 +1,188 @@
+use super::{GenerateOptions, GenerateResponse, Message, ProviderError, Result, ToolSchema};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Generation queued on the provider's side
+///
+/// Serializable so it can outlive the process, e.g. in
+/// `Session::pending_jobs`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct JobHandle {
+    pub id: String,
+    /// Name of the provider that accepted the job; only it can poll it
+    pub provider: String,
+    /// Unix seconds
+    pub submitted_at: u64,
+}
+
+impl JobHandle {
+    pub fn new(id: impl Into<String>, provider: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            provider: provider.into(),
+            submitted_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum JobStatus {
+    /// Queued or running
+    Pending,
+    Completed(GenerateResponse),
+    /// Failed, expired or cancelled
+    Failed(String),
+}
+
+/// Trait for providers that accept generations to run later, such as
+/// batch APIs, so callers need not hold a connection open
+pub trait DeferredProvider: Send + Sync {
+    fn submit(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<JobHandle>> + Send + '_>>;
+
+    fn poll<'a>(
+        &'a self,
+        job: &'a JobHandle,
+    ) -> Pin<Box<dyn Future<Output = Result<JobStatus>> + Send + 'a>>;
+
+    fn cancel<'a>(
+        &'a self,
+        job: &'a JobHandle,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+    /// Poll every `interval` until the job finishes
+    fn await_result<'a>(
+        &'a self,
+        job: &'a JobHandle,
+        interval: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + 'a>> {
+        Box::pin(async move {
+            loop {
+                match self.poll(job).await? {
+                    JobStatus::Pending => tokio::time::sleep(interval).await,
+                    JobStatus::Completed(response) => return Ok(response),
+                    JobStatus::Failed(reason) => {
+                        return Err(ProviderError::RequestFailed(format!(
+                            "Job {} failed: {}",
+                            job.id, reason
+                        )))
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Wait for `job` in the background and pass its outcome to `on_complete`
+pub fn spawn_completion<F>(
+    provider: Arc<dyn DeferredProvider>,
+    job: JobHandle,
+    interval: Duration,
+    on_complete: F,
+) -> tokio::task::JoinHandle<()>
+where
+    F: FnOnce(JobHandle, Result<GenerateResponse>) + Send + 'static,
+{
+    tokio::spawn(async move {
+        let result = provider.await_result(&job, interval).await;
+        on_complete(job, result);
+    })
+}
+
+/// Reject options a queued job cannot honour
+#[cfg_attr(not(feature = "providers"), allow(dead_code))]
+pub(super) fn check_deferrable(options: &Option<GenerateOptions>) -> Result<()> {
+    if options
+        .as_ref()
+        .is_some_and(|o| o.assistant_prefix.is_some())
+    {
+        return Err(ProviderError::Other(
+            "Assistant prefill is not supported for deferred generation".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Completes on the second poll
+    struct SlowBatch {
+        polls: AtomicUsize,
+    }
+
+    impl DeferredProvider for SlowBatch {
+        fn submit(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolSchema>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<JobHandle>> + Send + '_>> {
+            Box::pin(async { Ok(JobHandle::new("job-1", "slow")) })
+        }
+
+        fn poll<'a>(
+            &'a self,
+            _job: &'a JobHandle,
+        ) -> Pin<Box<dyn Future<Output = Result<JobStatus>> + Send + 'a>> {
+            Box::pin(async move {
+                if self.polls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Ok(JobStatus::Pending);
+                }
+                Ok(JobStatus::Completed(GenerateResponse {
+                    content: "done".to_string(),
+                    usage: None,
+                    model: "m".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                }))
+            })
+        }
+
+        fn cancel<'a>(
+            &'a self,
+            _job: &'a JobHandle,
+        ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn completes_submitted_jobs_in_the_background() {
+        let provider = Arc::new(SlowBatch {
+            polls: AtomicUsize::new(0),
+        });
+        let job = provider
+            .submit(vec![Message::user("hi")], Vec::new(), None)
+            .await
+            .unwrap();
+        let restored: JobHandle =
+            serde_json::from_value(serde_json::to_value(&job).unwrap()).unwrap();
+        assert_eq!(restored, job);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        spawn_completion(
+            provider.clone(),
+            job,
+            Duration::from_millis(1),
+            |job, result| {
+                let _ = tx.send((job.id, result.map(|r| r.content)));
+            },
+        );
+        let (id, content) = rx.await.unwrap();
+        assert_eq!(id, "job-1");
+        assert_eq!(content.unwrap(), "done");
+        assert_eq!(provider.polls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,6 +1,6 @@
 use std::future::Future;
 use std::pin::Pin;
-use super::{Result, ProviderError};
+use super::Result;
 
 /// Request for creating embeddings
 #[derive(Debug, Clone)]
@@ -0,0 +1,160 @@
+//! Provider-specific request parameters
+//!
+//! A plain name such as `top_k` is sent to every provider that reads
+//! extensions; a name prefixed with a provider name, such as
+//! `anthropic.thinking`, only to that provider, and it wins over the plain
+//! one. Each provider puts them where its API takes sampling parameters.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Name of an extension with the type of its value
+pub struct ExtensionKey<T> {
+    name: &'static str,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> ExtensionKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for ExtensionKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ExtensionKey<T> {}
+
+impl<T> fmt::Debug for ExtensionKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ExtensionKey").field(&self.name).finish()
+    }
+}
+
+/// Sample only from the `k` most likely tokens
+pub const TOP_K: ExtensionKey<u32> = ExtensionKey::new("top_k");
+/// Penalize tokens that already appeared; 1.0 leaves them alone
+pub const REPETITION_PENALTY: ExtensionKey<f32> = ExtensionKey::new("repetition_penalty");
+/// Seed for reproducible sampling, where supported
+pub const SEED: ExtensionKey<u64> = ExtensionKey::new("seed");
+
+/// Extra request parameters, by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extensions {
+    values: BTreeMap<String, Value>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a parameter by name, e.g. `"ollama.mirostat"`
+    pub fn insert(&mut self, name: impl Into<String>, value: Value) -> &mut Self {
+        self.values.insert(name.into(), value);
+        self
+    }
+
+    pub fn with(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Set a typed parameter
+    pub fn set<T: Serialize>(&mut self, key: ExtensionKey<T>, value: T) -> &mut Self {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.insert(key.name, value)
+    }
+
+    /// Typed parameter, if set to a value of that type
+    pub fn get<T: DeserializeOwned>(&self, key: ExtensionKey<T>) -> Option<T> {
+        self.values
+            .get(key.name)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    pub fn get_raw(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.values.remove(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Parameters for the provider named `provider`, without its prefix
+    pub fn for_provider(&self, provider: &str) -> Map<String, Value> {
+        let mut fields = Map::new();
+        for (name, value) in &self.values {
+            if !name.contains('.') {
+                fields.insert(name.clone(), value.clone());
+            }
+        }
+        for (name, value) in &self.values {
+            if let Some((namespace, field)) = name.split_once('.') {
+                if namespace == provider {
+                    fields.insert(field.to_string(), value.clone());
+                }
+            }
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn namespaced_parameters_only_reach_their_provider() {
+        let mut extensions = Extensions::new()
+            .with("anthropic.top_k", json!(20))
+            .with("ollama.mirostat", json!(2));
+        extensions.set(TOP_K, 40).set(SEED, 7);
+
+        assert_eq!(extensions.get(TOP_K), Some(40));
+        assert_eq!(extensions.get(REPETITION_PENALTY), None);
+        assert_eq!(
+            extensions.get(ExtensionKey::<String>::new("top_k")),
+            None,
+            "wrong type"
+        );
+
+        assert_eq!(
+            Value::Object(extensions.for_provider("anthropic")),
+            json!({"top_k": 20, "seed": 7})
+        );
+        assert_eq!(
+            Value::Object(extensions.for_provider("ollama")),
+            json!({"top_k": 40, "seed": 7, "mirostat": 2})
+        );
+        assert_eq!(
+            Value::Object(extensions.for_provider("openai")),
+            json!({"top_k": 40, "seed": 7})
+        );
+    }
+}
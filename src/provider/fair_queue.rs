@@ -0,0 +1,379 @@
+//! Fair queuing for agents that share one provider.
+//!
+//! Requests from every handle of a `FairShareProvider` wait in per-client
+//! queues and are admitted in weighted round-robin order, so a chatty agent
+//! cannot starve the others of the provider's rate limit.
+
+use super::{
+    GenerateOptions, GenerateResponse, LlmProvider, Message, ProviderCapabilities, RateLimiter,
+    Result, StreamEvents, StreamResponse, ToolSchema,
+};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+struct ClientQueue {
+    name: String,
+    weight: u32,
+    /// Grants left in the client's current round-robin turn
+    credit: u32,
+    waiters: VecDeque<oneshot::Sender<()>>,
+}
+
+struct SchedulerState {
+    in_flight: usize,
+    clients: Vec<ClientQueue>,
+    cursor: usize,
+}
+
+impl SchedulerState {
+    /// Next waiter in weighted round-robin order
+    fn next_waiter(&mut self) -> Option<oneshot::Sender<()>> {
+        let count = self.clients.len();
+        for _ in 0..count {
+            let client = &mut self.clients[self.cursor];
+            if let Some(waiter) = client.waiters.pop_front() {
+                client.credit -= 1;
+                if client.credit == 0 || client.waiters.is_empty() {
+                    client.credit = client.weight;
+                    self.cursor = (self.cursor + 1) % count;
+                }
+                return Some(waiter);
+            }
+            client.credit = client.weight;
+            self.cursor = (self.cursor + 1) % count;
+        }
+        None
+    }
+}
+
+struct Scheduler {
+    max_in_flight: usize,
+    state: Mutex<SchedulerState>,
+}
+
+impl Scheduler {
+    async fn acquire(self: &Arc<Self>, client: usize) -> FairPermit {
+        let rx = {
+            let mut state = self.state.lock().unwrap();
+            let nobody_waiting = state.clients.iter().all(|c| c.waiters.is_empty());
+            if nobody_waiting && state.in_flight < self.max_in_flight {
+                state.in_flight += 1;
+                return FairPermit {
+                    scheduler: self.clone(),
+                };
+            }
+            let (tx, rx) = oneshot::channel();
+            state.clients[client].waiters.push_back(tx);
+            rx
+        };
+
+        let mut pending = PendingGrant {
+            rx: Some(rx),
+            scheduler: self.clone(),
+        };
+        // Senders are only dropped unsent when the receiver is already gone
+        let _ = pending.rx.as_mut().expect("pending grant").await;
+        pending.rx = None;
+        FairPermit {
+            scheduler: self.clone(),
+        }
+    }
+
+    /// Hand the slot to the next waiter, or free it
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(waiter) = state.next_waiter() {
+            if waiter.send(()).is_ok() {
+                return;
+            }
+        }
+        state.in_flight -= 1;
+    }
+}
+
+/// Releases the slot if the waiting request is cancelled after being granted
+struct PendingGrant {
+    rx: Option<oneshot::Receiver<()>>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl Drop for PendingGrant {
+    fn drop(&mut self) {
+        if let Some(mut rx) = self.rx.take() {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                self.scheduler.release();
+            }
+        }
+    }
+}
+
+/// Admission to send one request, released on drop
+struct FairPermit {
+    scheduler: Arc<Scheduler>,
+}
+
+impl Drop for FairPermit {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+/// Provider shared between agents with fair admission
+///
+/// Give each agent its own handle from `handle` or `weighted_handle`; at most
+/// `max_in_flight` requests run at once and waiting requests are admitted
+/// round-robin across handles, `weight` turns at a time.
+pub struct FairShareProvider<P: LlmProvider> {
+    provider: Arc<P>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl<P: LlmProvider> FairShareProvider<P> {
+    pub fn new(provider: P, max_in_flight: usize) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            scheduler: Arc::new(Scheduler {
+                max_in_flight: max_in_flight.max(1),
+                state: Mutex::new(SchedulerState {
+                    in_flight: 0,
+                    clients: Vec::new(),
+                    cursor: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Handle with weight 1 for the client called `name`
+    pub fn handle(&self, name: impl Into<String>) -> FairShareHandle<P> {
+        self.weighted_handle(name, 1)
+    }
+
+    /// Handle for the client called `name`; handles with the same name share
+    /// one queue and the most recent weight
+    pub fn weighted_handle(&self, name: impl Into<String>, weight: u32) -> FairShareHandle<P> {
+        let name = name.into();
+        let weight = weight.max(1);
+        let mut state = self.scheduler.state.lock().unwrap();
+        let client = match state.clients.iter().position(|c| c.name == name) {
+            Some(index) => {
+                state.clients[index].weight = weight;
+                index
+            }
+            None => {
+                state.clients.push(ClientQueue {
+                    name,
+                    weight,
+                    credit: weight,
+                    waiters: VecDeque::new(),
+                });
+                state.clients.len() - 1
+            }
+        };
+        FairShareHandle {
+            provider: self.provider.clone(),
+            scheduler: self.scheduler.clone(),
+            client,
+        }
+    }
+
+    /// Number of waiting requests per client, in registration order
+    pub fn queued(&self) -> Vec<(String, usize)> {
+        let state = self.scheduler.state.lock().unwrap();
+        state
+            .clients
+            .iter()
+            .map(|c| (c.name.clone(), c.waiters.len()))
+            .collect()
+    }
+}
+
+/// One client's view of a `FairShareProvider`
+pub struct FairShareHandle<P: LlmProvider> {
+    provider: Arc<P>,
+    scheduler: Arc<Scheduler>,
+    client: usize,
+}
+
+impl<P: LlmProvider> Clone for FairShareHandle<P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            scheduler: self.scheduler.clone(),
+            client: self.client,
+        }
+    }
+}
+
+impl<P: LlmProvider> LlmProvider for FairShareHandle<P> {
+    fn name(&self) -> &str {
+        self.provider.name()
+    }
+
+    fn model(&self) -> &str {
+        self.provider.model()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.provider.capabilities()
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let _permit = self.scheduler.acquire(self.client).await;
+            self.provider.generate(messages, options).await
+        })
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let _permit = self.scheduler.acquire(self.client).await;
+            self.provider
+                .generate_with_tools(messages, tools, options)
+                .await
+        })
+    }
+
+    /// The slot is held until the stream is open, not until it ends
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let _permit = self.scheduler.acquire(self.client).await;
+            self.provider.generate_stream(messages, options).await
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            let _permit = self.scheduler.acquire(self.client).await;
+            self.provider
+                .generate_stream_events(messages, tools, options)
+                .await
+        })
+    }
+
+    fn health_check(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.provider.health_check()
+    }
+
+    /// Not admitted through the queue; opening a connection takes no slot
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.provider.preconnect()
+    }
+
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.provider.rate_limiter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl LlmProvider for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn model(&self) -> &str {
+            "echo"
+        }
+
+        fn generate(
+            &self,
+            messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+            Box::pin(async move {
+                Ok(GenerateResponse {
+                    content: messages[0].content_as_text(),
+                    usage: None,
+                    model: "echo".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    async fn wait_until_queued(shared: &FairShareProvider<Echo>, total: usize) {
+        while shared.queued().iter().map(|(_, n)| n).sum::<usize>() < total {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn admits_waiting_clients_round_robin() {
+        let shared = FairShareProvider::new(Echo, 1);
+        let chatty = shared.handle("chatty");
+        let quiet = shared.handle("quiet");
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // Occupy the only slot so every request below has to queue
+        let blocker = shared.scheduler.acquire(0).await;
+
+        let mut tasks = Vec::new();
+        for (handle, label) in [(&chatty, "a1"), (&chatty, "a2"), (&chatty, "a3")] {
+            let (handle, tx) = (handle.clone(), tx.clone());
+            tasks.push(tokio::spawn(async move {
+                let response = handle.generate(vec![Message::user(label)], None).await;
+                tx.send(response.unwrap().content).unwrap();
+            }));
+            wait_until_queued(&shared, tasks.len()).await;
+        }
+        let tx_quiet = tx.clone();
+        tasks.push(tokio::spawn(async move {
+            let response = quiet.generate(vec![Message::user("b1")], None).await;
+            tx_quiet.send(response.unwrap().content).unwrap();
+        }));
+        wait_until_queued(&shared, 4).await;
+
+        drop(blocker);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let order: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(order, vec!["a1", "b1", "a2", "a3"]);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_do_not_leak_slots() {
+        let shared = FairShareProvider::new(Echo, 1);
+        let handle = shared.handle("agent");
+
+        let blocker = shared.scheduler.acquire(0).await;
+        let waiting = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.generate(vec![Message::user("x")], None).await }
+        });
+        wait_until_queued(&shared, 1).await;
+        waiting.abort();
+        let _ = waiting.await;
+        drop(blocker);
+
+        let response = handle.generate(vec![Message::user("ok")], None).await;
+        assert_eq!(response.unwrap().content, "ok");
+    }
+}
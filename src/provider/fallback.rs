@@ -0,0 +1,407 @@
+//! Provider wrapper that fails over to the next provider on transient errors.
+
+use super::{
+    GenerateOptions, GenerateResponse, LlmProvider, Message, MiddlewareChain, ProviderCapabilities,
+    ProviderError, ResponseContext, Result, StreamEvents, StreamResponse, ToolSchema,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Which provider served a request and what failed before it
+#[derive(Debug, Clone)]
+pub struct FallbackRecord {
+    /// Name of the provider that served the request
+    pub provider: String,
+    /// Model of the provider that served the request
+    pub model: String,
+    /// Providers tried first, with the error that caused the failover
+    pub failed: Vec<(String, ProviderError)>,
+}
+
+impl FallbackRecord {
+    fn metadata(&self) -> HashMap<String, String> {
+        let failed = self
+            .failed
+            .iter()
+            .map(|(name, error)| format!("{}: {}", name, error))
+            .collect::<Vec<_>>()
+            .join("; ");
+        HashMap::from([
+            ("fallback.provider".to_string(), self.provider.clone()),
+            ("fallback.model".to_string(), self.model.clone()),
+            (
+                "fallback.attempts".to_string(),
+                (self.failed.len() + 1).to_string(),
+            ),
+            ("fallback.failed".to_string(), failed),
+        ])
+    }
+}
+
+pub type FallbackObserver = Arc<dyn Fn(&FallbackRecord) + Send + Sync>;
+
+/// Tries an ordered list of providers, moving to the next one when a
+/// provider is rate limited, does not have the model, or returns a 5xx
+///
+/// Other errors (authentication, parsing, unsupported options) are returned
+/// immediately since another provider would not fix the request.
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn LlmProvider>>,
+    middleware: Option<MiddlewareChain>,
+    observer: Option<FallbackObserver>,
+}
+
+impl FallbackProvider {
+    /// Create a builder; providers are tried in the order they are added
+    pub fn builder() -> FallbackProviderBuilder {
+        FallbackProviderBuilder::default()
+    }
+
+    /// Whether `error` should move the request on to the next provider
+    pub fn should_fail_over(error: &ProviderError) -> bool {
+        match error {
+            ProviderError::RateLimited { .. } | ProviderError::ModelNotAvailable(_) => true,
+            // Status errors are formatted as "<code> <reason>: <body>"
+            ProviderError::RequestFailed(msg) => msg
+                .split_whitespace()
+                .next()
+                .and_then(|code| code.trim_end_matches(':').parse::<u16>().ok())
+                .is_some_and(|code| (500..600).contains(&code)),
+            _ => false,
+        }
+    }
+
+    fn report(&self, record: &FallbackRecord) {
+        if let Some(observer) = &self.observer {
+            observer(record);
+        }
+    }
+
+    /// Run `call` against each provider in turn until one succeeds or fails
+    /// with an error that is not worth failing over
+    async fn try_each<'a, T, F>(&'a self, mut call: F) -> Result<(T, FallbackRecord)>
+    where
+        F: FnMut(&'a dyn LlmProvider) -> Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>,
+    {
+        let mut failed = Vec::new();
+        for provider in &self.providers {
+            match call(provider.as_ref()).await {
+                Ok(value) => {
+                    let record = FallbackRecord {
+                        provider: provider.name().to_string(),
+                        model: provider.model().to_string(),
+                        failed,
+                    };
+                    self.report(&record);
+                    return Ok((value, record));
+                }
+                Err(error) if Self::should_fail_over(&error) => {
+                    failed.push((provider.name().to_string(), error));
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Err(failed
+            .pop()
+            .map(|(_, error)| error)
+            .unwrap_or_else(|| ProviderError::Other("No providers configured".to_string())))
+    }
+
+    async fn generate_inner(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolSchema>>,
+        options: Option<GenerateOptions>,
+    ) -> Result<GenerateResponse> {
+        let result = self
+            .try_each(|provider| match &tools {
+                Some(tools) => {
+                    provider.generate_with_tools(messages.clone(), tools.clone(), options.clone())
+                }
+                None => provider.generate(messages.clone(), options.clone()),
+            })
+            .await;
+
+        match result {
+            Ok((response, record)) => {
+                let mut ctx = ResponseContext {
+                    response,
+                    metadata: record.metadata(),
+                };
+                if let Some(mw) = &self.middleware {
+                    mw.execute_after(&mut ctx).await?;
+                }
+                Ok(ctx.response)
+            }
+            Err(e) => {
+                if let Some(mw) = &self.middleware {
+                    let _ = mw.execute_error(&e).await;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Builder for `FallbackProvider`
+#[derive(Default)]
+pub struct FallbackProviderBuilder {
+    providers: Vec<Box<dyn LlmProvider>>,
+    middleware: Option<MiddlewareChain>,
+    observer: Option<FallbackObserver>,
+}
+
+impl FallbackProviderBuilder {
+    /// Append a provider to the failover order
+    pub fn provider(mut self, provider: impl LlmProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Set the middleware chain; `after_response` receives the serving
+    /// provider under the `fallback.*` metadata keys
+    pub fn middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = Some(middleware);
+        self
+    }
+
+    /// Called with a `FallbackRecord` for every request that succeeds,
+    /// including streaming requests
+    pub fn on_served<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&FallbackRecord) + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Build the fallback provider
+    pub fn build(self) -> Result<FallbackProvider> {
+        if self.providers.is_empty() {
+            return Err(ProviderError::RequestFailed(
+                "At least one provider is required".to_string(),
+            ));
+        }
+        Ok(FallbackProvider {
+            providers: self.providers,
+            middleware: self.middleware,
+            observer: self.observer,
+        })
+    }
+}
+
+impl LlmProvider for FallbackProvider {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    /// Model of the primary provider
+    fn model(&self) -> &str {
+        self.providers[0].model()
+    }
+
+    /// Only what every provider supports, so a failover never hits an option
+    /// the backup rejects
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.providers
+            .iter()
+            .map(|p| p.capabilities())
+            .reduce(|a, b| a.intersect(&b))
+            .unwrap_or_default()
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, None, options))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, Some(tools), options))
+    }
+
+    /// Fails over only while opening the stream; errors after the first
+    /// chunk are delivered on the stream
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let (stream, _) = self
+                .try_each(|provider| provider.generate_stream(messages.clone(), options.clone()))
+                .await?;
+            Ok(stream)
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            let (events, _) = self
+                .try_each(|provider| {
+                    provider.generate_stream_events(
+                        messages.clone(),
+                        tools.clone(),
+                        options.clone(),
+                    )
+                })
+                .await?;
+            Ok(events)
+        })
+    }
+
+    /// Connects the backups too, so a failover is not slowed down
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            futures_util::future::try_join_all(self.providers.iter().map(|p| p.preconnect()))
+                .await
+                .map(|_| ())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::Middleware;
+    use std::sync::Mutex;
+
+    struct Scripted {
+        name: &'static str,
+        error: Option<ProviderError>,
+    }
+
+    impl LlmProvider for Scripted {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn model(&self) -> &str {
+            "m"
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+            let result = match &self.error {
+                Some(error) => Err(error.clone()),
+                None => Ok(GenerateResponse {
+                    content: self.name.to_string(),
+                    usage: None,
+                    model: "m".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                }),
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    fn failing(name: &'static str, error: ProviderError) -> Scripted {
+        Scripted {
+            name,
+            error: Some(error),
+        }
+    }
+
+    fn working(name: &'static str) -> Scripted {
+        Scripted { name, error: None }
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Option<HashMap<String, String>>>);
+
+    #[async_trait::async_trait]
+    impl Middleware for Recorder {
+        async fn after_response(&self, ctx: &mut ResponseContext) -> Result<()> {
+            *self.0.lock().unwrap() = Some(ctx.metadata.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fails_over_only_on_transient_errors() {
+        assert!(FallbackProvider::should_fail_over(
+            &ProviderError::RateLimited { retry_after: None }
+        ));
+        assert!(FallbackProvider::should_fail_over(
+            &ProviderError::RequestFailed("503 Service Unavailable: busy".to_string())
+        ));
+        assert!(!FallbackProvider::should_fail_over(
+            &ProviderError::RequestFailed("400 Bad Request: nope".to_string())
+        ));
+        assert!(!FallbackProvider::should_fail_over(
+            &ProviderError::AuthenticationFailed("bad key".to_string())
+        ));
+    }
+
+    #[tokio::test]
+    async fn serves_from_next_provider_and_records_it() {
+        let recorder = Arc::new(Recorder::default());
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let served_clone = served.clone();
+
+        let provider = FallbackProvider::builder()
+            .provider(failing(
+                "primary",
+                ProviderError::RateLimited { retry_after: None },
+            ))
+            .provider(failing(
+                "secondary",
+                ProviderError::ModelNotAvailable("gone".to_string()),
+            ))
+            .provider(working("tertiary"))
+            .middleware(MiddlewareChain::new().add(recorder.clone()))
+            .on_served(move |record| served_clone.lock().unwrap().push(record.clone()))
+            .build()
+            .unwrap();
+
+        let response = provider
+            .generate(vec![Message::user("hi")], None)
+            .await
+            .unwrap();
+        assert_eq!(response.content, "tertiary");
+
+        let metadata = recorder.0.lock().unwrap().clone().unwrap();
+        assert_eq!(metadata["fallback.provider"], "tertiary");
+        assert_eq!(metadata["fallback.attempts"], "3");
+
+        let served = served.lock().unwrap();
+        assert_eq!(served[0].failed.len(), 2);
+        assert_eq!(served[0].failed[0].0, "primary");
+    }
+
+    #[tokio::test]
+    async fn stops_on_non_transient_errors() {
+        let provider = FallbackProvider::builder()
+            .provider(failing(
+                "primary",
+                ProviderError::AuthenticationFailed("bad key".to_string()),
+            ))
+            .provider(working("secondary"))
+            .build()
+            .unwrap();
+
+        let result = provider.generate(vec![Message::user("hi")], None).await;
+        assert!(matches!(
+            result,
+            Err(ProviderError::AuthenticationFailed(_))
+        ));
+    }
+}
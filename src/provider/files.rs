@@ -0,0 +1,143 @@
+use super::Result;
+use std::future::Future;
+use std::pin::Pin;
+
+/// File to upload to a provider
+#[derive(Debug, Clone)]
+pub struct FileUpload {
+    pub filename: String,
+    pub contents: Vec<u8>,
+    /// Guessed from the file extension unless set
+    pub media_type: String,
+    /// What the file is for, where the provider asks (e.g. OpenAI's
+    /// "user_data" or "fine-tune")
+    pub purpose: Option<String>,
+}
+
+impl FileUpload {
+    pub fn new(filename: impl Into<String>, contents: Vec<u8>) -> Self {
+        let filename = filename.into();
+        let media_type = media_type_for(&filename).to_string();
+        Self {
+            filename,
+            contents,
+            media_type,
+            purpose: None,
+        }
+    }
+
+    pub fn with_media_type(mut self, media_type: impl Into<String>) -> Self {
+        self.media_type = media_type.into();
+        self
+    }
+
+    pub fn with_purpose(mut self, purpose: impl Into<String>) -> Self {
+        self.purpose = Some(purpose.into());
+        self
+    }
+
+    /// `multipart/form-data` content type and body with a `file` part and,
+    /// if set, a `purpose` part
+    #[cfg_attr(not(feature = "providers"), allow(dead_code))]
+    pub(super) fn multipart(&self) -> (String, Vec<u8>) {
+        let boundary = format!("agent-sdk-{}", crate::ids::new_id());
+        (
+            format!("multipart/form-data; boundary={}", boundary),
+            self.multipart_body(&boundary),
+        )
+    }
+
+    #[cfg_attr(not(feature = "providers"), allow(dead_code))]
+    fn multipart_body(&self, boundary: &str) -> Vec<u8> {
+        let mut body = String::new();
+        if let Some(purpose) = &self.purpose {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\n{}\r\n",
+                boundary, purpose
+            ));
+        }
+        body.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            boundary,
+            self.filename.replace('"', ""),
+            self.media_type
+        ));
+        let mut body = body.into_bytes();
+        body.extend_from_slice(&self.contents);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        body
+    }
+}
+
+fn media_type_for(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "jsonl" => "application/jsonl",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// File stored by a provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderFile {
+    /// Id used to reference the file in messages
+    pub id: String,
+    pub filename: String,
+    /// Size in bytes
+    pub bytes: u64,
+    pub purpose: Option<String>,
+}
+
+/// Trait for providers that host files which messages can reference
+///
+/// Attach an uploaded file with `ContentBlock::File` or
+/// `Message::user_with_file` instead of inlining its content.
+pub trait FileProvider: Send + Sync {
+    fn upload_file(
+        &self,
+        upload: FileUpload,
+    ) -> Pin<Box<dyn Future<Output = Result<ProviderFile>> + Send + '_>>;
+
+    fn list_files(&self) -> Pin<Box<dyn Future<Output = Result<Vec<ProviderFile>>> + Send + '_>>;
+
+    fn delete_file<'a>(
+        &'a self,
+        file_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_upload_multipart() {
+        let upload = FileUpload::new("report.PDF", b"%PDF".to_vec()).with_purpose("user_data");
+        assert_eq!(upload.media_type, "application/pdf");
+
+        let body = String::from_utf8(upload.multipart_body("xyz")).unwrap();
+        assert!(body.starts_with(
+            "--xyz\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nuser_data\r\n"
+        ));
+        assert!(body.contains("filename=\"report.PDF\"\r\nContent-Type: application/pdf\r\n"));
+        assert!(body.ends_with("\r\n\r\n%PDF\r\n--xyz--\r\n"));
+
+        let upload = FileUpload::new("notes", Vec::new());
+        assert_eq!(upload.media_type, "application/octet-stream");
+        let body = String::from_utf8(upload.multipart_body("xyz")).unwrap();
+        assert!(!body.contains("purpose"));
+    }
+}
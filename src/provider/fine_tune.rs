@@ -0,0 +1,253 @@
+use super::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// File uploaded for training
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrainingFile {
+    pub id: String,
+    pub filename: String,
+    /// Size in bytes
+    pub bytes: u64,
+}
+
+/// Request for starting a fine-tuning job
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CreateFineTuneRequest {
+    /// Base model to fine-tune
+    pub model: String,
+    /// Id of an uploaded training file
+    pub training_file: String,
+    pub validation_file: Option<String>,
+    /// Added to the name of the fine-tuned model
+    pub suffix: Option<String>,
+    pub n_epochs: Option<u32>,
+    pub seed: Option<u64>,
+}
+
+impl CreateFineTuneRequest {
+    pub fn new(model: impl Into<String>, training_file: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            training_file: training_file.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_validation_file(mut self, file_id: impl Into<String>) -> Self {
+        self.validation_file = Some(file_id.into());
+        self
+    }
+
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    pub fn with_epochs(mut self, n_epochs: u32) -> Self {
+        self.n_epochs = Some(n_epochs);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// State of a fine-tuning job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FineTuneStatus {
+    ValidatingFiles,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// A status this SDK does not know about
+    Other(String),
+}
+
+impl FineTuneStatus {
+    /// Whether the job has stopped and will not change again
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Fine-tuning job as last reported by the provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct FineTuneJob {
+    pub id: String,
+    /// Base model
+    pub model: String,
+    pub status: FineTuneStatus,
+    /// Name of the resulting model, once the job has succeeded
+    pub fine_tuned_model: Option<String>,
+    pub trained_tokens: Option<u64>,
+    /// Why the job failed
+    pub error: Option<String>,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+}
+
+/// Model snapshot saved during training
+#[derive(Debug, Clone, PartialEq)]
+pub struct FineTuneCheckpoint {
+    pub id: String,
+    /// Name to serve the checkpoint under
+    pub model: String,
+    pub step: u64,
+    /// Training metrics at this step, e.g. loss
+    pub metrics: serde_json::Value,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+}
+
+/// Trait for providers that can fine-tune models
+pub trait FineTuneProvider: Send + Sync {
+    /// Upload a JSONL file of training examples
+    fn upload_training_file(
+        &self,
+        filename: String,
+        contents: Vec<u8>,
+    ) -> Pin<Box<dyn Future<Output = Result<TrainingFile>> + Send + '_>>;
+
+    /// Start a job
+    fn create_fine_tune(
+        &self,
+        request: CreateFineTuneRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + '_>>;
+
+    /// Current state of a job
+    fn get_fine_tune<'a>(
+        &'a self,
+        job_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>>;
+
+    /// Checkpoints saved so far, newest first
+    fn list_checkpoints<'a>(
+        &'a self,
+        job_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<FineTuneCheckpoint>>> + Send + 'a>>;
+
+    /// Stop a job that has not finished
+    fn cancel_fine_tune<'a>(
+        &'a self,
+        job_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>>;
+
+    /// Poll a job every `interval` until it reaches a terminal status
+    fn wait_for_fine_tune<'a>(
+        &'a self,
+        job_id: &'a str,
+        interval: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>> {
+        Box::pin(async move {
+            loop {
+                let job = self.get_fine_tune(job_id).await?;
+                if job.status.is_terminal() {
+                    return Ok(job);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ProviderError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Reports the job as running until the third poll
+    struct CountingProvider {
+        polls: AtomicUsize,
+    }
+
+    fn job(status: FineTuneStatus) -> FineTuneJob {
+        FineTuneJob {
+            id: "ftjob-1".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            status,
+            fine_tuned_model: None,
+            trained_tokens: None,
+            error: None,
+            created_at: 0,
+        }
+    }
+
+    impl FineTuneProvider for CountingProvider {
+        fn upload_training_file(
+            &self,
+            _filename: String,
+            _contents: Vec<u8>,
+        ) -> Pin<Box<dyn Future<Output = Result<TrainingFile>> + Send + '_>> {
+            Box::pin(async { Err(ProviderError::Other("not used".into())) })
+        }
+
+        fn create_fine_tune(
+            &self,
+            _request: CreateFineTuneRequest,
+        ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + '_>> {
+            Box::pin(async { Err(ProviderError::Other("not used".into())) })
+        }
+
+        fn get_fine_tune<'a>(
+            &'a self,
+            _job_id: &'a str,
+        ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>> {
+            Box::pin(async move {
+                let polls = self.polls.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(job(if polls < 3 {
+                    FineTuneStatus::Running
+                } else {
+                    FineTuneStatus::Succeeded
+                }))
+            })
+        }
+
+        fn list_checkpoints<'a>(
+            &'a self,
+            _job_id: &'a str,
+        ) -> Pin<Box<dyn Future<Output = Result<Vec<FineTuneCheckpoint>>> + Send + 'a>> {
+            Box::pin(async { Err(ProviderError::Other("not used".into())) })
+        }
+
+        fn cancel_fine_tune<'a>(
+            &'a self,
+            _job_id: &'a str,
+        ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>> {
+            Box::pin(async { Err(ProviderError::Other("not used".into())) })
+        }
+    }
+
+    #[test]
+    fn test_create_fine_tune_request_builder() {
+        let request = CreateFineTuneRequest::new("gpt-4o-mini", "file-abc")
+            .with_suffix("support")
+            .with_epochs(3);
+
+        assert_eq!(request.model, "gpt-4o-mini");
+        assert_eq!(request.training_file, "file-abc");
+        assert_eq!(request.suffix, Some("support".to_string()));
+        assert_eq!(request.n_epochs, Some(3));
+        assert_eq!(request.validation_file, None);
+    }
+
+    #[tokio::test]
+    async fn test_wait_polls_until_terminal() {
+        let provider = CountingProvider {
+            polls: AtomicUsize::new(0),
+        };
+        let job = provider
+            .wait_for_fine_tune("ftjob-1", Duration::from_millis(1))
+            .await
+            .unwrap();
+
+        assert_eq!(job.status, FineTuneStatus::Succeeded);
+        assert_eq!(provider.polls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,85 @@
+use super::openai_compat::{
+    delegate_llm_provider, OpenAiCompatProvider, OpenAiCompatProviderBuilder,
+};
+use super::{ProviderCapabilities, ProviderError, Result};
+
+/// Groq Provider 实现（OpenAI-compatible endpoint）
+pub struct GroqProvider {
+    inner: OpenAiCompatProvider,
+}
+
+/// Builder for `GroqProvider`
+pub type GroqProviderBuilder = OpenAiCompatProviderBuilder<GroqProvider>;
+
+impl GroqProvider {
+    /// Create a new Groq provider with default configuration
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Result<Self> {
+        Self::builder().api_key(api_key).model(model).build()
+    }
+
+    /// Create a builder for configuring the Groq provider
+    pub fn builder() -> GroqProviderBuilder {
+        GroqProviderBuilder::default()
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.inner = self.inner.with_base_url(url);
+        self
+    }
+}
+
+/// Groq hosts models from several vendors, so only reject ids that cannot
+/// name any model
+fn validate_model(model: &str) -> Result<()> {
+    if model.trim().is_empty() || model.chars().any(char::is_whitespace) {
+        return Err(ProviderError::ModelNotAvailable(format!(
+            "'{}' is not a Groq model id",
+            model
+        )));
+    }
+    Ok(())
+}
+
+impl Default for GroqProviderBuilder {
+    fn default() -> Self {
+        Self::vendor("groq", "https://api.groq.com/openai/v1")
+            .model_validator(validate_model)
+            .capabilities(ProviderCapabilities {
+                streaming: true,
+                native_tools: true,
+                structured_output: true,
+                tool_choice: true,
+                ..Default::default()
+            })
+    }
+}
+
+delegate_llm_provider!(GroqProvider);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::openai_compat::parse_stream_chunk;
+    use crate::provider::{LlmProvider, StreamEvent};
+
+    #[test]
+    fn presets_groq_endpoint() {
+        let provider = GroqProvider::new("test-key", "llama-3.1-8b-instant").unwrap();
+        assert_eq!(provider.name(), "groq");
+        assert_eq!(provider.inner.base_url, "https://api.groq.com/openai/v1");
+        assert!(provider.capabilities().native_tools);
+        assert!(GroqProvider::new("test-key", " ").is_err());
+    }
+
+    #[test]
+    fn reads_usage_from_x_groq_extension() {
+        let chunk = serde_json::json!({
+            "choices": [{"delta": {}, "finish_reason": "stop"}],
+            "x_groq": {"usage": {"prompt_tokens": 4, "completion_tokens": 2, "total_tokens": 6}}
+        });
+        let events = parse_stream_chunk(&chunk);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, StreamEvent::UsageUpdate(u) if u.total_tokens == 6)));
+    }
+}
@@ -0,0 +1,121 @@
+use super::{ImageSource, Result};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Dimensions of a generated image in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageSize {
+    pub const SQUARE: Self = Self::new(1024, 1024);
+
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+}
+
+impl std::fmt::Display for ImageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Rendering quality; higher is slower and more expensive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageQuality {
+    Low,
+    Medium,
+    High,
+}
+
+/// Request for generating images
+#[derive(Debug, Clone)]
+pub struct ImageRequest {
+    pub prompt: String,
+    /// Optional model to use (provider-specific)
+    pub model: Option<String>,
+    pub size: Option<ImageSize>,
+    pub quality: Option<ImageQuality>,
+    /// Number of images
+    pub n: u32,
+}
+
+impl ImageRequest {
+    /// Create a request for one image
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            model: None,
+            size: None,
+            quality: None,
+            n: 1,
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn with_size(mut self, size: ImageSize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_quality(mut self, quality: ImageQuality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    pub fn with_count(mut self, n: u32) -> Self {
+        self.n = n;
+        self
+    }
+}
+
+/// One generated image
+#[derive(Debug, Clone)]
+pub struct GeneratedImage {
+    /// Hosted URL or inline base64 data
+    pub source: ImageSource,
+    /// Prompt the provider actually used, if it rewrote it
+    pub revised_prompt: Option<String>,
+}
+
+/// Response from generating images
+#[derive(Debug, Clone)]
+pub struct ImageResponse {
+    pub images: Vec<GeneratedImage>,
+    /// The model used
+    pub model: String,
+}
+
+/// Trait for providers that can generate images from a prompt
+pub trait ImageGenerationProvider: Send + Sync {
+    fn generate_images(
+        &self,
+        request: ImageRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ImageResponse>> + Send + '_>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_request_builder() {
+        let request = ImageRequest::new("a lighthouse at dusk")
+            .with_size(ImageSize::new(1536, 1024))
+            .with_quality(ImageQuality::High)
+            .with_count(2);
+
+        assert_eq!(request.prompt, "a lighthouse at dusk");
+        assert_eq!(request.size.unwrap().to_string(), "1536x1024");
+        assert_eq!(request.quality, Some(ImageQuality::High));
+        assert_eq!(request.n, 2);
+        assert_eq!(request.model, None);
+    }
+}
@@ -55,6 +55,7 @@ impl MiddlewareChain {
     }
 
     /// Add a middleware to the chain
+    #[allow(clippy::should_implement_trait)]
     pub fn add(mut self, middleware: Arc<dyn Middleware>) -> Self {
         self.middlewares.push(middleware);
         self
@@ -334,6 +335,7 @@ mod tests {
                 }),
                 model: "test".to_string(),
                 finish_reason: None,
+                tool_calls: Vec::new(),
             },
             metadata: HashMap::new(),
         };
@@ -0,0 +1,102 @@
+use super::openai_compat::{
+    delegate_llm_provider, OpenAiCompatProvider, OpenAiCompatProviderBuilder,
+};
+use super::{ProviderCapabilities, ProviderError, Result};
+
+/// Mistral Provider 实现（La Plateforme chat completions API）
+pub struct MistralProvider {
+    inner: OpenAiCompatProvider,
+}
+
+/// Builder for `MistralProvider`
+pub type MistralProviderBuilder = OpenAiCompatProviderBuilder<MistralProvider>;
+
+impl MistralProvider {
+    /// Create a new Mistral provider with default configuration
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Result<Self> {
+        Self::builder().api_key(api_key).model(model).build()
+    }
+
+    /// Create a builder for configuring the Mistral provider
+    pub fn builder() -> MistralProviderBuilder {
+        MistralProviderBuilder::default()
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.inner = self.inner.with_base_url(url);
+        self
+    }
+}
+
+/// Mistral model ids are bare (`mistral-large-latest`); a routed id such as
+/// `mistralai/mistral-large` belongs to OpenRouter and would 404 here
+fn validate_model(model: &str) -> Result<()> {
+    if model.trim().is_empty() || model.contains('/') {
+        return Err(ProviderError::ModelNotAvailable(format!(
+            "'{}' is not a Mistral model id",
+            model
+        )));
+    }
+    Ok(())
+}
+
+/// Mistral always reports usage on the final chunk and rejects the
+/// `stream_options` field
+impl Default for MistralProviderBuilder {
+    fn default() -> Self {
+        Self::vendor("mistral", "https://api.mistral.ai/v1")
+            .stream_usage(false)
+            .model_validator(validate_model)
+            .capabilities(ProviderCapabilities {
+                streaming: true,
+                native_tools: true,
+                structured_output: true,
+                tool_choice: true,
+                ..Default::default()
+            })
+    }
+}
+
+delegate_llm_provider!(MistralProvider);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{GenerateOptions, LlmProvider, Message};
+
+    #[test]
+    fn presets_mistral_endpoint_without_stream_options() {
+        let provider = MistralProvider::new("test-key", "mistral-small-latest").unwrap();
+        assert_eq!(provider.name(), "mistral");
+        assert_eq!(provider.inner.base_url, "https://api.mistral.ai/v1");
+
+        let body = provider
+            .inner
+            .build_request_body(vec![Message::user("hi")], None, true);
+        assert_eq!(body["model"], "mistral-small-latest");
+        assert!(body.get("stream_options").is_none());
+    }
+
+    #[test]
+    fn rejects_routed_model_ids() {
+        assert!(MistralProvider::new("test-key", "mistralai/mistral-large").is_err());
+
+        let provider = MistralProvider::new("test-key", "mistral-small-latest").unwrap();
+        let options = GenerateOptions {
+            model: Some("openai/gpt-4o".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            provider.inner.check_supported(&[], &Some(options)),
+            Err(ProviderError::ModelNotAvailable(_))
+        ));
+    }
+
+    #[test]
+    fn requires_api_key() {
+        assert!(MistralProvider::builder()
+            .model("mistral-small-latest")
+            .build()
+            .is_err());
+    }
+}
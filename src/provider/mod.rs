@@ -1,31 +1,114 @@
+#[cfg(feature = "providers")]
 mod anthropic;
+#[cfg(feature = "providers")]
 mod open_router;
+#[cfg(feature = "providers")]
+mod openai;
+#[cfg(feature = "providers")]
+mod mistral;
+#[cfg(feature = "providers")]
+mod groq;
+#[cfg(feature = "providers")]
+mod ollama;
+#[cfg(feature = "bedrock")]
+mod bedrock;
+#[cfg(feature = "bedrock")]
+mod sigv4;
+#[cfg(feature = "providers")]
+mod openai_compat;
+#[cfg(feature = "providers")]
 mod client;
+#[cfg(feature = "providers")]
+mod pipeline;
 mod retry;
 mod rate_limit;
 mod timeout;
 mod middleware;
 mod context;
 mod cache;
+mod deferred;
 mod embeddings;
+mod files;
+mod fine_tune;
+mod images;
 mod batch;
+#[cfg(feature = "providers")]
+mod registry;
+mod fallback;
+mod pool;
+mod fair_queue;
+mod structured;
+mod multiplex;
+mod tokenizer;
+mod stream_decode;
+pub mod extensions;
+mod recording;
 
+#[cfg(feature = "providers")]
 #[allow(unused_imports)]
 pub use anthropic::AnthropicProvider;
-pub use open_router::OpenRouterProvider;
-pub use client::{ProviderClient, ProviderClientBuilder};
+#[cfg(feature = "providers")]
+pub use open_router::{OpenRouterProvider, OpenRouterProviderBuilder, OpenRouterRouting};
+#[cfg(feature = "providers")]
+pub use openai::{OpenAIProvider, OpenAIProviderBuilder};
+#[cfg(feature = "providers")]
+pub use mistral::{MistralProvider, MistralProviderBuilder};
+#[cfg(feature = "providers")]
+pub use groq::{GroqProvider, GroqProviderBuilder};
+#[cfg(feature = "providers")]
+pub use openai_compat::{
+    parse_chat_completion_chunk, AuthHeader, OpenAiCompatProvider, OpenAiCompatProviderBuilder,
+};
+#[cfg(feature = "providers")]
+pub use ollama::OllamaProvider;
+#[cfg(feature = "bedrock")]
+pub use bedrock::{BedrockModelFamily, BedrockProvider};
+#[cfg(feature = "bedrock")]
+pub use sigv4::AwsCredentials;
+#[cfg(feature = "providers")]
+pub use client::{KeepAliveConfig, ProviderClient, ProviderClientBuilder, WaitEstimate};
+#[cfg(feature = "providers")]
+pub use registry::{ProviderConfig, ProviderFactory, ProviderRegistry};
+pub use fallback::{FallbackObserver, FallbackProvider, FallbackProviderBuilder, FallbackRecord};
+pub use fair_queue::{FairShareHandle, FairShareProvider};
+pub use crate::schema::validate as validate_json_schema;
+pub use structured::{JsonSchema, ResponseFormat, STRUCTURED_REPAIR_ATTEMPTS};
+pub(crate) use structured::parse_with_repair;
+pub use multiplex::{MultiplexedReceiver, MuxPayload, StreamMultiplexer, TaggedStreamEvent};
+pub use pool::{
+    LoadBalanceStrategy, PoolMemberStats, ProviderPool, ProviderPoolBuilder, ProviderSwitch,
+};
 pub use retry::{RetryConfig, RetryPolicy};
-pub use rate_limit::{RateLimitConfig, RateLimiter, RateLimitGuard, RateLimitStats};
-pub use timeout::TimeoutConfig;
+pub use rate_limit::{RateLimitConfig, RateLimiter, RateLimitGuard, RateLimitKind, RateLimitStats, RateLimitWait};
+pub use timeout::{StreamTimeoutKind, TimeoutConfig};
+pub use stream_decode::{sse_data, LineDecoder};
+pub use extensions::{ExtensionKey, Extensions};
+pub use recording::{RecordedExchange, RecordingProvider, Redactor, ReplayProvider};
 pub use middleware::{
     Middleware, MiddlewareChain, RequestContext, ResponseContext,
     LoggingMiddleware, TokenCounterMiddleware, MetricsMiddleware,
 };
 pub use context::{ContextWindowConfig, ContextWindowManager, TruncationStrategy};
+pub(crate) use context::{transcript, SUMMARY_PROMPT};
+pub use tokenizer::{
+    context_window_for_model, tokenizer_for_model, BillingProfile, CharEstimateTokenizer,
+    ImageBilling, Tokenizer,
+};
+#[cfg(feature = "tiktoken")]
+pub use tokenizer::TiktokenTokenizer;
 pub use cache::{CacheConfig, CacheKey, ResponseCache, CacheStats};
 pub use embeddings::{
     EmbeddingProvider, EmbeddingRequest, EmbeddingResponse, EmbeddingUsage, EncodingFormat,
 };
+pub use deferred::{spawn_completion, DeferredProvider, JobHandle, JobStatus};
+pub use files::{FileProvider, FileUpload, ProviderFile};
+pub use fine_tune::{
+    CreateFineTuneRequest, FineTuneCheckpoint, FineTuneJob, FineTuneProvider, FineTuneStatus,
+    TrainingFile,
+};
+pub use images::{
+    GeneratedImage, ImageGenerationProvider, ImageQuality, ImageRequest, ImageResponse, ImageSize,
+};
 pub use batch::{
     BatchProvider, BatchRequest, BatchResponse, SingleRequest, SingleResponse,
     execute_batch_concurrent, execute_batch_sequential,
@@ -33,18 +116,20 @@ pub use batch::{
 
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
 /// 消息角色
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Role {
     System,
     User,
     Assistant,
 }
 
-/// Content block in a message (text or image)
+/// Content block in a message (text, image, tool call or tool result)
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentBlock {
     Text { text: String },
     Image {
@@ -52,6 +137,54 @@ pub enum ContentBlock {
         #[serde(skip_serializing_if = "Option::is_none")]
         detail: Option<ImageDetail>,
     },
+    /// Tool call requested by the assistant
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// File uploaded through a `FileProvider`, referenced by id
+    File { file_id: String },
+    /// Result of a tool call, sent back to the model
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        #[serde(default)]
+        is_error: bool,
+        /// Images returned with the result, e.g. screenshots
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        images: Vec<ImageSource>,
+    },
+}
+
+impl ContentBlock {
+    /// Create a tool result block for the given tool call id
+    pub fn tool_result(
+        tool_use_id: impl Into<String>,
+        content: impl Into<String>,
+        is_error: bool,
+    ) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content: content.into(),
+            is_error,
+            images: Vec::new(),
+        }
+    }
+
+    /// Successful tool result block that also carries images
+    pub fn tool_result_with_images(
+        tool_use_id: impl Into<String>,
+        content: impl Into<String>,
+        images: Vec<ImageSource>,
+    ) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content: content.into(),
+            is_error: false,
+            images,
+        }
+    }
 }
 
 /// Source of an image
@@ -75,10 +208,13 @@ pub enum ImageDetail {
 }
 
 /// 聊天消息
-#[derive(Debug, Clone)]
+///
+/// Content is shared between clones, so copying a conversation for a
+/// request, a cache key or a forked agent does not copy message bodies.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Message {
     pub role: Role,
-    pub content: Vec<ContentBlock>,
+    pub content: Arc<[ContentBlock]>,
 }
 
 impl Message {
@@ -87,7 +223,8 @@ impl Message {
             role: Role::System,
             content: vec![ContentBlock::Text {
                 text: content.into(),
-            }],
+            }]
+            .into(),
         }
     }
 
@@ -100,7 +237,8 @@ impl Message {
             role: Role::User,
             content: vec![ContentBlock::Text {
                 text: content.into(),
-            }],
+            }]
+            .into(),
         }
     }
 
@@ -113,7 +251,8 @@ impl Message {
                     source: image,
                     detail: None,
                 },
-            ],
+            ]
+            .into(),
         }
     }
 
@@ -135,12 +274,56 @@ impl Message {
         )
     }
 
+    /// User message attaching a file uploaded through a `FileProvider`
+    pub fn user_with_file(text: impl Into<String>, file_id: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: vec![
+                ContentBlock::Text { text: text.into() },
+                ContentBlock::File {
+                    file_id: file_id.into(),
+                },
+            ]
+            .into(),
+        }
+    }
+
     pub fn assistant(content: impl Into<String>) -> Self {
         Self {
             role: Role::Assistant,
             content: vec![ContentBlock::Text {
                 text: content.into(),
-            }],
+            }]
+            .into(),
+        }
+    }
+
+    /// Assistant message carrying native tool calls
+    pub fn assistant_with_tool_calls(
+        content: impl Into<String>,
+        calls: &[crate::tool::ToolCall],
+    ) -> Self {
+        let content = content.into();
+        let mut blocks = Vec::new();
+        if !content.is_empty() {
+            blocks.push(ContentBlock::Text { text: content });
+        }
+        blocks.extend(calls.iter().map(|call| ContentBlock::ToolUse {
+            id: call.id.clone(),
+            name: call.name.clone(),
+            input: call.parameters.clone(),
+        }));
+        Self {
+            role: Role::Assistant,
+            content: blocks.into(),
+        }
+    }
+
+    /// User message carrying tool result blocks
+    pub fn tool_results(results: Vec<ContentBlock>) -> Self {
+        Self {
+            role: Role::User,
+            content: results.into(),
         }
     }
 
@@ -156,6 +339,21 @@ impl Message {
             .join("\n")
     }
 
+    /// Get the tool calls carried by this message
+    pub fn tool_calls(&self) -> Vec<crate::tool::ToolCall> {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, input } => Some(crate::tool::ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    parameters: input.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Check if this message contains any images
     pub fn has_images(&self) -> bool {
         self.content.iter().any(|block| matches!(block, ContentBlock::Image { .. }))
@@ -165,14 +363,87 @@ impl Message {
 /// 生成参数配置
 #[derive(Debug, Clone, Default)]
 pub struct GenerateOptions {
+    /// Use this model for the request instead of the provider's default
+    pub model: Option<String>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
     pub top_p: Option<f32>,
     pub stop: Option<Vec<String>>,
+    /// Partial assistant message the model must continue (prefill).
+    ///
+    /// The prefix is sent as a trailing assistant turn and is prepended to the
+    /// returned content, so callers always receive the complete message.
+    pub assistant_prefix: Option<String>,
+    /// Grammar or regex constraint for backends that support guided decoding
+    pub constraint: Option<DecodingConstraint>,
+    /// JSON output format, for providers with `structured_output`
+    pub response_format: Option<ResponseFormat>,
+    /// Force a tool call, for providers with `tool_choice`; ignored without tools
+    pub tool_choice: Option<ToolSelection>,
+    /// `Some(false)` asks for at most one tool call per response, where supported
+    pub parallel_tool_calls: Option<bool>,
+    /// Top-level request body fields for OpenAI-compatible APIs, e.g. vendor
+    /// routing preferences; they override the provider's own body fields
+    pub extra_body: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Provider-specific parameters such as `top_k`, plain or prefixed with
+    /// a provider name; see `extensions`
+    pub extensions: Extensions,
+}
+
+/// Tool call the model is forced to make
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ToolSelection {
+    /// Any of the offered tools
+    Any,
+    /// The named tool
+    Tool(String),
+}
+
+/// Constraint applied to decoding by backends such as llama.cpp or vLLM
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DecodingConstraint {
+    /// GBNF grammar the output must match
+    Grammar(String),
+    /// Regular expression the output must match
+    Regex(String),
+}
+
+/// Features a provider supports, for callers that need to pick one at runtime
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderCapabilities {
+    /// Supports `generate_stream`
+    pub streaming: bool,
+    /// Honours `GenerateOptions::assistant_prefix`
+    pub assistant_prefill: bool,
+    /// Accepts `DecodingConstraint::Grammar`
+    pub grammar: bool,
+    /// Accepts `DecodingConstraint::Regex`
+    pub regex: bool,
+    /// Implements `generate_with_tools` using the provider's native tool API
+    pub native_tools: bool,
+    /// Honours `GenerateOptions::response_format`
+    pub structured_output: bool,
+    /// Honours `GenerateOptions::tool_choice`
+    pub tool_choice: bool,
+}
+
+impl ProviderCapabilities {
+    /// Features supported by both, for providers that wrap several others
+    pub fn intersect(&self, other: &Self) -> Self {
+        Self {
+            streaming: self.streaming && other.streaming,
+            assistant_prefill: self.assistant_prefill && other.assistant_prefill,
+            grammar: self.grammar && other.grammar,
+            regex: self.regex && other.regex,
+            native_tools: self.native_tools && other.native_tools,
+            structured_output: self.structured_output && other.structured_output,
+            tool_choice: self.tool_choice && other.tool_choice,
+        }
+    }
 }
 
 /// Token 使用统计
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
@@ -180,12 +451,28 @@ pub struct Usage {
 }
 
 /// 生成响应
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GenerateResponse {
     pub content: String,
     pub usage: Option<Usage>,
     pub model: String,
     pub finish_reason: Option<String>,
+    /// Tool calls returned by native tool calling (empty for plain generation)
+    #[serde(default)]
+    pub tool_calls: Vec<crate::tool::ToolCall>,
+}
+
+/// Tool definition sent to providers that support native tool calling
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ToolSchema {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the tool parameters
+    pub parameters: serde_json::Value,
+    /// Provider-defined tool, e.g. Anthropic's `computer_20250124`, sent in
+    /// place of the schema by providers that support it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub native: Option<serde_json::Value>,
 }
 
 /// Provider 错误类型
@@ -201,6 +488,8 @@ pub enum ProviderError {
     ModelNotAvailable(String),
     /// 响应解析失败
     ParseError(String),
+    /// 流式响应超时
+    StreamTimeout { kind: StreamTimeoutKind, after: std::time::Duration },
     /// 其他错误
     Other(String),
 }
@@ -219,6 +508,12 @@ impl std::fmt::Display for ProviderError {
             }
             Self::ModelNotAvailable(model) => write!(f, "Model not available: {}", model),
             Self::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            Self::StreamTimeout { kind: StreamTimeoutKind::Idle, after } => {
+                write!(f, "Stream timed out: no data for {:?}", after)
+            }
+            Self::StreamTimeout { kind: StreamTimeoutKind::Total, after } => {
+                write!(f, "Stream timed out after {:?}", after)
+            }
             Self::Other(msg) => write!(f, "{}", msg),
         }
     }
@@ -245,6 +540,16 @@ pub trait LlmProvider: Send + Sync {
         options: Option<GenerateOptions>,
     ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>>;
 
+    /// 使用原生工具调用生成响应（可选实现）
+    fn generate_with_tools(
+        &self,
+        _messages: Vec<Message>,
+        _tools: Vec<ToolSchema>,
+        _options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(async { Err(ProviderError::Other("Native tool calling not supported".into())) })
+    }
+
     /// 流式生成（可选实现）
     fn generate_stream(
         &self,
@@ -254,13 +559,373 @@ pub trait LlmProvider: Send + Sync {
         Box::pin(async { Err(ProviderError::Other("Streaming not supported".into())) })
     }
 
+    /// 带类型事件的流式生成，可观察工具调用增量与用量
+    ///
+    /// 默认实现基于 `generate_stream`，只产生文本增量和结束事件。
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            if !tools.is_empty() {
+                return Err(ProviderError::Other(
+                    "Streaming with tools not supported".into(),
+                ));
+            }
+            let mut text = self.generate_stream(messages, options).await?;
+            Ok(StreamEvents::spawn(100, |tx| async move {
+                while let Some(chunk) = text.receiver.recv().await {
+                    if tx.send(chunk.map(StreamEvent::TextDelta)).await.is_err() {
+                        return;
+                    }
+                }
+                let _ = tx.send(Ok(StreamEvent::Done { finish_reason: None })).await;
+            }))
+        })
+    }
+
+    /// 返回 provider 支持的能力
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    /// Context window of the model in tokens, if known
+    fn context_window(&self) -> Option<usize> {
+        context_window_for_model(self.model())
+    }
+
     /// 检查 provider 是否可用
     fn health_check(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
         Box::pin(async { Ok(()) })
     }
+
+    /// Resolve DNS and open connections to the backend ahead of the first
+    /// request; a no-op for providers without one
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Cut the latency of the first request: `preconnect`, then with `ping`
+    /// send a one-token request so the model is loaded as well
+    fn warm_up(&self, ping: bool) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            self.preconnect().await?;
+            if ping {
+                let options = GenerateOptions {
+                    max_tokens: Some(1),
+                    ..Default::default()
+                };
+                self.generate(vec![Message::user("ping")], Some(options))
+                    .await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Client-side rate limiter, so callers can observe throttling waits
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        None
+    }
+
+    /// Switches between backends for a pinned conversation, for providers
+    /// that route across several
+    fn provider_switches(&self) -> Option<tokio::sync::broadcast::Receiver<ProviderSwitch>> {
+        None
+    }
+
+    /// 生成结构化输出并反序列化为 `T`
+    ///
+    /// Sends `schema` as the native response format when the provider
+    /// supports it and as an instruction otherwise. Replies that do not match
+    /// the schema are sent back with a repair prompt, up to
+    /// `STRUCTURED_REPAIR_ATTEMPTS` times.
+    fn generate_structured<'a, T>(
+        &'a self,
+        messages: Vec<Message>,
+        schema: &'a JsonSchema,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>
+    where
+        Self: Sized,
+        T: serde::de::DeserializeOwned + 'a,
+    {
+        Box::pin(structured::generate_structured(self, messages, schema, options))
+    }
+}
+
+/// Lets `Agent` and other generic code use a provider chosen at runtime,
+/// e.g. one built by `ProviderRegistry`
+impl<T: LlmProvider + ?Sized> LlmProvider for Box<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn model(&self) -> &str {
+        (**self).model()
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        (**self).generate(messages, options)
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        (**self).generate_with_tools(messages, tools, options)
+    }
+
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamResponse>> + Send + '_>> {
+        (**self).generate_stream(messages, options)
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        (**self).generate_stream_events(messages, tools, options)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        (**self).capabilities()
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        (**self).context_window()
+    }
+
+    fn health_check(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        (**self).health_check()
+    }
+
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        (**self).preconnect()
+    }
+
+    fn warm_up(&self, ping: bool) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        (**self).warm_up(ping)
+    }
+
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        (**self).rate_limiter()
+    }
+
+    fn provider_switches(&self) -> Option<tokio::sync::broadcast::Receiver<ProviderSwitch>> {
+        (**self).provider_switches()
+    }
+}
+
+/// Run `produce` on a task feeding the returned receiver
+fn spawn_stream_task<T, F, Fut>(
+    buffer: usize,
+    produce: F,
+) -> (tokio::sync::mpsc::Receiver<T>, tokio::task::AbortHandle)
+where
+    F: FnOnce(tokio::sync::mpsc::Sender<T>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(buffer);
+    let handle = tokio::spawn(produce(tx));
+    (rx, handle.abort_handle())
 }
 
 /// 流式响应（简化版）
+///
+/// A stream made with `spawn` aborts its producing task when dropped, so a
+/// consumer that stops early does not leave the request running.
 pub struct StreamResponse {
     pub receiver: tokio::sync::mpsc::Receiver<Result<String>>,
+    task: Option<tokio::task::AbortHandle>,
+}
+
+impl StreamResponse {
+    /// Stream fed by the caller through `receiver`'s sender
+    pub fn new(receiver: tokio::sync::mpsc::Receiver<Result<String>>) -> Self {
+        Self {
+            receiver,
+            task: None,
+        }
+    }
+
+    /// Stream fed by `produce` on a task that is aborted when the stream is
+    /// dropped
+    pub fn spawn<F, Fut>(buffer: usize, produce: F) -> Self
+    where
+        F: FnOnce(tokio::sync::mpsc::Sender<Result<String>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (receiver, task) = spawn_stream_task(buffer, produce);
+        Self {
+            receiver,
+            task: Some(task),
+        }
+    }
+
+    /// Keep only the text deltas of a typed event stream
+    pub fn from_events(mut events: StreamEvents) -> Self {
+        Self::spawn(100, |tx| async move {
+            while let Some(event) = events.receiver.recv().await {
+                let chunk = match event {
+                    Ok(StreamEvent::TextDelta(text)) => Ok(text),
+                    Ok(_) => continue,
+                    Err(e) => Err(e),
+                };
+                if tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// End the stream with `AgentError::Cancelled`'s message once `token`
+    /// is cancelled, aborting the task producing it
+    pub fn cancel_on(mut self, token: tokio_util::sync::CancellationToken) -> Self {
+        Self::spawn(16, |tx| async move {
+            loop {
+                let chunk = tokio::select! {
+                    biased;
+                    _ = token.cancelled() => break,
+                    chunk = self.receiver.recv() => chunk,
+                };
+                let Some(chunk) = chunk else { return };
+                if tx.send(chunk).await.is_err() {
+                    return;
+                }
+            }
+            drop(self);
+            let error = ProviderError::Other(crate::AgentError::Cancelled.to_string());
+            let _ = tx.send(Err(error)).await;
+        })
+    }
+}
+
+impl Drop for StreamResponse {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Typed event produced while streaming a response
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// Incremental text output
+    TextDelta(String),
+    /// Incremental tool call; `id` and `name` arrive with the first delta of a call
+    ToolCallDelta {
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments_delta: String,
+    },
+    /// Token usage reported by the provider
+    UsageUpdate(Usage),
+    /// The stream finished
+    Done { finish_reason: Option<String> },
+}
+
+/// 类型化的流式响应
+///
+/// Like `StreamResponse`, a stream made with `spawn` aborts its producing
+/// task when dropped.
+pub struct StreamEvents {
+    pub receiver: tokio::sync::mpsc::Receiver<Result<StreamEvent>>,
+    task: Option<tokio::task::AbortHandle>,
+}
+
+impl StreamEvents {
+    /// Stream fed by the caller through `receiver`'s sender
+    pub fn new(receiver: tokio::sync::mpsc::Receiver<Result<StreamEvent>>) -> Self {
+        Self {
+            receiver,
+            task: None,
+        }
+    }
+
+    /// Stream fed by `produce` on a task that is aborted when the stream is
+    /// dropped
+    pub fn spawn<F, Fut>(buffer: usize, produce: F) -> Self
+    where
+        F: FnOnce(tokio::sync::mpsc::Sender<Result<StreamEvent>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (receiver, task) = spawn_stream_task(buffer, produce);
+        Self {
+            receiver,
+            task: Some(task),
+        }
+    }
+}
+
+impl Drop for StreamEvents {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    /// Stream whose producer sends `text` and then waits forever; `stopped`
+    /// resolves once the producer task is gone
+    fn stalled_events(text: &str) -> (StreamEvents, oneshot::Receiver<()>) {
+        let (alive, stopped) = oneshot::channel::<()>();
+        let text = text.to_string();
+        let events = StreamEvents::spawn(1, |tx| async move {
+            let _alive = alive;
+            let _ = tx.send(Ok(StreamEvent::TextDelta(text))).await;
+            std::future::pending::<()>().await;
+        });
+        (events, stopped)
+    }
+
+    async fn assert_stopped(stopped: oneshot::Receiver<()>) {
+        let result = tokio::time::timeout(Duration::from_secs(1), stopped)
+            .await
+            .expect("producer task leaked");
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn dropping_a_stream_aborts_the_tasks_feeding_it() {
+        let (events, stopped) = stalled_events("a");
+        let mut text = StreamResponse::from_events(events);
+        assert_eq!(text.receiver.recv().await.unwrap().unwrap(), "a");
+
+        drop(text);
+        assert_stopped(stopped).await;
+    }
+
+    #[tokio::test]
+    async fn cancellation_ends_the_stream_and_aborts_its_producer() {
+        let (events, stopped) = stalled_events("a");
+        let token = tokio_util::sync::CancellationToken::new();
+        let mut text = StreamResponse::from_events(events).cancel_on(token.clone());
+        assert_eq!(text.receiver.recv().await.unwrap().unwrap(), "a");
+
+        token.cancel();
+        assert!(text.receiver.recv().await.unwrap().is_err());
+        assert_stopped(stopped).await;
+        assert!(text.receiver.recv().await.is_none());
+    }
 }
@@ -0,0 +1,395 @@
+//! Fan many concurrent response streams into one tagged channel.
+//!
+//! A server that pushes output to UI sessions over a single connection (for
+//! example one WebSocket per user) attaches each agent run's stream to a
+//! `StreamMultiplexer` and forwards the interleaved `TaggedStreamEvent`s.
+//! With a time slice set, text deltas of a run are coalesced so a fast model
+//! produces one frame per slice instead of one per token.
+
+use super::{ProviderError, StreamEvent, StreamEvents, StreamResponse};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// What happened on a multiplexed stream
+#[derive(Debug, Clone)]
+pub enum MuxPayload {
+    Event(StreamEvent),
+    /// The source stream reported an error; more events may follow
+    Error(ProviderError),
+    /// The source stream closed; always the last payload of a run
+    End,
+}
+
+/// Stream event labelled with the run and agent it belongs to
+#[derive(Debug, Clone)]
+pub struct TaggedStreamEvent {
+    pub run_id: String,
+    pub agent_id: String,
+    /// Position within the run, starting at 0
+    pub seq: u64,
+    pub payload: MuxPayload,
+}
+
+impl TaggedStreamEvent {
+    /// JSON frame for a WebSocket or SSE transport
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut frame = match &self.payload {
+            MuxPayload::Event(StreamEvent::TextDelta(text)) => {
+                serde_json::json!({ "type": "text_delta", "text": text })
+            }
+            MuxPayload::Event(StreamEvent::ToolCallDelta {
+                index,
+                id,
+                name,
+                arguments_delta,
+            }) => serde_json::json!({
+                "type": "tool_call_delta",
+                "index": index,
+                "id": id,
+                "name": name,
+                "arguments_delta": arguments_delta,
+            }),
+            MuxPayload::Event(StreamEvent::UsageUpdate(usage)) => serde_json::json!({
+                "type": "usage",
+                "prompt_tokens": usage.prompt_tokens,
+                "completion_tokens": usage.completion_tokens,
+                "total_tokens": usage.total_tokens,
+            }),
+            MuxPayload::Event(StreamEvent::Done { finish_reason }) => {
+                serde_json::json!({ "type": "done", "finish_reason": finish_reason })
+            }
+            MuxPayload::Error(error) => {
+                serde_json::json!({ "type": "error", "message": error.to_string() })
+            }
+            MuxPayload::End => serde_json::json!({ "type": "end" }),
+        };
+        frame["run_id"] = serde_json::json!(self.run_id);
+        frame["agent_id"] = serde_json::json!(self.agent_id);
+        frame["seq"] = serde_json::json!(self.seq);
+        frame
+    }
+}
+
+/// Receiving side of a `StreamMultiplexer`
+pub struct MultiplexedReceiver {
+    receiver: mpsc::Receiver<TaggedStreamEvent>,
+}
+
+impl MultiplexedReceiver {
+    /// Next event from any attached run; `None` once every `StreamMultiplexer`
+    /// handle is dropped and all runs have ended
+    pub async fn recv(&mut self) -> Option<TaggedStreamEvent> {
+        self.receiver.recv().await
+    }
+}
+
+struct MuxInner {
+    sender: mpsc::Sender<TaggedStreamEvent>,
+    time_slice: Option<Duration>,
+    /// Forwarder per run id, with the attach generation that started it
+    runs: Mutex<HashMap<String, (u64, JoinHandle<()>)>>,
+    generation: std::sync::atomic::AtomicU64,
+}
+
+/// Tags streams with run and agent ids and merges them into one channel
+///
+/// Cloning shares the same output channel, so request handlers can attach
+/// runs independently.
+#[derive(Clone)]
+pub struct StreamMultiplexer {
+    inner: Arc<MuxInner>,
+}
+
+impl StreamMultiplexer {
+    /// Create a multiplexer whose output channel buffers `capacity` events
+    pub fn channel(capacity: usize) -> (Self, MultiplexedReceiver) {
+        Self::with_time_slice(capacity, None)
+    }
+
+    /// Like `channel`, coalescing each run's text deltas over `time_slice`
+    pub fn with_time_slice(
+        capacity: usize,
+        time_slice: Option<Duration>,
+    ) -> (Self, MultiplexedReceiver) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let mux = Self {
+            inner: Arc::new(MuxInner {
+                sender,
+                time_slice,
+                runs: Mutex::new(HashMap::new()),
+                generation: std::sync::atomic::AtomicU64::new(0),
+            }),
+        };
+        (mux, MultiplexedReceiver { receiver })
+    }
+
+    /// Forward a typed event stream under `run_id`
+    ///
+    /// Attaching a run id that is still active replaces the previous stream.
+    pub fn attach(
+        &self,
+        run_id: impl Into<String>,
+        agent_id: impl Into<String>,
+        events: StreamEvents,
+    ) {
+        self.spawn_forwarder(run_id.into(), agent_id.into(), events);
+    }
+
+    /// Forward a text stream, e.g. from `Agent::run_stream`, under `run_id`
+    pub fn attach_text(
+        &self,
+        run_id: impl Into<String>,
+        agent_id: impl Into<String>,
+        mut text: StreamResponse,
+    ) {
+        let events = StreamEvents::spawn(100, |tx| async move {
+            while let Some(chunk) = text.receiver.recv().await {
+                if tx.send(chunk.map(StreamEvent::TextDelta)).await.is_err() {
+                    return;
+                }
+            }
+        });
+        self.spawn_forwarder(run_id.into(), agent_id.into(), events);
+    }
+
+    /// Stop forwarding `run_id`; returns whether it was active
+    ///
+    /// The consumer does not receive `End` for a detached run.
+    pub fn detach(&self, run_id: &str) -> bool {
+        let handle = self.inner.runs.lock().unwrap().remove(run_id);
+        match handle {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ids of runs whose streams are still open, sorted
+    pub fn active_runs(&self) -> Vec<String> {
+        let mut runs: Vec<String> = self
+            .inner
+            .runs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (_, handle))| !handle.is_finished())
+            .map(|(id, _)| id.clone())
+            .collect();
+        runs.sort_unstable();
+        runs
+    }
+
+    fn spawn_forwarder(&self, run_id: String, agent_id: String, source: StreamEvents) {
+        let generation = self
+            .inner
+            .generation
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        // Hold the lock until the handle is stored so a forwarder that ends
+        // immediately still finds its own entry to remove
+        let mut runs = self.inner.runs.lock().unwrap();
+        let forwarder = Forwarder {
+            inner: Arc::downgrade(&self.inner),
+            generation,
+            sender: self.inner.sender.clone(),
+            time_slice: self.inner.time_slice,
+            run_id: run_id.clone(),
+            agent_id,
+            seq: 0,
+        };
+        let handle = tokio::spawn(forwarder.run(source));
+        if let Some((_, previous)) = runs.insert(run_id, (generation, handle)) {
+            previous.abort();
+        }
+    }
+}
+
+struct Forwarder {
+    inner: std::sync::Weak<MuxInner>,
+    generation: u64,
+    sender: mpsc::Sender<TaggedStreamEvent>,
+    time_slice: Option<Duration>,
+    run_id: String,
+    agent_id: String,
+    seq: u64,
+}
+
+impl Forwarder {
+    async fn emit(&mut self, payload: MuxPayload) -> bool {
+        let event = TaggedStreamEvent {
+            run_id: self.run_id.clone(),
+            agent_id: self.agent_id.clone(),
+            seq: self.seq,
+            payload,
+        };
+        self.seq += 1;
+        self.sender.send(event).await.is_ok()
+    }
+
+    async fn run(mut self, mut source: StreamEvents) {
+        // An item read while coalescing that still has to be forwarded
+        let mut pending = None;
+        loop {
+            let item = match pending.take() {
+                Some(item) => item,
+                None => source.receiver.recv().await,
+            };
+            let payload = match item {
+                None => break,
+                Some(Err(error)) => MuxPayload::Error(error),
+                Some(Ok(StreamEvent::TextDelta(mut text))) => {
+                    if let Some(slice) = self.time_slice {
+                        let deadline = Instant::now() + slice;
+                        loop {
+                            match tokio::time::timeout_at(deadline, source.receiver.recv()).await {
+                                Ok(Some(Ok(StreamEvent::TextDelta(more)))) => text.push_str(&more),
+                                Ok(other) => {
+                                    pending = Some(other);
+                                    break;
+                                }
+                                Err(_) => break,
+                            }
+                        }
+                    }
+                    MuxPayload::Event(StreamEvent::TextDelta(text))
+                }
+                Some(Ok(event)) => MuxPayload::Event(event),
+            };
+            if !self.emit(payload).await {
+                return;
+            }
+        }
+        // Deregister first so the run is no longer active once `End` arrives
+        if let Some(inner) = self.inner.upgrade() {
+            let mut runs = inner.runs.lock().unwrap();
+            if runs
+                .get(&self.run_id)
+                .is_some_and(|(g, _)| *g == self.generation)
+            {
+                runs.remove(&self.run_id);
+            }
+        }
+        self.emit(MuxPayload::End).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_stream() -> (mpsc::Sender<super::super::Result<String>>, StreamResponse) {
+        let (tx, rx) = mpsc::channel(16);
+        (tx, StreamResponse::new(rx))
+    }
+
+    async fn collect_until_ended(
+        receiver: &mut MultiplexedReceiver,
+        runs: usize,
+    ) -> Vec<TaggedStreamEvent> {
+        let mut events = Vec::new();
+        let mut ended = 0;
+        while ended < runs {
+            let event = receiver.recv().await.expect("channel open");
+            if matches!(event.payload, MuxPayload::End) {
+                ended += 1;
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    fn text_of(events: &[TaggedStreamEvent], run_id: &str) -> String {
+        events
+            .iter()
+            .filter(|e| e.run_id == run_id)
+            .filter_map(|e| match &e.payload {
+                MuxPayload::Event(StreamEvent::TextDelta(text)) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn interleaves_tagged_runs_in_order() {
+        let (mux, mut receiver) = StreamMultiplexer::channel(64);
+        let (tx_a, stream_a) = text_stream();
+        let (tx_b, stream_b) = text_stream();
+        mux.attach_text("run-a", "researcher", stream_a);
+        mux.attach_text("run-b", "writer", stream_b);
+
+        for (a, b) in [("Hel", "Wor"), ("lo", "ld")] {
+            tx_a.send(Ok(a.to_string())).await.unwrap();
+            tx_b.send(Ok(b.to_string())).await.unwrap();
+        }
+        tx_b.send(Err(ProviderError::Other("hiccup".into())))
+            .await
+            .unwrap();
+        drop((tx_a, tx_b));
+
+        let events = collect_until_ended(&mut receiver, 2).await;
+        assert_eq!(text_of(&events, "run-a"), "Hello");
+        assert_eq!(text_of(&events, "run-b"), "World");
+
+        for run in ["run-a", "run-b"] {
+            let seqs: Vec<u64> = events
+                .iter()
+                .filter(|e| e.run_id == run)
+                .map(|e| e.seq)
+                .collect();
+            assert_eq!(seqs, (0..seqs.len() as u64).collect::<Vec<_>>());
+        }
+        let error = events
+            .iter()
+            .find(|e| matches!(e.payload, MuxPayload::Error(_)))
+            .unwrap();
+        assert_eq!(error.agent_id, "writer");
+        assert_eq!(error.to_json()["type"], "error");
+        assert!(mux.active_runs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn coalesces_text_within_a_time_slice() {
+        let (mux, mut receiver) =
+            StreamMultiplexer::with_time_slice(64, Some(Duration::from_millis(200)));
+        let (tx, rx) = mpsc::channel(16);
+        for token in ["a", "b", "c"] {
+            tx.send(Ok(StreamEvent::TextDelta(token.to_string())))
+                .await
+                .unwrap();
+        }
+        tx.send(Ok(StreamEvent::Done {
+            finish_reason: Some("stop".into()),
+        }))
+        .await
+        .unwrap();
+        drop(tx);
+        mux.attach("run", "agent", StreamEvents::new(rx));
+
+        let events = collect_until_ended(&mut receiver, 1).await;
+        let frames: Vec<serde_json::Value> = events.iter().map(|e| e.to_json()).collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0]["text"], "abc");
+        assert_eq!(frames[1]["type"], "done");
+        assert_eq!(frames[2]["type"], "end");
+        assert_eq!(frames[2]["seq"], 2);
+    }
+
+    #[tokio::test]
+    async fn detached_runs_stop_forwarding() {
+        let (mux, mut receiver) = StreamMultiplexer::channel(64);
+        let (tx, stream) = text_stream();
+        mux.attach_text("run", "agent", stream);
+        assert_eq!(mux.active_runs(), vec!["run".to_string()]);
+
+        assert!(mux.detach("run"));
+        assert!(!mux.detach("run"));
+        let _ = tx.send(Ok("late".to_string())).await;
+        drop(mux);
+        assert!(receiver.recv().await.is_none());
+    }
+}
@@ -0,0 +1,715 @@
+use super::openai_compat;
+use super::pipeline::RequestPipeline;
+use super::stream_decode::LineDecoder;
+use super::{
+    CacheConfig, ContentBlock, ContextWindowConfig, ContextWindowManager, GenerateOptions,
+    GenerateResponse, ImageSource, KeepAliveConfig, LlmProvider, Message, MiddlewareChain,
+    ProviderCapabilities, ProviderClient, ProviderClientBuilder, ProviderError, RateLimitConfig,
+    ResponseCache, ResponseFormat, Result, RetryConfig, Role, StreamEvent, StreamEvents,
+    TimeoutConfig, ToolSchema, Usage,
+};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Ollama Provider 实现，连接本地 Ollama 服务（`/api/chat`）
+pub struct OllamaProvider {
+    model: String,
+    client: ProviderClient,
+    base_url: String,
+    middleware: Option<MiddlewareChain>,
+    cache: Option<ResponseCache>,
+    context_manager: Option<ContextWindowManager>,
+}
+
+impl OllamaProvider {
+    /// Create a provider for `model` on the default local server
+    pub fn new(model: impl Into<String>) -> Result<Self> {
+        Self::builder().model(model).build()
+    }
+
+    /// Create a builder for configuring the Ollama provider
+    pub fn builder() -> OllamaProviderBuilder {
+        OllamaProviderBuilder::default()
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+
+    /// List the models available on the server (`/api/tags`)
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self
+            .client
+            .http_client()
+            .get(format!("{}/api/tags", self.base_url))
+            .send()
+            .await
+            .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(ProviderError::RequestFailed(format!(
+                "{}: {}",
+                status, text
+            )));
+        }
+
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+
+        Ok(json["models"]
+            .as_array()
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m["name"].as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Ollama has no assistant prefill and no grammar or regex decoding; JSON
+    /// output goes through `format`
+    fn check_supported(options: &Option<GenerateOptions>) -> Result<()> {
+        let Some(opts) = options else {
+            return Ok(());
+        };
+        if opts.constraint.is_some() {
+            return Err(ProviderError::Other(
+                "Constrained decoding is not supported by the Ollama provider".to_string(),
+            ));
+        }
+        if opts.assistant_prefix.is_some() {
+            return Err(ProviderError::Other(
+                "Assistant prefill is not supported by the Ollama provider".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Format a message for `/api/chat`
+    ///
+    /// Text blocks are joined, base64 images go in `images`, and tool results
+    /// become separate `tool` role messages. Image URLs are not supported by
+    /// Ollama and are dropped.
+    fn format_message(m: &Message) -> Vec<serde_json::Value> {
+        let role = match m.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+
+        let mut formatted = Vec::new();
+        let mut text = Vec::new();
+        let mut images = Vec::new();
+        let mut tool_calls = Vec::new();
+
+        for block in m.content.iter() {
+            match block {
+                ContentBlock::Text { text: t } => text.push(t.as_str()),
+                ContentBlock::Image {
+                    source: ImageSource::Base64 { data, .. },
+                    ..
+                } => images.push(data.clone()),
+                ContentBlock::Image { .. } | ContentBlock::File { .. } => {}
+                ContentBlock::ToolUse { name, input, .. } => {
+                    tool_calls.push(serde_json::json!({
+                        "function": {"name": name, "arguments": input},
+                    }));
+                }
+                ContentBlock::ToolResult { content, .. } => {
+                    formatted.push(serde_json::json!({
+                        "role": "tool",
+                        "content": content,
+                    }));
+                }
+            }
+        }
+
+        if text.is_empty() && images.is_empty() && tool_calls.is_empty() {
+            return formatted;
+        }
+
+        let mut message = serde_json::json!({
+            "role": role,
+            "content": text.join("\n"),
+        });
+        if !images.is_empty() {
+            message["images"] = serde_json::json!(images);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = serde_json::json!(tool_calls);
+        }
+        formatted.push(message);
+        formatted
+    }
+
+    fn build_request_body(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+        stream: bool,
+    ) -> serde_json::Value {
+        let opts = options.unwrap_or_default();
+
+        let messages_json: Vec<serde_json::Value> =
+            messages.iter().flat_map(Self::format_message).collect();
+
+        let mut body = serde_json::json!({
+            "model": opts.model.as_deref().unwrap_or(&self.model),
+            "messages": messages_json,
+            "stream": stream,
+        });
+
+        // Sampling parameters live under `options` in Ollama's API
+        let mut model_options = serde_json::Map::new();
+        if let Some(temp) = opts.temperature {
+            model_options.insert("temperature".into(), serde_json::json!(temp));
+        }
+        if let Some(max) = opts.max_tokens {
+            model_options.insert("num_predict".into(), serde_json::json!(max));
+        }
+        if let Some(top_p) = opts.top_p {
+            model_options.insert("top_p".into(), serde_json::json!(top_p));
+        }
+        if let Some(stop) = opts.stop {
+            model_options.insert("stop".into(), serde_json::json!(stop));
+        }
+        model_options.extend(opts.extensions.for_provider("ollama"));
+        if !model_options.is_empty() {
+            body["options"] = serde_json::Value::Object(model_options);
+        }
+        match opts.response_format {
+            Some(ResponseFormat::Json) => body["format"] = serde_json::json!("json"),
+            Some(ResponseFormat::JsonSchema(schema)) => body["format"] = schema.schema,
+            None => {}
+        }
+
+        body
+    }
+
+    /// Extract tool calls from a chat message
+    ///
+    /// Ollama does not assign call ids, so each call gets a random one.
+    fn parse_tool_calls(message: &serde_json::Value) -> Vec<crate::tool::ToolCall> {
+        message["tool_calls"]
+            .as_array()
+            .map(|calls| {
+                calls
+                    .iter()
+                    .map(|call| crate::tool::ToolCall {
+                        id: crate::ids::tool_call_id(),
+                        name: call["function"]["name"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        parameters: call["function"]["arguments"].clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn parse_usage(json: &serde_json::Value) -> Option<Usage> {
+        let prompt = json.get("prompt_eval_count")?.as_u64()? as u32;
+        let completion = json["eval_count"].as_u64().unwrap_or(0) as u32;
+        Some(Usage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        })
+    }
+
+    fn parse_generate_response(json: serde_json::Value, fallback_model: &str) -> GenerateResponse {
+        let message = &json["message"];
+        GenerateResponse {
+            content: message["content"].as_str().unwrap_or("").to_string(),
+            usage: Self::parse_usage(&json),
+            model: json["model"].as_str().unwrap_or(fallback_model).to_string(),
+            finish_reason: json["done_reason"].as_str().map(String::from),
+            tool_calls: Self::parse_tool_calls(message),
+        }
+    }
+
+    /// Translate one NDJSON stream line into typed events
+    fn parse_stream_line(json: &serde_json::Value) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        let message = &json["message"];
+
+        if let Some(content) = message["content"].as_str() {
+            if !content.is_empty() {
+                events.push(StreamEvent::TextDelta(content.to_string()));
+            }
+        }
+
+        // Tool calls arrive complete rather than as argument fragments
+        for (index, call) in Self::parse_tool_calls(message).into_iter().enumerate() {
+            events.push(StreamEvent::ToolCallDelta {
+                index,
+                id: Some(call.id),
+                name: Some(call.name),
+                arguments_delta: call.parameters.to_string(),
+            });
+        }
+
+        if json["done"].as_bool() == Some(true) {
+            if let Some(usage) = Self::parse_usage(json) {
+                events.push(StreamEvent::UsageUpdate(usage));
+            }
+            events.push(StreamEvent::Done {
+                finish_reason: json["done_reason"].as_str().map(String::from),
+            });
+        }
+
+        events
+    }
+
+    fn pipeline(&self) -> RequestPipeline<'_> {
+        RequestPipeline {
+            model: &self.model,
+            context_manager: self.context_manager.as_ref(),
+            cache: self.cache.as_ref(),
+            middleware: self.middleware.as_ref(),
+        }
+    }
+
+    async fn generate_inner(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Result<GenerateResponse> {
+        Self::check_supported(&options)?;
+        let tools = &tools;
+        self.pipeline()
+            .generate(messages, tools, options, |messages, options| async move {
+                let mut body = self.build_request_body(messages, options, false);
+                openai_compat::add_tools_to_body(&mut body, tools, None);
+                let response = self.send_request(body).await?;
+                let json: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+                Ok(Self::parse_generate_response(json, &self.model))
+            })
+            .await
+    }
+
+    async fn send_request(&self, body: serde_json::Value) -> Result<reqwest::Response> {
+        let _guard = self.client.acquire_rate_limit().await;
+        let streaming = body["stream"] == true;
+
+        self.client
+            .retry_policy()
+            .execute_with_retry(|| async {
+                let mut request = self
+                    .client
+                    .http_client()
+                    .post(format!("{}/api/chat", self.base_url))
+                    .header("Content-Type", "application/json")
+                    .json(&body);
+                if streaming {
+                    request = self.client.stream_request(request);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+
+                let status = response.status();
+                if status == reqwest::StatusCode::NOT_FOUND {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::ModelNotAvailable(text));
+                }
+                if !status.is_success() {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::RequestFailed(format!(
+                        "{}: {}",
+                        status, text
+                    )));
+                }
+
+                Ok(response)
+            })
+            .await
+    }
+}
+
+/// Builder for creating an OllamaProvider with custom configuration
+pub struct OllamaProviderBuilder {
+    model: Option<String>,
+    base_url: Option<String>,
+    client_builder: ProviderClientBuilder,
+    middleware: Option<MiddlewareChain>,
+    cache_config: Option<CacheConfig>,
+    context_config: Option<ContextWindowConfig>,
+}
+
+impl Default for OllamaProviderBuilder {
+    fn default() -> Self {
+        Self {
+            model: None,
+            base_url: None,
+            client_builder: ProviderClient::builder(),
+            middleware: None,
+            cache_config: None,
+            context_config: None,
+        }
+    }
+}
+
+impl OllamaProviderBuilder {
+    /// Set the model
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the base URL
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// Set the retry configuration
+    pub fn retry_config(mut self, config: RetryConfig) -> Self {
+        self.client_builder = self.client_builder.retry_config(config);
+        self
+    }
+
+    /// Set the timeout configuration
+    pub fn timeout_config(mut self, config: TimeoutConfig) -> Self {
+        self.client_builder = self.client_builder.timeout_config(config);
+        self
+    }
+
+    /// Set the rate limit configuration
+    pub fn rate_limit_config(mut self, config: RateLimitConfig) -> Self {
+        self.client_builder = self.client_builder.rate_limit_config(config);
+        self
+    }
+
+    /// Set the connection pool and keep-alive configuration
+    pub fn keep_alive_config(mut self, config: KeepAliveConfig) -> Self {
+        self.client_builder = self.client_builder.keep_alive_config(config);
+        self
+    }
+
+    /// Set a proxy URL
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.client_builder = self.client_builder.proxy(proxy);
+        self
+    }
+
+    /// Add a header sent with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.client_builder = self.client_builder.header(name, value);
+        self
+    }
+
+    /// Set the middleware chain
+    pub fn middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = Some(middleware);
+        self
+    }
+
+    /// Enable response caching with the given configuration
+    pub fn cache_config(mut self, config: CacheConfig) -> Self {
+        self.cache_config = Some(config);
+        self
+    }
+
+    /// Enable context window management with the given configuration
+    pub fn context_config(mut self, config: ContextWindowConfig) -> Self {
+        self.context_config = Some(config);
+        self
+    }
+
+    /// Disable retries
+    pub fn no_retry(mut self) -> Self {
+        self.client_builder = self.client_builder.no_retry();
+        self
+    }
+
+    /// Disable rate limiting
+    pub fn no_rate_limit(mut self) -> Self {
+        self.client_builder = self.client_builder.no_rate_limit();
+        self
+    }
+
+    /// Build the Ollama provider
+    pub fn build(self) -> Result<OllamaProvider> {
+        let model = self
+            .model
+            .ok_or_else(|| ProviderError::RequestFailed("Model is required".to_string()))?;
+
+        let client = self.client_builder.build()?;
+
+        let cache = self.cache_config.map(ResponseCache::new);
+        let context_manager = self.context_config.map(ContextWindowManager::new);
+
+        Ok(OllamaProvider {
+            model,
+            client,
+            base_url: self
+                .base_url
+                .unwrap_or_else(|| "http://localhost:11434".to_string()),
+            middleware: self.middleware,
+            cache,
+            context_manager,
+        })
+    }
+}
+
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            native_tools: true,
+            structured_output: true,
+            ..Default::default()
+        }
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, Vec::new(), options))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, tools, options))
+    }
+
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<super::StreamResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let events = self
+                .generate_stream_events(messages, Vec::new(), options)
+                .await?;
+            Ok(super::StreamResponse::from_events(events))
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            Self::check_supported(&options)?;
+            let mut body = self.build_request_body(messages, options, true);
+            openai_compat::add_tools_to_body(&mut body, &tools, None);
+            let deadline = self.client.stream_deadline();
+            let response = self.send_request(body).await?;
+
+            Ok(StreamEvents::spawn(100, |tx| async move {
+                let mut stream = response.bytes_stream();
+                let mut lines = LineDecoder::new();
+
+                loop {
+                    let chunk = match deadline.next(&mut stream).await {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(e) => {
+                            let _ = tx.send(Err(e)).await;
+                            return;
+                        }
+                    };
+                    match chunk {
+                        Ok(bytes) => {
+                            lines.push(&bytes);
+
+                            // The stream is newline-delimited JSON, one object per line
+                            while let Some(line) = lines.next_line() {
+                                if line.is_empty() {
+                                    continue;
+                                }
+                                let json = match serde_json::from_slice::<serde_json::Value>(&line)
+                                {
+                                    Ok(json) => json,
+                                    Err(e) => {
+                                        let _ = tx
+                                            .send(Err(ProviderError::ParseError(e.to_string())))
+                                            .await;
+                                        return;
+                                    }
+                                };
+                                if let Some(error) = json["error"].as_str() {
+                                    let _ = tx
+                                        .send(Err(ProviderError::RequestFailed(error.to_string())))
+                                        .await;
+                                    return;
+                                }
+                                for event in Self::parse_stream_line(&json) {
+                                    if tx.send(Ok(event)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let _ = tx
+                                .send(Err(ProviderError::RequestFailed(e.to_string())))
+                                .await;
+                            return;
+                        }
+                    }
+                }
+            }))
+        })
+    }
+
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(self.client.preconnect(&self.base_url))
+    }
+
+    fn rate_limiter(&self) -> Option<&super::RateLimiter> {
+        Some(self.client.rate_limiter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::Extensions;
+    use crate::tool::ToolCall;
+
+    fn provider() -> OllamaProvider {
+        OllamaProvider::new("llama3.2").unwrap()
+    }
+
+    #[test]
+    fn builds_chat_body_with_model_options() {
+        let options = GenerateOptions {
+            temperature: Some(0.2),
+            max_tokens: Some(128),
+            stop: Some(vec!["END".to_string()]),
+            extensions: Extensions::new()
+                .with("top_k", serde_json::json!(40))
+                .with("ollama.num_ctx", serde_json::json!(8192)),
+            ..Default::default()
+        };
+        let body = provider().build_request_body(
+            vec![
+                Message::system("be brief"),
+                Message::user_with_image_base64("what is this?", "image/png", "aGVsbG8="),
+            ],
+            Some(options),
+            false,
+        );
+
+        assert_eq!(body["model"], "llama3.2");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["options"]["num_predict"], 128);
+        assert_eq!(body["options"]["stop"][0], "END");
+        assert_eq!(body["options"]["top_k"], 40);
+        assert_eq!(body["options"]["num_ctx"], 8192);
+        assert_eq!(body["messages"][1]["content"], "what is this?");
+        assert_eq!(body["messages"][1]["images"][0], "aGVsbG8=");
+    }
+
+    #[test]
+    fn sends_response_format_as_format() {
+        let options = GenerateOptions {
+            response_format: Some(ResponseFormat::Json),
+            ..Default::default()
+        };
+        let body = provider().build_request_body(vec![Message::user("hi")], Some(options), false);
+        assert_eq!(body["format"], "json");
+    }
+
+    #[test]
+    fn formats_tool_round_trip() {
+        let call = ToolCall {
+            id: "call_0".to_string(),
+            name: "calculator".to_string(),
+            parameters: serde_json::json!({"a": 1}),
+        };
+        let body = provider().build_request_body(
+            vec![
+                Message::assistant_with_tool_calls("", &[call]),
+                Message::tool_results(vec![ContentBlock::tool_result("call_0", "3", false)]),
+            ],
+            None,
+            false,
+        );
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(
+            messages[0]["tool_calls"][0]["function"]["arguments"]["a"],
+            1
+        );
+        assert_eq!(messages[1]["role"], "tool");
+        assert_eq!(messages[1]["content"], "3");
+    }
+
+    #[test]
+    fn parses_chat_response_with_tool_calls() {
+        let json = serde_json::json!({
+            "model": "llama3.2",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "calculator", "arguments": {"a": 1}}}]
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 10,
+            "eval_count": 5
+        });
+
+        let resp = OllamaProvider::parse_generate_response(json, "fallback");
+        assert!(resp.tool_calls[0].id.starts_with("call_"));
+        assert_eq!(resp.tool_calls[0].parameters["a"], 1);
+        assert_eq!(resp.usage.map(|u| u.total_tokens), Some(15));
+        assert_eq!(resp.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn parses_ndjson_stream_lines() {
+        let partial = serde_json::json!({"message": {"content": "Hel"}, "done": false});
+        assert!(matches!(
+            OllamaProvider::parse_stream_line(&partial).as_slice(),
+            [StreamEvent::TextDelta(t)] if t == "Hel"
+        ));
+
+        let last = serde_json::json!({
+            "message": {"content": ""},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 3,
+            "eval_count": 2
+        });
+        match OllamaProvider::parse_stream_line(&last).as_slice() {
+            [StreamEvent::UsageUpdate(usage), StreamEvent::Done { finish_reason }] => {
+                assert_eq!(usage.total_tokens, 5);
+                assert_eq!(finish_reason.as_deref(), Some("stop"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+}
@@ -1,24 +1,18 @@
-use super::{
-    CacheConfig, CacheKey, ContextWindowConfig, ContextWindowManager, GenerateOptions,
-    GenerateResponse, LlmProvider, Message, MiddlewareChain, ProviderClient, ProviderClientBuilder,
-    ProviderError, RateLimitConfig, ResponseCache, Result, RetryConfig, Role, TimeoutConfig, Usage,
+use super::openai_compat::{
+    delegate_llm_provider, OpenAiCompatProvider, OpenAiCompatProviderBuilder,
 };
-use futures_util::StreamExt;
+use super::{EmbeddingProvider, EmbeddingRequest, EmbeddingResponse, ProviderCapabilities, Result};
 use std::future::Future;
 use std::pin::Pin;
-use tokio::sync::mpsc;
 
 /// OpenRouter Provider 实现
 pub struct OpenRouterProvider {
-    api_key: String,
-    model: String,
-    client: ProviderClient,
-    base_url: String,
-    middleware: Option<MiddlewareChain>,
-    cache: Option<ResponseCache>,
-    context_manager: Option<ContextWindowManager>,
+    inner: OpenAiCompatProvider,
 }
 
+/// Builder for `OpenRouterProvider`
+pub type OpenRouterProviderBuilder = OpenAiCompatProviderBuilder<OpenRouterProvider>;
+
 impl OpenRouterProvider {
     /// Create a new OpenRouter provider with default configuration
     pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Result<Self> {
@@ -31,457 +25,265 @@ impl OpenRouterProvider {
     }
 
     pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
-        self.base_url = url.into();
+        self.inner = self.inner.with_base_url(url);
         self
     }
-
-    fn build_request_body(
-        &self,
-        messages: Vec<Message>,
-        options: Option<GenerateOptions>,
-        stream: bool,
-    ) -> serde_json::Value {
-        let opts = options.unwrap_or_default();
-
-        let messages_json: Vec<serde_json::Value> = messages
-            .into_iter()
-            .map(|m| {
-                let role = match m.role {
-                    Role::System => "system",
-                    Role::User => "user",
-                    Role::Assistant => "assistant",
-                };
-
-                // Format content - OpenAI format supports both string and array
-                let content = if m.content.len() == 1 {
-                    // Single text block - use string format
-                    if let super::ContentBlock::Text { text } = &m.content[0] {
-                        serde_json::json!(text)
-                    } else {
-                        // Single image block - use array format
-                        self.format_content_blocks(&m.content)
-                    }
-                } else {
-                    // Multiple blocks - use array format
-                    self.format_content_blocks(&m.content)
-                };
-
-                serde_json::json!({
-                    "role": role,
-                    "content": content,
-                })
-            })
-            .collect();
-
-        let mut body = serde_json::json!({
-            "model": &self.model,
-            "messages": messages_json,
-            "stream": stream,
-        });
-
-        if let Some(temp) = opts.temperature {
-            body["temperature"] = serde_json::json!(temp);
-        }
-        if let Some(max) = opts.max_tokens {
-            body["max_tokens"] = serde_json::json!(max);
-        }
-        if let Some(top_p) = opts.top_p {
-            body["top_p"] = serde_json::json!(top_p);
-        }
-        if let Some(stop) = opts.stop {
-            body["stop"] = serde_json::json!(stop);
-        }
-
-        body
-    }
-
-    fn format_content_blocks(&self, content: &[super::ContentBlock]) -> serde_json::Value {
-        use super::{ContentBlock, ImageSource};
-
-        serde_json::json!(content
-            .iter()
-            .map(|block| match block {
-                ContentBlock::Text { text } => serde_json::json!({
-                    "type": "text",
-                    "text": text,
-                }),
-                ContentBlock::Image { source, detail } => {
-                    let mut img = serde_json::json!({
-                        "type": "image_url",
-                    });
-                    match source {
-                        ImageSource::Url { url } => {
-                            let mut image_url = serde_json::json!({
-                                "url": url,
-                            });
-                            if let Some(d) = detail {
-                                image_url["detail"] = serde_json::json!(match d {
-                                    super::ImageDetail::Low => "low",
-                                    super::ImageDetail::High => "high",
-                                    super::ImageDetail::Auto => "auto",
-                                });
-                            }
-                            img["image_url"] = image_url;
-                        }
-                        ImageSource::Base64 { media_type, data } => {
-                            let data_url = format!("data:{};base64,{}", media_type, data);
-                            let mut image_url = serde_json::json!({
-                                "url": data_url,
-                            });
-                            if let Some(d) = detail {
-                                image_url["detail"] = serde_json::json!(match d {
-                                    super::ImageDetail::Low => "low",
-                                    super::ImageDetail::High => "high",
-                                    super::ImageDetail::Auto => "auto",
-                                });
-                            }
-                            img["image_url"] = image_url;
-                        }
-                    }
-                    img
-                }
-            })
-            .collect::<Vec<_>>())
-    }
-
-    async fn send_request(&self, body: serde_json::Value) -> Result<reqwest::Response> {
-        let _guard = self.client.acquire_rate_limit().await;
-
-        self.client
-            .retry_policy()
-            .execute_with_retry(|| async {
-                let response = self
-                    .client
-                    .http_client()
-                    .post(format!("{}/chat/completions", self.base_url))
-                    .header("Authorization", format!("Bearer {}", self.api_key))
-                    .header("Content-Type", "application/json")
-                    .json(&body)
-                    .send()
-                    .await
-                    .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
-
-                let status = response.status();
-                if status == reqwest::StatusCode::UNAUTHORIZED {
-                    return Err(ProviderError::AuthenticationFailed(
-                        "Invalid API key".to_string(),
-                    ));
-                }
-                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
-                    let retry_after = response
-                        .headers()
-                        .get("retry-after")
-                        .and_then(|v| v.to_str().ok())
-                        .and_then(|s| s.parse().ok());
-                    return Err(ProviderError::RateLimited { retry_after });
-                }
-                if !status.is_success() {
-                    let text = response.text().await.unwrap_or_default();
-                    return Err(ProviderError::RequestFailed(format!(
-                        "{}: {}",
-                        status, text
-                    )));
-                }
-
-                Ok(response)
-            })
-            .await
-    }
-}
-
-/// Builder for creating an OpenRouterProvider with custom configuration
-pub struct OpenRouterProviderBuilder {
-    api_key: Option<String>,
-    model: Option<String>,
-    base_url: Option<String>,
-    client_builder: ProviderClientBuilder,
-    middleware: Option<MiddlewareChain>,
-    cache_config: Option<CacheConfig>,
-    context_config: Option<ContextWindowConfig>,
 }
 
+/// Constraints and prefill are passed through as-is; they take effect when
+/// `base_url` points at an OpenAI-compatible server that understands them.
 impl Default for OpenRouterProviderBuilder {
     fn default() -> Self {
-        Self {
-            api_key: None,
-            model: None,
-            base_url: None,
-            client_builder: ProviderClient::builder(),
-            middleware: None,
-            cache_config: None,
-            context_config: None,
-        }
+        Self::vendor("openrouter", "https://openrouter.ai/api/v1")
+            .capabilities(ProviderCapabilities {
+                streaming: true,
+                assistant_prefill: true,
+                grammar: true,
+                regex: true,
+                native_tools: true,
+                structured_output: true,
+                tool_choice: true,
+            })
+            .embedding_model("openai/text-embedding-3-small")
     }
 }
 
 impl OpenRouterProviderBuilder {
-    /// Set the API key
-    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
-        self.api_key = Some(api_key.into());
-        self
-    }
-
-    /// Set the model
-    pub fn model(mut self, model: impl Into<String>) -> Self {
-        self.model = Some(model.into());
-        self
+    /// Site URL sent as `HTTP-Referer`, which attributes usage to your app
+    pub fn http_referer(self, url: impl Into<String>) -> Self {
+        self.header("HTTP-Referer", url)
     }
 
-    /// Set the base URL
-    pub fn base_url(mut self, url: impl Into<String>) -> Self {
-        self.base_url = Some(url.into());
-        self
+    /// App name sent as `X-Title`, shown in OpenRouter rankings
+    pub fn x_title(self, title: impl Into<String>) -> Self {
+        self.header("X-Title", title)
     }
 
-    /// Set the retry configuration
-    pub fn retry_config(mut self, config: RetryConfig) -> Self {
-        self.client_builder = self.client_builder.retry_config(config);
-        self
-    }
-
-    /// Set the timeout configuration
-    pub fn timeout_config(mut self, config: TimeoutConfig) -> Self {
-        self.client_builder = self.client_builder.timeout_config(config);
-        self
-    }
-
-    /// Set the rate limit configuration
-    pub fn rate_limit_config(mut self, config: RateLimitConfig) -> Self {
-        self.client_builder = self.client_builder.rate_limit_config(config);
-        self
-    }
-
-    /// Set a proxy URL
-    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
-        self.client_builder = self.client_builder.proxy(proxy);
+    /// Route every request with `routing`
+    pub fn routing(mut self, routing: &OpenRouterRouting) -> Self {
+        for (name, value) in routing.body_fields() {
+            self = self.body_field(name, value);
+        }
         self
     }
+}
 
-    /// Set the middleware chain
-    pub fn middleware(mut self, middleware: MiddlewareChain) -> Self {
-        self.middleware = Some(middleware);
-        self
-    }
+/// OpenRouter provider preferences, model fallbacks and prompt transforms
+///
+/// Set on the builder for every request, or per request by passing
+/// `body_fields()` as `GenerateOptions::extra_body`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpenRouterRouting {
+    /// Providers to try first, in order, e.g. `["anthropic", "openai"]`
+    pub order: Vec<String>,
+    /// Providers never to use
+    pub ignore: Vec<String>,
+    /// Whether providers outside `order` may serve the request
+    pub allow_fallbacks: Option<bool>,
+    /// Only use providers that do not store or train on prompts
+    pub deny_data_collection: bool,
+    /// Models to try when the requested one is unavailable
+    pub fallback_models: Vec<String>,
+    /// Prompt transforms, e.g. `middle-out`
+    pub transforms: Vec<String>,
+}
 
-    /// Enable response caching with the given configuration
-    pub fn cache_config(mut self, config: CacheConfig) -> Self {
-        self.cache_config = Some(config);
-        self
-    }
+impl OpenRouterRouting {
+    /// Request body fields: `provider`, `models` and `transforms`
+    pub fn body_fields(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut provider = serde_json::Map::new();
+        if !self.order.is_empty() {
+            provider.insert("order".to_string(), serde_json::json!(self.order));
+        }
+        if !self.ignore.is_empty() {
+            provider.insert("ignore".to_string(), serde_json::json!(self.ignore));
+        }
+        if let Some(allow) = self.allow_fallbacks {
+            provider.insert("allow_fallbacks".to_string(), serde_json::json!(allow));
+        }
+        if self.deny_data_collection {
+            provider.insert("data_collection".to_string(), serde_json::json!("deny"));
+        }
 
-    /// Enable context window management with the given configuration
-    pub fn context_config(mut self, config: ContextWindowConfig) -> Self {
-        self.context_config = Some(config);
-        self
+        let mut fields = serde_json::Map::new();
+        if !provider.is_empty() {
+            fields.insert("provider".to_string(), serde_json::Value::Object(provider));
+        }
+        if !self.fallback_models.is_empty() {
+            fields.insert(
+                "models".to_string(),
+                serde_json::json!(self.fallback_models),
+            );
+        }
+        if !self.transforms.is_empty() {
+            fields.insert("transforms".to_string(), serde_json::json!(self.transforms));
+        }
+        fields
     }
+}
 
-    /// Disable retries
-    pub fn no_retry(mut self) -> Self {
-        self.client_builder = self.client_builder.no_retry();
-        self
-    }
+delegate_llm_provider!(OpenRouterProvider);
 
-    /// Disable rate limiting
-    pub fn no_rate_limit(mut self) -> Self {
-        self.client_builder = self.client_builder.no_rate_limit();
-        self
+/// Embeddings through OpenRouter's OpenAI-compatible `/embeddings`, with
+/// `openai/text-embedding-3-small` unless the request or builder names
+/// another model
+impl EmbeddingProvider for OpenRouterProvider {
+    fn create_embeddings(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<EmbeddingResponse>> + Send + '_>> {
+        self.inner.create_embeddings(request)
     }
+}
 
-    /// Build the OpenRouter provider
-    pub fn build(self) -> Result<OpenRouterProvider> {
-        let api_key = self
-            .api_key
-            .ok_or_else(|| ProviderError::RequestFailed("API key is required".to_string()))?;
-
-        let model = self
-            .model
-            .ok_or_else(|| ProviderError::RequestFailed("Model is required".to_string()))?;
-
-        let client = self.client_builder.build()?;
-
-        let cache = self.cache_config.map(ResponseCache::new);
-        let context_manager = self.context_config.map(ContextWindowManager::new);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ContentBlock;
+    use crate::provider::{LlmProvider, Message};
+    use crate::tool::ToolCall;
 
-        Ok(OpenRouterProvider {
-            api_key,
-            model,
-            client,
-            base_url: self
-                .base_url
-                .unwrap_or_else(|| "https://openrouter.ai/api/v1".to_string()),
-            middleware: self.middleware,
-            cache,
-            context_manager,
-        })
+    fn provider() -> OpenRouterProvider {
+        OpenRouterProvider::new("test-key", "openai/gpt-4o-mini").unwrap()
     }
-}
 
-impl LlmProvider for OpenRouterProvider {
-    fn name(&self) -> &str {
-        "openrouter"
+    #[test]
+    fn formats_tool_calls_and_tool_results_in_openai_shape() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "calculator".to_string(),
+            parameters: serde_json::json!({"a": 1}),
+        };
+        let body = provider().inner.build_request_body(
+            vec![
+                Message::user("add"),
+                Message::assistant_with_tool_calls("", &[call]),
+                Message::tool_results(vec![ContentBlock::tool_result("call_1", "3", false)]),
+            ],
+            None,
+            false,
+        );
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[1]["content"].is_null());
+        assert_eq!(
+            messages[1]["tool_calls"][0]["function"]["name"],
+            "calculator"
+        );
+        assert_eq!(
+            messages[1]["tool_calls"][0]["function"]["arguments"],
+            r#"{"a":1}"#
+        );
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[2]["tool_call_id"], "call_1");
     }
 
-    fn model(&self) -> &str {
-        &self.model
+    #[test]
+    fn maps_prefill_and_constraints_for_compatible_servers() {
+        let options = crate::provider::GenerateOptions {
+            assistant_prefix: Some("{".to_string()),
+            constraint: Some(crate::provider::DecodingConstraint::Regex(
+                "\\d+".to_string(),
+            )),
+            ..Default::default()
+        };
+        let provider = provider();
+        assert!(provider
+            .inner
+            .check_supported(&[], &Some(options.clone()))
+            .is_ok());
+
+        let body =
+            provider
+                .inner
+                .build_request_body(vec![Message::user("n?")], Some(options), true);
+        assert_eq!(body["messages"][1]["role"], "assistant");
+        assert_eq!(body["messages"][1]["content"], "{");
+        assert_eq!(body["guided_regex"], "\\d+");
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        assert_eq!(provider.name(), "openrouter");
+        assert_eq!(provider.inner.base_url, "https://openrouter.ai/api/v1");
     }
 
-    fn generate(
-        &self,
-        messages: Vec<Message>,
-        options: Option<GenerateOptions>,
-    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
-        Box::pin(async move {
-            // Apply context window management if configured
-            let messages = if let Some(manager) = &self.context_manager {
-                manager.truncate_if_needed(messages)
-            } else {
-                messages
-            };
-
-            // Check cache first
-            if let Some(cache) = &self.cache {
-                let key = CacheKey::from_request(&messages, &self.model, &options);
-                if let Some(cached) = cache.get(&key).await {
-                    return Ok(cached);
-                }
-            }
-
-            // Execute middleware before_request
-            let mut ctx = super::RequestContext {
-                messages: messages.clone(),
-                options: options.clone(),
-                metadata: std::collections::HashMap::new(),
-            };
-
-            if let Some(mw) = &self.middleware {
-                if let Err(e) = mw.execute_before(&mut ctx).await {
-                    if let Some(mw) = &self.middleware {
-                        let _ = mw.execute_error(&e).await;
-                    }
-                    return Err(e);
-                }
-            }
-
-            // Make the actual request
-            let result = async {
-                let body =
-                    self.build_request_body(ctx.messages.clone(), ctx.options.clone(), false);
-                let response = self.send_request(body).await?;
-
-                let json: serde_json::Value = response
-                    .json()
-                    .await
-                    .map_err(|e| ProviderError::ParseError(e.to_string()))?;
-
-                let content = json["choices"][0]["message"]["content"]
-                    .as_str()
-                    .unwrap_or("")
-                    .to_string();
-
-                let usage = json.get("usage").map(|u| Usage {
-                    prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                    completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                    total_tokens: u["total_tokens"].as_u64().unwrap_or(0) as u32,
-                });
-
-                let finish_reason = json["choices"][0]["finish_reason"]
-                    .as_str()
-                    .map(String::from);
-
-                Ok(GenerateResponse {
-                    content,
-                    usage,
-                    model: self.model.clone(),
-                    finish_reason,
-                })
-            }
-            .await;
-
-            match result {
-                Ok(response) => {
-                    // Store in cache
-                    if let Some(cache) = &self.cache {
-                        let key = CacheKey::from_request(&messages, &self.model, &options);
-                        cache.put(key, response.clone()).await;
-                    }
-
-                    // Execute middleware after_response
-                    let mut resp_ctx = super::ResponseContext {
-                        response: response.clone(),
-                        metadata: ctx.metadata,
-                    };
-
-                    if let Some(mw) = &self.middleware {
-                        mw.execute_after(&mut resp_ctx).await?;
-                    }
-
-                    Ok(resp_ctx.response)
-                }
-                Err(e) => {
-                    // Execute middleware on_error
-                    if let Some(mw) = &self.middleware {
-                        let _ = mw.execute_error(&e).await;
-                    }
-                    Err(e)
-                }
-            }
-        })
+    #[test]
+    fn sends_attribution_headers() {
+        let provider = OpenRouterProvider::builder()
+            .api_key("test-key")
+            .model("openai/gpt-4o-mini")
+            .http_referer("https://example.com")
+            .x_title("Example App")
+            .build()
+            .unwrap();
+
+        assert!(provider.inner.headers.contains(&(
+            "HTTP-Referer".to_string(),
+            "https://example.com".to_string()
+        )));
+        assert!(provider
+            .inner
+            .headers
+            .contains(&("X-Title".to_string(), "Example App".to_string())));
     }
 
-    fn generate_stream(
-        &self,
-        messages: Vec<Message>,
-        options: Option<GenerateOptions>,
-    ) -> Pin<Box<dyn Future<Output = Result<super::StreamResponse>> + Send + '_>> {
-        Box::pin(async move {
-            let body = self.build_request_body(messages, options, true);
-            let response = self.send_request(body).await?;
-
-            let (tx, rx) = mpsc::channel(100);
-
-            tokio::spawn(async move {
-                let mut stream = response.bytes_stream();
-                let mut buffer = String::new();
-
-                while let Some(chunk) = stream.next().await {
-                    match chunk {
-                        Ok(bytes) => {
-                            buffer.push_str(&String::from_utf8_lossy(&bytes));
-
-                            while let Some(line_end) = buffer.find('\n') {
-                                let line = buffer[..line_end].trim().to_string();
-                                buffer.drain(..=line_end);
-
-                                if let Some(data) = line.strip_prefix("data: ") {
-                                    if data == "[DONE]" {
-                                        break;
-                                    }
-
-                                    if let Ok(json) =
-                                        serde_json::from_str::<serde_json::Value>(data)
-                                    {
-                                        if let Some(content) =
-                                            json["choices"][0]["delta"]["content"].as_str()
-                                        {
-                                            if tx.send(Ok(content.to_string())).await.is_err() {
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let _ = tx
-                                .send(Err(ProviderError::RequestFailed(e.to_string())))
-                                .await;
-                            break;
-                        }
-                    }
-                }
-            });
-
-            Ok(super::StreamResponse { receiver: rx })
-        })
+    #[test]
+    fn sends_routing_preferences() {
+        let provider = OpenRouterProvider::builder()
+            .api_key("test-key")
+            .model("openai/gpt-4o-mini")
+            .routing(&OpenRouterRouting {
+                order: vec!["openai".to_string(), "azure".to_string()],
+                deny_data_collection: true,
+                transforms: vec!["middle-out".to_string()],
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let body = provider
+            .inner
+            .build_request_body(vec![Message::user("hi")], None, false);
+        assert_eq!(
+            body["provider"]["order"],
+            serde_json::json!(["openai", "azure"])
+        );
+        assert_eq!(body["provider"]["data_collection"], "deny");
+        assert_eq!(body["transforms"], serde_json::json!(["middle-out"]));
+
+        // Per-request routing replaces the provider's
+        let per_request = OpenRouterRouting {
+            allow_fallbacks: Some(false),
+            fallback_models: vec!["anthropic/claude-3.5-haiku".to_string()],
+            ..Default::default()
+        };
+        let options = crate::provider::GenerateOptions {
+            extra_body: Some(per_request.body_fields()),
+            ..Default::default()
+        };
+        let body =
+            provider
+                .inner
+                .build_request_body(vec![Message::user("hi")], Some(options), false);
+        assert_eq!(
+            body["provider"],
+            serde_json::json!({"allow_fallbacks": false})
+        );
+        assert_eq!(body["models"][0], "anthropic/claude-3.5-haiku");
+        assert_eq!(body["transforms"], serde_json::json!(["middle-out"]));
+
+        // Extensions are sent at the top level, only under this provider's name
+        let mut options = crate::provider::GenerateOptions::default();
+        options
+            .extensions
+            .set(crate::provider::extensions::REPETITION_PENALTY, 1.5)
+            .insert("openrouter.top_k", serde_json::json!(20))
+            .insert("anthropic.top_a", serde_json::json!(0.1));
+        let body =
+            provider
+                .inner
+                .build_request_body(vec![Message::user("hi")], Some(options), false);
+        assert_eq!(body["repetition_penalty"], 1.5);
+        assert_eq!(body["top_k"], 20);
+        assert!(body.get("top_a").is_none());
     }
 }
@@ -0,0 +1,717 @@
+use super::deferred::check_deferrable;
+use super::openai_compat::{
+    add_tools_to_body, delegate_llm_provider, parse_generate_response, OpenAiCompatProvider,
+    OpenAiCompatProviderBuilder,
+};
+use super::{
+    CreateFineTuneRequest, DeferredProvider, EmbeddingProvider, EmbeddingRequest,
+    EmbeddingResponse, FileProvider, FileUpload, FineTuneCheckpoint, FineTuneJob, FineTuneProvider,
+    FineTuneStatus, GenerateOptions, GeneratedImage, ImageGenerationProvider, ImageQuality,
+    ImageRequest, ImageResponse, ImageSource, JobHandle, JobStatus, LlmProvider, Message,
+    ProviderCapabilities, ProviderError, ProviderFile, Result, ToolSchema, TrainingFile,
+};
+use std::future::Future;
+use std::pin::Pin;
+
+/// OpenAI Provider 实现（Chat Completions API）
+pub struct OpenAIProvider {
+    inner: OpenAiCompatProvider,
+}
+
+/// Builder for `OpenAIProvider`
+pub type OpenAIProviderBuilder = OpenAiCompatProviderBuilder<OpenAIProvider>;
+
+impl OpenAIProvider {
+    /// Create a new OpenAI provider with default configuration
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Result<Self> {
+        Self::builder().api_key(api_key).model(model).build()
+    }
+
+    /// Create a builder for configuring the OpenAI provider
+    pub fn builder() -> OpenAIProviderBuilder {
+        OpenAIProviderBuilder::default()
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.inner = self.inner.with_base_url(url);
+        self
+    }
+}
+
+/// OpenAI has no assistant prefill and no grammar or regex decoding, but
+/// enforces JSON schemas through `response_format`
+impl Default for OpenAIProviderBuilder {
+    fn default() -> Self {
+        Self::vendor("openai", "https://api.openai.com/v1")
+            .capabilities(ProviderCapabilities {
+                streaming: true,
+                native_tools: true,
+                structured_output: true,
+                tool_choice: true,
+                ..Default::default()
+            })
+            .embedding_model("text-embedding-3-small")
+    }
+}
+
+impl OpenAIProviderBuilder {
+    /// Set the organization sent as the `OpenAI-Organization` header
+    pub fn organization(self, organization: impl Into<String>) -> Self {
+        self.header("OpenAI-Organization", organization)
+    }
+}
+
+delegate_llm_provider!(OpenAIProvider);
+
+impl OpenAIProvider {
+    async fn api_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let url = format!("{}{}", self.inner.base_url, path);
+        let response = self
+            .inner
+            .send(|client| {
+                let request = client.request(method.clone(), &url);
+                match &body {
+                    Some(body) => request.json(body),
+                    None => request,
+                }
+            })
+            .await?;
+        response
+            .json()
+            .await
+            .map_err(|e| ProviderError::ParseError(e.to_string()))
+    }
+
+    async fn file_content(&self, file_id: &str) -> Result<String> {
+        let url = format!("{}/files/{}/content", self.inner.base_url, file_id);
+        let response = self.inner.send(|client| client.get(&url)).await?;
+        response
+            .text()
+            .await
+            .map_err(|e| ProviderError::ParseError(e.to_string()))
+    }
+}
+
+/// Embeddings through `/embeddings`, with `text-embedding-3-small` unless
+/// the request or builder names another model
+impl EmbeddingProvider for OpenAIProvider {
+    fn create_embeddings(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<EmbeddingResponse>> + Send + '_>> {
+        self.inner.create_embeddings(request)
+    }
+}
+
+/// Fine-tuning through the `/files` and `/fine_tuning/jobs` endpoints
+impl FineTuneProvider for OpenAIProvider {
+    fn upload_training_file(
+        &self,
+        filename: String,
+        contents: Vec<u8>,
+    ) -> Pin<Box<dyn Future<Output = Result<TrainingFile>> + Send + '_>> {
+        Box::pin(async move {
+            let upload = FileUpload::new(filename, contents)
+                .with_media_type("application/jsonl")
+                .with_purpose("fine-tune");
+            let file = self.upload_file(upload).await?;
+            Ok(TrainingFile {
+                id: file.id,
+                filename: file.filename,
+                bytes: file.bytes,
+            })
+        })
+    }
+
+    fn create_fine_tune(
+        &self,
+        request: CreateFineTuneRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + '_>> {
+        Box::pin(async move {
+            let json = self
+                .api_request(
+                    reqwest::Method::POST,
+                    "/fine_tuning/jobs",
+                    Some(fine_tune_body(&request)),
+                )
+                .await?;
+            parse_fine_tune_job(&json)
+        })
+    }
+
+    fn get_fine_tune<'a>(
+        &'a self,
+        job_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>> {
+        Box::pin(async move {
+            let path = format!("/fine_tuning/jobs/{}", job_id);
+            let json = self.api_request(reqwest::Method::GET, &path, None).await?;
+            parse_fine_tune_job(&json)
+        })
+    }
+
+    fn list_checkpoints<'a>(
+        &'a self,
+        job_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<FineTuneCheckpoint>>> + Send + 'a>> {
+        Box::pin(async move {
+            let path = format!("/fine_tuning/jobs/{}/checkpoints", job_id);
+            let json = self.api_request(reqwest::Method::GET, &path, None).await?;
+            json["data"]
+                .as_array()
+                .map(|data| data.iter().map(parse_checkpoint).collect())
+                .unwrap_or_else(|| Ok(Vec::new()))
+        })
+    }
+
+    fn cancel_fine_tune<'a>(
+        &'a self,
+        job_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<FineTuneJob>> + Send + 'a>> {
+        Box::pin(async move {
+            let path = format!("/fine_tuning/jobs/{}/cancel", job_id);
+            let json = self.api_request(reqwest::Method::POST, &path, None).await?;
+            parse_fine_tune_job(&json)
+        })
+    }
+}
+
+/// Images through `/images/generations`, with `gpt-image-1` unless the
+/// request names another model
+impl ImageGenerationProvider for OpenAIProvider {
+    fn generate_images(
+        &self,
+        request: ImageRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ImageResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let body = image_body(&request);
+            let json = self
+                .api_request(reqwest::Method::POST, "/images/generations", Some(body))
+                .await?;
+            Ok(ImageResponse {
+                images: parse_images(&json)?,
+                model: request.model.unwrap_or_else(|| "gpt-image-1".to_string()),
+            })
+        })
+    }
+}
+
+/// Request body; DALL-E models take different quality names and need to be
+/// asked for base64 data
+fn image_body(request: &ImageRequest) -> serde_json::Value {
+    let model = request.model.as_deref().unwrap_or("gpt-image-1");
+    let mut body = serde_json::json!({
+        "model": model,
+        "prompt": request.prompt,
+        "n": request.n,
+    });
+    if let Some(size) = request.size {
+        body["size"] = serde_json::json!(size.to_string());
+    }
+    let quality = match (model, request.quality) {
+        (_, None) | ("dall-e-2", _) => None,
+        ("dall-e-3", Some(ImageQuality::High)) => Some("hd"),
+        ("dall-e-3", Some(_)) => Some("standard"),
+        (_, Some(ImageQuality::Low)) => Some("low"),
+        (_, Some(ImageQuality::Medium)) => Some("medium"),
+        (_, Some(ImageQuality::High)) => Some("high"),
+    };
+    if let Some(quality) = quality {
+        body["quality"] = serde_json::json!(quality);
+    }
+    if model.starts_with("dall-e") {
+        body["response_format"] = serde_json::json!("b64_json");
+    }
+    body
+}
+
+fn parse_images(json: &serde_json::Value) -> Result<Vec<GeneratedImage>> {
+    let media_type = format!("image/{}", json["output_format"].as_str().unwrap_or("png"));
+    let data = json["data"]
+        .as_array()
+        .ok_or_else(|| ProviderError::ParseError("Missing `data` in response".to_string()))?;
+    data.iter()
+        .map(|image| {
+            let source = if let Some(data) = image["b64_json"].as_str() {
+                ImageSource::Base64 {
+                    media_type: media_type.clone(),
+                    data: data.to_string(),
+                }
+            } else if let Some(url) = image["url"].as_str() {
+                ImageSource::Url {
+                    url: url.to_string(),
+                }
+            } else {
+                return Err(ProviderError::ParseError(
+                    "Image has neither `b64_json` nor `url`".to_string(),
+                ));
+            };
+            Ok(GeneratedImage {
+                source,
+                revised_prompt: image["revised_prompt"].as_str().map(String::from),
+            })
+        })
+        .collect()
+}
+
+/// Files through `/files`, uploaded for the "user_data" purpose unless the
+/// upload names another
+impl FileProvider for OpenAIProvider {
+    fn upload_file(
+        &self,
+        upload: FileUpload,
+    ) -> Pin<Box<dyn Future<Output = Result<ProviderFile>> + Send + '_>> {
+        Box::pin(async move {
+            let upload = match upload.purpose {
+                Some(_) => upload,
+                None => upload.with_purpose("user_data"),
+            };
+            let (content_type, body) = upload.multipart();
+            let url = format!("{}/files", self.inner.base_url);
+            let response = self
+                .inner
+                .send(|client| {
+                    client
+                        .post(&url)
+                        .header("Content-Type", content_type.as_str())
+                        .body(body.clone())
+                })
+                .await?;
+            let json: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+            parse_file(&json)
+        })
+    }
+
+    fn list_files(&self) -> Pin<Box<dyn Future<Output = Result<Vec<ProviderFile>>> + Send + '_>> {
+        Box::pin(async move {
+            let json = self
+                .api_request(reqwest::Method::GET, "/files", None)
+                .await?;
+            json["data"]
+                .as_array()
+                .map(|data| data.iter().map(parse_file).collect())
+                .unwrap_or_else(|| Ok(Vec::new()))
+        })
+    }
+
+    fn delete_file<'a>(
+        &'a self,
+        file_id: &'a str,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let path = format!("/files/{}", file_id);
+            self.api_request(reqwest::Method::DELETE, &path, None)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+/// Deferred generation through the Batch API, one chat completion per batch;
+/// batches finish within 24 hours at a discount
+impl DeferredProvider for OpenAIProvider {
+    fn submit(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<JobHandle>> + Send + '_>> {
+        Box::pin(async move {
+            self.inner.check_supported(&tools, &options)?;
+            check_deferrable(&options)?;
+            let line = batch_line(
+                self.inner
+                    .build_request_body(messages, options.clone(), false),
+                &tools,
+                options.as_ref(),
+            );
+            let upload = FileUpload::new("batch.jsonl", format!("{}\n", line).into_bytes())
+                .with_purpose("batch");
+            let file = self.upload_file(upload).await?;
+            let batch = self
+                .api_request(
+                    reqwest::Method::POST,
+                    "/batches",
+                    Some(serde_json::json!({
+                        "input_file_id": file.id,
+                        "endpoint": "/v1/chat/completions",
+                        "completion_window": "24h",
+                    })),
+                )
+                .await?;
+            Ok(JobHandle::new(required_str(&batch, "id")?, "openai"))
+        })
+    }
+
+    fn poll<'a>(
+        &'a self,
+        job: &'a JobHandle,
+    ) -> Pin<Box<dyn Future<Output = Result<JobStatus>> + Send + 'a>> {
+        Box::pin(async move {
+            let path = format!("/batches/{}", job.id);
+            let batch = self.api_request(reqwest::Method::GET, &path, None).await?;
+            match batch["status"].as_str().unwrap_or_default() {
+                "completed" => {}
+                "failed" | "expired" | "cancelling" | "cancelled" => {
+                    let reason = batch["errors"]["data"][0]["message"]
+                        .as_str()
+                        .or(batch["status"].as_str())
+                        .unwrap_or_default();
+                    return Ok(JobStatus::Failed(reason.to_string()));
+                }
+                _ => return Ok(JobStatus::Pending),
+            }
+            // A request that failed lands in the error file instead
+            let file_id = match (
+                batch["output_file_id"].as_str(),
+                batch["error_file_id"].as_str(),
+            ) {
+                (Some(id), _) | (None, Some(id)) => id,
+                (None, None) => {
+                    return Ok(JobStatus::Failed("Batch produced no output".to_string()))
+                }
+            };
+            let output = self.file_content(file_id).await?;
+            Ok(parse_batch_output(&output, self.inner.model()))
+        })
+    }
+
+    fn cancel<'a>(
+        &'a self,
+        job: &'a JobHandle,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let path = format!("/batches/{}/cancel", job.id);
+            self.api_request(reqwest::Method::POST, &path, None).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Batch input line for one chat completion
+fn batch_line(
+    mut body: serde_json::Value,
+    tools: &[ToolSchema],
+    options: Option<&GenerateOptions>,
+) -> serde_json::Value {
+    add_tools_to_body(&mut body, tools, options);
+    if let Some(body) = body.as_object_mut() {
+        body.remove("stream");
+    }
+    serde_json::json!({
+        "custom_id": "generation",
+        "method": "POST",
+        "url": "/v1/chat/completions",
+        "body": body,
+    })
+}
+
+fn parse_batch_output(output: &str, fallback_model: &str) -> JobStatus {
+    let Some(line) = output
+        .lines()
+        .find_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+    else {
+        return JobStatus::Failed("Batch output is empty".to_string());
+    };
+    let response = &line["response"];
+    if line["error"].is_object() || response["status_code"].as_u64() != Some(200) {
+        let reason = line["error"]["message"]
+            .as_str()
+            .or(response["body"]["error"]["message"].as_str())
+            .unwrap_or("Request failed");
+        return JobStatus::Failed(reason.to_string());
+    }
+    JobStatus::Completed(parse_generate_response(
+        response["body"].clone(),
+        None,
+        fallback_model,
+    ))
+}
+
+fn parse_file(json: &serde_json::Value) -> Result<ProviderFile> {
+    Ok(ProviderFile {
+        id: required_str(json, "id")?,
+        filename: json["filename"].as_str().unwrap_or_default().to_string(),
+        bytes: json["bytes"].as_u64().unwrap_or_default(),
+        purpose: json["purpose"].as_str().map(String::from),
+    })
+}
+
+fn fine_tune_body(request: &CreateFineTuneRequest) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "model": request.model,
+        "training_file": request.training_file,
+    });
+    if let Some(file) = &request.validation_file {
+        body["validation_file"] = serde_json::json!(file);
+    }
+    if let Some(suffix) = &request.suffix {
+        body["suffix"] = serde_json::json!(suffix);
+    }
+    if let Some(n_epochs) = request.n_epochs {
+        body["hyperparameters"] = serde_json::json!({ "n_epochs": n_epochs });
+    }
+    if let Some(seed) = request.seed {
+        body["seed"] = serde_json::json!(seed);
+    }
+    body
+}
+
+fn required_str(json: &serde_json::Value, field: &str) -> Result<String> {
+    json[field]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| ProviderError::ParseError(format!("Missing `{}` in response", field)))
+}
+
+fn parse_fine_tune_job(json: &serde_json::Value) -> Result<FineTuneJob> {
+    let status = match json["status"].as_str().unwrap_or_default() {
+        "validating_files" => FineTuneStatus::ValidatingFiles,
+        "queued" => FineTuneStatus::Queued,
+        "running" => FineTuneStatus::Running,
+        "succeeded" => FineTuneStatus::Succeeded,
+        "failed" => FineTuneStatus::Failed,
+        "cancelled" => FineTuneStatus::Cancelled,
+        other => FineTuneStatus::Other(other.to_string()),
+    };
+    Ok(FineTuneJob {
+        id: required_str(json, "id")?,
+        model: json["model"].as_str().unwrap_or_default().to_string(),
+        status,
+        fine_tuned_model: json["fine_tuned_model"].as_str().map(String::from),
+        trained_tokens: json["trained_tokens"].as_u64(),
+        error: json["error"]["message"].as_str().map(String::from),
+        created_at: json["created_at"].as_u64().unwrap_or_default(),
+    })
+}
+
+fn parse_checkpoint(json: &serde_json::Value) -> Result<FineTuneCheckpoint> {
+    Ok(FineTuneCheckpoint {
+        id: required_str(json, "id")?,
+        model: required_str(json, "fine_tuned_model_checkpoint")?,
+        step: json["step_number"].as_u64().unwrap_or_default(),
+        metrics: json["metrics"].clone(),
+        created_at: json["created_at"].as_u64().unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{
+        DecodingConstraint, GenerateOptions, JsonSchema, LlmProvider, Message, ResponseFormat,
+    };
+
+    fn provider() -> OpenAIProvider {
+        OpenAIProvider::new("test-key", "gpt-4o-mini").unwrap()
+    }
+
+    #[test]
+    fn builds_chat_completions_body() {
+        let options = GenerateOptions {
+            model: Some("gpt-4o".to_string()),
+            max_tokens: Some(64),
+            stop: Some(vec!["END".to_string()]),
+            ..Default::default()
+        };
+        let body = provider().inner.build_request_body(
+            vec![Message::system("be brief"), Message::user("hi")],
+            Some(options),
+            true,
+        );
+
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["max_tokens"], 64);
+        assert_eq!(body["stop"][0], "END");
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "hi");
+    }
+
+    #[test]
+    fn sends_json_schema_response_format() {
+        let schema = JsonSchema::new("answer", serde_json::json!({"type": "object"})).strict(true);
+        let options = GenerateOptions {
+            response_format: Some(ResponseFormat::JsonSchema(schema)),
+            ..Default::default()
+        };
+        let provider = provider();
+        let options = Some(options);
+        assert!(provider.inner.check_supported(&[], &options).is_ok());
+
+        let body = provider
+            .inner
+            .build_request_body(vec![Message::user("hi")], options, false);
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["name"], "answer");
+        assert_eq!(body["response_format"]["json_schema"]["strict"], true);
+    }
+
+    #[test]
+    fn rejects_prefill_and_constraints() {
+        let provider = provider();
+        let prefill = GenerateOptions {
+            assistant_prefix: Some("{".to_string()),
+            ..Default::default()
+        };
+        assert!(provider.inner.check_supported(&[], &Some(prefill)).is_err());
+
+        let constrained = GenerateOptions {
+            constraint: Some(DecodingConstraint::Regex("\\d+".to_string())),
+            ..Default::default()
+        };
+        assert!(provider
+            .inner
+            .check_supported(&[], &Some(constrained))
+            .is_err());
+        assert!(provider.inner.check_supported(&[], &None).is_ok());
+    }
+
+    #[test]
+    fn defaults_to_openai_base_url() {
+        let provider = OpenAIProvider::builder()
+            .api_key("test-key")
+            .model("gpt-4o-mini")
+            .organization("org-123")
+            .build()
+            .unwrap();
+        assert_eq!(provider.inner.base_url, "https://api.openai.com/v1");
+        assert_eq!(
+            provider.inner.headers,
+            vec![("OpenAI-Organization".to_string(), "org-123".to_string())]
+        );
+        assert!(!provider.capabilities().assistant_prefill);
+    }
+
+    #[test]
+    fn parses_fine_tune_jobs_and_checkpoints() {
+        let job = parse_fine_tune_job(&serde_json::json!({
+            "id": "ftjob-abc",
+            "model": "gpt-4o-mini",
+            "status": "failed",
+            "fine_tuned_model": null,
+            "error": {"message": "Training file is invalid"},
+            "created_at": 1_700_000_000u64
+        }))
+        .unwrap();
+        assert_eq!(job.status, FineTuneStatus::Failed);
+        assert!(job.status.is_terminal());
+        assert_eq!(job.error.as_deref(), Some("Training file is invalid"));
+        assert_eq!(job.fine_tuned_model, None);
+
+        let checkpoint = parse_checkpoint(&serde_json::json!({
+            "id": "ftckpt-1",
+            "fine_tuned_model_checkpoint": "ft:gpt-4o-mini:org::abc:ckpt-step-100",
+            "step_number": 100,
+            "metrics": {"train_loss": 0.42}
+        }))
+        .unwrap();
+        assert_eq!(checkpoint.step, 100);
+        assert_eq!(checkpoint.metrics["train_loss"], 0.42);
+
+        assert!(parse_fine_tune_job(&serde_json::json!({"status": "queued"})).is_err());
+    }
+
+    #[test]
+    fn builds_fine_tune_requests() {
+        let request = CreateFineTuneRequest::new("gpt-4o-mini", "file-abc").with_epochs(2);
+        let body = fine_tune_body(&request);
+        assert_eq!(body["training_file"], "file-abc");
+        assert_eq!(body["hyperparameters"]["n_epochs"], 2);
+        assert!(body.get("suffix").is_none());
+
+        let file = parse_file(&serde_json::json!({
+            "id": "file-abc",
+            "filename": "train.jsonl",
+            "bytes": 120,
+            "purpose": "fine-tune"
+        }))
+        .unwrap();
+        assert_eq!(file.bytes, 120);
+        assert_eq!(file.purpose.as_deref(), Some("fine-tune"));
+    }
+
+    #[test]
+    fn references_uploaded_files() {
+        let body = provider().inner.build_request_body(
+            vec![Message::user_with_file("summarize this", "file-abc")],
+            None,
+            false,
+        );
+        let content = &body["messages"][0]["content"];
+        assert_eq!(content[0]["type"], "text");
+        assert_eq!(content[1]["type"], "file");
+        assert_eq!(content[1]["file"]["file_id"], "file-abc");
+    }
+
+    #[test]
+    fn builds_image_requests_per_model() {
+        let request = ImageRequest::new("a fox").with_quality(ImageQuality::High);
+        let body = image_body(&request);
+        assert_eq!(body["model"], "gpt-image-1");
+        assert_eq!(body["quality"], "high");
+        assert!(body.get("response_format").is_none());
+
+        let body = image_body(&request.with_model("dall-e-3"));
+        assert_eq!(body["quality"], "hd");
+        assert_eq!(body["response_format"], "b64_json");
+
+        let images = parse_images(&serde_json::json!({
+            "output_format": "webp",
+            "data": [
+                {"b64_json": "AAAA", "revised_prompt": "a red fox"},
+                {"url": "https://example.com/fox.png"}
+            ]
+        }))
+        .unwrap();
+        assert!(matches!(
+            &images[0].source,
+            ImageSource::Base64 { media_type, .. } if media_type == "image/webp"
+        ));
+        assert_eq!(images[0].revised_prompt.as_deref(), Some("a red fox"));
+        assert!(matches!(&images[1].source, ImageSource::Url { .. }));
+    }
+
+    #[test]
+    fn builds_and_parses_batch_lines() {
+        let p = provider();
+        let body = p
+            .inner
+            .build_request_body(vec![Message::user("hi")], None, false);
+        let line = batch_line(body, &[], None);
+        assert_eq!(line["url"], "/v1/chat/completions");
+        assert_eq!(line["body"]["model"], "gpt-4o-mini");
+        assert!(line["body"].get("stream").is_none());
+
+        let output = serde_json::json!({
+            "custom_id": "generation",
+            "response": {"status_code": 200, "body": {
+                "model": "gpt-4o-mini",
+                "choices": [{"message": {"role": "assistant", "content": "later"},
+                             "finish_reason": "stop"}]
+            }},
+            "error": null
+        });
+        match parse_batch_output(&format!("{}\n", output), "fallback") {
+            JobStatus::Completed(resp) => assert_eq!(resp.content, "later"),
+            other => panic!("unexpected status: {:?}", other),
+        }
+
+        let output = serde_json::json!({
+            "response": {"status_code": 400, "body": {"error": {"message": "Bad model"}}},
+            "error": null
+        });
+        assert!(matches!(
+            parse_batch_output(&output.to_string(), "fallback"),
+            JobStatus::Failed(reason) if reason == "Bad model"
+        ));
+    }
+}
@@ -0,0 +1,1621 @@
+//! Provider base for servers that speak the OpenAI chat completions format.
+//!
+//! `OpenAiCompatProvider` owns the whole request pipeline (context window,
+//! cache, middleware, retries, streaming). Vendor providers such as
+//! OpenRouter, OpenAI, Mistral and Groq wrap it and only preset the base URL,
+//! authentication, capabilities and model validation. It also implements
+//! `EmbeddingProvider` against the `/embeddings` endpoint.
+
+use super::pipeline::RequestPipeline;
+use super::stream_decode::{sse_data, LineDecoder};
+use super::timeout::StreamDeadline;
+use super::{
+    CacheConfig, ContextWindowConfig, ContextWindowManager, DecodingConstraint, EmbeddingProvider,
+    EmbeddingRequest, EmbeddingResponse, EmbeddingUsage, EncodingFormat, GenerateOptions,
+    GenerateResponse, KeepAliveConfig, LlmProvider, Message, MiddlewareChain, ProviderCapabilities,
+    ProviderClient, ProviderClientBuilder, ProviderError, RateLimitConfig, ResponseCache,
+    ResponseFormat, Result, RetryConfig, Role, StreamEvent, StreamEvents, TimeoutConfig,
+    ToolSchema, ToolSelection, Usage,
+};
+use base64::Engine;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+/// How the API key is sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthHeader {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// The raw key in the named header, e.g. `api-key` for Azure OpenAI
+    Header(String),
+}
+
+fn accept_any_model(_model: &str) -> Result<()> {
+    Ok(())
+}
+
+/// Generic OpenAI-compatible provider (vLLM, llama.cpp server, LM Studio, ...)
+pub struct OpenAiCompatProvider {
+    name: String,
+    api_key: Option<String>,
+    auth: AuthHeader,
+    model: String,
+    client: ProviderClient,
+    pub(super) base_url: String,
+    pub(super) headers: Vec<(String, String)>,
+    body_fields: serde_json::Map<String, serde_json::Value>,
+    capabilities: ProviderCapabilities,
+    stream_usage: bool,
+    validate_model: fn(&str) -> Result<()>,
+    middleware: Option<MiddlewareChain>,
+    cache: Option<ResponseCache>,
+    context_manager: Option<ContextWindowManager>,
+    embedding_model: Option<String>,
+    embedding_batch_size: usize,
+}
+
+impl OpenAiCompatProvider {
+    /// Create a builder; `base_url` and `model` are required
+    pub fn builder() -> OpenAiCompatProviderBuilder {
+        OpenAiCompatProviderBuilder::default()
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+
+    /// Reject options the server is not declared to support
+    pub(super) fn check_supported(
+        &self,
+        tools: &[ToolSchema],
+        options: &Option<GenerateOptions>,
+    ) -> Result<()> {
+        if !tools.is_empty() && !self.capabilities.native_tools {
+            return Err(ProviderError::Other(format!(
+                "Native tool calling is not supported by the {} provider",
+                self.name
+            )));
+        }
+        let Some(opts) = options else {
+            return Ok(());
+        };
+        if let Some(model) = &opts.model {
+            (self.validate_model)(model)?;
+        }
+        match &opts.constraint {
+            Some(DecodingConstraint::Grammar(_)) if !self.capabilities.grammar => {
+                return Err(ProviderError::Other(format!(
+                    "Grammar-constrained decoding is not supported by the {} provider",
+                    self.name
+                )));
+            }
+            Some(DecodingConstraint::Regex(_)) if !self.capabilities.regex => {
+                return Err(ProviderError::Other(format!(
+                    "Regex-constrained decoding is not supported by the {} provider",
+                    self.name
+                )));
+            }
+            _ => {}
+        }
+        if opts.assistant_prefix.is_some() && !self.capabilities.assistant_prefill {
+            return Err(ProviderError::Other(format!(
+                "Assistant prefill is not supported by the {} provider",
+                self.name
+            )));
+        }
+        if opts.response_format.is_some() && !self.capabilities.structured_output {
+            return Err(ProviderError::Other(format!(
+                "Structured output is not supported by the {} provider",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    pub(super) fn build_request_body(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+        stream: bool,
+    ) -> serde_json::Value {
+        let opts = options.unwrap_or_default();
+
+        let mut messages_json: Vec<serde_json::Value> =
+            messages.iter().flat_map(format_message).collect();
+
+        if let Some(prefix) = &opts.assistant_prefix {
+            messages_json.push(serde_json::json!({
+                "role": "assistant",
+                "content": prefix,
+            }));
+        }
+
+        let mut body = serde_json::json!({
+            "model": opts.model.as_deref().unwrap_or(&self.model),
+            "messages": messages_json,
+            "stream": stream,
+        });
+
+        if stream && self.stream_usage {
+            body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        if let Some(temp) = opts.temperature {
+            body["temperature"] = serde_json::json!(temp);
+        }
+        if let Some(max) = opts.max_tokens {
+            body["max_tokens"] = serde_json::json!(max);
+        }
+        if let Some(top_p) = opts.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(stop) = opts.stop {
+            body["stop"] = serde_json::json!(stop);
+        }
+        // Field names follow llama.cpp (`grammar`) and vLLM (`guided_regex`)
+        match opts.constraint {
+            Some(DecodingConstraint::Grammar(grammar)) => {
+                body["grammar"] = serde_json::json!(grammar);
+            }
+            Some(DecodingConstraint::Regex(regex)) => {
+                body["guided_regex"] = serde_json::json!(regex);
+            }
+            None => {}
+        }
+        match opts.response_format {
+            Some(ResponseFormat::Json) => {
+                body["response_format"] = serde_json::json!({ "type": "json_object" });
+            }
+            Some(ResponseFormat::JsonSchema(schema)) => {
+                body["response_format"] = serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": schema.name,
+                        "schema": schema.schema,
+                        "strict": schema.strict,
+                    },
+                });
+            }
+            None => {}
+        }
+
+        let extensions = opts.extensions.for_provider(&self.name);
+        for (name, value) in self
+            .body_fields
+            .iter()
+            .chain(&extensions)
+            .chain(opts.extra_body.iter().flatten())
+        {
+            body[name] = value.clone();
+        }
+
+        body
+    }
+
+    fn pipeline(&self) -> RequestPipeline<'_> {
+        RequestPipeline {
+            model: &self.model,
+            context_manager: self.context_manager.as_ref(),
+            cache: self.cache.as_ref(),
+            middleware: self.middleware.as_ref(),
+        }
+    }
+
+    async fn generate_inner(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Result<GenerateResponse> {
+        self.check_supported(&tools, &options)?;
+        let tools = &tools;
+        self.pipeline()
+            .generate(messages, tools, options, |messages, options| async move {
+                let mut body = self.build_request_body(messages, options.clone(), false);
+                add_tools_to_body(&mut body, tools, options.as_ref());
+                let response = self.send_request(body).await?;
+                let json: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+                Ok(parse_generate_response(json, options.as_ref(), &self.model))
+            })
+            .await
+    }
+
+    async fn send_request(&self, body: serde_json::Value) -> Result<reqwest::Response> {
+        let streaming = body["stream"] == true;
+        self.send(|client| {
+            let request = client
+                .post(format!("{}/chat/completions", self.base_url))
+                .header("Content-Type", "application/json")
+                .json(&body);
+            if streaming {
+                self.client.stream_request(request)
+            } else {
+                request
+            }
+        })
+        .await
+    }
+
+    /// Send the request made by `build` with authentication, extra headers,
+    /// rate limiting and retries
+    pub(super) async fn send<F>(&self, build: F) -> Result<reqwest::Response>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let _guard = self.client.acquire_rate_limit().await;
+
+        self.client
+            .retry_policy()
+            .execute_with_retry(|| async {
+                let mut request = build(self.client.http_client());
+                if let Some(key) = &self.api_key {
+                    request = match &self.auth {
+                        AuthHeader::Bearer => {
+                            request.header("Authorization", format!("Bearer {}", key))
+                        }
+                        AuthHeader::Header(name) => request.header(name.as_str(), key),
+                    };
+                }
+                for (name, value) in &self.headers {
+                    request = request.header(name.as_str(), value.as_str());
+                }
+
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+
+                let status = response.status();
+                if status == reqwest::StatusCode::UNAUTHORIZED {
+                    return Err(ProviderError::AuthenticationFailed(
+                        "Invalid API key".to_string(),
+                    ));
+                }
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    let retry_after = response
+                        .headers()
+                        .get("retry-after")
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|s| s.parse().ok());
+                    return Err(ProviderError::RateLimited { retry_after });
+                }
+                if status == reqwest::StatusCode::NOT_FOUND {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::ModelNotAvailable(text));
+                }
+                if !status.is_success() {
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(ProviderError::RequestFailed(format!(
+                        "{}: {}",
+                        status, text
+                    )));
+                }
+
+                Ok(response)
+            })
+            .await
+    }
+}
+
+/// Builder for `OpenAiCompatProvider` and the vendor providers wrapping it
+///
+/// `P` is the provider produced by `build`; vendor constructors return a
+/// builder with their base URL, authentication and capabilities preset.
+pub struct OpenAiCompatProviderBuilder<P = OpenAiCompatProvider> {
+    name: String,
+    api_key: Option<String>,
+    api_key_required: bool,
+    auth: AuthHeader,
+    model: Option<String>,
+    base_url: Option<String>,
+    headers: Vec<(String, String)>,
+    body_fields: serde_json::Map<String, serde_json::Value>,
+    capabilities: ProviderCapabilities,
+    stream_usage: bool,
+    validate_model: fn(&str) -> Result<()>,
+    client_builder: ProviderClientBuilder,
+    middleware: Option<MiddlewareChain>,
+    cache_config: Option<CacheConfig>,
+    context_config: Option<ContextWindowConfig>,
+    embedding_model: Option<String>,
+    embedding_batch_size: usize,
+    _provider: PhantomData<fn() -> P>,
+}
+
+impl Default for OpenAiCompatProviderBuilder {
+    fn default() -> Self {
+        Self::new("openai-compatible")
+    }
+}
+
+impl<P> OpenAiCompatProviderBuilder<P> {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            api_key: None,
+            api_key_required: false,
+            auth: AuthHeader::Bearer,
+            model: None,
+            base_url: None,
+            headers: Vec::new(),
+            body_fields: serde_json::Map::new(),
+            capabilities: ProviderCapabilities {
+                streaming: true,
+                native_tools: true,
+                tool_choice: true,
+                ..Default::default()
+            },
+            stream_usage: true,
+            validate_model: accept_any_model,
+            client_builder: ProviderClient::builder(),
+            middleware: None,
+            cache_config: None,
+            context_config: None,
+            embedding_model: None,
+            embedding_batch_size: 2048,
+            _provider: PhantomData,
+        }
+    }
+
+    /// Preset used by vendor providers: named, keyed and pointed at `base_url`
+    pub(super) fn vendor(name: &str, base_url: &str) -> Self {
+        let mut builder = Self::new(name);
+        builder.api_key_required = true;
+        builder.base_url = Some(base_url.to_string());
+        builder
+    }
+
+    /// Set the provider name reported by `LlmProvider::name`
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the API key
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Set how the API key is sent
+    pub fn auth_header(mut self, auth: AuthHeader) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Set the model
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the base URL
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// Add a header sent with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add a top-level field to every request body, for vendor parameters the
+    /// SDK does not model
+    pub fn body_field(mut self, name: impl Into<String>, value: serde_json::Value) -> Self {
+        self.body_fields.insert(name.into(), value);
+        self
+    }
+
+    /// Declare what the server supports; unsupported options are rejected
+    pub fn capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Request usage in the final stream chunk via `stream_options`
+    pub fn stream_usage(mut self, enabled: bool) -> Self {
+        self.stream_usage = enabled;
+        self
+    }
+
+    /// Validate model ids at build time and on per-request overrides
+    pub fn model_validator(mut self, validate: fn(&str) -> Result<()>) -> Self {
+        self.validate_model = validate;
+        self
+    }
+
+    /// Set the retry configuration
+    pub fn retry_config(mut self, config: RetryConfig) -> Self {
+        self.client_builder = self.client_builder.retry_config(config);
+        self
+    }
+
+    /// Set the timeout configuration
+    pub fn timeout_config(mut self, config: TimeoutConfig) -> Self {
+        self.client_builder = self.client_builder.timeout_config(config);
+        self
+    }
+
+    /// Set the rate limit configuration
+    pub fn rate_limit_config(mut self, config: RateLimitConfig) -> Self {
+        self.client_builder = self.client_builder.rate_limit_config(config);
+        self
+    }
+
+    /// Set the connection pool and keep-alive configuration
+    pub fn keep_alive_config(mut self, config: KeepAliveConfig) -> Self {
+        self.client_builder = self.client_builder.keep_alive_config(config);
+        self
+    }
+
+    /// Set a proxy URL
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.client_builder = self.client_builder.proxy(proxy);
+        self
+    }
+
+    /// Set the middleware chain
+    pub fn middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = Some(middleware);
+        self
+    }
+
+    /// Enable response caching with the given configuration
+    pub fn cache_config(mut self, config: CacheConfig) -> Self {
+        self.cache_config = Some(config);
+        self
+    }
+
+    /// Enable context window management with the given configuration
+    pub fn context_config(mut self, config: ContextWindowConfig) -> Self {
+        self.context_config = Some(config);
+        self
+    }
+
+    /// Disable retries
+    pub fn no_retry(mut self) -> Self {
+        self.client_builder = self.client_builder.no_retry();
+        self
+    }
+
+    /// Disable rate limiting
+    pub fn no_rate_limit(mut self) -> Self {
+        self.client_builder = self.client_builder.no_rate_limit();
+        self
+    }
+
+    /// Model for embedding requests that do not name one
+    pub fn embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    /// Most inputs sent in one `/embeddings` request; larger requests are
+    /// split and their results joined in order. 2048 by default
+    pub fn embedding_batch_size(mut self, size: usize) -> Self {
+        self.embedding_batch_size = size.max(1);
+        self
+    }
+}
+
+impl<P: From<OpenAiCompatProvider>> OpenAiCompatProviderBuilder<P> {
+    /// Build the provider
+    pub fn build(self) -> Result<P> {
+        if self.api_key_required && self.api_key.is_none() {
+            return Err(ProviderError::RequestFailed(
+                "API key is required".to_string(),
+            ));
+        }
+
+        let model = self
+            .model
+            .ok_or_else(|| ProviderError::RequestFailed("Model is required".to_string()))?;
+        (self.validate_model)(&model)?;
+
+        let base_url = self
+            .base_url
+            .ok_or_else(|| ProviderError::RequestFailed("Base URL is required".to_string()))?;
+
+        let client = self.client_builder.build()?;
+
+        let cache = self.cache_config.map(ResponseCache::new);
+        let context_manager = self.context_config.map(ContextWindowManager::new);
+
+        Ok(P::from(OpenAiCompatProvider {
+            name: self.name,
+            api_key: self.api_key,
+            auth: self.auth,
+            model,
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: self.headers,
+            body_fields: self.body_fields,
+            capabilities: self.capabilities,
+            stream_usage: self.stream_usage,
+            validate_model: self.validate_model,
+            middleware: self.middleware,
+            cache,
+            context_manager,
+            embedding_model: self.embedding_model,
+            embedding_batch_size: self.embedding_batch_size,
+        }))
+    }
+}
+
+impl LlmProvider for OpenAiCompatProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities.clone()
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, Vec::new(), options))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.generate_inner(messages, tools, options))
+    }
+
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<super::StreamResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let events = self
+                .generate_stream_events(messages, Vec::new(), options)
+                .await?;
+            Ok(super::StreamResponse::from_events(events))
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        Box::pin(async move {
+            self.check_supported(&tools, &options)?;
+            let prefix = options.as_ref().and_then(|o| o.assistant_prefix.clone());
+            let tool_options = options.clone();
+            let mut body = self.build_request_body(messages, options, true);
+            add_tools_to_body(&mut body, &tools, tool_options.as_ref());
+            let deadline = self.client.stream_deadline();
+            let response = self.send_request(body).await?;
+
+            Ok(spawn_event_stream(response, prefix, deadline))
+        })
+    }
+
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(self.client.preconnect(&self.base_url))
+    }
+
+    fn rate_limiter(&self) -> Option<&super::RateLimiter> {
+        Some(self.client.rate_limiter())
+    }
+}
+
+impl OpenAiCompatProvider {
+    async fn create_embeddings_inner(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Result<EmbeddingResponse> {
+        let model = request
+            .model
+            .or_else(|| self.embedding_model.clone())
+            .ok_or_else(|| {
+                ProviderError::ModelNotAvailable(format!(
+                    "{} has no embedding model set",
+                    self.name
+                ))
+            })?;
+        let url = format!("{}/embeddings", self.base_url);
+
+        let mut embeddings = Vec::with_capacity(request.input.len());
+        let mut usage: Option<EmbeddingUsage> = None;
+        let mut response_model = model.clone();
+        for batch in request.input.chunks(self.embedding_batch_size) {
+            let mut body = serde_json::json!({ "model": model, "input": batch });
+            if let Some(format) = request.encoding_format {
+                body["encoding_format"] = serde_json::json!(match format {
+                    EncodingFormat::Float => "float",
+                    EncodingFormat::Base64 => "base64",
+                });
+            }
+            let json: serde_json::Value = self
+                .send(|client| client.post(&url).json(&body))
+                .await?
+                .json()
+                .await
+                .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+
+            embeddings.extend(parse_embeddings(&json, batch.len())?);
+            if let Some(u) = json.get("usage") {
+                let total = usage.get_or_insert(EmbeddingUsage {
+                    prompt_tokens: 0,
+                    total_tokens: 0,
+                });
+                total.prompt_tokens += u["prompt_tokens"].as_u64().unwrap_or(0) as u32;
+                total.total_tokens += u["total_tokens"].as_u64().unwrap_or(0) as u32;
+            }
+            if let Some(name) = json["model"].as_str() {
+                response_model = name.to_string();
+            }
+        }
+
+        Ok(EmbeddingResponse {
+            embeddings,
+            model: response_model,
+            usage,
+        })
+    }
+}
+
+/// Embeddings through `/embeddings`, in batches of `embedding_batch_size`
+/// inputs with usage summed across them
+impl EmbeddingProvider for OpenAiCompatProvider {
+    fn create_embeddings(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<EmbeddingResponse>> + Send + '_>> {
+        Box::pin(self.create_embeddings_inner(request))
+    }
+}
+
+/// Vectors of an `/embeddings` response in input order, from float arrays
+/// or base64-encoded little-endian `f32`s
+fn parse_embeddings(json: &serde_json::Value, expected: usize) -> Result<Vec<Vec<f32>>> {
+    let data = json["data"]
+        .as_array()
+        .ok_or_else(|| ProviderError::ParseError("Missing `data` in response".to_string()))?;
+    let mut indexed = data
+        .iter()
+        .enumerate()
+        .map(|(position, item)| {
+            let index = item["index"].as_u64().map_or(position, |i| i as usize);
+            let embedding = match &item["embedding"] {
+                serde_json::Value::Array(values) => values
+                    .iter()
+                    .map(|v| v.as_f64().map(|v| v as f32))
+                    .collect::<Option<Vec<f32>>>(),
+                serde_json::Value::String(encoded) => base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .ok()
+                    .filter(|bytes| bytes.len() % 4 == 0)
+                    .map(|bytes| {
+                        bytes
+                            .chunks_exact(4)
+                            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                            .collect()
+                    }),
+                _ => None,
+            };
+            embedding
+                .map(|embedding| (index, embedding))
+                .ok_or_else(|| {
+                    ProviderError::ParseError(format!("Invalid embedding at index {}", index))
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if indexed.len() != expected {
+        return Err(ProviderError::ParseError(format!(
+            "Expected {} embeddings, got {}",
+            expected,
+            indexed.len()
+        )));
+    }
+    indexed.sort_by_key(|(index, _)| *index);
+    Ok(indexed
+        .into_iter()
+        .map(|(_, embedding)| embedding)
+        .collect())
+}
+
+/// Implement `LlmProvider` for a vendor wrapper by delegating to its `inner`
+/// `OpenAiCompatProvider`
+macro_rules! delegate_llm_provider {
+    ($provider:ty) => {
+        impl From<$crate::provider::OpenAiCompatProvider> for $provider {
+            fn from(inner: $crate::provider::OpenAiCompatProvider) -> Self {
+                Self { inner }
+            }
+        }
+
+        impl $crate::provider::LlmProvider for $provider {
+            fn name(&self) -> &str {
+                self.inner.name()
+            }
+
+            fn model(&self) -> &str {
+                self.inner.model()
+            }
+
+            fn capabilities(&self) -> $crate::provider::ProviderCapabilities {
+                self.inner.capabilities()
+            }
+
+            fn generate(
+                &self,
+                messages: Vec<$crate::provider::Message>,
+                options: Option<$crate::provider::GenerateOptions>,
+            ) -> std::pin::Pin<
+                Box<
+                    dyn std::future::Future<
+                            Output = $crate::provider::Result<$crate::provider::GenerateResponse>,
+                        > + Send
+                        + '_,
+                >,
+            > {
+                self.inner.generate(messages, options)
+            }
+
+            fn generate_with_tools(
+                &self,
+                messages: Vec<$crate::provider::Message>,
+                tools: Vec<$crate::provider::ToolSchema>,
+                options: Option<$crate::provider::GenerateOptions>,
+            ) -> std::pin::Pin<
+                Box<
+                    dyn std::future::Future<
+                            Output = $crate::provider::Result<$crate::provider::GenerateResponse>,
+                        > + Send
+                        + '_,
+                >,
+            > {
+                self.inner.generate_with_tools(messages, tools, options)
+            }
+
+            fn generate_stream(
+                &self,
+                messages: Vec<$crate::provider::Message>,
+                options: Option<$crate::provider::GenerateOptions>,
+            ) -> std::pin::Pin<
+                Box<
+                    dyn std::future::Future<
+                            Output = $crate::provider::Result<$crate::provider::StreamResponse>,
+                        > + Send
+                        + '_,
+                >,
+            > {
+                self.inner.generate_stream(messages, options)
+            }
+
+            fn generate_stream_events(
+                &self,
+                messages: Vec<$crate::provider::Message>,
+                tools: Vec<$crate::provider::ToolSchema>,
+                options: Option<$crate::provider::GenerateOptions>,
+            ) -> std::pin::Pin<
+                Box<
+                    dyn std::future::Future<
+                            Output = $crate::provider::Result<$crate::provider::StreamEvents>,
+                        > + Send
+                        + '_,
+                >,
+            > {
+                self.inner.generate_stream_events(messages, tools, options)
+            }
+
+            fn preconnect(
+                &self,
+            ) -> std::pin::Pin<
+                Box<dyn std::future::Future<Output = $crate::provider::Result<()>> + Send + '_>,
+            > {
+                self.inner.preconnect()
+            }
+
+            fn rate_limiter(&self) -> Option<&$crate::provider::RateLimiter> {
+                self.inner.rate_limiter()
+            }
+        }
+    };
+}
+pub(super) use delegate_llm_provider;
+
+/// Format a message in the OpenAI chat format.
+///
+/// Tool results become separate `tool` role messages and tool calls are
+/// attached to the assistant message as `tool_calls`.
+pub(super) fn format_message(m: &Message) -> Vec<serde_json::Value> {
+    use super::ContentBlock;
+
+    let role = match m.role {
+        Role::System => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+    };
+
+    let mut formatted: Vec<serde_json::Value> = m
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                ..
+            } => Some(serde_json::json!({
+                "role": "tool",
+                "tool_call_id": tool_use_id,
+                "content": content,
+            })),
+            _ => None,
+        })
+        .collect();
+
+    let tool_calls: Vec<serde_json::Value> = m
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, input } => Some(serde_json::json!({
+                "id": id,
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "arguments": input.to_string(),
+                },
+            })),
+            _ => None,
+        })
+        .collect();
+
+    let content_blocks: Vec<ContentBlock> = m
+        .content
+        .iter()
+        .filter(|block| {
+            matches!(
+                block,
+                ContentBlock::Text { .. } | ContentBlock::Image { .. } | ContentBlock::File { .. }
+            )
+        })
+        .cloned()
+        .collect();
+
+    if content_blocks.is_empty() && tool_calls.is_empty() && !formatted.is_empty() {
+        return formatted;
+    }
+
+    // Format content - OpenAI format supports both string and array
+    let content = match content_blocks.as_slice() {
+        // Single text block - use string format
+        [ContentBlock::Text { text }] => serde_json::json!(text),
+        // Assistant turn that only carries tool calls
+        [] if !tool_calls.is_empty() => serde_json::Value::Null,
+        // Images or multiple blocks - use array format
+        blocks => format_content_blocks(blocks),
+    };
+
+    let mut message = serde_json::json!({
+        "role": role,
+        "content": content,
+    });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = serde_json::json!(tool_calls);
+    }
+    formatted.push(message);
+    formatted
+}
+
+pub(super) fn add_tools_to_body(
+    body: &mut serde_json::Value,
+    tools: &[ToolSchema],
+    options: Option<&GenerateOptions>,
+) {
+    if tools.is_empty() {
+        return;
+    }
+    if let Some(parallel) = options.and_then(|o| o.parallel_tool_calls) {
+        body["parallel_tool_calls"] = serde_json::json!(parallel);
+    }
+    match options.and_then(|o| o.tool_choice.as_ref()) {
+        Some(ToolSelection::Any) => body["tool_choice"] = serde_json::json!("required"),
+        Some(ToolSelection::Tool(name)) => {
+            body["tool_choice"] =
+                serde_json::json!({"type": "function", "function": {"name": name}})
+        }
+        None => {}
+    }
+    body["tools"] = serde_json::json!(tools
+        .iter()
+        .map(|tool| serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": tool.parameters,
+            },
+        }))
+        .collect::<Vec<_>>());
+}
+
+/// Extract native tool calls from a chat completion message
+pub(super) fn parse_tool_calls(message: &serde_json::Value) -> Vec<crate::tool::ToolCall> {
+    message["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .map(|call| {
+                    let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+                    crate::tool::ToolCall {
+                        id: call["id"].as_str().unwrap_or_default().to_string(),
+                        name: call["function"]["name"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        parameters: serde_json::from_str(arguments)
+                            .unwrap_or_else(|_| serde_json::Value::String(arguments.to_string())),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Translate one streamed chunk into typed events
+///
+/// A `finish_reason` is reported as `Done`; the caller holds it back until
+/// the trailing usage chunk and `[DONE]` marker have been read.
+pub(super) fn parse_stream_chunk(json: &serde_json::Value) -> Vec<StreamEvent> {
+    let mut events = Vec::new();
+    let choice = &json["choices"][0];
+
+    if let Some(content) = choice["delta"]["content"].as_str() {
+        if !content.is_empty() {
+            events.push(StreamEvent::TextDelta(content.to_string()));
+        }
+    }
+
+    if let Some(calls) = choice["delta"]["tool_calls"].as_array() {
+        for (position, call) in calls.iter().enumerate() {
+            events.push(StreamEvent::ToolCallDelta {
+                index: call["index"].as_u64().map_or(position, |i| i as usize),
+                id: call["id"].as_str().map(String::from),
+                name: call["function"]["name"].as_str().map(String::from),
+                arguments_delta: call["function"]["arguments"]
+                    .as_str()
+                    .unwrap_or("")
+                    .to_string(),
+            });
+        }
+    }
+
+    // Groq reports streaming usage under `x_groq` instead of `usage`
+    let usage = json
+        .get("usage")
+        .filter(|u| u.is_object())
+        .or_else(|| json["x_groq"].get("usage"));
+    if let Some(usage) = usage {
+        events.push(StreamEvent::UsageUpdate(Usage {
+            prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0) as u32,
+            completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0) as u32,
+            total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
+        }));
+    }
+
+    if let Some(reason) = choice["finish_reason"].as_str() {
+        events.push(StreamEvent::Done {
+            finish_reason: Some(reason.to_string()),
+        });
+    }
+
+    events
+}
+
+#[derive(Deserialize)]
+struct ChunkRef<'a> {
+    #[serde(borrow, default)]
+    choices: Vec<ChoiceRef<'a>>,
+    #[serde(default)]
+    usage: Option<UsageRef>,
+    #[serde(default)]
+    x_groq: Option<GroqRef>,
+}
+
+#[derive(Deserialize)]
+struct ChoiceRef<'a> {
+    #[serde(borrow, default)]
+    delta: Option<DeltaRef<'a>>,
+    #[serde(borrow, default)]
+    finish_reason: Option<Cow<'a, str>>,
+}
+
+#[derive(Deserialize)]
+struct DeltaRef<'a> {
+    #[serde(borrow, default)]
+    content: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    tool_calls: Option<Vec<ToolCallRef<'a>>>,
+}
+
+#[derive(Deserialize)]
+struct ToolCallRef<'a> {
+    #[serde(default)]
+    index: Option<u64>,
+    #[serde(borrow, default)]
+    id: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    function: Option<FunctionRef<'a>>,
+}
+
+#[derive(Deserialize)]
+struct FunctionRef<'a> {
+    #[serde(borrow, default)]
+    name: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    arguments: Option<Cow<'a, str>>,
+}
+
+#[derive(Deserialize)]
+struct UsageRef {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    total_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct GroqRef {
+    #[serde(default)]
+    usage: Option<UsageRef>,
+}
+
+/// Parse the `data` of one chat completions stream chunk; `None` if it is
+/// not JSON
+///
+/// Strings are borrowed from `data` until an event needs its own copy.
+/// Chunks of an unexpected shape go through `parse_stream_chunk`, which
+/// skips fields it does not understand.
+pub fn parse_chat_completion_chunk(data: &[u8]) -> Option<Vec<StreamEvent>> {
+    let Ok(chunk) = serde_json::from_slice::<ChunkRef>(data) else {
+        let json = serde_json::from_slice::<serde_json::Value>(data).ok()?;
+        return Some(parse_stream_chunk(&json));
+    };
+
+    let mut events = Vec::new();
+    let choice = chunk.choices.into_iter().next();
+    let (delta, finish_reason) = match choice {
+        Some(choice) => (choice.delta, choice.finish_reason),
+        None => (None, None),
+    };
+    if let Some(delta) = delta {
+        if let Some(content) = delta.content.filter(|c| !c.is_empty()) {
+            events.push(StreamEvent::TextDelta(content.into_owned()));
+        }
+        for (position, call) in delta.tool_calls.into_iter().flatten().enumerate() {
+            let function = call.function;
+            let (name, arguments) = match function {
+                Some(f) => (f.name, f.arguments),
+                None => (None, None),
+            };
+            events.push(StreamEvent::ToolCallDelta {
+                index: call.index.map_or(position, |i| i as usize),
+                id: call.id.map(Cow::into_owned),
+                name: name.map(Cow::into_owned),
+                arguments_delta: arguments.map(Cow::into_owned).unwrap_or_default(),
+            });
+        }
+    }
+
+    // Groq reports streaming usage under `x_groq` instead of `usage`
+    if let Some(usage) = chunk.usage.or_else(|| chunk.x_groq.and_then(|x| x.usage)) {
+        events.push(StreamEvent::UsageUpdate(Usage {
+            prompt_tokens: usage.prompt_tokens as u32,
+            completion_tokens: usage.completion_tokens as u32,
+            total_tokens: usage.total_tokens as u32,
+        }));
+    }
+
+    if let Some(reason) = finish_reason {
+        events.push(StreamEvent::Done {
+            finish_reason: Some(reason.into_owned()),
+        });
+    }
+
+    Some(events)
+}
+
+pub(super) fn format_content_blocks(content: &[super::ContentBlock]) -> serde_json::Value {
+    use super::{ContentBlock, ImageSource};
+
+    serde_json::json!(content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(serde_json::json!({
+                "type": "text",
+                "text": text,
+            })),
+            ContentBlock::Image { source, detail } => {
+                let mut img = serde_json::json!({
+                    "type": "image_url",
+                });
+                match source {
+                    ImageSource::Url { url } => {
+                        let mut image_url = serde_json::json!({
+                            "url": url,
+                        });
+                        if let Some(d) = detail {
+                            image_url["detail"] = serde_json::json!(match d {
+                                super::ImageDetail::Low => "low",
+                                super::ImageDetail::High => "high",
+                                super::ImageDetail::Auto => "auto",
+                            });
+                        }
+                        img["image_url"] = image_url;
+                    }
+                    ImageSource::Base64 { media_type, data } => {
+                        let data_url = format!("data:{};base64,{}", media_type, data);
+                        let mut image_url = serde_json::json!({
+                            "url": data_url,
+                        });
+                        if let Some(d) = detail {
+                            image_url["detail"] = serde_json::json!(match d {
+                                super::ImageDetail::Low => "low",
+                                super::ImageDetail::High => "high",
+                                super::ImageDetail::Auto => "auto",
+                            });
+                        }
+                        img["image_url"] = image_url;
+                    }
+                }
+                Some(img)
+            }
+            ContentBlock::File { file_id } => Some(serde_json::json!({
+                "type": "file",
+                "file": { "file_id": file_id },
+            })),
+            ContentBlock::ToolUse { .. } | ContentBlock::ToolResult { .. } => None,
+        })
+        .collect::<Vec<_>>())
+}
+
+pub(super) fn parse_generate_response(
+    json: serde_json::Value,
+    options: Option<&GenerateOptions>,
+    fallback_model: &str,
+) -> GenerateResponse {
+    let message = &json["choices"][0]["message"];
+    let content = message["content"].as_str().unwrap_or("").to_string();
+    let content = match options.and_then(|o| o.assistant_prefix.as_deref()) {
+        Some(prefix) => format!("{}{}", prefix, content),
+        None => content,
+    };
+    let tool_calls = parse_tool_calls(message);
+
+    let usage = json.get("usage").map(|u| Usage {
+        prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0) as u32,
+        completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0) as u32,
+        total_tokens: u["total_tokens"].as_u64().unwrap_or(0) as u32,
+    });
+
+    let finish_reason = json["choices"][0]["finish_reason"]
+        .as_str()
+        .map(String::from);
+
+    let model = json["model"].as_str().map(String::from).unwrap_or_else(|| {
+        options
+            .and_then(|o| o.model.clone())
+            .unwrap_or_else(|| fallback_model.to_string())
+    });
+
+    GenerateResponse {
+        content,
+        usage,
+        model,
+        finish_reason,
+        tool_calls,
+    }
+}
+
+/// Parse a chat completions SSE response into typed stream events
+///
+/// `prefix` is emitted first as a text delta, mirroring how assistant
+/// prefill is prepended to non-streaming responses. A stream that misses
+/// `deadline` ends with a `StreamTimeout` error.
+pub(super) fn spawn_event_stream(
+    response: reqwest::Response,
+    prefix: Option<String>,
+    deadline: StreamDeadline,
+) -> StreamEvents {
+    StreamEvents::spawn(100, |tx| async move {
+        if let Some(prefix) = prefix {
+            if tx.send(Ok(StreamEvent::TextDelta(prefix))).await.is_err() {
+                return;
+            }
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut lines = LineDecoder::new();
+        let mut finish_reason = None;
+
+        'outer: loop {
+            let chunk = match deadline.next(&mut stream).await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            match chunk {
+                Ok(bytes) => {
+                    lines.push(&bytes);
+
+                    while let Some(line) = lines.next_line() {
+                        if let Some(data) = sse_data(&line) {
+                            if data == b"[DONE]" {
+                                break 'outer;
+                            }
+
+                            if let Some(events) = parse_chat_completion_chunk(data) {
+                                for event in events {
+                                    if let StreamEvent::Done {
+                                        finish_reason: reason,
+                                    } = event
+                                    {
+                                        finish_reason = reason;
+                                        continue;
+                                    }
+                                    if tx.send(Ok(event)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx
+                        .send(Err(ProviderError::RequestFailed(e.to_string())))
+                        .await;
+                    return;
+                }
+            }
+        }
+
+        let _ = tx.send(Ok(StreamEvent::Done { finish_reason })).await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_tool_choice_only_with_tools() {
+        let tools = [ToolSchema {
+            name: "search".to_string(),
+            description: "Search the web".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+            native: None,
+        }];
+        let forced = GenerateOptions {
+            tool_choice: Some(ToolSelection::Tool("search".to_string())),
+            parallel_tool_calls: Some(false),
+            ..Default::default()
+        };
+
+        let mut body = serde_json::json!({});
+        add_tools_to_body(&mut body, &tools, Some(&forced));
+        assert_eq!(body["tool_choice"]["function"]["name"], "search");
+        assert_eq!(body["parallel_tool_calls"], false);
+
+        let any = GenerateOptions {
+            tool_choice: Some(ToolSelection::Any),
+            ..Default::default()
+        };
+        let mut body = serde_json::json!({});
+        add_tools_to_body(&mut body, &tools, Some(&any));
+        assert_eq!(body["tool_choice"], "required");
+        assert!(body.get("parallel_tool_calls").is_none());
+
+        let mut body = serde_json::json!({});
+        add_tools_to_body(&mut body, &[], Some(&forced));
+        assert!(body.get("tool_choice").is_none());
+    }
+
+    #[test]
+    fn parses_native_tool_calls() {
+        let json = serde_json::json!({
+            "model": "openai/gpt-4o-mini",
+            "choices": [{
+                "finish_reason": "tool_calls",
+                "message": {
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "calculator", "arguments": "{\"a\":1,\"b\":2}"}
+                    }]
+                }
+            }]
+        });
+
+        let resp = parse_generate_response(json, None, "fallback");
+        assert_eq!(resp.content, "");
+        assert_eq!(resp.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(resp.tool_calls[0].name, "calculator");
+        assert_eq!(resp.tool_calls[0].parameters["b"], 2);
+    }
+
+    #[test]
+    fn parses_stream_chunks_into_typed_events() {
+        let text = serde_json::json!({"choices": [{"delta": {"content": "Hi"}}]});
+        assert!(matches!(
+            parse_stream_chunk(&text).as_slice(),
+            [StreamEvent::TextDelta(t)] if t == "Hi"
+        ));
+
+        let tool = serde_json::json!({"choices": [{"delta": {"tool_calls": [{
+            "index": 1,
+            "id": "call_1",
+            "function": {"name": "search", "arguments": "{\"q\":"}
+        }]}}]});
+        match parse_stream_chunk(&tool).as_slice() {
+            [StreamEvent::ToolCallDelta {
+                index,
+                id,
+                name,
+                arguments_delta,
+            }] => {
+                assert_eq!(*index, 1);
+                assert_eq!(id.as_deref(), Some("call_1"));
+                assert_eq!(name.as_deref(), Some("search"));
+                assert_eq!(arguments_delta, "{\"q\":");
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        let last = serde_json::json!({
+            "choices": [{"delta": {}, "finish_reason": "tool_calls"}],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 3, "total_tokens": 8}
+        });
+        match parse_stream_chunk(&last).as_slice() {
+            [StreamEvent::UsageUpdate(usage), StreamEvent::Done { finish_reason }] => {
+                assert_eq!(usage.total_tokens, 8);
+                assert_eq!(finish_reason.as_deref(), Some("tool_calls"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn borrowed_chunk_parsing_matches_value_parsing() {
+        let chunks = [
+            r#"{"choices":[{"index":0,"delta":{"role":"assistant","content":"Caf\u00e9 \"x\""},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"search","arguments":""}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"function":{"arguments":"{\"q\":1}"}}]}}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"stop"}],"usage":{"prompt_tokens":5,"completion_tokens":3,"total_tokens":8}}"#,
+            r#"{"choices":[],"x_groq":{"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}}"#,
+            // Content in an unexpected shape falls back to the lenient parser
+            r#"{"choices":[{"delta":{"content":[{"type":"thinking"}]},"finish_reason":"stop"}]}"#,
+        ];
+        for chunk in chunks {
+            let value = serde_json::from_str::<serde_json::Value>(chunk).unwrap();
+            let expected = format!("{:?}", parse_stream_chunk(&value));
+            let parsed = parse_chat_completion_chunk(chunk.as_bytes()).unwrap();
+            assert_eq!(format!("{:?}", parsed), expected, "{}", chunk);
+        }
+        assert!(parse_chat_completion_chunk(b"not json").is_none());
+    }
+
+    #[tokio::test]
+    async fn stalled_stream_ends_with_a_timeout() {
+        use crate::provider::StreamTimeoutKind;
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Sends headers and one chunk, then goes quiet
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).await;
+            let chunk = "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+                 transfer-encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                chunk.len(),
+                chunk
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let provider: OpenAiCompatProvider = OpenAiCompatProvider::builder()
+            .base_url(format!("http://{}", addr))
+            .model("test")
+            .no_retry()
+            .timeout_config(
+                TimeoutConfig::default().stream_idle_timeout(Some(Duration::from_millis(100))),
+            )
+            .build()
+            .unwrap();
+        let mut events = provider
+            .generate_stream_events(vec![Message::user("hi")], Vec::new(), None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            events.receiver.recv().await,
+            Some(Ok(StreamEvent::TextDelta(text))) if text == "Hi"
+        ));
+        assert!(matches!(
+            events.receiver.recv().await,
+            Some(Err(ProviderError::StreamTimeout {
+                kind: StreamTimeoutKind::Idle,
+                ..
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn dropping_the_stream_closes_the_connection() {
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Starts a response and never finishes it; reports when the client
+        // closes the connection
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let closed = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            let chunk = "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+                 transfer-encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                chunk.len(),
+                chunk
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            while socket.read(&mut buf).await.is_ok_and(|n| n > 0) {}
+        });
+
+        let provider: OpenAiCompatProvider = OpenAiCompatProvider::builder()
+            .base_url(format!("http://{}", addr))
+            .model("test")
+            .no_retry()
+            .build()
+            .unwrap();
+        let mut events = provider
+            .generate_stream_events(vec![Message::user("hi")], Vec::new(), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            events.receiver.recv().await,
+            Some(Ok(StreamEvent::TextDelta(text))) if text == "Hi"
+        ));
+
+        drop(events);
+        tokio::time::timeout(Duration::from_secs(2), closed)
+            .await
+            .expect("stream task kept the connection open")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn embeds_in_batches_and_sums_usage() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Embeds each input as [its length], listing results in reverse
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let body = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length: usize = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break body.to_string();
+                        }
+                    }
+                };
+                let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+                let inputs = body["input"].as_array().unwrap();
+                let data: Vec<_> = inputs
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, text)| {
+                        serde_json::json!({
+                            "index": i,
+                            "embedding": [text.as_str().unwrap().len() as f32]
+                        })
+                    })
+                    .collect();
+                let response = serde_json::json!({
+                    "data": data,
+                    "model": body["model"],
+                    "usage": {"prompt_tokens": inputs.len(), "total_tokens": inputs.len()}
+                })
+                .to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let provider: OpenAiCompatProvider = OpenAiCompatProvider::builder()
+            .base_url(format!("http://{}", addr))
+            .model("chat")
+            .embedding_model("embed")
+            .embedding_batch_size(2)
+            .no_retry()
+            .build()
+            .unwrap();
+        let inputs = ["a", "bb", "ccc", "dddd", "eeeee"]
+            .map(String::from)
+            .to_vec();
+        let response = provider
+            .create_embeddings(EmbeddingRequest::new_batch(inputs))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.embeddings,
+            vec![vec![1.0], vec![2.0], vec![3.0], vec![4.0], vec![5.0]]
+        );
+        assert_eq!(response.model, "embed");
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.total_tokens), (5, 5));
+    }
+
+    #[test]
+    fn parses_base64_embeddings() {
+        let bytes: Vec<u8> = [0.5f32, -2.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+        let json = serde_json::json!({"data": [{"index": 0, "embedding": encoded}]});
+        assert_eq!(parse_embeddings(&json, 1).unwrap(), vec![vec![0.5, -2.0]]);
+        assert!(parse_embeddings(&json, 2).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! The layers HTTP providers run around a non-streaming request.
+//!
+//! Each provider owns an optional context window manager, response cache
+//! and middleware chain. `RequestPipeline` applies them in the same order
+//! for all of them, leaving the provider to build, send and parse the
+//! request itself.
+
+use super::{
+    CacheKey, ContextWindowManager, GenerateOptions, GenerateResponse, Message, MiddlewareChain,
+    RequestContext, ResponseCache, ResponseContext, Result, ToolSchema,
+};
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Borrowed view of a provider's request layers
+pub(super) struct RequestPipeline<'a> {
+    pub model: &'a str,
+    pub context_manager: Option<&'a ContextWindowManager>,
+    pub cache: Option<&'a ResponseCache>,
+    pub middleware: Option<&'a MiddlewareChain>,
+}
+
+impl RequestPipeline<'_> {
+    /// Truncate `messages` to the context window, answer from the cache if
+    /// possible, and otherwise call `send` between the middleware's before
+    /// and after hooks, caching its response
+    ///
+    /// `send` gets the messages and options as middleware left them.
+    pub(super) async fn generate<F, Fut>(
+        &self,
+        messages: Vec<Message>,
+        tools: &[ToolSchema],
+        options: Option<GenerateOptions>,
+        send: F,
+    ) -> Result<GenerateResponse>
+    where
+        F: FnOnce(Vec<Message>, Option<GenerateOptions>) -> Fut,
+        Fut: Future<Output = Result<GenerateResponse>>,
+    {
+        let messages = match self.context_manager {
+            Some(manager) => manager.truncate(messages).await,
+            None => messages,
+        };
+
+        let key = self
+            .cache
+            .map(|_| CacheKey::from_request(&messages, self.model, &options).with_tools(tools));
+        if let (Some(cache), Some(key)) = (self.cache, &key) {
+            if let Some(cached) = cache.get(key).await {
+                return Ok(cached);
+            }
+        }
+
+        let mut ctx = RequestContext {
+            messages,
+            options,
+            metadata: HashMap::new(),
+        };
+        if let Some(mw) = self.middleware {
+            if let Err(e) = mw.execute_before(&mut ctx).await {
+                let _ = mw.execute_error(&e).await;
+                return Err(e);
+            }
+        }
+
+        let response = match send(ctx.messages, ctx.options).await {
+            Ok(response) => response,
+            Err(e) => {
+                if let Some(mw) = self.middleware {
+                    let _ = mw.execute_error(&e).await;
+                }
+                return Err(e);
+            }
+        };
+
+        if let (Some(cache), Some(key)) = (self.cache, key) {
+            cache.put(key, response.clone()).await;
+        }
+
+        let mut resp_ctx = ResponseContext {
+            response,
+            metadata: ctx.metadata,
+        };
+        if let Some(mw) = self.middleware {
+            mw.execute_after(&mut resp_ctx).await?;
+        }
+        Ok(resp_ctx.response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{CacheConfig, Middleware};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Rewrites the request and tags the response
+    struct Rewrite;
+
+    #[async_trait]
+    impl Middleware for Rewrite {
+        async fn before_request(&self, ctx: &mut RequestContext) -> Result<()> {
+            ctx.messages = vec![Message::user("rewritten")];
+            ctx.metadata.insert("tag".to_string(), "!".to_string());
+            Ok(())
+        }
+
+        async fn after_response(&self, ctx: &mut ResponseContext) -> Result<()> {
+            ctx.response.content.push_str(&ctx.metadata["tag"]);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_middleware_around_send_and_caches_the_response() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let middleware = MiddlewareChain::new().add(Arc::new(Rewrite));
+        let pipeline = RequestPipeline {
+            model: "model",
+            context_manager: None,
+            cache: Some(&cache),
+            middleware: Some(&middleware),
+        };
+        let sends = AtomicUsize::new(0);
+        let send = |messages: Vec<Message>, _options| {
+            sends.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Ok(GenerateResponse {
+                    content: messages[0].content_as_text(),
+                    usage: None,
+                    model: "model".to_string(),
+                    finish_reason: Some("stop".to_string()),
+                    tool_calls: Vec::new(),
+                })
+            }
+        };
+
+        let response = pipeline
+            .generate(vec![Message::user("hi")], &[], None, send)
+            .await
+            .unwrap();
+        assert_eq!(response.content, "rewritten!");
+
+        // Answered from the cache, which holds the response before middleware
+        let response = pipeline
+            .generate(vec![Message::user("hi")], &[], None, send)
+            .await
+            .unwrap();
+        assert_eq!(response.content, "rewritten");
+        assert_eq!(sends.load(Ordering::SeqCst), 1);
+    }
+}
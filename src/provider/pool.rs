@@ -0,0 +1,673 @@
+//! Load balancing across several providers or API keys.
+
+use super::{
+    GenerateOptions, GenerateResponse, LlmProvider, Message, ProviderCapabilities, ProviderError,
+    Result, StreamEvents, StreamResponse, ToolSchema,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// How `ProviderPool` picks the provider for each request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadBalanceStrategy {
+    /// Take turns in registration order
+    #[default]
+    RoundRobin,
+    /// Prefer the provider with the lowest recent latency; untried providers
+    /// go first
+    LeastLatency,
+    /// Smooth weighted round-robin using the weights given at registration
+    Weighted,
+}
+
+/// Snapshot of one pool member, see `ProviderPool::stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolMemberStats {
+    pub name: String,
+    pub model: String,
+    pub weight: u32,
+    pub requests: u64,
+    pub failures: u64,
+    /// Exponentially weighted moving average of request latency
+    pub latency: Option<Duration>,
+    /// Whether the member is currently skipped by selection
+    pub ejected: bool,
+}
+
+impl PoolMemberStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.requests as f64
+        }
+    }
+}
+
+/// A pinned conversation moved to another member, see
+/// `ProviderPoolBuilder::session_affinity`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderSwitch {
+    pub from_provider: String,
+    pub from_model: String,
+    pub to_provider: String,
+    pub to_model: String,
+    pub reason: String,
+}
+
+/// Smoothing factor for the latency moving average
+const LATENCY_ALPHA: f64 = 0.3;
+
+#[derive(Default)]
+struct MemberState {
+    requests: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    latency_ms: Option<f64>,
+    ejected_until: Option<Instant>,
+    /// Running weight for smooth weighted round-robin
+    current_weight: i64,
+}
+
+struct PoolState {
+    members: Vec<MemberState>,
+    next: usize,
+    /// Member each conversation is pinned to
+    affinity: HashMap<u64, usize>,
+    /// Conversations in pinning order, oldest first, for eviction
+    affinity_order: VecDeque<u64>,
+}
+
+/// Distributes requests across providers and ejects failing ones
+///
+/// A member is ejected for `ejection_duration` after
+/// `max_consecutive_failures` failed requests or a failed `health_check`.
+/// When every member is ejected the pool fails open and uses the one whose
+/// ejection ends first. Requests are not retried on another member; wrap the
+/// pool in a `FallbackProvider` for that.
+pub struct ProviderPool {
+    providers: Vec<Box<dyn LlmProvider>>,
+    weights: Vec<u32>,
+    strategy: LoadBalanceStrategy,
+    max_consecutive_failures: u32,
+    ejection_duration: Duration,
+    /// Most conversations pinned at once; `None` disables affinity
+    max_sessions: Option<usize>,
+    switches: broadcast::Sender<ProviderSwitch>,
+    state: Mutex<PoolState>,
+}
+
+impl ProviderPool {
+    /// Create a builder for configuring the pool
+    pub fn builder() -> ProviderPoolBuilder {
+        ProviderPoolBuilder::default()
+    }
+
+    /// Current statistics for each member, in registration order
+    pub fn stats(&self) -> Vec<PoolMemberStats> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        self.providers
+            .iter()
+            .zip(&self.weights)
+            .zip(&state.members)
+            .map(|((provider, &weight), member)| PoolMemberStats {
+                name: provider.name().to_string(),
+                model: provider.model().to_string(),
+                weight,
+                requests: member.requests,
+                failures: member.failures,
+                latency: member
+                    .latency_ms
+                    .map(|ms| Duration::from_secs_f64(ms / 1000.0)),
+                ejected: member.ejected_until.is_some_and(|until| until > now),
+            })
+            .collect()
+    }
+
+    /// Run every member's `health_check`, ejecting failures and readmitting
+    /// members that pass
+    ///
+    /// Call periodically, e.g. from a `tokio::time::interval` loop.
+    pub async fn check_health(&self) {
+        for (index, provider) in self.providers.iter().enumerate() {
+            let healthy = provider.health_check().await.is_ok();
+            let mut state = self.state.lock().unwrap();
+            let member = &mut state.members[index];
+            if healthy {
+                member.consecutive_failures = 0;
+                member.ejected_until = None;
+            } else {
+                member.ejected_until = Some(Instant::now() + self.ejection_duration);
+            }
+        }
+    }
+
+    /// Receive a `ProviderSwitch` each time a pinned conversation moves
+    pub fn subscribe_switches(&self) -> broadcast::Receiver<ProviderSwitch> {
+        self.switches.subscribe()
+    }
+
+    /// Identity of the conversation `messages` belong to: its first system
+    /// and user messages, which stay the same as it grows
+    fn conversation_key(messages: &[Message]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let system = messages.iter().find(|m| m.role == super::Role::System);
+        let first = messages.iter().find(|m| m.role != super::Role::System);
+        for message in system.into_iter().chain(first) {
+            serde_json::to_string(message)
+                .unwrap_or_default()
+                .hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Pick the member for a request in `messages`' conversation, keeping
+    /// it on its pinned member while that member is not ejected
+    fn select_for(&self, messages: &[Message]) -> usize {
+        let Some(max_sessions) = self.max_sessions else {
+            return self.select();
+        };
+        let key = Self::conversation_key(messages);
+        let now = Instant::now();
+        let pinned = {
+            let state = self.state.lock().unwrap();
+            let pinned = state.affinity.get(&key).copied();
+            if let Some(index) = pinned {
+                if state.members[index]
+                    .ejected_until
+                    .is_none_or(|until| until <= now)
+                {
+                    return index;
+                }
+            }
+            pinned
+        };
+
+        let index = self.select();
+        let mut state = self.state.lock().unwrap();
+        if state.affinity.insert(key, index).is_none() {
+            state.affinity_order.push_back(key);
+            while state.affinity_order.len() > max_sessions {
+                if let Some(oldest) = state.affinity_order.pop_front() {
+                    state.affinity.remove(&oldest);
+                }
+            }
+        }
+        drop(state);
+        if let Some(from) = pinned.filter(|&from| from != index) {
+            let _ = self.switches.send(ProviderSwitch {
+                from_provider: self.providers[from].name().to_string(),
+                from_model: self.providers[from].model().to_string(),
+                to_provider: self.providers[index].name().to_string(),
+                to_model: self.providers[index].model().to_string(),
+                reason: "pinned member ejected after failures".to_string(),
+            });
+        }
+        index
+    }
+
+    /// Pick the member for the next request
+    fn select(&self) -> usize {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let available: Vec<usize> = (0..self.providers.len())
+            .filter(|&i| {
+                state.members[i]
+                    .ejected_until
+                    .is_none_or(|until| until <= now)
+            })
+            .collect();
+
+        if available.is_empty() {
+            return (0..self.providers.len())
+                .min_by_key(|&i| state.members[i].ejected_until)
+                .unwrap_or(0);
+        }
+
+        match self.strategy {
+            LoadBalanceStrategy::RoundRobin => {
+                let start = state.next;
+                let index = available
+                    .iter()
+                    .copied()
+                    .find(|&i| i >= start)
+                    .unwrap_or(available[0]);
+                state.next = index + 1;
+                index
+            }
+            LoadBalanceStrategy::LeastLatency => available
+                .iter()
+                .copied()
+                .min_by(|&a, &b| {
+                    let latency = |i: usize| state.members[i].latency_ms.unwrap_or(-1.0);
+                    latency(a).total_cmp(&latency(b))
+                })
+                .unwrap_or(available[0]),
+            LoadBalanceStrategy::Weighted => {
+                let total: i64 = available.iter().map(|&i| self.weights[i] as i64).sum();
+                let mut best = available[0];
+                for &i in &available {
+                    state.members[i].current_weight += self.weights[i] as i64;
+                    if state.members[i].current_weight > state.members[best].current_weight {
+                        best = i;
+                    }
+                }
+                state.members[best].current_weight -= total;
+                best
+            }
+        }
+    }
+
+    fn record<T>(&self, index: usize, started: Instant, result: &Result<T>) {
+        let mut state = self.state.lock().unwrap();
+        let member = &mut state.members[index];
+        member.requests += 1;
+
+        match result {
+            Ok(_) => {
+                let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+                member.latency_ms = Some(match member.latency_ms {
+                    Some(avg) => avg + LATENCY_ALPHA * (elapsed - avg),
+                    None => elapsed,
+                });
+                member.consecutive_failures = 0;
+            }
+            // Unsupported options are the caller's fault, not the provider's
+            Err(ProviderError::Other(_)) => {}
+            Err(_) => {
+                member.failures += 1;
+                member.consecutive_failures += 1;
+                if member.consecutive_failures >= self.max_consecutive_failures {
+                    member.ejected_until = Some(Instant::now() + self.ejection_duration);
+                    member.consecutive_failures = 0;
+                }
+            }
+        }
+    }
+
+    async fn dispatch<'a, T>(
+        &'a self,
+        index: usize,
+        call: impl FnOnce(&'a dyn LlmProvider) -> Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>,
+    ) -> Result<T> {
+        let started = Instant::now();
+        let result = call(self.providers[index].as_ref()).await;
+        self.record(index, started, &result);
+        result
+    }
+}
+
+/// Builder for `ProviderPool`
+pub struct ProviderPoolBuilder {
+    providers: Vec<Box<dyn LlmProvider>>,
+    weights: Vec<u32>,
+    strategy: LoadBalanceStrategy,
+    max_consecutive_failures: u32,
+    ejection_duration: Duration,
+    max_sessions: Option<usize>,
+}
+
+impl Default for ProviderPoolBuilder {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            weights: Vec::new(),
+            strategy: LoadBalanceStrategy::default(),
+            max_consecutive_failures: 3,
+            ejection_duration: Duration::from_secs(30),
+            max_sessions: None,
+        }
+    }
+}
+
+impl ProviderPoolBuilder {
+    /// Add a member with weight 1
+    pub fn provider(self, provider: impl LlmProvider + 'static) -> Self {
+        self.weighted(provider, 1)
+    }
+
+    /// Add a member with a weight used by `LoadBalanceStrategy::Weighted`
+    pub fn weighted(mut self, provider: impl LlmProvider + 'static, weight: u32) -> Self {
+        self.providers.push(Box::new(provider));
+        self.weights.push(weight.max(1));
+        self
+    }
+
+    /// Set the selection strategy
+    pub fn strategy(mut self, strategy: LoadBalanceStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Eject a member after this many failed requests in a row
+    pub fn max_consecutive_failures(mut self, failures: u32) -> Self {
+        self.max_consecutive_failures = failures.max(1);
+        self
+    }
+
+    /// How long an ejected member is skipped
+    pub fn ejection_duration(mut self, duration: Duration) -> Self {
+        self.ejection_duration = duration;
+        self
+    }
+
+    /// Keep each conversation on the member that served it first, so it
+    /// reuses that backend's prompt cache and model
+    ///
+    /// A conversation is identified by its first system and user messages.
+    /// It moves only when its member is ejected, reported through
+    /// `ProviderPool::subscribe_switches`. At most `max_sessions`
+    /// conversations are remembered; the oldest are forgotten first.
+    pub fn session_affinity(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = Some(max_sessions.max(1));
+        self
+    }
+
+    /// Build the pool
+    pub fn build(self) -> Result<ProviderPool> {
+        if self.providers.is_empty() {
+            return Err(ProviderError::RequestFailed(
+                "At least one provider is required".to_string(),
+            ));
+        }
+        let members = self
+            .providers
+            .iter()
+            .map(|_| MemberState::default())
+            .collect();
+        Ok(ProviderPool {
+            providers: self.providers,
+            weights: self.weights,
+            strategy: self.strategy,
+            max_consecutive_failures: self.max_consecutive_failures,
+            ejection_duration: self.ejection_duration,
+            max_sessions: self.max_sessions,
+            switches: broadcast::channel(16).0,
+            state: Mutex::new(PoolState {
+                members,
+                next: 0,
+                affinity: HashMap::new(),
+                affinity_order: VecDeque::new(),
+            }),
+        })
+    }
+}
+
+impl LlmProvider for ProviderPool {
+    fn name(&self) -> &str {
+        "pool"
+    }
+
+    /// Model of the first member
+    fn model(&self) -> &str {
+        self.providers[0].model()
+    }
+
+    /// Only what every member supports, since any member may serve a request
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.providers
+            .iter()
+            .map(|p| p.capabilities())
+            .reduce(|a, b| a.intersect(&b))
+            .unwrap_or_default()
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        let index = self.select_for(&messages);
+        Box::pin(self.dispatch(index, move |provider| provider.generate(messages, options)))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        let index = self.select_for(&messages);
+        Box::pin(self.dispatch(index, move |provider| {
+            provider.generate_with_tools(messages, tools, options)
+        }))
+    }
+
+    /// Latency is measured until the stream is open
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamResponse>> + Send + '_>> {
+        let index = self.select_for(&messages);
+        Box::pin(self.dispatch(index, move |provider| {
+            provider.generate_stream(messages, options)
+        }))
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        let index = self.select_for(&messages);
+        Box::pin(self.dispatch(index, move |provider| {
+            provider.generate_stream_events(messages, tools, options)
+        }))
+    }
+
+    /// Connects every member, since any of them may serve the next request
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            futures_util::future::try_join_all(self.providers.iter().map(|p| p.preconnect()))
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Warms every member, not just the one selection would pick
+    fn warm_up(&self, ping: bool) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            futures_util::future::try_join_all(self.providers.iter().map(|p| p.warm_up(ping)))
+                .await
+                .map(|_| ())
+        })
+    }
+
+    fn provider_switches(&self) -> Option<broadcast::Receiver<ProviderSwitch>> {
+        Some(self.subscribe_switches())
+    }
+
+    /// Healthy while at least one member is not ejected
+    fn health_check(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            if self.stats().iter().any(|member| !member.ejected) {
+                Ok(())
+            } else {
+                Err(ProviderError::Other(
+                    "All pool members are ejected".to_string(),
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    struct Member {
+        name: &'static str,
+        healthy: Arc<AtomicBool>,
+    }
+
+    impl Member {
+        fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                healthy: Arc::new(AtomicBool::new(true)),
+            }
+        }
+    }
+
+    impl LlmProvider for Member {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn model(&self) -> &str {
+            "m"
+        }
+
+        fn generate(
+            &self,
+            _messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+            let result = if self.healthy.load(Ordering::SeqCst) {
+                Ok(GenerateResponse {
+                    content: self.name.to_string(),
+                    usage: None,
+                    model: "m".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            } else {
+                Err(ProviderError::RequestFailed(
+                    "503 Service Unavailable".to_string(),
+                ))
+            };
+            Box::pin(async move { result })
+        }
+
+        fn health_check(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+            let healthy = self.healthy.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if healthy {
+                    Ok(())
+                } else {
+                    Err(ProviderError::RequestFailed("down".to_string()))
+                }
+            })
+        }
+    }
+
+    async fn serve(pool: &ProviderPool, n: usize) -> Vec<String> {
+        let mut served = Vec::new();
+        for _ in 0..n {
+            let response = pool.generate(vec![Message::user("hi")], None).await;
+            served.push(
+                response
+                    .map(|r| r.content)
+                    .unwrap_or_else(|e| e.to_string()),
+            );
+        }
+        served
+    }
+
+    #[tokio::test]
+    async fn round_robin_and_weighted_distribution() {
+        let pool = ProviderPool::builder()
+            .provider(Member::new("a"))
+            .provider(Member::new("b"))
+            .build()
+            .unwrap();
+        assert_eq!(serve(&pool, 4).await, vec!["a", "b", "a", "b"]);
+
+        let pool = ProviderPool::builder()
+            .strategy(LoadBalanceStrategy::Weighted)
+            .weighted(Member::new("a"), 3)
+            .weighted(Member::new("b"), 1)
+            .build()
+            .unwrap();
+        let served = serve(&pool, 8).await;
+        assert_eq!(served.iter().filter(|s| *s == "a").count(), 6);
+        assert_eq!(served.iter().filter(|s| *s == "b").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn ejects_failing_members_and_readmits_after_health_check() {
+        let flaky = Member::new("flaky");
+        let flaky_health = flaky.healthy.clone();
+        flaky_health.store(false, Ordering::SeqCst);
+
+        let pool = ProviderPool::builder()
+            .provider(flaky)
+            .provider(Member::new("steady"))
+            .max_consecutive_failures(2)
+            .build()
+            .unwrap();
+
+        // flaky fails twice (turns 1 and 3), then only steady serves
+        let served = serve(&pool, 6).await;
+        assert_eq!(&served[4..], &["steady", "steady"]);
+        let stats = pool.stats();
+        assert!(stats[0].ejected);
+        assert_eq!(stats[0].failures, 2);
+        assert_eq!(stats[0].error_rate(), 1.0);
+
+        flaky_health.store(true, Ordering::SeqCst);
+        pool.check_health().await;
+        assert!(!pool.stats()[0].ejected);
+        assert!(serve(&pool, 2).await.contains(&"flaky".to_string()));
+    }
+
+    #[tokio::test]
+    async fn pins_conversations_until_their_member_is_ejected() {
+        let a = Member::new("a");
+        let a_health = a.healthy.clone();
+        let pool = ProviderPool::builder()
+            .provider(a)
+            .provider(Member::new("b"))
+            .max_consecutive_failures(1)
+            .session_affinity(16)
+            .build()
+            .unwrap();
+        let mut switches = pool.subscribe_switches();
+
+        let turn = |text: &str| vec![Message::user("plan a trip"), Message::assistant(text)];
+        let first = |messages| async { pool.generate(messages, None).await.unwrap().content };
+        assert_eq!(first(turn("1")).await, "a");
+        assert_eq!(first(vec![Message::user("other chat")]).await, "b");
+        assert_eq!(first(turn("2")).await, "a");
+        assert_eq!(first(turn("3")).await, "a");
+        assert!(switches.try_recv().is_err());
+
+        a_health.store(false, Ordering::SeqCst);
+        assert!(pool.generate(turn("4"), None).await.is_err());
+        assert_eq!(first(turn("5")).await, "b");
+        let switch = switches.try_recv().unwrap();
+        assert_eq!(
+            (switch.from_provider.as_str(), switch.to_provider.as_str()),
+            ("a", "b")
+        );
+
+        // Stays on the new member once the old one recovers
+        a_health.store(true, Ordering::SeqCst);
+        pool.check_health().await;
+        assert_eq!(first(turn("6")).await, "b");
+    }
+
+    #[tokio::test]
+    async fn warm_up_pings_every_member() {
+        let down = Member::new("down");
+        down.healthy.store(false, Ordering::SeqCst);
+        let pool = ProviderPool::builder()
+            .provider(Member::new("up"))
+            .provider(down)
+            .build()
+            .unwrap();
+        assert!(pool.warm_up(false).await.is_ok());
+        assert!(pool.warm_up(true).await.is_err());
+    }
+}
@@ -1,6 +1,7 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{Semaphore, RwLock};
+use tokio::sync::{broadcast, Semaphore};
 
 /// Configuration for rate limiting
 #[derive(Debug, Clone)]
@@ -61,16 +62,185 @@ impl RateLimitConfig {
     }
 }
 
+/// Which limit a request is waiting on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKind {
+    /// `requests_per_minute`
+    Requests,
+    /// `tokens_per_minute`
+    Tokens,
+}
+
+/// Sent to `RateLimiter::subscribe_waits` receivers when a request starts
+/// waiting for the rate limit window
+#[derive(Debug, Clone)]
+pub struct RateLimitWait {
+    pub limit: RateLimitKind,
+    /// How long the request will sleep before checking the window again
+    pub wait: Duration,
+}
+
+/// Length of the rate limit window in seconds, one bucket per second
+const WINDOW_SECS: u64 = 60;
+
+fn pack(second: u64, count: u64) -> u64 {
+    (second << 32) | count
+}
+
+fn unpack(bucket: u64) -> (u64, u64) {
+    (bucket >> 32, bucket & u32::MAX as u64)
+}
+
+/// Sliding one-minute window of counts kept in one-second buckets
+///
+/// Each bucket packs the second it covers and its count into one atomic,
+/// and `total` holds the sum of the buckets still in the window, so
+/// counting and admitting never take a lock.
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    buckets: Box<[AtomicU64]>,
+    total: AtomicU64,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            buckets: (0..WINDOW_SECS).map(|_| AtomicU64::new(0)).collect(),
+            total: AtomicU64::new(0),
+        }
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs()
+    }
+
+    /// Take buckets that have left the window out of `total`
+    fn expire(&self, now_sec: u64) {
+        for bucket in self.buckets.iter() {
+            let mut current = bucket.load(Ordering::Acquire);
+            loop {
+                let (second, count) = unpack(current);
+                if count == 0 || second + WINDOW_SECS > now_sec {
+                    break;
+                }
+                match bucket.compare_exchange_weak(
+                    current,
+                    pack(second, 0),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        self.total.fetch_sub(count, Ordering::AcqRel);
+                        break;
+                    }
+                    Err(actual) => current = actual,
+                }
+            }
+        }
+    }
+
+    /// Put `amount`, already counted in `total`, into the bucket for `now_sec`
+    fn record(&self, now_sec: u64, amount: u64) {
+        let bucket = &self.buckets[(now_sec % WINDOW_SECS) as usize];
+        let mut current = bucket.load(Ordering::Acquire);
+        loop {
+            let (second, count) = unpack(current);
+            // A bucket from a later second means this thread fell behind;
+            // counting there only keeps the amount in the window longer
+            let (next, expired) = if second >= now_sec {
+                (pack(second, count + amount), 0)
+            } else {
+                (pack(now_sec, amount), count)
+            };
+            match bucket.compare_exchange_weak(current, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    if expired > 0 {
+                        self.total.fetch_sub(expired, Ordering::AcqRel);
+                    }
+                    return;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Count one more if the window holds fewer than `limit`, otherwise
+    /// return how long until room may free up
+    fn try_acquire(&self, now: Instant, limit: u64) -> Result<(), Duration> {
+        let now_sec = self.second(now);
+        self.expire(now_sec);
+        let mut total = self.total.load(Ordering::Acquire);
+        loop {
+            if total >= limit {
+                // In-flight records can leave no bucket to wait for yet
+                return Err(self.wait(now).max(Duration::from_millis(1)));
+            }
+            match self.total.compare_exchange_weak(
+                total,
+                total + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(actual) => total = actual,
+            }
+        }
+        self.record(now_sec, 1);
+        Ok(())
+    }
+
+    fn add(&self, now: Instant, amount: u64) {
+        let now_sec = self.second(now);
+        self.expire(now_sec);
+        self.total.fetch_add(amount, Ordering::AcqRel);
+        self.record(now_sec, amount);
+    }
+
+    fn count(&self, now: Instant) -> u64 {
+        self.expire(self.second(now));
+        self.total.load(Ordering::Acquire)
+    }
+
+    /// Time until the oldest bucket in the window leaves it
+    fn wait(&self, now: Instant) -> Duration {
+        let now_sec = self.second(now);
+        self.buckets
+            .iter()
+            .map(|bucket| unpack(bucket.load(Ordering::Acquire)))
+            .filter(|&(second, count)| count > 0 && second + WINDOW_SECS > now_sec)
+            .map(|(second, _)| second)
+            .min()
+            .map(|oldest| {
+                (self.start + Duration::from_secs(oldest + WINDOW_SECS))
+                    .saturating_duration_since(now)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Wait before the window can take more, if it holds `limit` already
+    fn wait_at(&self, now: Instant, limit: u64) -> Duration {
+        if self.count(now) >= limit {
+            self.wait(now)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
 /// Rate limiter using sliding window and semaphore for concurrency control
 #[derive(Debug)]
 pub struct RateLimiter {
     config: RateLimitConfig,
     /// Semaphore for controlling concurrent requests
     semaphore: Arc<Semaphore>,
-    /// Sliding window of request timestamps
-    request_times: Arc<RwLock<Vec<Instant>>>,
+    /// Sliding window of requests
+    requests: Arc<Window>,
     /// Sliding window of token usage
-    token_usage: Arc<RwLock<Vec<(Instant, u32)>>>,
+    tokens: Arc<Window>,
+    /// Notifications for requests that are about to wait
+    waits: broadcast::Sender<RateLimitWait>,
 }
 
 impl RateLimiter {
@@ -78,13 +248,37 @@ impl RateLimiter {
     pub fn new(config: RateLimitConfig) -> Self {
         Self {
             semaphore: Arc::new(Semaphore::new(config.concurrent_requests)),
-            request_times: Arc::new(RwLock::new(Vec::new())),
-            token_usage: Arc::new(RwLock::new(Vec::new())),
+            requests: Arc::new(Window::new()),
+            tokens: Arc::new(Window::new()),
+            waits: broadcast::channel(16).0,
             config,
         }
     }
 
+    /// Receive a `RateLimitWait` each time a request blocks on the window
+    pub fn subscribe_waits(&self) -> broadcast::Receiver<RateLimitWait> {
+        self.waits.subscribe()
+    }
+
+    /// How long a request made now would wait for the rate limit window
+    ///
+    /// Does not include time spent waiting for a concurrency permit.
+    pub async fn wait_estimate(&self) -> Duration {
+        let now = Instant::now();
+        let request_wait = self
+            .requests
+            .wait_at(now, self.config.requests_per_minute as u64);
+        let token_wait = match self.config.tokens_per_minute {
+            Some(max_tokens) => self.tokens.wait_at(now, max_tokens as u64),
+            None => Duration::ZERO,
+        };
+        request_wait.max(token_wait)
+    }
+
     /// Acquire a permit to make a request, waiting if necessary
+    ///
+    /// The request is counted in the window at the moment it is admitted,
+    /// so concurrent callers cannot overshoot `requests_per_minute`.
     pub async fn acquire(&self) -> RateLimitGuard {
         // Acquire semaphore permit for concurrency control
         let permit = self
@@ -94,13 +288,13 @@ impl RateLimiter {
             .await
             .expect("Semaphore closed");
 
-        // Wait for rate limit window if needed
-        self.wait_for_rate_limit().await;
+        // Check token rate limit if configured
+        if let Some(max_tokens) = self.config.tokens_per_minute {
+            self.wait_for_token_limit(max_tokens).await;
+        }
 
-        // Record this request
-        let now = Instant::now();
-        let mut times = self.request_times.write().await;
-        times.push(now);
+        // Wait for rate limit window if needed, then count this request
+        self.wait_for_rate_limit().await;
 
         RateLimitGuard {
             _permit: permit,
@@ -108,106 +302,62 @@ impl RateLimiter {
         }
     }
 
-    /// Wait until we're within the rate limit window
+    /// Wait until this request fits in the window, and count it
     async fn wait_for_rate_limit(&self) {
-        loop {
-            let now = Instant::now();
-            let window_start = now - Duration::from_secs(60);
-
-            // Clean up old entries and count recent requests
-            let mut times = self.request_times.write().await;
-            times.retain(|&time| time > window_start);
-
-            let recent_requests = times.len() as u32;
-
-            if recent_requests < self.config.requests_per_minute {
-                break;
-            }
-
-            // Calculate how long to wait
-            if let Some(oldest) = times.first() {
-                let wait_duration = Duration::from_secs(60) - now.duration_since(*oldest);
-                drop(times); // Release lock before sleeping
-
-                // Log rate limit wait (optional, only if tracing is available)
-                #[cfg(feature = "tracing")]
-                tracing::debug!(
-                    "Rate limit reached ({}/{}), waiting {:?}",
-                    recent_requests,
-                    self.config.requests_per_minute,
-                    wait_duration
-                );
-
-                tokio::time::sleep(wait_duration).await;
-            } else {
-                break;
-            }
-        }
-
-        // Check token rate limit if configured
-        if let Some(max_tokens) = self.config.tokens_per_minute {
-            self.wait_for_token_limit(max_tokens).await;
+        let limit = self.config.requests_per_minute as u64;
+        while let Err(wait_duration) = self.requests.try_acquire(Instant::now(), limit) {
+            // Log rate limit wait (optional, only if tracing is available)
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "Rate limit reached ({} per minute), waiting {:?}",
+                limit,
+                wait_duration
+            );
+
+            let _ = self.waits.send(RateLimitWait {
+                limit: RateLimitKind::Requests,
+                wait: wait_duration,
+            });
+            tokio::time::sleep(wait_duration).await;
         }
     }
 
     /// Wait until we're within the token rate limit window
     async fn wait_for_token_limit(&self, max_tokens: u32) {
         loop {
-            let now = Instant::now();
-            let window_start = now - Duration::from_secs(60);
-
-            let mut usage = self.token_usage.write().await;
-            usage.retain(|(time, _)| *time > window_start);
-
-            let recent_tokens: u32 = usage.iter().map(|(_, tokens)| tokens).sum();
-
-            if recent_tokens < max_tokens {
+            let wait_duration = self.tokens.wait_at(Instant::now(), max_tokens as u64);
+            if wait_duration.is_zero() {
                 break;
             }
 
-            // Calculate how long to wait
-            if let Some((oldest_time, _)) = usage.first() {
-                let wait_duration = Duration::from_secs(60) - now.duration_since(*oldest_time);
-                drop(usage); // Release lock before sleeping
-
-                // Log token rate limit wait (optional, only if tracing is available)
-                #[cfg(feature = "tracing")]
-                tracing::debug!(
-                    "Token rate limit reached ({}/{}), waiting {:?}",
-                    recent_tokens,
-                    max_tokens,
-                    wait_duration
-                );
-
-                tokio::time::sleep(wait_duration).await;
-            } else {
-                break;
-            }
+            // Log token rate limit wait (optional, only if tracing is available)
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "Token rate limit reached (max {}), waiting {:?}",
+                max_tokens,
+                wait_duration
+            );
+
+            let _ = self.waits.send(RateLimitWait {
+                limit: RateLimitKind::Tokens,
+                wait: wait_duration,
+            });
+            tokio::time::sleep(wait_duration).await;
         }
     }
 
     /// Record token usage for rate limiting
     pub async fn record_tokens(&self, tokens: u32) {
         if self.config.tokens_per_minute.is_some() {
-            let mut usage = self.token_usage.write().await;
-            usage.push((Instant::now(), tokens));
+            self.tokens.add(Instant::now(), tokens as u64);
         }
     }
 
     /// Get current rate limit statistics
     pub async fn stats(&self) -> RateLimitStats {
         let now = Instant::now();
-        let window_start = now - Duration::from_secs(60);
-
-        let times = self.request_times.read().await;
-        let recent_requests = times.iter().filter(|&&time| time > window_start).count() as u32;
-
-        let usage = self.token_usage.read().await;
-        let recent_tokens: u32 = usage
-            .iter()
-            .filter(|(time, _)| *time > window_start)
-            .map(|(_, tokens)| tokens)
-            .sum();
+        let recent_requests = self.requests.count(now).min(u32::MAX as u64) as u32;
+        let recent_tokens = self.tokens.count(now).min(u32::MAX as u64) as u32;
 
         RateLimitStats {
             requests_in_window: recent_requests,
@@ -229,8 +379,9 @@ impl Clone for RateLimiter {
         Self {
             config: self.config.clone(),
             semaphore: Arc::clone(&self.semaphore),
-            request_times: Arc::clone(&self.request_times),
-            token_usage: Arc::clone(&self.token_usage),
+            requests: Arc::clone(&self.requests),
+            tokens: Arc::clone(&self.tokens),
+            waits: self.waits.clone(),
         }
     }
 }
@@ -300,4 +451,66 @@ mod tests {
         assert_eq!(stats.tokens_in_window, Some(100));
         assert_eq!(stats.available_permits, 4);
     }
+
+    #[tokio::test]
+    async fn test_wait_notification_and_estimate() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(1, 5));
+        let mut waits = limiter.subscribe_waits();
+
+        assert_eq!(limiter.wait_estimate().await, Duration::ZERO);
+        drop(limiter.acquire().await);
+        let estimate = limiter.wait_estimate().await;
+        assert!(estimate > Duration::from_secs(59));
+
+        let blocked = limiter.clone();
+        let handle = tokio::spawn(async move {
+            blocked.acquire().await;
+        });
+
+        let wait = waits.recv().await.unwrap();
+        assert_eq!(wait.limit, RateLimitKind::Requests);
+        assert!(wait.wait > Duration::from_secs(59) && wait.wait <= estimate);
+        handle.abort();
+    }
+
+    #[test]
+    fn test_window_expires_by_second() {
+        let window = Window::new();
+        let start = window.start;
+        assert!(window.try_acquire(start, 2).is_ok());
+        let later = start + Duration::from_millis(30_500);
+        assert!(window.try_acquire(later, 2).is_ok());
+
+        let wait = window.try_acquire(later, 2).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(29_500));
+
+        // The first second's bucket leaves the window after a minute
+        let minute = start + Duration::from_secs(60);
+        assert_eq!(window.count(minute), 1);
+        assert!(window.try_acquire(minute, 2).is_ok());
+        assert_eq!(window.count(start + Duration::from_secs(91)), 1);
+        assert_eq!(window.count(start + Duration::from_secs(121)), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_acquires_never_overshoot() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(1000, 5000));
+        let tasks: Vec<_> = (0..5000)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    tokio::time::timeout(Duration::from_millis(500), limiter.acquire())
+                        .await
+                        .is_ok()
+                })
+            })
+            .collect();
+
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap() as u32;
+        }
+        assert_eq!(admitted, 1000);
+        assert_eq!(limiter.stats().await.requests_in_window, 1000);
+    }
 }
@@ -0,0 +1,409 @@
+//! Record provider exchanges to a file and replay them offline.
+//!
+//! `RecordingProvider` appends every request and its response to a JSON
+//! lines file; `ReplayProvider` answers from such a file, so agent runs can
+//! be tested and debugged deterministically without network access or API
+//! keys.
+
+use super::{
+    GenerateOptions, GenerateResponse, LlmProvider, Message, ProviderCapabilities, ProviderError,
+    ProviderSwitch, RateLimiter, Result, ToolSchema,
+};
+use crate::storage;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// One request and what the provider returned
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordedExchange {
+    pub provider: String,
+    pub messages: Vec<Message>,
+    /// Tools sent with `generate_with_tools`; None for `generate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolSchema>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<GenerateResponse>,
+    /// The provider's error, if the request failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RecordedExchange {
+    fn request_key(&self) -> serde_json::Value {
+        serde_json::json!([self.messages, self.tools])
+    }
+}
+
+/// Rewrites an exchange before it is written or matched, e.g. to mask
+/// secrets or personal data
+pub type Redactor = Arc<dyn Fn(&mut RecordedExchange) + Send + Sync>;
+
+/// Wraps a provider and appends each exchange to a JSON lines file
+///
+/// Streaming is reported as unsupported, so agents send whole requests and
+/// every exchange is recorded.
+pub struct RecordingProvider<P> {
+    inner: P,
+    path: PathBuf,
+    redactors: Vec<Redactor>,
+}
+
+impl<P: LlmProvider> RecordingProvider<P> {
+    pub fn new(inner: P, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            redactors: Vec::new(),
+        }
+    }
+
+    /// Apply `redactor` to each exchange before it is written
+    pub fn redact(
+        mut self,
+        redactor: impl Fn(&mut RecordedExchange) + Send + Sync + 'static,
+    ) -> Self {
+        self.redactors.push(Arc::new(redactor));
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    async fn record(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolSchema>>,
+        result: &Result<GenerateResponse>,
+    ) -> Result<()> {
+        let mut exchange = RecordedExchange {
+            provider: self.inner.name().to_string(),
+            messages,
+            tools,
+            response: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(ToString::to_string),
+        };
+        for redactor in &self.redactors {
+            redactor(&mut exchange);
+        }
+        storage::append_json_line(&self.path, &exchange)
+            .await
+            .map_err(|e| {
+                ProviderError::Other(format!(
+                    "Failed to record exchange to {}: {}",
+                    self.path.display(),
+                    e
+                ))
+            })
+    }
+}
+
+impl<P: LlmProvider> LlmProvider for RecordingProvider<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let result = self.inner.generate(messages.clone(), options).await;
+            self.record(messages, None, &result).await?;
+            result
+        })
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(async move {
+            let result = self
+                .inner
+                .generate_with_tools(messages.clone(), tools.clone(), options)
+                .await;
+            self.record(messages, Some(tools), &result).await?;
+            result
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: false,
+            ..self.inner.capabilities()
+        }
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        self.inner.context_window()
+    }
+
+    fn health_check(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.inner.health_check()
+    }
+
+    fn preconnect(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.inner.preconnect()
+    }
+
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.inner.rate_limiter()
+    }
+
+    fn provider_switches(&self) -> Option<tokio::sync::broadcast::Receiver<ProviderSwitch>> {
+        self.inner.provider_switches()
+    }
+}
+
+/// Answers requests with exchanges recorded by `RecordingProvider`
+///
+/// By default each request gets the first unused exchange with the same
+/// messages and tools, and a request with none fails. `in_order` serves
+/// exchanges in recorded order instead, whatever the request.
+pub struct ReplayProvider {
+    name: String,
+    model: String,
+    native_tools: bool,
+    exchanges: Mutex<Vec<Option<RecordedExchange>>>,
+    in_order: bool,
+    redactors: Vec<Redactor>,
+}
+
+impl ReplayProvider {
+    pub fn new(exchanges: Vec<RecordedExchange>) -> Self {
+        let first = exchanges.first();
+        Self {
+            name: first.map_or("replay", |e| e.provider.as_str()).to_string(),
+            model: first
+                .and_then(|e| e.response.as_ref())
+                .map_or("replay", |r| r.model.as_str())
+                .to_string(),
+            native_tools: exchanges.iter().any(|e| e.tools.is_some()),
+            exchanges: Mutex::new(exchanges.into_iter().map(Some).collect()),
+            in_order: false,
+            redactors: Vec::new(),
+        }
+    }
+
+    /// Load exchanges written by `RecordingProvider`
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = tokio::fs::read_to_string(path).await.map_err(|e| {
+            ProviderError::Other(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let exchanges = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| ProviderError::ParseError(e.to_string()))?;
+        Ok(Self::new(exchanges))
+    }
+
+    /// Serve exchanges in recorded order without matching requests
+    pub fn in_order(mut self) -> Self {
+        self.in_order = true;
+        self
+    }
+
+    /// Apply `redactor` to each request before matching it, usually the
+    /// one used while recording
+    pub fn redact(
+        mut self,
+        redactor: impl Fn(&mut RecordedExchange) + Send + Sync + 'static,
+    ) -> Self {
+        self.redactors.push(Arc::new(redactor));
+        self
+    }
+
+    /// Exchanges not served yet
+    pub fn remaining(&self) -> usize {
+        self.exchanges.lock().unwrap().iter().flatten().count()
+    }
+
+    fn replay(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolSchema>>,
+    ) -> Result<GenerateResponse> {
+        let mut request = RecordedExchange {
+            provider: self.name.clone(),
+            messages,
+            tools,
+            response: None,
+            error: None,
+        };
+        for redactor in &self.redactors {
+            redactor(&mut request);
+        }
+        let key = request.request_key();
+
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let exchange = exchanges
+            .iter_mut()
+            .find(|slot| {
+                slot.as_ref()
+                    .is_some_and(|e| self.in_order || e.request_key() == key)
+            })
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                ProviderError::Other("No recorded response matches the request".to_string())
+            })?;
+        match (exchange.response, exchange.error) {
+            (Some(response), _) => Ok(response),
+            (None, error) => Err(ProviderError::RequestFailed(error.unwrap_or_default())),
+        }
+    }
+}
+
+impl LlmProvider for ReplayProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        _options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        let result = self.replay(messages, None);
+        Box::pin(async move { result })
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        _options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        let result = self.replay(messages, Some(tools));
+        Box::pin(async move { result })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            native_tools: self.native_tools,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{ContentBlock, Usage};
+
+    struct EchoProvider;
+
+    impl LlmProvider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn model(&self) -> &str {
+            "echo-1"
+        }
+
+        fn generate(
+            &self,
+            messages: Vec<Message>,
+            _options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+            let text = messages.last().unwrap().content_as_text();
+            Box::pin(async move {
+                if text == "fail" {
+                    return Err(ProviderError::RequestFailed("503: overloaded".to_string()));
+                }
+                Ok(GenerateResponse {
+                    content: format!("you said {}", text),
+                    usage: Some(Usage::default()),
+                    model: "echo-1".to_string(),
+                    finish_reason: Some("stop".to_string()),
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    fn mask_key(exchange: &mut RecordedExchange) {
+        for message in &mut exchange.messages {
+            let content: Vec<ContentBlock> = message
+                .content
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::Text { text } => ContentBlock::Text {
+                        text: text.replace("sk-123", "[key]"),
+                    },
+                    other => other.clone(),
+                })
+                .collect();
+            message.content = content.into();
+        }
+        if let Some(response) = &mut exchange.response {
+            response.content = response.content.replace("sk-123", "[key]");
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_exchanges_offline() {
+        let path =
+            std::env::temp_dir().join(format!("agent-sdk-recording-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let recorder = RecordingProvider::new(EchoProvider, &path).redact(mask_key);
+        for text in ["hello", "my key is sk-123", "fail"] {
+            let _ = recorder.generate(vec![Message::user(text)], None).await;
+        }
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(recorded.lines().count(), 3);
+        assert!(!recorded.contains("sk-123"));
+
+        let replay = ReplayProvider::from_file(&path)
+            .await
+            .unwrap()
+            .redact(mask_key);
+        assert_eq!((replay.name(), replay.model()), ("echo", "echo-1"));
+        // Matched by request, not by order
+        let reply = replay
+            .generate(vec![Message::user("my key is sk-123")], None)
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "you said my key is [key]");
+        let reply = replay
+            .generate(vec![Message::user("hello")], None)
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "you said hello");
+        assert!(matches!(
+            replay.generate(vec![Message::user("fail")], None).await,
+            Err(ProviderError::RequestFailed(e)) if e.contains("503")
+        ));
+        assert!(replay
+            .generate(vec![Message::user("hello")], None)
+            .await
+            .is_err());
+        assert_eq!(replay.remaining(), 0);
+
+        let replay = ReplayProvider::from_file(&path).await.unwrap().in_order();
+        let reply = replay
+            .generate(vec![Message::user("anything")], None)
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "you said hello");
+        let _ = std::fs::remove_file(&path);
+    }
+}
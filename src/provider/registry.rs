@@ -0,0 +1,237 @@
+//! Construct providers by name from configuration.
+//!
+//! Applications that read their provider choice from a config file register
+//! factories under a kind ("anthropic", "openrouter", ...) and build a boxed
+//! `LlmProvider` from a `ProviderConfig` at runtime.
+
+use super::{
+    AnthropicProvider, GroqProvider, LlmProvider, MistralProvider, OllamaProvider, OpenAIProvider,
+    OpenAiCompatProvider, OpenRouterProvider, ProviderError, Result,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Provider selection as it would appear in a config file
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ProviderConfig {
+    /// Registered provider kind, e.g. `"anthropic"`
+    pub kind: String,
+    pub model: String,
+    /// Falls back to the provider's usual environment variable when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+}
+
+impl ProviderConfig {
+    pub fn new(kind: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            model: model.into(),
+            api_key: None,
+            base_url: None,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// The configured key, or the value of `env_var` if none was configured
+    pub fn api_key_or_env(&self, env_var: &str) -> Option<String> {
+        self.api_key
+            .clone()
+            .or_else(|| std::env::var(env_var).ok().filter(|k| !k.is_empty()))
+    }
+}
+
+/// Builds a provider from its configuration
+pub type ProviderFactory =
+    Arc<dyn Fn(&ProviderConfig) -> Result<Box<dyn LlmProvider>> + Send + Sync>;
+
+/// Named provider factories
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    factories: HashMap<String, ProviderFactory>,
+}
+
+impl ProviderRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with every provider built into the crate
+    ///
+    /// Kinds: `anthropic`, `openrouter`, `openai`, `mistral`, `groq`, `ollama`,
+    /// `openai-compatible` (requires `base_url`) and, with the `bedrock`
+    /// feature, `bedrock`.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register("anthropic", |config| {
+            let mut builder = AnthropicProvider::builder().model(&config.model);
+            if let Some(key) = config.api_key_or_env("ANTHROPIC_API_KEY") {
+                builder = builder.api_key(key);
+            }
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry.register("openrouter", |config| {
+            let mut builder = OpenRouterProvider::builder().model(&config.model);
+            if let Some(key) = config.api_key_or_env("OPENROUTER_API_KEY") {
+                builder = builder.api_key(key);
+            }
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry.register("openai", |config| {
+            let mut builder = OpenAIProvider::builder().model(&config.model);
+            if let Some(key) = config.api_key_or_env("OPENAI_API_KEY") {
+                builder = builder.api_key(key);
+            }
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry.register("mistral", |config| {
+            let mut builder = MistralProvider::builder().model(&config.model);
+            if let Some(key) = config.api_key_or_env("MISTRAL_API_KEY") {
+                builder = builder.api_key(key);
+            }
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry.register("groq", |config| {
+            let mut builder = GroqProvider::builder().model(&config.model);
+            if let Some(key) = config.api_key_or_env("GROQ_API_KEY") {
+                builder = builder.api_key(key);
+            }
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry.register("ollama", |config| {
+            let mut builder = OllamaProvider::builder().model(&config.model);
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry.register("openai-compatible", |config| {
+            let mut builder = OpenAiCompatProvider::builder().model(&config.model);
+            if let Some(key) = &config.api_key {
+                builder = builder.api_key(key);
+            }
+            if let Some(url) = &config.base_url {
+                builder = builder.base_url(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        #[cfg(feature = "bedrock")]
+        registry.register("bedrock", |config| {
+            let mut builder = super::BedrockProvider::builder().model(&config.model);
+            if let Some(url) = &config.base_url {
+                builder = builder.endpoint(url);
+            }
+            Ok(Box::new(builder.build()?))
+        });
+        registry
+    }
+
+    /// Register a factory, replacing any existing one for `kind`
+    pub fn register<F>(&mut self, kind: impl Into<String>, factory: F)
+    where
+        F: Fn(&ProviderConfig) -> Result<Box<dyn LlmProvider>> + Send + Sync + 'static,
+    {
+        self.factories.insert(kind.into(), Arc::new(factory));
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.factories.contains_key(kind)
+    }
+
+    /// Registered kinds, sorted
+    pub fn kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        kinds.sort_unstable();
+        kinds
+    }
+
+    /// Build the provider described by `config`
+    pub fn create(&self, config: &ProviderConfig) -> Result<Box<dyn LlmProvider>> {
+        let factory = self.factories.get(&config.kind).ok_or_else(|| {
+            ProviderError::Other(format!("Unknown provider kind: {}", config.kind))
+        })?;
+        factory(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_providers_by_kind() {
+        let registry = ProviderRegistry::with_defaults();
+        assert!(registry.contains("openrouter"));
+
+        let config = ProviderConfig::new("groq", "llama-3.1-8b-instant").with_api_key("test-key");
+        let provider = registry.create(&config).unwrap();
+        assert_eq!(provider.name(), "groq");
+        assert_eq!(provider.model(), "llama-3.1-8b-instant");
+
+        let unknown = ProviderConfig::new("nope", "model");
+        assert!(matches!(
+            registry.create(&unknown),
+            Err(ProviderError::Other(message)) if message.contains("nope")
+        ));
+    }
+
+    #[test]
+    fn reads_config_from_json() {
+        let config: ProviderConfig = serde_json::from_str(
+            r#"{"kind": "openai-compatible", "model": "local", "base_url": "http://localhost:8000/v1"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.api_key, None);
+
+        let provider = ProviderRegistry::with_defaults().create(&config).unwrap();
+        assert_eq!(provider.name(), "openai-compatible");
+    }
+
+    #[test]
+    fn custom_factories_override_defaults() {
+        let mut registry = ProviderRegistry::with_defaults();
+        registry.register("ollama", |config| {
+            Ok(Box::new(
+                OllamaProvider::builder()
+                    .model(&config.model)
+                    .base_url("http://gpu-box:11434")
+                    .build()?,
+            ))
+        });
+        let provider = registry
+            .create(&ProviderConfig::new("ollama", "llama3.2"))
+            .unwrap();
+        assert_eq!(provider.name(), "ollama");
+        assert_eq!(
+            registry.kinds().iter().filter(|k| **k == "ollama").count(),
+            1
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use std::future::Future;
-use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use crate::provider::{Result, ProviderError};
 
 /// Configuration for retry behavior
@@ -64,16 +66,70 @@ impl RetryConfig {
     }
 }
 
+/// End times of the backoffs retries are currently sleeping through
+#[derive(Debug, Default)]
+struct Backoffs {
+    next_id: AtomicU64,
+    until: Mutex<HashMap<u64, Instant>>,
+}
+
+/// Forgets a backoff once its sleep ends or is cancelled
+struct BackoffGuard<'a> {
+    backoffs: &'a Backoffs,
+    id: u64,
+}
+
+impl Drop for BackoffGuard<'_> {
+    fn drop(&mut self) {
+        self.backoffs.until.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Backoffs {
+    fn start(&self, backoff: Duration) -> BackoffGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.until
+            .lock()
+            .unwrap()
+            .insert(id, Instant::now() + backoff);
+        BackoffGuard { backoffs: self, id }
+    }
+}
+
 /// Policy for handling retries with exponential backoff
+///
+/// Clones share the record of backoffs in flight.
 #[derive(Debug, Clone)]
 pub struct RetryPolicy {
     config: RetryConfig,
+    backoffs: Arc<Backoffs>,
 }
 
 impl RetryPolicy {
     /// Create a new retry policy with the given configuration
     pub fn new(config: RetryConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            backoffs: Arc::default(),
+        }
+    }
+
+    /// Number of retries sleeping through a backoff, and the longest time
+    /// left on any of them
+    pub fn backoffs_in_flight(&self) -> (usize, Duration) {
+        let now = Instant::now();
+        let until = self.backoffs.until.lock().unwrap();
+        let longest = until
+            .values()
+            .map(|end| end.saturating_duration_since(now))
+            .max()
+            .unwrap_or_default();
+        (until.len(), longest)
+    }
+
+    async fn sleep_backoff(&self, backoff: Duration) {
+        let _guard = self.backoffs.start(backoff);
+        tokio::time::sleep(backoff).await;
     }
 
     /// Determine if an error should be retried
@@ -83,20 +139,22 @@ impl RetryPolicy {
         }
 
         match error {
+            // Retry timeouts if configured
+            ProviderError::RequestFailed(msg) if msg.contains("timeout") => {
+                self.config.retry_on_timeout
+            }
             // Always retry server errors
             ProviderError::RequestFailed(msg) => {
                 msg.contains("502") || msg.contains("503") || msg.contains("504")
             }
             // Retry rate limits if configured
             ProviderError::RateLimited { .. } => self.config.retry_on_rate_limit,
-            // Retry timeouts if configured
-            ProviderError::RequestFailed(msg) if msg.contains("timeout") => {
-                self.config.retry_on_timeout
-            }
             // Don't retry authentication or parse errors
             ProviderError::AuthenticationFailed(_) | ProviderError::ParseError(_) => false,
             // Don't retry model not available
             ProviderError::ModelNotAvailable(_) => false,
+            // Output was already delivered when a stream times out
+            ProviderError::StreamTimeout { .. } => false,
             // Don't retry other errors by default
             ProviderError::Other(_) => false,
         }
@@ -144,7 +202,7 @@ impl RetryPolicy {
                         backoff
                     );
 
-                    tokio::time::sleep(backoff).await;
+                    self.sleep_backoff(backoff).await;
                     attempt += 1;
                 }
             }
@@ -175,7 +233,7 @@ impl RetryPolicy {
                     let backoff = self.calculate_backoff(attempt);
                     on_retry(attempt + 1, &error, backoff);
 
-                    tokio::time::sleep(backoff).await;
+                    self.sleep_backoff(backoff).await;
                     attempt += 1;
                 }
             }
@@ -207,6 +265,18 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_retry_on_timeout_decides_timeouts() {
+        let timeout = ProviderError::RequestFailed("operation timeout".to_string());
+        assert!(RetryPolicy::default().should_retry(&timeout, 0));
+
+        let policy = RetryPolicy::new(RetryConfig {
+            retry_on_timeout: false,
+            ..Default::default()
+        });
+        assert!(!policy.should_retry(&timeout, 0));
+    }
+
     #[test]
     fn test_should_not_retry_auth_errors() {
         let policy = RetryPolicy::default();
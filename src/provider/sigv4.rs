@@ -0,0 +1,247 @@
+//! Minimal AWS Signature Version 4 request signing.
+//!
+//! Only what the Bedrock provider needs: POST/GET requests with a JSON body,
+//! no query string, signing `host`, `x-amz-date` and, for temporary
+//! credentials, `x-amz-security-token`.
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// AWS credentials used to sign requests
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+
+    /// Attach a session token for temporary (STS) credentials
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// Read `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    pub fn from_env() -> Option<Self> {
+        let access_key_id = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+        let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+        Some(Self {
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"<redacted>")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Request to be signed
+pub(super) struct SigningRequest<'a> {
+    pub method: &'a str,
+    pub host: &'a str,
+    /// Path as sent on the wire, already percent-encoded
+    pub path: &'a str,
+    pub body: &'a [u8],
+    pub region: &'a str,
+    pub service: &'a str,
+}
+
+/// Headers to add to the signed request
+pub(super) struct SignedHeaders {
+    pub authorization: String,
+    pub amz_date: String,
+    pub security_token: Option<String>,
+}
+
+pub(super) fn sign(
+    request: &SigningRequest<'_>,
+    credentials: &AwsCredentials,
+    time: SystemTime,
+) -> SignedHeaders {
+    let amz_date = format_amz_date(time);
+    let date = &amz_date[..8];
+
+    let mut headers = vec![
+        ("host", request.host.to_string()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    // Non-S3 services expect each path segment to be encoded a second time
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        request.method,
+        uri_encode_path(request.path),
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(request.body)),
+    );
+
+    let scope = format!(
+        "{}/{}/{}/aws4_request",
+        date, request.region, request.service
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes())),
+    );
+
+    let k_date = hmac(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    let k_region = hmac(&k_date, request.region.as_bytes());
+    let k_service = hmac(&k_region, request.service.as_bytes());
+    let k_signing = hmac(&k_service, b"aws4_request");
+    let signature = hex::encode(hmac(&k_signing, string_to_sign.as_bytes()));
+
+    SignedHeaders {
+        authorization: format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        ),
+        amz_date,
+        security_token: credentials.session_token.clone(),
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode every byte outside the unreserved set
+pub(super) fn uri_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn uri_encode_path(path: &str) -> String {
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Format a time as `YYYYMMDD'T'HHMMSS'Z'`
+fn format_amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        (rem % 3_600) / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_amz_date() {
+        // 2015-08-30T12:36:00Z
+        let time = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+        assert_eq!(format_amz_date(time), "20150830T123600Z");
+    }
+
+    #[test]
+    fn matches_aws_get_vanilla_test_vector() {
+        let credentials =
+            AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
+        let request = SigningRequest {
+            method: "GET",
+            host: "example.amazonaws.com",
+            path: "/",
+            body: b"",
+            region: "us-east-1",
+            service: "service",
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+
+        let signed = sign(&request, &credentials, time);
+        assert_eq!(
+            signed.authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn double_encodes_path_segments() {
+        assert_eq!(
+            uri_encode_path("/model/anthropic.claude-v2%3A1/invoke"),
+            "/model/anthropic.claude-v2%253A1/invoke"
+        );
+    }
+}
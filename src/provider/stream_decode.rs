@@ -0,0 +1,90 @@
+//! Framing for streamed HTTP responses.
+//!
+//! Chunks are buffered as bytes and split into lines without decoding UTF-8
+//! first, so a character split across two chunks survives and a line costs
+//! no allocation or copy of its own.
+
+use bytes::{Bytes, BytesMut};
+
+/// Splits a byte stream into lines
+#[derive(Debug, Default)]
+pub struct LineDecoder {
+    buffer: BytesMut,
+    /// Bytes at the start of `buffer` already known to hold no newline
+    scanned: usize,
+}
+
+impl LineDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a chunk as received from the network
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Next complete line, with surrounding whitespace and the line ending
+    /// trimmed; blank lines come back empty
+    pub fn next_line(&mut self) -> Option<Bytes> {
+        let Some(offset) = self.buffer[self.scanned..].iter().position(|&b| b == b'\n') else {
+            self.scanned = self.buffer.len();
+            return None;
+        };
+        let line = self.buffer.split_to(self.scanned + offset + 1).freeze();
+        self.scanned = 0;
+        Some(trim(line))
+    }
+
+    /// Bytes received after the last complete line
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+fn trim(line: Bytes) -> Bytes {
+    let start = line
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(line.len());
+    let end = line
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    line.slice(start..end)
+}
+
+/// Payload of a server-sent events `data:` line
+pub fn sse_data(line: &[u8]) -> Option<&[u8]> {
+    let data = line.strip_prefix(b"data:")?;
+    Some(data.strip_prefix(b" ").unwrap_or(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lines_across_chunks() {
+        let mut lines = LineDecoder::new();
+        lines.push(b"data: {\"a\"");
+        assert_eq!(lines.next_line(), None);
+        lines.push(b":1}\r\n\r\ndata: caf\xC3");
+        assert_eq!(lines.next_line().as_deref(), Some(&b"data: {\"a\":1}"[..]));
+        assert_eq!(lines.next_line().as_deref(), Some(&b""[..]));
+        assert_eq!(lines.next_line(), None);
+
+        // A character split between chunks is not mangled
+        lines.push(b"\xA9\n");
+        let line = lines.next_line().unwrap();
+        assert_eq!(std::str::from_utf8(&line).unwrap(), "data: café");
+        assert_eq!(lines.pending(), 0);
+    }
+
+    #[test]
+    fn strips_the_data_field_name() {
+        assert_eq!(sse_data(b"data: [DONE]"), Some(&b"[DONE]"[..]));
+        assert_eq!(sse_data(b"data:{}"), Some(&b"{}"[..]));
+        assert_eq!(sse_data(b"event: ping"), None);
+    }
+}
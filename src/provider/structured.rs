@@ -0,0 +1,327 @@
+//! Structured (JSON) output with schema validation and repair retries.
+//!
+//! Providers that declare `structured_output` receive the schema as a native
+//! `response_format`; for the others the schema is sent as an instruction.
+//! Either way the reply is validated against the schema and, if it does not
+//! parse, the model is asked to correct it.
+
+use super::{GenerateOptions, LlmProvider, Message, ProviderError, Result};
+use crate::schema::validate;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Repair prompts sent after the first reply before giving up
+pub const STRUCTURED_REPAIR_ATTEMPTS: u32 = 2;
+
+/// Output format requested from the model
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    /// Any valid JSON value
+    Json,
+    /// JSON matching a schema
+    JsonSchema(JsonSchema),
+}
+
+/// Named JSON Schema for structured output
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonSchema {
+    /// Identifier sent to providers that require one (letters, digits, `_`, `-`)
+    pub name: String,
+    pub schema: Value,
+    /// Ask the provider to enforce the schema exactly where supported
+    pub strict: bool,
+}
+
+impl JsonSchema {
+    pub fn new(name: impl Into<String>, schema: Value) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            strict: false,
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Instruction used when the provider cannot enforce the schema itself
+    pub(crate) fn instruction(&self) -> String {
+        format!(
+            "Respond with only a JSON value that matches this JSON Schema, without any other text:\n{}",
+            self.schema
+        )
+    }
+
+    /// Parse `content` as JSON, check it against the schema and deserialize it
+    pub fn parse<T: DeserializeOwned>(&self, content: &str) -> std::result::Result<T, String> {
+        let value = extract_json(content)?;
+        let errors = validate(&value, &self.schema);
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+/// Find the JSON value in a reply, ignoring code fences and surrounding prose
+fn extract_json(content: &str) -> std::result::Result<Value, String> {
+    let trimmed = content.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed)
+        .trim();
+    match serde_json::from_str(unfenced) {
+        Ok(value) => Ok(value),
+        Err(error) => {
+            let start = unfenced.find(['{', '[']);
+            let end = unfenced.rfind(['}', ']']);
+            match (start, end) {
+                (Some(start), Some(end)) if start < end => {
+                    serde_json::from_str(&unfenced[start..=end]).map_err(|e| e.to_string())
+                }
+                _ => Err(error.to_string()),
+            }
+        }
+    }
+}
+
+fn repair_prompt(error: &str) -> String {
+    format!(
+        "Your previous reply did not match the required JSON Schema: {}. Reply with only the corrected JSON.",
+        error
+    )
+}
+
+/// Options for a structured request, using the native response format when
+/// the provider supports it
+pub(crate) fn structured_options<P: LlmProvider + ?Sized>(
+    provider: &P,
+    schema: &JsonSchema,
+    options: Option<GenerateOptions>,
+) -> (Option<GenerateOptions>, bool) {
+    if provider.capabilities().structured_output {
+        let mut options = options.unwrap_or_default();
+        options.response_format = Some(ResponseFormat::JsonSchema(schema.clone()));
+        (Some(options), true)
+    } else {
+        (options, false)
+    }
+}
+
+/// Parse `content`, asking the model to correct it up to
+/// `STRUCTURED_REPAIR_ATTEMPTS` times
+///
+/// `messages` is the conversation that produced `content`, without the reply.
+pub(crate) async fn parse_with_repair<T, P>(
+    provider: &P,
+    mut messages: Vec<Message>,
+    mut content: String,
+    schema: &JsonSchema,
+    options: Option<GenerateOptions>,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: LlmProvider + ?Sized,
+{
+    let mut attempt = 0;
+    loop {
+        let error = match schema.parse(&content) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        if attempt == STRUCTURED_REPAIR_ATTEMPTS {
+            return Err(ProviderError::ParseError(format!(
+                "Structured output did not match schema '{}': {}",
+                schema.name, error
+            )));
+        }
+        attempt += 1;
+        messages.push(Message::assistant(content));
+        messages.push(Message::user(repair_prompt(&error)));
+        content = provider
+            .generate(messages.clone(), options.clone())
+            .await?
+            .content;
+    }
+}
+
+/// Generate a reply and deserialize it as `T` after validating it against
+/// `schema`; see `LlmProvider::generate_structured`
+pub(crate) async fn generate_structured<T, P>(
+    provider: &P,
+    mut messages: Vec<Message>,
+    schema: &JsonSchema,
+    options: Option<GenerateOptions>,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: LlmProvider + ?Sized,
+{
+    let (options, native) = structured_options(provider, schema, options);
+    if !native {
+        messages.push(Message::system(schema.instruction()));
+    }
+    let content = provider
+        .generate(messages.clone(), options.clone())
+        .await?
+        .content;
+    parse_with_repair(provider, messages, content, schema, options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{GenerateResponse, ProviderCapabilities};
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Weather {
+        city: String,
+        celsius: i64,
+    }
+
+    fn weather_schema() -> JsonSchema {
+        JsonSchema::new(
+            "weather",
+            json!({
+                "type": "object",
+                "properties": {
+                    "city": {"type": "string"},
+                    "celsius": {"type": "integer", "minimum": -90, "maximum": 60}
+                },
+                "required": ["city", "celsius"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    struct Scripted {
+        native: bool,
+        replies: Mutex<Vec<&'static str>>,
+        requests: Mutex<Vec<(Vec<Message>, Option<GenerateOptions>)>>,
+    }
+
+    impl Scripted {
+        fn new(native: bool, mut replies: Vec<&'static str>) -> Self {
+            replies.reverse();
+            Self {
+                native,
+                replies: Mutex::new(replies),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl LlmProvider for Scripted {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn model(&self) -> &str {
+            "m"
+        }
+
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                structured_output: self.native,
+                ..Default::default()
+            }
+        }
+
+        fn generate(
+            &self,
+            messages: Vec<Message>,
+            options: Option<GenerateOptions>,
+        ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+            self.requests.lock().unwrap().push((messages, options));
+            let content = self.replies.lock().unwrap().pop().unwrap_or("").to_string();
+            Box::pin(async move {
+                Ok(GenerateResponse {
+                    content,
+                    usage: None,
+                    model: "m".to_string(),
+                    finish_reason: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+        }
+    }
+
+    #[test]
+    fn validates_common_schema_keywords() {
+        let schema = weather_schema().schema;
+        assert!(validate(&json!({"city": "Oslo", "celsius": 3}), &schema).is_empty());
+
+        let errors = validate(&json!({"celsius": 3.5, "wind": 4}), &schema);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("'city'")));
+        assert!(errors.iter().any(|e| e.starts_with("$.celsius")));
+        assert!(errors.iter().any(|e| e.starts_with("$.wind")));
+    }
+
+    #[test]
+    fn extracts_json_from_fenced_or_wrapped_replies() {
+        let schema = weather_schema();
+        let fenced: Weather = schema
+            .parse("```json\n{\"city\": \"Oslo\", \"celsius\": 3}\n```")
+            .unwrap();
+        assert_eq!(fenced.city, "Oslo");
+        let wrapped: Weather = schema
+            .parse("Here you go: {\"city\": \"Rome\", \"celsius\": 21} Enjoy!")
+            .unwrap();
+        assert_eq!(wrapped.celsius, 21);
+    }
+
+    #[tokio::test]
+    async fn sends_native_format_and_repairs_invalid_replies() {
+        let provider = Scripted::new(
+            true,
+            vec![
+                "{\"city\": \"Oslo\"}",
+                "{\"city\": \"Oslo\", \"celsius\": 3}",
+            ],
+        );
+        let weather: Weather = provider
+            .generate_structured(vec![Message::user("Weather?")], &weather_schema(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            weather,
+            Weather {
+                city: "Oslo".into(),
+                celsius: 3
+            }
+        );
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let format = requests[0].1.as_ref().unwrap().response_format.clone();
+        assert_eq!(format, Some(ResponseFormat::JsonSchema(weather_schema())));
+        // The repair request carries the bad reply and the validation error
+        let repair = &requests[1].0;
+        assert_eq!(repair.len(), 3);
+        assert!(repair[2].content_as_text().contains("'celsius'"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_instructions_and_gives_up_after_repairs() {
+        let provider = Scripted::new(false, vec!["nope", "still no", "{}"]);
+        let result: Result<Weather> = provider
+            .generate_structured(vec![Message::user("Weather?")], &weather_schema(), None)
+            .await;
+        assert!(matches!(result, Err(ProviderError::ParseError(_))));
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1 + STRUCTURED_REPAIR_ATTEMPTS as usize);
+        assert!(requests[0].1.is_none());
+        assert!(requests[0].0[1].content_as_text().contains("JSON Schema"));
+    }
+}
@@ -1,4 +1,6 @@
-use std::time::Duration;
+use super::{ProviderError, Result};
+use futures_util::{Stream, StreamExt};
+use std::time::{Duration, Instant};
 
 /// Configuration for various timeout settings
 #[derive(Debug, Clone)]
@@ -7,8 +9,12 @@ pub struct TimeoutConfig {
     pub connect_timeout: Duration,
     /// Timeout for the entire request (including response)
     pub request_timeout: Duration,
-    /// Optional timeout for streaming responses
+    /// Longest a streamed response may take in total, replacing
+    /// `request_timeout` for streams; `None` leaves them bounded by
+    /// `request_timeout`
     pub stream_timeout: Option<Duration>,
+    /// Longest a streamed response may go without sending data
+    pub stream_idle_timeout: Option<Duration>,
 }
 
 impl Default for TimeoutConfig {
@@ -17,6 +23,7 @@ impl Default for TimeoutConfig {
             connect_timeout: Duration::from_secs(10),
             request_timeout: Duration::from_secs(120),
             stream_timeout: Some(Duration::from_secs(300)),
+            stream_idle_timeout: Some(Duration::from_secs(60)),
         }
     }
 }
@@ -32,15 +39,23 @@ impl TimeoutConfig {
             connect_timeout,
             request_timeout,
             stream_timeout,
+            ..Self::default()
         }
     }
 
+    /// Set how long a stream may go without data; `None` disables the check
+    pub fn stream_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.stream_idle_timeout = timeout;
+        self
+    }
+
     /// Create a timeout configuration with shorter timeouts for quick operations
     pub fn fast() -> Self {
         Self {
             connect_timeout: Duration::from_secs(5),
             request_timeout: Duration::from_secs(30),
             stream_timeout: Some(Duration::from_secs(60)),
+            stream_idle_timeout: Some(Duration::from_secs(20)),
         }
     }
 
@@ -50,6 +65,98 @@ impl TimeoutConfig {
             connect_timeout: Duration::from_secs(30),
             request_timeout: Duration::from_secs(300),
             stream_timeout: Some(Duration::from_secs(600)),
+            stream_idle_timeout: Some(Duration::from_secs(180)),
+        }
+    }
+
+    /// Deadlines for a streamed response whose request starts now
+    #[cfg_attr(not(feature = "providers"), allow(dead_code))]
+    pub(crate) fn stream_deadline(&self) -> StreamDeadline {
+        StreamDeadline {
+            idle: self.stream_idle_timeout,
+            total: self.stream_timeout.map(|limit| (Instant::now(), limit)),
         }
     }
 }
+
+/// Which stream limit ran out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamTimeoutKind {
+    /// `stream_idle_timeout`: no data arrived for too long
+    Idle,
+    /// `stream_timeout`: the whole stream took too long
+    Total,
+}
+
+/// Idle and total deadlines for reading one streamed response
+#[cfg_attr(not(feature = "providers"), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreamDeadline {
+    idle: Option<Duration>,
+    total: Option<(Instant, Duration)>,
+}
+
+#[cfg_attr(not(feature = "providers"), allow(dead_code))]
+impl StreamDeadline {
+    /// Next item of `stream`, or `ProviderError::StreamTimeout` if it does
+    /// not arrive in time
+    pub(crate) async fn next<S: Stream + Unpin>(&self, stream: &mut S) -> Result<Option<S::Item>> {
+        let remaining = self
+            .total
+            .map(|(started, limit)| (limit.saturating_sub(started.elapsed()), limit));
+        let (wait, kind, after) = match (self.idle, remaining) {
+            (Some(idle), Some((left, _))) if idle < left => (idle, StreamTimeoutKind::Idle, idle),
+            (_, Some((left, limit))) => (left, StreamTimeoutKind::Total, limit),
+            (Some(idle), None) => (idle, StreamTimeoutKind::Idle, idle),
+            (None, None) => return Ok(stream.next().await),
+        };
+        tokio::time::timeout(wait, stream.next())
+            .await
+            .map_err(|_| ProviderError::StreamTimeout { kind, after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stalled_streams_time_out() {
+        let config = TimeoutConfig {
+            stream_timeout: Some(Duration::from_millis(200)),
+            stream_idle_timeout: Some(Duration::from_millis(20)),
+            ..TimeoutConfig::default()
+        };
+
+        // Idle: nothing after the first chunk
+        let deadline = config.stream_deadline();
+        let mut stalled = futures_util::stream::iter([1]).chain(futures_util::stream::pending());
+        assert_eq!(deadline.next(&mut stalled).await.unwrap(), Some(1));
+        assert!(matches!(
+            deadline.next(&mut stalled).await,
+            Err(ProviderError::StreamTimeout {
+                kind: StreamTimeoutKind::Idle,
+                ..
+            })
+        ));
+
+        // Total: chunks keep coming, but too slowly overall
+        let deadline = config.stream_deadline();
+        let mut trickle = Box::pin(futures_util::stream::unfold((), |_| async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Some(((), ()))
+        }));
+        let error = loop {
+            if let Err(error) = deadline.next(&mut trickle).await {
+                break error;
+            }
+        };
+        assert!(matches!(
+            error,
+            ProviderError::StreamTimeout {
+                kind: StreamTimeoutKind::Total,
+                after,
+            } if after == Duration::from_millis(200)
+        ));
+    }
+}
@@ -0,0 +1,445 @@
+//! Token counting for context window decisions.
+//!
+//! `CharEstimateTokenizer` is the dependency-free default. With the
+//! `tiktoken` feature, `TiktokenTokenizer` counts with the BPE vocabularies
+//! OpenAI models use, which also approximate Claude closely enough for
+//! truncation.
+//!
+//! Text is only part of what a provider bills. A `BillingProfile` adds the
+//! per-message framing, the tool definitions and the image tokens the
+//! provider charges for, so counts line up with reported usage.
+
+use super::{ContentBlock, ImageDetail, ImageSource, Message, ToolSchema};
+use std::sync::Arc;
+
+/// Counts tokens the way a model would
+pub trait Tokenizer: Send + Sync {
+    /// Tokens in `text`
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// How the provider frames and bills content beyond text
+    fn billing(&self) -> BillingProfile {
+        BillingProfile::default()
+    }
+
+    /// Tokens a message occupies in a request, including any framing
+    fn count_message_tokens(&self, message: &Message) -> usize {
+        let billing = self.billing();
+        let content: usize = message
+            .content
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text } => self.count_tokens(text),
+                ContentBlock::Image { source, detail } => billing.image_tokens(source, *detail),
+                ContentBlock::ToolUse { name, input, .. } => {
+                    self.count_tokens(name) + self.count_tokens(&input.to_string())
+                }
+                // Size unknown until the provider reads the file
+                ContentBlock::File { .. } => 0,
+                ContentBlock::ToolResult {
+                    content, images, ..
+                } => {
+                    self.count_tokens(content)
+                        + images
+                            .iter()
+                            .map(|image| billing.image_tokens(image, None))
+                            .sum::<usize>()
+                }
+            })
+            .sum();
+        content + billing.per_message
+    }
+
+    /// Tokens the tool definitions add to a request
+    fn count_tool_tokens(&self, tools: &[ToolSchema]) -> usize {
+        if tools.is_empty() {
+            return 0;
+        }
+        let billing = self.billing();
+        let definitions: usize = tools
+            .iter()
+            .map(|tool| {
+                let definition = match &tool.native {
+                    Some(native) => self.count_tokens(&native.to_string()),
+                    None => {
+                        self.count_tokens(&tool.name)
+                            + self.count_tokens(&tool.description)
+                            + self.count_tokens(&tool.parameters.to_string())
+                    }
+                };
+                definition + billing.per_tool
+            })
+            .sum();
+        definitions + billing.tools_overhead
+    }
+
+    /// Prompt tokens for a whole request, as the provider would bill them
+    fn count_request_tokens(&self, messages: &[Message], tools: &[ToolSchema]) -> usize {
+        let messages: usize = messages.iter().map(|m| self.count_message_tokens(m)).sum();
+        messages + self.count_tool_tokens(tools) + self.billing().per_request
+    }
+}
+
+/// How a provider prices images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageBilling {
+    /// 85 tokens plus 170 per 512px tile after scaling, as OpenAI bills
+    Tiles,
+    /// One token per 750 pixels after scaling, as Anthropic bills
+    Pixels,
+}
+
+/// Tokens a provider adds around the content of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BillingProfile {
+    /// Role and separators around each message
+    pub per_message: usize,
+    /// Priming for the reply, once per request
+    pub per_request: usize,
+    /// Framing around each tool definition
+    pub per_tool: usize,
+    /// Added once when any tools are sent, e.g. a tool-use system prompt
+    pub tools_overhead: usize,
+    pub images: ImageBilling,
+}
+
+impl Default for BillingProfile {
+    fn default() -> Self {
+        Self::openai()
+    }
+}
+
+impl BillingProfile {
+    pub fn openai() -> Self {
+        Self {
+            per_message: 3,
+            per_request: 3,
+            per_tool: 7,
+            tools_overhead: 12,
+            images: ImageBilling::Tiles,
+        }
+    }
+
+    pub fn anthropic() -> Self {
+        Self {
+            per_message: 3,
+            per_request: 0,
+            per_tool: 0,
+            tools_overhead: 346,
+            images: ImageBilling::Pixels,
+        }
+    }
+
+    /// Profile for `model`: Anthropic's for Claude models, OpenAI's otherwise
+    pub fn for_model(model: &str) -> Self {
+        if model.contains("claude") {
+            Self::anthropic()
+        } else {
+            Self::openai()
+        }
+    }
+
+    /// Tokens billed for an image; images whose size cannot be read, such
+    /// as URLs, are billed as 1024x1024
+    pub fn image_tokens(&self, image: &ImageSource, detail: Option<ImageDetail>) -> usize {
+        let (width, height) = image_dimensions(image).unwrap_or((1024, 1024));
+        let (width, height) = (width as f64, height as f64);
+        match self.images {
+            ImageBilling::Tiles if detail == Some(ImageDetail::Low) => 85,
+            ImageBilling::Tiles => {
+                let fit = (2048.0 / width.max(height)).min(1.0);
+                let shortest = (768.0 / (width.min(height) * fit)).min(1.0);
+                let scale = fit * shortest;
+                let tiles = ((width * scale) / 512.0).ceil() * ((height * scale) / 512.0).ceil();
+                85 + 170 * tiles as usize
+            }
+            ImageBilling::Pixels => {
+                let scale = (1568.0 / width.max(height))
+                    .min((1_150_000.0 / (width * height)).sqrt())
+                    .min(1.0);
+                ((width * scale) * (height * scale) / 750.0).ceil() as usize
+            }
+        }
+    }
+}
+
+/// Pixel size of a base64 PNG, GIF or JPEG image
+fn image_dimensions(image: &ImageSource) -> Option<(u32, u32)> {
+    let ImageSource::Base64 { data, .. } = image else {
+        return None;
+    };
+    // JPEG frame headers usually follow the metadata in the first few KB
+    let bytes = decode_base64_prefix(data, 64 * 1024);
+    let be16 = |at: usize| Some(u16::from_be_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]) as u32);
+    if bytes.starts_with(b"\x89PNG") {
+        let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        let le16 =
+            |at: usize| Some(u16::from_le_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]) as u32);
+        return Some((le16(6)?, le16(8)?));
+    }
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut at = 2;
+        while *bytes.get(at)? == 0xFF {
+            let marker = *bytes.get(at + 1)?;
+            // Start-of-frame markers, excluding DHT, JPG and DAC
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                return Some((be16(at + 7)?, be16(at + 5)?));
+            }
+            at += 2 + be16(at + 2)? as usize;
+        }
+    }
+    None
+}
+
+/// Decode up to `max_bytes` from the start of standard base64 `data`
+fn decode_base64_prefix(data: &str, max_bytes: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in data.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => break,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            if bytes.len() >= max_bytes {
+                break;
+            }
+        }
+    }
+    bytes
+}
+
+/// Estimates tokens from text length
+#[derive(Debug, Clone, Copy)]
+pub struct CharEstimateTokenizer {
+    pub chars_per_token: usize,
+    pub billing: BillingProfile,
+}
+
+impl Default for CharEstimateTokenizer {
+    /// About 4 bytes per token, typical for English text
+    fn default() -> Self {
+        Self {
+            chars_per_token: 4,
+            billing: BillingProfile::default(),
+        }
+    }
+}
+
+impl CharEstimateTokenizer {
+    pub fn with_billing(mut self, billing: BillingProfile) -> Self {
+        self.billing = billing;
+        self
+    }
+}
+
+impl Tokenizer for CharEstimateTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        text.len().div_ceil(self.chars_per_token.max(1))
+    }
+
+    fn billing(&self) -> BillingProfile {
+        self.billing
+    }
+}
+
+/// Exact BPE counts for OpenAI models (feature = "tiktoken")
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenTokenizer {
+    bpe: &'static tiktoken_rs::CoreBPE,
+    billing: BillingProfile,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenTokenizer {
+    /// `o200k_base`, used by GPT-4o, GPT-4.1 and the o-series
+    pub fn o200k() -> Self {
+        Self {
+            bpe: tiktoken_rs::o200k_base_singleton(),
+            billing: BillingProfile::openai(),
+        }
+    }
+
+    /// `cl100k_base`, used by GPT-4 and GPT-3.5
+    pub fn cl100k() -> Self {
+        Self {
+            bpe: tiktoken_rs::cl100k_base_singleton(),
+            billing: BillingProfile::openai(),
+        }
+    }
+
+    /// Vocabulary for `model`; unknown models, including Claude, use
+    /// `cl100k_base` as an approximation
+    pub fn for_model(model: &str) -> Self {
+        let name = model.rsplit('/').next().unwrap_or(model);
+        let tokenizer = match tiktoken_rs::tokenizer::get_tokenizer(name) {
+            Some(tiktoken_rs::tokenizer::Tokenizer::O200kBase) => Self::o200k(),
+            _ => Self::cl100k(),
+        };
+        tokenizer.with_billing(BillingProfile::for_model(model))
+    }
+
+    pub fn with_billing(mut self, billing: BillingProfile) -> Self {
+        self.billing = billing;
+        self
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for TiktokenTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+
+    fn billing(&self) -> BillingProfile {
+        self.billing
+    }
+}
+
+/// Most accurate tokenizer available for `model` in this build
+pub fn tokenizer_for_model(model: &str) -> Arc<dyn Tokenizer> {
+    #[cfg(feature = "tiktoken")]
+    {
+        Arc::new(TiktokenTokenizer::for_model(model))
+    }
+    #[cfg(not(feature = "tiktoken"))]
+    {
+        Arc::new(CharEstimateTokenizer::default().with_billing(BillingProfile::for_model(model)))
+    }
+}
+
+/// Context window size in tokens for well-known models
+///
+/// Matches on the model id without any `vendor/` routing prefix.
+pub fn context_window_for_model(model: &str) -> Option<usize> {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    const WINDOWS: &[(&str, usize)] = &[
+        ("gpt-4.1", 1_047_576),
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo", 16_385),
+        ("o1-mini", 128_000),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4-mini", 200_000),
+        ("claude", 200_000),
+        ("anthropic.claude", 200_000),
+        ("mistral-large", 128_000),
+        ("mistral-small", 32_000),
+        ("llama-3", 128_000),
+        ("llama3", 128_000),
+    ];
+    WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_from_length() {
+        let tokenizer = CharEstimateTokenizer::default();
+        assert_eq!(tokenizer.count_tokens("Hello world"), 3);
+        assert_eq!(
+            tokenizer.count_message_tokens(&Message::user("Hello world")),
+            6
+        );
+    }
+
+    #[test]
+    fn counts_tool_calls_and_definitions() {
+        let tokenizer = CharEstimateTokenizer::default();
+        let call = Message::assistant_with_tool_calls(
+            "",
+            &[crate::tool::ToolCall {
+                id: "call_1".to_string(),
+                name: "calc".to_string(),
+                parameters: serde_json::json!({"a": 1}),
+            }],
+        );
+        // "calc" + `{"a":1}` + framing
+        assert_eq!(tokenizer.count_message_tokens(&call), 1 + 2 + 3);
+
+        let tools = vec![ToolSchema {
+            name: "calc".to_string(),
+            description: "Adds".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+            native: None,
+        }];
+        // 1 + 1 + 5 for the definition, 7 per tool and 12 per request
+        assert_eq!(tokenizer.count_tool_tokens(&tools), 26);
+        assert_eq!(
+            tokenizer.count_request_tokens(&[Message::user("Hello world")], &tools),
+            6 + 26 + 3
+        );
+
+        let claude = CharEstimateTokenizer::default()
+            .with_billing(BillingProfile::for_model("claude-3-5-haiku"));
+        assert_eq!(claude.count_tool_tokens(&tools), 7 + 346);
+    }
+
+    #[test]
+    fn bills_images_by_size() {
+        // 1x1 PNG, header through IHDR
+        let png = ImageSource::Base64 {
+            media_type: "image/png".to_string(),
+            data: "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==".to_string(),
+        };
+        assert_eq!(image_dimensions(&png), Some((1, 1)));
+
+        let openai = BillingProfile::openai();
+        let anthropic = BillingProfile::anthropic();
+        assert_eq!(openai.image_tokens(&png, None), 85 + 170);
+        assert_eq!(anthropic.image_tokens(&png, None), 1);
+
+        let url = ImageSource::Url {
+            url: "https://example.com/cat.png".to_string(),
+        };
+        // Assumed 1024x1024: scaled to 768x768, four tiles
+        assert_eq!(openai.image_tokens(&url, None), 765);
+        assert_eq!(openai.image_tokens(&url, Some(ImageDetail::Low)), 85);
+        assert_eq!(anthropic.image_tokens(&url, None), 1399);
+    }
+
+    #[test]
+    fn knows_common_context_windows() {
+        assert_eq!(context_window_for_model("gpt-4o-mini"), Some(128_000));
+        assert_eq!(context_window_for_model("gpt-4-0613"), Some(8_192));
+        assert_eq!(
+            context_window_for_model("anthropic/claude-3.5-sonnet"),
+            Some(200_000)
+        );
+        assert_eq!(context_window_for_model("my-local-model"), None);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn counts_bpe_tokens() {
+        let tokenizer = TiktokenTokenizer::for_model("gpt-4o");
+        assert_eq!(tokenizer.count_tokens("Hello world"), 2);
+        assert_eq!(
+            tokenizer.count_message_tokens(&Message::user("Hello world")),
+            5
+        );
+        assert_eq!(
+            TiktokenTokenizer::for_model("claude-sonnet-4").billing(),
+            BillingProfile::anthropic()
+        );
+    }
+}
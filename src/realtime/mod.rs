@@ -0,0 +1,322 @@
+//! Realtime sessions.
+//!
+//! A `RealtimeSession` is a long-lived, bidirectional connection to a speech
+//! or text model: audio and text stream in both directions and the model can
+//! call tools mid-session. Attach a `ToolExecutor` and tool calls run
+//! through the same registry, timeouts and output policy as agent runs.
+//!
+//! `OpenAIRealtime` implements `RealtimeProvider` over WebSocket with the
+//! `realtime` feature.
+
+#[cfg(feature = "realtime")]
+mod openai;
+
+#[cfg(feature = "realtime")]
+pub use openai::OpenAIRealtime;
+
+use crate::provider::{Result, ToolSchema, Usage};
+use crate::tool::{ToolCall, ToolExecutor};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Audio encoding for input and output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioFormat {
+    /// 16-bit little-endian PCM, 24kHz mono
+    #[default]
+    Pcm16,
+    G711Ulaw,
+    G711Alaw,
+}
+
+/// Session settings sent when connecting
+#[derive(Debug, Clone)]
+pub struct RealtimeConfig {
+    pub instructions: Option<String>,
+    pub voice: Option<String>,
+    /// Respond with audio as well as text
+    pub audio_output: bool,
+    pub audio_format: AudioFormat,
+    /// Let the server detect when the user stops speaking; otherwise send
+    /// `CommitAudio` and `CreateResponse` yourself
+    pub turn_detection: bool,
+    /// Model that transcribes the user's speech, if wanted
+    pub input_transcription: Option<String>,
+    pub tools: Vec<ToolSchema>,
+    pub temperature: Option<f32>,
+}
+
+impl Default for RealtimeConfig {
+    fn default() -> Self {
+        Self {
+            instructions: None,
+            voice: None,
+            audio_output: true,
+            audio_format: AudioFormat::default(),
+            turn_detection: true,
+            input_transcription: None,
+            tools: Vec::new(),
+            temperature: None,
+        }
+    }
+}
+
+impl RealtimeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    pub fn with_voice(mut self, voice: impl Into<String>) -> Self {
+        self.voice = Some(voice.into());
+        self
+    }
+
+    pub fn with_audio_format(mut self, format: AudioFormat) -> Self {
+        self.audio_format = format;
+        self
+    }
+
+    /// Respond with text only
+    pub fn text_only(mut self) -> Self {
+        self.audio_output = false;
+        self
+    }
+
+    pub fn with_turn_detection(mut self, enabled: bool) -> Self {
+        self.turn_detection = enabled;
+        self
+    }
+
+    pub fn with_input_transcription(mut self, model: impl Into<String>) -> Self {
+        self.input_transcription = Some(model.into());
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Vec<ToolSchema>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+/// Sent to the model
+#[derive(Debug, Clone, PartialEq)]
+pub enum RealtimeInput {
+    /// Add a user text message to the conversation
+    Text(String),
+    /// Append a chunk of the user's speech to the input buffer
+    Audio(Vec<u8>),
+    /// End the user's turn when turn detection is off
+    CommitAudio,
+    /// Ask the model to respond to the conversation so far
+    CreateResponse,
+    /// Interrupt the response in progress, e.g. when the user barges in
+    Cancel,
+    ToolResult {
+        call_id: String,
+        output: String,
+    },
+}
+
+/// Received from the model
+#[derive(Debug, Clone, PartialEq)]
+pub enum RealtimeEvent {
+    AudioDelta(Vec<u8>),
+    TextDelta(String),
+    /// Transcript of the model's spoken response
+    TranscriptDelta(String),
+    /// Transcript of what the user said
+    InputTranscript(String),
+    SpeechStarted,
+    SpeechStopped,
+    /// The model wants a tool run; answer with `RealtimeInput::ToolResult`
+    /// unless the session has tools attached
+    ToolCall(ToolCall),
+    ResponseDone {
+        usage: Option<Usage>,
+    },
+    /// The server rejected something; the session stays open
+    Error(String),
+}
+
+/// Provider of realtime sessions
+pub trait RealtimeProvider: Send + Sync {
+    fn connect(
+        &self,
+        config: RealtimeConfig,
+    ) -> Pin<Box<dyn Future<Output = Result<RealtimeSession>> + Send + '_>>;
+}
+
+/// Cloneable handle for sending to a session from another task, e.g. a
+/// microphone loop
+#[derive(Clone)]
+pub struct RealtimeSender {
+    outgoing: mpsc::UnboundedSender<RealtimeInput>,
+}
+
+impl RealtimeSender {
+    /// Fails once the connection has closed
+    pub fn send(&self, input: RealtimeInput) -> Result<()> {
+        self.outgoing
+            .send(input)
+            .map_err(|_| crate::provider::ProviderError::Other("Realtime session closed".into()))
+    }
+
+    /// Add a user message and ask for a response
+    pub fn send_text(&self, text: impl Into<String>) -> Result<()> {
+        self.send(RealtimeInput::Text(text.into()))?;
+        self.send(RealtimeInput::CreateResponse)
+    }
+
+    pub fn send_audio(&self, chunk: Vec<u8>) -> Result<()> {
+        self.send(RealtimeInput::Audio(chunk))
+    }
+}
+
+/// Open realtime connection
+///
+/// The connection closes once the session and its senders are dropped.
+pub struct RealtimeSession {
+    sender: RealtimeSender,
+    events: mpsc::UnboundedReceiver<Result<RealtimeEvent>>,
+    tools: Option<Arc<ToolExecutor>>,
+}
+
+impl RealtimeSession {
+    /// Session over a connection's channels, for `RealtimeProvider`
+    /// implementations; the connection ends when `outgoing` closes
+    pub fn new(
+        outgoing: mpsc::UnboundedSender<RealtimeInput>,
+        events: mpsc::UnboundedReceiver<Result<RealtimeEvent>>,
+    ) -> Self {
+        Self {
+            sender: RealtimeSender { outgoing },
+            events,
+            tools: None,
+        }
+    }
+
+    /// Run the model's tool calls with `executor` and send back the results
+    ///
+    /// `ToolCall` events are still returned, for display.
+    pub fn with_tools(mut self, executor: ToolExecutor) -> Self {
+        self.tools = Some(Arc::new(executor));
+        self
+    }
+
+    pub fn sender(&self) -> RealtimeSender {
+        self.sender.clone()
+    }
+
+    pub fn send(&self, input: RealtimeInput) -> Result<()> {
+        self.sender.send(input)
+    }
+
+    pub fn send_text(&self, text: impl Into<String>) -> Result<()> {
+        self.sender.send_text(text)
+    }
+
+    pub fn send_audio(&self, chunk: Vec<u8>) -> Result<()> {
+        self.sender.send_audio(chunk)
+    }
+
+    /// Next event from the model; `None` once the connection has closed
+    pub async fn next_event(&mut self) -> Option<Result<RealtimeEvent>> {
+        let event = self.events.recv().await?;
+        if let (Ok(RealtimeEvent::ToolCall(call)), Some(tools)) = (&event, &self.tools) {
+            // Keep delivering events while the tool runs
+            let tools = tools.clone();
+            let sender = self.sender.clone();
+            let call = call.clone();
+            tokio::spawn(async move {
+                let result = tools.execute_single(&call).await;
+                let output = if result.success {
+                    result.content
+                } else {
+                    result.error.unwrap_or(result.content)
+                };
+                let _ = sender.send(RealtimeInput::ToolResult {
+                    call_id: call.id,
+                    output,
+                });
+                let _ = sender.send(RealtimeInput::CreateResponse);
+            });
+        }
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{Tool, ToolRegistry, ToolResult};
+    use async_trait::async_trait;
+    use serde_json::Value;
+
+    struct Clock;
+
+    #[async_trait]
+    impl Tool for Clock {
+        fn name(&self) -> &str {
+            "clock"
+        }
+
+        fn description(&self) -> &str {
+            "Current time"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolResult::success("12:00")
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_tool_calls_mid_session() {
+        let (outgoing, mut sent) = mpsc::unbounded_channel();
+        let (events, incoming) = mpsc::unbounded_channel();
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(Clock)).await;
+        let mut session =
+            RealtimeSession::new(outgoing, incoming).with_tools(ToolExecutor::new(registry));
+
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "clock".to_string(),
+            parameters: serde_json::json!({}),
+        };
+        events
+            .send(Ok(RealtimeEvent::ToolCall(call.clone())))
+            .unwrap();
+        assert_eq!(
+            session.next_event().await.unwrap().unwrap(),
+            RealtimeEvent::ToolCall(call)
+        );
+
+        assert_eq!(
+            sent.recv().await.unwrap(),
+            RealtimeInput::ToolResult {
+                call_id: "call_1".to_string(),
+                output: "12:00".to_string()
+            }
+        );
+        assert_eq!(sent.recv().await.unwrap(), RealtimeInput::CreateResponse);
+
+        drop(events);
+        assert!(session.next_event().await.is_none());
+    }
+}
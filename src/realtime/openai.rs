@@ -0,0 +1,323 @@
+use super::{
+    AudioFormat, RealtimeConfig, RealtimeEvent, RealtimeInput, RealtimeProvider, RealtimeSession,
+};
+use crate::provider::{ProviderError, Result, Usage};
+use crate::tool::ToolCall;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// OpenAI Realtime API over WebSocket
+pub struct OpenAIRealtime {
+    api_key: String,
+    model: String,
+    base_url: String,
+}
+
+impl OpenAIRealtime {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: "gpt-4o-realtime-preview".to_string(),
+            base_url: "wss://api.openai.com/v1/realtime".to_string(),
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// WebSocket endpoint, e.g. for Azure or a proxy
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+
+    async fn open(&self, config: RealtimeConfig) -> Result<RealtimeSession> {
+        let url = format!("{}?model={}", self.base_url, self.model);
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        let headers = request.headers_mut();
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+                .map_err(|e| ProviderError::AuthenticationFailed(e.to_string()))?,
+        );
+        headers.insert("OpenAI-Beta", HeaderValue::from_static("realtime=v1"));
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| match e {
+                tokio_tungstenite::tungstenite::Error::Http(response)
+                    if response.status() == 401 =>
+                {
+                    ProviderError::AuthenticationFailed("Invalid API key".to_string())
+                }
+                e => ProviderError::RequestFailed(e.to_string()),
+            })?;
+        let (mut sink, mut stream) = socket.split();
+        sink.send(WsMessage::Text(session_update(&config).to_string()))
+            .await
+            .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+
+        let (outgoing, mut inputs) = mpsc::unbounded_channel();
+        let (events, incoming) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(input) = inputs.recv().await {
+                let event = client_event(input).to_string();
+                if sink.send(WsMessage::Text(event)).await.is_err() {
+                    return;
+                }
+            }
+            let _ = sink.close().await;
+        });
+        tokio::spawn(async move {
+            while let Some(message) = stream.next().await {
+                let text = match message {
+                    Ok(WsMessage::Text(text)) => text,
+                    Ok(WsMessage::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        let _ = events.send(Err(ProviderError::RequestFailed(e.to_string())));
+                        break;
+                    }
+                };
+                let Ok(event) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+                if let Some(event) = server_event(&event) {
+                    if events.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(RealtimeSession::new(outgoing, incoming))
+    }
+}
+
+impl RealtimeProvider for OpenAIRealtime {
+    fn connect(
+        &self,
+        config: RealtimeConfig,
+    ) -> Pin<Box<dyn Future<Output = Result<RealtimeSession>> + Send + '_>> {
+        Box::pin(self.open(config))
+    }
+}
+
+fn session_update(config: &RealtimeConfig) -> Value {
+    let format = match config.audio_format {
+        AudioFormat::Pcm16 => "pcm16",
+        AudioFormat::G711Ulaw => "g711_ulaw",
+        AudioFormat::G711Alaw => "g711_alaw",
+    };
+    let modalities = if config.audio_output {
+        serde_json::json!(["text", "audio"])
+    } else {
+        serde_json::json!(["text"])
+    };
+    let tools: Vec<Value> = config
+        .tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": tool.parameters,
+            })
+        })
+        .collect();
+
+    let mut session = serde_json::json!({
+        "modalities": modalities,
+        "input_audio_format": format,
+        "output_audio_format": format,
+        "turn_detection": if config.turn_detection {
+            serde_json::json!({"type": "server_vad"})
+        } else {
+            Value::Null
+        },
+        "tools": tools,
+        "tool_choice": "auto",
+    });
+    if let Some(instructions) = &config.instructions {
+        session["instructions"] = serde_json::json!(instructions);
+    }
+    if let Some(voice) = &config.voice {
+        session["voice"] = serde_json::json!(voice);
+    }
+    if let Some(model) = &config.input_transcription {
+        session["input_audio_transcription"] = serde_json::json!({ "model": model });
+    }
+    if let Some(temperature) = config.temperature {
+        session["temperature"] = serde_json::json!(temperature);
+    }
+    serde_json::json!({"type": "session.update", "session": session})
+}
+
+/// Client event for an input
+fn client_event(input: RealtimeInput) -> Value {
+    match input {
+        RealtimeInput::Text(text) => serde_json::json!({
+            "type": "conversation.item.create",
+            "item": {
+                "type": "message",
+                "role": "user",
+                "content": [{"type": "input_text", "text": text}],
+            },
+        }),
+        RealtimeInput::Audio(chunk) => serde_json::json!({
+            "type": "input_audio_buffer.append",
+            "audio": base64::engine::general_purpose::STANDARD.encode(chunk),
+        }),
+        RealtimeInput::CommitAudio => serde_json::json!({"type": "input_audio_buffer.commit"}),
+        RealtimeInput::CreateResponse => serde_json::json!({"type": "response.create"}),
+        RealtimeInput::Cancel => serde_json::json!({"type": "response.cancel"}),
+        RealtimeInput::ToolResult { call_id, output } => serde_json::json!({
+            "type": "conversation.item.create",
+            "item": {"type": "function_call_output", "call_id": call_id, "output": output},
+        }),
+    }
+}
+
+/// Event for a server message, if it is one callers care about
+fn server_event(event: &Value) -> Option<Result<RealtimeEvent>> {
+    let text = |field: &str| event[field].as_str().unwrap_or_default().to_string();
+    let event = match event["type"].as_str()? {
+        "response.audio.delta" => {
+            match base64::engine::general_purpose::STANDARD.decode(text("delta")) {
+                Ok(audio) => RealtimeEvent::AudioDelta(audio),
+                Err(e) => return Some(Err(ProviderError::ParseError(e.to_string()))),
+            }
+        }
+        "response.text.delta" => RealtimeEvent::TextDelta(text("delta")),
+        "response.audio_transcript.delta" => RealtimeEvent::TranscriptDelta(text("delta")),
+        "conversation.item.input_audio_transcription.completed" => {
+            RealtimeEvent::InputTranscript(text("transcript"))
+        }
+        "input_audio_buffer.speech_started" => RealtimeEvent::SpeechStarted,
+        "input_audio_buffer.speech_stopped" => RealtimeEvent::SpeechStopped,
+        "response.function_call_arguments.done" => {
+            let arguments = text("arguments");
+            RealtimeEvent::ToolCall(ToolCall {
+                id: text("call_id"),
+                name: text("name"),
+                parameters: serde_json::from_str(&arguments)
+                    .unwrap_or_else(|_| serde_json::json!({})),
+            })
+        }
+        "response.done" => {
+            let usage = &event["response"]["usage"];
+            RealtimeEvent::ResponseDone {
+                usage: usage.is_object().then(|| Usage {
+                    prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0) as u32,
+                    completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0) as u32,
+                    total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
+                }),
+            }
+        }
+        "error" => RealtimeEvent::Error(
+            event["error"]["message"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string(),
+        ),
+        _ => return None,
+    };
+    Some(Ok(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ToolSchema;
+
+    #[test]
+    fn configures_session() {
+        let config = RealtimeConfig::new()
+            .with_instructions("Be brief")
+            .with_voice("alloy")
+            .with_turn_detection(false)
+            .with_input_transcription("whisper-1")
+            .with_tools(vec![ToolSchema {
+                name: "clock".to_string(),
+                description: "Current time".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+                native: None,
+            }]);
+        let update = session_update(&config);
+
+        assert_eq!(update["type"], "session.update");
+        let session = &update["session"];
+        assert_eq!(session["modalities"], serde_json::json!(["text", "audio"]));
+        assert_eq!(session["voice"], "alloy");
+        assert!(session["turn_detection"].is_null());
+        assert_eq!(session["input_audio_transcription"]["model"], "whisper-1");
+        assert_eq!(session["tools"][0]["type"], "function");
+        assert_eq!(session["tools"][0]["name"], "clock");
+    }
+
+    #[test]
+    fn maps_inputs_and_server_events() {
+        let event = client_event(RealtimeInput::Audio(vec![1, 2, 3]));
+        assert_eq!(event["type"], "input_audio_buffer.append");
+        assert_eq!(event["audio"], "AQID");
+
+        let event = client_event(RealtimeInput::ToolResult {
+            call_id: "call_1".to_string(),
+            output: "12:00".to_string(),
+        });
+        assert_eq!(event["item"]["type"], "function_call_output");
+        assert_eq!(event["item"]["call_id"], "call_1");
+
+        let event = server_event(&serde_json::json!({
+            "type": "response.audio.delta", "delta": "AQID"
+        }));
+        assert_eq!(
+            event.unwrap().unwrap(),
+            RealtimeEvent::AudioDelta(vec![1, 2, 3])
+        );
+
+        let event = server_event(&serde_json::json!({
+            "type": "response.function_call_arguments.done",
+            "call_id": "call_1",
+            "name": "weather",
+            "arguments": "{\"city\":\"Paris\"}"
+        }));
+        assert_eq!(
+            event.unwrap().unwrap(),
+            RealtimeEvent::ToolCall(ToolCall {
+                id: "call_1".to_string(),
+                name: "weather".to_string(),
+                parameters: serde_json::json!({"city": "Paris"}),
+            })
+        );
+
+        let event = server_event(&serde_json::json!({
+            "type": "response.done",
+            "response": {"usage": {"input_tokens": 10, "output_tokens": 5, "total_tokens": 15}}
+        }));
+        assert_eq!(
+            event.unwrap().unwrap(),
+            RealtimeEvent::ResponseDone {
+                usage: Some(Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 5,
+                    total_tokens: 15,
+                })
+            }
+        );
+
+        assert!(server_event(&serde_json::json!({"type": "rate_limits.updated"})).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+/// Splits text into overlapping chunks, preferring to break between
+/// paragraphs, then sentences, then words
+#[derive(Debug, Clone)]
+pub struct TextChunker {
+    max_chars: usize,
+    overlap: usize,
+}
+
+impl Default for TextChunker {
+    /// Chunks of up to 1000 characters overlapping by 200
+    fn default() -> Self {
+        Self::new(1000, 200)
+    }
+}
+
+impl TextChunker {
+    /// Chunks of up to `max_chars` characters, each repeating about the last
+    /// `overlap` characters of the one before
+    pub fn new(max_chars: usize, overlap: usize) -> Self {
+        let max_chars = max_chars.max(1);
+        Self {
+            max_chars,
+            overlap: overlap.min(max_chars / 2),
+        }
+    }
+
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = (start + self.max_chars).min(chars.len());
+            if end < chars.len() {
+                end = self.break_before(&chars, start, end);
+            }
+            let chunk: String = chars[start..end].iter().collect();
+            let chunk = chunk.trim();
+            if !chunk.is_empty() {
+                chunks.push(chunk.to_string());
+            }
+            if end == chars.len() {
+                break;
+            }
+            start = self.next_start(&chars, start, end);
+        }
+        chunks
+    }
+
+    /// Best place to end a chunk in `start..end`, no earlier than halfway
+    fn break_before(&self, chars: &[char], start: usize, end: usize) -> usize {
+        let earliest = (start + (end - start) / 2).max(start + 1);
+        let window = earliest..end;
+        let paragraph = window
+            .clone()
+            .rev()
+            .find(|&i| chars[i] == '\n' && chars[i - 1] == '\n');
+        let sentence = || {
+            window.clone().rev().find(|&i| {
+                matches!(chars[i - 1], '.' | '!' | '?' | '\n') && chars[i].is_whitespace()
+            })
+        };
+        let word = || window.clone().rev().find(|&i| chars[i].is_whitespace());
+        paragraph.or_else(sentence).or_else(word).unwrap_or(end)
+    }
+
+    /// Start of the next chunk: `overlap` characters back from `end`, moved
+    /// forward to the start of a word
+    fn next_start(&self, chars: &[char], start: usize, end: usize) -> usize {
+        if self.overlap == 0 {
+            return end;
+        }
+        let mut next = end.saturating_sub(self.overlap).max(start + 1);
+        while next < end && !chars[next - 1].is_whitespace() {
+            next += 1;
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaks_at_natural_boundaries_with_overlap() {
+        let text = "First paragraph here.\n\nSecond one follows. It has two sentences.";
+        let chunks = TextChunker::new(30, 0).split(text);
+        assert_eq!(chunks[0], "First paragraph here.");
+        assert!(chunks.iter().all(|c| c.chars().count() <= 30));
+        assert_eq!(chunks.join(" ").split_whitespace().count(), 10);
+
+        let chunks = TextChunker::new(24, 10).split("one two three four five six seven eight nine");
+        assert!(chunks.len() > 1);
+        for pair in chunks.windows(2) {
+            // Each chunk starts with words the previous one ended with
+            let first_word = pair[1].split_whitespace().next().unwrap();
+            assert!(pair[0].contains(first_word), "{:?}", pair);
+        }
+
+        assert!(TextChunker::default().split("  \n ").is_empty());
+        assert_eq!(
+            TextChunker::new(4, 0).split("héllo wörld"),
+            ["héll", "o wö", "rld"]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use super::{Chunk, Result, RetrievalError, ScoredChunk, VectorIndex};
+use async_trait::async_trait;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::RwLock;
+
+/// In-memory approximate nearest-neighbour index (HNSW) by cosine similarity
+///
+/// Replaced and removed chunks stay in the graph so searches can still route
+/// through them, but are never returned.
+pub struct HnswIndex {
+    graph: RwLock<Graph>,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+}
+
+struct Node {
+    chunk: Chunk,
+    /// Normalized, so cosine similarity is a dot product
+    vector: Vec<f32>,
+    /// Neighbours on each layer the node is on, bottom first
+    links: Vec<Vec<usize>>,
+    removed: bool,
+}
+
+struct Graph {
+    nodes: Vec<Node>,
+    ids: HashMap<String, usize>,
+    entry: Option<usize>,
+    top: usize,
+    dimension: Option<usize>,
+    removed: usize,
+    rng: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HnswIndex {
+    pub fn new() -> Self {
+        Self::with_params(16, 200, 64)
+    }
+
+    /// `m` links per node (twice as many on the bottom layer), and the
+    /// candidate list sizes used while inserting and searching
+    ///
+    /// Larger values find better neighbours at the cost of speed and memory.
+    pub fn with_params(m: usize, ef_construction: usize, ef_search: usize) -> Self {
+        Self {
+            graph: RwLock::new(Graph {
+                nodes: Vec::new(),
+                ids: HashMap::new(),
+                entry: None,
+                top: 0,
+                dimension: None,
+                removed: 0,
+                rng: 0x9e37_79b9_7f4a_7c15,
+            }),
+            m: m.max(2),
+            ef_construction: ef_construction.max(1),
+            ef_search: ef_search.max(1),
+        }
+    }
+
+    /// Number of chunks that can be returned
+    pub fn len(&self) -> usize {
+        self.graph.read().unwrap().ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Graph {
+    fn check_dimension(&self, len: usize) -> Result<()> {
+        match self.dimension {
+            Some(dimension) if dimension != len => Err(RetrievalError::Index(format!(
+                "Expected vectors of dimension {}, got {}",
+                dimension, len
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Layer for a new node, exponentially less likely the higher it is
+    fn random_level(&mut self, m: usize) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let unit = (self.rng >> 11) as f64 / (1u64 << 53) as f64;
+        (-(1.0 - unit).ln() / (m as f64).ln()).floor() as usize
+    }
+
+    fn remove(&mut self, node: usize) {
+        if !self.nodes[node].removed {
+            self.nodes[node].removed = true;
+            self.removed += 1;
+        }
+    }
+
+    /// Closest node to `query` on `layer` reachable from `entry`
+    fn greedy(&self, query: &[f32], mut entry: usize, layer: usize) -> usize {
+        let mut best = distance(query, &self.nodes[entry].vector);
+        loop {
+            let mut moved = false;
+            for &node in &self.nodes[entry].links[layer] {
+                let d = distance(query, &self.nodes[node].vector);
+                if d < best {
+                    best = d;
+                    entry = node;
+                    moved = true;
+                }
+            }
+            if !moved {
+                return entry;
+            }
+        }
+    }
+
+    /// Up to `ef` nodes on `layer` closest to `query`, closest first
+    fn search_layer(&self, query: &[f32], entry: usize, ef: usize, layer: usize) -> Vec<Candidate> {
+        let start = Candidate {
+            distance: distance(query, &self.nodes[entry].vector),
+            node: entry,
+        };
+        let mut visited = HashSet::from([entry]);
+        let mut candidates = BinaryHeap::from([Reverse(start)]);
+        let mut found = BinaryHeap::from([start]);
+        while let Some(Reverse(current)) = candidates.pop() {
+            if found.len() >= ef && current.distance > found.peek().unwrap().distance {
+                break;
+            }
+            for &node in &self.nodes[current.node].links[layer] {
+                if !visited.insert(node) {
+                    continue;
+                }
+                let candidate = Candidate {
+                    distance: distance(query, &self.nodes[node].vector),
+                    node,
+                };
+                if found.len() < ef || candidate.distance < found.peek().unwrap().distance {
+                    candidates.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    fn insert(&mut self, chunk: Chunk, vector: Vec<f32>, m: usize, ef_construction: usize) {
+        let id = self.nodes.len();
+        let level = self.random_level(m);
+        self.nodes.push(Node {
+            chunk,
+            vector,
+            links: vec![Vec::new(); level + 1],
+            removed: false,
+        });
+        let Some(mut entry) = self.entry else {
+            self.entry = Some(id);
+            self.top = level;
+            return;
+        };
+
+        let query = self.nodes[id].vector.clone();
+        for layer in (level + 1..=self.top).rev() {
+            entry = self.greedy(&query, entry, layer);
+        }
+        for layer in (0..=level.min(self.top)).rev() {
+            let found = self.search_layer(&query, entry, ef_construction, layer);
+            entry = found[0].node;
+            let max_links = if layer == 0 { 2 * m } else { m };
+            let neighbours: Vec<usize> = found.iter().take(max_links).map(|c| c.node).collect();
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].links[layer].push(id);
+                if self.nodes[neighbour].links[layer].len() > max_links {
+                    self.prune(neighbour, layer, max_links);
+                }
+            }
+            self.nodes[id].links[layer] = neighbours;
+        }
+        if level > self.top {
+            self.top = level;
+            self.entry = Some(id);
+        }
+    }
+
+    /// Keep only the `max_links` closest neighbours of `node` on `layer`
+    fn prune(&mut self, node: usize, layer: usize, max_links: usize) {
+        let vector = &self.nodes[node].vector;
+        let mut links: Vec<Candidate> = self.nodes[node].links[layer]
+            .iter()
+            .map(|&n| Candidate {
+                distance: distance(vector, &self.nodes[n].vector),
+                node: n,
+            })
+            .collect();
+        links.sort_unstable();
+        links.truncate(max_links);
+        self.nodes[node].links[layer] = links.into_iter().map(|c| c.node).collect();
+    }
+}
+
+#[async_trait]
+impl VectorIndex for HnswIndex {
+    async fn upsert(&self, entries: Vec<(Chunk, Vec<f32>)>) -> Result<()> {
+        let mut graph = self.graph.write().unwrap();
+        for (_, vector) in &entries {
+            graph.check_dimension(vector.len())?;
+            graph.dimension = Some(vector.len());
+        }
+        for (chunk, vector) in entries {
+            if let Some(old) = graph.ids.remove(&chunk.id) {
+                graph.remove(old);
+            }
+            let node = graph.nodes.len();
+            graph.ids.insert(chunk.id.clone(), node);
+            graph.insert(chunk, normalize(vector), self.m, self.ef_construction);
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &[f32], limit: usize) -> Result<Vec<ScoredChunk>> {
+        let graph = self.graph.read().unwrap();
+        let Some(mut entry) = graph.entry else {
+            return Ok(Vec::new());
+        };
+        graph.check_dimension(query.len())?;
+        let query = normalize(query.to_vec());
+        for layer in (1..=graph.top).rev() {
+            entry = graph.greedy(&query, entry, layer);
+        }
+        // Removed nodes take up places in the candidate list
+        let ef = self.ef_search.max(limit) + graph.removed;
+        Ok(graph
+            .search_layer(&query, entry, ef, 0)
+            .into_iter()
+            .filter(|c| !graph.nodes[c.node].removed)
+            .take(limit)
+            .map(|c| ScoredChunk {
+                chunk: graph.nodes[c.node].chunk.clone(),
+                score: 1.0 - c.distance,
+            })
+            .collect())
+    }
+
+    async fn remove_document(&self, document: &str) -> Result<()> {
+        let mut graph = self.graph.write().unwrap();
+        let removed: Vec<usize> = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.removed && node.chunk.document == document)
+            .map(|(i, _)| i)
+            .collect();
+        for node in removed {
+            let id = graph.nodes[node].chunk.id.clone();
+            graph.ids.remove(&id);
+            graph.remove(node);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: usize) -> Chunk {
+        Chunk {
+            id: id.to_string(),
+            document: format!("doc-{}", id % 10),
+            text: String::new(),
+            metadata: serde_json::Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_nearly_all_true_neighbours() {
+        let mut seed = 42u64;
+        let mut random = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        let vectors: Vec<Vec<f32>> = (0..500)
+            .map(|_| (0..32).map(|_| random()).collect())
+            .collect();
+        let index = HnswIndex::with_params(8, 64, 32);
+        index
+            .upsert(
+                vectors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (chunk(i), v.clone()))
+                    .collect(),
+            )
+            .await
+            .unwrap();
+
+        let mut hits = 0;
+        for query in vectors.iter().take(50) {
+            let q = normalize(query.clone());
+            let mut exact: Vec<(f32, usize)> = vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (distance(&q, &normalize(v.clone())), i))
+                .collect();
+            exact.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let found = index.search(query, 10).await.unwrap();
+            hits += exact[..10]
+                .iter()
+                .filter(|(_, i)| found.iter().any(|hit| hit.chunk.id == i.to_string()))
+                .count();
+        }
+        assert!(hits >= 450, "recall {}/500", hits);
+
+        // Removed documents are skipped, and the rest still fill the limit
+        index.remove_document("doc-0").await.unwrap();
+        let found = index.search(&vectors[0], 10).await.unwrap();
+        assert_eq!(found.len(), 10);
+        assert!(found.iter().all(|hit| hit.chunk.document != "doc-0"));
+        assert_eq!(index.len(), 450);
+
+        assert!(matches!(
+            index.search(&[1.0, 0.0], 1).await,
+            Err(RetrievalError::Index(_))
+        ));
+    }
+}
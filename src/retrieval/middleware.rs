@@ -0,0 +1,95 @@
+use super::{format_chunks, Retriever};
+use crate::provider::{Message, Middleware, ProviderError, RequestContext, Role};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Adds passages relevant to the latest user message to each request
+///
+/// The passages go in a system message just before that user message.
+pub struct RetrievalMiddleware {
+    retriever: Arc<Retriever>,
+    limit: usize,
+    min_score: f32,
+}
+
+impl RetrievalMiddleware {
+    pub fn new(retriever: Arc<Retriever>) -> Self {
+        Self {
+            retriever,
+            limit: 3,
+            min_score: 0.0,
+        }
+    }
+
+    /// Most passages added per request; 3 by default
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Leave out passages less similar than `min_score`
+    pub fn min_score(mut self, min_score: f32) -> Self {
+        self.min_score = min_score;
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for RetrievalMiddleware {
+    async fn before_request(&self, ctx: &mut RequestContext) -> crate::provider::Result<()> {
+        let Some(position) = ctx.messages.iter().rposition(|m| m.role == Role::User) else {
+            return Ok(());
+        };
+        let query = ctx.messages[position].content_as_text();
+        if query.trim().is_empty() || self.limit == 0 {
+            return Ok(());
+        }
+        let mut hits = self
+            .retriever
+            .retrieve(&query, self.limit)
+            .await
+            .map_err(|e| ProviderError::Other(e.to_string()))?;
+        hits.retain(|hit| hit.score >= self.min_score);
+        if !hits.is_empty() {
+            let context = format!(
+                "Passages that may help answer the next message:\n\n{}",
+                format_chunks(&hits)
+            );
+            ctx.messages.insert(position, Message::system(context));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retrieval::tests::retriever;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn injects_passages_before_the_last_user_message() {
+        let middleware = RetrievalMiddleware::new(Arc::new(retriever().await))
+            .limit(2)
+            .min_score(0.5);
+        let mut ctx = RequestContext {
+            messages: vec![
+                Message::system("Be brief"),
+                Message::user("hi"),
+                Message::assistant("hello"),
+                Message::user("What do you know about rust?"),
+            ],
+            options: None,
+            metadata: HashMap::new(),
+        };
+        middleware.before_request(&mut ctx).await.unwrap();
+
+        assert_eq!(ctx.messages.len(), 5);
+        assert_eq!(ctx.messages[3].role, Role::System);
+        let context = ctx.messages[3].content_as_text();
+        assert!(context.contains("[1] (langs) Rust is a systems language."));
+        // The nature passage scores below the threshold
+        assert!(!context.contains("ocean"));
+        assert_eq!(ctx.messages[4].role, Role::User);
+    }
+}
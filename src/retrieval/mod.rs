@@ -0,0 +1,299 @@
+//! Retrieval-augmented generation.
+//!
+//! A `Retriever` splits documents with a `TextChunker`, embeds the chunks
+//! with an `EmbeddingProvider` and stores them in a `VectorIndex`. Queries
+//! are embedded the same way and answered with the nearest chunks. Agents
+//! use it through `RetrievalTool`, which the model calls when it wants to
+//! look something up, or `RetrievalMiddleware`, which adds matching chunks
+//! to every request.
+//!
+//! `HnswIndex` keeps vectors in memory. `QdrantIndex` (feature = "qdrant")
+//! and `PgVectorIndex` (feature = "pgvector") store them in a database.
+
+mod chunk;
+mod hnsw;
+mod middleware;
+#[cfg(feature = "pgvector")]
+mod pgvector;
+#[cfg(feature = "qdrant")]
+mod qdrant;
+mod tool;
+
+pub use chunk::TextChunker;
+pub use hnsw::HnswIndex;
+pub use middleware::RetrievalMiddleware;
+#[cfg(feature = "pgvector")]
+pub use pgvector::PgVectorIndex;
+#[cfg(feature = "qdrant")]
+pub use qdrant::QdrantIndex;
+pub use tool::RetrievalTool;
+
+use crate::provider::{EmbeddingProvider, EmbeddingRequest, ProviderError};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum RetrievalError {
+    /// The embedding provider failed
+    Embedding(ProviderError),
+    /// The vector index failed or rejected the data
+    Index(String),
+}
+
+impl std::fmt::Display for RetrievalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Embedding(err) => write!(f, "Embedding failed: {}", err),
+            Self::Index(msg) => write!(f, "Vector index error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RetrievalError {}
+
+impl From<ProviderError> for RetrievalError {
+    fn from(err: ProviderError) -> Self {
+        Self::Embedding(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RetrievalError>;
+
+/// A piece of a document, as stored in an index
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Chunk {
+    /// Unique in the index, e.g. `<document>#<n>`
+    pub id: String,
+    /// Id of the document the chunk was cut from
+    pub document: String,
+    pub text: String,
+    /// Application data carried with the chunk, e.g. a source URL
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+/// A search hit
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredChunk {
+    pub chunk: Chunk,
+    /// Cosine similarity to the query, higher is closer
+    pub score: f32,
+}
+
+/// Nearest-neighbour search over chunk embeddings
+#[async_trait]
+pub trait VectorIndex: Send + Sync {
+    /// Insert chunks with their embeddings, replacing chunks with the same id
+    async fn upsert(&self, entries: Vec<(Chunk, Vec<f32>)>) -> Result<()>;
+
+    /// Up to `limit` chunks closest to `query`, best first
+    async fn search(&self, query: &[f32], limit: usize) -> Result<Vec<ScoredChunk>>;
+
+    /// Remove every chunk of `document`
+    async fn remove_document(&self, document: &str) -> Result<()>;
+}
+
+/// Chunks, embeds and indexes documents, and answers queries from the index
+pub struct Retriever {
+    embedder: Arc<dyn EmbeddingProvider>,
+    index: Arc<dyn VectorIndex>,
+    chunker: TextChunker,
+    model: Option<String>,
+    batch_size: usize,
+}
+
+impl Retriever {
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>, index: Arc<dyn VectorIndex>) -> Self {
+        Self {
+            embedder,
+            index,
+            chunker: TextChunker::default(),
+            model: None,
+            batch_size: 64,
+        }
+    }
+
+    pub fn with_chunker(mut self, chunker: TextChunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Embedding model to request instead of the provider's default
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Most texts sent in one embedding request; 64 by default
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn index(&self) -> &Arc<dyn VectorIndex> {
+        &self.index
+    }
+
+    /// Index `text` as document `id`, replacing any earlier version
+    ///
+    /// Returns the number of chunks stored.
+    pub async fn add_document(&self, id: &str, text: &str, metadata: Value) -> Result<usize> {
+        let texts = self.chunker.split(text);
+        let embeddings = self.embed(texts.clone()).await?;
+        let entries: Vec<(Chunk, Vec<f32>)> = texts
+            .into_iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(n, (text, embedding))| {
+                let chunk = Chunk {
+                    id: format!("{}#{}", id, n),
+                    document: id.to_string(),
+                    text,
+                    metadata: metadata.clone(),
+                };
+                (chunk, embedding)
+            })
+            .collect();
+        let count = entries.len();
+        self.index.remove_document(id).await?;
+        self.index.upsert(entries).await?;
+        Ok(count)
+    }
+
+    /// Up to `limit` chunks most similar to `query`
+    pub async fn retrieve(&self, query: &str, limit: usize) -> Result<Vec<ScoredChunk>> {
+        let embedding = self
+            .embed(vec![query.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| RetrievalError::Index("No embedding returned for the query".into()))?;
+        self.index.search(&embedding, limit).await
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            let mut request = EmbeddingRequest::new_batch(batch.to_vec());
+            request.model = self.model.clone();
+            let response = self.embedder.create_embeddings(request).await?;
+            if response.embeddings.len() != batch.len() {
+                return Err(RetrievalError::Embedding(ProviderError::ParseError(
+                    format!(
+                        "Expected {} embeddings, got {}",
+                        batch.len(),
+                        response.embeddings.len()
+                    ),
+                )));
+            }
+            embeddings.extend(response.embeddings);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Retrieved chunks as numbered passages for a prompt or tool result
+pub(crate) fn format_chunks(chunks: &[ScoredChunk]) -> String {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| format!("[{}] ({}) {}", i + 1, hit.chunk.document, hit.chunk.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::provider::{EmbeddingResponse, Result as ProviderResult};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds text as counts of a few keywords, so related texts are close
+    #[derive(Default)]
+    pub(crate) struct KeywordEmbedder {
+        pub(crate) requests: AtomicUsize,
+    }
+
+    const KEYWORDS: [&str; 4] = ["rust", "python", "ocean", "mountain"];
+
+    impl EmbeddingProvider for KeywordEmbedder {
+        fn create_embeddings(
+            &self,
+            request: EmbeddingRequest,
+        ) -> Pin<Box<dyn Future<Output = ProviderResult<EmbeddingResponse>> + Send + '_>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let embeddings = request
+                .input
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    let mut vector: Vec<f32> = KEYWORDS
+                        .iter()
+                        .map(|k| text.matches(k).count() as f32)
+                        .collect();
+                    vector.push(0.1);
+                    vector
+                })
+                .collect();
+            Box::pin(async move {
+                Ok(EmbeddingResponse {
+                    embeddings,
+                    model: "keywords".to_string(),
+                    usage: None,
+                })
+            })
+        }
+    }
+
+    pub(crate) async fn retriever() -> Retriever {
+        let retriever = Retriever::new(
+            Arc::new(KeywordEmbedder::default()),
+            Arc::new(HnswIndex::new()),
+        );
+        retriever
+            .add_document("langs", "Rust is a systems language.", Value::Null)
+            .await
+            .unwrap();
+        retriever
+            .add_document(
+                "nature",
+                "The ocean is deep. The mountain is high.",
+                Value::Null,
+            )
+            .await
+            .unwrap();
+        retriever
+    }
+
+    #[tokio::test]
+    async fn retrieves_the_closest_chunks() {
+        let retriever = retriever().await;
+        let hits = retriever.retrieve("Tell me about rust", 1).await.unwrap();
+        assert_eq!(hits[0].chunk.document, "langs");
+        assert_eq!(hits[0].chunk.id, "langs#0");
+
+        // Re-adding a document replaces its chunks
+        retriever
+            .add_document("langs", "Python is a scripting language.", Value::Null)
+            .await
+            .unwrap();
+        let hits = retriever.retrieve("rust", 3).await.unwrap();
+        assert!(hits.iter().all(|hit| !hit.chunk.text.contains("Rust")));
+    }
+
+    #[tokio::test]
+    async fn embeds_in_batches() {
+        let embedder = Arc::new(KeywordEmbedder::default());
+        let retriever = Retriever::new(embedder.clone(), Arc::new(HnswIndex::new()))
+            .with_chunker(TextChunker::new(20, 0))
+            .with_batch_size(2);
+        let count = retriever
+            .add_document("doc", &"rust and python. ".repeat(6), Value::Null)
+            .await
+            .unwrap();
+        assert!(count > 2);
+        assert_eq!(embedder.requests.load(Ordering::SeqCst), count.div_ceil(2));
+    }
+}
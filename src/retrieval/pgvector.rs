@@ -0,0 +1,165 @@
+use super::{Chunk, Result, RetrievalError, ScoredChunk, VectorIndex};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+/// Chunks in a PostgreSQL table with the pgvector extension
+/// (feature = "pgvector")
+#[derive(Clone)]
+pub struct PgVectorIndex {
+    client: Arc<tokio_postgres::Client>,
+    table: String,
+    /// Why the connection task ended, reported by the next query
+    connection_error: Arc<Mutex<Option<String>>>,
+}
+
+fn index_error(error: impl std::fmt::Display) -> RetrievalError {
+    RetrievalError::Index(error.to_string())
+}
+
+/// pgvector's text form, e.g. `[0.1,0.2]`
+fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(f32::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+impl PgVectorIndex {
+    /// Connect with a libpq-style `config`, e.g.
+    /// `host=localhost user=postgres`, storing chunks in `table`
+    ///
+    /// The connection is driven by a background task; if it fails, later
+    /// queries return its error.
+    pub async fn connect(config: &str, table: impl Into<String>) -> Result<Self> {
+        let table = table.into();
+        let valid = !table.is_empty()
+            && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !table.starts_with(|c: char| c.is_ascii_digit());
+        if !valid {
+            return Err(RetrievalError::Index(format!(
+                "Table name '{}' must use letters, digits and '_'",
+                table
+            )));
+        }
+        let (client, connection) = tokio_postgres::connect(config, tokio_postgres::NoTls)
+            .await
+            .map_err(index_error)?;
+        let connection_error = Arc::new(Mutex::new(None));
+        let failed = connection_error.clone();
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                *failed.lock().unwrap() = Some(e.to_string());
+            }
+        });
+        Ok(Self {
+            client: Arc::new(client),
+            table,
+            connection_error,
+        })
+    }
+
+    /// `error` from a query, or the connection's failure if it caused it
+    fn query_error(&self, error: tokio_postgres::Error) -> RetrievalError {
+        match self.connection_error.lock().unwrap().as_deref() {
+            Some(cause) if error.is_closed() => {
+                RetrievalError::Index(format!("Connection closed: {}", cause))
+            }
+            _ => index_error(error),
+        }
+    }
+
+    /// Create the extension and table for vectors of `dimension` if missing
+    pub async fn ensure_table(&self, dimension: usize) -> Result<()> {
+        self.client
+            .batch_execute(&format!(
+                "CREATE EXTENSION IF NOT EXISTS vector;
+                 CREATE TABLE IF NOT EXISTS {table} (
+                     id TEXT PRIMARY KEY,
+                     document TEXT NOT NULL,
+                     text TEXT NOT NULL,
+                     metadata TEXT NOT NULL,
+                     embedding vector({dimension}) NOT NULL
+                 );
+                 CREATE INDEX IF NOT EXISTS {table}_document ON {table} (document);",
+                table = self.table,
+                dimension = dimension
+            ))
+            .await
+            .map_err(|e| self.query_error(e))
+    }
+}
+
+#[async_trait]
+impl VectorIndex for PgVectorIndex {
+    async fn upsert(&self, entries: Vec<(Chunk, Vec<f32>)>) -> Result<()> {
+        let statement = format!(
+            "INSERT INTO {} (id, document, text, metadata, embedding)
+             VALUES ($1, $2, $3, $4, $5::text::vector)
+             ON CONFLICT (id) DO UPDATE SET document = excluded.document,
+                 text = excluded.text, metadata = excluded.metadata,
+                 embedding = excluded.embedding",
+            self.table
+        );
+        for (chunk, vector) in entries {
+            let metadata = chunk.metadata.to_string();
+            self.client
+                .execute(
+                    &statement,
+                    &[
+                        &chunk.id,
+                        &chunk.document,
+                        &chunk.text,
+                        &metadata,
+                        &vector_literal(&vector),
+                    ],
+                )
+                .await
+                .map_err(|e| self.query_error(e))?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &[f32], limit: usize) -> Result<Vec<ScoredChunk>> {
+        let statement = format!(
+            "SELECT id, document, text, metadata, 1 - (embedding <=> $1::text::vector)
+             FROM {} ORDER BY embedding <=> $1::text::vector LIMIT $2",
+            self.table
+        );
+        let rows = self
+            .client
+            .query(&statement, &[&vector_literal(query), &(limit as i64)])
+            .await
+            .map_err(|e| self.query_error(e))?;
+        rows.iter()
+            .map(|row| {
+                let metadata: String = row.get(3);
+                Ok(ScoredChunk {
+                    chunk: Chunk {
+                        id: row.get(0),
+                        document: row.get(1),
+                        text: row.get(2),
+                        metadata: serde_json::from_str(&metadata).map_err(index_error)?,
+                    },
+                    score: row.get::<_, f64>(4) as f32,
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_document(&self, document: &str) -> Result<()> {
+        let statement = format!("DELETE FROM {} WHERE document = $1", self.table);
+        self.client
+            .execute(&statement, &[&document])
+            .await
+            .map(|_| ())
+            .map_err(|e| self.query_error(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_vectors_as_pgvector_text() {
+        assert_eq!(vector_literal(&[0.5, -1.0, 2.25]), "[0.5,-1,2.25]");
+    }
+}
@@ -0,0 +1,151 @@
+use super::{Chunk, Result, RetrievalError, ScoredChunk, VectorIndex};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Chunks in a Qdrant collection, through its REST API (feature = "qdrant")
+///
+/// Each chunk is a point whose payload holds the chunk's fields; point ids
+/// are derived from chunk ids.
+#[derive(Debug, Clone)]
+pub struct QdrantIndex {
+    client: reqwest::Client,
+    url: String,
+    collection: String,
+    api_key: Option<String>,
+}
+
+fn index_error(error: impl std::fmt::Display) -> RetrievalError {
+    RetrievalError::Index(error.to_string())
+}
+
+impl QdrantIndex {
+    /// Collection `collection` on the server at `url`, e.g.
+    /// `http://localhost:6333`
+    pub fn new(url: impl Into<String>, collection: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+            collection: collection.into(),
+            api_key: None,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Create the collection for vectors of `dimension` if it does not exist
+    pub async fn ensure_collection(&self, dimension: usize) -> Result<()> {
+        let path = format!("/collections/{}", self.collection);
+        let exists = self
+            .request(reqwest::Method::GET, &path, None)
+            .await
+            .is_ok();
+        if !exists {
+            let body = json!({"vectors": {"size": dimension, "distance": "Cosine"}});
+            self.request(reqwest::Method::PUT, &path, Some(body))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value> {
+        let mut request = self.client.request(method, format!("{}{}", self.url, path));
+        if let Some(key) = &self.api_key {
+            request = request.header("api-key", key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.map_err(index_error)?;
+        let status = response.status();
+        let json: Value = response.json().await.map_err(index_error)?;
+        if !status.is_success() {
+            return Err(RetrievalError::Index(format!(
+                "Qdrant returned {}: {}",
+                status, json["status"]["error"]
+            )));
+        }
+        Ok(json)
+    }
+}
+
+/// Qdrant accepts only integers and UUIDs as point ids
+fn point_id(chunk_id: &str) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, chunk_id.as_bytes()).to_string()
+}
+
+#[async_trait]
+impl VectorIndex for QdrantIndex {
+    async fn upsert(&self, entries: Vec<(Chunk, Vec<f32>)>) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let points: Vec<Value> = entries
+            .into_iter()
+            .map(|(chunk, vector)| {
+                json!({
+                    "id": point_id(&chunk.id),
+                    "vector": vector,
+                    "payload": chunk,
+                })
+            })
+            .collect();
+        let path = format!("/collections/{}/points?wait=true", self.collection);
+        self.request(
+            reqwest::Method::PUT,
+            &path,
+            Some(json!({ "points": points })),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn search(&self, query: &[f32], limit: usize) -> Result<Vec<ScoredChunk>> {
+        let path = format!("/collections/{}/points/search", self.collection);
+        let body = json!({"vector": query, "limit": limit, "with_payload": true});
+        let json = self
+            .request(reqwest::Method::POST, &path, Some(body))
+            .await?;
+        json["result"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|hit| {
+                Ok(ScoredChunk {
+                    chunk: serde_json::from_value(hit["payload"].clone()).map_err(index_error)?,
+                    score: hit["score"].as_f64().unwrap_or_default() as f32,
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_document(&self, document: &str) -> Result<()> {
+        let path = format!("/collections/{}/points/delete?wait=true", self.collection);
+        let body = json!({
+            "filter": {"must": [{"key": "document", "match": {"value": document}}]}
+        });
+        self.request(reqwest::Method::POST, &path, Some(body))
+            .await
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_ids_are_stable_uuids() {
+        assert_eq!(point_id("doc#0"), point_id("doc#0"));
+        assert_ne!(point_id("doc#0"), point_id("doc#1"));
+        assert!(Uuid::parse_str(&point_id("doc#0")).is_ok());
+    }
+}
@@ -0,0 +1,115 @@
+use super::{format_chunks, Retriever};
+use crate::tool::{Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Lets the model search indexed documents
+pub struct RetrievalTool {
+    retriever: Arc<Retriever>,
+    name: String,
+    description: String,
+    max_results: usize,
+}
+
+impl RetrievalTool {
+    pub fn new(retriever: Arc<Retriever>) -> Self {
+        Self {
+            retriever,
+            name: "search_documents".to_string(),
+            description: "Search the indexed documents and return the most relevant passages"
+                .to_string(),
+            max_results: 5,
+        }
+    }
+
+    /// Name the model calls the tool by, e.g. to tell several collections
+    /// apart
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Describe what the documents are about, so the model knows when to
+    /// search them
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Most passages returned per call, whatever the model asks for; 5 by
+    /// default
+    pub fn max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for RetrievalTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "What to look for"},
+                "max_results": {
+                    "type": "number",
+                    "description": format!("Number of passages, at most {}", self.max_results)
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let query = params["query"].as_str().unwrap_or_default().trim();
+        if query.is_empty() {
+            return ToolError::invalid_args("query must not be empty").into();
+        }
+        let max_results = params["max_results"]
+            .as_u64()
+            .map_or(self.max_results, |n| {
+                (n as usize).clamp(1, self.max_results)
+            });
+
+        match self.retriever.retrieve(query, max_results).await {
+            Ok(hits) if hits.is_empty() => ToolResult::success("No matching passages found"),
+            Ok(hits) => ToolResult::success(format_chunks(&hits)),
+            Err(e) => ToolError::upstream(e.to_string()).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retrieval::tests::retriever;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn returns_numbered_passages() {
+        let tool = RetrievalTool::new(Arc::new(retriever().await)).max_results(1);
+        let result = tool
+            .execute(&json!({"query": "the ocean", "max_results": 10}))
+            .await;
+        assert_eq!(
+            result.content,
+            "[1] (nature) The ocean is deep. The mountain is high."
+        );
+
+        let result = tool.execute(&json!({"query": " "})).await;
+        assert!(result.error.is_some());
+    }
+}
@@ -0,0 +1,172 @@
+//! JSON Schema validation shared by tool parameters and structured output.
+//!
+//! Only the commonly used subset of JSON Schema is checked; other keywords,
+//! such as `$ref` or `format`, are ignored.
+
+use serde_json::Value;
+
+/// Check `value` against the commonly used subset of JSON Schema
+///
+/// Supports `type`, `enum`, `const`, `properties`, `required`,
+/// `additionalProperties`, `items`, `minItems`/`maxItems`,
+/// `minimum`/`maximum`, `minLength`/`maxLength` and `anyOf`. Returns one
+/// message per violation.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at("$", value, schema, &mut errors);
+    errors
+}
+
+fn type_matches(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn validate_at(path: &str, value: &Value, schema: &Value, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| type_matches(value, name)) {
+            errors.push(format!("{}: expected {}", path, names.join(" or ")));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            errors.push(format!(
+                "{}: must be one of {}",
+                path,
+                Value::from(allowed.clone())
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: must be {}", path, expected));
+        }
+    }
+    if let Some(options) = schema.get("anyOf").and_then(Value::as_array) {
+        if !options
+            .iter()
+            .any(|option| validate(value, option).is_empty())
+        {
+            errors.push(format!("{}: does not match any allowed schema", path));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        errors.push(format!("{}: missing required property '{}'", path, key));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, item) in object {
+                let item_path = format!("{}.{}", path, key);
+                match (
+                    properties.and_then(|p| p.get(key)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(item_schema), _) => validate_at(&item_path, item, item_schema, errors),
+                    (None, Some(Value::Bool(false))) => {
+                        errors.push(format!("{}: unexpected property", item_path))
+                    }
+                    (None, Some(extra)) => validate_at(&item_path, item, extra, errors),
+                    (None, None) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: expected at least {} items", path, min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if items.len() as u64 > max {
+                    errors.push(format!("{}: expected at most {} items", path, max));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(&format!("{}[{}]", path, index), item, item_schema, errors);
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < min {
+                    errors.push(format!("{}: must be at least {}", path, min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > max {
+                    errors.push(format!("{}: must be at most {}", path, max));
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    errors.push(format!("{}: must be at least {} characters", path, min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    errors.push(format!("{}: must be at most {} characters", path, max));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn integers_may_have_a_zero_fraction() {
+        let schema = json!({"type": "integer"});
+        assert!(validate(&json!(5), &schema).is_empty());
+        assert!(validate(&json!(5.0), &schema).is_empty());
+        assert_eq!(validate(&json!(5.5), &schema), ["$: expected integer"]);
+    }
+
+    #[test]
+    fn nullable_types_and_nested_items_are_checked() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "note": {"type": ["string", "null"]},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}}
+            }
+        });
+        assert!(validate(&json!({"note": null, "tags": ["a"]}), &schema).is_empty());
+        let errors = validate(&json!({"note": 1, "tags": ["c"]}), &schema);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("$.note: expected string or null"));
+        assert!(errors[1].starts_with("$.tags[0]: must be one of"));
+    }
+}
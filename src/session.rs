@@ -0,0 +1,488 @@
+//! Conversation sessions that survive process restarts.
+//!
+//! A `Session` holds the turns of a conversation plus application variables.
+//! `Agent::run_in_session` continues the conversation and records the new
+//! turns; a `SessionStore` persists sessions between runs.
+
+use crate::error::{AgentError, Result};
+use crate::provider::{GenerateResponse, JobHandle, Message, Role};
+use crate::storage;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Persisted state of one conversation
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub id: String,
+    /// User, assistant and tool turns; system prompts are rebuilt each run
+    pub messages: Vec<Message>,
+    /// Application state carried with the conversation
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+    /// Completed runs
+    #[serde(default)]
+    pub runs: u64,
+    /// Deferred generations whose replies belong to this conversation
+    #[serde(default)]
+    pub pending_jobs: Vec<JobHandle>,
+    /// Unix seconds
+    pub created_at: u64,
+    /// Unix seconds
+    pub updated_at: u64,
+    /// Snapshots to roll back to, oldest first
+    #[serde(default)]
+    checkpoints: Vec<Checkpoint>,
+    #[serde(default)]
+    next_checkpoint: u64,
+}
+
+/// Snapshot of a session taken by `Session::checkpoint`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Checkpoint {
+    pub id: u64,
+    pub messages: Vec<Message>,
+    pub variables: HashMap<String, Value>,
+    pub runs: u64,
+    /// Unix seconds
+    pub created_at: u64,
+}
+
+impl Session {
+    pub fn new(id: impl Into<String>) -> Self {
+        let now = now_secs();
+        Self {
+            id: id.into(),
+            messages: Vec::new(),
+            variables: HashMap::new(),
+            runs: 0,
+            pending_jobs: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            checkpoints: Vec::new(),
+            next_checkpoint: 0,
+        }
+    }
+
+    /// A new session with a random id
+    pub fn with_random_id() -> Self {
+        Self::new(crate::ids::new_id())
+    }
+
+    /// Snapshot the history, variables and run count
+    ///
+    /// Returns the id to pass to `rollback`. Checkpoints are saved with the
+    /// session.
+    pub fn checkpoint(&mut self) -> u64 {
+        let id = self.next_checkpoint;
+        self.next_checkpoint += 1;
+        self.checkpoints.push(Checkpoint {
+            id,
+            messages: self.messages.clone(),
+            variables: self.variables.clone(),
+            runs: self.runs,
+            created_at: now_secs(),
+        });
+        id
+    }
+
+    /// Restore the state captured by checkpoint `id`
+    ///
+    /// Later checkpoints are discarded; the restored one is kept so the
+    /// session can be rolled back to it again. Returns false if `id` is
+    /// unknown.
+    pub fn rollback(&mut self, id: u64) -> bool {
+        let Some(index) = self.checkpoints.iter().position(|c| c.id == id) else {
+            return false;
+        };
+        self.checkpoints.truncate(index + 1);
+        let checkpoint = &self.checkpoints[index];
+        self.messages = checkpoint.messages.clone();
+        self.variables = checkpoint.variables.clone();
+        self.runs = checkpoint.runs;
+        self.updated_at = now_secs();
+        true
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Forget checkpoint `id` without restoring it
+    pub fn release_checkpoint(&mut self, id: u64) -> bool {
+        let before = self.checkpoints.len();
+        self.checkpoints.retain(|c| c.id != id);
+        self.checkpoints.len() != before
+    }
+
+    pub fn set_variable(&mut self, key: impl Into<String>, value: Value) {
+        self.variables.insert(key.into(), value);
+    }
+
+    pub fn variable(&self, key: &str) -> Option<&Value> {
+        self.variables.get(key)
+    }
+
+    /// Remember a submitted job until `complete_job` records its reply
+    pub fn track_job(&mut self, job: JobHandle) {
+        self.pending_jobs.push(job);
+        self.updated_at = now_secs();
+    }
+
+    /// Append a finished job's reply as an assistant turn and stop tracking
+    /// it; returns false if the job is not pending
+    pub fn complete_job(&mut self, job_id: &str, response: &GenerateResponse) -> bool {
+        let before = self.pending_jobs.len();
+        self.pending_jobs.retain(|job| job.id != job_id);
+        if self.pending_jobs.len() == before {
+            return false;
+        }
+        self.messages
+            .push(Message::assistant(response.content.clone()));
+        self.updated_at = now_secs();
+        true
+    }
+
+    /// Replace the history with the turns of `conversation` after its
+    /// leading system prompts
+    pub(crate) fn record_run(&mut self, conversation: &[Message]) {
+        self.messages = conversation
+            .iter()
+            .skip_while(|m| m.role == Role::System)
+            .cloned()
+            .collect();
+        self.runs += 1;
+        self.updated_at = now_secs();
+    }
+}
+
+/// Storage backend for sessions
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn load(&self, id: &str) -> Result<Option<Session>>;
+
+    /// Insert or replace the session with the same id
+    async fn save(&self, session: &Session) -> Result<()>;
+
+    /// Returns whether a session was removed
+    async fn delete(&self, id: &str) -> Result<bool>;
+
+    /// Stored session ids, sorted
+    async fn list(&self) -> Result<Vec<String>>;
+
+    /// The stored session, or a new empty one
+    async fn load_or_create(&self, id: &str) -> Result<Session> {
+        Ok(self.load(id).await?.unwrap_or_else(|| Session::new(id)))
+    }
+}
+
+/// Sessions kept in memory, for tests and short-lived processes
+#[derive(Clone, Default)]
+pub struct InMemorySessionStore {
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        Ok(self.sessions.read().await.get(id).cloned())
+    }
+
+    async fn save(&self, session: &Session) -> Result<()> {
+        self.sessions
+            .write()
+            .await
+            .insert(session.id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self.sessions.write().await.remove(id).is_some())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut ids: Vec<String> = self.sessions.read().await.keys().cloned().collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+}
+
+/// One pretty-printed JSON file per session in a directory
+#[derive(Debug, Clone)]
+pub struct FileSessionStore {
+    dir: PathBuf,
+}
+
+impl FileSessionStore {
+    /// Store sessions in `dir`, created on first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        storage::id_path(&self.dir, id, "json").ok_or_else(|| {
+            AgentError::Session(format!(
+                "Session id '{}' must use letters, digits, '-', '_' or '.'",
+                id
+            ))
+        })
+    }
+}
+
+fn io_error(error: std::io::Error) -> AgentError {
+    AgentError::Session(error.to_string())
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        match tokio::fs::read(self.path(id)?).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| AgentError::Session(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    async fn save(&self, session: &Session) -> Result<()> {
+        let path = self.path(&session.id)?;
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(io_error)?;
+        let json =
+            serde_json::to_vec_pretty(session).map_err(|e| AgentError::Session(e.to_string()))?;
+        // Write then rename so a crash never leaves a truncated session
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, json).await.map_err(io_error)?;
+        tokio::fs::rename(&tmp, &path).await.map_err(io_error)
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        match tokio::fs::remove_file(self.path(id)?).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        storage::list_ids(&self.dir, "json").await.map_err(io_error)
+    }
+}
+
+/// Sessions in a SQLite table (feature = "sqlite")
+#[cfg(feature = "sqlite")]
+#[derive(Clone)]
+pub struct SqliteSessionStore {
+    db: storage::SqliteDb,
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(error: rusqlite::Error) -> AgentError {
+    AgentError::Session(error.to_string())
+}
+
+#[cfg(feature = "sqlite")]
+const SESSION_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS agent_sessions (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+)";
+
+#[cfg(feature = "sqlite")]
+impl SqliteSessionStore {
+    /// Open `path`, creating the database and its table if needed
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let db = storage::SqliteDb::open(path.as_ref(), SESSION_SCHEMA, AgentError::Session)?;
+        Ok(Self { db })
+    }
+
+    /// Sessions kept in an in-process database, lost with the store
+    pub fn open_in_memory() -> Result<Self> {
+        let db = storage::SqliteDb::open_in_memory(SESSION_SCHEMA, AgentError::Session)?;
+        Ok(Self { db })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait]
+impl SessionStore for SqliteSessionStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        let id = id.to_string();
+        let data: Option<String> = self
+            .db
+            .with_connection(move |db| {
+                use rusqlite::OptionalExtension;
+                db.query_row(
+                    "SELECT data FROM agent_sessions WHERE id = ?1",
+                    [&id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(sqlite_error)
+            })
+            .await?;
+        data.map(|data| serde_json::from_str(&data).map_err(|e| AgentError::Session(e.to_string())))
+            .transpose()
+    }
+
+    async fn save(&self, session: &Session) -> Result<()> {
+        let data =
+            serde_json::to_string(session).map_err(|e| AgentError::Session(e.to_string()))?;
+        let id = session.id.clone();
+        let updated_at = session.updated_at as i64;
+        self.db.with_connection(move |db| {
+            db.execute(
+                "INSERT INTO agent_sessions (id, data, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+                rusqlite::params![id, data, updated_at],
+            )
+            .map(|_| ())
+            .map_err(sqlite_error)
+        })
+        .await
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        let id = id.to_string();
+        self.db
+            .with_connection(move |db| {
+                db.execute("DELETE FROM agent_sessions WHERE id = ?1", [&id])
+                    .map(|rows| rows > 0)
+                    .map_err(sqlite_error)
+            })
+            .await
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        self.db
+            .with_connection(|db| {
+                let mut statement = db
+                    .prepare("SELECT id FROM agent_sessions ORDER BY id")
+                    .map_err(sqlite_error)?;
+                let ids = statement
+                    .query_map([], |row| row.get(0))
+                    .map_err(sqlite_error)?
+                    .collect::<std::result::Result<Vec<String>, _>>()
+                    .map_err(sqlite_error)?;
+                Ok(ids)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn round_trip(store: &dyn SessionStore) {
+        let mut session = Session::new("chat-1");
+        session.record_run(&[
+            Message::system("be brief"),
+            Message::user("hi"),
+            Message::assistant("hello"),
+        ]);
+        session.set_variable("user", serde_json::json!({"name": "Ada"}));
+        session.track_job(JobHandle::new("batch-1", "openai"));
+        store.save(&session).await.unwrap();
+
+        let loaded = store.load("chat-1").await.unwrap().unwrap();
+        assert_eq!(loaded.messages.len(), 2);
+        assert_eq!(loaded.messages[1].content_as_text(), "hello");
+        assert_eq!(loaded.variable("user").unwrap()["name"], "Ada");
+        assert_eq!(loaded.runs, 1);
+        assert_eq!(loaded.pending_jobs[0].id, "batch-1");
+
+        assert_eq!(store.list().await.unwrap(), vec!["chat-1".to_string()]);
+        assert!(store.delete("chat-1").await.unwrap());
+        assert!(!store.delete("chat-1").await.unwrap());
+        assert!(store.load("chat-1").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn rollback_restores_checkpointed_state() {
+        let mut session = Session::new("chat");
+        session.record_run(&[Message::user("hi"), Message::assistant("hello")]);
+        let good = session.checkpoint();
+
+        session.set_variable("mood", serde_json::json!("bad"));
+        session.record_run(&[
+            Message::user("hi"),
+            Message::assistant("hello"),
+            Message::user("do it"),
+            Message::assistant("oops"),
+        ]);
+        let later = session.checkpoint();
+
+        assert!(session.rollback(good));
+        assert_eq!(session.messages.len(), 2);
+        assert_eq!(session.runs, 1);
+        assert!(session.variable("mood").is_none());
+        // Checkpoints after the restored one are gone
+        assert!(!session.rollback(later));
+        assert_eq!(session.checkpoints().len(), 1);
+
+        let restored: Session =
+            serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert_eq!(restored.checkpoints()[0].id, good);
+    }
+
+    #[test]
+    fn completing_a_job_records_its_reply() {
+        let mut session = Session::new("chat");
+        session.track_job(JobHandle::new("batch-1", "anthropic"));
+        let response = GenerateResponse {
+            content: "summary".to_string(),
+            usage: None,
+            model: "m".to_string(),
+            finish_reason: None,
+            tool_calls: Vec::new(),
+        };
+
+        assert!(session.complete_job("batch-1", &response));
+        assert!(session.pending_jobs.is_empty());
+        assert_eq!(session.messages[0].content_as_text(), "summary");
+        assert!(!session.complete_job("batch-1", &response));
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_round_trips() {
+        round_trip(&InMemorySessionStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn file_store_round_trips_and_rejects_unsafe_ids() {
+        let dir = std::env::temp_dir().join(format!("agent-sdk-sessions-{}", std::process::id()));
+        let store = FileSessionStore::new(&dir);
+        round_trip(&store).await;
+        assert!(matches!(
+            store.save(&Session::new("../escape")).await,
+            Err(AgentError::Session(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_store_round_trips() {
+        round_trip(&SqliteSessionStore::open_in_memory().unwrap()).await;
+    }
+}
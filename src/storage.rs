@@ -0,0 +1,117 @@
+//! Plumbing shared by the file and SQLite backed stores and the JSON lines
+//! logs.
+
+#[cfg(feature = "sqlite")]
+use crate::error::{AgentError, Result};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// `dir/<id>.<extension>`, or `None` if `id` is not safe as a file name
+///
+/// Ids may use letters, digits, '-', '_' and '.', but not start with '.'.
+pub(crate) fn id_path(dir: &Path, id: &str, extension: &str) -> Option<PathBuf> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !id.starts_with('.');
+    valid.then(|| dir.join(format!("{}.{}", id, extension)))
+}
+
+/// Sorted ids of the `<id>.<extension>` files in `dir`; none if it is missing
+pub(crate) async fn list_ids(dir: &Path, extension: &str) -> std::io::Result<Vec<String>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let suffix = format!(".{}", extension);
+    let mut ids = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        if let Some(id) = name.to_str().and_then(|n| n.strip_suffix(suffix.as_str())) {
+            ids.push(id.to_string());
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Append `values` to `path` as JSON lines in one write, creating the file
+/// if needed; returns once the write is flushed
+pub(crate) async fn append_json_lines<T: serde::Serialize>(
+    path: &Path,
+    values: &[T],
+) -> std::io::Result<()> {
+    let mut lines = Vec::new();
+    for value in values {
+        serde_json::to_writer(&mut lines, value)?;
+        lines.push(b'\n');
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&lines).await?;
+    file.flush().await
+}
+
+pub(crate) async fn append_json_line(
+    path: &Path,
+    value: &impl serde::Serialize,
+) -> std::io::Result<()> {
+    append_json_lines(path, std::slice::from_ref(value)).await
+}
+
+/// SQLite connection shared by a store's clones
+///
+/// Errors are reported through `error`, e.g. `AgentError::Session`.
+#[cfg(feature = "sqlite")]
+#[derive(Clone)]
+pub(crate) struct SqliteDb {
+    connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    error: fn(String) -> AgentError,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteDb {
+    /// Open or create the database at `path` and run `schema` on it
+    pub(crate) fn open(path: &Path, schema: &str, error: fn(String) -> AgentError) -> Result<Self> {
+        let connection = rusqlite::Connection::open(path).map_err(|e| error(e.to_string()))?;
+        Self::init(connection, schema, error)
+    }
+
+    /// Database that lives as long as the connection
+    pub(crate) fn open_in_memory(schema: &str, error: fn(String) -> AgentError) -> Result<Self> {
+        let connection =
+            rusqlite::Connection::open_in_memory().map_err(|e| error(e.to_string()))?;
+        Self::init(connection, schema, error)
+    }
+
+    fn init(
+        connection: rusqlite::Connection,
+        schema: &str,
+        error: fn(String) -> AgentError,
+    ) -> Result<Self> {
+        connection
+            .execute_batch(schema)
+            .map_err(|e| error(e.to_string()))?;
+        Ok(Self {
+            connection: std::sync::Arc::new(std::sync::Mutex::new(connection)),
+            error,
+        })
+    }
+
+    /// Run a blocking query off the async runtime
+    pub(crate) async fn with_connection<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut rusqlite::Connection) -> Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || f(&mut connection.lock().unwrap()))
+            .await
+            .map_err(|e| (self.error)(e.to_string()))?
+    }
+}
@@ -0,0 +1,502 @@
+//! Helpers for testing agents without network access.
+//!
+//! `MockProvider` answers from a script of canned replies, tool calls and
+//! errors, and records every request it receives. `EventRecorder` collects
+//! the events an agent emits so tests can assert on them.
+
+use crate::events::{AgentEvent, EventBus, EventKind};
+use crate::provider::{
+    GenerateOptions, GenerateResponse, LlmProvider, Message, ProviderCapabilities, ProviderError,
+    Result, StreamEvent, StreamEvents, StreamResponse, ToolSchema, Usage,
+};
+use crate::tool::ToolCall;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Request received by a `MockProvider`
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub messages: Vec<Message>,
+    /// Tools offered through native tool calling
+    pub tools: Vec<ToolSchema>,
+    pub options: Option<GenerateOptions>,
+}
+
+impl MockRequest {
+    /// Text of the last user message
+    pub fn last_user_text(&self) -> Option<String> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == crate::provider::Role::User)
+            .map(Message::content_as_text)
+    }
+}
+
+struct Step {
+    reply: Result<GenerateResponse>,
+    delay: Option<Duration>,
+}
+
+#[derive(Default)]
+struct MockState {
+    script: Mutex<VecDeque<Step>>,
+    requests: Mutex<Vec<MockRequest>>,
+    next_call: Mutex<u64>,
+}
+
+/// Provider that replays a script of replies, in order
+///
+/// Clones share the script and the recorded requests, so keep a clone to
+/// inspect what an agent sent. Native tool calling and streaming are
+/// supported; a request after the script runs out fails.
+#[derive(Clone)]
+pub struct MockProvider {
+    name: String,
+    model: String,
+    latency: Duration,
+    capabilities: ProviderCapabilities,
+    state: Arc<MockState>,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self {
+            name: "mock".to_string(),
+            model: "mock-model".to_string(),
+            latency: Duration::ZERO,
+            capabilities: ProviderCapabilities {
+                streaming: true,
+                native_tools: true,
+                tool_choice: true,
+                ..Default::default()
+            },
+            state: Arc::default(),
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Capabilities to report, e.g. without `native_tools` to test
+    /// prompt-based tool calling
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Delay before every reply without its own `delay`
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    fn push(self, reply: Result<GenerateResponse>) -> Self {
+        self.state
+            .script
+            .lock()
+            .unwrap()
+            .push_back(Step { reply, delay: None });
+        self
+    }
+
+    /// Queue a text reply
+    pub fn text(self, content: impl Into<String>) -> Self {
+        let response = self.response_with(content.into(), Vec::new());
+        self.push(Ok(response))
+    }
+
+    /// Queue a reply calling one tool
+    pub fn tool_call(self, name: impl Into<String>, parameters: serde_json::Value) -> Self {
+        let id = {
+            let mut next = self.state.next_call.lock().unwrap();
+            *next += 1;
+            format!("call_{}", next)
+        };
+        self.tool_calls(vec![ToolCall {
+            id,
+            name: name.into(),
+            parameters,
+        }])
+    }
+
+    /// Queue a reply calling several tools at once
+    pub fn tool_calls(self, calls: Vec<ToolCall>) -> Self {
+        let response = self.response_with(String::new(), calls);
+        self.push(Ok(response))
+    }
+
+    /// Queue a complete response
+    pub fn response(self, response: GenerateResponse) -> Self {
+        self.push(Ok(response))
+    }
+
+    /// Queue a failed request
+    pub fn error(self, error: ProviderError) -> Self {
+        self.push(Err(error))
+    }
+
+    /// Delay the most recently queued reply
+    pub fn delay(self, delay: Duration) -> Self {
+        if let Some(step) = self.state.script.lock().unwrap().back_mut() {
+            step.delay = Some(delay);
+        }
+        self
+    }
+
+    fn response_with(&self, content: String, tool_calls: Vec<ToolCall>) -> GenerateResponse {
+        let finish_reason = if tool_calls.is_empty() {
+            "stop"
+        } else {
+            "tool_calls"
+        };
+        GenerateResponse {
+            content,
+            usage: Some(Usage::default()),
+            model: self.model.clone(),
+            finish_reason: Some(finish_reason.to_string()),
+            tool_calls,
+        }
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.state.requests.lock().unwrap().len()
+    }
+
+    /// Replies not served yet
+    pub fn remaining(&self) -> usize {
+        self.state.script.lock().unwrap().len()
+    }
+
+    fn next_reply(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> impl Future<Output = Result<GenerateResponse>> + Send + 'static {
+        self.state.requests.lock().unwrap().push(MockRequest {
+            messages,
+            tools,
+            options,
+        });
+        let step = self.state.script.lock().unwrap().pop_front();
+        let latency = self.latency;
+        async move {
+            let Some(step) = step else {
+                return Err(ProviderError::Other(
+                    "MockProvider has no replies left".to_string(),
+                ));
+            };
+            let delay = step.delay.unwrap_or(latency);
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            step.reply
+        }
+    }
+}
+
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn generate(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.next_reply(messages, Vec::new(), options))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<GenerateResponse>> + Send + '_>> {
+        Box::pin(self.next_reply(messages, tools, options))
+    }
+
+    fn generate_stream(
+        &self,
+        messages: Vec<Message>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamResponse>> + Send + '_>> {
+        let reply = self.next_reply(messages, Vec::new(), options);
+        Box::pin(async move {
+            let response = reply.await?;
+            Ok(StreamResponse::spawn(1, |tx| async move {
+                let _ = tx.send(Ok(response.content)).await;
+            }))
+        })
+    }
+
+    fn generate_stream_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolSchema>,
+        options: Option<GenerateOptions>,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamEvents>> + Send + '_>> {
+        let reply = self.next_reply(messages, tools, options);
+        Box::pin(async move {
+            let response = reply.await?;
+            let mut events = Vec::new();
+            if !response.content.is_empty() {
+                events.push(StreamEvent::TextDelta(response.content));
+            }
+            for (index, call) in response.tool_calls.into_iter().enumerate() {
+                events.push(StreamEvent::ToolCallDelta {
+                    index,
+                    id: Some(call.id),
+                    name: Some(call.name),
+                    arguments_delta: call.parameters.to_string(),
+                });
+            }
+            if let Some(usage) = response.usage {
+                events.push(StreamEvent::UsageUpdate(usage));
+            }
+            events.push(StreamEvent::Done {
+                finish_reason: response.finish_reason,
+            });
+            Ok(StreamEvents::spawn(events.len(), |tx| async move {
+                for event in events {
+                    if tx.send(Ok(event)).await.is_err() {
+                        return;
+                    }
+                }
+            }))
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities.clone()
+    }
+}
+
+/// Collects events from an `EventBus` for assertions
+///
+/// Events are read when inspected, so the bus must have room for every
+/// event emitted in between.
+pub struct EventRecorder {
+    bus: Arc<EventBus>,
+    receiver: Mutex<broadcast::Receiver<AgentEvent>>,
+    events: Mutex<Vec<AgentEvent>>,
+}
+
+impl Default for EventRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventRecorder {
+    /// Recorder with its own bus; pass `bus()` to `Agent::with_event_bus`
+    pub fn new() -> Self {
+        Self::attach(Arc::new(EventBus::new(10_000)))
+    }
+
+    /// Record events emitted on an existing bus from now on
+    pub fn attach(bus: Arc<EventBus>) -> Self {
+        Self {
+            receiver: Mutex::new(bus.subscribe()),
+            bus,
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn bus(&self) -> Arc<EventBus> {
+        self.bus.clone()
+    }
+
+    /// Every event recorded so far, in emission order
+    ///
+    /// Panics if events were lost because the bus was too small.
+    pub fn events(&self) -> Vec<AgentEvent> {
+        let mut receiver = self.receiver.lock().unwrap();
+        let mut events = self.events.lock().unwrap();
+        loop {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(broadcast::error::TryRecvError::Lagged(n)) => {
+                    panic!("EventRecorder missed {} events; use a larger bus", n)
+                }
+                Err(_) => return events.clone(),
+            }
+        }
+    }
+
+    pub fn kinds(&self) -> Vec<EventKind> {
+        self.events().iter().map(AgentEvent::kind).collect()
+    }
+
+    pub fn count(&self, kind: EventKind) -> usize {
+        self.kinds().into_iter().filter(|k| *k == kind).count()
+    }
+
+    /// Tool calls started, in order
+    pub fn tool_calls(&self) -> Vec<ToolCall> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                AgentEvent::ToolCallStarted { call } => Some(call),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Forget the events recorded so far
+    pub fn clear(&self) {
+        self.events();
+        self.events.lock().unwrap().clear();
+    }
+
+    #[track_caller]
+    pub fn assert_emitted(&self, kind: EventKind) {
+        let kinds = self.kinds();
+        assert!(
+            kinds.contains(&kind),
+            "expected a {:?} event, got {:?}",
+            kind,
+            kinds
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_not_emitted(&self, kind: EventKind) {
+        let kinds = self.kinds();
+        assert!(
+            !kinds.contains(&kind),
+            "expected no {:?} event, got {:?}",
+            kind,
+            kinds
+        );
+    }
+
+    /// Assert `expected` were emitted in this order, possibly with other
+    /// events in between
+    #[track_caller]
+    pub fn assert_sequence(&self, expected: &[EventKind]) {
+        let kinds = self.kinds();
+        let mut remaining = kinds.iter();
+        for kind in expected {
+            assert!(
+                remaining.any(|k| k == kind),
+                "expected {:?} in order, got {:?}",
+                expected,
+                kinds
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Agent, AgentStreamEvent};
+    use crate::tool::{Tool, ToolResult};
+    use async_trait::async_trait;
+    use futures_util::StreamExt;
+    use serde_json::{json, Value};
+
+    struct Weather;
+
+    #[async_trait]
+    impl Tool for Weather {
+        fn name(&self) -> &str {
+            "weather"
+        }
+
+        fn description(&self) -> &str {
+            "Weather in a city"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"city": {"type": "string"}}})
+        }
+
+        async fn execute(&self, params: &Value) -> ToolResult {
+            ToolResult::success(format!("Sunny in {}", params["city"].as_str().unwrap()))
+        }
+    }
+
+    #[tokio::test]
+    async fn scripts_an_agent_run_and_records_its_events() {
+        let mock = MockProvider::new()
+            .tool_call("weather", json!({"city": "Oslo"}))
+            .delay(Duration::from_millis(10))
+            .text("It is sunny in Oslo")
+            .error(ProviderError::RateLimited { retry_after: None });
+        let recorder = EventRecorder::new();
+        let mut agent = Agent::new(mock.clone()).with_event_bus(recorder.bus());
+        agent.register_tool(Box::new(Weather)).await;
+
+        assert_eq!(
+            agent.run("Weather in Oslo?").await.unwrap(),
+            "It is sunny in Oslo"
+        );
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].tools[0].name, "weather");
+        assert_eq!(
+            requests[0].last_user_text().as_deref(),
+            Some("Weather in Oslo?")
+        );
+        assert_eq!(recorder.tool_calls()[0].parameters, json!({"city": "Oslo"}));
+        recorder.assert_sequence(&[
+            EventKind::ConversationStarted,
+            EventKind::ToolCallStarted,
+            EventKind::ToolCallCompleted,
+            EventKind::ConversationCompleted,
+        ]);
+        recorder.assert_not_emitted(EventKind::ConversationFailed);
+
+        // Injected error, then an exhausted script
+        recorder.clear();
+        assert!(agent.run("Again?").await.is_err());
+        recorder.assert_emitted(EventKind::ConversationFailed);
+        assert_eq!(recorder.count(EventKind::ConversationStarted), 1);
+        assert!(agent.run("Again?").await.is_err());
+        assert_eq!(mock.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn streams_scripted_replies() {
+        let mock = MockProvider::new().text("Hello there");
+        let mut agent = Agent::new(mock);
+        let mut stream = agent.run_stream_events("Hi");
+        let mut text = String::new();
+        while let Some(event) = stream.next().await {
+            if let AgentStreamEvent::TextDelta(delta) = event.unwrap() {
+                text.push_str(&delta);
+            }
+        }
+        assert_eq!(text, "Hello there");
+    }
+}
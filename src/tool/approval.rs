@@ -0,0 +1,551 @@
+//! Approving tool calls before they run.
+//!
+//! An `ApprovalManager` set on the executor lets calls to safe tools
+//! through and holds the rest as pending. A pending call is handed to the
+//! manager's `ApprovalHandler`; if there is none, or it does not answer in
+//! time, the manager's default decision applies. Timeouts are also
+//! broadcast, so an agent can report them as `AgentEvent::ApprovalTimedOut`.
+//!
+//! `ApprovalRule`s settle calls by tool name and parameters before the
+//! danger level is considered, and can be loaded from configuration.
+//! With an `ApprovalAuditLog` set, every decision is also recorded; records
+//! a sink failed to write are broadcast as `AuditFailure`s.
+
+use super::audit::{ApprovalAuditLog, ApprovalRecord, DecidedBy};
+use super::{DangerLevel, ToolCall, ToolInfo};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Outcome of an approval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    Approve,
+    /// Refuse the call; the reason is shown to the model
+    Reject(String),
+}
+
+/// Test applied to one parameter of a call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldTest {
+    Equals(Value),
+    StartsWith(String),
+    EndsWith(String),
+    Contains(String),
+    /// Whether the field is present at all
+    Exists(bool),
+}
+
+impl FieldTest {
+    fn matches(&self, value: Option<&Value>) -> bool {
+        let text = value.and_then(Value::as_str);
+        match self {
+            Self::Equals(expected) => value == Some(expected),
+            Self::StartsWith(prefix) => text.is_some_and(|t| t.starts_with(prefix.as_str())),
+            Self::EndsWith(suffix) => text.is_some_and(|t| t.ends_with(suffix.as_str())),
+            Self::Contains(part) => text.is_some_and(|t| t.contains(part.as_str())),
+            Self::Exists(exists) => value.is_some() == *exists,
+        }
+    }
+}
+
+/// Condition on a tool call, composable with `all`, `any` and `not`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallMatcher {
+    /// The tool has this name
+    Tool(String),
+    /// The parameter at `path` passes `test`
+    ///
+    /// `path` is dot-separated, with numbers indexing arrays, e.g.
+    /// `files.0.path`.
+    Field {
+        path: String,
+        #[serde(flatten)]
+        test: FieldTest,
+    },
+    All(Vec<CallMatcher>),
+    Any(Vec<CallMatcher>),
+    Not(Box<CallMatcher>),
+}
+
+impl CallMatcher {
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self::Tool(name.into())
+    }
+
+    pub fn field(path: impl Into<String>, test: FieldTest) -> Self {
+        Self::Field {
+            path: path.into(),
+            test,
+        }
+    }
+
+    pub fn matches(&self, call: &ToolCall) -> bool {
+        match self {
+            Self::Tool(name) => call.name == *name,
+            Self::Field { path, test } => test.matches(field(&call.parameters, path)),
+            Self::All(matchers) => matchers.iter().all(|m| m.matches(call)),
+            Self::Any(matchers) => matchers.iter().any(|m| m.matches(call)),
+            Self::Not(matcher) => !matcher.matches(call),
+        }
+    }
+}
+
+fn field<'a>(params: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(params, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+/// What a matching rule does with a call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Approve,
+    /// Reject with this reason
+    Reject(String),
+    /// Send the call to the handler whatever its danger level
+    Ask,
+}
+
+/// Declarative approval rule; the first rule that matches a call decides it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRule {
+    pub when: CallMatcher,
+    pub action: RuleAction,
+}
+
+impl ApprovalRule {
+    pub fn new(when: CallMatcher, action: RuleAction) -> Self {
+        Self { when, action }
+    }
+}
+
+/// A pending call the handler did not decide in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalTimeout {
+    pub call_id: String,
+    pub tool: String,
+    pub timeout: Duration,
+    /// The default decision that was applied instead
+    pub decision: ApprovalDecision,
+}
+
+/// A decision the audit log's sinks failed to write
+///
+/// The decision itself still applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditFailure {
+    pub call_id: String,
+    pub tool: String,
+    pub error: String,
+}
+
+/// Decides pending tool calls, e.g. by asking the user
+#[async_trait]
+pub trait ApprovalHandler: Send + Sync {
+    async fn decide(&self, call: &ToolCall, tool: &ToolInfo) -> ApprovalDecision;
+}
+
+/// Which tool calls need approval and who gives it
+#[derive(Clone)]
+pub struct ApprovalManager {
+    handler: Option<Arc<dyn ApprovalHandler>>,
+    threshold: DangerLevel,
+    rules: Vec<ApprovalRule>,
+    timeout: Duration,
+    tool_timeouts: HashMap<String, Duration>,
+    default_decision: ApprovalDecision,
+    timeouts: broadcast::Sender<ApprovalTimeout>,
+    audit: Option<ApprovalAuditLog>,
+    audit_failures: broadcast::Sender<AuditFailure>,
+}
+
+impl std::fmt::Debug for ApprovalManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApprovalManager")
+            .field("handler", &self.handler.is_some())
+            .field("threshold", &self.threshold)
+            .field("rules", &self.rules)
+            .field("timeout", &self.timeout)
+            .field("tool_timeouts", &self.tool_timeouts)
+            .field("default_decision", &self.default_decision)
+            .field("audit", &self.audit)
+            .finish()
+    }
+}
+
+impl Default for ApprovalManager {
+    fn default() -> Self {
+        Self {
+            handler: None,
+            threshold: DangerLevel::Moderate,
+            rules: Vec::new(),
+            timeout: Duration::from_secs(300),
+            tool_timeouts: HashMap::new(),
+            default_decision: ApprovalDecision::Reject("No approval was given".to_string()),
+            timeouts: broadcast::channel(16).0,
+            audit: None,
+            audit_failures: broadcast::channel(16).0,
+        }
+    }
+}
+
+impl ApprovalManager {
+    /// Calls to moderate and destructive tools are pending; without a
+    /// handler they are rejected
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send pending calls to `handler`
+    pub fn with_handler(mut self, handler: Arc<dyn ApprovalHandler>) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// Lowest danger level that needs approval
+    pub fn require_from(mut self, level: DangerLevel) -> Self {
+        self.threshold = level;
+        self
+    }
+
+    /// Add a rule, checked after those added before it
+    pub fn rule(mut self, rule: ApprovalRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Add rules, e.g. deserialized from a config file
+    pub fn rules(mut self, rules: impl IntoIterator<Item = ApprovalRule>) -> Self {
+        self.rules.extend(rules);
+        self
+    }
+
+    /// How long the handler may take; 5 minutes by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long the handler may take for calls to `tool`, overriding
+    /// `timeout`
+    pub fn tool_timeout(mut self, tool: impl Into<String>, timeout: Duration) -> Self {
+        self.tool_timeouts.insert(tool.into(), timeout);
+        self
+    }
+
+    pub fn timeout_for(&self, tool: &str) -> Duration {
+        self.tool_timeouts
+            .get(tool)
+            .copied()
+            .unwrap_or(self.timeout)
+    }
+
+    /// Decision for pending calls the handler did not answer in time, or
+    /// all of them without a handler
+    pub fn default_decision(mut self, decision: ApprovalDecision) -> Self {
+        self.default_decision = decision;
+        self
+    }
+
+    /// Record every decision in `log`
+    pub fn audit(mut self, log: ApprovalAuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Receive a notice for each call whose approval timed out
+    pub fn subscribe_timeouts(&self) -> broadcast::Receiver<ApprovalTimeout> {
+        self.timeouts.subscribe()
+    }
+
+    /// Receive a notice for each decision the audit log failed to persist
+    pub fn subscribe_audit_failures(&self) -> broadcast::Receiver<AuditFailure> {
+        self.audit_failures.subscribe()
+    }
+
+    pub fn requires_approval(&self, tool: &ToolInfo) -> bool {
+        tool.metadata.danger >= self.threshold
+    }
+
+    /// Settle `call` by the first matching rule, or by its danger level,
+    /// asking the handler when the call is pending
+    pub async fn decide(&self, call: &ToolCall, tool: &ToolInfo) -> ApprovalDecision {
+        let (decision, decided_by, rule) = self.settle(call, tool).await;
+        if let Some(audit) = &self.audit {
+            let record = ApprovalRecord::new(call, &decision, decided_by, rule);
+            if let Err(error) = audit.record(record).await {
+                let _ = self.audit_failures.send(AuditFailure {
+                    call_id: call.id.clone(),
+                    tool: tool.name.clone(),
+                    error: error.to_string(),
+                });
+            }
+        }
+        decision
+    }
+
+    async fn settle(
+        &self,
+        call: &ToolCall,
+        tool: &ToolInfo,
+    ) -> (ApprovalDecision, DecidedBy, Option<usize>) {
+        let matched = self.rules.iter().position(|rule| rule.when.matches(call));
+        match matched.map(|i| &self.rules[i].action) {
+            Some(RuleAction::Approve) => {
+                return (ApprovalDecision::Approve, DecidedBy::Rule, matched)
+            }
+            Some(RuleAction::Reject(reason)) => {
+                return (
+                    ApprovalDecision::Reject(reason.clone()),
+                    DecidedBy::Rule,
+                    matched,
+                )
+            }
+            Some(RuleAction::Ask) => {}
+            None if !self.requires_approval(tool) => {
+                return (ApprovalDecision::Approve, DecidedBy::DangerLevel, None)
+            }
+            None => {}
+        }
+        let Some(handler) = &self.handler else {
+            return (self.default_decision.clone(), DecidedBy::Default, matched);
+        };
+        let timeout = self.timeout_for(&tool.name);
+        match tokio::time::timeout(timeout, handler.decide(call, tool)).await {
+            Ok(decision) => (decision, DecidedBy::Handler, matched),
+            Err(_) => {
+                let _ = self.timeouts.send(ApprovalTimeout {
+                    call_id: call.id.clone(),
+                    tool: tool.name.clone(),
+                    timeout,
+                    decision: self.default_decision.clone(),
+                });
+                (self.default_decision.clone(), DecidedBy::Timeout, matched)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{Tool, ToolErrorKind, ToolExecutor, ToolMetadata, ToolRegistry, ToolResult};
+    use serde_json::{json, Value};
+
+    struct DeleteTool;
+
+    #[async_trait]
+    impl Tool for DeleteTool {
+        fn name(&self) -> &str {
+            "delete"
+        }
+
+        fn description(&self) -> &str {
+            "Delete a file"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"path": {"type": "string"}}})
+        }
+
+        fn metadata(&self) -> ToolMetadata {
+            ToolMetadata::default().danger(DangerLevel::Destructive)
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolResult::success("deleted")
+        }
+    }
+
+    /// Approves everything outside /etc, after `delay`
+    struct PathPolicy {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl ApprovalHandler for PathPolicy {
+        async fn decide(&self, call: &ToolCall, _tool: &ToolInfo) -> ApprovalDecision {
+            tokio::time::sleep(self.delay).await;
+            match call.parameters["path"].as_str() {
+                Some(path) if path.starts_with("/etc") => {
+                    ApprovalDecision::Reject("system files are off limits".to_string())
+                }
+                _ => ApprovalDecision::Approve,
+            }
+        }
+    }
+
+    fn call(path: &str) -> ToolCall {
+        ToolCall {
+            id: "1".to_string(),
+            name: "delete".to_string(),
+            parameters: json!({ "path": path }),
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_pending_calls_to_the_handler() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(DeleteTool)).await;
+        let handler = Arc::new(PathPolicy {
+            delay: Duration::ZERO,
+        });
+        let executor = ToolExecutor::new(registry)
+            .with_approvals(ApprovalManager::new().with_handler(handler));
+
+        let result = executor.execute_single(&call("/tmp/a")).await;
+        assert_eq!(result.content, "deleted");
+
+        let result = executor.execute_single(&call("/etc/passwd")).await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::PermissionDenied));
+        assert!(result
+            .error
+            .unwrap()
+            .contains("system files are off limits"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_default_decision() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(DeleteTool)).await;
+        let tool = registry.tool_info("delete").await.unwrap();
+
+        // No handler
+        let manager = ApprovalManager::new();
+        assert!(matches!(
+            manager.decide(&call("/tmp/a"), &tool).await,
+            ApprovalDecision::Reject(_)
+        ));
+        let manager = manager.default_decision(ApprovalDecision::Approve);
+        assert_eq!(
+            manager.decide(&call("/etc/a"), &tool).await,
+            ApprovalDecision::Approve
+        );
+
+        // Handler too slow
+        let manager = ApprovalManager::new()
+            .with_handler(Arc::new(PathPolicy {
+                delay: Duration::from_millis(50),
+            }))
+            .timeout(Duration::from_millis(10));
+        let mut timeouts = manager.subscribe_timeouts();
+        assert!(matches!(
+            manager.decide(&call("/tmp/a"), &tool).await,
+            ApprovalDecision::Reject(_)
+        ));
+        let timeout = timeouts.try_recv().unwrap();
+        assert_eq!(timeout.tool, "delete");
+        assert_eq!(timeout.timeout, Duration::from_millis(10));
+
+        // A longer timeout for this tool lets the handler answer
+        let manager = manager.tool_timeout("delete", Duration::from_secs(5));
+        assert_eq!(
+            manager.decide(&call("/tmp/a"), &tool).await,
+            ApprovalDecision::Approve
+        );
+        assert!(timeouts.try_recv().is_err());
+
+        // Below the threshold nothing is asked
+        let manager = manager.require_from(DangerLevel::Destructive);
+        assert!(manager.requires_approval(&tool));
+        let mut safe = tool.clone();
+        safe.metadata.danger = DangerLevel::Safe;
+        assert_eq!(
+            manager.decide(&call("/tmp/a"), &safe).await,
+            ApprovalDecision::Approve
+        );
+    }
+
+    #[tokio::test]
+    async fn rules_loaded_from_config_decide_first() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(DeleteTool)).await;
+        let tool = registry.tool_info("delete").await.unwrap();
+        let rules: Vec<ApprovalRule> = serde_json::from_value(json!([
+            {
+                "when": {"all": [
+                    {"tool": "delete"},
+                    {"any": [
+                        {"field": {"path": "path", "starts_with": "/etc"}},
+                        {"field": {"path": "path", "ends_with": ".key"}}
+                    ]}
+                ]},
+                "action": {"reject": "protected file"}
+            },
+            {
+                "when": {"not": {"field": {"path": "path", "exists": true}}},
+                "action": "ask"
+            },
+            {
+                "when": {"field": {"path": "path", "starts_with": "/tmp/"}},
+                "action": "approve"
+            }
+        ]))
+        .unwrap();
+        // No handler, so calls left pending are rejected
+        let manager = ApprovalManager::new().rules(rules);
+
+        let decision = manager.decide(&call("/etc/hosts"), &tool).await;
+        assert_eq!(
+            decision,
+            ApprovalDecision::Reject("protected file".to_string())
+        );
+        let decision = manager.decide(&call("/home/me/id.key"), &tool).await;
+        assert_eq!(
+            decision,
+            ApprovalDecision::Reject("protected file".to_string())
+        );
+        assert_eq!(
+            manager.decide(&call("/tmp/scratch"), &tool).await,
+            ApprovalDecision::Approve
+        );
+        assert_eq!(
+            manager.decide(&call("/home/me/notes"), &tool).await,
+            ApprovalDecision::Reject("No approval was given".to_string())
+        );
+
+        // `ask` holds even safe calls for the handler
+        let mut safe = tool.clone();
+        safe.metadata.danger = DangerLevel::Safe;
+        let mut bare = call("");
+        bare.parameters = json!({});
+        assert!(matches!(
+            manager.decide(&bare, &safe).await,
+            ApprovalDecision::Reject(_)
+        ));
+        assert_eq!(
+            manager.decide(&call("/home/me/notes"), &safe).await,
+            ApprovalDecision::Approve
+        );
+    }
+
+    #[tokio::test]
+    async fn rules_on_the_tool_name_apply_to_its_aliases() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(DeleteTool)).await;
+        registry.register_alias("remove", "delete").await;
+        let approvals = ApprovalManager::new()
+            .default_decision(ApprovalDecision::Approve)
+            .rule(ApprovalRule::new(
+                CallMatcher::tool("delete"),
+                RuleAction::Reject("deleting is disabled".to_string()),
+            ));
+        let executor = ToolExecutor::new(registry).with_approvals(approvals);
+
+        let mut aliased = call("/tmp/a");
+        aliased.name = "remove".to_string();
+        let result = executor.execute_single(&aliased).await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::PermissionDenied));
+        assert!(result.error.unwrap().contains("deleting is disabled"));
+    }
+}
@@ -0,0 +1,170 @@
+//! Asking the end user for input mid-run.
+//!
+//! `AskUserTool` lets the model ask a clarifying question. The question is
+//! delivered on a `UserQuestions` channel held by the application, and the
+//! run waits in the tool call until the question is answered, declined or
+//! times out.
+
+use super::{Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// A question from the model waiting for the user
+#[derive(Debug)]
+pub struct UserQuestion {
+    pub question: String,
+    /// Suggested answers, if the model offered any
+    pub choices: Vec<String>,
+    reply: oneshot::Sender<Option<String>>,
+}
+
+impl UserQuestion {
+    /// Send the user's answer back to the model
+    pub fn answer(self, answer: impl Into<String>) {
+        let _ = self.reply.send(Some(answer.into()));
+    }
+
+    /// Tell the model the user will not answer
+    pub fn decline(self) {
+        let _ = self.reply.send(None);
+    }
+}
+
+/// Receiving end for the questions of an `AskUserTool`
+#[derive(Debug)]
+pub struct UserQuestions {
+    receiver: mpsc::Receiver<UserQuestion>,
+}
+
+impl UserQuestions {
+    /// Next question; `None` once the tool is dropped
+    pub async fn recv(&mut self) -> Option<UserQuestion> {
+        self.receiver.recv().await
+    }
+}
+
+/// Lets the model ask the user a question and wait for the answer
+pub struct AskUserTool {
+    sender: mpsc::Sender<UserQuestion>,
+    timeout: Duration,
+}
+
+impl AskUserTool {
+    /// Create the tool and the channel its questions arrive on
+    pub fn channel() -> (Self, UserQuestions) {
+        let (sender, receiver) = mpsc::channel(8);
+        let tool = Self {
+            sender,
+            timeout: Duration::from_secs(600),
+        };
+        (tool, UserQuestions { receiver })
+    }
+
+    /// How long to wait for an answer; 10 minutes by default
+    ///
+    /// Declared as the tool's timeout, so the agent's `tool_timeout` does
+    /// not cut a question short.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for AskUserTool {
+    fn name(&self) -> &str {
+        "ask_user"
+    }
+
+    fn description(&self) -> &str {
+        "Ask the user a question and wait for the answer. Use it when the request is \
+         ambiguous or a decision needs the user, not for things you can find out yourself."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "question": {"type": "string", "description": "The question to ask"},
+                "choices": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Suggested answers, if there are obvious ones"
+                }
+            },
+            "required": ["question"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default().timeout(self.timeout)
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let Some(question) = params["question"].as_str() else {
+            return ToolError::invalid_args("Missing required parameter: question").into();
+        };
+        let choices = params["choices"]
+            .as_array()
+            .map(|choices| {
+                choices
+                    .iter()
+                    .filter_map(|c| c.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let (reply, answer) = oneshot::channel();
+        let question = UserQuestion {
+            question: question.to_string(),
+            choices,
+            reply,
+        };
+        if self.sender.send(question).await.is_err() {
+            return ToolError::not_found("No user is available to answer questions").into();
+        }
+        match answer.await {
+            Ok(Some(answer)) => ToolResult::success(answer),
+            Ok(None) => ToolResult::success("The user declined to answer."),
+            Err(_) => ToolError::not_found("The question was dropped without an answer").into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{ToolCall, ToolErrorKind, ToolExecutor, ToolRegistry};
+
+    #[tokio::test]
+    async fn waits_for_the_users_answer() {
+        let (tool, mut questions) = AskUserTool::channel();
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(tool)).await;
+        let executor = ToolExecutor::new(registry);
+
+        let user = tokio::spawn(async move {
+            let question = questions.recv().await.unwrap();
+            assert_eq!(question.question, "Which region?");
+            assert_eq!(question.choices, ["eu", "us"]);
+            question.answer("eu");
+            questions
+        });
+        let call = ToolCall {
+            id: "1".to_string(),
+            name: "ask_user".to_string(),
+            parameters: json!({"question": "Which region?", "choices": ["eu", "us"]}),
+        };
+        let result = executor
+            .execute_with_timeout(&call, Some(Duration::from_millis(1)))
+            .await;
+        assert_eq!(result.content, "eu");
+
+        // Nobody listening any more
+        drop(user.await.unwrap());
+        let result = executor.execute_single(&call).await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::NotFound));
+    }
+}
@@ -0,0 +1,320 @@
+//! Record of approval decisions for compliance review.
+//!
+//! Set an `ApprovalAuditLog` on the `ApprovalManager` and every call it
+//! settles is recorded: which call, the decision, what made it and when.
+//! Parameters are kept only as a hash. Records stay in memory for export
+//! as JSON or CSV, and are passed to any `AuditSink`s as they happen.
+
+use super::{ApprovalDecision, ToolCall};
+use crate::storage;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What settled an approval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecidedBy {
+    /// A matching `ApprovalRule`
+    Rule,
+    /// The tool's danger level is below the approval threshold
+    DangerLevel,
+    Handler,
+    /// The default decision, as there is no handler
+    Default,
+    /// The default decision, as the handler did not answer in time
+    Timeout,
+}
+
+impl DecidedBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rule => "rule",
+            Self::DangerLevel => "danger_level",
+            Self::Handler => "handler",
+            Self::Default => "default",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+/// One settled approval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    /// Unix milliseconds
+    pub timestamp: u64,
+    pub call_id: String,
+    pub tool: String,
+    /// FNV-1a hash of the parameters as JSON, in hex
+    pub parameters_hash: String,
+    pub approved: bool,
+    /// Why the call was rejected
+    pub reason: Option<String>,
+    pub decided_by: DecidedBy,
+    /// Index of the rule that decided, for `DecidedBy::Rule`
+    pub rule: Option<usize>,
+}
+
+impl ApprovalRecord {
+    pub fn new(
+        call: &ToolCall,
+        decision: &ApprovalDecision,
+        decided_by: DecidedBy,
+        rule: Option<usize>,
+    ) -> Self {
+        let (approved, reason) = match decision {
+            ApprovalDecision::Approve => (true, None),
+            ApprovalDecision::Reject(reason) => (false, Some(reason.clone())),
+        };
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            call_id: call.id.clone(),
+            tool: call.name.clone(),
+            parameters_hash: parameters_hash(&call.parameters),
+            approved,
+            reason,
+            decided_by,
+            rule,
+        }
+    }
+}
+
+/// Stable across builds and platforms, unlike `DefaultHasher`; object keys
+/// serialize sorted, so equal parameters hash equal
+fn parameters_hash(parameters: &Value) -> String {
+    let hash = parameters
+        .to_string()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// Receives approval records as they are made, e.g. to persist them
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn record(&self, record: &ApprovalRecord) -> std::io::Result<()>;
+}
+
+/// Appends records to a file as JSON lines
+#[derive(Debug, Clone)]
+pub struct JsonlAuditSink {
+    path: PathBuf,
+}
+
+impl JsonlAuditSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl AuditSink for JsonlAuditSink {
+    async fn record(&self, record: &ApprovalRecord) -> std::io::Result<()> {
+        storage::append_json_line(&self.path, record).await
+    }
+}
+
+/// Approval records kept in memory and forwarded to sinks
+///
+/// Clones share the records.
+#[derive(Clone, Default)]
+pub struct ApprovalAuditLog {
+    records: Arc<Mutex<Vec<ApprovalRecord>>>,
+    sinks: Vec<Arc<dyn AuditSink>>,
+}
+
+impl std::fmt::Debug for ApprovalAuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApprovalAuditLog")
+            .field("records", &self.records.lock().unwrap().len())
+            .field("sinks", &self.sinks.len())
+            .finish()
+    }
+}
+
+impl ApprovalAuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also pass each record to `sink`
+    pub fn with_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Keep `record` and pass it to every sink, returning the first sink
+    /// failure
+    ///
+    /// The record is kept in memory even if a sink fails.
+    pub async fn record(&self, record: ApprovalRecord) -> std::io::Result<()> {
+        let mut result = Ok(());
+        for sink in &self.sinks {
+            if let Err(error) = sink.record(&record).await {
+                result = result.and(Err(error));
+            }
+        }
+        self.records.lock().unwrap().push(record);
+        result
+    }
+
+    pub fn records(&self) -> Vec<ApprovalRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// All records as a JSON array
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&*self.records.lock().unwrap()).unwrap_or_default()
+    }
+
+    /// All records as CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "timestamp,call_id,tool,parameters_hash,approved,reason,decided_by,rule\n",
+        );
+        for record in self.records.lock().unwrap().iter() {
+            let fields = [
+                record.timestamp.to_string(),
+                csv_field(&record.call_id),
+                csv_field(&record.tool),
+                record.parameters_hash.clone(),
+                record.approved.to_string(),
+                csv_field(record.reason.as_deref().unwrap_or_default()),
+                record.decided_by.as_str().to_string(),
+                record.rule.map(|rule| rule.to_string()).unwrap_or_default(),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{
+        ApprovalManager, ApprovalRule, CallMatcher, DangerLevel, FieldTest, RuleAction, ToolInfo,
+        ToolMetadata,
+    };
+    use serde_json::json;
+
+    fn call(id: &str, parameters: Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: "shell".to_string(),
+            parameters,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_each_decision_and_persists_it() {
+        let path =
+            std::env::temp_dir().join(format!("approval-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = ApprovalAuditLog::new().with_sink(Arc::new(JsonlAuditSink::new(&path)));
+        let manager = ApprovalManager::new()
+            .audit(log.clone())
+            .rule(ApprovalRule::new(
+                CallMatcher::field("command", FieldTest::StartsWith("ls".into())),
+                RuleAction::Approve,
+            ));
+        let tool = ToolInfo {
+            name: "shell".to_string(),
+            description: String::new(),
+            parameters_schema: json!({}),
+            metadata: ToolMetadata::default().danger(DangerLevel::Destructive),
+        };
+
+        manager
+            .decide(&call("1", json!({"command": "ls -la"})), &tool)
+            .await;
+        manager
+            .decide(&call("2", json!({"command": "rm \"a, b\""})), &tool)
+            .await;
+        let mut safe = tool.clone();
+        safe.metadata.danger = DangerLevel::Safe;
+        manager
+            .decide(&call("3", json!({"command": "pwd"})), &safe)
+            .await;
+
+        let records = log.records();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].approved);
+        assert_eq!(
+            (records[0].decided_by, records[0].rule),
+            (DecidedBy::Rule, Some(0))
+        );
+        assert!(!records[1].approved);
+        assert_eq!(records[1].decided_by, DecidedBy::Default);
+        assert_eq!(records[2].decided_by, DecidedBy::DangerLevel);
+
+        // Equal parameters hash equal whatever their key order
+        assert_eq!(
+            parameters_hash(&json!({"a": 1, "b": 2})),
+            parameters_hash(&serde_json::from_str(r#"{"b":2,"a":1}"#).unwrap())
+        );
+        assert_ne!(records[0].parameters_hash, records[1].parameters_hash);
+
+        let csv = log.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with(&format!("{},2,shell,", records[1].timestamp)));
+        assert!(lines[2].ends_with(",false,No approval was given,default,"));
+        let exported: Vec<ApprovalRecord> = serde_json::from_str(&log.to_json()).unwrap();
+        assert_eq!(exported, records);
+
+        let persisted: Vec<ApprovalRecord> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(persisted, records);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn sink_failures_are_broadcast() {
+        // A directory cannot be opened for appending
+        let log =
+            ApprovalAuditLog::new().with_sink(Arc::new(JsonlAuditSink::new(std::env::temp_dir())));
+        let manager = ApprovalManager::new().audit(log.clone());
+        let mut failures = manager.subscribe_audit_failures();
+        let tool = ToolInfo {
+            name: "shell".to_string(),
+            description: String::new(),
+            parameters_schema: json!({}),
+            metadata: ToolMetadata::default(),
+        };
+
+        manager.decide(&call("1", json!({})), &tool).await;
+        let failure = failures.try_recv().unwrap();
+        assert_eq!(
+            (failure.call_id.as_str(), failure.tool.as_str()),
+            ("1", "shell")
+        );
+        assert_eq!(log.records().len(), 1);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,261 @@
+//! Caching results of idempotent tools.
+//!
+//! A model often repeats a search or re-reads a file within one run. A
+//! `ToolResultCache` set on the executor answers repeated calls to the tools
+//! it was told about from memory. Calls are keyed by tool name and
+//! parameters, with object keys sorted so `{"a":1,"b":2}` and
+//! `{"b":2,"a":1}` hit the same entry. Only successful results are stored.
+
+use super::{ToolCall, ToolResult};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Parameters as JSON with object keys sorted
+fn canonical_parameters(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}:{}",
+                        Value::String(key.clone()),
+                        canonical_parameters(value)
+                    )
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_parameters).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+struct CachedResult {
+    result: ToolResult,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// TTL of each cached tool
+    ttls: HashMap<String, Duration>,
+    /// Results by (tool, canonical parameters)
+    entries: HashMap<(String, String), CachedResult>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Hit and miss counts of a `ToolResultCache`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries stored, including expired ones not yet evicted
+    pub entries: usize,
+}
+
+/// Results of opted-in tools, reused until their TTL runs out
+///
+/// Clones share entries, so the agent's cache can be invalidated from
+/// elsewhere, e.g. after a tool that writes files ran.
+#[derive(Clone, Default)]
+pub struct ToolResultCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl std::fmt::Debug for ToolResultCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("ToolResultCache")
+            .field("ttls", &state.ttls)
+            .field("entries", &state.entries.len())
+            .finish()
+    }
+}
+
+impl ToolResultCache {
+    /// A cache that stores nothing until tools are added with `cache_tool`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache results of `tool` for `ttl`
+    pub fn cache_tool(self, tool: impl Into<String>, ttl: Duration) -> Self {
+        self.set_ttl(tool, Some(ttl));
+        self
+    }
+
+    /// Start or, with `None`, stop caching `tool`; stopping drops its entries
+    pub fn set_ttl(&self, tool: impl Into<String>, ttl: Option<Duration>) {
+        let tool = tool.into();
+        let mut state = self.state.lock().unwrap();
+        match ttl {
+            Some(ttl) => {
+                state.ttls.insert(tool, ttl);
+            }
+            None => {
+                state.ttls.remove(&tool);
+                state.entries.retain(|(name, _), _| *name != tool);
+            }
+        }
+    }
+
+    /// Whether results of `tool` are cached
+    pub fn caches(&self, tool: &str) -> bool {
+        self.state.lock().unwrap().ttls.contains_key(tool)
+    }
+
+    /// The stored result of an identical earlier call, if it has not expired
+    ///
+    /// Counts a hit or a miss for cached tools; other tools are ignored.
+    pub fn get(&self, call: &ToolCall) -> Option<ToolResult> {
+        let mut state = self.state.lock().unwrap();
+        if !state.ttls.contains_key(&call.name) {
+            return None;
+        }
+        let key = (call.name.clone(), canonical_parameters(&call.parameters));
+        let result = match state.entries.get(&key) {
+            Some(cached) if cached.expires_at > Instant::now() => Some(cached.result.clone()),
+            Some(_) => {
+                state.entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+        match result {
+            Some(_) => state.hits += 1,
+            None => state.misses += 1,
+        }
+        result
+    }
+
+    /// Store `result` of `call` if its tool is cached and the call succeeded
+    pub fn insert(&self, call: &ToolCall, result: &ToolResult) {
+        let mut state = self.state.lock().unwrap();
+        let Some(&ttl) = state.ttls.get(&call.name) else {
+            return;
+        };
+        if !result.success {
+            return;
+        }
+        state.entries.insert(
+            (call.name.clone(), canonical_parameters(&call.parameters)),
+            CachedResult {
+                result: result.clone(),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /// Drop the result of one call; true if there was one
+    pub fn invalidate(&self, tool: &str, parameters: &Value) -> bool {
+        let key = (tool.to_string(), canonical_parameters(parameters));
+        self.state.lock().unwrap().entries.remove(&key).is_some()
+    }
+
+    /// Drop every result of `tool`, returning how many there were
+    pub fn invalidate_tool(&self, tool: &str) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let before = state.entries.len();
+        state.entries.retain(|(name, _), _| name != tool);
+        before - state.entries.len()
+    }
+
+    /// Drop every stored result; tools stay cached
+    pub fn clear(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    pub fn stats(&self) -> ToolCacheStats {
+        let state = self.state.lock().unwrap();
+        ToolCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.entries.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, parameters: Value) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            parameters,
+        }
+    }
+
+    #[test]
+    fn parameters_match_regardless_of_key_order() {
+        let cache = ToolResultCache::new().cache_tool("search", Duration::from_secs(60));
+        let first = call(
+            "search",
+            json!({"query": "rust", "filters": {"lang": "en", "max": 3}}),
+        );
+        cache.insert(&first, &ToolResult::success("results"));
+
+        let reordered = call(
+            "search",
+            json!({"filters": {"max": 3, "lang": "en"}, "query": "rust"}),
+        );
+        assert_eq!(cache.get(&reordered).unwrap().content, "results");
+        assert!(cache.get(&call("search", json!({"query": "go"}))).is_none());
+        assert_eq!(
+            cache.stats(),
+            ToolCacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 1
+            }
+        );
+    }
+
+    #[test]
+    fn only_successful_results_of_cached_tools_are_stored() {
+        let cache = ToolResultCache::new().cache_tool("search", Duration::from_secs(60));
+        cache.insert(&call("search", json!({})), &ToolResult::error("503"));
+        cache.insert(&call("write", json!({})), &ToolResult::success("ok"));
+
+        assert!(cache.get(&call("search", json!({}))).is_none());
+        assert!(cache.get(&call("write", json!({}))).is_none());
+        assert_eq!(cache.stats().entries, 0);
+        // Calls to tools that are not cached are not misses
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn entries_expire_and_can_be_invalidated() {
+        let cache = ToolResultCache::new()
+            .cache_tool("read", Duration::from_secs(60))
+            .cache_tool("stale", Duration::ZERO);
+        let a = call("read", json!({"path": "a"}));
+        let b = call("read", json!({"path": "b"}));
+        cache.insert(&a, &ToolResult::success("A"));
+        cache.insert(&b, &ToolResult::success("B"));
+        cache.insert(&call("stale", json!({})), &ToolResult::success("old"));
+
+        assert!(cache.get(&call("stale", json!({}))).is_none());
+        assert!(cache.invalidate("read", &json!({"path": "a"})));
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&b).is_some());
+
+        cache.insert(&a, &ToolResult::success("A"));
+        assert_eq!(cache.invalidate_tool("read"), 2);
+        cache.insert(&a, &ToolResult::success("A"));
+        cache.set_ttl("read", None);
+        assert!(!cache.caches("read"));
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
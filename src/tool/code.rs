@@ -0,0 +1,263 @@
+//! Sandboxed script evaluation.
+//!
+//! `CodeTool` runs small [Rhai](https://rhai.rs) scripts in an embedded
+//! interpreter, so the model can compute without a shell. Scripts have no
+//! file, network or module access, and each call gets a fresh engine with
+//! limits on operations, data size, printed output, call depth and
+//! wall-clock time.
+
+use super::{Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use rhai::{Dynamic, Engine, EvalAltResult};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Resource limits for one script run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeLimits {
+    /// Interpreter operations, a proxy for CPU time
+    pub max_operations: u64,
+    /// Longest string, and most printed output, in bytes
+    pub max_string_size: usize,
+    /// Most elements in an array or entries in a map
+    pub max_collection_size: usize,
+    pub max_call_depth: usize,
+    pub timeout: Duration,
+}
+
+impl Default for CodeLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 1_000_000,
+            max_string_size: 64 * 1024,
+            max_collection_size: 10_000,
+            max_call_depth: 32,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Progress token that stops a script whose output is full
+const OUTPUT_FULL: &str = "output full";
+
+/// Printed lines, capped at `limit` bytes
+#[derive(Debug, Default)]
+struct PrintBuffer {
+    text: String,
+    limit: usize,
+    /// Set once a print did not fit, to stop the script
+    full: bool,
+}
+
+impl PrintBuffer {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    fn print(&mut self, line: &str) {
+        let separator = usize::from(!self.text.is_empty());
+        let room = self.limit.saturating_sub(self.text.len() + separator);
+        if line.len() > room {
+            self.full = true;
+        }
+        if room == 0 {
+            return;
+        }
+        let mut end = line.len().min(room);
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        if separator == 1 {
+            self.text.push('\n');
+        }
+        self.text.push_str(&line[..end]);
+    }
+}
+
+/// Evaluates Rhai scripts for the model
+#[derive(Debug, Clone, Default)]
+pub struct CodeTool {
+    limits: CodeLimits,
+}
+
+impl CodeTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn limits(mut self, limits: CodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn engine(&self, output: Arc<Mutex<PrintBuffer>>) -> Engine {
+        let limits = self.limits;
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(limits.max_operations)
+            .set_max_string_size(limits.max_string_size)
+            .set_max_array_size(limits.max_collection_size)
+            .set_max_map_size(limits.max_collection_size)
+            .set_max_call_levels(limits.max_call_depth)
+            .set_max_modules(0);
+
+        let started = Instant::now();
+        let progress_output = output.clone();
+        engine.on_progress(move |_| {
+            if progress_output.lock().unwrap().full {
+                return Some(Dynamic::from(OUTPUT_FULL));
+            }
+            (started.elapsed() > limits.timeout).then(|| Dynamic::from("timeout"))
+        });
+        engine.on_print(move |text| output.lock().unwrap().print(text));
+        engine.on_debug(|_, _, _| {});
+        engine
+    }
+
+    /// Run `script`, returning the printed lines and the final value
+    fn run(&self, script: &str) -> Result<String, ToolError> {
+        let output = Arc::new(Mutex::new(PrintBuffer::new(self.limits.max_string_size)));
+        let engine = self.engine(output.clone());
+        let value = engine
+            .eval::<Dynamic>(script)
+            .map_err(|e| self.classify(*e))?;
+
+        let printed = std::mem::take(&mut output.lock().unwrap().text);
+        let mut text = String::new();
+        if !printed.is_empty() {
+            text.push_str(&format!("output:\n{}\n", printed));
+        }
+        if !value.is_unit() {
+            text.push_str(&format!("result: {}", value));
+        }
+        if text.is_empty() {
+            text.push_str("(no output)");
+        }
+        Ok(text)
+    }
+
+    fn classify(&self, error: EvalAltResult) -> ToolError {
+        match error {
+            EvalAltResult::ErrorTerminated(token, _) if token.to_string() == OUTPUT_FULL => {
+                ToolError::invalid_args(format!(
+                    "Script printed more than {} bytes",
+                    self.limits.max_string_size
+                ))
+            }
+            EvalAltResult::ErrorTerminated(..) => {
+                ToolError::timeout(format!("Script timed out after {:?}", self.limits.timeout))
+                    .with_retryable(false)
+            }
+            EvalAltResult::ErrorTooManyOperations(_) => ToolError::timeout(format!(
+                "Script exceeded {} operations",
+                self.limits.max_operations
+            ))
+            .with_retryable(false),
+            EvalAltResult::ErrorParsing(..) => {
+                ToolError::invalid_args(format!("Syntax error: {}", error))
+            }
+            _ => ToolError::invalid_args(format!("Script failed: {}", error)),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for CodeTool {
+    fn name(&self) -> &str {
+        "run_code"
+    }
+
+    fn description(&self) -> &str {
+        "Evaluate a Rhai script and return what it printed and its final value. \
+         Rhai is a small Rust-like language; there is no file or network access."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "code": {"type": "string", "description": "Rhai script"}
+            },
+            "required": ["code"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+            .example(
+                "Sum the squares of 1 to 10",
+                serde_json::json!({"code": "let sum = 0; for i in 1..=10 { sum += i * i; } sum"}),
+            )
+            .timeout(self.limits.timeout + Duration::from_secs(1))
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let script = params["code"].as_str().unwrap_or_default().to_string();
+        let tool = self.clone();
+        match tokio::task::spawn_blocking(move || tool.run(&script)).await {
+            Ok(Ok(text)) => ToolResult::success(text),
+            Ok(Err(e)) => e.into(),
+            Err(e) => ToolError::upstream(format!("Script runner failed: {}", e)).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::ToolErrorKind;
+
+    async fn run(tool: &CodeTool, code: &str) -> ToolResult {
+        tool.execute(&serde_json::json!({ "code": code })).await
+    }
+
+    #[tokio::test]
+    async fn returns_printed_output_and_value() {
+        let tool = CodeTool::new();
+
+        let result = run(&tool, "print(\"hi\"); let x = 6; x * 7").await;
+        assert_eq!(result.content, "output:\nhi\nresult: 42");
+        assert_eq!(run(&tool, "let x = 1;").await.content, "(no output)");
+
+        let result = run(&tool, "let x = ;").await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::InvalidArgs));
+        assert!(!result.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn enforces_limits() {
+        let tool = CodeTool::new().limits(CodeLimits {
+            max_operations: 10_000,
+            ..Default::default()
+        });
+        let result = run(&tool, "loop {}").await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::Timeout));
+
+        let tool = CodeTool::new().limits(CodeLimits {
+            max_operations: 0,
+            timeout: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let result = run(&tool, "loop {}").await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::Timeout));
+        assert!(result.error.unwrap().contains("timed out"));
+
+        let result = run(&CodeTool::new(), "let s = \"x\"; loop { s += s; }").await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::InvalidArgs));
+
+        let script = "let s = \"x\"; for i in 0..16 { s += s; } loop { print(s); }";
+        let result = run(&CodeTool::new(), script).await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::InvalidArgs));
+        assert!(result
+            .error
+            .unwrap()
+            .contains("printed more than 65536 bytes"));
+
+        let result = run(&CodeTool::new(), "import \"std\" as s;").await;
+        assert!(!result.success);
+    }
+}
@@ -0,0 +1,189 @@
+//! Repair of near-miss tool arguments before validation.
+//!
+//! Models often send `"5"` for a number, `"True"` for a boolean or `"Celsius"`
+//! for the enum value `"celsius"`, and leave out optional fields. Coercion
+//! fixes these against the tool's JSON schema so the call does not fail.
+
+use serde_json::{Map, Value};
+
+/// Coerce `params` toward `schema` in place
+///
+/// Converts strings to numbers and booleans (and scalars to strings), matches
+/// enum strings case-insensitively and fills in missing properties that have
+/// a schema `default`. Returns one description per change.
+pub fn coerce_arguments(params: &mut Value, schema: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    if params.is_null() && schema.get("type").and_then(Value::as_str) == Some("object") {
+        *params = Value::Object(Map::new());
+        changes.push("replaced null arguments with an empty object".to_string());
+    }
+    coerce_value(params, schema, "", &mut changes);
+    changes
+}
+
+fn coerce_value(value: &mut Value, schema: &Value, path: &str, changes: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    let name = if path.is_empty() { "arguments" } else { path };
+
+    match schema.get("type").and_then(Value::as_str) {
+        Some("number") | Some("integer") => {
+            let integer = schema.get("type").and_then(Value::as_str) == Some("integer");
+            if let Some(number) = value.as_str().and_then(|s| parse_number(s.trim(), integer)) {
+                changes.push(format!("{}: string {} to number", name, value));
+                *value = number;
+            }
+        }
+        Some("boolean") => {
+            let parsed =
+                value
+                    .as_str()
+                    .and_then(|s| match s.trim().to_ascii_lowercase().as_str() {
+                        "true" | "yes" | "1" => Some(true),
+                        "false" | "no" | "0" => Some(false),
+                        _ => None,
+                    });
+            if let Some(parsed) = parsed {
+                changes.push(format!("{}: string {} to boolean", name, value));
+                *value = Value::Bool(parsed);
+            }
+        }
+        Some("string") if value.is_number() || value.is_boolean() => {
+            changes.push(format!("{}: {} to string", name, value));
+            *value = Value::String(value.to_string());
+        }
+        Some("object") => {
+            if let Some(object) = value.as_object_mut() {
+                coerce_object(object, schema, path, changes);
+            }
+        }
+        Some("array") => {
+            if let (Some(items), Some(array)) = (schema.get("items"), value.as_array_mut()) {
+                for (i, item) in array.iter_mut().enumerate() {
+                    coerce_value(item, items, &format!("{}[{}]", name, i), changes);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let (Some(options), Some(text)) =
+        (schema.get("enum").and_then(Value::as_array), value.as_str())
+    {
+        if !options.contains(value) {
+            let matched = options
+                .iter()
+                .find(|option| {
+                    option
+                        .as_str()
+                        .is_some_and(|option| option.eq_ignore_ascii_case(text.trim()))
+                })
+                .cloned();
+            if let Some(matched) = matched {
+                changes.push(format!("{}: {} to enum value {}", name, value, matched));
+                *value = matched;
+            }
+        }
+    }
+}
+
+fn coerce_object(
+    object: &mut Map<String, Value>,
+    schema: &Map<String, Value>,
+    path: &str,
+    changes: &mut Vec<String>,
+) {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return;
+    };
+    for (key, property) in properties {
+        let field = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match object.get_mut(key) {
+            Some(value) => coerce_value(value, property, &field, changes),
+            None => {
+                if let Some(default) = property.get("default") {
+                    changes.push(format!("{}: filled default {}", field, default));
+                    object.insert(key.clone(), default.clone());
+                }
+            }
+        }
+    }
+}
+
+fn parse_number(text: &str, integer: bool) -> Option<Value> {
+    if let Ok(n) = text.parse::<i64>() {
+        return Some(Value::from(n));
+    }
+    let n = text.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    if integer {
+        // "3.0" is an integer; "3.5" is left for validation to reject
+        (n.fract() == 0.0).then(|| Value::from(n as i64))
+    } else {
+        serde_json::Number::from_f64(n).map(Value::Number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fixes_near_miss_arguments() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "count": {"type": "integer"},
+                "ratio": {"type": "number"},
+                "verbose": {"type": "boolean"},
+                "unit": {"type": "string", "enum": ["celsius", "fahrenheit"]},
+                "label": {"type": "string"},
+                "limit": {"type": "integer", "default": 10},
+                "tags": {"type": "array", "items": {"type": "number"}}
+            }
+        });
+        let mut params = json!({
+            "count": "5",
+            "ratio": " 0.5 ",
+            "verbose": "True",
+            "unit": "Celsius",
+            "label": 42,
+            "tags": ["1", 2]
+        });
+
+        let changes = coerce_arguments(&mut params, &schema);
+        assert_eq!(
+            params,
+            json!({
+                "count": 5,
+                "ratio": 0.5,
+                "verbose": true,
+                "unit": "celsius",
+                "label": "42",
+                "limit": 10,
+                "tags": [1, 2]
+            })
+        );
+        assert_eq!(changes.len(), 7);
+    }
+
+    #[test]
+    fn leaves_unrepairable_values_for_validation() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "count": {"type": "integer"},
+                "unit": {"type": "string", "enum": ["celsius"]}
+            }
+        });
+        let mut params = json!({"count": "3.5", "unit": "kelvin"});
+
+        assert!(coerce_arguments(&mut params, &schema).is_empty());
+        assert_eq!(params, json!({"count": "3.5", "unit": "kelvin"}));
+    }
+}
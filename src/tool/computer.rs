@@ -0,0 +1,345 @@
+//! Computer use.
+//!
+//! `ComputerTool` declares Anthropic's versioned `computer` tool, whose
+//! actions (screenshot, click, type, ...) are parsed into `ComputerAction`s
+//! and carried out by a `ComputerEnvironment` you implement, e.g. over VNC
+//! or a headless browser. Anthropic requests get the matching beta header
+//! automatically; other providers see an ordinary tool with the same
+//! parameters.
+
+use super::{DangerLevel, Tool, ToolError, ToolMetadata, ToolResult};
+use crate::provider::ImageSource;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Version of Anthropic's computer-use tool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ComputerUseVersion {
+    /// `computer_20241022`, for Claude 3.5 Sonnet
+    V20241022,
+    /// `computer_20250124`, adding scroll, wait and more click actions
+    #[default]
+    V20250124,
+}
+
+impl ComputerUseVersion {
+    pub fn tool_type(&self) -> &'static str {
+        match self {
+            Self::V20241022 => "computer_20241022",
+            Self::V20250124 => "computer_20250124",
+        }
+    }
+}
+
+/// Screen the model sees, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayConfig {
+    pub width: u32,
+    pub height: u32,
+    /// X11 display number, if there is more than one
+    pub number: Option<u32>,
+}
+
+impl DisplayConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            number: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Action requested by the model; coordinates are `[x, y]` pixels
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ComputerAction {
+    Screenshot,
+    CursorPosition,
+    MouseMove {
+        coordinate: [i64; 2],
+    },
+    /// Click at `coordinate`, or where the cursor is, holding the modifier
+    /// keys in `text` (e.g. "shift")
+    LeftClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<[i64; 2]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    RightClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<[i64; 2]>,
+    },
+    MiddleClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<[i64; 2]>,
+    },
+    DoubleClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<[i64; 2]>,
+    },
+    TripleClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<[i64; 2]>,
+    },
+    LeftClickDrag {
+        start_coordinate: [i64; 2],
+        coordinate: [i64; 2],
+    },
+    /// Press a key or combination in xdotool syntax, e.g. "ctrl+s"
+    Key {
+        text: String,
+    },
+    Type {
+        text: String,
+    },
+    Scroll {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<[i64; 2]>,
+        scroll_direction: ScrollDirection,
+        scroll_amount: u32,
+    },
+    /// Pause for `duration` seconds
+    Wait {
+        duration: f64,
+    },
+}
+
+/// What an action produced
+#[derive(Debug, Clone, Default)]
+pub struct ComputerOutput {
+    /// Text for the model, e.g. the cursor position
+    pub text: Option<String>,
+    pub screenshot: Option<ImageSource>,
+}
+
+impl ComputerOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: Some(text.into()),
+            screenshot: None,
+        }
+    }
+
+    /// Base64-encoded PNG screenshot
+    pub fn screenshot(png_base64: impl Into<String>) -> Self {
+        Self {
+            text: None,
+            screenshot: Some(ImageSource::Base64 {
+                media_type: "image/png".to_string(),
+                data: png_base64.into(),
+            }),
+        }
+    }
+}
+
+/// Machine that carries out computer-use actions
+#[async_trait]
+pub trait ComputerEnvironment: Send + Sync {
+    fn display(&self) -> DisplayConfig;
+
+    async fn execute(&self, action: &ComputerAction) -> Result<ComputerOutput, ToolError>;
+}
+
+/// Lets the model see and control a computer
+pub struct ComputerTool {
+    environment: Arc<dyn ComputerEnvironment>,
+    version: ComputerUseVersion,
+    screenshot_after_action: bool,
+}
+
+impl ComputerTool {
+    pub fn new(environment: Arc<dyn ComputerEnvironment>) -> Self {
+        Self {
+            environment,
+            version: ComputerUseVersion::default(),
+            screenshot_after_action: true,
+        }
+    }
+
+    pub fn version(mut self, version: ComputerUseVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Take a screenshot after actions that return none, so the model sees
+    /// their effect; on by default
+    pub fn screenshot_after_action(mut self, enabled: bool) -> Self {
+        self.screenshot_after_action = enabled;
+        self
+    }
+
+    /// Definition Anthropic expects for this version and display
+    fn native_definition(&self) -> Value {
+        let display = self.environment.display();
+        let mut definition = serde_json::json!({
+            "type": self.version.tool_type(),
+            "name": "computer",
+            "display_width_px": display.width,
+            "display_height_px": display.height,
+        });
+        if let Some(number) = display.number {
+            definition["display_number"] = serde_json::json!(number);
+        }
+        definition
+    }
+}
+
+#[async_trait]
+impl Tool for ComputerTool {
+    fn name(&self) -> &str {
+        "computer"
+    }
+
+    fn description(&self) -> &str {
+        "Use the mouse and keyboard to interact with a computer and take screenshots"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": [
+                        "screenshot", "cursor_position", "mouse_move", "left_click",
+                        "right_click", "middle_click", "double_click", "triple_click",
+                        "left_click_drag", "key", "type", "scroll", "wait"
+                    ]
+                },
+                "coordinate": {"type": "array", "description": "[x, y] in pixels"},
+                "start_coordinate": {"type": "array", "description": "[x, y] where a drag starts"},
+                "text": {"type": "string", "description": "Text to type, or keys such as ctrl+s"},
+                "scroll_direction": {"type": "string", "enum": ["up", "down", "left", "right"]},
+                "scroll_amount": {"type": "number"},
+                "duration": {"type": "number", "description": "Seconds to wait"}
+            },
+            "required": ["action"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+            .danger(DangerLevel::Destructive)
+            .native(self.native_definition())
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let action: ComputerAction = match serde_json::from_value(params.clone()) {
+            Ok(action) => action,
+            Err(e) => return ToolError::invalid_args(format!("Invalid action: {}", e)).into(),
+        };
+        let mut output = match self.environment.execute(&action).await {
+            Ok(output) => output,
+            Err(e) => return e.into(),
+        };
+        if output.screenshot.is_none()
+            && self.screenshot_after_action
+            && action != ComputerAction::CursorPosition
+        {
+            match self.environment.execute(&ComputerAction::Screenshot).await {
+                Ok(shot) => output.screenshot = shot.screenshot,
+                Err(e) => return e.into(),
+            }
+        }
+
+        let mut result = ToolResult::success(output.text.unwrap_or_default());
+        if let Some(screenshot) = output.screenshot {
+            result = result.with_image(screenshot);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct FakeScreen {
+        actions: Mutex<Vec<ComputerAction>>,
+    }
+
+    #[async_trait]
+    impl ComputerEnvironment for FakeScreen {
+        fn display(&self) -> DisplayConfig {
+            DisplayConfig::new(1280, 800)
+        }
+
+        async fn execute(&self, action: &ComputerAction) -> Result<ComputerOutput, ToolError> {
+            self.actions.lock().unwrap().push(action.clone());
+            Ok(match action {
+                ComputerAction::Screenshot => ComputerOutput::screenshot("iVBORw0KGgo="),
+                ComputerAction::CursorPosition => ComputerOutput::text("X=10,Y=20"),
+                _ => ComputerOutput::default(),
+            })
+        }
+    }
+
+    #[test]
+    fn declares_versioned_native_tool() {
+        let screen = Arc::new(FakeScreen {
+            actions: Mutex::new(Vec::new()),
+        });
+        let tool = ComputerTool::new(screen).version(ComputerUseVersion::V20241022);
+
+        assert_eq!(
+            tool.metadata().native,
+            Some(serde_json::json!({
+                "type": "computer_20241022",
+                "name": "computer",
+                "display_width_px": 1280,
+                "display_height_px": 800
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_actions_and_returns_screenshots() {
+        let screen = Arc::new(FakeScreen {
+            actions: Mutex::new(Vec::new()),
+        });
+        let tool = ComputerTool::new(screen.clone());
+
+        let result = tool
+            .execute(&serde_json::json!({"action": "left_click", "coordinate": [100, 200]}))
+            .await;
+        assert!(result.success);
+        assert_eq!(result.images.len(), 1);
+        assert_eq!(
+            screen.actions.lock().unwrap().as_slice(),
+            [
+                ComputerAction::LeftClick {
+                    coordinate: Some([100, 200]),
+                    text: None
+                },
+                ComputerAction::Screenshot
+            ]
+        );
+
+        let result = tool
+            .execute(&serde_json::json!({"action": "cursor_position"}))
+            .await;
+        assert_eq!(result.content, "X=10,Y=20");
+        assert!(result.images.is_empty());
+
+        let result = tool
+            .execute(&serde_json::json!({"action": "scroll", "scroll_amount": 3}))
+            .await;
+        assert_eq!(
+            result.error_kind(),
+            Some(crate::tool::ToolErrorKind::InvalidArgs)
+        );
+    }
+}
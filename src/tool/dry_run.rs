@@ -0,0 +1,168 @@
+//! Previewing tool calls without running them.
+//!
+//! With a `DryRun` set on the executor, valid calls are recorded instead of
+//! executed and answered with a synthetic result, or with a stub registered
+//! for the tool. Clones share the record, so keep one to inspect what the
+//! agent tried to do.
+
+use super::{ToolCall, ToolResult};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type ToolStub = Arc<dyn Fn(&ToolCall) -> ToolResult + Send + Sync>;
+
+/// Records tool calls in place of executing them
+#[derive(Clone, Default)]
+pub struct DryRun {
+    stubs: HashMap<String, ToolStub>,
+    calls: Arc<Mutex<Vec<ToolCall>>>,
+}
+
+impl std::fmt::Debug for DryRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DryRun")
+            .field("stubs", &self.stubs.keys().collect::<Vec<_>>())
+            .field("calls", &self.calls.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl DryRun {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer calls to `tool` with `stub` instead of the default result
+    pub fn stub(
+        mut self,
+        tool: impl Into<String>,
+        stub: impl Fn(&ToolCall) -> ToolResult + Send + Sync + 'static,
+    ) -> Self {
+        self.stubs.insert(tool.into(), Arc::new(stub));
+        self
+    }
+
+    /// Answer every call to `tool` with `result`
+    pub fn stub_result(self, tool: impl Into<String>, result: ToolResult) -> Self {
+        self.stub(tool, move |_| result.clone())
+    }
+
+    /// Record `call` and return the result the model sees
+    pub fn intercept(&self, call: &ToolCall) -> ToolResult {
+        self.calls.lock().unwrap().push(call.clone());
+        match self.stubs.get(&call.name) {
+            Some(stub) => stub(call),
+            None => ToolResult::success(format!(
+                "[dry run] '{}' was not executed; assume it succeeded",
+                call.name
+            )),
+        }
+    }
+
+    /// Calls intercepted so far, in order
+    pub fn calls(&self) -> Vec<ToolCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Forget the recorded calls, keeping the stubs
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{Tool, ToolErrorKind, ToolExecutor, ToolRegistry};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct DeleteFile {
+        executed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Tool for DeleteFile {
+        fn name(&self) -> &str {
+            "delete_file"
+        }
+
+        fn description(&self) -> &str {
+            "Deletes a file"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({
+                "type": "object",
+                "properties": {"path": {"type": "string"}},
+                "required": ["path"]
+            })
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            self.executed.store(true, Ordering::SeqCst);
+            ToolResult::success("deleted")
+        }
+    }
+
+    fn call(name: &str, parameters: Value) -> ToolCall {
+        ToolCall {
+            id: "1".to_string(),
+            name: name.to_string(),
+            parameters,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_valid_calls_without_executing_them() {
+        let executed = Arc::new(AtomicBool::new(false));
+        let registry = ToolRegistry::new();
+        registry
+            .register(Box::new(DeleteFile {
+                executed: executed.clone(),
+            }))
+            .await;
+        let dry_run = DryRun::new();
+        let executor = ToolExecutor::new(registry).with_dry_run(dry_run.clone());
+
+        let result = executor
+            .execute_single(&call("delete_file", json!({"path": "a.txt"})))
+            .await;
+        assert!(result.success);
+        assert!(result.content.starts_with("[dry run]"));
+
+        let invalid = executor
+            .execute_single(&call("delete_file", json!({})))
+            .await;
+        assert_eq!(invalid.error_kind(), Some(ToolErrorKind::InvalidArgs));
+        let missing = executor
+            .execute_single(&call("format_disk", json!({})))
+            .await;
+        assert_eq!(missing.error_kind(), Some(ToolErrorKind::NotFound));
+
+        assert!(!executed.load(Ordering::SeqCst));
+        let calls = dry_run.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].parameters["path"], "a.txt");
+    }
+
+    #[tokio::test]
+    async fn stubs_answer_calls_to_their_tool() {
+        let registry = ToolRegistry::new();
+        registry
+            .register(Box::new(DeleteFile {
+                executed: Arc::new(AtomicBool::new(false)),
+            }))
+            .await;
+        let dry_run = DryRun::new().stub("delete_file", |call| {
+            ToolResult::success(format!("would delete {}", call.parameters["path"]))
+        });
+        let executor = ToolExecutor::new(registry).with_dry_run(dry_run);
+
+        let result = executor
+            .execute_single(&call("delete_file", json!({"path": "a.txt"})))
+            .await;
+        assert_eq!(result.content, "would delete \"a.txt\"");
+    }
+}
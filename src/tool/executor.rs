@@ -1,12 +1,82 @@
-use super::{ToolCall, ToolRegistry, ToolResult};
+use super::{
+    ApprovalDecision, ApprovalManager, DryRun, ToolCall, ToolError, ToolOutputPolicy, ToolRegistry,
+    ToolResult, ToolResultCache,
+};
+use std::time::Duration;
 
 pub struct ToolExecutor {
     registry: ToolRegistry,
+    output_policy: Option<ToolOutputPolicy>,
+    cache: Option<ToolResultCache>,
+    dry_run: Option<DryRun>,
+    approvals: Option<ApprovalManager>,
 }
 
 impl ToolExecutor {
     pub fn new(registry: ToolRegistry) -> Self {
-        Self { registry }
+        Self {
+            registry,
+            output_policy: None,
+            cache: None,
+            dry_run: None,
+            approvals: None,
+        }
+    }
+
+    /// Record calls in `dry_run` instead of executing them
+    pub fn with_dry_run(mut self, dry_run: DryRun) -> Self {
+        self.dry_run = Some(dry_run);
+        self
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: Option<DryRun>) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn dry_run(&self) -> Option<&DryRun> {
+        self.dry_run.as_ref()
+    }
+
+    /// Hold calls to risky tools until `approvals` allows them
+    pub fn with_approvals(mut self, approvals: ApprovalManager) -> Self {
+        self.approvals = Some(approvals);
+        self
+    }
+
+    pub fn set_approvals(&mut self, approvals: Option<ApprovalManager>) {
+        self.approvals = approvals;
+    }
+
+    pub fn approvals(&self) -> Option<&ApprovalManager> {
+        self.approvals.as_ref()
+    }
+
+    /// Truncate tool results that exceed `policy` before returning them
+    pub fn with_output_policy(mut self, policy: ToolOutputPolicy) -> Self {
+        self.output_policy = Some(policy);
+        self
+    }
+
+    pub fn set_output_policy(&mut self, policy: Option<ToolOutputPolicy>) {
+        self.output_policy = policy;
+    }
+
+    pub fn output_policy(&self) -> Option<&ToolOutputPolicy> {
+        self.output_policy.as_ref()
+    }
+
+    /// Answer repeated calls to the tools `cache` covers from memory
+    pub fn with_cache(mut self, cache: ToolResultCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn set_cache(&mut self, cache: Option<ToolResultCache>) {
+        self.cache = cache;
+    }
+
+    pub fn cache(&self) -> Option<&ToolResultCache> {
+        self.cache.as_ref()
     }
 
     pub async fn execute_calls(&self, calls: Vec<ToolCall>) -> Vec<ToolResult> {
@@ -17,9 +87,101 @@ impl ToolExecutor {
         results
     }
 
+    /// Execute a call, bounded only by the tool's own metadata timeout
     pub async fn execute_single(&self, call: &ToolCall) -> ToolResult {
-        self.registry
-            .execute_tool(&call.name, &call.parameters)
+        self.execute_with_timeout(call, None).await
+    }
+
+    /// Execute a call, giving up after the tool's metadata timeout or, if it
+    /// declares none, `default_timeout`
+    ///
+    /// A timed-out call returns a `Timeout` error whose details carry
+    /// `timeout_ms`. Failures are retried under the registry's retry policy
+    /// for the tool, each attempt with its own timeout. Results are cut down
+    /// to the output policy, if any, and cached results are returned without
+    /// running the tool.
+    ///
+    /// In a dry run the call is only checked and, if valid, handed to the
+    /// `DryRun`. Otherwise a call rejected by the approval manager returns a
+    /// `PermissionDenied` error without running.
+    pub async fn execute_with_timeout(
+        &self,
+        call: &ToolCall,
+        default_timeout: Option<Duration>,
+    ) -> ToolResult {
+        if let Some(dry_run) = &self.dry_run {
+            return match self.registry.check_call(&call.name, &call.parameters).await {
+                Ok(()) => dry_run.intercept(call),
+                Err(error) => error.into(),
+            };
+        }
+        if let Err(error) = self.approve(call).await {
+            return error.into();
+        }
+        if let Some(result) = self.cache.as_ref().and_then(|cache| cache.get(call)) {
+            return result;
+        }
+        let mut result = self.execute_bounded(call, default_timeout).await;
+        if let Some(retry) = self.registry.retry_config_for(&call.name).await {
+            let mut attempts = 1;
+            while retry.should_retry(&result, attempts) {
+                tokio::time::sleep(retry.backoff(attempts)).await;
+                attempts += 1;
+                result = self.execute_bounded(call, default_timeout).await;
+            }
+        }
+        if let Some(policy) = &self.output_policy {
+            policy.apply(call, &mut result).await;
+        }
+        if let Some(cache) = &self.cache {
+            cache.insert(call, &result);
+        }
+        result
+    }
+
+    async fn approve(&self, call: &ToolCall) -> Result<(), ToolError> {
+        let Some(approvals) = &self.approvals else {
+            return Ok(());
+        };
+        // Rules see the tool's current name, so an alias cannot dodge them
+        let mut resolved = call.clone();
+        if let Some(name) = self.registry.resolve_alias(&call.name).await {
+            resolved.name = name;
+        }
+        // Unknown tools fail when executed
+        let Some(tool) = self.registry.tool_info(&resolved.name).await else {
+            return Ok(());
+        };
+        match approvals.decide(&resolved, &tool).await {
+            ApprovalDecision::Approve => Ok(()),
+            ApprovalDecision::Reject(reason) => Err(ToolError::permission_denied(format!(
+                "Tool '{}' was not approved: {}",
+                call.name, reason
+            ))),
+        }
+    }
+
+    async fn execute_bounded(
+        &self,
+        call: &ToolCall,
+        default_timeout: Option<Duration>,
+    ) -> ToolResult {
+        let limit = self
+            .registry
+            .timeout_for(&call.name)
             .await
+            .or(default_timeout);
+        let execution = self.registry.execute_tool(&call.name, &call.parameters);
+        let Some(limit) = limit else {
+            return execution.await;
+        };
+        match tokio::time::timeout(limit, execution).await {
+            Ok(result) => result,
+            Err(_) => {
+                ToolError::timeout(format!("Tool '{}' timed out after {:?}", call.name, limit))
+                    .with_details(serde_json::json!({ "timeout_ms": limit.as_millis() as u64 }))
+                    .into()
+            }
+        }
     }
 }
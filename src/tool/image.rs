@@ -0,0 +1,233 @@
+//! Image generation as a tool.
+//!
+//! `ImageGenerationTool` lets the model create images through any
+//! `ImageGenerationProvider`. The model gets a text summary of each image;
+//! the images themselves go to the `on_image` callback, e.g. to save or
+//! display them.
+
+use super::{Tool, ToolError, ToolMetadata, ToolResult};
+use crate::provider::{
+    GeneratedImage, ImageGenerationProvider, ImageQuality, ImageRequest, ImageSize, ImageSource,
+    ProviderError,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+type ImageCallback = Arc<dyn Fn(&GeneratedImage) + Send + Sync>;
+
+/// Lets the model generate images
+pub struct ImageGenerationTool {
+    provider: Arc<dyn ImageGenerationProvider>,
+    model: Option<String>,
+    size: Option<ImageSize>,
+    quality: Option<ImageQuality>,
+    on_image: Option<ImageCallback>,
+}
+
+impl ImageGenerationTool {
+    pub fn new(provider: Arc<dyn ImageGenerationProvider>) -> Self {
+        Self {
+            provider,
+            model: None,
+            size: None,
+            quality: None,
+            on_image: None,
+        }
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Size used when the model does not pick one
+    pub fn size(mut self, size: ImageSize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn quality(mut self, quality: ImageQuality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Called with every image generated
+    pub fn on_image(mut self, callback: impl Fn(&GeneratedImage) + Send + Sync + 'static) -> Self {
+        self.on_image = Some(Arc::new(callback));
+        self
+    }
+}
+
+/// Parse "WIDTHxHEIGHT"
+fn parse_size(size: &str) -> Option<ImageSize> {
+    let (width, height) = size.split_once('x')?;
+    Some(ImageSize::new(
+        width.trim().parse().ok()?,
+        height.trim().parse().ok()?,
+    ))
+}
+
+fn describe(index: usize, image: &GeneratedImage) -> String {
+    let mut line = match &image.source {
+        ImageSource::Url { url } => format!("{}. {}", index + 1, url),
+        ImageSource::Base64 { media_type, data } => format!(
+            "{}. {} image ({} KB, delivered to the application)",
+            index + 1,
+            media_type,
+            data.len() * 3 / 4 / 1024
+        ),
+    };
+    if let Some(prompt) = &image.revised_prompt {
+        line.push_str(&format!("\n   Revised prompt: {}", prompt));
+    }
+    line
+}
+
+#[async_trait]
+impl Tool for ImageGenerationTool {
+    fn name(&self) -> &str {
+        "generate_image"
+    }
+
+    fn description(&self) -> &str {
+        "Generate an image from a detailed text description"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "prompt": {"type": "string", "description": "What the image should show"},
+                "size": {"type": "string", "description": "WIDTHxHEIGHT, e.g. 1024x1024"}
+            },
+            "required": ["prompt"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+            .expected_latency(Duration::from_secs(20))
+            .cost_hint("a few cents per image")
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let prompt = params["prompt"].as_str().unwrap_or_default();
+        if prompt.trim().is_empty() {
+            return ToolError::invalid_args("prompt must not be empty").into();
+        }
+        let size = match params["size"].as_str() {
+            Some(size) => match parse_size(size) {
+                Some(size) => Some(size),
+                None => {
+                    return ToolError::invalid_args(format!(
+                        "size must look like 1024x1024, got {}",
+                        size
+                    ))
+                    .into()
+                }
+            },
+            None => self.size,
+        };
+
+        let request = ImageRequest {
+            prompt: prompt.to_string(),
+            model: self.model.clone(),
+            size,
+            quality: self.quality,
+            n: 1,
+        };
+        let response = match self.provider.generate_images(request).await {
+            Ok(response) => response,
+            Err(ProviderError::RateLimited { .. }) => {
+                return ToolError::upstream("Image provider is rate limited").into()
+            }
+            Err(e) => {
+                return ToolError::upstream(format!("Image generation failed: {}", e))
+                    .with_retryable(false)
+                    .into()
+            }
+        };
+
+        if let Some(callback) = &self.on_image {
+            response.images.iter().for_each(|image| callback(image));
+        }
+        let lines: Vec<String> = response
+            .images
+            .iter()
+            .enumerate()
+            .map(|(i, image)| describe(i, image))
+            .collect();
+        ToolResult::success(format!(
+            "Generated {} image(s):\n{}",
+            lines.len(),
+            lines.join("\n")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ImageResponse;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Mutex;
+
+    struct FakeImages {
+        requests: Mutex<Vec<ImageRequest>>,
+    }
+
+    impl ImageGenerationProvider for FakeImages {
+        fn generate_images(
+            &self,
+            request: ImageRequest,
+        ) -> Pin<Box<dyn Future<Output = crate::provider::Result<ImageResponse>> + Send + '_>>
+        {
+            self.requests.lock().unwrap().push(request);
+            Box::pin(async {
+                Ok(ImageResponse {
+                    images: vec![GeneratedImage {
+                        source: ImageSource::Base64 {
+                            media_type: "image/png".to_string(),
+                            data: "A".repeat(4096),
+                        },
+                        revised_prompt: Some("a small red fox".to_string()),
+                    }],
+                    model: "fake".to_string(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_images_and_describes_them() {
+        let provider = Arc::new(FakeImages {
+            requests: Mutex::new(Vec::new()),
+        });
+        let delivered = Arc::new(Mutex::new(0));
+        let counter = delivered.clone();
+        let tool = ImageGenerationTool::new(provider.clone())
+            .size(ImageSize::SQUARE)
+            .on_image(move |_| *counter.lock().unwrap() += 1);
+
+        let result = tool
+            .execute(&serde_json::json!({"prompt": "a fox", "size": "1536x1024"}))
+            .await;
+        assert_eq!(
+            result.content,
+            "Generated 1 image(s):\n1. image/png image (3 KB, delivered to the application)\n   Revised prompt: a small red fox"
+        );
+        assert_eq!(*delivered.lock().unwrap(), 1);
+        assert_eq!(
+            provider.requests.lock().unwrap()[0].size,
+            Some(ImageSize::new(1536, 1024))
+        );
+
+        let result = tool
+            .execute(&serde_json::json!({"prompt": "a fox", "size": "big"}))
+            .await;
+        assert!(!result.success);
+    }
+}
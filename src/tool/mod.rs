@@ -1,19 +1,67 @@
+pub mod approval;
+pub mod ask_user;
+pub mod audit;
+pub mod cache;
+#[cfg(feature = "rhai")]
+pub mod code;
+pub mod coerce;
+pub mod computer;
+pub mod dry_run;
 pub mod executor;
+pub mod image;
+pub mod output;
 pub mod parser;
 pub mod registry;
+pub mod remember;
+pub mod retry;
+pub mod shell;
+pub mod typed;
+pub mod web_search;
 
+pub use approval::{
+    ApprovalDecision, ApprovalHandler, ApprovalManager, ApprovalRule, ApprovalTimeout,
+    AuditFailure, CallMatcher, FieldTest, RuleAction,
+};
+pub use ask_user::{AskUserTool, UserQuestion, UserQuestions};
+pub use audit::{ApprovalAuditLog, ApprovalRecord, AuditSink, DecidedBy, JsonlAuditSink};
+pub use cache::{ToolCacheStats, ToolResultCache};
+#[cfg(feature = "rhai")]
+pub use code::{CodeLimits, CodeTool};
+pub use coerce::coerce_arguments;
+pub use computer::{
+    ComputerAction, ComputerEnvironment, ComputerOutput, ComputerTool, ComputerUseVersion,
+    DisplayConfig, ScrollDirection,
+};
+pub use dry_run::DryRun;
 pub use executor::*;
+pub use image::ImageGenerationTool;
+pub use output::{OutputSummarizer, OutputTruncation, ToolOutputPolicy};
 pub use parser::*;
 pub use registry::*;
+pub use remember::{RecallTool, RememberTool};
+pub use retry::ToolRetryConfig;
+pub use shell::ShellTool;
+pub use typed::{parameters_schema_for, Typed, TypedTool};
+#[cfg(feature = "web-search")]
+pub use web_search::{BraveSearch, SerpApiSearch, TavilySearch};
+pub use web_search::{SearchBackend, SearchResult, WebSearchTool};
 
+use crate::provider::ImageSource;
 use async_trait::async_trait;
 use serde_json::Value;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct ToolResult {
     pub success: bool,
     pub content: String,
+    /// Message shown to the model when the call failed
     pub error: Option<String>,
+    /// Classified failure, when the tool reported one
+    pub failure: Option<ToolError>,
+    /// Images sent back with a successful result, where the provider
+    /// accepts them
+    pub images: Vec<ImageSource>,
 }
 
 impl ToolResult {
@@ -22,6 +70,8 @@ impl ToolResult {
             success: true,
             content: content.into(),
             error: None,
+            failure: None,
+            images: Vec::new(),
         }
     }
 
@@ -30,10 +80,124 @@ impl ToolResult {
             success: false,
             content: String::new(),
             error: Some(error.into()),
+            failure: None,
+            images: Vec::new(),
         }
     }
+
+    /// Failed result carrying a classified error
+    pub fn failed(error: ToolError) -> Self {
+        Self {
+            success: false,
+            content: String::new(),
+            error: Some(error.message.clone()),
+            failure: Some(error),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn with_image(mut self, image: ImageSource) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    pub fn error_kind(&self) -> Option<ToolErrorKind> {
+        self.failure.as_ref().map(|f| f.kind)
+    }
+
+    /// Whether running the same call again may succeed
+    pub fn is_retryable(&self) -> bool {
+        self.failure.as_ref().is_some_and(|f| f.retryable)
+    }
+}
+
+impl From<ToolError> for ToolResult {
+    fn from(error: ToolError) -> Self {
+        Self::failed(error)
+    }
+}
+
+/// Class of a tool failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorKind {
+    /// Arguments missing, malformed or out of range
+    InvalidArgs,
+    /// The tool, or what it was asked to act on, does not exist
+    NotFound,
+    Timeout,
+    PermissionDenied,
+    /// A service the tool depends on failed
+    Upstream,
+}
+
+impl ToolErrorKind {
+    /// Timeouts and upstream failures are usually transient
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Timeout | Self::Upstream)
+    }
+}
+
+/// Structured tool failure for retry policies and the agent loop
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolError {
+    pub kind: ToolErrorKind,
+    pub message: String,
+    pub retryable: bool,
+    /// Extra context, e.g. the offending field or an upstream status code
+    pub details: Option<Value>,
+}
+
+impl ToolError {
+    /// Retryable when `kind` is transient
+    pub fn new(kind: ToolErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retryable: kind.is_transient(),
+            details: None,
+        }
+    }
+
+    pub fn invalid_args(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::InvalidArgs, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::NotFound, message)
+    }
+
+    pub fn timeout(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::Timeout, message)
+    }
+
+    pub fn permission_denied(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::PermissionDenied, message)
+    }
+
+    pub fn upstream(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::Upstream, message)
+    }
+
+    pub fn with_retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
 }
 
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
 #[async_trait]
 pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
@@ -46,75 +210,140 @@ pub trait Tool: Send + Sync {
         validate_against_schema(params, &schema)
     }
 
+    /// Examples, cost hints and risk shown to the model and to approvers
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+    }
+
     async fn execute(&self, params: &Value) -> ToolResult;
 }
 
-/// Basic JSON schema validation
+#[async_trait]
+impl<T: Tool + ?Sized> Tool for std::sync::Arc<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn description(&self) -> &str {
+        (**self).description()
+    }
+
+    fn parameters_schema(&self) -> Value {
+        (**self).parameters_schema()
+    }
+
+    fn validate_parameters(&self, params: &Value) -> Result<(), String> {
+        (**self).validate_parameters(params)
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        (**self).metadata()
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        (**self).execute(params).await
+    }
+}
+
+/// Check parameters against the tool's schema; see `crate::schema::validate`
 fn validate_against_schema(params: &Value, schema: &Value) -> Result<(), String> {
-    let schema_obj = schema.as_object().ok_or("Schema must be an object")?;
-    let params_obj = params.as_object().ok_or("Parameters must be an object")?;
-
-    // Check required fields
-    if let Some(required) = schema_obj.get("required").and_then(|r| r.as_array()) {
-        for req_field in required {
-            let field_name = req_field
-                .as_str()
-                .ok_or("Required field name must be string")?;
-            if !params_obj.contains_key(field_name) {
-                return Err(format!("Missing required parameter: {}", field_name));
-            }
-        }
+    if !params.is_object() {
+        return Err("Parameters must be an object".to_string());
+    }
+    let errors = crate::schema::validate(params, schema);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
     }
+}
 
-    // Check properties
-    if let Some(properties) = schema_obj.get("properties").and_then(|p| p.as_object()) {
-        for (param_name, param_value) in params_obj {
-            if let Some(prop_schema) = properties.get(param_name).and_then(|p| p.as_object()) {
-                validate_property(param_value, prop_schema, param_name)?;
-            }
+/// How much harm a tool call can do, for prompts and approval prompts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DangerLevel {
+    /// Reads data or computes; safe to call freely
+    #[default]
+    Safe,
+    /// Changes state that can be undone
+    Moderate,
+    /// Deletes data, spends money or acts irreversibly
+    Destructive,
+}
+
+impl DangerLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Safe => "safe",
+            Self::Moderate => "moderate",
+            Self::Destructive => "destructive",
         }
     }
+}
 
-    Ok(())
-}
-
-fn validate_property(
-    value: &Value,
-    schema: &serde_json::Map<String, Value>,
-    param_name: &str,
-) -> Result<(), String> {
-    // Check type
-    if let Some(expected_type) = schema.get("type").and_then(|t| t.as_str()) {
-        let actual_type = match value {
-            Value::String(_) => "string",
-            Value::Number(_) => "number",
-            Value::Bool(_) => "boolean",
-            Value::Array(_) => "array",
-            Value::Object(_) => "object",
-            Value::Null => "null",
-        };
-
-        if actual_type != expected_type {
-            return Err(format!(
-                "Parameter '{}' must be of type '{}', got '{}'",
-                param_name, expected_type, actual_type
-            ));
+/// Example call included in the tool description
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolExample {
+    /// When this call is appropriate
+    pub description: String,
+    pub parameters: Value,
+}
+
+impl ToolExample {
+    pub fn new(description: impl Into<String>, parameters: Value) -> Self {
+        Self {
+            description: description.into(),
+            parameters,
         }
     }
+}
 
-    // Check enum values
-    if let Some(enum_values) = schema.get("enum").and_then(|e| e.as_array()) {
-        if !enum_values.contains(value) {
-            let valid_values: Vec<String> = enum_values.iter().map(|v| v.to_string()).collect();
-            return Err(format!(
-                "Parameter '{}' must be one of: [{}]",
-                param_name,
-                valid_values.join(", ")
-            ));
-        }
+/// Optional hints that help models pick tools and approvers judge calls
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolMetadata {
+    pub examples: Vec<ToolExample>,
+    /// Typical time a call takes
+    pub expected_latency: Option<Duration>,
+    /// Free-form cost note, e.g. "$0.01 per call"
+    pub cost_hint: Option<String>,
+    pub danger: DangerLevel,
+    /// Longest a call may run before the executor gives up, overriding the
+    /// agent's `tool_timeout`
+    pub timeout: Option<Duration>,
+    /// Provider-defined tool definition, see `ToolSchema::native`
+    pub native: Option<Value>,
+}
+
+impl ToolMetadata {
+    pub fn example(mut self, description: impl Into<String>, parameters: Value) -> Self {
+        self.examples
+            .push(ToolExample::new(description, parameters));
+        self
+    }
+
+    pub fn expected_latency(mut self, latency: Duration) -> Self {
+        self.expected_latency = Some(latency);
+        self
+    }
+
+    pub fn cost_hint(mut self, hint: impl Into<String>) -> Self {
+        self.cost_hint = Some(hint.into());
+        self
+    }
+
+    pub fn danger(mut self, danger: DangerLevel) -> Self {
+        self.danger = danger;
+        self
     }
 
-    Ok(())
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn native(mut self, definition: Value) -> Self {
+        self.native = Some(definition);
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -122,9 +351,54 @@ pub struct ToolInfo {
     pub name: String,
     pub description: String,
     pub parameters_schema: Value,
+    pub metadata: ToolMetadata,
 }
 
-#[derive(Debug, Clone)]
+impl ToolInfo {
+    /// Description followed by any hints and examples, as sent to the model
+    pub fn full_description(&self) -> String {
+        let meta = &self.metadata;
+        let mut hints = Vec::new();
+        if meta.danger != DangerLevel::Safe {
+            hints.push(format!("Risk: {}.", meta.danger.as_str()));
+        }
+        if let Some(latency) = meta.expected_latency {
+            hints.push(format!("Typical latency: {:?}.", latency));
+        }
+        if let Some(cost) = &meta.cost_hint {
+            hints.push(format!("Cost: {}.", cost));
+        }
+
+        let mut text = self.description.clone();
+        if !hints.is_empty() {
+            text.push('\n');
+            text.push_str(&hints.join(" "));
+        }
+        if !meta.examples.is_empty() {
+            text.push_str("\nExamples:");
+            for example in &meta.examples {
+                text.push_str(&format!(
+                    "\n- {}: {}",
+                    example.description, example.parameters
+                ));
+            }
+        }
+        text
+    }
+}
+
+impl From<ToolInfo> for crate::provider::ToolSchema {
+    fn from(info: ToolInfo) -> Self {
+        Self {
+            description: info.full_description(),
+            native: info.metadata.native.clone(),
+            name: info.name,
+            parameters: info.parameters_schema,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
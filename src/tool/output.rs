@@ -0,0 +1,229 @@
+//! Size limits for tool results.
+//!
+//! A tool that returns a whole file or a large API response can fill the
+//! context window in one call. A `ToolOutputPolicy` set on the executor cuts
+//! results down before they are added to the conversation.
+
+use super::{ToolCall, ToolResult};
+use crate::provider::{CharEstimateTokenizer, Tokenizer};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Shortens tool output that is over the limit, e.g. with a cheap model
+#[async_trait]
+pub trait OutputSummarizer: Send + Sync {
+    /// Shorten `content`, returned by `call`, to at most `max_bytes`
+    async fn summarize(&self, call: &ToolCall, content: &str, max_bytes: usize) -> String;
+}
+
+/// Which part of an oversized result is kept
+#[derive(Clone, Default)]
+pub enum OutputTruncation {
+    /// The beginning
+    #[default]
+    Head,
+    /// The end, e.g. for logs where the error comes last
+    Tail,
+    /// Half the budget from each end
+    HeadAndTail,
+    /// A summary; cut to the head if it is still too long
+    Summarize(Arc<dyn OutputSummarizer>),
+}
+
+impl std::fmt::Debug for OutputTruncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Head => write!(f, "Head"),
+            Self::Tail => write!(f, "Tail"),
+            Self::HeadAndTail => write!(f, "HeadAndTail"),
+            Self::Summarize(_) => write!(f, "Summarize(..)"),
+        }
+    }
+}
+
+/// Limits on the content of a tool result
+///
+/// Truncated content gets a `[... N bytes truncated ...]` marker so the model
+/// knows output is missing.
+#[derive(Clone)]
+pub struct ToolOutputPolicy {
+    pub max_bytes: Option<usize>,
+    /// Converted to a byte budget in proportion to the content's token count
+    pub max_tokens: Option<usize>,
+    pub strategy: OutputTruncation,
+    pub tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl std::fmt::Debug for ToolOutputPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolOutputPolicy")
+            .field("max_bytes", &self.max_bytes)
+            .field("max_tokens", &self.max_tokens)
+            .field("strategy", &self.strategy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ToolOutputPolicy {
+    /// No limits
+    fn default() -> Self {
+        Self {
+            max_bytes: None,
+            max_tokens: None,
+            strategy: OutputTruncation::default(),
+            tokenizer: Arc::new(CharEstimateTokenizer::default()),
+        }
+    }
+}
+
+impl ToolOutputPolicy {
+    pub fn max_bytes(max_bytes: usize) -> Self {
+        Self {
+            max_bytes: Some(max_bytes),
+            ..Default::default()
+        }
+    }
+
+    pub fn max_tokens(max_tokens: usize) -> Self {
+        Self {
+            max_tokens: Some(max_tokens),
+            ..Default::default()
+        }
+    }
+
+    pub fn strategy(mut self, strategy: OutputTruncation) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Bytes of `content` that may be kept, if it is over a limit
+    fn byte_budget(&self, content: &str) -> Option<usize> {
+        let by_bytes = self.max_bytes.filter(|max| content.len() > *max);
+        let by_tokens = self.max_tokens.and_then(|max| {
+            let tokens = self.tokenizer.count_tokens(content);
+            (tokens > max).then(|| content.len() * max / tokens)
+        });
+        match (by_bytes, by_tokens) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Cut `result`'s content down to the limits; returns whether it changed
+    pub async fn apply(&self, call: &ToolCall, result: &mut ToolResult) -> bool {
+        let Some(budget) = self.byte_budget(&result.content) else {
+            return false;
+        };
+        let content = &result.content;
+        let omitted = |kept: usize| content.len() - kept;
+        result.content = match &self.strategy {
+            OutputTruncation::Head => {
+                let head = head(content, budget);
+                format!("{}\n{}", head, marker(omitted(head.len())))
+            }
+            OutputTruncation::Tail => {
+                let tail = tail(content, budget);
+                format!("{}\n{}", marker(omitted(tail.len())), tail)
+            }
+            OutputTruncation::HeadAndTail => {
+                let head = head(content, budget / 2);
+                let tail = tail(content, budget - head.len());
+                format!(
+                    "{}\n{}\n{}",
+                    head,
+                    marker(omitted(head.len() + tail.len())),
+                    tail
+                )
+            }
+            OutputTruncation::Summarize(summarizer) => {
+                let summary = summarizer.summarize(call, content, budget).await;
+                if summary.len() > budget {
+                    head(&summary, budget).to_string()
+                } else {
+                    summary
+                }
+            }
+        };
+        true
+    }
+}
+
+fn marker(omitted: usize) -> String {
+    format!("[... {} bytes truncated ...]", omitted)
+}
+
+/// Longest prefix of at most `max` bytes that ends on a char boundary
+fn head(text: &str, max: usize) -> &str {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Longest suffix of at most `max` bytes that starts on a char boundary
+fn tail(text: &str, max: usize) -> &str {
+    let mut start = text.len().saturating_sub(max);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call() -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            parameters: serde_json::json!({}),
+        }
+    }
+
+    async fn truncate(policy: &ToolOutputPolicy, content: &str) -> String {
+        let mut result = ToolResult::success(content);
+        policy.apply(&call(), &mut result).await;
+        result.content
+    }
+
+    #[tokio::test]
+    async fn truncates_on_char_boundaries() {
+        let content = "ab€cdefgh€ij";
+        let policy = ToolOutputPolicy::max_bytes(4);
+
+        assert_eq!(
+            truncate(&policy, content).await,
+            "ab\n[... 14 bytes truncated ...]"
+        );
+        let policy = policy.strategy(OutputTruncation::Tail);
+        assert_eq!(
+            truncate(&policy, content).await,
+            "[... 14 bytes truncated ...]\nij"
+        );
+        let policy = policy.strategy(OutputTruncation::HeadAndTail);
+        assert_eq!(
+            truncate(&policy, content).await,
+            "ab\n[... 12 bytes truncated ...]\nij"
+        );
+
+        assert_eq!(truncate(&policy, "tiny").await, "tiny");
+    }
+
+    #[tokio::test]
+    async fn token_limit_scales_the_byte_budget() {
+        // 400 bytes is about 100 tokens at 4 bytes per token
+        let content = "x".repeat(400);
+        let policy = ToolOutputPolicy::max_tokens(25);
+
+        let truncated = truncate(&policy, &content).await;
+        assert!(truncated.starts_with(&"x".repeat(100)));
+        assert!(truncated.ends_with("[... 300 bytes truncated ...]"));
+    }
+}
@@ -24,7 +24,7 @@ impl ToolCallParser {
                 if let Ok(json) = serde_json::from_str::<Value>(json_str) {
                     if let Some(tool_calls) = json.get("tool_calls").and_then(|v| v.as_array()) {
                         let mut calls = Vec::new();
-                        for (i, call) in tool_calls.iter().enumerate() {
+                        for call in tool_calls {
                             if let (Some(name), Some(params)) = (
                                 call.get("name").and_then(|v| v.as_str()),
                                 call.get("parameters"),
@@ -33,8 +33,8 @@ impl ToolCallParser {
                                     id: call
                                         .get("id")
                                         .and_then(|v| v.as_str())
-                                        .unwrap_or(&format!("call_{}", i))
-                                        .to_string(),
+                                        .map(str::to_string)
+                                        .unwrap_or_else(crate::ids::tool_call_id),
                                     name: name.to_string(),
                                     parameters: params.clone(),
                                 });
@@ -73,7 +73,7 @@ impl ToolCallParser {
 
     fn parse_single_xml_call(xml: &str) -> Option<ToolCall> {
         // 简单的 XML 解析
-        let id = Self::extract_xml_attribute(xml, "id").unwrap_or_else(|| "call_0".to_string());
+        let id = Self::extract_xml_attribute(xml, "id").unwrap_or_else(crate::ids::tool_call_id);
         let name = Self::extract_xml_attribute(xml, "name")?;
 
         // 解析参数
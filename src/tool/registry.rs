@@ -1,23 +1,155 @@
-use super::{Tool, ToolInfo};
-use std::collections::HashMap;
+use super::{Tool, ToolError, ToolInfo, ToolMetadata, ToolResult, ToolRetryConfig};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// Separates a tool's namespace from its name, as in `fs__read_file`
+///
+/// Providers only accept letters, digits, `_` and `-` in tool names, so a
+/// dot cannot be used.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// Shared set of tools; clones see the same tools, so tools registered
+/// through any clone are offered from the next iteration of a running agent
+///
+/// Tools named `<namespace>__<name>` form a group that can be disabled and
+/// re-enabled as a whole.
 pub struct ToolRegistry {
-    tools: Arc<RwLock<HashMap<String, Box<dyn Tool>>>>,
+    tools: Arc<RwLock<HashMap<String, Arc<dyn Tool>>>>,
+    /// Deprecated name -> current name
+    aliases: Arc<RwLock<HashMap<String, String>>>,
+    disabled_namespaces: Arc<RwLock<HashSet<String>>>,
+    version: Arc<AtomicU64>,
+    /// Retry policy for tools without their own
+    retry: Arc<RwLock<Option<ToolRetryConfig>>>,
+    tool_retries: Arc<RwLock<HashMap<String, ToolRetryConfig>>>,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: Arc::new(RwLock::new(HashMap::new())),
+            aliases: Arc::new(RwLock::new(HashMap::new())),
+            disabled_namespaces: Arc::new(RwLock::new(HashSet::new())),
+            version: Arc::new(AtomicU64::new(0)),
+            retry: Arc::new(RwLock::new(None)),
+            tool_retries: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Namespace of a tool name, e.g. `fs` for `fs__read_file`
+    pub fn namespace_of(name: &str) -> Option<&str> {
+        name.split_once(NAMESPACE_SEPARATOR)
+            .map(|(namespace, _)| namespace)
+            .filter(|namespace| !namespace.is_empty())
+    }
+
     pub async fn register(&self, tool: Box<dyn Tool>) {
         let name = tool.name().to_string();
         let mut tools = self.tools.write().await;
-        tools.insert(name, tool);
+        tools.insert(name, Arc::from(tool));
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Register `tool` as `<namespace>__<name>`
+    pub async fn register_namespaced(&self, namespace: &str, tool: Box<dyn Tool>) {
+        let name = format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, tool.name());
+        self.register(Box::new(NamespacedTool {
+            name,
+            inner: Arc::from(tool),
+        }))
+        .await;
+    }
+
+    /// Remove a tool; returns whether it was registered
+    pub async fn unregister(&self, name: &str) -> bool {
+        let mut tools = self.tools.write().await;
+        let removed = tools.remove(name).is_some();
+        if removed {
+            self.version.fetch_add(1, Ordering::SeqCst);
+        }
+        removed
+    }
+
+    /// Resolve calls to `alias` to the tool named `target`
+    ///
+    /// Keeps a renamed tool reachable under its old name. Aliases are not
+    /// offered to the model, and a registered tool named `alias` wins.
+    pub async fn register_alias(&self, alias: impl Into<String>, target: impl Into<String>) {
+        self.aliases
+            .write()
+            .await
+            .insert(alias.into(), target.into());
+    }
+
+    /// Remove an alias; returns whether it was registered
+    pub async fn unregister_alias(&self, alias: &str) -> bool {
+        self.aliases.write().await.remove(alias).is_some()
+    }
+
+    /// Current name for a deprecated alias
+    ///
+    /// `None` if `name` is not an alias or a tool with that name is
+    /// registered.
+    pub async fn resolve_alias(&self, name: &str) -> Option<String> {
+        if self.tools.read().await.contains_key(name) {
+            return None;
+        }
+        self.aliases.read().await.get(name).cloned()
+    }
+
+    /// Namespaces of the registered tools, sorted
+    pub async fn namespaces(&self) -> Vec<String> {
+        let tools = self.tools.read().await;
+        let mut namespaces: Vec<String> = tools
+            .keys()
+            .filter_map(|name| Self::namespace_of(name))
+            .map(str::to_string)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        namespaces.sort();
+        namespaces
+    }
+
+    /// Hide every tool in `namespace` from the model and refuse calls to
+    /// them, including tools registered into it later
+    pub async fn disable_namespace(&self, namespace: &str) {
+        if self
+            .disabled_namespaces
+            .write()
+            .await
+            .insert(namespace.to_string())
+        {
+            self.version.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub async fn enable_namespace(&self, namespace: &str) {
+        if self.disabled_namespaces.write().await.remove(namespace) {
+            self.version.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub async fn is_namespace_enabled(&self, namespace: &str) -> bool {
+        !self.disabled_namespaces.read().await.contains(namespace)
+    }
+
+    async fn is_disabled(&self, name: &str) -> bool {
+        match Self::namespace_of(name) {
+            Some(namespace) => !self.is_namespace_enabled(namespace).await,
+            None => false,
+        }
+    }
+
+    /// Changes on every register or unregister, and when a namespace is
+    /// enabled or disabled
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
     }
 
     pub async fn execute_tool(
@@ -25,34 +157,110 @@ impl ToolRegistry {
         name: &str,
         params: &serde_json::Value,
     ) -> crate::tool::ToolResult {
-        let tools = self.tools.read().await;
-        if let Some(tool) = tools.get(name) {
-            // Validate parameters first
-            if let Err(validation_error) = tool.validate_parameters(params) {
-                return crate::tool::ToolResult::error(format!(
+        match self.checked_tool(name, params).await {
+            Ok(tool) => tool.execute(params).await,
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Check a call the way `execute_tool` does without executing it
+    ///
+    /// Returns the error `execute_tool` would return for a missing or
+    /// disabled tool or invalid parameters.
+    pub async fn check_call(&self, name: &str, params: &Value) -> Result<(), ToolError> {
+        self.checked_tool(name, params).await.map(|_| ())
+    }
+
+    /// Resolve `name` to an enabled tool that accepts `params`
+    async fn checked_tool(&self, name: &str, params: &Value) -> Result<Arc<dyn Tool>, ToolError> {
+        let name = self
+            .resolve_alias(name)
+            .await
+            .unwrap_or_else(|| name.to_string());
+        if self.is_disabled(&name).await {
+            return Err(ToolError::permission_denied(format!(
+                "Tool group '{}' is disabled",
+                Self::namespace_of(&name).unwrap_or_default()
+            )));
+        }
+        // Release the lock before executing so tools can change the registry
+        let tool = self.tools.read().await.get(&name).cloned();
+        let tool = tool.ok_or_else(|| ToolError::not_found("Tool not found"))?;
+        tool.validate_parameters(params)
+            .map_err(|validation_error| {
+                ToolError::invalid_args(format!(
                     "Parameter validation failed: {}",
                     validation_error
-                ));
-            }
+                ))
+            })?;
+        Ok(tool)
+    }
+
+    /// Timeout declared in a tool's metadata, following aliases
+    pub async fn timeout_for(&self, name: &str) -> Option<Duration> {
+        let name = self
+            .resolve_alias(name)
+            .await
+            .unwrap_or_else(|| name.to_string());
+        let tool = self.tools.read().await.get(&name).cloned()?;
+        tool.metadata().timeout
+    }
+
+    /// Retry policy for every tool that has none of its own; `None` runs
+    /// calls once
+    pub async fn set_retry_config(&self, config: Option<ToolRetryConfig>) {
+        *self.retry.write().await = config;
+    }
+
+    /// Retry policy for the tool registered as `name`, replacing the global
+    /// one; `None` falls back to the global policy again
+    pub async fn set_tool_retry_config(&self, name: &str, config: Option<ToolRetryConfig>) {
+        let mut retries = self.tool_retries.write().await;
+        match config {
+            Some(config) => retries.insert(name.to_string(), config),
+            None => retries.remove(name),
+        };
+    }
 
-            // Execute tool if validation passes
-            tool.execute(params).await
-        } else {
-            crate::tool::ToolResult::error("Tool not found")
+    /// Retry policy applied to calls of `name`, following aliases
+    pub async fn retry_config_for(&self, name: &str) -> Option<ToolRetryConfig> {
+        let name = self
+            .resolve_alias(name)
+            .await
+            .unwrap_or_else(|| name.to_string());
+        if let Some(config) = self.tool_retries.read().await.get(&name) {
+            return Some(config.clone());
         }
+        self.retry.read().await.clone()
     }
 
+    /// Tools offered to the model, leaving out disabled namespaces
     pub async fn list_tools(&self) -> Vec<ToolInfo> {
+        let disabled = self.disabled_namespaces.read().await;
         let tools = self.tools.read().await;
         tools
-            .values()
-            .map(|tool| ToolInfo {
-                name: tool.name().to_string(),
-                description: tool.description().to_string(),
-                parameters_schema: tool.parameters_schema(),
+            .iter()
+            .filter(|(name, _)| {
+                Self::namespace_of(name).is_none_or(|namespace| !disabled.contains(namespace))
             })
+            .map(|(_, tool)| Self::info(tool.as_ref()))
             .collect()
     }
+
+    /// Description, schema and metadata of a registered tool
+    pub async fn tool_info(&self, name: &str) -> Option<ToolInfo> {
+        let tools = self.tools.read().await;
+        tools.get(name).map(|tool| Self::info(tool.as_ref()))
+    }
+
+    fn info(tool: &dyn Tool) -> ToolInfo {
+        ToolInfo {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            parameters_schema: tool.parameters_schema(),
+            metadata: tool.metadata(),
+        }
+    }
 }
 
 impl Default for ToolRegistry {
@@ -65,6 +273,143 @@ impl Clone for ToolRegistry {
     fn clone(&self) -> Self {
         Self {
             tools: self.tools.clone(),
+            aliases: self.aliases.clone(),
+            disabled_namespaces: self.disabled_namespaces.clone(),
+            version: self.version.clone(),
+            retry: self.retry.clone(),
+            tool_retries: self.tool_retries.clone(),
         }
     }
 }
+
+/// Tool registered under a namespaced name
+struct NamespacedTool {
+    name: String,
+    inner: Arc<dyn Tool>,
+}
+
+#[async_trait]
+impl Tool for NamespacedTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn parameters_schema(&self) -> Value {
+        self.inner.parameters_schema()
+    }
+
+    fn validate_parameters(&self, params: &Value) -> Result<(), String> {
+        self.inner.validate_parameters(params)
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        // A provider-defined tool keeps its own name, which would no longer
+        // match the registered one
+        ToolMetadata {
+            native: None,
+            ..self.inner.metadata()
+        }
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        self.inner.execute(params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{ToolErrorKind, ToolResult};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+
+    struct FetchTool;
+
+    #[async_trait]
+    impl Tool for FetchTool {
+        fn name(&self) -> &str {
+            "fetch"
+        }
+
+        fn description(&self) -> &str {
+            "Fetches a URL"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({
+                "type": "object",
+                "properties": {"url": {"type": "string"}},
+                "required": ["url"]
+            })
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            ToolError::upstream("502 from origin")
+                .with_details(json!({"status": 502}))
+                .into()
+        }
+    }
+
+    #[tokio::test]
+    async fn namespaces_are_enabled_and_disabled_together() {
+        let registry = ToolRegistry::new();
+        registry
+            .register_namespaced("web", Box::new(FetchTool))
+            .await;
+        registry.register(Box::new(FetchTool)).await;
+        assert_eq!(registry.namespaces().await, ["web"]);
+        assert_eq!(ToolRegistry::namespace_of("web__fetch"), Some("web"));
+        assert_eq!(ToolRegistry::namespace_of("fetch"), None);
+
+        let version = registry.version();
+        registry.disable_namespace("web").await;
+        assert_ne!(registry.version(), version);
+        let names: Vec<_> = registry
+            .list_tools()
+            .await
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        assert_eq!(names, ["fetch"]);
+        let refused = registry
+            .execute_tool("web__fetch", &json!({"url": "https://example.com"}))
+            .await;
+        assert_eq!(refused.error_kind(), Some(ToolErrorKind::PermissionDenied));
+
+        registry.enable_namespace("web").await;
+        assert_eq!(registry.list_tools().await.len(), 2);
+        let result = registry
+            .execute_tool("web__fetch", &json!({"url": "https://example.com"}))
+            .await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::Upstream));
+    }
+
+    #[tokio::test]
+    async fn failures_are_classified() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(FetchTool)).await;
+
+        let missing = registry.execute_tool("nope", &json!({})).await;
+        assert_eq!(missing.error_kind(), Some(ToolErrorKind::NotFound));
+        assert_eq!(missing.error.as_deref(), Some("Tool not found"));
+
+        let invalid = registry.execute_tool("fetch", &json!({})).await;
+        assert_eq!(invalid.error_kind(), Some(ToolErrorKind::InvalidArgs));
+        assert!(!invalid.is_retryable());
+
+        let upstream = registry
+            .execute_tool("fetch", &json!({"url": "https://example.com"}))
+            .await;
+        assert_eq!(upstream.error_kind(), Some(ToolErrorKind::Upstream));
+        assert!(upstream.is_retryable());
+        assert_eq!(upstream.error.as_deref(), Some("502 from origin"));
+        assert_eq!(
+            upstream.failure.unwrap().details,
+            Some(json!({"status": 502}))
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Tools for the model to save and look up what it learns.
+//!
+//! `RememberTool` stores a fact under a key, or notes an episode when no
+//! key is given; `RecallTool` reads them back. Both work on an
+//! `AgentMemory`, so what one agent remembers stays under its id.
+
+use super::{Tool, ToolError, ToolMetadata, ToolResult};
+use crate::memory::AgentMemory;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Lets the model save facts and episodes for later runs
+pub struct RememberTool {
+    memory: AgentMemory,
+}
+
+impl RememberTool {
+    pub fn new(memory: AgentMemory) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for RememberTool {
+    fn name(&self) -> &str {
+        "remember"
+    }
+
+    fn description(&self) -> &str {
+        "Save something to remember in later conversations. With a key, stores a fact \
+         that replaces any earlier fact under that key, e.g. the user's name; without one, \
+         notes an event, e.g. what was decided."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "content": {"type": "string", "description": "What to remember"},
+                "key": {
+                    "type": "string",
+                    "description": "Short name for a fact, e.g. \"user_name\""
+                }
+            },
+            "required": ["content"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let content = params["content"].as_str().unwrap_or_default().trim();
+        if content.is_empty() {
+            return ToolError::invalid_args("content must not be empty").into();
+        }
+        let key = params["key"]
+            .as_str()
+            .map(str::trim)
+            .filter(|k| !k.is_empty());
+        let saved = match key {
+            Some(key) => self.memory.remember(key, content).await,
+            None => self.memory.record_episode(content).await,
+        };
+        match (saved, key) {
+            (Ok(()), Some(key)) => ToolResult::success(format!("Remembered '{}'", key)),
+            (Ok(()), None) => ToolResult::success("Noted"),
+            (Err(e), _) => ToolError::upstream(e.to_string()).into(),
+        }
+    }
+}
+
+/// Lets the model look up what it saved with `RememberTool`
+pub struct RecallTool {
+    memory: AgentMemory,
+    max_episodes: usize,
+}
+
+impl RecallTool {
+    pub fn new(memory: AgentMemory) -> Self {
+        Self {
+            memory,
+            max_episodes: 10,
+        }
+    }
+
+    /// Most recent episodes returned per call; 10 by default
+    pub fn max_episodes(mut self, max_episodes: usize) -> Self {
+        self.max_episodes = max_episodes;
+        self
+    }
+
+    async fn recall(&self, query: &str) -> crate::error::Result<String> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let matches = |text: &str| {
+            let text = text.to_lowercase();
+            words.is_empty() || words.iter().any(|word| text.contains(word.as_str()))
+        };
+
+        let facts: Vec<String> = self
+            .memory
+            .facts()
+            .await?
+            .into_iter()
+            .filter(|(key, value)| matches(key) || matches(value))
+            .map(|(key, value)| format!("- {}: {}", key, value))
+            .collect();
+        let episodes: Vec<_> = self
+            .memory
+            .episodes()
+            .await?
+            .into_iter()
+            .filter(|episode| matches(&episode.text))
+            .collect();
+        let episodes: Vec<String> = episodes[episodes.len().saturating_sub(self.max_episodes)..]
+            .iter()
+            .map(|episode| format!("- {}", episode.text))
+            .collect();
+
+        let mut sections = Vec::new();
+        if !facts.is_empty() {
+            sections.push(format!("Facts:\n{}", facts.join("\n")));
+        }
+        if !episodes.is_empty() {
+            sections.push(format!("Episodes, oldest first:\n{}", episodes.join("\n")));
+        }
+        Ok(if sections.is_empty() {
+            "Nothing remembered matches".to_string()
+        } else {
+            sections.join("\n\n")
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for RecallTool {
+    fn name(&self) -> &str {
+        "recall"
+    }
+
+    fn description(&self) -> &str {
+        "Look up facts and events saved with `remember`. Give a key for one fact, words \
+         to search for, or nothing to list everything."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "key": {"type": "string", "description": "Key of one fact"},
+                "query": {"type": "string", "description": "Words to search for"}
+            }
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        if let Some(key) = params["key"].as_str().filter(|k| !k.trim().is_empty()) {
+            return match self.memory.fact(key.trim()).await {
+                Ok(Some(value)) => ToolResult::success(value),
+                Ok(None) => ToolResult::success(format!("Nothing remembered under '{}'", key)),
+                Err(e) => ToolError::upstream(e.to_string()).into(),
+            };
+        }
+        match self
+            .recall(params["query"].as_str().unwrap_or_default())
+            .await
+        {
+            Ok(text) => ToolResult::success(text),
+            Err(e) => ToolError::upstream(e.to_string()).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::InMemoryStore;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn recalls_facts_and_episodes_per_agent() {
+        let store = Arc::new(InMemoryStore::new());
+        let memory = AgentMemory::new("assistant", store.clone());
+        let remember = RememberTool::new(memory.clone());
+        let recall = RecallTool::new(memory.clone()).max_episodes(1);
+
+        for params in [
+            json!({"key": "user_name", "content": "Bob"}),
+            json!({"key": "user_name", "content": "Ada"}),
+            json!({"content": "Booked a table for Friday"}),
+            json!({"content": "Ada prefers tea"}),
+        ] {
+            assert!(remember.execute(&params).await.success);
+        }
+        assert!(remember
+            .execute(&json!({"content": " "}))
+            .await
+            .error
+            .is_some());
+
+        let result = recall.execute(&json!({"key": "user_name"})).await;
+        assert_eq!(result.content, "Ada");
+        let result = recall.execute(&json!({"query": "ADA"})).await;
+        assert_eq!(
+            result.content,
+            "Facts:\n- user_name: Ada\n\nEpisodes, oldest first:\n- Ada prefers tea"
+        );
+        let result = recall.execute(&json!({"query": "friday"})).await;
+        assert_eq!(
+            result.content,
+            "Episodes, oldest first:\n- Booked a table for Friday"
+        );
+
+        // Another agent on the same store has its own memory
+        let other = RecallTool::new(AgentMemory::new("planner", store));
+        let result = other.execute(&json!({})).await;
+        assert_eq!(result.content, "Nothing remembered matches");
+
+        assert!(memory.forget("user_name").await.unwrap());
+        assert!(!memory.forget("user_name").await.unwrap());
+        assert_eq!(memory.episodes().await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,210 @@
+use super::ToolResult;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+type RetryPredicate = Arc<dyn Fn(&ToolResult) -> bool + Send + Sync>;
+
+/// Retry policy for tool calls, set for all tools or per tool on the
+/// `ToolRegistry`
+///
+/// Failed calls are run again with exponential backoff while `retry_on`
+/// accepts the result, up to `max_attempts` runs in total. By default only
+/// results marked retryable, such as timeouts and upstream failures, are
+/// retried.
+#[derive(Clone)]
+pub struct ToolRetryConfig {
+    /// Runs of the call in total, including the first
+    pub max_attempts: u32,
+    /// Wait before the first retry
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub backoff_multiplier: f64,
+    retry_on: RetryPredicate,
+}
+
+impl Default for ToolRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            backoff_multiplier: 2.0,
+            retry_on: Arc::new(ToolResult::is_retryable),
+        }
+    }
+}
+
+impl fmt::Debug for ToolRetryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolRetryConfig")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("backoff_multiplier", &self.backoff_multiplier)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ToolRetryConfig {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    /// Run calls once, e.g. to exempt one tool from a global policy
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.backoff_multiplier = multiplier;
+        self
+    }
+
+    /// Decide which failed results are retried
+    ///
+    /// Only called for failed results; successes are never retried.
+    pub fn retry_on(
+        mut self,
+        predicate: impl Fn(&ToolResult) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_on = Arc::new(predicate);
+        self
+    }
+
+    /// Whether to run the call again after `attempts` runs ended in `result`
+    pub fn should_retry(&self, result: &ToolResult, attempts: u32) -> bool {
+        !result.success && attempts < self.max_attempts && (self.retry_on)(result)
+    }
+
+    /// Wait before the retry following `attempts` runs
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1) as i32;
+        let backoff = self
+            .initial_backoff
+            .mul_f64(self.backoff_multiplier.max(1.0).powi(exponent));
+        backoff.min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{Tool, ToolCall, ToolError, ToolExecutor, ToolRegistry};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with `error` until it has been called `failures` times
+    struct Flaky {
+        calls: Arc<AtomicU32>,
+        failures: u32,
+        error: ToolError,
+    }
+
+    #[async_trait]
+    impl Tool for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn description(&self) -> &str {
+            "Fails a few times"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: &Value) -> ToolResult {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                self.error.clone().into()
+            } else {
+                ToolResult::success("ok")
+            }
+        }
+    }
+
+    async fn run(
+        error: ToolError,
+        failures: u32,
+        config: Option<ToolRetryConfig>,
+    ) -> (ToolResult, u32) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let registry = ToolRegistry::new();
+        registry
+            .register(Box::new(Flaky {
+                calls: calls.clone(),
+                failures,
+                error,
+            }))
+            .await;
+        registry
+            .set_retry_config(Some(
+                ToolRetryConfig::new(3).with_backoff(Duration::ZERO, Duration::ZERO),
+            ))
+            .await;
+        if let Some(config) = config {
+            registry.set_tool_retry_config("flaky", Some(config)).await;
+        }
+        let call = ToolCall {
+            id: "1".to_string(),
+            name: "flaky".to_string(),
+            parameters: json!({}),
+        };
+        let result = ToolExecutor::new(registry).execute_single(&call).await;
+        (result, calls.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_up_to_max_attempts() {
+        let (result, calls) = run(ToolError::upstream("reset"), 2, None).await;
+        assert!(result.success);
+        assert_eq!(calls, 3);
+
+        let (result, calls) = run(ToolError::upstream("reset"), 5, None).await;
+        assert!(!result.success);
+        assert_eq!(calls, 3);
+
+        // Invalid arguments will not get better
+        let (result, calls) = run(ToolError::invalid_args("bad"), 1, None).await;
+        assert!(!result.success);
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn per_tool_config_overrides_the_global_one() {
+        let (_, calls) = run(
+            ToolError::upstream("reset"),
+            2,
+            Some(ToolRetryConfig::none()),
+        )
+        .await;
+        assert_eq!(calls, 1);
+
+        let retry_all = ToolRetryConfig::new(2)
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .retry_on(|_| true);
+        let (result, calls) = run(ToolError::invalid_args("bad"), 1, Some(retry_all)).await;
+        assert!(result.success);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let config = ToolRetryConfig::default()
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(config.backoff(1), Duration::from_millis(100));
+        assert_eq!(config.backoff(2), Duration::from_millis(200));
+        assert_eq!(config.backoff(3), Duration::from_millis(350));
+    }
+}
@@ -0,0 +1,359 @@
+//! Shell command execution.
+//!
+//! `ShellTool` runs `sh -c <command>` with a cleared environment apart from
+//! allowlisted variables and a timeout. Only the directory a command starts
+//! in is checked against the working directory; the command itself can `cd`
+//! elsewhere or use absolute paths, so this is not a sandbox. On Unix each
+//! command gets its own process group, which is killed as a whole on timeout.
+//!
+//! Approval goes through the executor's `ApprovalManager`; `approval_rules`
+//! sends every command to its handler except those on the caller's
+//! allowlist.
+
+use super::approval::{ApprovalRule, CallMatcher, FieldTest, RuleAction};
+use super::{DangerLevel, Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+/// Runs shell commands for the model
+pub struct ShellTool {
+    root: PathBuf,
+    env_allowlist: Vec<String>,
+    timeout: Duration,
+    max_output_bytes: usize,
+    allowed_commands: Vec<String>,
+}
+
+impl ShellTool {
+    /// Commands run in `root` or, with the `cwd` parameter, a directory below it
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            env_allowlist: vec!["PATH".to_string()],
+            timeout: Duration::from_secs(30),
+            max_output_bytes: 64 * 1024,
+            allowed_commands: Vec::new(),
+        }
+    }
+
+    /// Pass this environment variable through; only `PATH` is by default
+    pub fn allow_env(mut self, name: impl Into<String>) -> Self {
+        self.env_allowlist.push(name.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Bytes of stdout and of stderr kept
+    pub fn max_output_bytes(mut self, max_output_bytes: usize) -> Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+
+    /// Commands `approval_rules` approves without asking, e.g. `git status`
+    ///
+    /// A command must equal one of these exactly, so `ls; rm -rf ~` does not
+    /// pass as `ls`.
+    pub fn allow_commands(mut self, commands: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_commands
+            .extend(commands.into_iter().map(Into::into));
+        self
+    }
+
+    /// Rules for an `ApprovalManager`: approve allowlisted commands and ask
+    /// its handler about every other one
+    pub fn approval_rules(&self) -> Vec<ApprovalRule> {
+        let mut rules = Vec::new();
+        if !self.allowed_commands.is_empty() {
+            let allowed = self
+                .allowed_commands
+                .iter()
+                .map(|command| {
+                    CallMatcher::field("command", FieldTest::Equals(Value::from(command.as_str())))
+                })
+                .collect();
+            rules.push(ApprovalRule::new(
+                CallMatcher::All(vec![
+                    CallMatcher::tool(self.name()),
+                    CallMatcher::Any(allowed),
+                ]),
+                RuleAction::Approve,
+            ));
+        }
+        rules.push(ApprovalRule::new(
+            CallMatcher::tool(self.name()),
+            RuleAction::Ask,
+        ));
+        rules
+    }
+
+    /// `cwd` resolved below the root, refusing paths that escape it
+    fn working_dir(&self, cwd: Option<&str>) -> Result<PathBuf, ToolError> {
+        let root = self.root.canonicalize().map_err(|e| {
+            ToolError::not_found(format!(
+                "Working directory {} is unavailable: {}",
+                self.root.display(),
+                e
+            ))
+        })?;
+        let Some(cwd) = cwd else {
+            return Ok(root);
+        };
+        let dir = root
+            .join(cwd)
+            .canonicalize()
+            .map_err(|e| ToolError::not_found(format!("Directory {} not found: {}", cwd, e)))?;
+        if !dir.starts_with(&root) {
+            return Err(ToolError::permission_denied(format!(
+                "Directory {} is outside the working directory",
+                cwd
+            )));
+        }
+        Ok(dir)
+    }
+
+    /// Keep the first `max_output_bytes` of `reader` and discard the rest,
+    /// so the command never blocks on a full pipe
+    async fn capture(&self, mut reader: impl AsyncRead + Unpin) -> std::io::Result<String> {
+        let mut kept = Vec::new();
+        (&mut reader)
+            .take(self.max_output_bytes as u64)
+            .read_to_end(&mut kept)
+            .await?;
+        let dropped = tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+        let mut text = String::from_utf8_lossy(&kept).into_owned();
+        if dropped > 0 {
+            text.push_str(&format!("\n[... {} bytes truncated ...]", dropped));
+        }
+        Ok(text)
+    }
+
+    async fn run(&self, mut process: Command) -> std::io::Result<(String, String, String)> {
+        #[cfg(unix)]
+        process.process_group(0);
+        let mut child = process.spawn()?;
+        #[cfg(unix)]
+        let mut group = ProcessGroup(child.id().map(|id| id as i32));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let (stdout, stderr) = tokio::try_join!(self.capture(stdout), self.capture(stderr))?;
+        let exit_code = child
+            .wait()
+            .await?
+            .code()
+            .map_or_else(|| "none (killed by signal)".to_string(), |c| c.to_string());
+        #[cfg(unix)]
+        group.disarm();
+        Ok((exit_code, stdout, stderr))
+    }
+}
+
+/// Kills a command's process group when dropped, so processes the shell
+/// started do not outlive a call that timed out or was cancelled
+#[cfg(unix)]
+struct ProcessGroup(Option<i32>);
+
+#[cfg(unix)]
+impl ProcessGroup {
+    /// Keep the group alive; its leader has been reaped
+    fn disarm(&mut self) {
+        self.0 = None;
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0 {
+            // SAFETY: killpg only sends a signal; the group was created for
+            // this command and its leader has not been reaped
+            unsafe {
+                libc::killpg(pgid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Run a shell command and return its exit code, stdout and stderr"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "command": {"type": "string", "description": "Command for sh -c"},
+                "cwd": {
+                    "type": "string",
+                    "description": "Directory relative to the working directory"
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+            .example("List files", serde_json::json!({"command": "ls -la"}))
+            .danger(DangerLevel::Destructive)
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let Some(command) = params["command"].as_str() else {
+            return ToolError::invalid_args("Missing required parameter: command").into();
+        };
+        let dir = match self.working_dir(params["cwd"].as_str()) {
+            Ok(dir) => dir,
+            Err(e) => return e.into(),
+        };
+
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg(command)
+            .current_dir(&dir)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        for name in &self.env_allowlist {
+            if let Ok(value) = std::env::var(name) {
+                process.env(name, value);
+            }
+        }
+
+        let (exit_code, stdout, stderr) =
+            match tokio::time::timeout(self.timeout, self.run(process)).await {
+                Ok(Ok(output)) => output,
+                Ok(Err(e)) => {
+                    return ToolError::upstream(format!("Failed to run command: {}", e)).into()
+                }
+                Err(_) => {
+                    return ToolError::timeout(format!(
+                        "Command timed out after {:?}",
+                        self.timeout
+                    ))
+                    .with_details(
+                        serde_json::json!({ "timeout_ms": self.timeout.as_millis() as u64 }),
+                    )
+                    .into()
+                }
+            };
+
+        ToolResult::success(format!(
+            "exit code: {}\nstdout:\n{}\nstderr:\n{}",
+            exit_code, stdout, stderr
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tool::{ApprovalManager, ToolCall, ToolErrorKind, ToolExecutor, ToolRegistry};
+
+    fn sandbox() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("agent-sdk-shell-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn reports_exit_code_and_output() {
+        let tool = ShellTool::new(sandbox());
+        let result = tool
+            .execute(&serde_json::json!({"command": "pwd; echo oops >&2; exit 3", "cwd": "sub"}))
+            .await;
+
+        assert!(result.success);
+        assert!(result.content.starts_with("exit code: 3\nstdout:\n"));
+        assert!(result.content.contains("/sub\n"));
+        assert!(result.content.ends_with("stderr:\noops\n"));
+    }
+
+    #[tokio::test]
+    async fn keeps_only_the_first_output_bytes() {
+        let tool = ShellTool::new(sandbox()).max_output_bytes(4);
+        let result = tool
+            .execute(&serde_json::json!({"command": "yes | head -c 1000000"}))
+            .await;
+
+        assert!(result
+            .content
+            .starts_with("exit code: 0\nstdout:\ny\ny\n\n[... 999996 bytes truncated ...]"));
+    }
+
+    #[tokio::test]
+    async fn enforces_sandbox_controls() {
+        let tool = ShellTool::new(sandbox()).timeout(Duration::from_millis(50));
+        let run = |params: Value| {
+            let tool = &tool;
+            async move { tool.execute(&params).await.error_kind() }
+        };
+
+        assert_eq!(
+            run(serde_json::json!({"cwd": "sub"})).await,
+            Some(ToolErrorKind::InvalidArgs)
+        );
+        assert_eq!(
+            run(serde_json::json!({"command": "ls", "cwd": ".."})).await,
+            Some(ToolErrorKind::PermissionDenied)
+        );
+        assert_eq!(
+            run(serde_json::json!({"command": "yes"})).await,
+            Some(ToolErrorKind::Timeout)
+        );
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_the_whole_process_group() {
+        let marker = sandbox().join("survivor");
+        let _ = std::fs::remove_file(&marker);
+        let tool = ShellTool::new(sandbox()).timeout(Duration::from_millis(100));
+        let result = tool
+            .execute(&serde_json::json!({"command": "(sleep 0.4; touch survivor) & sleep 5"}))
+            .await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::Timeout));
+
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn approval_rules_only_pass_allowlisted_commands() {
+        let tool = ShellTool::new(sandbox()).allow_commands(["echo hi"]);
+        let approvals = ApprovalManager::new().rules(tool.approval_rules());
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(tool)).await;
+        let executor = ToolExecutor::new(registry).with_approvals(approvals);
+        let call = |command: &str| ToolCall {
+            id: "1".to_string(),
+            name: "shell".to_string(),
+            parameters: serde_json::json!({ "command": command }),
+        };
+
+        for command in ["rm -R sub", "echo hi; rm -R sub", "find sub -delete"] {
+            let result = executor.execute_single(&call(command)).await;
+            assert_eq!(result.error_kind(), Some(ToolErrorKind::PermissionDenied));
+        }
+        assert!(sandbox().join("sub").exists());
+
+        let result = executor.execute_single(&call("echo hi")).await;
+        assert!(result.content.starts_with("exit code: 0\nstdout:\nhi\n"));
+    }
+}
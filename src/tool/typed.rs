@@ -0,0 +1,175 @@
+//! Tools with typed parameters.
+//!
+//! A `TypedTool` receives its arguments as a struct instead of raw JSON. The
+//! parameter schema is generated from the struct with `schemars`, and
+//! arguments that do not deserialize are rejected as invalid before
+//! `execute` runs. Wrap one in `Typed` to register it.
+
+use super::{Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Tool whose parameters deserialize into `Self::Params`
+#[async_trait]
+pub trait TypedTool: Send + Sync {
+    type Params: DeserializeOwned + JsonSchema + Send;
+
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default()
+    }
+
+    async fn execute(&self, params: Self::Params) -> ToolResult;
+}
+
+/// Adapter registering a `TypedTool` as a `Tool`
+pub struct Typed<T>(pub T);
+
+impl<T: TypedTool> Typed<T> {
+    pub fn new(tool: T) -> Self {
+        Self(tool)
+    }
+}
+
+/// JSON schema for `P` as tools send it: subschemas inlined, no `$schema`
+/// or `title`
+pub fn parameters_schema_for<P: JsonSchema>() -> Value {
+    let schema = SchemaSettings::draft2020_12()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<P>();
+    let mut schema = schema.to_value();
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+        object.remove("title");
+    }
+    schema
+}
+
+fn parse_params<P: DeserializeOwned>(params: &Value) -> Result<P, String> {
+    serde_json::from_value(params.clone()).map_err(|e| e.to_string())
+}
+
+#[async_trait]
+impl<T: TypedTool> Tool for Typed<T> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    fn parameters_schema(&self) -> Value {
+        parameters_schema_for::<T::Params>()
+    }
+
+    fn validate_parameters(&self, params: &Value) -> Result<(), String> {
+        parse_params::<T::Params>(params).map(|_| ())
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        self.0.metadata()
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        match parse_params(params) {
+            Ok(params) => self.0.execute(params).await,
+            Err(e) => ToolError::invalid_args(e).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::ToolRegistry;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "lowercase")]
+    enum Operation {
+        Add,
+        Mul,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct CalculatorParams {
+        /// First number
+        a: f64,
+        b: f64,
+        operation: Operation,
+    }
+
+    struct Calculator;
+
+    #[async_trait]
+    impl TypedTool for Calculator {
+        type Params = CalculatorParams;
+
+        fn name(&self) -> &str {
+            "calculator"
+        }
+
+        fn description(&self) -> &str {
+            "Add or multiply"
+        }
+
+        async fn execute(&self, params: CalculatorParams) -> ToolResult {
+            let result = match params.operation {
+                Operation::Add => params.a + params.b,
+                Operation::Mul => params.a * params.b,
+            };
+            ToolResult::success(result.to_string())
+        }
+    }
+
+    #[test]
+    fn generates_schema_from_params() {
+        let schema = Typed(Calculator).parameters_schema();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["a"]["description"], "First number");
+        assert_eq!(
+            schema["properties"]["operation"]["enum"],
+            serde_json::json!(["add", "mul"])
+        );
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["a", "b", "operation"])
+        );
+        assert!(schema.get("$schema").is_none());
+        assert!(schema.get("$defs").is_none());
+    }
+
+    #[tokio::test]
+    async fn deserializes_params_and_rejects_bad_ones() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(Typed(Calculator))).await;
+
+        let result = registry
+            .execute_tool(
+                "calculator",
+                &serde_json::json!({"a": 2, "b": 3, "operation": "mul"}),
+            )
+            .await;
+        assert_eq!(result.content, "6");
+
+        let result = registry
+            .execute_tool(
+                "calculator",
+                &serde_json::json!({"a": 2, "b": 3, "operation": "pow"}),
+            )
+            .await;
+        assert!(!result.success);
+        assert_eq!(
+            result.error_kind(),
+            Some(crate::tool::ToolErrorKind::InvalidArgs)
+        );
+        assert!(result.error.unwrap().contains("unknown variant `pow`"));
+    }
+}
@@ -0,0 +1,394 @@
+//! Web search for research-style agents.
+//!
+//! `WebSearchTool` exposes any `SearchBackend` to the model. Backends for
+//! SerpAPI, Brave and Tavily are available with the `web-search` feature.
+
+use super::{Tool, ToolError, ToolMetadata, ToolResult};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// One search hit
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// 1-based position in the backend's ranking
+    pub rank: usize,
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// Search engine queried by `WebSearchTool`
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// Name shown in errors, e.g. "brave"
+    fn name(&self) -> &str;
+
+    /// Up to `max_results` results for `query`, best first
+    async fn search(&self, query: &str, max_results: usize)
+        -> Result<Vec<SearchResult>, ToolError>;
+}
+
+/// Lets the model search the web
+pub struct WebSearchTool {
+    backend: Arc<dyn SearchBackend>,
+    max_results: usize,
+}
+
+impl WebSearchTool {
+    pub fn new(backend: Arc<dyn SearchBackend>) -> Self {
+        Self {
+            backend,
+            max_results: 5,
+        }
+    }
+
+    /// Most results returned per call, whatever the model asks for; 5 by default
+    pub fn max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+}
+
+/// Results as a numbered list of title, URL and snippet
+fn format_results(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return "No results found".to_string();
+    }
+    results
+        .iter()
+        .map(|r| format!("{}. {}\n   {}\n   {}", r.rank, r.title, r.url, r.snippet))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn description(&self) -> &str {
+        "Search the web and return ranked results with title, URL and snippet"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "Search query"},
+                "max_results": {
+                    "type": "number",
+                    "description": format!("Number of results, at most {}", self.max_results)
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata::default().example(
+            "Look up recent information",
+            serde_json::json!({"query": "rust 2024 edition release date"}),
+        )
+    }
+
+    async fn execute(&self, params: &Value) -> ToolResult {
+        let query = params["query"].as_str().unwrap_or_default().trim();
+        if query.is_empty() {
+            return ToolError::invalid_args("query must not be empty").into();
+        }
+        let max_results = params["max_results"]
+            .as_u64()
+            .map_or(self.max_results, |n| {
+                (n as usize).clamp(1, self.max_results)
+            });
+
+        match self.backend.search(query, max_results).await {
+            Ok(mut results) => {
+                results.truncate(max_results);
+                ToolResult::success(format_results(&results))
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(feature = "web-search")]
+pub use backends::{BraveSearch, SerpApiSearch, TavilySearch};
+
+#[cfg(feature = "web-search")]
+mod backends {
+    use super::{SearchBackend, SearchResult};
+    use crate::tool::ToolError;
+    use async_trait::async_trait;
+    use serde_json::Value;
+
+    /// `ToolError` for a failed request or a non-success status
+    async fn send(backend: &str, request: reqwest::RequestBuilder) -> Result<Value, ToolError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::upstream(format!("{} request failed: {}", backend, e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            let error = ToolError::upstream(format!("{} returned {}: {}", backend, status, text))
+                .with_details(serde_json::json!({ "status": status.as_u16() }));
+            return Err(if status.is_client_error() && status.as_u16() != 429 {
+                error.with_retryable(false)
+            } else {
+                error
+            });
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| ToolError::upstream(format!("{} sent invalid JSON: {}", backend, e)))
+    }
+
+    /// Results from the array at `items`, reading `snippet_field` as the snippet
+    fn parse_results(
+        items: &Value,
+        url_field: &str,
+        snippet_field: &str,
+        max_results: usize,
+    ) -> Vec<SearchResult> {
+        items
+            .as_array()
+            .map(|items| items.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|item| {
+                Some((
+                    item["title"].as_str()?,
+                    item[url_field].as_str()?,
+                    item[snippet_field].as_str().unwrap_or_default(),
+                ))
+            })
+            .take(max_results)
+            .enumerate()
+            .map(|(i, (title, url, snippet))| SearchResult {
+                rank: i + 1,
+                title: title.to_string(),
+                url: url.to_string(),
+                snippet: snippet.to_string(),
+            })
+            .collect()
+    }
+
+    /// Google results through SerpAPI
+    pub struct SerpApiSearch {
+        api_key: String,
+        engine: String,
+        client: reqwest::Client,
+    }
+
+    impl SerpApiSearch {
+        pub fn new(api_key: impl Into<String>) -> Self {
+            Self {
+                api_key: api_key.into(),
+                engine: "google".to_string(),
+                client: reqwest::Client::new(),
+            }
+        }
+
+        /// SerpAPI engine, "google" by default
+        pub fn engine(mut self, engine: impl Into<String>) -> Self {
+            self.engine = engine.into();
+            self
+        }
+    }
+
+    #[async_trait]
+    impl SearchBackend for SerpApiSearch {
+        fn name(&self) -> &str {
+            "serpapi"
+        }
+
+        async fn search(
+            &self,
+            query: &str,
+            max_results: usize,
+        ) -> Result<Vec<SearchResult>, ToolError> {
+            let num = max_results.to_string();
+            let request = self.client.get("https://serpapi.com/search.json").query(&[
+                ("engine", self.engine.as_str()),
+                ("q", query),
+                ("num", num.as_str()),
+                ("api_key", self.api_key.as_str()),
+            ]);
+            let json = send(self.name(), request).await?;
+            Ok(parse_results(
+                &json["organic_results"],
+                "link",
+                "snippet",
+                max_results,
+            ))
+        }
+    }
+
+    /// Brave Search API
+    pub struct BraveSearch {
+        api_key: String,
+        client: reqwest::Client,
+    }
+
+    impl BraveSearch {
+        pub fn new(api_key: impl Into<String>) -> Self {
+            Self {
+                api_key: api_key.into(),
+                client: reqwest::Client::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SearchBackend for BraveSearch {
+        fn name(&self) -> &str {
+            "brave"
+        }
+
+        async fn search(
+            &self,
+            query: &str,
+            max_results: usize,
+        ) -> Result<Vec<SearchResult>, ToolError> {
+            let count = max_results.min(20).to_string();
+            let request = self
+                .client
+                .get("https://api.search.brave.com/res/v1/web/search")
+                .header("X-Subscription-Token", &self.api_key)
+                .header("Accept", "application/json")
+                .query(&[("q", query), ("count", count.as_str())]);
+            let json = send(self.name(), request).await?;
+            Ok(parse_results(
+                &json["web"]["results"],
+                "url",
+                "description",
+                max_results,
+            ))
+        }
+    }
+
+    /// Tavily search API, built for LLM agents
+    pub struct TavilySearch {
+        api_key: String,
+        search_depth: String,
+        client: reqwest::Client,
+    }
+
+    impl TavilySearch {
+        pub fn new(api_key: impl Into<String>) -> Self {
+            Self {
+                api_key: api_key.into(),
+                search_depth: "basic".to_string(),
+                client: reqwest::Client::new(),
+            }
+        }
+
+        /// Slower, more thorough searches
+        pub fn advanced(mut self) -> Self {
+            self.search_depth = "advanced".to_string();
+            self
+        }
+    }
+
+    #[async_trait]
+    impl SearchBackend for TavilySearch {
+        fn name(&self) -> &str {
+            "tavily"
+        }
+
+        async fn search(
+            &self,
+            query: &str,
+            max_results: usize,
+        ) -> Result<Vec<SearchResult>, ToolError> {
+            let request = self
+                .client
+                .post("https://api.tavily.com/search")
+                .bearer_auth(&self.api_key)
+                .json(&serde_json::json!({
+                    "query": query,
+                    "max_results": max_results,
+                    "search_depth": self.search_depth,
+                }));
+            let json = send(self.name(), request).await?;
+            Ok(parse_results(
+                &json["results"],
+                "url",
+                "content",
+                max_results,
+            ))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parses_backend_responses() {
+            let brave = serde_json::json!({"web": {"results": [
+                {"title": "Rust", "url": "https://rust-lang.org", "description": "A language"},
+                {"title": "No URL"},
+                {"title": "Docs", "url": "https://doc.rust-lang.org"}
+            ]}});
+            let results = parse_results(&brave["web"]["results"], "url", "description", 5);
+
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].snippet, "A language");
+            assert_eq!(results[1].rank, 2);
+            assert_eq!(results[1].url, "https://doc.rust-lang.org");
+            assert!(parse_results(&Value::Null, "link", "snippet", 5).is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::ToolErrorKind;
+
+    struct FixedBackend;
+
+    #[async_trait]
+    impl SearchBackend for FixedBackend {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn search(
+            &self,
+            query: &str,
+            max_results: usize,
+        ) -> Result<Vec<SearchResult>, ToolError> {
+            Ok((1..=10)
+                .take(max_results + 1)
+                .map(|rank| SearchResult {
+                    rank,
+                    title: format!("{} {}", query, rank),
+                    url: format!("https://example.com/{}", rank),
+                    snippet: "snippet".to_string(),
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn caps_and_formats_results() {
+        let tool = WebSearchTool::new(Arc::new(FixedBackend)).max_results(3);
+
+        let result = tool
+            .execute(&serde_json::json!({"query": "rust", "max_results": 50}))
+            .await;
+        assert_eq!(result.content.lines().count(), 9);
+        assert!(result
+            .content
+            .starts_with("1. rust 1\n   https://example.com/1\n   snippet\n2."));
+
+        let result = tool.execute(&serde_json::json!({"query": " "})).await;
+        assert_eq!(result.error_kind(), Some(ToolErrorKind::InvalidArgs));
+    }
+}